@@ -0,0 +1,37 @@
+//! Latency of single `Core` operations on plans of a few sizes
+//!
+//! Run with `cargo bench`; `cargo bench -- distilled` runs only the matching benches.
+//! For many operations at once from several threads, see `scatterbrain bench`.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use scatterbrain::bench::{BenchConfig, Operation, Workload};
+
+/// Tree shapes as (depth, fanout): 20, 155 and 1,364 tasks per plan
+const SHAPES: [(usize, usize); 3] = [(2, 4), (3, 5), (5, 4)];
+
+fn operations(c: &mut Criterion) {
+    for operation in Operation::ALL {
+        let mut group = c.benchmark_group(operation.name());
+        for (depth, fanout) in SHAPES {
+            let config = BenchConfig {
+                plans: 1,
+                depth,
+                fanout,
+                ..Default::default()
+            };
+            let tasks = config.tasks_per_plan();
+            group.bench_with_input(BenchmarkId::from_parameter(tasks), &config, |b, config| {
+                // A fresh plan per shape, so tasks added by one shape don't slow the next
+                let workload = Workload::new(config).expect("valid benchmark configuration");
+                let mut rng = StdRng::seed_from_u64(0);
+                b.iter(|| workload.run(operation, &mut rng))
+            });
+        }
+        group.finish();
+    }
+}
+
+criterion_group!(benches, operations);
+criterion_main!(benches);
@@ -2,41 +2,120 @@
 //!
 //! This module provides the HTTP client implementation for the scatterbrain API.
 
+use std::collections::BTreeSet;
+
 use reqwest::{
     header::{HeaderMap, HeaderValue, CONTENT_TYPE},
-    Client as ReqwestClient, Error as ReqwestError, Method,
+    Client as ReqwestClient, Error as ReqwestError, Method, StatusCode,
 };
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
 use super::Client;
-use crate::models::{self, Index};
+use crate::api::config::ReloadReport;
+use crate::api::storage::{PlanArchive, PlanExport};
+use crate::models::{self, Index, TaskRef};
+use crate::search::SearchHit;
+use crate::spec::{PlanSpec, TaskSpec};
 
 // Import the request structs from the server module
 use crate::api::server::{
-    AddTaskRequest, ChangeLevelRequest, CompleteTaskRequest, CreatePlanRequest, LeaseRequest,
-    MoveToRequest, SetTaskNotesRequest, UncompleteTaskRequest,
+    AddQuestionRequest, AddTaskFromTemplateRequest, AddTaskRequest, AnswerQuestionRequest,
+    ApproveTaskRequest, ChangeLevelRequest, CompactHistoryRequest, CompleteCurrentRequest,
+    CompleteTaskRequest, CreatePlanRequest, DependencyRequest, ImportSubtreeRequest, LeaseRequest,
+    LogTaskTimeRequest, MoveTaskRequest, MoveToRequest, QuotaOverview, RejectTaskRequest,
+    RequestReviewRequest, RevertNotesRequest, SetFocusRequest, SetNoteSectionRequest,
+    SetPlanStatusRequest, SetTaskEstimateRequest, SetTaskNotesRequest, SetTaskOrderRequest,
+    SetTaskPriorityRequest, SetTaskWeightRequest, SetTombstonesRequest, SetUsageSummaryRequest,
+    StartSessionRequest, StartTaskRequest, TagTaskRequest, UncompleteTaskRequest,
+    UpdatePlanRequest, UpdateTaskRequest, AGENT_HEADER, CLI_USER_AGENT_PREFIX, SESSION_HEADER,
 };
 
+/// How long to wait for a connection before failing over to the next server
+const FAILOVER_CONNECT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(3);
+
 /// API client configuration
 #[derive(Debug, Clone)]
 pub struct ClientConfig {
     pub base_url: String,
+    /// Bearer token sent with every request, for servers that require one
+    pub auth_token: Option<String>,
+    /// Session sent with every request; leases generated by this client only work for it
+    pub session: Option<String>,
+    /// Agent name sent with every request, recorded in the transition history of the
+    /// plans it changes
+    pub agent: Option<String>,
+    /// Workspace whose plans and templates this client works on, instead of the server's
+    /// default ones
+    pub workspace: Option<String>,
+    /// Servers to read from, in order, when `base_url` can't be reached, such as a standby
+    /// restored from the primary's backups. Only `GET` requests fail over; changes always
+    /// go to `base_url`, so they can't end up on a copy that is later thrown away.
+    pub fallback_urls: Vec<String>,
 }
 
 impl Default for ClientConfig {
     fn default() -> Self {
         Self {
             base_url: "http://localhost:3000".to_string(),
+            auth_token: None,
+            session: None,
+            agent: None,
+            workspace: None,
+            fallback_urls: Vec::new(),
         }
     }
 }
 
+impl ClientConfig {
+    /// The URL for an API `path`, pointing plan and template routes, and the admin routes
+    /// acting on plans, at the workspace
+    ///
+    /// ```
+    /// # use scatterbrain::api::ClientConfig;
+    /// let config = ClientConfig {
+    ///     workspace: Some("infra".to_string()),
+    ///     ..Default::default()
+    /// };
+    /// assert_eq!(
+    ///     config.url("/api/plans/3/current"),
+    ///     "http://localhost:3000/api/workspaces/infra/plans/3/current"
+    /// );
+    /// assert_eq!(
+    ///     config.url("/api/admin/export"),
+    ///     "http://localhost:3000/api/admin/workspaces/infra/export"
+    /// );
+    /// assert_eq!(config.url("/api/admin/reload"), "http://localhost:3000/api/admin/reload");
+    /// ```
+    pub fn url(&self, path: &str) -> String {
+        self.url_on(&self.base_url, path)
+    }
+
+    /// Like [`ClientConfig::url`], on the server at `base_url`
+    fn url_on(&self, base_url: &str, path: &str) -> String {
+        let scoped = self.workspace.as_ref().and_then(|workspace| {
+            let rest = path.strip_prefix("/api/")?;
+            // Reloads and quotas cover the whole server
+            if let Some(admin) = rest.strip_prefix("admin/") {
+                let shared = ["reload", "quotas"].contains(&admin);
+                return (!shared).then(|| format!("/api/admin/workspaces/{workspace}/{admin}"));
+            }
+            let shared = ["plugins/", "workspaces"]
+                .iter()
+                .any(|prefix| rest.starts_with(prefix));
+            (!shared).then(|| format!("/api/workspaces/{workspace}/{rest}"))
+        });
+        format!("{base_url}{}", scoped.as_deref().unwrap_or(path))
+    }
+}
+
 /// Generic API response structure
 #[derive(Debug, Deserialize)]
 struct ApiResponse<T> {
     success: bool,
     data: Option<T>,
     error: Option<String>,
+    #[serde(default)]
+    task_error: Option<models::TaskError>,
 }
 
 /// Client errors
@@ -48,9 +127,15 @@ pub enum ClientError {
     #[error("API error: {0}")]
     Api(String),
 
+    #[error("API error: {message}")]
+    Http { status: StatusCode, message: String },
+
     #[error("Plan not found: ID {0:?}")]
     PlanNotFound(models::PlanId),
 
+    #[error("Task error: {0}")]
+    Task(#[from] models::TaskError),
+
     #[error("Serialization error: {0}")]
     Serialization(#[from] serde_json::Error),
 
@@ -73,30 +158,83 @@ impl HttpClientImpl {
 
     /// Create a new client with custom configuration
     pub fn with_config(config: ClientConfig) -> Self {
+        let mut builder = ReqwestClient::builder().user_agent(format!(
+            "{CLI_USER_AGENT_PREFIX}{}",
+            env!("CARGO_PKG_VERSION")
+        ));
+        // With somewhere to fail over to, don't wait long on a primary that is down
+        if !config.fallback_urls.is_empty() {
+            builder = builder.connect_timeout(FAILOVER_CONNECT_TIMEOUT);
+        }
+        let http_client = builder.build().expect("failed to build HTTP client");
         Self {
-            http_client: ReqwestClient::new(),
+            http_client,
             config,
         }
     }
 
-    /// Helper function to send requests
+    /// Helper function to send requests; reads go to the fallback servers in turn while
+    /// the ones before can't be reached
     async fn request<T: DeserializeOwned, B: Serialize + ?Sized>(
         &self,
         method: Method,
         path: &str,
         body: Option<&B>,
     ) -> Result<T, ClientError> {
-        let url = format!("{}{}", self.config.base_url, path);
+        let fallbacks = match method {
+            Method::GET => self.config.fallback_urls.as_slice(),
+            _ => &[],
+        };
+        let mut response = self
+            .send(&self.config.base_url, method.clone(), path, body)
+            .await;
+        for fallback in fallbacks {
+            match &response {
+                Err(e) if e.is_connect() || e.is_timeout() => {
+                    tracing::warn!("Can't reach the server ({e}), reading from {fallback}");
+                    response = self.send(fallback, method.clone(), path, body).await;
+                }
+                _ => break,
+            }
+        }
+        Self::parse_response(response?).await
+    }
+
+    /// Sends a request to the server at `base_url`
+    async fn send<B: Serialize + ?Sized>(
+        &self,
+        base_url: &str,
+        method: Method,
+        path: &str,
+        body: Option<&B>,
+    ) -> Result<reqwest::Response, ReqwestError> {
+        let url = self.config.url_on(base_url, path);
         let mut headers = HeaderMap::new();
         headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
 
         let mut request_builder = self.http_client.request(method, &url).headers(headers);
 
+        if let Some(token) = &self.config.auth_token {
+            request_builder = request_builder.bearer_auth(token);
+        }
+        if let Some(session) = &self.config.session {
+            request_builder = request_builder.header(SESSION_HEADER, session);
+        }
+        if let Some(agent) = &self.config.agent {
+            request_builder = request_builder.header(AGENT_HEADER, agent);
+        }
+
         if let Some(body_data) = body {
             request_builder = request_builder.json(body_data);
         }
 
-        let response = request_builder.send().await?;
+        request_builder.send().await
+    }
+
+    /// Turns a response into the data it carries, or the error it reports
+    async fn parse_response<T: DeserializeOwned>(
+        response: reqwest::Response,
+    ) -> Result<T, ClientError> {
         let status = response.status();
 
         // Check if the status code indicates success
@@ -117,23 +255,98 @@ impl HttpClientImpl {
             }
         } else {
             // Attempt to deserialize the error response body
-            let error_response: Result<ApiResponse<()>, _> = response.json().await;
-            let error_message = error_response
-                .ok()
-                .and_then(|resp| resp.error)
-                .unwrap_or_else(|| format!("HTTP error: {status}"));
-
-            // Check for specific PlanNotFound error pattern if possible
-            if error_message.contains("Plan ID '") && error_message.contains("' not found") {
-                // Extract token (best effort) - Needs update for u8 ID
-                let id_str = error_message.split('\'').nth(1).unwrap_or(""); // Fallback
-                let id_val = id_str.parse::<u8>().unwrap_or(255); // Try parse, fallback
-                Err(ClientError::PlanNotFound(models::Lease::new(id_val)))
-            } else {
-                Err(ClientError::Api(error_message))
+            let error_response: Option<ApiResponse<()>> = response.json().await.ok();
+            let (error_message, task_error) = error_response
+                .map(|resp| (resp.error, resp.task_error))
+                .unwrap_or_default();
+            if let Some(task_error) = task_error {
+                return Err(ClientError::Task(task_error));
+            }
+            let error_message = error_message.unwrap_or_else(|| format!("HTTP error: {status}"));
+
+            // The server reports a missing plan as "Plan '<id>' not found"
+            let plan_id = error_message
+                .strip_prefix("Plan '")
+                .and_then(|rest| rest.strip_suffix("' not found"))
+                .and_then(|id| id.parse::<u8>().ok());
+            match plan_id {
+                Some(id) if status == StatusCode::NOT_FOUND => {
+                    Err(ClientError::PlanNotFound(models::Lease::new(id)))
+                }
+                _ => Err(ClientError::Http {
+                    status,
+                    message: error_message,
+                }),
             }
         }
     }
+
+    // --- Plan transfer (HTTP only) --- //
+
+    /// Downloads a plan with its cursor, history and leases
+    pub async fn export_plan(&self, id: u8) -> Result<PlanExport, ClientError> {
+        let path = format!("/api/plans/{id}/export");
+        self.request(Method::GET, &path, None::<&()>).await
+    }
+
+    /// Uploads an exported plan, which the server creates under a new ID
+    pub async fn import_plan(&self, export: &PlanExport) -> Result<models::PlanId, ClientError> {
+        self.request(Method::POST, "/api/plans/import", Some(export))
+            .await
+    }
+
+    // --- Admin API (HTTP only; needs the server's admin token) --- //
+
+    /// Releases every lease on a plan
+    pub async fn force_unlock(&self, id: u8) -> Result<models::PlanResponse<usize>, ClientError> {
+        let path = format!("/api/admin/plans/{id}/unlock");
+        self.request(Method::POST, &path, None::<&()>).await
+    }
+
+    /// Releases a plan's leases on missing or completed tasks
+    pub async fn clear_orphaned_leases(
+        &self,
+        id: u8,
+    ) -> Result<models::PlanResponse<Vec<models::Index>>, ClientError> {
+        let path = format!("/api/admin/plans/{id}/orphaned-leases");
+        self.request(Method::DELETE, &path, None::<&()>).await
+    }
+
+    /// Drops a plan's transition log and trims every task's notes history
+    pub async fn compact_history(
+        &self,
+        id: u8,
+        keep_note_versions: usize,
+    ) -> Result<models::PlanResponse<models::HistoryCompaction>, ClientError> {
+        let path = format!("/api/admin/plans/{id}/compact");
+        let body = CompactHistoryRequest { keep_note_versions };
+        self.request(Method::POST, &path, Some(&body)).await
+    }
+
+    /// Downloads every plan of the client's workspace as a backup archive
+    pub async fn export_plans(&self) -> Result<PlanArchive, ClientError> {
+        self.request(Method::GET, "/api/admin/export", None::<&()>)
+            .await
+    }
+
+    /// Uploads a backup archive into the client's workspace, replacing plans with the same
+    /// IDs; returns how many were imported
+    pub async fn import_plans(&self, archive: &PlanArchive) -> Result<usize, ClientError> {
+        self.request(Method::POST, "/api/admin/import", Some(archive))
+            .await
+    }
+
+    /// Reports the server's quotas and how much of them each workspace's plans use
+    pub async fn quotas(&self) -> Result<QuotaOverview, ClientError> {
+        self.request(Method::GET, "/api/admin/quotas", None::<&()>)
+            .await
+    }
+
+    /// Asks the server to re-read its config file
+    pub async fn reload_config(&self) -> Result<ReloadReport, ClientError> {
+        self.request(Method::POST, "/api/admin/reload", None::<&()>)
+            .await
+    }
 }
 
 #[async_trait::async_trait]
@@ -153,6 +366,50 @@ impl Client for HttpClientImpl {
         self.request(Method::GET, &path, None::<&()>).await
     }
 
+    /// Get the plan as it was at the given time
+    async fn get_plan_at(
+        &self,
+        id: u8,
+        at: chrono::DateTime<chrono::Utc>,
+    ) -> Result<models::PlanResponse<models::Plan>, ClientError> {
+        let timestamp = at.to_rfc3339_opts(chrono::SecondsFormat::Millis, true);
+        let path = format!("/api/plans/{id}/at?timestamp={timestamp}");
+        self.request(Method::GET, &path, None::<&()>).await
+    }
+
+    /// Get a one-line summary of the plan's progress, without the distilled context
+    async fn get_progress(&self, id: u8) -> Result<models::ProgressSummary, ClientError> {
+        let path = format!("/api/plans/{id}/progress");
+        self.request(Method::GET, &path, None::<&()>).await
+    }
+
+    /// Get counts of the plan's tasks by state and level, without the distilled context
+    async fn get_plan_stats(&self, id: u8) -> Result<models::PlanStats, ClientError> {
+        let path = format!("/api/plans/{id}/stats");
+        self.request(Method::GET, &path, None::<&()>).await
+    }
+
+    /// Compare the plan's estimates with the time logged, without the distilled context
+    async fn get_effort_report(&self, id: u8) -> Result<models::EffortReport, ClientError> {
+        let path = format!("/api/plans/{id}/effort");
+        self.request(Method::GET, &path, None::<&()>).await
+    }
+
+    /// Check the plan for inconsistencies, such as orphaned leases
+    async fn lint_plan(
+        &self,
+        id: u8,
+    ) -> Result<models::PlanResponse<models::PlanLint>, ClientError> {
+        let path = format!("/api/plans/{id}/lint");
+        self.request(Method::GET, &path, None::<&()>).await
+    }
+
+    /// Get the plan's full transition log, oldest first
+    async fn get_history(&self, id: u8) -> Result<Vec<models::HistoryRecord>, ClientError> {
+        let path = format!("/api/plans/{id}/history");
+        self.request(Method::GET, &path, None::<&()>).await
+    }
+
     /// Get the distilled context
     async fn get_distilled_context(&self, id: u8) -> Result<models::PlanResponse<()>, ClientError> {
         let path = format!("/api/plans/{id}/distilled");
@@ -176,6 +433,18 @@ impl Client for HttpClientImpl {
         self.request(Method::POST, &path, Some(&body)).await
     }
 
+    /// Add a new task by instantiating a template with parameters
+    async fn add_task_from_template(
+        &self,
+        id: u8,
+        template: String,
+        params: models::TemplateParams,
+    ) -> Result<models::PlanResponse<(models::Task, Index)>, ClientError> {
+        let path = format!("/api/plans/{id}/task/template");
+        let body = AddTaskFromTemplateRequest { template, params };
+        self.request(Method::POST, &path, Some(&body)).await
+    }
+
     /// Complete the current task
     async fn complete_task(
         &self,
@@ -183,13 +452,34 @@ impl Client for HttpClientImpl {
         index: Index,
         lease: Option<u8>,
         force: bool,
+        cascade: Option<bool>,
         summary: Option<String>,
-    ) -> Result<models::PlanResponse<bool>, ClientError> {
+    ) -> Result<models::PlanResponse<()>, ClientError> {
         let path = format!("/api/plans/{id}/task/complete");
         let body = CompleteTaskRequest {
-            index,
+            index: index.into(),
             lease,
             force,
+            cascade,
+            summary,
+        };
+        self.request(Method::POST, &path, Some(&body)).await
+    }
+
+    /// Complete the task under the plan's cursor, returning its index
+    async fn complete_current_task(
+        &self,
+        id: u8,
+        lease: Option<u8>,
+        force: bool,
+        cascade: Option<bool>,
+        summary: Option<String>,
+    ) -> Result<models::PlanResponse<Index>, ClientError> {
+        let path = format!("/api/plans/{id}/current/complete");
+        let body = CompleteCurrentRequest {
+            lease,
+            force,
+            cascade,
             summary,
         };
         self.request(Method::POST, &path, Some(&body)).await
@@ -200,9 +490,11 @@ impl Client for HttpClientImpl {
         &self,
         id: u8,
         index: Index,
-    ) -> Result<models::PlanResponse<Option<String>>, ClientError> {
+    ) -> Result<models::PlanResponse<String>, ClientError> {
         let path = format!("/api/plans/{id}/move");
-        let body = MoveToRequest { index };
+        let body = MoveToRequest {
+            index: index.into(),
+        };
         self.request(Method::POST, &path, Some(&body)).await
     }
 
@@ -212,9 +504,72 @@ impl Client for HttpClientImpl {
         id: u8,
         index: Index,
         level_index: usize,
-    ) -> Result<models::PlanResponse<Result<(), String>>, ClientError> {
+    ) -> Result<models::PlanResponse<()>, ClientError> {
         let path = format!("/api/plans/{id}/task/level");
-        let body = ChangeLevelRequest { index, level_index };
+        let body = ChangeLevelRequest {
+            index: index.into(),
+            level_index,
+        };
+        self.request(Method::POST, &path, Some(&body)).await
+    }
+
+    /// Set how much a task counts toward plan progress
+    async fn set_task_weight(
+        &self,
+        id: u8,
+        index: Index,
+        weight: Option<u32>,
+    ) -> Result<models::PlanResponse<()>, ClientError> {
+        let path = format!("/api/plans/{id}/task/weight");
+        let body = SetTaskWeightRequest {
+            index: index.into(),
+            weight,
+        };
+        self.request(Method::POST, &path, Some(&body)).await
+    }
+
+    /// Set how urgent a task is
+    async fn set_task_priority(
+        &self,
+        id: u8,
+        index: Index,
+        priority: Option<u8>,
+    ) -> Result<models::PlanResponse<()>, ClientError> {
+        let path = format!("/api/plans/{id}/task/priority");
+        let body = SetTaskPriorityRequest {
+            index: index.into(),
+            priority,
+        };
+        self.request(Method::POST, &path, Some(&body)).await
+    }
+
+    /// Set how many minutes a task is estimated to take
+    async fn set_task_estimate(
+        &self,
+        id: u8,
+        index: Index,
+        minutes: Option<u32>,
+    ) -> Result<models::PlanResponse<()>, ClientError> {
+        let path = format!("/api/plans/{id}/task/estimate");
+        let body = SetTaskEstimateRequest {
+            index: index.into(),
+            minutes,
+        };
+        self.request(Method::POST, &path, Some(&body)).await
+    }
+
+    /// Log minutes of work on a task
+    async fn log_task_time(
+        &self,
+        id: u8,
+        index: Index,
+        minutes: u32,
+    ) -> Result<models::PlanResponse<u32>, ClientError> {
+        let path = format!("/api/plans/{id}/task/time");
+        let body = LogTaskTimeRequest {
+            index: index.into(),
+            minutes,
+        };
         self.request(Method::POST, &path, Some(&body)).await
     }
 
@@ -223,18 +578,48 @@ impl Client for HttpClientImpl {
         &self,
         id: u8,
         index: Index,
+        single_use: bool,
     ) -> Result<models::PlanResponse<(models::Lease, Vec<String>)>, ClientError> {
         let path = format!("/api/plans/{id}/task/lease");
-        let body = LeaseRequest { index };
+        let body = LeaseRequest {
+            index: index.into(),
+            single_use,
+        };
         self.request(Method::POST, &path, Some(&body)).await
     }
 
+    async fn get_task(
+        &self,
+        id: u8,
+        task: TaskRef,
+    ) -> Result<models::PlanResponse<(models::Task, Index)>, ClientError> {
+        let path = format!("/api/plans/{id}/tasks/{task}");
+        self.request(Method::GET, &path, None::<&()>).await
+    }
+
+    /// Replaces the description of a task
+    async fn update_task_description(
+        &self,
+        id: u8,
+        index: Index,
+        description: String,
+    ) -> Result<models::PlanResponse<models::Task>, ClientError> {
+        let index_str = index
+            .iter()
+            .map(|i| i.to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+        let path = format!("/api/plans/{id}/tasks/{index_str}");
+        let body = UpdateTaskRequest { description };
+        self.request(Method::PATCH, &path, Some(&body)).await
+    }
+
     /// Removes a task by its index
     async fn remove_task(
         &self,
         id: u8,
         index: Index,
-    ) -> Result<models::PlanResponse<Result<models::Task, String>>, ClientError> {
+    ) -> Result<models::PlanResponse<models::Task>, ClientError> {
         let index_str = index
             .iter()
             .map(|i| i.to_string())
@@ -244,7 +629,107 @@ impl Client for HttpClientImpl {
         self.request(Method::DELETE, &path, None::<&()>).await
     }
 
+    async fn move_task(
+        &self,
+        id: u8,
+        index: Index,
+        parent: Index,
+    ) -> Result<models::PlanResponse<Index>, ClientError> {
+        let path = format!("/api/plans/{id}/task/move");
+        let body = MoveTaskRequest {
+            index: index.into(),
+            parent: parent.into(),
+        };
+        self.request(Method::POST, &path, Some(&body)).await
+    }
+
+    async fn tag_task(
+        &self,
+        id: u8,
+        index: Index,
+        tags: Vec<String>,
+    ) -> Result<models::PlanResponse<BTreeSet<String>>, ClientError> {
+        let path = format!("/api/plans/{id}/task/tags");
+        let body = TagTaskRequest {
+            index: index.into(),
+            tags,
+        };
+        self.request(Method::POST, &path, Some(&body)).await
+    }
+
+    async fn untag_task(
+        &self,
+        id: u8,
+        index: Index,
+        tags: Vec<String>,
+    ) -> Result<models::PlanResponse<BTreeSet<String>>, ClientError> {
+        let path = format!("/api/plans/{id}/task/tags/remove");
+        let body = TagTaskRequest {
+            index: index.into(),
+            tags,
+        };
+        self.request(Method::POST, &path, Some(&body)).await
+    }
+
+    async fn add_dependency(
+        &self,
+        id: u8,
+        index: Index,
+        on: Index,
+    ) -> Result<models::PlanResponse<bool>, ClientError> {
+        let path = format!("/api/plans/{id}/task/dependency");
+        let body = DependencyRequest {
+            index: index.into(),
+            on: on.into(),
+        };
+        self.request(Method::POST, &path, Some(&body)).await
+    }
+
+    async fn remove_dependency(
+        &self,
+        id: u8,
+        index: Index,
+        on: Index,
+    ) -> Result<models::PlanResponse<bool>, ClientError> {
+        let path = format!("/api/plans/{id}/task/dependency/remove");
+        let body = DependencyRequest {
+            index: index.into(),
+            on: on.into(),
+        };
+        self.request(Method::POST, &path, Some(&body)).await
+    }
+
     /// Gets the notes for a specific task
+    /// Exports the task at `index` and everything below it
+    async fn export_subtree(
+        &self,
+        id: u8,
+        index: Index,
+    ) -> Result<models::PlanResponse<TaskSpec>, ClientError> {
+        let index_str = index
+            .iter()
+            .map(|i| i.to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+        let path = format!("/api/plans/{id}/subtree/{index_str}");
+        self.request(Method::GET, &path, None::<&()>).await
+    }
+
+    /// Adds an exported subtree as the last child of `parent`, returning its index
+    async fn import_subtree(
+        &self,
+        id: u8,
+        parent: Index,
+        subtree: TaskSpec,
+    ) -> Result<models::PlanResponse<Index>, ClientError> {
+        let path = format!("/api/plans/{id}/subtree");
+        let body = ImportSubtreeRequest {
+            parent: parent.into(),
+            subtree,
+        };
+        self.request(Method::POST, &path, Some(&body)).await
+    }
+
     async fn get_task_notes(&self, id: u8, index: Index) -> Result<Option<String>, ClientError> {
         let index_str = index
             .iter()
@@ -252,23 +737,26 @@ impl Client for HttpClientImpl {
             .collect::<Vec<_>>()
             .join(",");
         let path = format!("/api/plans/{id}/notes/{index_str}");
-        self.request(Method::GET, &path, None::<&()>).await
+        let response: models::PlanResponse<Option<String>> =
+            self.request(Method::GET, &path, None::<&()>).await?;
+        Ok(response.into_inner())
     }
 
-    /// Sets the notes for a specific task
+    /// Sets the notes for a specific task, or appends a timestamped block when `append` is true
     async fn set_task_notes(
         &self,
         id: u8,
         index: Index,
         notes: String,
-    ) -> Result<models::PlanResponse<Result<(), String>>, ClientError> {
+        append: bool,
+    ) -> Result<models::PlanResponse<()>, ClientError> {
         let index_str = index
             .iter()
             .map(|i| i.to_string())
             .collect::<Vec<_>>()
             .join(",");
         let path = format!("/api/plans/{id}/notes/{index_str}");
-        let body = SetTaskNotesRequest { notes };
+        let body = SetTaskNotesRequest { notes, append };
         self.request(Method::POST, &path, Some(&body)).await
     }
 
@@ -277,7 +765,7 @@ impl Client for HttpClientImpl {
         &self,
         id: u8,
         index: Index,
-    ) -> Result<models::PlanResponse<Result<(), String>>, ClientError> {
+    ) -> Result<models::PlanResponse<()>, ClientError> {
         let index_str = index
             .iter()
             .map(|i| i.to_string())
@@ -287,17 +775,408 @@ impl Client for HttpClientImpl {
         self.request(Method::DELETE, &path, None::<&()>).await
     }
 
+    /// Gets the structured notes sections for a specific task, optionally only a single section
+    async fn get_note_sections(
+        &self,
+        id: u8,
+        index: Index,
+        section: Option<String>,
+    ) -> Result<models::NoteSections, ClientError> {
+        let index_str = index
+            .iter()
+            .map(|i| i.to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+        let mut path = format!("/api/plans/{id}/note-sections/{index_str}");
+        if let Some(section) = section {
+            path.push_str(&format!("?section={}", urlencoding_encode(&section)));
+        }
+        self.request(Method::GET, &path, None::<&()>).await
+    }
+
+    /// Sets, appends to, or removes (`None` content) a named notes section for a specific task
+    async fn set_note_section(
+        &self,
+        id: u8,
+        index: Index,
+        section: String,
+        content: Option<String>,
+        append: bool,
+    ) -> Result<models::PlanResponse<()>, ClientError> {
+        let index_str = index
+            .iter()
+            .map(|i| i.to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+        let path = format!("/api/plans/{id}/note-sections/{index_str}");
+        let body = SetNoteSectionRequest {
+            section,
+            content,
+            append,
+        };
+        self.request(Method::POST, &path, Some(&body)).await
+    }
+
+    /// Gets the previous versions of the notes for a specific task
+    async fn get_notes_history(
+        &self,
+        id: u8,
+        index: Index,
+    ) -> Result<Vec<models::NoteVersion>, ClientError> {
+        let index_str = index
+            .iter()
+            .map(|i| i.to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+        let path = format!("/api/plans/{id}/notes-history/{index_str}");
+        self.request(Method::GET, &path, None::<&()>).await
+    }
+
+    /// Restores the notes for a specific task to a previous version
+    async fn revert_task_notes(
+        &self,
+        id: u8,
+        index: Index,
+        version: usize,
+    ) -> Result<models::PlanResponse<()>, ClientError> {
+        let index_str = index
+            .iter()
+            .map(|i| i.to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+        let path = format!("/api/plans/{id}/notes-history/{index_str}");
+        let body = RevertNotesRequest { version };
+        self.request(Method::POST, &path, Some(&body)).await
+    }
+
+    /// Marks a task as in progress by its index
+    async fn start_task(
+        &self,
+        id: u8,
+        index: Index,
+    ) -> Result<models::PlanResponse<()>, ClientError> {
+        let path = format!("/api/plans/{id}/task/start");
+        let body = StartTaskRequest {
+            index: index.into(),
+        };
+        self.request(Method::POST, &path, Some(&body)).await
+    }
+
     /// Uncompletes a task by its index
     async fn uncomplete_task(
         &self,
         id: u8,
         index: Index,
-    ) -> Result<models::PlanResponse<Result<bool, String>>, ClientError> {
+    ) -> Result<models::PlanResponse<()>, ClientError> {
         let path = format!("/api/plans/{id}/task/uncomplete");
-        let body = UncompleteTaskRequest { index };
+        let body = UncompleteTaskRequest {
+            index: index.into(),
+        };
+        self.request(Method::POST, &path, Some(&body)).await
+    }
+
+    /// Submits a task for review with a completion summary
+    async fn request_review(
+        &self,
+        id: u8,
+        index: Index,
+        summary: String,
+    ) -> Result<models::PlanResponse<()>, ClientError> {
+        let path = format!("/api/plans/{id}/task/review");
+        let body = RequestReviewRequest {
+            index: index.into(),
+            summary,
+        };
+        self.request(Method::POST, &path, Some(&body)).await
+    }
+
+    /// Approves a task awaiting review, marking it complete
+    async fn approve_task(
+        &self,
+        id: u8,
+        index: Index,
+    ) -> Result<models::PlanResponse<()>, ClientError> {
+        let path = format!("/api/plans/{id}/task/approve");
+        let body = ApproveTaskRequest {
+            index: index.into(),
+        };
+        self.request(Method::POST, &path, Some(&body)).await
+    }
+
+    /// Lists the tasks awaiting review
+    async fn get_review_queue(
+        &self,
+        id: u8,
+    ) -> Result<models::PlanResponse<Vec<models::ReviewItem>>, ClientError> {
+        let path = format!("/api/plans/{id}/review-queue");
+        self.request(Method::GET, &path, None::<&()>).await
+    }
+
+    /// Lists the tasks completed between `from` (the start by default) and `to` (now)
+    async fn get_changelog(
+        &self,
+        id: u8,
+        from: Option<chrono::DateTime<chrono::Utc>>,
+        to: Option<chrono::DateTime<chrono::Utc>>,
+    ) -> Result<models::PlanResponse<models::Changelog>, ClientError> {
+        let query: Vec<String> = [("from", from), ("to", to)]
+            .into_iter()
+            .filter_map(|(name, at)| {
+                let at = at?.to_rfc3339_opts(chrono::SecondsFormat::Millis, true);
+                Some(format!("{name}={at}"))
+            })
+            .collect();
+        let path = format!("/api/plans/{id}/changelog?{}", query.join("&"));
+        self.request(Method::GET, &path, None::<&()>).await
+    }
+
+    /// Rolls up completion summaries under each task at `level`
+    async fn get_summaries(
+        &self,
+        id: u8,
+        level: usize,
+    ) -> Result<models::PlanResponse<Vec<models::SummaryGroup>>, ClientError> {
+        let path = format!("/api/plans/{id}/summaries?level={level}");
+        self.request(Method::GET, &path, None::<&()>).await
+    }
+
+    /// Lists the tasks that match the filter, without their subtasks
+    async fn list_tasks(
+        &self,
+        id: u8,
+        filter: models::TaskFilter,
+    ) -> Result<models::PlanResponse<Vec<models::TaskListing>>, ClientError> {
+        let mut query = Vec::new();
+        if filter.incomplete_only {
+            query.push("incomplete_only=true".to_string());
+        }
+        if let Some(level) = filter.level {
+            query.push(format!("level={level}"));
+        }
+        if let Some(depth) = filter.depth {
+            query.push(format!("depth={depth}"));
+        }
+        if !filter.subtree.is_empty() {
+            let subtree = filter
+                .subtree
+                .iter()
+                .map(|i| i.to_string())
+                .collect::<Vec<_>>()
+                .join(",");
+            query.push(format!("subtree={subtree}"));
+        }
+        if let Some(tag) = &filter.tag {
+            query.push(format!("tag={}", urlencoding_encode(tag)));
+        }
+        let mut path = format!("/api/plans/{id}/tasks");
+        if !query.is_empty() {
+            path.push_str(&format!("?{}", query.join("&")));
+        }
+        self.request(Method::GET, &path, None::<&()>).await
+    }
+
+    /// Finds the live tasks containing every word of `query`, with a snippet of each match
+    async fn search_tasks(
+        &self,
+        id: u8,
+        query: String,
+    ) -> Result<models::PlanResponse<Vec<SearchHit>>, ClientError> {
+        let path = format!("/api/plans/{id}/search?q={}", urlencoding_encode(&query));
+        self.request(Method::GET, &path, None::<&()>).await
+    }
+
+    /// Rejects a task awaiting review with an optional reason
+    async fn reject_task(
+        &self,
+        id: u8,
+        index: Index,
+        reason: Option<String>,
+    ) -> Result<models::PlanResponse<()>, ClientError> {
+        let path = format!("/api/plans/{id}/task/reject");
+        let body = RejectTaskRequest {
+            index: index.into(),
+            reason,
+        };
         self.request(Method::POST, &path, Some(&body)).await
     }
 
+    /// Sets a plan's lifecycle status; Completed requires all tasks complete unless forced
+    async fn set_plan_status(
+        &self,
+        id: u8,
+        status: models::PlanStatus,
+        force: bool,
+    ) -> Result<models::PlanResponse<()>, ClientError> {
+        let path = format!("/api/plans/{id}/status");
+        let body = SetPlanStatusRequest { status, force };
+        self.request(Method::POST, &path, Some(&body)).await
+    }
+
+    /// Locks a plan read-only, or unlocks it, returning whether the lock state changed
+    async fn set_plan_locked(
+        &self,
+        id: u8,
+        locked: bool,
+    ) -> Result<models::PlanResponse<bool>, ClientError> {
+        let action = if locked { "lock" } else { "unlock" };
+        let path = format!("/api/plans/{id}/{action}");
+        self.request(Method::POST, &path, None::<&()>).await
+    }
+
+    /// Sets the order a plan's subtasks are listed in, returning whether it changed
+    async fn set_task_order(
+        &self,
+        id: u8,
+        order: models::TaskOrder,
+    ) -> Result<models::PlanResponse<bool>, ClientError> {
+        let path = format!("/api/plans/{id}/task-order");
+        let body = SetTaskOrderRequest { order };
+        self.request(Method::POST, &path, Some(&body)).await
+    }
+
+    /// Turns tombstones for removed tasks on or off, returning whether the setting changed
+    async fn set_tombstones(
+        &self,
+        id: u8,
+        enabled: bool,
+    ) -> Result<models::PlanResponse<bool>, ClientError> {
+        let path = format!("/api/plans/{id}/tombstones");
+        let body = SetTombstonesRequest { enabled };
+        self.request(Method::POST, &path, Some(&body)).await
+    }
+
+    /// Shares a plan under a join code, returning the code
+    async fn share_plan(&self, id: u8) -> Result<models::PlanResponse<String>, ClientError> {
+        let path = format!("/api/plans/{id}/share");
+        self.request(Method::POST, &path, None::<&()>).await
+    }
+
+    /// Stops sharing a plan, returning whether it was shared
+    async fn unshare_plan(&self, id: u8) -> Result<models::PlanResponse<bool>, ClientError> {
+        let path = format!("/api/plans/{id}/share");
+        self.request(Method::DELETE, &path, None::<&()>).await
+    }
+
+    /// Replaces a plan's goal and notes
+    async fn update_plan_metadata(
+        &self,
+        id: u8,
+        goal: Option<String>,
+        notes: Option<String>,
+    ) -> Result<models::PlanResponse<()>, ClientError> {
+        let path = format!("/api/plans/{id}");
+        let body = UpdatePlanRequest { goal, notes };
+        self.request(Method::PATCH, &path, Some(&body)).await
+    }
+
+    /// Sets a plan's own usage summary, or goes back to the server's when `None`
+    async fn set_usage_summary(
+        &self,
+        id: u8,
+        usage_summary: Option<String>,
+    ) -> Result<models::PlanResponse<()>, ClientError> {
+        let path = format!("/api/plans/{id}/usage-summary");
+        let body = SetUsageSummaryRequest { usage_summary };
+        self.request(Method::POST, &path, Some(&body)).await
+    }
+
+    /// Pins a plan's work to the task at `index`, or clears the focus when `None`
+    async fn set_focus(
+        &self,
+        id: u8,
+        index: Option<Index>,
+        strict: bool,
+    ) -> Result<models::PlanResponse<Option<models::Focus>>, ClientError> {
+        let path = format!("/api/plans/{id}/focus");
+        let body = SetFocusRequest {
+            index: index.map(Into::into),
+            strict,
+        };
+        self.request(Method::POST, &path, Some(&body)).await
+    }
+
+    /// Starts timing a working session on a plan, optionally changing its reminder interval
+    async fn start_session(
+        &self,
+        id: u8,
+        reminder_minutes: Option<u32>,
+    ) -> Result<models::PlanResponse<models::SessionStatus>, ClientError> {
+        let path = format!("/api/plans/{id}/session");
+        let body = StartSessionRequest { reminder_minutes };
+        self.request(Method::POST, &path, Some(&body)).await
+    }
+
+    /// Stops a plan's working session, returning how long it ran
+    async fn stop_session(
+        &self,
+        id: u8,
+    ) -> Result<models::PlanResponse<Option<models::SessionStatus>>, ClientError> {
+        let path = format!("/api/plans/{id}/session");
+        self.request(Method::DELETE, &path, None::<&()>).await
+    }
+
+    /// Replaces the color and emoji a plan is shown with
+    async fn set_plan_badge(
+        &self,
+        id: u8,
+        badge: models::PlanBadge,
+    ) -> Result<models::PlanResponse<models::PlanBadge>, ClientError> {
+        let path = format!("/api/plans/{id}/badge");
+        self.request(Method::POST, &path, Some(&badge)).await
+    }
+
+    /// Replaces a plan's completion policy
+    async fn set_completion_policy(
+        &self,
+        id: u8,
+        policy: models::CompletionPolicy,
+    ) -> Result<models::PlanResponse<models::CompletionPolicy>, ClientError> {
+        let path = format!("/api/plans/{id}/policy");
+        self.request(Method::POST, &path, Some(&policy)).await
+    }
+
+    /// Records a new open question for a plan
+    async fn add_question(
+        &self,
+        id: u8,
+        question: String,
+    ) -> Result<models::PlanResponse<models::Question>, ClientError> {
+        let path = format!("/api/plans/{id}/questions");
+        let body = AddQuestionRequest { question };
+        self.request(Method::POST, &path, Some(&body)).await
+    }
+
+    /// Lists the questions tracked for a plan
+    async fn list_questions(
+        &self,
+        id: u8,
+    ) -> Result<models::PlanResponse<Vec<models::Question>>, ClientError> {
+        let path = format!("/api/plans/{id}/questions");
+        self.request(Method::GET, &path, None::<&()>).await
+    }
+
+    /// Records an answer for a question, marking it answered
+    async fn answer_question(
+        &self,
+        id: u8,
+        question_id: usize,
+        answer: String,
+    ) -> Result<models::PlanResponse<models::Question>, ClientError> {
+        let path = format!("/api/plans/{id}/questions/{question_id}/answer");
+        let body = AnswerQuestionRequest { answer };
+        self.request(Method::POST, &path, Some(&body)).await
+    }
+
+    /// Removes a question from a plan
+    async fn remove_question(
+        &self,
+        id: u8,
+        question_id: usize,
+    ) -> Result<models::PlanResponse<models::Question>, ClientError> {
+        let path = format!("/api/plans/{id}/questions/{question_id}");
+        self.request(Method::DELETE, &path, None::<&()>).await
+    }
+
     /// Create a new plan with a required prompt and optional notes
     async fn create_plan(
         &self,
@@ -308,6 +1187,12 @@ impl Client for HttpClientImpl {
         self.request(Method::POST, "/api/plans", Some(&body)).await
     }
 
+    /// Create a plan, with its levels and task tree, from a spec
+    async fn create_plan_from_spec(&self, spec: PlanSpec) -> Result<models::PlanId, ClientError> {
+        self.request(Method::POST, "/api/plans/spec", Some(&spec))
+            .await
+    }
+
     /// Delete a plan by its ID
     async fn delete_plan(&self, id: u8) -> Result<(), ClientError> {
         let path = format!("/api/plans/{id}");
@@ -318,6 +1203,30 @@ impl Client for HttpClientImpl {
     async fn list_plans(&self) -> Result<Vec<models::Lease>, ClientError> {
         self.request(Method::GET, "/api/plans", None::<&()>).await
     }
+
+    /// List all plans with their goal and completion state
+    async fn list_plan_summaries(&self) -> Result<Vec<models::PlanSummary>, ClientError> {
+        self.request(Method::GET, "/api/plans/summaries", None::<&()>)
+            .await
+    }
+
+    /// List all task templates
+    async fn list_templates(&self) -> Result<Vec<models::TaskTemplate>, ClientError> {
+        self.request(Method::GET, "/api/templates", None::<&()>)
+            .await
+    }
+
+    /// Create or replace a task template
+    async fn save_template(&self, template: models::TaskTemplate) -> Result<(), ClientError> {
+        self.request(Method::POST, "/api/templates", Some(&template))
+            .await
+    }
+
+    /// Remove a task template by name, returning it
+    async fn remove_template(&self, name: String) -> Result<models::TaskTemplate, ClientError> {
+        let path = format!("/api/templates/{}", urlencoding_encode(&name));
+        self.request(Method::DELETE, &path, None::<&()>).await
+    }
 }
 
 impl Default for HttpClientImpl {
@@ -325,3 +1234,148 @@ impl Default for HttpClientImpl {
         Self::new()
     }
 }
+
+/// Percent-encodes a value for use in a URL query string
+fn urlencoding_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{b:02X}"),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::server::serve_until;
+    use crate::api::ServerConfig;
+    use crate::Core;
+
+    /// A local port nothing is listening on
+    fn free_port() -> u16 {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.local_addr().unwrap().port()
+    }
+
+    #[tokio::test]
+    async fn test_reads_fail_over_when_the_primary_is_down() {
+        let core = Core::new();
+        let plan_id = core.create_plan("Replicated".to_string(), None).unwrap();
+        let id = plan_id.value();
+        let standby_port = free_port();
+        let config = ServerConfig {
+            address: ([127, 0, 0, 1], standby_port).into(),
+            ..Default::default()
+        };
+        let (stop, stopped) = tokio::sync::oneshot::channel::<()>();
+        let standby = tokio::spawn(async move {
+            serve_until(core, config, None, async move {
+                let _ = stopped.await;
+            })
+            .await
+            .map_err(|e| e.to_string())
+        });
+
+        let client = HttpClientImpl::with_config(ClientConfig {
+            base_url: format!("http://127.0.0.1:{}", free_port()),
+            fallback_urls: vec![format!("http://127.0.0.1:{standby_port}")],
+            ..Default::default()
+        });
+        let mut progress = client.get_progress(id).await;
+        for _ in 0..100 {
+            if progress.is_ok() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+            progress = client.get_progress(id).await;
+        }
+        assert_eq!(progress.unwrap().total, 0);
+
+        // Changes are never sent to a fallback
+        let added = client.add_task(id, "Lost".to_string(), 0, None).await;
+        assert!(matches!(added, Err(ClientError::Request(_))));
+
+        // Errors from a reachable primary are returned as they are
+        let standby_client = HttpClientImpl::with_config(ClientConfig {
+            base_url: format!("http://127.0.0.1:{standby_port}"),
+            fallback_urls: vec![format!("http://127.0.0.1:{}", free_port())],
+            ..Default::default()
+        });
+        let missing = standby_client.get_progress(id.wrapping_add(1)).await;
+        assert!(matches!(missing, Err(ClientError::PlanNotFound(_))));
+
+        stop.send(()).unwrap();
+        standby.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_backups_follow_the_workspace() {
+        let core = Core::new();
+        core.create_plan("Default".to_string(), None).unwrap();
+        let port = free_port();
+        let config = ServerConfig {
+            address: ([127, 0, 0, 1], port).into(),
+            ..ServerConfig::from_toml_str(
+                r#"
+                admin_token = "admin"
+
+                [[workspaces]]
+                name = "infra"
+                "#,
+            )
+            .unwrap()
+        };
+        let (stop, stopped) = tokio::sync::oneshot::channel::<()>();
+        let server = tokio::spawn(async move {
+            serve_until(core, config, None, async move {
+                let _ = stopped.await;
+            })
+            .await
+            .map_err(|e| e.to_string())
+        });
+
+        // What `scatterbrain --workspace infra backup` and `restore` send
+        let infra = HttpClientImpl::with_config(ClientConfig {
+            base_url: format!("http://127.0.0.1:{port}"),
+            auth_token: Some("admin".to_string()),
+            workspace: Some("infra".to_string()),
+            ..Default::default()
+        });
+        let mut created = infra.create_plan("Infra".to_string(), None).await;
+        for _ in 0..100 {
+            if created.is_ok() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+            created = infra.create_plan("Infra".to_string(), None).await;
+        }
+        let plan_id = created.unwrap();
+
+        let archive = infra.export_plans().await.unwrap();
+        let exported: Vec<u8> = archive.plans.iter().map(|plan| plan.id).collect();
+        assert_eq!(exported, vec![plan_id.value()]);
+        assert_eq!(
+            archive.plans[0].snapshot.plan.goal.as_deref(),
+            Some("Infra")
+        );
+
+        infra.delete_plan(plan_id.value()).await.unwrap();
+        assert_eq!(infra.import_plans(&archive).await.unwrap(), 1);
+        assert_eq!(infra.list_plans().await.unwrap(), vec![plan_id]);
+        assert_eq!(
+            infra
+                .force_unlock(plan_id.value())
+                .await
+                .unwrap()
+                .into_inner(),
+            0
+        );
+
+        stop.send(()).unwrap();
+        server.await.unwrap().unwrap();
+    }
+}
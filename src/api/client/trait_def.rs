@@ -2,8 +2,12 @@
 //!
 //! This module defines the `Client` trait that abstracts over different client implementations.
 
+use std::collections::BTreeSet;
+
 use super::ClientError;
-use crate::models::{self, Index};
+use crate::models::{self, Index, TaskRef};
+use crate::search::SearchHit;
+use crate::spec::{PlanSpec, TaskSpec};
 
 /// Trait defining the API client interface for the scatterbrain service
 #[async_trait::async_trait]
@@ -11,12 +15,38 @@ pub trait Client {
     /// Get the full plan
     async fn get_plan(&self, id: u8) -> Result<models::PlanResponse<models::Plan>, ClientError>;
 
+    /// Get the plan as it was at the given time
+    async fn get_plan_at(
+        &self,
+        id: u8,
+        at: chrono::DateTime<chrono::Utc>,
+    ) -> Result<models::PlanResponse<models::Plan>, ClientError>;
+
     /// Get the current task
     async fn get_current(
         &self,
         id: u8,
     ) -> Result<models::PlanResponse<Option<models::Current>>, ClientError>;
 
+    /// Get a one-line summary of the plan's progress, without the distilled context
+    async fn get_progress(&self, id: u8) -> Result<models::ProgressSummary, ClientError>;
+
+    /// Get counts of the plan's tasks by state and level, without the distilled context
+    async fn get_plan_stats(&self, id: u8) -> Result<models::PlanStats, ClientError>;
+
+    /// Compare completed tasks' estimates with the time logged on them, per level and per
+    /// top-level task
+    async fn get_effort_report(&self, id: u8) -> Result<models::EffortReport, ClientError>;
+
+    /// Check the plan for inconsistencies, such as orphaned leases
+    async fn lint_plan(
+        &self,
+        id: u8,
+    ) -> Result<models::PlanResponse<models::PlanLint>, ClientError>;
+
+    /// Get the plan's full transition log, oldest first
+    async fn get_history(&self, id: u8) -> Result<Vec<models::HistoryRecord>, ClientError>;
+
     /// Get the distilled context
     async fn get_distilled_context(&self, id: u8) -> Result<models::PlanResponse<()>, ClientError>;
 
@@ -29,22 +59,43 @@ pub trait Client {
         notes: Option<String>,
     ) -> Result<models::PlanResponse<(models::Task, Index)>, ClientError>;
 
-    /// Complete the current task
+    /// Add a new task by instantiating a template with parameters
+    async fn add_task_from_template(
+        &self,
+        id: u8,
+        template: String,
+        params: models::TemplateParams,
+    ) -> Result<models::PlanResponse<(models::Task, Index)>, ClientError>;
+
+    /// Complete the current task.
+    /// `cascade` completes incomplete subtasks too (`Some(true)`) or rejects the
+    /// completion while any remain (`Some(false)`); `None` follows the plan's policy.
     async fn complete_task(
         &self,
         id: u8,
         index: Index,
         lease: Option<u8>,
         force: bool,
+        cascade: Option<bool>,
         summary: Option<String>,
-    ) -> Result<models::PlanResponse<bool>, ClientError>;
+    ) -> Result<models::PlanResponse<()>, ClientError>;
+
+    /// Complete the task under the plan's cursor, returning its index
+    async fn complete_current_task(
+        &self,
+        id: u8,
+        lease: Option<u8>,
+        force: bool,
+        cascade: Option<bool>,
+        summary: Option<String>,
+    ) -> Result<models::PlanResponse<Index>, ClientError>;
 
     /// Move to a specific task
     async fn move_to(
         &self,
         id: u8,
         index: Index,
-    ) -> Result<models::PlanResponse<Option<String>>, ClientError>;
+    ) -> Result<models::PlanResponse<String>, ClientError>;
 
     /// Change the abstraction level of a task
     async fn change_level(
@@ -52,46 +103,376 @@ pub trait Client {
         id: u8,
         index: Index,
         level_index: usize,
-    ) -> Result<models::PlanResponse<Result<(), String>>, ClientError>;
+    ) -> Result<models::PlanResponse<()>, ClientError>;
+
+    /// Set how much a task counts toward plan progress, or reset it to the default with `None`
+    async fn set_task_weight(
+        &self,
+        id: u8,
+        index: Index,
+        weight: Option<u32>,
+    ) -> Result<models::PlanResponse<()>, ClientError>;
+
+    /// Set how urgent a task is, from 0 (most) to 9 (least), or clear it with `None`
+    async fn set_task_priority(
+        &self,
+        id: u8,
+        index: Index,
+        priority: Option<u8>,
+    ) -> Result<models::PlanResponse<()>, ClientError>;
+
+    /// Set how many minutes a task is estimated to take, or clear it with `None`
+    async fn set_task_estimate(
+        &self,
+        id: u8,
+        index: Index,
+        minutes: Option<u32>,
+    ) -> Result<models::PlanResponse<()>, ClientError>;
 
-    /// Generate a lease for a specific task
+    /// Log minutes of work on a task, returning the total logged on it
+    async fn log_task_time(
+        &self,
+        id: u8,
+        index: Index,
+        minutes: u32,
+    ) -> Result<models::PlanResponse<u32>, ClientError>;
+
+    /// Generate a lease for a specific task, bound to the client's session if it has one.
+    /// A `single_use` lease completes the task once and is rejected when presented again.
     async fn generate_lease(
         &self,
         id: u8,
         index: Index,
+        single_use: bool,
     ) -> Result<models::PlanResponse<(models::Lease, Vec<String>)>, ClientError>;
 
+    /// Gets the task an index or ID refers to, along with its current index
+    async fn get_task(
+        &self,
+        id: u8,
+        task: TaskRef,
+    ) -> Result<models::PlanResponse<(models::Task, Index)>, ClientError>;
+
+    /// Resolves a task given by index or ID to its current index. Indices are returned as
+    /// they are, without a request.
+    async fn resolve_task(&self, id: u8, task: TaskRef) -> Result<Index, ClientError> {
+        match task {
+            TaskRef::Index(index) => Ok(index),
+            TaskRef::Id(_) => Ok(self.get_task(id, task).await?.into_inner().1),
+        }
+    }
+
+    /// Replaces the description of a task, keeping its subtasks, notes and state
+    async fn update_task_description(
+        &self,
+        id: u8,
+        index: Index,
+        description: String,
+    ) -> Result<models::PlanResponse<models::Task>, ClientError>;
+
     /// Removes a task by its index
     async fn remove_task(
         &self,
         id: u8,
         index: Index,
-    ) -> Result<models::PlanResponse<Result<models::Task, String>>, ClientError>;
+    ) -> Result<models::PlanResponse<models::Task>, ClientError>;
+
+    /// Moves a task, with its subtasks, to be the last subtask of `parent` (the root when
+    /// empty), returning its new index
+    async fn move_task(
+        &self,
+        id: u8,
+        index: Index,
+        parent: Index,
+    ) -> Result<models::PlanResponse<Index>, ClientError>;
+
+    /// Adds tags to a task, returning all of its tags
+    async fn tag_task(
+        &self,
+        id: u8,
+        index: Index,
+        tags: Vec<String>,
+    ) -> Result<models::PlanResponse<BTreeSet<String>>, ClientError>;
+
+    /// Removes tags from a task, returning the tags it has left
+    async fn untag_task(
+        &self,
+        id: u8,
+        index: Index,
+        tags: Vec<String>,
+    ) -> Result<models::PlanResponse<BTreeSet<String>>, ClientError>;
+
+    /// Makes the task at `index` depend on the task at `on`, so it can't be completed
+    /// before it without force. Returns whether the dependency is new.
+    async fn add_dependency(
+        &self,
+        id: u8,
+        index: Index,
+        on: Index,
+    ) -> Result<models::PlanResponse<bool>, ClientError>;
+
+    /// Drops the dependency of the task at `index` on the task at `on`, returning whether
+    /// there was one
+    async fn remove_dependency(
+        &self,
+        id: u8,
+        index: Index,
+        on: Index,
+    ) -> Result<models::PlanResponse<bool>, ClientError>;
+
+    /// Exports the task at `index` and everything below it
+    async fn export_subtree(
+        &self,
+        id: u8,
+        index: Index,
+    ) -> Result<models::PlanResponse<TaskSpec>, ClientError>;
+
+    /// Adds an exported subtree as the last child of `parent`, returning its index
+    async fn import_subtree(
+        &self,
+        id: u8,
+        parent: Index,
+        subtree: TaskSpec,
+    ) -> Result<models::PlanResponse<Index>, ClientError>;
 
     /// Gets the notes for a specific task
     async fn get_task_notes(&self, id: u8, index: Index) -> Result<Option<String>, ClientError>;
 
-    /// Sets the notes for a specific task
+    /// Sets the notes for a specific task, or appends a timestamped block when `append` is true
     async fn set_task_notes(
         &self,
         id: u8,
         index: Index,
         notes: String,
-    ) -> Result<models::PlanResponse<Result<(), String>>, ClientError>;
+        append: bool,
+    ) -> Result<models::PlanResponse<()>, ClientError>;
 
     /// Deletes the notes for a specific task
     async fn delete_task_notes(
         &self,
         id: u8,
         index: Index,
-    ) -> Result<models::PlanResponse<Result<(), String>>, ClientError>;
+    ) -> Result<models::PlanResponse<()>, ClientError>;
+
+    /// Gets the structured notes sections for a specific task, optionally only a single section
+    async fn get_note_sections(
+        &self,
+        id: u8,
+        index: Index,
+        section: Option<String>,
+    ) -> Result<models::NoteSections, ClientError>;
+
+    /// Sets, appends to, or removes (`None` content) a named notes section for a specific task
+    async fn set_note_section(
+        &self,
+        id: u8,
+        index: Index,
+        section: String,
+        content: Option<String>,
+        append: bool,
+    ) -> Result<models::PlanResponse<()>, ClientError>;
+
+    /// Gets the previous versions of the notes for a specific task
+    async fn get_notes_history(
+        &self,
+        id: u8,
+        index: Index,
+    ) -> Result<Vec<models::NoteVersion>, ClientError>;
+
+    /// Restores the notes for a specific task to a previous version
+    async fn revert_task_notes(
+        &self,
+        id: u8,
+        index: Index,
+        version: usize,
+    ) -> Result<models::PlanResponse<()>, ClientError>;
+
+    /// Marks a task as in progress by its index
+    async fn start_task(
+        &self,
+        id: u8,
+        index: Index,
+    ) -> Result<models::PlanResponse<()>, ClientError>;
 
     /// Uncompletes a task by its index
     async fn uncomplete_task(
         &self,
         id: u8,
         index: Index,
-    ) -> Result<models::PlanResponse<Result<bool, String>>, ClientError>;
+    ) -> Result<models::PlanResponse<()>, ClientError>;
+
+    /// Submits a task for review with a completion summary
+    async fn request_review(
+        &self,
+        id: u8,
+        index: Index,
+        summary: String,
+    ) -> Result<models::PlanResponse<()>, ClientError>;
+
+    /// Approves a task awaiting review, marking it complete
+    async fn approve_task(
+        &self,
+        id: u8,
+        index: Index,
+    ) -> Result<models::PlanResponse<()>, ClientError>;
+
+    /// Lists the tasks awaiting review
+    async fn get_review_queue(
+        &self,
+        id: u8,
+    ) -> Result<models::PlanResponse<Vec<models::ReviewItem>>, ClientError>;
+
+    /// Lists the tasks completed between `from` (the start by default) and `to` (now)
+    async fn get_changelog(
+        &self,
+        id: u8,
+        from: Option<chrono::DateTime<chrono::Utc>>,
+        to: Option<chrono::DateTime<chrono::Utc>>,
+    ) -> Result<models::PlanResponse<models::Changelog>, ClientError>;
+
+    /// Rolls up completion summaries under each task at `level`
+    async fn get_summaries(
+        &self,
+        id: u8,
+        level: usize,
+    ) -> Result<models::PlanResponse<Vec<models::SummaryGroup>>, ClientError>;
+
+    /// Lists the tasks that match the filter, without their subtasks
+    async fn list_tasks(
+        &self,
+        id: u8,
+        filter: models::TaskFilter,
+    ) -> Result<models::PlanResponse<Vec<models::TaskListing>>, ClientError>;
+
+    /// Finds the live tasks containing every word of `query`, with a snippet of each match
+    async fn search_tasks(
+        &self,
+        id: u8,
+        query: String,
+    ) -> Result<models::PlanResponse<Vec<SearchHit>>, ClientError>;
+
+    /// Rejects a task awaiting review with an optional reason
+    async fn reject_task(
+        &self,
+        id: u8,
+        index: Index,
+        reason: Option<String>,
+    ) -> Result<models::PlanResponse<()>, ClientError>;
+
+    /// Sets a plan's lifecycle status; Completed requires all tasks complete unless forced
+    async fn set_plan_status(
+        &self,
+        id: u8,
+        status: models::PlanStatus,
+        force: bool,
+    ) -> Result<models::PlanResponse<()>, ClientError>;
+
+    /// Locks a plan read-only, or unlocks it, returning whether the lock state changed
+    async fn set_plan_locked(
+        &self,
+        id: u8,
+        locked: bool,
+    ) -> Result<models::PlanResponse<bool>, ClientError>;
+
+    /// Sets the order a plan's subtasks are listed in, returning whether it changed
+    async fn set_task_order(
+        &self,
+        id: u8,
+        order: models::TaskOrder,
+    ) -> Result<models::PlanResponse<bool>, ClientError>;
+
+    /// Turns tombstones for removed tasks on or off, returning whether the setting changed.
+    /// While they're on, removing or moving a task never shifts its siblings' indices.
+    async fn set_tombstones(
+        &self,
+        id: u8,
+        enabled: bool,
+    ) -> Result<models::PlanResponse<bool>, ClientError>;
+
+    /// Shares a plan under a join code, returning the code
+    async fn share_plan(&self, id: u8) -> Result<models::PlanResponse<String>, ClientError>;
+
+    /// Stops sharing a plan, returning whether it was shared
+    async fn unshare_plan(&self, id: u8) -> Result<models::PlanResponse<bool>, ClientError>;
+
+    /// Replaces a plan's goal and notes; `None` leaves a field as it is, and empty notes
+    /// clear them
+    async fn update_plan_metadata(
+        &self,
+        id: u8,
+        goal: Option<String>,
+        notes: Option<String>,
+    ) -> Result<models::PlanResponse<()>, ClientError>;
+
+    /// Sets a plan's own usage summary, or goes back to the server's when `None`
+    async fn set_usage_summary(
+        &self,
+        id: u8,
+        usage_summary: Option<String>,
+    ) -> Result<models::PlanResponse<()>, ClientError>;
+
+    /// Pins a plan's work to the task at `index`, or clears the focus when `None`
+    async fn set_focus(
+        &self,
+        id: u8,
+        index: Option<Index>,
+        strict: bool,
+    ) -> Result<models::PlanResponse<Option<models::Focus>>, ClientError>;
+
+    /// Starts timing a working session on a plan, optionally changing its reminder interval
+    async fn start_session(
+        &self,
+        id: u8,
+        reminder_minutes: Option<u32>,
+    ) -> Result<models::PlanResponse<models::SessionStatus>, ClientError>;
+
+    /// Stops a plan's working session, returning how long it ran
+    async fn stop_session(
+        &self,
+        id: u8,
+    ) -> Result<models::PlanResponse<Option<models::SessionStatus>>, ClientError>;
+
+    /// Replaces the color and emoji a plan is shown with
+    async fn set_plan_badge(
+        &self,
+        id: u8,
+        badge: models::PlanBadge,
+    ) -> Result<models::PlanResponse<models::PlanBadge>, ClientError>;
+
+    /// Replaces a plan's completion policy
+    async fn set_completion_policy(
+        &self,
+        id: u8,
+        policy: models::CompletionPolicy,
+    ) -> Result<models::PlanResponse<models::CompletionPolicy>, ClientError>;
+
+    /// Records a new open question for a plan
+    async fn add_question(
+        &self,
+        id: u8,
+        question: String,
+    ) -> Result<models::PlanResponse<models::Question>, ClientError>;
+
+    /// Lists the questions tracked for a plan
+    async fn list_questions(
+        &self,
+        id: u8,
+    ) -> Result<models::PlanResponse<Vec<models::Question>>, ClientError>;
+
+    /// Records an answer for a question, marking it answered
+    async fn answer_question(
+        &self,
+        id: u8,
+        question_id: usize,
+        answer: String,
+    ) -> Result<models::PlanResponse<models::Question>, ClientError>;
+
+    /// Removes a question from a plan
+    async fn remove_question(
+        &self,
+        id: u8,
+        question_id: usize,
+    ) -> Result<models::PlanResponse<models::Question>, ClientError>;
 
     /// Create a new plan with a required prompt and optional notes
     async fn create_plan(
@@ -100,9 +481,24 @@ pub trait Client {
         notes: Option<String>,
     ) -> Result<models::PlanId, ClientError>;
 
+    /// Create a plan, with its levels and task tree, from a spec
+    async fn create_plan_from_spec(&self, spec: PlanSpec) -> Result<models::PlanId, ClientError>;
+
     /// Delete a plan by its ID
     async fn delete_plan(&self, id: u8) -> Result<(), ClientError>;
 
     /// List all available plans
     async fn list_plans(&self) -> Result<Vec<models::Lease>, ClientError>;
+
+    /// List all plans with their goal and completion state
+    async fn list_plan_summaries(&self) -> Result<Vec<models::PlanSummary>, ClientError>;
+
+    /// List all task templates
+    async fn list_templates(&self) -> Result<Vec<models::TaskTemplate>, ClientError>;
+
+    /// Create or replace a task template
+    async fn save_template(&self, template: models::TaskTemplate) -> Result<(), ClientError>;
+
+    /// Remove a task template by name, returning it
+    async fn remove_template(&self, name: String) -> Result<models::TaskTemplate, ClientError>;
 }
@@ -0,0 +1,157 @@
+//! Event hooks
+//!
+//! This module runs the shell commands configured under `[[hooks]]` when plan events
+//! happen, much like git hooks. Each command gets the event as JSON on stdin, so
+//! integrations can be scripted without built-in support for them.
+
+use std::process::Stdio;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tokio::sync::broadcast::error::RecvError;
+
+use super::config::SharedConfig;
+use crate::models::{EventKind, PlanEvent};
+use crate::Core;
+
+/// A command run on every event of one kind
+///
+/// ```
+/// # use scatterbrain::api::hooks::EventHook;
+/// # use scatterbrain::models::EventKind;
+/// let hook: EventHook = toml::from_str(r#"
+///     event = "task_completed"
+///     command = "notify-send \"$(jq -r .description)\""
+/// "#).unwrap();
+/// assert_eq!(hook.event, EventKind::TaskCompleted);
+/// assert_eq!(hook.timeout_secs, 60);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EventHook {
+    /// Event the command runs on
+    pub event: EventKind,
+    /// Run with `sh -c`, with the event as JSON on stdin
+    pub command: String,
+    /// Seconds the command may run before it is killed
+    #[serde(default = "default_hook_timeout")]
+    pub timeout_secs: u64,
+}
+
+fn default_hook_timeout() -> u64 {
+    60
+}
+
+/// Spawns a background task that runs the configured hooks for every plan event.
+///
+/// Hooks are looked up for each event, so config reloads take effect immediately. Each
+/// event's hooks run one after another, in config order, on a task of their own, so a
+/// slow hook doesn't hold up other events. Hooks are best effort: failures are logged
+/// and nothing is retried.
+pub fn spawn_hook_runner(core: &Core, config: SharedConfig) -> tokio::task::JoinHandle<()> {
+    let mut receiver = core.subscribe_events();
+
+    tokio::spawn(async move {
+        loop {
+            match receiver.recv().await {
+                Ok(event) => {
+                    let hooks: Vec<EventHook> = config
+                        .read()
+                        .map(|c| {
+                            c.hooks
+                                .iter()
+                                .filter(|hook| hook.event == event.kind())
+                                .cloned()
+                                .collect()
+                        })
+                        .unwrap_or_default();
+                    if !hooks.is_empty() {
+                        tokio::spawn(async move {
+                            for hook in hooks {
+                                if let Err(e) = run_hook(&hook, &event).await {
+                                    tracing::warn!("Hook `{}` failed: {e}", hook.command);
+                                }
+                            }
+                        });
+                    }
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    tracing::warn!("Hook runner lagged, dropped {skipped} events");
+                }
+                Err(RecvError::Closed) => break,
+            }
+        }
+    })
+}
+
+/// Runs one hook to completion, killing it if it outlives its timeout
+pub async fn run_hook(hook: &EventHook, event: &PlanEvent) -> Result<(), String> {
+    let input = serde_json::to_vec(event).map_err(|e| e.to_string())?;
+    let event_name = serde_json::to_value(event.kind())
+        .ok()
+        .and_then(|name| name.as_str().map(str::to_string))
+        .unwrap_or_default();
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(&hook.command)
+        .env("SCATTERBRAIN_EVENT", event_name)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| format!("failed to run: {e}"))?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        // A hook that doesn't read its input closes the pipe early, which is fine
+        let _ = stdin.write_all(&input).await;
+    }
+    let timeout = Duration::from_secs(hook.timeout_secs);
+    let output = tokio::time::timeout(timeout, child.wait_with_output())
+        .await
+        .map_err(|_| format!("timed out after {}s", hook.timeout_secs))?
+        .map_err(|e| format!("failed to wait: {e}"))?;
+    if output.status.success() {
+        tracing::debug!("Hook `{}` ran for {event:?}", hook.command);
+        return Ok(());
+    }
+    Err(format!(
+        "{}: {}",
+        output.status,
+        String::from_utf8_lossy(&output.stderr).trim()
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_run_hook() {
+        let event = PlanEvent::TaskAdded {
+            plan_id: crate::models::Lease::new(3),
+            index: vec![0, 1],
+            description: "Write tests".to_string(),
+            level_index: 2,
+        };
+        let hook = |command: &str, timeout_secs| EventHook {
+            event: EventKind::TaskAdded,
+            command: command.to_string(),
+            timeout_secs,
+        };
+
+        let reads_event = hook(
+            r#"test "$SCATTERBRAIN_EVENT" = task_added && grep -q '"description":"Write tests"'"#,
+            10,
+        );
+        assert_eq!(run_hook(&reads_event, &event).await, Ok(()));
+
+        let fails = run_hook(&hook("echo broken >&2; exit 3", 10), &event).await;
+        assert!(fails.unwrap_err().ends_with("broken"));
+
+        let hangs = run_hook(&hook("sleep 5", 0), &event).await;
+        assert!(hangs.unwrap_err().contains("timed out"));
+    }
+}
@@ -3,11 +3,23 @@
 //! This module provides an MCP server that exposes scatterbrain functionality as MCP tools,
 //! allowing AI assistants to interact with scatterbrain plans and tasks through the standardized MCP protocol.
 
+use std::collections::BTreeSet;
+use std::sync::Arc;
+
 use crate::api::client::{Client, ClientError};
-use crate::models::{self, Index, PlanError};
+use crate::api::plugins::Plugin;
+use crate::api::server::run_blocking;
+use crate::models::{self, Index, PlanError, TaskError, TaskRef};
+use crate::search::SearchHit;
+use crate::spec::{PlanSpec, TaskSpec};
+use crate::templates::parse_param;
 use crate::Core;
+use reqwest::StatusCode;
 use rmcp::{model::*, tool, Error as McpError};
 
+/// Plugin tools, each with the plugin that serves it
+type PluginTools = Vec<(Arc<dyn Plugin>, Tool)>;
+
 /// MCP server implementation for scatterbrain
 ///
 /// This server wraps a Core instance and exposes scatterbrain functionality as MCP tools.
@@ -15,18 +27,93 @@ use rmcp::{model::*, tool, Error as McpError};
 #[derive(Clone)]
 pub struct ScatterbrainMcpServer {
     core: Core,
+    /// Session this server's leases are bound to, so no other client can complete with them
+    session: String,
 }
 
 impl ScatterbrainMcpServer {
     /// Create a new MCP server with the given Core instance
     pub fn new(core: Core) -> Self {
-        Self { core }
+        Self {
+            core,
+            session: format!("mcp-{:016x}", rand::random::<u64>()),
+        }
     }
 
     /// Create a new MCP server with the given Core instance (alias for new)
     pub fn with_core(core: Core) -> Self {
         Self::new(core)
     }
+
+    /// Parses a task index or ID given to a tool and resolves it to the task's current index
+    async fn resolve_index(&self, plan_id: u8, index_str: &str) -> Result<Index, McpError> {
+        let task = parse_index(index_str)?;
+        Client::resolve_task(self, plan_id, task)
+            .await
+            .map_err(to_mcp_error)
+    }
+
+    /// Tools of the core's plugins with the plugin serving each, leaving out tools named
+    /// like a built-in tool or an earlier plugin's tool
+    fn plugin_tools(&self) -> Result<PluginTools, McpError> {
+        let plugins = self
+            .core
+            .plugins()
+            .map_err(|e| McpError::internal_error(e.to_string(), None))?;
+        let builtin = Self::tool_box();
+        let mut tools: PluginTools = Vec::new();
+        for plugin in plugins {
+            for tool in plugin.tools() {
+                let taken = builtin.map.contains_key(&tool.name)
+                    || tools.iter().any(|(_, t)| t.name == tool.name);
+                if taken {
+                    tracing::warn!(
+                        "Ignoring tool '{}' of plugin '{}', the name is taken",
+                        tool.name,
+                        plugin.name()
+                    );
+                } else {
+                    tools.push((plugin.clone(), tool));
+                }
+            }
+        }
+        Ok(tools)
+    }
+
+    /// Runs a built-in or plugin tool on behalf of the MCP client that called it
+    async fn run_tool(
+        &self,
+        request: CallToolRequestParam,
+        context: rmcp::service::RequestContext<rmcp::RoleServer>,
+    ) -> Result<CallToolResult, McpError> {
+        let name = &context.peer.peer_info().client_info.name;
+        let actor = models::ActorInfo {
+            source: models::ActorSource::Mcp,
+            agent: (!name.is_empty()).then(|| name.clone()),
+            request_id: Some(context.id.to_string()),
+        };
+        let plugin = if Self::tool_box().map.contains_key(&request.name) {
+            None
+        } else {
+            self.plugin_tools()?
+                .into_iter()
+                .find(|(_, tool)| tool.name == request.name)
+                .map(|(plugin, _)| plugin)
+        };
+        match plugin {
+            Some(plugin) => {
+                let arguments = request.arguments.unwrap_or_default();
+                actor
+                    .scope(plugin.call_tool(&self.core, &request.name, arguments))
+                    .await
+            }
+            None => {
+                let context =
+                    rmcp::handler::server::tool::ToolCallContext::new(self, request, context);
+                actor.scope(Self::tool_box().call(context)).await
+            }
+        }
+    }
 }
 
 /// Convert PlanError to ClientError for interface compatibility
@@ -35,7 +122,15 @@ impl From<PlanError> for ClientError {
         match error {
             PlanError::PlanNotFound(plan_id) => ClientError::PlanNotFound(plan_id),
             PlanError::Internal(msg) => ClientError::Internal(msg),
+            PlanError::Journal(_) => ClientError::Internal(error.to_string()),
             PlanError::LockError => ClientError::Internal("Lock error".to_string()),
+            PlanError::Task(e) => ClientError::Task(e),
+            PlanError::TemplateNotFound(_)
+            | PlanError::InvalidTemplate(_)
+            | PlanError::InvalidSpec(_)
+            | PlanError::InvalidPlugin(_)
+            | PlanError::RevisionNotFound { .. }
+            | PlanError::InvalidWindow { .. } => ClientError::Api(error.to_string()),
         }
     }
 }
@@ -44,379 +139,2523 @@ impl From<PlanError> for ClientError {
 fn to_mcp_result<T: serde::Serialize>(
     result: Result<T, ClientError>,
 ) -> Result<CallToolResult, McpError> {
-    match result {
-        Ok(value) => {
-            let json = serde_json::to_value(value)
-                .map_err(|e| McpError::internal_error(format!("Serialization error: {e}"), None))?;
-            Ok(CallToolResult::success(vec![Content::text(
-                serde_json::to_string_pretty(&json).map_err(|e| {
-                    McpError::internal_error(format!("JSON formatting error: {e}"), None)
-                })?,
-            )]))
+    let value = result.map_err(to_mcp_error)?;
+    let json = serde_json::to_value(value)
+        .map_err(|e| McpError::internal_error(format!("Serialization error: {e}"), None))?;
+    Ok(CallToolResult::success(vec![Content::text(
+        serde_json::to_string_pretty(&json)
+            .map_err(|e| McpError::internal_error(format!("JSON formatting error: {e}"), None))?,
+    )]))
+}
+
+/// The MCP error a failed scatterbrain call is reported as
+fn to_mcp_error(error: ClientError) -> McpError {
+    match error {
+        // Rejected task operations are the caller's to fix, not server failures. Conflicts
+        // with the plan's state are invalid requests, everything else an invalid argument.
+        // The error itself goes in `data` so agents can tell e.g. a lease mismatch from a
+        // missing summary without parsing the message.
+        ClientError::Task(
+            e @ (TaskError::PlanArchived
+            | TaskError::PlanLocked
+            | TaskError::NoCurrentTask
+            | TaskError::LeaseRequired { .. }
+            | TaskError::LeaseMismatch { .. }
+            | TaskError::LeaseSpent { .. }
+            | TaskError::LeaseSessionMismatch { .. }
+            | TaskError::AlreadyCompleted { .. }
+            | TaskError::NotCompleted { .. }
+            | TaskError::NotAwaitingReview { .. }
+            | TaskError::ReviewPending { .. }
+            | TaskError::TransitionNotAllowed { .. }
+            | TaskError::ChangedDuringValidation { .. }
+            | TaskError::IncompleteTasks
+            | TaskError::IncompleteSubtasks { .. }
+            | TaskError::BlockedByDependencies { .. }
+            | TaskError::NotLeaf { .. }
+            | TaskError::CompletionRejected { .. }
+            | TaskError::OutsideFocus { .. }
+            | TaskError::QuotaExceeded { .. }),
+        ) => McpError::invalid_request(e.to_string(), task_error_data(&e)),
+        ClientError::Task(e) => McpError::invalid_params(e.to_string(), task_error_data(&e)),
+        // A missing plan won't appear on retry; say which one so the agent can list plans
+        ClientError::PlanNotFound(plan_id) => McpError::resource_not_found(
+            format!("Plan {} not found", plan_id.value()),
+            Some(serde_json::json!({ "plan_id": plan_id.value() })),
+        ),
+        // Unknown templates, invalid specs and windows and the like: the arguments need fixing
+        ClientError::Api(message) => McpError::invalid_params(message, None),
+        ClientError::Http { status, message } => {
+            let data = Some(serde_json::json!({ "status": status.as_u16() }));
+            match status {
+                StatusCode::NOT_FOUND => McpError::resource_not_found(message, data),
+                StatusCode::CONFLICT => McpError::invalid_request(message, data),
+                status if status.is_client_error() => McpError::invalid_params(message, data),
+                _ => McpError::internal_error(format!("Scatterbrain error: {message}"), data),
+            }
+        }
+        e
+        @ (ClientError::Request(_) | ClientError::Serialization(_) | ClientError::Internal(_)) => {
+            McpError::internal_error(format!("Scatterbrain error: {e}"), None)
+        }
+    }
+}
+
+/// Error answering a tool call the client cancelled; clients ignore responses to cancelled
+/// requests, but one is still sent
+fn request_cancelled() -> McpError {
+    McpError::invalid_request("Request cancelled by the client", None)
+}
+
+/// Structured form of a rejected task operation, attached to MCP errors as `task_error`
+fn task_error_data(error: &TaskError) -> Option<serde_json::Value> {
+    serde_json::to_value(error)
+        .ok()
+        .map(|task_error| serde_json::json!({ "task_error": task_error }))
+}
+
+/// Pattern of a task index or ID in tool parameter schemas, as [`TaskRef`] parses them
+const INDEX_PATTERN: &str = r"^(\d+(,\d+)*|t-[0-9a-fA-F]{8})$";
+
+// Example values shown in tool parameter schemas, so clients see the expected formats
+fn example_plan_id() -> u8 {
+    42
+}
+
+fn example_index() -> &'static str {
+    "0,2"
+}
+
+fn example_level_index() -> usize {
+    1
+}
+
+fn example_prompt() -> &'static str {
+    "Add rate limiting to the public API"
+}
+
+fn example_status() -> &'static str {
+    "active"
+}
+
+fn example_description() -> &'static str {
+    "Write integration tests for the token bucket"
+}
+
+fn example_summary() -> &'static str {
+    "Added the limiter middleware and tests; all passing"
+}
+
+fn example_template_params() -> Vec<&'static str> {
+    vec!["component=auth"]
+}
+
+fn example_time() -> &'static str {
+    "2025-01-31T09:00:00Z"
+}
+
+/// Helper function to parse a task index or ID from string
+fn parse_index(index_str: &str) -> Result<TaskRef, McpError> {
+    index_str.parse::<TaskRef>().map_err(|e| {
+        McpError::invalid_params(
+            format!("{e}; expected comma-separated positions such as '0,2' or an ID such as 't-3f9a2c1b'"),
+            Some(serde_json::json!({ "index": index_str, "pattern": INDEX_PATTERN })),
+        )
+    })
+}
+
+// Implement the Client trait for ScatterbrainMcpServer
+#[async_trait::async_trait]
+impl Client for ScatterbrainMcpServer {
+    async fn get_plan(&self, id: u8) -> Result<models::PlanResponse<models::Plan>, ClientError> {
+        let plan_id = models::Lease::new(id);
+        self.core.get_plan(&plan_id).map_err(ClientError::from)
+    }
+
+    async fn get_current(
+        &self,
+        id: u8,
+    ) -> Result<models::PlanResponse<Option<models::Current>>, ClientError> {
+        let plan_id = models::Lease::new(id);
+        self.core.current(&plan_id).map_err(ClientError::from)
+    }
+
+    async fn get_plan_at(
+        &self,
+        id: u8,
+        at: chrono::DateTime<chrono::Utc>,
+    ) -> Result<models::PlanResponse<models::Plan>, ClientError> {
+        let (core, plan_id) = (self.core.clone(), models::Lease::new(id));
+        run_blocking(move || core.plan_at(&plan_id, at))
+            .await
+            .map_err(ClientError::from)
+    }
+
+    async fn get_progress(&self, id: u8) -> Result<models::ProgressSummary, ClientError> {
+        let plan_id = models::Lease::new(id);
+        self.core.progress(&plan_id).map_err(ClientError::from)
+    }
+
+    async fn get_plan_stats(&self, id: u8) -> Result<models::PlanStats, ClientError> {
+        let plan_id = models::Lease::new(id);
+        self.core.stats(&plan_id).map_err(ClientError::from)
+    }
+
+    async fn get_effort_report(&self, id: u8) -> Result<models::EffortReport, ClientError> {
+        let plan_id = models::Lease::new(id);
+        self.core.effort_report(&plan_id).map_err(ClientError::from)
+    }
+
+    async fn lint_plan(
+        &self,
+        id: u8,
+    ) -> Result<models::PlanResponse<models::PlanLint>, ClientError> {
+        let plan_id = models::Lease::new(id);
+        self.core.lint_plan(&plan_id).map_err(ClientError::from)
+    }
+
+    async fn get_history(&self, id: u8) -> Result<Vec<models::HistoryRecord>, ClientError> {
+        let (core, plan_id) = (self.core.clone(), models::Lease::new(id));
+        run_blocking(move || core.history(&plan_id))
+            .await
+            .map_err(ClientError::from)
+    }
+
+    async fn get_distilled_context(&self, id: u8) -> Result<models::PlanResponse<()>, ClientError> {
+        let plan_id = models::Lease::new(id);
+        // Suggestion engines may run plugins for as long as their fuel lasts
+        let core = self.core.clone();
+        run_blocking(move || core.distilled_context(&plan_id))
+            .await
+            .map_err(ClientError::from)
+    }
+
+    async fn add_task(
+        &self,
+        id: u8,
+        description: String,
+        level_index: usize,
+        notes: Option<String>,
+    ) -> Result<models::PlanResponse<(models::Task, Index)>, ClientError> {
+        let plan_id = models::Lease::new(id);
+        self.core
+            .add_task(&plan_id, description, level_index, notes)
+            .map_err(ClientError::from)
+    }
+
+    async fn add_task_from_template(
+        &self,
+        id: u8,
+        template: String,
+        params: models::TemplateParams,
+    ) -> Result<models::PlanResponse<(models::Task, Index)>, ClientError> {
+        let plan_id = models::Lease::new(id);
+        self.core
+            .add_task_from_template(&plan_id, &template, &params)
+            .map_err(ClientError::from)
+    }
+
+    async fn complete_task(
+        &self,
+        id: u8,
+        index: Index,
+        lease: Option<u8>,
+        force: bool,
+        cascade: Option<bool>,
+        summary: Option<String>,
+    ) -> Result<models::PlanResponse<()>, ClientError> {
+        let plan_id = models::Lease::new(id);
+        let (core, session) = (self.core.clone(), self.session.clone());
+        run_blocking(move || {
+            core.complete_task(
+                &plan_id,
+                index,
+                lease,
+                Some(&session),
+                force,
+                cascade,
+                summary,
+            )
+        })
+        .await
+        .map_err(ClientError::from)
+    }
+
+    async fn complete_current_task(
+        &self,
+        id: u8,
+        lease: Option<u8>,
+        force: bool,
+        cascade: Option<bool>,
+        summary: Option<String>,
+    ) -> Result<models::PlanResponse<Index>, ClientError> {
+        let plan_id = models::Lease::new(id);
+        let (core, session) = (self.core.clone(), self.session.clone());
+        run_blocking(move || {
+            core.complete_current_task(&plan_id, lease, Some(&session), force, cascade, summary)
+        })
+        .await
+        .map_err(ClientError::from)
+    }
+
+    async fn move_to(
+        &self,
+        id: u8,
+        index: Index,
+    ) -> Result<models::PlanResponse<String>, ClientError> {
+        let plan_id = models::Lease::new(id);
+        self.core
+            .move_to(&plan_id, index)
+            .map_err(ClientError::from)
+    }
+
+    async fn change_level(
+        &self,
+        id: u8,
+        index: Index,
+        level_index: usize,
+    ) -> Result<models::PlanResponse<()>, ClientError> {
+        let plan_id = models::Lease::new(id);
+        self.core
+            .change_level(&plan_id, index, level_index)
+            .map_err(ClientError::from)
+    }
+
+    async fn set_task_weight(
+        &self,
+        id: u8,
+        index: Index,
+        weight: Option<u32>,
+    ) -> Result<models::PlanResponse<()>, ClientError> {
+        let plan_id = models::Lease::new(id);
+        self.core
+            .set_task_weight(&plan_id, index, weight)
+            .map_err(ClientError::from)
+    }
+
+    async fn set_task_priority(
+        &self,
+        id: u8,
+        index: Index,
+        priority: Option<u8>,
+    ) -> Result<models::PlanResponse<()>, ClientError> {
+        let plan_id = models::Lease::new(id);
+        self.core
+            .set_task_priority(&plan_id, index, priority)
+            .map_err(ClientError::from)
+    }
+
+    async fn set_task_estimate(
+        &self,
+        id: u8,
+        index: Index,
+        minutes: Option<u32>,
+    ) -> Result<models::PlanResponse<()>, ClientError> {
+        let plan_id = models::Lease::new(id);
+        self.core
+            .set_task_estimate(&plan_id, index, minutes)
+            .map_err(ClientError::from)
+    }
+
+    async fn log_task_time(
+        &self,
+        id: u8,
+        index: Index,
+        minutes: u32,
+    ) -> Result<models::PlanResponse<u32>, ClientError> {
+        let plan_id = models::Lease::new(id);
+        self.core
+            .log_task_time(&plan_id, index, minutes)
+            .map_err(ClientError::from)
+    }
+
+    async fn generate_lease(
+        &self,
+        id: u8,
+        index: Index,
+        single_use: bool,
+    ) -> Result<models::PlanResponse<(models::Lease, Vec<String>)>, ClientError> {
+        let plan_id = models::Lease::new(id);
+        let options = models::LeaseOptions {
+            single_use,
+            session: Some(self.session.clone()),
+        };
+        self.core
+            .generate_lease(&plan_id, index, options)
+            .map_err(ClientError::from)
+    }
+
+    async fn get_task(
+        &self,
+        id: u8,
+        task: TaskRef,
+    ) -> Result<models::PlanResponse<(models::Task, Index)>, ClientError> {
+        let plan_id = models::Lease::new(id);
+        self.core
+            .get_task(&plan_id, &task)
+            .map_err(ClientError::from)
+    }
+
+    async fn update_task_description(
+        &self,
+        id: u8,
+        index: Index,
+        description: String,
+    ) -> Result<models::PlanResponse<models::Task>, ClientError> {
+        let plan_id = models::Lease::new(id);
+        self.core
+            .update_task_description(&plan_id, index, description)
+            .map_err(ClientError::from)
+    }
+
+    async fn remove_task(
+        &self,
+        id: u8,
+        index: Index,
+    ) -> Result<models::PlanResponse<models::Task>, ClientError> {
+        let plan_id = models::Lease::new(id);
+        self.core
+            .remove_task(&plan_id, index)
+            .map_err(ClientError::from)
+    }
+
+    async fn move_task(
+        &self,
+        id: u8,
+        index: Index,
+        parent: Index,
+    ) -> Result<models::PlanResponse<Index>, ClientError> {
+        let plan_id = models::Lease::new(id);
+        self.core
+            .move_task(&plan_id, index, parent)
+            .map_err(ClientError::from)
+    }
+
+    async fn tag_task(
+        &self,
+        id: u8,
+        index: Index,
+        tags: Vec<String>,
+    ) -> Result<models::PlanResponse<BTreeSet<String>>, ClientError> {
+        let plan_id = models::Lease::new(id);
+        self.core
+            .tag_task(&plan_id, index, tags)
+            .map_err(ClientError::from)
+    }
+
+    async fn untag_task(
+        &self,
+        id: u8,
+        index: Index,
+        tags: Vec<String>,
+    ) -> Result<models::PlanResponse<BTreeSet<String>>, ClientError> {
+        let plan_id = models::Lease::new(id);
+        self.core
+            .untag_task(&plan_id, index, tags)
+            .map_err(ClientError::from)
+    }
+
+    async fn add_dependency(
+        &self,
+        id: u8,
+        index: Index,
+        on: Index,
+    ) -> Result<models::PlanResponse<bool>, ClientError> {
+        let plan_id = models::Lease::new(id);
+        self.core
+            .add_dependency(&plan_id, index, on)
+            .map_err(ClientError::from)
+    }
+
+    async fn remove_dependency(
+        &self,
+        id: u8,
+        index: Index,
+        on: Index,
+    ) -> Result<models::PlanResponse<bool>, ClientError> {
+        let plan_id = models::Lease::new(id);
+        self.core
+            .remove_dependency(&plan_id, index, on)
+            .map_err(ClientError::from)
+    }
+
+    async fn export_subtree(
+        &self,
+        id: u8,
+        index: Index,
+    ) -> Result<models::PlanResponse<TaskSpec>, ClientError> {
+        let plan_id = models::Lease::new(id);
+        self.core
+            .export_subtree(&plan_id, index)
+            .map_err(ClientError::from)
+    }
+
+    async fn import_subtree(
+        &self,
+        id: u8,
+        parent: Index,
+        subtree: TaskSpec,
+    ) -> Result<models::PlanResponse<Index>, ClientError> {
+        let plan_id = models::Lease::new(id);
+        self.core
+            .import_subtree(&plan_id, parent, subtree)
+            .map_err(ClientError::from)
+    }
+
+    async fn get_task_notes(&self, id: u8, index: Index) -> Result<Option<String>, ClientError> {
+        let plan_id = models::Lease::new(id);
+        // Note: Core's get_task_notes returns PlanResponse<Option<String>>
+        // We need to extract the inner value
+        self.core
+            .get_task_notes(&plan_id, index)
+            .map(models::PlanResponse::into_inner)
+            .map_err(ClientError::from)
+    }
+
+    async fn set_task_notes(
+        &self,
+        id: u8,
+        index: Index,
+        notes: String,
+        append: bool,
+    ) -> Result<models::PlanResponse<()>, ClientError> {
+        let plan_id = models::Lease::new(id);
+        let result = if append {
+            self.core.append_task_notes(&plan_id, index, notes)
+        } else {
+            self.core.set_task_notes(&plan_id, index, notes)
+        };
+        result.map_err(ClientError::from)
+    }
+
+    async fn delete_task_notes(
+        &self,
+        id: u8,
+        index: Index,
+    ) -> Result<models::PlanResponse<()>, ClientError> {
+        let plan_id = models::Lease::new(id);
+        self.core
+            .delete_task_notes(&plan_id, index)
+            .map_err(ClientError::from)
+    }
+
+    async fn get_note_sections(
+        &self,
+        id: u8,
+        index: Index,
+        section: Option<String>,
+    ) -> Result<models::NoteSections, ClientError> {
+        let plan_id = models::Lease::new(id);
+        self.core
+            .get_note_sections(&plan_id, index, section)
+            .map(models::PlanResponse::into_inner)
+            .map_err(ClientError::from)
+    }
+
+    async fn set_note_section(
+        &self,
+        id: u8,
+        index: Index,
+        section: String,
+        content: Option<String>,
+        append: bool,
+    ) -> Result<models::PlanResponse<()>, ClientError> {
+        let plan_id = models::Lease::new(id);
+        self.core
+            .set_note_section(&plan_id, index, section, content, append)
+            .map_err(ClientError::from)
+    }
+
+    async fn get_notes_history(
+        &self,
+        id: u8,
+        index: Index,
+    ) -> Result<Vec<models::NoteVersion>, ClientError> {
+        let plan_id = models::Lease::new(id);
+        self.core
+            .get_notes_history(&plan_id, index)
+            .map(models::PlanResponse::into_inner)
+            .map_err(ClientError::from)
+    }
+
+    async fn revert_task_notes(
+        &self,
+        id: u8,
+        index: Index,
+        version: usize,
+    ) -> Result<models::PlanResponse<()>, ClientError> {
+        let plan_id = models::Lease::new(id);
+        self.core
+            .revert_task_notes(&plan_id, index, version)
+            .map_err(ClientError::from)
+    }
+
+    async fn start_task(
+        &self,
+        id: u8,
+        index: Index,
+    ) -> Result<models::PlanResponse<()>, ClientError> {
+        let plan_id = models::Lease::new(id);
+        self.core
+            .start_task(&plan_id, index)
+            .map_err(ClientError::from)
+    }
+
+    async fn uncomplete_task(
+        &self,
+        id: u8,
+        index: Index,
+    ) -> Result<models::PlanResponse<()>, ClientError> {
+        let plan_id = models::Lease::new(id);
+        self.core
+            .uncomplete_task(&plan_id, index)
+            .map_err(ClientError::from)
+    }
+
+    async fn request_review(
+        &self,
+        id: u8,
+        index: Index,
+        summary: String,
+    ) -> Result<models::PlanResponse<()>, ClientError> {
+        let plan_id = models::Lease::new(id);
+        self.core
+            .request_review(&plan_id, index, summary)
+            .map_err(ClientError::from)
+    }
+
+    async fn approve_task(
+        &self,
+        id: u8,
+        index: Index,
+    ) -> Result<models::PlanResponse<()>, ClientError> {
+        let plan_id = models::Lease::new(id);
+        let core = self.core.clone();
+        run_blocking(move || core.approve_task(&plan_id, index))
+            .await
+            .map_err(ClientError::from)
+    }
+
+    async fn get_review_queue(
+        &self,
+        id: u8,
+    ) -> Result<models::PlanResponse<Vec<models::ReviewItem>>, ClientError> {
+        let plan_id = models::Lease::new(id);
+        self.core.review_queue(&plan_id).map_err(ClientError::from)
+    }
+
+    async fn get_changelog(
+        &self,
+        id: u8,
+        from: Option<chrono::DateTime<chrono::Utc>>,
+        to: Option<chrono::DateTime<chrono::Utc>>,
+    ) -> Result<models::PlanResponse<models::Changelog>, ClientError> {
+        let (core, plan_id) = (self.core.clone(), models::Lease::new(id));
+        run_blocking(move || core.changelog(&plan_id, from, to))
+            .await
+            .map_err(ClientError::from)
+    }
+
+    async fn get_summaries(
+        &self,
+        id: u8,
+        level: usize,
+    ) -> Result<models::PlanResponse<Vec<models::SummaryGroup>>, ClientError> {
+        let plan_id = models::Lease::new(id);
+        self.core
+            .summaries(&plan_id, level)
+            .map_err(ClientError::from)
+    }
+
+    async fn list_tasks(
+        &self,
+        id: u8,
+        filter: models::TaskFilter,
+    ) -> Result<models::PlanResponse<Vec<models::TaskListing>>, ClientError> {
+        let plan_id = models::Lease::new(id);
+        self.core
+            .list_tasks(&plan_id, &filter)
+            .map_err(ClientError::from)
+    }
+
+    async fn search_tasks(
+        &self,
+        id: u8,
+        query: String,
+    ) -> Result<models::PlanResponse<Vec<SearchHit>>, ClientError> {
+        let plan_id = models::Lease::new(id);
+        self.core
+            .search_tasks(&plan_id, &query)
+            .map_err(ClientError::from)
+    }
+
+    async fn reject_task(
+        &self,
+        id: u8,
+        index: Index,
+        reason: Option<String>,
+    ) -> Result<models::PlanResponse<()>, ClientError> {
+        let plan_id = models::Lease::new(id);
+        self.core
+            .reject_task(&plan_id, index, reason)
+            .map_err(ClientError::from)
+    }
+
+    async fn set_plan_status(
+        &self,
+        id: u8,
+        status: models::PlanStatus,
+        force: bool,
+    ) -> Result<models::PlanResponse<()>, ClientError> {
+        let plan_id = models::Lease::new(id);
+        self.core
+            .set_plan_status(&plan_id, status, force)
+            .map_err(ClientError::from)
+    }
+
+    async fn set_plan_locked(
+        &self,
+        id: u8,
+        locked: bool,
+    ) -> Result<models::PlanResponse<bool>, ClientError> {
+        let plan_id = models::Lease::new(id);
+        self.core
+            .set_plan_locked(&plan_id, locked)
+            .map_err(ClientError::from)
+    }
+
+    async fn set_task_order(
+        &self,
+        id: u8,
+        order: models::TaskOrder,
+    ) -> Result<models::PlanResponse<bool>, ClientError> {
+        let plan_id = models::Lease::new(id);
+        self.core
+            .set_task_order(&plan_id, order)
+            .map_err(ClientError::from)
+    }
+
+    async fn set_tombstones(
+        &self,
+        id: u8,
+        enabled: bool,
+    ) -> Result<models::PlanResponse<bool>, ClientError> {
+        let plan_id = models::Lease::new(id);
+        self.core
+            .set_tombstones(&plan_id, enabled)
+            .map_err(ClientError::from)
+    }
+
+    async fn share_plan(&self, id: u8) -> Result<models::PlanResponse<String>, ClientError> {
+        let plan_id = models::Lease::new(id);
+        self.core.share_plan(&plan_id).map_err(ClientError::from)
+    }
+
+    async fn unshare_plan(&self, id: u8) -> Result<models::PlanResponse<bool>, ClientError> {
+        let plan_id = models::Lease::new(id);
+        self.core.unshare_plan(&plan_id).map_err(ClientError::from)
+    }
+
+    async fn update_plan_metadata(
+        &self,
+        id: u8,
+        goal: Option<String>,
+        notes: Option<String>,
+    ) -> Result<models::PlanResponse<()>, ClientError> {
+        let plan_id = models::Lease::new(id);
+        self.core
+            .update_plan_metadata(&plan_id, goal, notes)
+            .map_err(ClientError::from)
+    }
+
+    async fn set_usage_summary(
+        &self,
+        id: u8,
+        usage_summary: Option<String>,
+    ) -> Result<models::PlanResponse<()>, ClientError> {
+        let plan_id = models::Lease::new(id);
+        self.core
+            .set_plan_usage_summary(&plan_id, usage_summary)
+            .map_err(ClientError::from)
+    }
+
+    async fn set_focus(
+        &self,
+        id: u8,
+        index: Option<Index>,
+        strict: bool,
+    ) -> Result<models::PlanResponse<Option<models::Focus>>, ClientError> {
+        let plan_id = models::Lease::new(id);
+        self.core
+            .set_focus(&plan_id, index, strict)
+            .map_err(ClientError::from)
+    }
+
+    async fn start_session(
+        &self,
+        id: u8,
+        reminder_minutes: Option<u32>,
+    ) -> Result<models::PlanResponse<models::SessionStatus>, ClientError> {
+        let plan_id = models::Lease::new(id);
+        self.core
+            .start_session(&plan_id, reminder_minutes)
+            .map_err(ClientError::from)
+    }
+
+    async fn stop_session(
+        &self,
+        id: u8,
+    ) -> Result<models::PlanResponse<Option<models::SessionStatus>>, ClientError> {
+        let plan_id = models::Lease::new(id);
+        self.core.stop_session(&plan_id).map_err(ClientError::from)
+    }
+
+    async fn set_plan_badge(
+        &self,
+        id: u8,
+        badge: models::PlanBadge,
+    ) -> Result<models::PlanResponse<models::PlanBadge>, ClientError> {
+        let plan_id = models::Lease::new(id);
+        self.core
+            .set_plan_badge(&plan_id, badge)
+            .map_err(ClientError::from)
+    }
+
+    async fn set_completion_policy(
+        &self,
+        id: u8,
+        policy: models::CompletionPolicy,
+    ) -> Result<models::PlanResponse<models::CompletionPolicy>, ClientError> {
+        let plan_id = models::Lease::new(id);
+        self.core
+            .set_completion_policy(&plan_id, policy)
+            .map_err(ClientError::from)
+    }
+
+    async fn add_question(
+        &self,
+        id: u8,
+        question: String,
+    ) -> Result<models::PlanResponse<models::Question>, ClientError> {
+        let plan_id = models::Lease::new(id);
+        self.core
+            .add_question(&plan_id, question)
+            .map_err(ClientError::from)
+    }
+
+    async fn list_questions(
+        &self,
+        id: u8,
+    ) -> Result<models::PlanResponse<Vec<models::Question>>, ClientError> {
+        let plan_id = models::Lease::new(id);
+        self.core
+            .list_questions(&plan_id)
+            .map_err(ClientError::from)
+    }
+
+    async fn answer_question(
+        &self,
+        id: u8,
+        question_id: usize,
+        answer: String,
+    ) -> Result<models::PlanResponse<models::Question>, ClientError> {
+        let plan_id = models::Lease::new(id);
+        self.core
+            .answer_question(&plan_id, question_id, answer)
+            .map_err(ClientError::from)
+    }
+
+    async fn remove_question(
+        &self,
+        id: u8,
+        question_id: usize,
+    ) -> Result<models::PlanResponse<models::Question>, ClientError> {
+        let plan_id = models::Lease::new(id);
+        self.core
+            .remove_question(&plan_id, question_id)
+            .map_err(ClientError::from)
+    }
+
+    async fn create_plan(
+        &self,
+        prompt: String,
+        notes: Option<String>,
+    ) -> Result<models::PlanId, ClientError> {
+        self.core
+            .create_plan(prompt, notes)
+            .map_err(ClientError::from)
+    }
+
+    async fn create_plan_from_spec(&self, spec: PlanSpec) -> Result<models::PlanId, ClientError> {
+        self.core
+            .create_plan_from_spec(spec)
+            .map_err(ClientError::from)
+    }
+
+    async fn delete_plan(&self, id: u8) -> Result<(), ClientError> {
+        let plan_id = models::Lease::new(id);
+        self.core.delete_plan(&plan_id).map_err(ClientError::from)
+    }
+
+    async fn list_plans(&self) -> Result<Vec<models::Lease>, ClientError> {
+        self.core.list_plans().map_err(ClientError::from)
+    }
+
+    async fn list_plan_summaries(&self) -> Result<Vec<models::PlanSummary>, ClientError> {
+        self.core.list_plan_summaries().map_err(ClientError::from)
+    }
+
+    async fn list_templates(&self) -> Result<Vec<models::TaskTemplate>, ClientError> {
+        self.core.list_templates().map_err(ClientError::from)
+    }
+
+    async fn save_template(&self, template: models::TaskTemplate) -> Result<(), ClientError> {
+        self.core.save_template(template).map_err(ClientError::from)
+    }
+
+    async fn remove_template(&self, name: String) -> Result<models::TaskTemplate, ClientError> {
+        self.core.remove_template(&name).map_err(ClientError::from)
+    }
+}
+
+#[tool(tool_box)]
+impl ScatterbrainMcpServer {
+    // Plan Management Tools
+
+    #[tool(description = "Get a plan by ID")]
+    async fn get_plan(
+        &self,
+        #[tool(param)]
+        #[schemars(
+            description = "Plan ID, as returned by create_plan or list_plans",
+            range(min = 0, max = 255),
+            example = "example_plan_id"
+        )]
+        plan_id: u8,
+    ) -> Result<CallToolResult, McpError> {
+        let result = Client::get_plan(self, plan_id).await;
+        to_mcp_result(result)
+    }
+
+    #[tool(description = "Create a new plan with required prompt and optional notes")]
+    async fn create_plan(
+        &self,
+        #[tool(param)]
+        #[schemars(
+            description = "Short title for the plan's goal; put details in notes",
+            example = "example_prompt"
+        )]
+        prompt: String,
+        #[tool(param)]
+        #[schemars(description = "Longer description of the goal, constraints and context")]
+        notes: Option<String>,
+    ) -> Result<CallToolResult, McpError> {
+        let result = Client::create_plan(self, prompt, notes).await;
+        to_mcp_result(result)
+    }
+
+    #[tool(description = "Delete a plan by ID")]
+    async fn delete_plan(
+        &self,
+        #[tool(param)]
+        #[schemars(
+            description = "Plan ID, as returned by create_plan or list_plans",
+            range(min = 0, max = 255),
+            example = "example_plan_id"
+        )]
+        plan_id: u8,
+    ) -> Result<CallToolResult, McpError> {
+        let result = Client::delete_plan(self, plan_id).await;
+        to_mcp_result(result)
+    }
+
+    #[tool(
+        description = "Refine a plan's goal or notes as your understanding of the work evolves. Omitted fields stay as they are; empty notes clear them"
+    )]
+    async fn update_plan(
+        &self,
+        #[tool(param)]
+        #[schemars(
+            description = "Plan ID, as returned by create_plan or list_plans",
+            range(min = 0, max = 255),
+            example = "example_plan_id"
+        )]
+        plan_id: u8,
+        #[tool(param)]
+        #[schemars(
+            description = "New short title for the plan's goal",
+            example = "example_prompt"
+        )]
+        goal: Option<String>,
+        #[tool(param)]
+        #[schemars(description = "New longer description of the goal, constraints and context")]
+        notes: Option<String>,
+    ) -> Result<CallToolResult, McpError> {
+        if goal.as_deref().is_some_and(|goal| goal.trim().is_empty()) {
+            return Err(McpError::invalid_params("goal must not be empty", None));
+        }
+        let result = Client::update_plan_metadata(self, plan_id, goal, notes).await;
+        to_mcp_result(result)
+    }
+
+    #[tool(
+        description = "Set a plan's status: draft, active, completed or abandoned. Completed requires all tasks to be complete unless force=true"
+    )]
+    async fn set_plan_status(
+        &self,
+        #[tool(param)]
+        #[schemars(
+            description = "Plan ID, as returned by create_plan or list_plans",
+            range(min = 0, max = 255),
+            example = "example_plan_id"
+        )]
+        plan_id: u8,
+        #[tool(param)]
+        #[schemars(
+            description = "New status",
+            regex(pattern = "^(draft|active|completed|abandoned)$"),
+            example = "example_status"
+        )]
+        status: String,
+        #[tool(param)]
+        #[schemars(description = "Mark the plan completed even if some tasks are incomplete")]
+        force: Option<bool>,
+    ) -> Result<CallToolResult, McpError> {
+        let status = status
+            .parse::<models::PlanStatus>()
+            .map_err(|e| McpError::invalid_params(e, None))?;
+        let result = Client::set_plan_status(self, plan_id, status, force.unwrap_or(false)).await;
+        to_mcp_result(result)
+    }
+
+    #[tool(
+        description = "List all available plans with their goal, status (draft, active, completed, abandoned) and completion time"
+    )]
+    async fn list_plans(&self) -> Result<CallToolResult, McpError> {
+        let result = Client::list_plan_summaries(self).await;
+        to_mcp_result(result)
+    }
+
+    // Navigation Tools
+
+    #[tool(description = "Get the current task for a plan")]
+    async fn get_current(
+        &self,
+        #[tool(param)]
+        #[schemars(
+            description = "Plan ID, as returned by create_plan or list_plans",
+            range(min = 0, max = 255),
+            example = "example_plan_id"
+        )]
+        plan_id: u8,
+    ) -> Result<CallToolResult, McpError> {
+        let result = Client::get_current(self, plan_id).await;
+        to_mcp_result(result)
+    }
+
+    #[tool(
+        description = "Get a brief summary of a plan's progress: status, current task and completed/total task counts"
+    )]
+    async fn get_progress(
+        &self,
+        #[tool(param)]
+        #[schemars(
+            description = "Plan ID, as returned by create_plan or list_plans",
+            range(min = 0, max = 255),
+            example = "example_plan_id"
+        )]
+        plan_id: u8,
+    ) -> Result<CallToolResult, McpError> {
+        let result = Client::get_progress(self, plan_id).await;
+        to_mcp_result(result)
+    }
+
+    #[tool(
+        description = "Get a compact JSON summary of a plan's progress for reporting to the user: task counts overall and per level, percent complete, tasks awaiting review or leased, open questions, recent completions and last activity"
+    )]
+    async fn get_plan_stats(
+        &self,
+        #[tool(param)]
+        #[schemars(
+            description = "Plan ID, as returned by create_plan or list_plans",
+            range(min = 0, max = 255),
+            example = "example_plan_id"
+        )]
+        plan_id: u8,
+    ) -> Result<CallToolResult, McpError> {
+        let result = Client::get_plan_stats(self, plan_id).await;
+        to_mcp_result(result)
+    }
+
+    #[tool(
+        description = "Compare the estimates of completed tasks with the minutes logged on them and their subtasks, per level and per top-level task, to see how optimistic planning at each level is. actual_minutes above estimated_minutes means the work took longer than planned"
+    )]
+    async fn get_effort_report(
+        &self,
+        #[tool(param)]
+        #[schemars(
+            description = "Plan ID, as returned by create_plan or list_plans",
+            range(min = 0, max = 255),
+            example = "example_plan_id"
+        )]
+        plan_id: u8,
+    ) -> Result<CallToolResult, McpError> {
+        let result = Client::get_effort_report(self, plan_id).await;
+        to_mcp_result(result)
+    }
+
+    #[tool(
+        description = "Check a plan for inconsistencies, such as leases left on missing or completed tasks"
+    )]
+    async fn lint_plan(
+        &self,
+        #[tool(param)]
+        #[schemars(
+            description = "Plan ID, as returned by create_plan or list_plans",
+            range(min = 0, max = 255),
+            example = "example_plan_id"
+        )]
+        plan_id: u8,
+    ) -> Result<CallToolResult, McpError> {
+        let result = Client::lint_plan(self, plan_id).await;
+        to_mcp_result(result)
+    }
+
+    #[tool(
+        description = "Get distilled context for a plan. Optionally pass comma-separated section names (e.g., 'findings,decisions') to include only those notes sections, and format 'compact' for dense plain text to put in a prompt instead of JSON"
+    )]
+    async fn get_distilled_context(
+        &self,
+        #[tool(param)]
+        #[schemars(
+            description = "Plan ID, as returned by create_plan or list_plans",
+            range(min = 0, max = 255),
+            example = "example_plan_id"
+        )]
+        plan_id: u8,
+        #[tool(param)]
+        #[schemars(
+            description = "Comma-separated notes section names to keep, e.g. 'findings,decisions'; omit for all"
+        )]
+        sections: Option<String>,
+        #[tool(param)]
+        #[schemars(
+            description = "'json' (default) or 'compact' plain text",
+            regex(pattern = "^(json|compact)$")
+        )]
+        format: Option<String>,
+    ) -> Result<CallToolResult, McpError> {
+        let compact = match format.as_deref() {
+            None | Some("json") => false,
+            Some("compact") => true,
+            Some(other) => {
+                return Err(McpError::invalid_params(
+                    format!("Unknown context format '{other}', expected json or compact"),
+                    None,
+                ))
+            }
+        };
+        let result = Client::get_distilled_context(self, plan_id)
+            .await
+            .map(|mut response| {
+                if let Some(sections) = sections {
+                    let sections: Vec<String> =
+                        sections.split(',').map(|s| s.to_string()).collect();
+                    response.distilled_context.retain_note_sections(&sections);
+                }
+                response
+            });
+        match result {
+            Ok(response) if compact => Ok(CallToolResult::success(vec![Content::text(
+                response.distilled_context.to_compact(),
+            )])),
+            result => to_mcp_result(result),
+        }
+    }
+
+    #[tool(description = "Move to a specific task by index (e.g., '0,1,2')")]
+    async fn move_to(
+        &self,
+        #[tool(param)]
+        #[schemars(
+            description = "Plan ID, as returned by create_plan or list_plans",
+            range(min = 0, max = 255),
+            example = "example_plan_id"
+        )]
+        plan_id: u8,
+        #[tool(param)]
+        #[schemars(
+            description = "Task index: comma-separated zero-based positions from the root, e.g. '0' for the first top-level task and '0,2' for its third subtask, or the task's ID, e.g. 't-3f9a2c1b', which doesn't change as tasks are added and removed",
+            regex = "INDEX_PATTERN",
+            example = "example_index"
+        )]
+        index: String,
+    ) -> Result<CallToolResult, McpError> {
+        let parsed_index = self.resolve_index(plan_id, &index).await?;
+        let result = Client::move_to(self, plan_id, parsed_index).await;
+        to_mcp_result(result)
+    }
+
+    // Task Operations
+
+    #[tool(description = "Add a new task to a plan")]
+    async fn add_task(
+        &self,
+        #[tool(param)]
+        #[schemars(
+            description = "Plan ID, as returned by create_plan or list_plans",
+            range(min = 0, max = 255),
+            example = "example_plan_id"
+        )]
+        plan_id: u8,
+        #[tool(param)]
+        #[schemars(
+            description = "One-line description of the task",
+            example = "example_description"
+        )]
+        description: String,
+        #[tool(param)]
+        #[schemars(
+            description = "Zero-based level index, 0 being the most abstract level; get_plan lists the plan's levels",
+            example = "example_level_index"
+        )]
+        level_index: usize,
+        #[tool(param)]
+        #[schemars(
+            description = "Longer notes for the task, such as approach or acceptance criteria"
+        )]
+        notes: Option<String>,
+    ) -> Result<CallToolResult, McpError> {
+        let result = Client::add_task(self, plan_id, description, level_index, notes).await;
+        to_mcp_result(result)
+    }
+
+    #[tool(
+        description = "Add a new task from a template. Params are 'key=value' strings filling the template's {key} placeholders"
+    )]
+    async fn add_task_from_template(
+        &self,
+        #[tool(param)]
+        #[schemars(
+            description = "Plan ID, as returned by create_plan or list_plans",
+            range(min = 0, max = 255),
+            example = "example_plan_id"
+        )]
+        plan_id: u8,
+        #[tool(param)]
+        #[schemars(description = "Template name from list_templates")]
+        template: String,
+        #[tool(param)]
+        #[schemars(
+            description = "'key=value' strings filling the template's {key} placeholders",
+            example = "example_template_params"
+        )]
+        params: Option<Vec<String>>,
+    ) -> Result<CallToolResult, McpError> {
+        let params = params
+            .unwrap_or_default()
+            .iter()
+            .map(|param| parse_param(param))
+            .collect::<Result<models::TemplateParams, _>>()
+            .map_err(|e| McpError::invalid_params(e, None))?;
+        let result = Client::add_task_from_template(self, plan_id, template, params).await;
+        to_mcp_result(result)
+    }
+
+    #[tool(description = "List the task templates available on the server")]
+    async fn list_templates(&self) -> Result<CallToolResult, McpError> {
+        let result = Client::list_templates(self).await;
+        to_mcp_result(result)
+    }
+
+    #[tool(
+        description = "Create or replace a task template. Description, notes and checklist items may contain {param} placeholders"
+    )]
+    async fn save_template(
+        &self,
+        #[tool(param)]
+        #[schemars(description = "Template name; saving an existing name replaces it")]
+        name: String,
+        #[tool(param)]
+        #[schemars(description = "Task description, which may contain {param} placeholders")]
+        description: String,
+        #[tool(param)]
+        #[schemars(
+            description = "Zero-based level index, 0 being the most abstract level; get_plan lists the plan's levels",
+            example = "example_level_index"
+        )]
+        level_index: usize,
+        #[tool(param)]
+        #[schemars(description = "Task notes, which may contain {param} placeholders")]
+        notes: Option<String>,
+        #[tool(param)]
+        #[schemars(description = "Checklist items added to the task's notes")]
+        checklist: Option<Vec<String>>,
+    ) -> Result<CallToolResult, McpError> {
+        let template = models::TaskTemplate {
+            name,
+            description,
+            level_index,
+            notes,
+            checklist: checklist.unwrap_or_default(),
+        };
+        let result = Client::save_template(self, template).await;
+        to_mcp_result(result)
+    }
+
+    #[tool(description = "Remove a task template by name")]
+    async fn remove_template(
+        &self,
+        #[tool(param)]
+        #[schemars(description = "Template name from list_templates")]
+        name: String,
+    ) -> Result<CallToolResult, McpError> {
+        let result = Client::remove_template(self, name).await;
+        to_mcp_result(result)
+    }
+
+    #[tool(
+        description = "Complete a task by index. Set cascade to also complete its incomplete subtasks, or false to fail while any remain; the plan's policy decides when unset"
+    )]
+    async fn complete_task(
+        &self,
+        #[tool(param)]
+        #[schemars(
+            description = "Plan ID, as returned by create_plan or list_plans",
+            range(min = 0, max = 255),
+            example = "example_plan_id"
+        )]
+        plan_id: u8,
+        #[tool(param)]
+        #[schemars(
+            description = "Task index: comma-separated zero-based positions from the root, e.g. '0' for the first top-level task and '0,2' for its third subtask, or the task's ID, e.g. 't-3f9a2c1b', which doesn't change as tasks are added and removed",
+            regex = "INDEX_PATTERN",
+            example = "example_index"
+        )]
+        index: String,
+        #[tool(param)]
+        #[schemars(
+            description = "Lease number from generate_lease; required when the task is leased",
+            range(min = 0, max = 255)
+        )]
+        lease: Option<u8>,
+        #[tool(param)]
+        #[schemars(description = "Complete even if the lease doesn't match")]
+        force: Option<bool>,
+        #[tool(param)]
+        #[schemars(
+            description = "true also completes incomplete subtasks, false fails while any remain; omit to follow the plan's policy"
+        )]
+        cascade: Option<bool>,
+        #[tool(param)]
+        #[schemars(
+            description = "What was done, shown in changelogs and summaries; the plan's policy may require one",
+            example = "example_summary"
+        )]
+        summary: Option<String>,
+    ) -> Result<CallToolResult, McpError> {
+        let parsed_index = self.resolve_index(plan_id, &index).await?;
+        let result = Client::complete_task(
+            self,
+            plan_id,
+            parsed_index,
+            lease,
+            force.unwrap_or(false),
+            cascade,
+            summary,
+        )
+        .await;
+        to_mcp_result(result)
+    }
+
+    #[tool(description = "Complete the current task (the one the plan's cursor is on)")]
+    async fn complete_current_task(
+        &self,
+        #[tool(param)]
+        #[schemars(
+            description = "Plan ID, as returned by create_plan or list_plans",
+            range(min = 0, max = 255),
+            example = "example_plan_id"
+        )]
+        plan_id: u8,
+        #[tool(param)]
+        #[schemars(
+            description = "Lease number from generate_lease; required when the task is leased",
+            range(min = 0, max = 255)
+        )]
+        lease: Option<u8>,
+        #[tool(param)]
+        #[schemars(description = "Complete even if the lease doesn't match")]
+        force: Option<bool>,
+        #[tool(param)]
+        #[schemars(
+            description = "true also completes incomplete subtasks, false fails while any remain; omit to follow the plan's policy"
+        )]
+        cascade: Option<bool>,
+        #[tool(param)]
+        #[schemars(
+            description = "What was done, shown in changelogs and summaries; the plan's policy may require one",
+            example = "example_summary"
+        )]
+        summary: Option<String>,
+    ) -> Result<CallToolResult, McpError> {
+        let force = force.unwrap_or(false);
+        let result =
+            Client::complete_current_task(self, plan_id, lease, force, cascade, summary).await;
+        to_mcp_result(result)
+    }
+
+    #[tool(
+        description = "Mark a task as in progress by index; plans with status rules may require this before completing it"
+    )]
+    async fn start_task(
+        &self,
+        #[tool(param)]
+        #[schemars(
+            description = "Plan ID, as returned by create_plan or list_plans",
+            range(min = 0, max = 255),
+            example = "example_plan_id"
+        )]
+        plan_id: u8,
+        #[tool(param)]
+        #[schemars(
+            description = "Task index: comma-separated zero-based positions from the root, e.g. '0' for the first top-level task and '0,2' for its third subtask, or the task's ID, e.g. 't-3f9a2c1b', which doesn't change as tasks are added and removed",
+            regex = "INDEX_PATTERN",
+            example = "example_index"
+        )]
+        index: String,
+    ) -> Result<CallToolResult, McpError> {
+        let parsed_index = self.resolve_index(plan_id, &index).await?;
+        let result = Client::start_task(self, plan_id, parsed_index).await;
+        to_mcp_result(result)
+    }
+
+    #[tool(description = "Uncomplete a task by index")]
+    async fn uncomplete_task(
+        &self,
+        #[tool(param)]
+        #[schemars(
+            description = "Plan ID, as returned by create_plan or list_plans",
+            range(min = 0, max = 255),
+            example = "example_plan_id"
+        )]
+        plan_id: u8,
+        #[tool(param)]
+        #[schemars(
+            description = "Task index: comma-separated zero-based positions from the root, e.g. '0' for the first top-level task and '0,2' for its third subtask, or the task's ID, e.g. 't-3f9a2c1b', which doesn't change as tasks are added and removed",
+            regex = "INDEX_PATTERN",
+            example = "example_index"
+        )]
+        index: String,
+    ) -> Result<CallToolResult, McpError> {
+        let parsed_index = self.resolve_index(plan_id, &index).await?;
+        let result = Client::uncomplete_task(self, plan_id, parsed_index).await;
+        to_mcp_result(result)
+    }
+
+    #[tool(
+        description = "Submit a task for review with a completion summary instead of completing it directly"
+    )]
+    async fn request_review(
+        &self,
+        #[tool(param)]
+        #[schemars(
+            description = "Plan ID, as returned by create_plan or list_plans",
+            range(min = 0, max = 255),
+            example = "example_plan_id"
+        )]
+        plan_id: u8,
+        #[tool(param)]
+        #[schemars(
+            description = "Task index: comma-separated zero-based positions from the root, e.g. '0' for the first top-level task and '0,2' for its third subtask, or the task's ID, e.g. 't-3f9a2c1b', which doesn't change as tasks are added and removed",
+            regex = "INDEX_PATTERN",
+            example = "example_index"
+        )]
+        index: String,
+        #[tool(param)]
+        #[schemars(
+            description = "What was done, shown in changelogs and summaries; the plan's policy may require one",
+            example = "example_summary"
+        )]
+        summary: String,
+    ) -> Result<CallToolResult, McpError> {
+        let parsed_index = self.resolve_index(plan_id, &index).await?;
+        let result = Client::request_review(self, plan_id, parsed_index, summary).await;
+        to_mcp_result(result)
+    }
+
+    #[tool(description = "Approve a task awaiting review, marking it complete")]
+    async fn approve_task(
+        &self,
+        #[tool(param)]
+        #[schemars(
+            description = "Plan ID, as returned by create_plan or list_plans",
+            range(min = 0, max = 255),
+            example = "example_plan_id"
+        )]
+        plan_id: u8,
+        #[tool(param)]
+        #[schemars(
+            description = "Task index: comma-separated zero-based positions from the root, e.g. '0' for the first top-level task and '0,2' for its third subtask, or the task's ID, e.g. 't-3f9a2c1b', which doesn't change as tasks are added and removed",
+            regex = "INDEX_PATTERN",
+            example = "example_index"
+        )]
+        index: String,
+    ) -> Result<CallToolResult, McpError> {
+        let parsed_index = self.resolve_index(plan_id, &index).await?;
+        let result = Client::approve_task(self, plan_id, parsed_index).await;
+        to_mcp_result(result)
+    }
+
+    #[tool(description = "List tasks awaiting review with their submitted summaries")]
+    async fn get_review_queue(
+        &self,
+        #[tool(param)]
+        #[schemars(
+            description = "Plan ID, as returned by create_plan or list_plans",
+            range(min = 0, max = 255),
+            example = "example_plan_id"
+        )]
+        plan_id: u8,
+    ) -> Result<CallToolResult, McpError> {
+        let result = Client::get_review_queue(self, plan_id).await;
+        to_mcp_result(result)
+    }
+
+    #[tool(
+        description = "List the tasks completed between two RFC 3339 times (from: the plan's start by default, to: now by default) with their summaries. Pass format 'markdown' for release notes instead of JSON"
+    )]
+    async fn get_changelog(
+        &self,
+        #[tool(param)]
+        #[schemars(
+            description = "Plan ID, as returned by create_plan or list_plans",
+            range(min = 0, max = 255),
+            example = "example_plan_id"
+        )]
+        plan_id: u8,
+        #[tool(param)]
+        #[schemars(
+            description = "RFC 3339 start time, e.g. '2025-01-31T09:00:00Z'; defaults to the plan's start",
+            example = "example_time"
+        )]
+        from: Option<String>,
+        #[tool(param)]
+        #[schemars(
+            description = "RFC 3339 end time; defaults to now",
+            example = "example_time"
+        )]
+        to: Option<String>,
+        #[tool(param)]
+        #[schemars(
+            description = "'json' (default) or 'markdown' release notes",
+            regex(pattern = "^(json|markdown)$")
+        )]
+        format: Option<String>,
+    ) -> Result<CallToolResult, McpError> {
+        let parse_time = |time: Option<String>| {
+            time.map(|time| {
+                chrono::DateTime::parse_from_rfc3339(&time)
+                    .map(|at| at.with_timezone(&chrono::Utc))
+                    .map_err(|e| {
+                        McpError::invalid_params(format!("Invalid time '{time}': {e}"), None)
+                    })
+            })
+            .transpose()
+        };
+        let (from, to) = (parse_time(from)?, parse_time(to)?);
+        let markdown = match format.as_deref() {
+            None | Some("json") => false,
+            Some("markdown") => true,
+            Some(other) => {
+                return Err(McpError::invalid_params(
+                    format!("Unknown changelog format '{other}', expected json or markdown"),
+                    None,
+                ))
+            }
+        };
+        match Client::get_changelog(self, plan_id, from, to).await {
+            Ok(response) if markdown => Ok(CallToolResult::success(vec![Content::text(
+                response.into_inner().to_markdown(),
+            )])),
+            result => to_mcp_result(result),
         }
-        Err(e) => Err(McpError::internal_error(
-            format!("Scatterbrain error: {e}"),
-            None,
-        )),
     }
-}
 
-/// Helper function to parse index from string
-fn parse_index(index_str: &str) -> Result<Index, McpError> {
-    models::parse_index(index_str).map_err(|e| {
-        McpError::invalid_params(format!("Invalid index format '{index_str}': {e}"), None)
-    })
-}
+    #[tool(
+        description = "Roll up completion summaries under each task at a level (1, the components, by default), as a per-component change log"
+    )]
+    async fn get_summaries(
+        &self,
+        #[tool(param)]
+        #[schemars(
+            description = "Plan ID, as returned by create_plan or list_plans",
+            range(min = 0, max = 255),
+            example = "example_plan_id"
+        )]
+        plan_id: u8,
+        #[tool(param)]
+        #[schemars(description = "Level index whose tasks group the summaries; defaults to 1")]
+        level: Option<usize>,
+    ) -> Result<CallToolResult, McpError> {
+        let level = level.unwrap_or(models::DEFAULT_SUMMARY_LEVEL);
+        let result = Client::get_summaries(self, plan_id, level).await;
+        to_mcp_result(result)
+    }
 
-// Implement the Client trait for ScatterbrainMcpServer
-#[async_trait::async_trait]
-impl Client for ScatterbrainMcpServer {
-    async fn get_plan(&self, id: u8) -> Result<models::PlanResponse<models::Plan>, ClientError> {
-        let plan_id = models::Lease::new(id);
-        self.core.get_plan(&plan_id).map_err(ClientError::from)
+    #[tool(
+        description = "List a plan's tasks without their subtasks. Optionally only incomplete tasks, tasks at one level index, tasks at most `depth` levels down, tasks below the `subtree` index (e.g. \"0,2\"), or tasks with a tag"
+    )]
+    async fn list_tasks(
+        &self,
+        #[tool(param)]
+        #[schemars(
+            description = "Plan ID, as returned by create_plan or list_plans",
+            range(min = 0, max = 255),
+            example = "example_plan_id"
+        )]
+        plan_id: u8,
+        #[tool(param)]
+        #[schemars(description = "Only list incomplete tasks")]
+        incomplete_only: Option<bool>,
+        #[tool(param)]
+        #[schemars(description = "Only list tasks at this level index")]
+        level: Option<usize>,
+        #[tool(param)]
+        #[schemars(
+            description = "Only list tasks at most this many levels below the root (1 for top-level tasks)"
+        )]
+        depth: Option<usize>,
+        #[tool(param)]
+        #[schemars(
+            description = "Only list tasks below this task index, e.g. '0,2'",
+            regex = "INDEX_PATTERN",
+            example = "example_index"
+        )]
+        subtree: Option<String>,
+        #[tool(param)]
+        #[schemars(description = "Only list tasks with this tag, e.g. 'backend'")]
+        tag: Option<String>,
+    ) -> Result<CallToolResult, McpError> {
+        let filter = models::TaskFilter {
+            incomplete_only: incomplete_only.unwrap_or(false),
+            level,
+            depth,
+            subtree: match subtree {
+                Some(subtree) => self.resolve_index(plan_id, &subtree).await?,
+                None => Index::new(),
+            },
+            tag,
+        };
+        let result = Client::list_tasks(self, plan_id, filter).await;
+        to_mcp_result(result)
     }
 
-    async fn get_current(
+    #[tool(
+        description = "Search a plan's tasks by the words in their descriptions, notes and completion summaries. Returns each matching task's index and description with a snippet of the text that matched. Search before adding a task to check it isn't already in the plan"
+    )]
+    async fn search_tasks(
         &self,
-        id: u8,
-    ) -> Result<models::PlanResponse<Option<models::Current>>, ClientError> {
-        let plan_id = models::Lease::new(id);
-        self.core.current(&plan_id).map_err(ClientError::from)
+        #[tool(param)]
+        #[schemars(
+            description = "Plan ID, as returned by create_plan or list_plans",
+            range(min = 0, max = 255),
+            example = "example_plan_id"
+        )]
+        plan_id: u8,
+        #[tool(param)]
+        #[schemars(
+            description = "Words every matching task contains, in any order and case; 'pars' also matches 'parser'"
+        )]
+        query: String,
+    ) -> Result<CallToolResult, McpError> {
+        let result = Client::search_tasks(self, plan_id, query).await;
+        to_mcp_result(result)
     }
 
-    async fn get_distilled_context(&self, id: u8) -> Result<models::PlanResponse<()>, ClientError> {
-        let plan_id = models::Lease::new(id);
-        self.core
-            .distilled_context(&plan_id)
-            .map_err(ClientError::from)
+    #[tool(description = "Reject a task awaiting review with an optional reason")]
+    async fn reject_task(
+        &self,
+        #[tool(param)]
+        #[schemars(
+            description = "Plan ID, as returned by create_plan or list_plans",
+            range(min = 0, max = 255),
+            example = "example_plan_id"
+        )]
+        plan_id: u8,
+        #[tool(param)]
+        #[schemars(
+            description = "Task index: comma-separated zero-based positions from the root, e.g. '0' for the first top-level task and '0,2' for its third subtask, or the task's ID, e.g. 't-3f9a2c1b', which doesn't change as tasks are added and removed",
+            regex = "INDEX_PATTERN",
+            example = "example_index"
+        )]
+        index: String,
+        #[tool(param)]
+        #[schemars(description = "Why the task was rejected, shown to whoever picks it up again")]
+        reason: Option<String>,
+    ) -> Result<CallToolResult, McpError> {
+        let parsed_index = self.resolve_index(plan_id, &index).await?;
+        let result = Client::reject_task(self, plan_id, parsed_index, reason).await;
+        to_mcp_result(result)
     }
 
-    async fn add_task(
+    #[tool(
+        description = "Replace a task's description, e.g. to fix a typo. Its subtasks, notes, ID and completion state stay as they are"
+    )]
+    async fn update_task(
         &self,
-        id: u8,
+        #[tool(param)]
+        #[schemars(
+            description = "Plan ID, as returned by create_plan or list_plans",
+            range(min = 0, max = 255),
+            example = "example_plan_id"
+        )]
+        plan_id: u8,
+        #[tool(param)]
+        #[schemars(
+            description = "Task index: comma-separated zero-based positions from the root, e.g. '0' for the first top-level task and '0,2' for its third subtask, or the task's ID, e.g. 't-3f9a2c1b', which doesn't change as tasks are added and removed",
+            regex = "INDEX_PATTERN",
+            example = "example_index"
+        )]
+        index: String,
+        #[tool(param)]
+        #[schemars(
+            description = "New one-line description of the task",
+            example = "example_description"
+        )]
         description: String,
-        level_index: usize,
-        notes: Option<String>,
-    ) -> Result<models::PlanResponse<(models::Task, Index)>, ClientError> {
-        let plan_id = models::Lease::new(id);
-        self.core
-            .add_task(&plan_id, description, level_index, notes)
-            .map_err(ClientError::from)
+    ) -> Result<CallToolResult, McpError> {
+        let parsed_index = self.resolve_index(plan_id, &index).await?;
+        let result =
+            Client::update_task_description(self, plan_id, parsed_index, description).await;
+        to_mcp_result(result)
     }
 
-    async fn complete_task(
+    #[tool(description = "Remove a task by index")]
+    async fn remove_task(
         &self,
-        id: u8,
-        index: Index,
-        lease: Option<u8>,
-        force: bool,
-        summary: Option<String>,
-    ) -> Result<models::PlanResponse<bool>, ClientError> {
-        let plan_id = models::Lease::new(id);
-        self.core
-            .complete_task(&plan_id, index, lease, force, summary)
-            .map_err(ClientError::from)
+        #[tool(param)]
+        #[schemars(
+            description = "Plan ID, as returned by create_plan or list_plans",
+            range(min = 0, max = 255),
+            example = "example_plan_id"
+        )]
+        plan_id: u8,
+        #[tool(param)]
+        #[schemars(
+            description = "Task index: comma-separated zero-based positions from the root, e.g. '0' for the first top-level task and '0,2' for its third subtask, or the task's ID, e.g. 't-3f9a2c1b', which doesn't change as tasks are added and removed",
+            regex = "INDEX_PATTERN",
+            example = "example_index"
+        )]
+        index: String,
+    ) -> Result<CallToolResult, McpError> {
+        let parsed_index = self.resolve_index(plan_id, &index).await?;
+        let result = Client::remove_task(self, plan_id, parsed_index).await;
+        to_mcp_result(result)
     }
 
-    async fn move_to(
+    #[tool(
+        description = "Move a task, with its subtasks, to be the last subtask of another task, keeping its leases and ID. Returns its new index; suggested_followups lists the indices that changed"
+    )]
+    async fn move_task(
         &self,
-        id: u8,
-        index: Index,
-    ) -> Result<models::PlanResponse<Option<String>>, ClientError> {
-        let plan_id = models::Lease::new(id);
-        self.core
-            .move_to(&plan_id, index)
-            .map_err(ClientError::from)
+        #[tool(param)]
+        #[schemars(
+            description = "Plan ID, as returned by create_plan or list_plans",
+            range(min = 0, max = 255),
+            example = "example_plan_id"
+        )]
+        plan_id: u8,
+        #[tool(param)]
+        #[schemars(
+            description = "Task index: comma-separated zero-based positions from the root, e.g. '0' for the first top-level task and '0,2' for its third subtask, or the task's ID, e.g. 't-3f9a2c1b', which doesn't change as tasks are added and removed",
+            regex = "INDEX_PATTERN",
+            example = "example_index"
+        )]
+        index: String,
+        #[tool(param)]
+        #[schemars(
+            description = "Index or ID of the task to move it under, e.g. '1'; omit to move it to the root. Its level must not be above this task's",
+            regex = "INDEX_PATTERN",
+            example = "example_index"
+        )]
+        parent_index: Option<String>,
+    ) -> Result<CallToolResult, McpError> {
+        let parsed_index = self.resolve_index(plan_id, &index).await?;
+        let parent = match parent_index {
+            Some(parent) => self.resolve_index(plan_id, &parent).await?,
+            None => Vec::new(),
+        };
+        let result = Client::move_task(self, plan_id, parsed_index, parent).await;
+        to_mcp_result(result)
     }
 
-    async fn change_level(
+    #[tool(
+        description = "Tag a task, e.g. 'backend' or 'docs', so large plans can be sliced by concern with list_tasks. Tags are lowercased and can't contain whitespace or commas. Returns the task's tags"
+    )]
+    async fn tag_task(
         &self,
-        id: u8,
-        index: Index,
-        level_index: usize,
-    ) -> Result<models::PlanResponse<Result<(), String>>, ClientError> {
-        let plan_id = models::Lease::new(id);
-        self.core
-            .change_level(&plan_id, index, level_index)
-            .map_err(ClientError::from)
+        #[tool(param)]
+        #[schemars(
+            description = "Plan ID, as returned by create_plan or list_plans",
+            range(min = 0, max = 255),
+            example = "example_plan_id"
+        )]
+        plan_id: u8,
+        #[tool(param)]
+        #[schemars(
+            description = "Task index: comma-separated zero-based positions from the root, e.g. '0' for the first top-level task and '0,2' for its third subtask, or the task's ID, e.g. 't-3f9a2c1b', which doesn't change as tasks are added and removed",
+            regex = "INDEX_PATTERN",
+            example = "example_index"
+        )]
+        index: String,
+        #[tool(param)]
+        #[schemars(description = "Tags to add, e.g. [\"backend\", \"api\"]")]
+        tags: Vec<String>,
+    ) -> Result<CallToolResult, McpError> {
+        let parsed_index = self.resolve_index(plan_id, &index).await?;
+        let result = Client::tag_task(self, plan_id, parsed_index, tags).await;
+        to_mcp_result(result)
     }
 
-    async fn generate_lease(
+    #[tool(description = "Remove tags from a task. Returns the tags it has left")]
+    async fn untag_task(
         &self,
-        id: u8,
-        index: Index,
-    ) -> Result<models::PlanResponse<(models::Lease, Vec<String>)>, ClientError> {
-        let plan_id = models::Lease::new(id);
-        self.core
-            .generate_lease(&plan_id, index)
-            .map_err(ClientError::from)
+        #[tool(param)]
+        #[schemars(
+            description = "Plan ID, as returned by create_plan or list_plans",
+            range(min = 0, max = 255),
+            example = "example_plan_id"
+        )]
+        plan_id: u8,
+        #[tool(param)]
+        #[schemars(
+            description = "Task index: comma-separated zero-based positions from the root, e.g. '0' for the first top-level task and '0,2' for its third subtask, or the task's ID, e.g. 't-3f9a2c1b', which doesn't change as tasks are added and removed",
+            regex = "INDEX_PATTERN",
+            example = "example_index"
+        )]
+        index: String,
+        #[tool(param)]
+        #[schemars(description = "Tags to remove; tags the task doesn't have are ignored")]
+        tags: Vec<String>,
+    ) -> Result<CallToolResult, McpError> {
+        let parsed_index = self.resolve_index(plan_id, &index).await?;
+        let result = Client::untag_task(self, plan_id, parsed_index, tags).await;
+        to_mcp_result(result)
     }
 
-    async fn remove_task(
+    #[tool(
+        description = "Make a task depend on another: it can't be completed until the other is, unless forced, and shows as blocked in the task tree. Returns whether the dependency is new"
+    )]
+    async fn add_dependency(
         &self,
-        id: u8,
-        index: Index,
-    ) -> Result<models::PlanResponse<Result<models::Task, String>>, ClientError> {
-        let plan_id = models::Lease::new(id);
-        self.core
-            .remove_task(&plan_id, index)
-            .map_err(ClientError::from)
+        #[tool(param)]
+        #[schemars(
+            description = "Plan ID, as returned by create_plan or list_plans",
+            range(min = 0, max = 255),
+            example = "example_plan_id"
+        )]
+        plan_id: u8,
+        #[tool(param)]
+        #[schemars(
+            description = "Index or ID of the task that waits, e.g. '1' or 't-3f9a2c1b'",
+            regex = "INDEX_PATTERN",
+            example = "example_index"
+        )]
+        index: String,
+        #[tool(param)]
+        #[schemars(
+            description = "Index or ID of the task it depends on; it must not already depend on the waiting task",
+            regex = "INDEX_PATTERN",
+            example = "example_index"
+        )]
+        depends_on: String,
+    ) -> Result<CallToolResult, McpError> {
+        let parsed_index = self.resolve_index(plan_id, &index).await?;
+        let on = self.resolve_index(plan_id, &depends_on).await?;
+        let result = Client::add_dependency(self, plan_id, parsed_index, on).await;
+        to_mcp_result(result)
     }
 
-    async fn get_task_notes(&self, id: u8, index: Index) -> Result<Option<String>, ClientError> {
-        let plan_id = models::Lease::new(id);
-        // Note: Core's get_task_notes returns PlanResponse<Result<Option<String>, String>>
-        // We need to extract the inner value and handle the nested Result
-        match self.core.get_task_notes(&plan_id, index) {
-            Ok(plan_response) => match plan_response.into_inner() {
-                Ok(notes) => Ok(notes),
-                Err(err) => Err(ClientError::Internal(err)),
-            },
-            Err(plan_error) => Err(ClientError::from(plan_error)),
-        }
+    #[tool(description = "Drop a task's dependency on another. Returns whether there was one")]
+    async fn remove_dependency(
+        &self,
+        #[tool(param)]
+        #[schemars(
+            description = "Plan ID, as returned by create_plan or list_plans",
+            range(min = 0, max = 255),
+            example = "example_plan_id"
+        )]
+        plan_id: u8,
+        #[tool(param)]
+        #[schemars(
+            description = "Index or ID of the task that waits, e.g. '1' or 't-3f9a2c1b'",
+            regex = "INDEX_PATTERN",
+            example = "example_index"
+        )]
+        index: String,
+        #[tool(param)]
+        #[schemars(
+            description = "Index or ID of the task it depends on",
+            regex = "INDEX_PATTERN",
+            example = "example_index"
+        )]
+        depends_on: String,
+    ) -> Result<CallToolResult, McpError> {
+        let parsed_index = self.resolve_index(plan_id, &index).await?;
+        let on = self.resolve_index(plan_id, &depends_on).await?;
+        let result = Client::remove_dependency(self, plan_id, parsed_index, on).await;
+        to_mcp_result(result)
     }
 
-    async fn set_task_notes(
+    #[tool(
+        description = "Pin a plan's work to a task (e.g., '0,1'): working on tasks outside it then warns, or fails with strict. Omit index to clear the focus"
+    )]
+    async fn set_focus(
         &self,
-        id: u8,
-        index: Index,
-        notes: String,
-    ) -> Result<models::PlanResponse<Result<(), String>>, ClientError> {
-        let plan_id = models::Lease::new(id);
-        self.core
-            .set_task_notes(&plan_id, index, notes)
-            .map_err(ClientError::from)
+        #[tool(param)]
+        #[schemars(
+            description = "Plan ID, as returned by create_plan or list_plans",
+            range(min = 0, max = 255),
+            example = "example_plan_id"
+        )]
+        plan_id: u8,
+        #[tool(param)]
+        #[schemars(
+            description = "Task index to focus on, e.g. '0,1'; omit to clear the focus",
+            regex = "INDEX_PATTERN",
+            example = "example_index"
+        )]
+        index: Option<String>,
+        #[tool(param)]
+        #[schemars(description = "Fail, rather than warn, when working outside the focus")]
+        strict: Option<bool>,
+    ) -> Result<CallToolResult, McpError> {
+        let parsed_index = match index {
+            Some(index) => Some(self.resolve_index(plan_id, &index).await?),
+            None => None,
+        };
+        let result = Client::set_focus(self, plan_id, parsed_index, strict.unwrap_or(false)).await;
+        to_mcp_result(result)
     }
 
-    async fn delete_task_notes(
+    #[tool(
+        description = "Start timing a working session on a plan. The elapsed time appears in distilled_context.session, and every reminder_minutes (default 30, 0 for none) a response reminds you to re-read the goal and update notes"
+    )]
+    async fn start_session(
         &self,
-        id: u8,
-        index: Index,
-    ) -> Result<models::PlanResponse<Result<(), String>>, ClientError> {
-        let plan_id = models::Lease::new(id);
-        self.core
-            .delete_task_notes(&plan_id, index)
-            .map_err(ClientError::from)
+        #[tool(param)]
+        #[schemars(
+            description = "Plan ID, as returned by create_plan or list_plans",
+            range(min = 0, max = 255),
+            example = "example_plan_id"
+        )]
+        plan_id: u8,
+        #[tool(param)]
+        #[schemars(description = "Minutes between reminders; defaults to 30, 0 for none")]
+        reminder_minutes: Option<u32>,
+    ) -> Result<CallToolResult, McpError> {
+        let result = Client::start_session(self, plan_id, reminder_minutes).await;
+        to_mcp_result(result)
     }
 
-    async fn uncomplete_task(
+    #[tool(description = "Stop timing a plan's working session and report how long it ran")]
+    async fn stop_session(
         &self,
-        id: u8,
-        index: Index,
-    ) -> Result<models::PlanResponse<Result<bool, String>>, ClientError> {
-        let plan_id = models::Lease::new(id);
-        self.core
-            .uncomplete_task(&plan_id, index)
-            .map_err(ClientError::from)
+        #[tool(param)]
+        #[schemars(
+            description = "Plan ID, as returned by create_plan or list_plans",
+            range(min = 0, max = 255),
+            example = "example_plan_id"
+        )]
+        plan_id: u8,
+    ) -> Result<CallToolResult, McpError> {
+        let result = Client::stop_session(self, plan_id).await;
+        to_mcp_result(result)
     }
 
-    async fn create_plan(
+    #[tool(description = "Change the level of a task")]
+    async fn change_level(
         &self,
-        prompt: String,
-        notes: Option<String>,
-    ) -> Result<models::PlanId, ClientError> {
-        self.core
-            .create_plan(prompt, notes)
-            .map_err(ClientError::from)
+        #[tool(param)]
+        #[schemars(
+            description = "Plan ID, as returned by create_plan or list_plans",
+            range(min = 0, max = 255),
+            example = "example_plan_id"
+        )]
+        plan_id: u8,
+        #[tool(param)]
+        #[schemars(
+            description = "Task index: comma-separated zero-based positions from the root, e.g. '0' for the first top-level task and '0,2' for its third subtask, or the task's ID, e.g. 't-3f9a2c1b', which doesn't change as tasks are added and removed",
+            regex = "INDEX_PATTERN",
+            example = "example_index"
+        )]
+        index: String,
+        #[tool(param)]
+        #[schemars(
+            description = "Zero-based level index, 0 being the most abstract level; get_plan lists the plan's levels",
+            example = "example_level_index"
+        )]
+        level_index: usize,
+    ) -> Result<CallToolResult, McpError> {
+        let parsed_index = self.resolve_index(plan_id, &index).await?;
+        let result = Client::change_level(self, plan_id, parsed_index, level_index).await;
+        to_mcp_result(result)
     }
 
-    async fn delete_plan(&self, id: u8) -> Result<(), ClientError> {
-        let plan_id = models::Lease::new(id);
-        self.core.delete_plan(&plan_id).map_err(ClientError::from)
+    #[tool(
+        description = "Set how much a task counts toward plan progress (default 1), so a large task outweighs several small ones. Omit weight to reset it"
+    )]
+    async fn set_task_weight(
+        &self,
+        #[tool(param)]
+        #[schemars(
+            description = "Plan ID, as returned by create_plan or list_plans",
+            range(min = 0, max = 255),
+            example = "example_plan_id"
+        )]
+        plan_id: u8,
+        #[tool(param)]
+        #[schemars(
+            description = "Task index: comma-separated zero-based positions from the root, e.g. '0' for the first top-level task and '0,2' for its third subtask, or the task's ID, e.g. 't-3f9a2c1b', which doesn't change as tasks are added and removed",
+            regex = "INDEX_PATTERN",
+            example = "example_index"
+        )]
+        index: String,
+        #[tool(param)]
+        #[schemars(
+            description = "Weight of the task, e.g. 5 for a task worth five typical ones; 0 leaves it out of progress"
+        )]
+        weight: Option<u32>,
+    ) -> Result<CallToolResult, McpError> {
+        let parsed_index = self.resolve_index(plan_id, &index).await?;
+        let result = Client::set_task_weight(self, plan_id, parsed_index, weight).await;
+        to_mcp_result(result)
     }
 
-    async fn list_plans(&self) -> Result<Vec<models::Lease>, ClientError> {
-        self.core.list_plans().map_err(ClientError::from)
+    #[tool(
+        description = "Set how urgent a task is, from 0 (most) to 9 (least), so the most important work is picked first. Plans listing tasks by priority show the most urgent first. Omit priority to clear it"
+    )]
+    async fn set_task_priority(
+        &self,
+        #[tool(param)]
+        #[schemars(
+            description = "Plan ID, as returned by create_plan or list_plans",
+            range(min = 0, max = 255),
+            example = "example_plan_id"
+        )]
+        plan_id: u8,
+        #[tool(param)]
+        #[schemars(
+            description = "Task index: comma-separated zero-based positions from the root, e.g. '0' for the first top-level task and '0,2' for its third subtask, or the task's ID, e.g. 't-3f9a2c1b', which doesn't change as tasks are added and removed",
+            regex = "INDEX_PATTERN",
+            example = "example_index"
+        )]
+        index: String,
+        #[tool(param)]
+        #[schemars(
+            description = "Priority of the task, 0 being the most urgent",
+            range(min = 0, max = 9)
+        )]
+        priority: Option<u8>,
+    ) -> Result<CallToolResult, McpError> {
+        let parsed_index = self.resolve_index(plan_id, &index).await?;
+        let result = Client::set_task_priority(self, plan_id, parsed_index, priority).await;
+        to_mcp_result(result)
     }
-}
-
-#[tool(tool_box)]
-impl ScatterbrainMcpServer {
-    // Plan Management Tools
 
-    #[tool(description = "Get a plan by ID")]
-    async fn get_plan(&self, #[tool(param)] plan_id: u8) -> Result<CallToolResult, McpError> {
-        let result = Client::get_plan(self, plan_id).await;
+    #[tool(
+        description = "Estimate how many minutes of work a task will take, its subtasks included, before starting on it. get_effort_report compares estimates with the time logged. Omit minutes to clear the estimate"
+    )]
+    async fn set_task_estimate(
+        &self,
+        #[tool(param)]
+        #[schemars(
+            description = "Plan ID, as returned by create_plan or list_plans",
+            range(min = 0, max = 255),
+            example = "example_plan_id"
+        )]
+        plan_id: u8,
+        #[tool(param)]
+        #[schemars(
+            description = "Task index: comma-separated zero-based positions from the root, e.g. '0' for the first top-level task and '0,2' for its third subtask, or the task's ID, e.g. 't-3f9a2c1b', which doesn't change as tasks are added and removed",
+            regex = "INDEX_PATTERN",
+            example = "example_index"
+        )]
+        index: String,
+        #[tool(param)]
+        #[schemars(description = "Estimated minutes of work, e.g. 90")]
+        minutes: Option<u32>,
+    ) -> Result<CallToolResult, McpError> {
+        let parsed_index = self.resolve_index(plan_id, &index).await?;
+        let result = Client::set_task_estimate(self, plan_id, parsed_index, minutes).await;
         to_mcp_result(result)
     }
 
-    #[tool(description = "Create a new plan with required prompt and optional notes")]
-    async fn create_plan(
+    #[tool(
+        description = "Log minutes of work spent on a task itself, e.g. at the end of a work session. Time is added to what was logged before; returns the task's total"
+    )]
+    async fn log_task_time(
         &self,
-        #[tool(param)] prompt: String,
-        #[tool(param)] notes: Option<String>,
+        #[tool(param)]
+        #[schemars(
+            description = "Plan ID, as returned by create_plan or list_plans",
+            range(min = 0, max = 255),
+            example = "example_plan_id"
+        )]
+        plan_id: u8,
+        #[tool(param)]
+        #[schemars(
+            description = "Task index: comma-separated zero-based positions from the root, e.g. '0' for the first top-level task and '0,2' for its third subtask, or the task's ID, e.g. 't-3f9a2c1b', which doesn't change as tasks are added and removed",
+            regex = "INDEX_PATTERN",
+            example = "example_index"
+        )]
+        index: String,
+        #[tool(param)]
+        #[schemars(description = "Minutes of work to add, e.g. 25")]
+        minutes: u32,
     ) -> Result<CallToolResult, McpError> {
-        let result = Client::create_plan(self, prompt, notes).await;
+        let parsed_index = self.resolve_index(plan_id, &index).await?;
+        let result = Client::log_task_time(self, plan_id, parsed_index, minutes).await;
         to_mcp_result(result)
     }
 
-    #[tool(description = "Delete a plan by ID")]
-    async fn delete_plan(&self, #[tool(param)] plan_id: u8) -> Result<CallToolResult, McpError> {
-        let result = Client::delete_plan(self, plan_id).await;
+    #[tool(
+        description = "Generate a lease for a task. The lease only works from this session; with single_use it completes the task once and can't be replayed"
+    )]
+    async fn generate_lease(
+        &self,
+        #[tool(param)]
+        #[schemars(
+            description = "Plan ID, as returned by create_plan or list_plans",
+            range(min = 0, max = 255),
+            example = "example_plan_id"
+        )]
+        plan_id: u8,
+        #[tool(param)]
+        #[schemars(
+            description = "Task index: comma-separated zero-based positions from the root, e.g. '0' for the first top-level task and '0,2' for its third subtask, or the task's ID, e.g. 't-3f9a2c1b', which doesn't change as tasks are added and removed",
+            regex = "INDEX_PATTERN",
+            example = "example_index"
+        )]
+        index: String,
+        #[tool(param)]
+        #[schemars(description = "The lease completes the task once and can't be reused")]
+        single_use: Option<bool>,
+    ) -> Result<CallToolResult, McpError> {
+        let parsed_index = self.resolve_index(plan_id, &index).await?;
+        let result =
+            Client::generate_lease(self, plan_id, parsed_index, single_use.unwrap_or(false)).await;
         to_mcp_result(result)
     }
 
-    #[tool(description = "List all available plans")]
-    async fn list_plans(&self) -> Result<CallToolResult, McpError> {
-        let result = Client::list_plans(self).await;
+    // Notes Management
+
+    #[tool(
+        description = "Export a task and everything below it as JSON, to copy the breakdown into another plan with import_subtree"
+    )]
+    async fn export_subtree(
+        &self,
+        #[tool(param)]
+        #[schemars(
+            description = "Plan ID, as returned by create_plan or list_plans",
+            range(min = 0, max = 255),
+            example = "example_plan_id"
+        )]
+        plan_id: u8,
+        #[tool(param)]
+        #[schemars(
+            description = "Task index: comma-separated zero-based positions from the root, e.g. '0' for the first top-level task and '0,2' for its third subtask, or the task's ID, e.g. 't-3f9a2c1b', which doesn't change as tasks are added and removed",
+            regex = "INDEX_PATTERN",
+            example = "example_index"
+        )]
+        index: String,
+    ) -> Result<CallToolResult, McpError> {
+        let parsed_index = self.resolve_index(plan_id, &index).await?;
+        let result = Client::export_subtree(self, plan_id, parsed_index).await;
         to_mcp_result(result)
     }
 
-    // Navigation Tools
+    #[tool(
+        description = "Add a subtree exported with export_subtree (JSON or YAML) as the last child of parent_index (omit for the root). The whole subtree must satisfy the plan's level rules"
+    )]
+    async fn import_subtree(
+        &self,
+        #[tool(param)]
+        #[schemars(
+            description = "Plan ID, as returned by create_plan or list_plans",
+            range(min = 0, max = 255),
+            example = "example_plan_id"
+        )]
+        plan_id: u8,
+        #[tool(param)]
+        #[schemars(
+            description = "Task index to add the subtree under, e.g. '0,1'; omit to add it at the root",
+            regex = "INDEX_PATTERN",
+            example = "example_index"
+        )]
+        parent_index: Option<String>,
+        #[tool(param)]
+        #[schemars(description = "Subtree as exported by export_subtree, in JSON or YAML")]
+        subtree: String,
+    ) -> Result<CallToolResult, McpError> {
+        let parent = match parent_index {
+            Some(index) => self.resolve_index(plan_id, &index).await?,
+            None => Vec::new(),
+        };
+        let subtree: TaskSpec = serde_yaml::from_str(&subtree)
+            .map_err(|e| McpError::invalid_params(format!("Invalid subtree: {e}"), None))?;
+        let result = Client::import_subtree(self, plan_id, parent, subtree).await;
+        to_mcp_result(result)
+    }
 
-    #[tool(description = "Get the current task for a plan")]
-    async fn get_current(&self, #[tool(param)] plan_id: u8) -> Result<CallToolResult, McpError> {
-        let result = Client::get_current(self, plan_id).await;
+    #[tool(
+        description = "Get a task by index or ID, with its current index. Use it to find where a task you hold the ID of has moved to"
+    )]
+    async fn get_task(
+        &self,
+        #[tool(param)]
+        #[schemars(
+            description = "Plan ID, as returned by create_plan or list_plans",
+            range(min = 0, max = 255),
+            example = "example_plan_id"
+        )]
+        plan_id: u8,
+        #[tool(param)]
+        #[schemars(
+            description = "Task index: comma-separated zero-based positions from the root, e.g. '0' for the first top-level task and '0,2' for its third subtask, or the task's ID, e.g. 't-3f9a2c1b', which doesn't change as tasks are added and removed",
+            regex = "INDEX_PATTERN",
+            example = "example_index"
+        )]
+        index: String,
+    ) -> Result<CallToolResult, McpError> {
+        let task = parse_index(&index)?;
+        let result = Client::get_task(self, plan_id, task).await;
         to_mcp_result(result)
     }
 
-    #[tool(description = "Get distilled context for a plan")]
-    async fn get_distilled_context(
+    #[tool(description = "Get notes for a task")]
+    async fn get_task_notes(
         &self,
-        #[tool(param)] plan_id: u8,
+        #[tool(param)]
+        #[schemars(
+            description = "Plan ID, as returned by create_plan or list_plans",
+            range(min = 0, max = 255),
+            example = "example_plan_id"
+        )]
+        plan_id: u8,
+        #[tool(param)]
+        #[schemars(
+            description = "Task index: comma-separated zero-based positions from the root, e.g. '0' for the first top-level task and '0,2' for its third subtask, or the task's ID, e.g. 't-3f9a2c1b', which doesn't change as tasks are added and removed",
+            regex = "INDEX_PATTERN",
+            example = "example_index"
+        )]
+        index: String,
     ) -> Result<CallToolResult, McpError> {
-        let result = Client::get_distilled_context(self, plan_id).await;
+        let parsed_index = self.resolve_index(plan_id, &index).await?;
+        let result = Client::get_task_notes(self, plan_id, parsed_index).await;
         to_mcp_result(result)
     }
 
-    #[tool(description = "Move to a specific task by index (e.g., '0,1,2')")]
-    async fn move_to(
+    #[tool(
+        description = "Set notes for a task. With append=true, adds a timestamped block after the existing notes instead of replacing them"
+    )]
+    async fn set_task_notes(
         &self,
-        #[tool(param)] plan_id: u8,
-        #[tool(param)] index: String,
+        #[tool(param)]
+        #[schemars(
+            description = "Plan ID, as returned by create_plan or list_plans",
+            range(min = 0, max = 255),
+            example = "example_plan_id"
+        )]
+        plan_id: u8,
+        #[tool(param)]
+        #[schemars(
+            description = "Task index: comma-separated zero-based positions from the root, e.g. '0' for the first top-level task and '0,2' for its third subtask, or the task's ID, e.g. 't-3f9a2c1b', which doesn't change as tasks are added and removed",
+            regex = "INDEX_PATTERN",
+            example = "example_index"
+        )]
+        index: String,
+        #[tool(param)]
+        #[schemars(description = "New notes text")]
+        notes: String,
+        #[tool(param)]
+        #[schemars(
+            description = "Add a timestamped block after the existing notes instead of replacing them"
+        )]
+        append: Option<bool>,
     ) -> Result<CallToolResult, McpError> {
-        let parsed_index = parse_index(&index)?;
-        let result = Client::move_to(self, plan_id, parsed_index).await;
+        let parsed_index = self.resolve_index(plan_id, &index).await?;
+        let result =
+            Client::set_task_notes(self, plan_id, parsed_index, notes, append.unwrap_or(false))
+                .await;
         to_mcp_result(result)
     }
 
-    // Task Operations
+    #[tool(description = "Delete notes for a task")]
+    async fn delete_task_notes(
+        &self,
+        #[tool(param)]
+        #[schemars(
+            description = "Plan ID, as returned by create_plan or list_plans",
+            range(min = 0, max = 255),
+            example = "example_plan_id"
+        )]
+        plan_id: u8,
+        #[tool(param)]
+        #[schemars(
+            description = "Task index: comma-separated zero-based positions from the root, e.g. '0' for the first top-level task and '0,2' for its third subtask, or the task's ID, e.g. 't-3f9a2c1b', which doesn't change as tasks are added and removed",
+            regex = "INDEX_PATTERN",
+            example = "example_index"
+        )]
+        index: String,
+    ) -> Result<CallToolResult, McpError> {
+        let parsed_index = self.resolve_index(plan_id, &index).await?;
+        let result = Client::delete_task_notes(self, plan_id, parsed_index).await;
+        to_mcp_result(result)
+    }
 
-    #[tool(description = "Add a new task to a plan")]
-    async fn add_task(
+    #[tool(
+        description = "Get the structured notes sections (e.g., findings, decisions) for a task, or only the named section"
+    )]
+    async fn get_note_sections(
         &self,
-        #[tool(param)] plan_id: u8,
-        #[tool(param)] description: String,
-        #[tool(param)] level_index: usize,
-        #[tool(param)] notes: Option<String>,
+        #[tool(param)]
+        #[schemars(
+            description = "Plan ID, as returned by create_plan or list_plans",
+            range(min = 0, max = 255),
+            example = "example_plan_id"
+        )]
+        plan_id: u8,
+        #[tool(param)]
+        #[schemars(
+            description = "Task index: comma-separated zero-based positions from the root, e.g. '0' for the first top-level task and '0,2' for its third subtask, or the task's ID, e.g. 't-3f9a2c1b', which doesn't change as tasks are added and removed",
+            regex = "INDEX_PATTERN",
+            example = "example_index"
+        )]
+        index: String,
+        #[tool(param)]
+        #[schemars(description = "Section name, e.g. 'findings'; omit for all sections")]
+        section: Option<String>,
     ) -> Result<CallToolResult, McpError> {
-        let result = Client::add_task(self, plan_id, description, level_index, notes).await;
+        let parsed_index = self.resolve_index(plan_id, &index).await?;
+        let result = Client::get_note_sections(self, plan_id, parsed_index, section).await;
         to_mcp_result(result)
     }
 
-    #[tool(description = "Complete a task by index")]
-    async fn complete_task(
+    #[tool(
+        description = "Set a named notes section for a task (e.g., 'findings', 'decisions', 'open questions'). Omit content to remove the section; append=true adds a timestamped block"
+    )]
+    async fn set_note_section(
         &self,
-        #[tool(param)] plan_id: u8,
-        #[tool(param)] index: String,
-        #[tool(param)] lease: Option<u8>,
-        #[tool(param)] force: Option<bool>,
-        #[tool(param)] summary: Option<String>,
+        #[tool(param)]
+        #[schemars(
+            description = "Plan ID, as returned by create_plan or list_plans",
+            range(min = 0, max = 255),
+            example = "example_plan_id"
+        )]
+        plan_id: u8,
+        #[tool(param)]
+        #[schemars(
+            description = "Task index: comma-separated zero-based positions from the root, e.g. '0' for the first top-level task and '0,2' for its third subtask, or the task's ID, e.g. 't-3f9a2c1b', which doesn't change as tasks are added and removed",
+            regex = "INDEX_PATTERN",
+            example = "example_index"
+        )]
+        index: String,
+        #[tool(param)]
+        #[schemars(description = "Section name, e.g. 'findings', 'decisions' or 'open questions'")]
+        section: String,
+        #[tool(param)]
+        #[schemars(description = "Section text; omit to remove the section")]
+        content: Option<String>,
+        #[tool(param)]
+        #[schemars(
+            description = "Add a timestamped block after the existing section text instead of replacing it"
+        )]
+        append: Option<bool>,
     ) -> Result<CallToolResult, McpError> {
-        let parsed_index = parse_index(&index)?;
-        let result = Client::complete_task(
+        let parsed_index = self.resolve_index(plan_id, &index).await?;
+        let result = Client::set_note_section(
             self,
             plan_id,
             parsed_index,
-            lease,
-            force.unwrap_or(false),
-            summary,
+            section,
+            content,
+            append.unwrap_or(false),
         )
         .await;
         to_mcp_result(result)
     }
 
-    #[tool(description = "Uncomplete a task by index")]
-    async fn uncomplete_task(
+    #[tool(description = "Get the previous versions of a task's notes, oldest first")]
+    async fn get_notes_history(
         &self,
-        #[tool(param)] plan_id: u8,
-        #[tool(param)] index: String,
+        #[tool(param)]
+        #[schemars(
+            description = "Plan ID, as returned by create_plan or list_plans",
+            range(min = 0, max = 255),
+            example = "example_plan_id"
+        )]
+        plan_id: u8,
+        #[tool(param)]
+        #[schemars(
+            description = "Task index: comma-separated zero-based positions from the root, e.g. '0' for the first top-level task and '0,2' for its third subtask, or the task's ID, e.g. 't-3f9a2c1b', which doesn't change as tasks are added and removed",
+            regex = "INDEX_PATTERN",
+            example = "example_index"
+        )]
+        index: String,
     ) -> Result<CallToolResult, McpError> {
-        let parsed_index = parse_index(&index)?;
-        let result = Client::uncomplete_task(self, plan_id, parsed_index).await;
+        let parsed_index = self.resolve_index(plan_id, &index).await?;
+        let result = Client::get_notes_history(self, plan_id, parsed_index).await;
         to_mcp_result(result)
     }
 
-    #[tool(description = "Remove a task by index")]
-    async fn remove_task(
+    #[tool(description = "Restore a task's notes to a previous version from its notes history")]
+    async fn revert_task_notes(
         &self,
-        #[tool(param)] plan_id: u8,
-        #[tool(param)] index: String,
+        #[tool(param)]
+        #[schemars(
+            description = "Plan ID, as returned by create_plan or list_plans",
+            range(min = 0, max = 255),
+            example = "example_plan_id"
+        )]
+        plan_id: u8,
+        #[tool(param)]
+        #[schemars(
+            description = "Task index: comma-separated zero-based positions from the root, e.g. '0' for the first top-level task and '0,2' for its third subtask, or the task's ID, e.g. 't-3f9a2c1b', which doesn't change as tasks are added and removed",
+            regex = "INDEX_PATTERN",
+            example = "example_index"
+        )]
+        index: String,
+        #[tool(param)]
+        #[schemars(description = "Zero-based version from get_notes_history")]
+        version: usize,
     ) -> Result<CallToolResult, McpError> {
-        let parsed_index = parse_index(&index)?;
-        let result = Client::remove_task(self, plan_id, parsed_index).await;
+        let parsed_index = self.resolve_index(plan_id, &index).await?;
+        let result = Client::revert_task_notes(self, plan_id, parsed_index, version).await;
         to_mcp_result(result)
     }
 
-    #[tool(description = "Change the level of a task")]
-    async fn change_level(
-        &self,
-        #[tool(param)] plan_id: u8,
-        #[tool(param)] index: String,
-        #[tool(param)] level_index: usize,
-    ) -> Result<CallToolResult, McpError> {
-        let parsed_index = parse_index(&index)?;
-        let result = Client::change_level(self, plan_id, parsed_index, level_index).await;
-        to_mcp_result(result)
-    }
+    // Open Questions
 
-    #[tool(description = "Generate a lease for a task")]
-    async fn generate_lease(
+    #[tool(description = "Record an open question for a plan that needs an answer later")]
+    async fn add_question(
         &self,
-        #[tool(param)] plan_id: u8,
-        #[tool(param)] index: String,
+        #[tool(param)]
+        #[schemars(
+            description = "Plan ID, as returned by create_plan or list_plans",
+            range(min = 0, max = 255),
+            example = "example_plan_id"
+        )]
+        plan_id: u8,
+        #[tool(param)]
+        #[schemars(description = "The question, phrased so it can be answered later")]
+        question: String,
     ) -> Result<CallToolResult, McpError> {
-        let parsed_index = parse_index(&index)?;
-        let result = Client::generate_lease(self, plan_id, parsed_index).await;
+        let result = Client::add_question(self, plan_id, question).await;
         to_mcp_result(result)
     }
 
-    // Notes Management
-
-    #[tool(description = "Get notes for a task")]
-    async fn get_task_notes(
+    #[tool(description = "List the open and answered questions for a plan")]
+    async fn list_questions(
         &self,
-        #[tool(param)] plan_id: u8,
-        #[tool(param)] index: String,
+        #[tool(param)]
+        #[schemars(
+            description = "Plan ID, as returned by create_plan or list_plans",
+            range(min = 0, max = 255),
+            example = "example_plan_id"
+        )]
+        plan_id: u8,
     ) -> Result<CallToolResult, McpError> {
-        let parsed_index = parse_index(&index)?;
-        let result = Client::get_task_notes(self, plan_id, parsed_index).await;
+        let result = Client::list_questions(self, plan_id).await;
         to_mcp_result(result)
     }
 
-    #[tool(description = "Set notes for a task")]
-    async fn set_task_notes(
+    #[tool(description = "Answer a tracked question by its id, marking it answered")]
+    async fn answer_question(
         &self,
-        #[tool(param)] plan_id: u8,
-        #[tool(param)] index: String,
-        #[tool(param)] notes: String,
+        #[tool(param)]
+        #[schemars(
+            description = "Plan ID, as returned by create_plan or list_plans",
+            range(min = 0, max = 255),
+            example = "example_plan_id"
+        )]
+        plan_id: u8,
+        #[tool(param)]
+        #[schemars(description = "Question id from list_questions")]
+        question_id: usize,
+        #[tool(param)]
+        #[schemars(description = "The answer")]
+        answer: String,
     ) -> Result<CallToolResult, McpError> {
-        let parsed_index = parse_index(&index)?;
-        let result = Client::set_task_notes(self, plan_id, parsed_index, notes).await;
+        let result = Client::answer_question(self, plan_id, question_id, answer).await;
         to_mcp_result(result)
     }
 
-    #[tool(description = "Delete notes for a task")]
-    async fn delete_task_notes(
+    #[tool(description = "Remove a tracked question by its id")]
+    async fn remove_question(
         &self,
-        #[tool(param)] plan_id: u8,
-        #[tool(param)] index: String,
+        #[tool(param)]
+        #[schemars(
+            description = "Plan ID, as returned by create_plan or list_plans",
+            range(min = 0, max = 255),
+            example = "example_plan_id"
+        )]
+        plan_id: u8,
+        #[tool(param)]
+        #[schemars(description = "Question id from list_questions")]
+        question_id: usize,
     ) -> Result<CallToolResult, McpError> {
-        let parsed_index = parse_index(&index)?;
-        let result = Client::delete_task_notes(self, plan_id, parsed_index).await;
+        let result = Client::remove_question(self, plan_id, question_id).await;
         to_mcp_result(result)
     }
 
     #[tool(description = "Get comprehensive guide on using Scatterbrain through MCP")]
     async fn get_guide(&self) -> Result<CallToolResult, McpError> {
-        let guide_content = crate::guide::get_guide_string(crate::guide::GuideMode::Mcp);
+        let guide_content = crate::guide::load_guide_string(crate::guide::GuideMode::Mcp)
+            .map_err(|e| McpError::internal_error(e.to_string(), None))?;
         Ok(CallToolResult::success(vec![Content::text(guide_content)]))
     }
 }
 
 // Implement ServerHandler for the MCP server
-#[tool(tool_box)]
 impl rmcp::ServerHandler for ScatterbrainMcpServer {
+    async fn list_tools(
+        &self,
+        _: PaginatedRequestParam,
+        _: rmcp::service::RequestContext<rmcp::RoleServer>,
+    ) -> Result<ListToolsResult, McpError> {
+        let mut tools = Self::tool_box().list();
+        for (_, tool) in self.plugin_tools()? {
+            tools.push(tool);
+        }
+        Ok(ListToolsResult {
+            next_cursor: None,
+            tools,
+        })
+    }
+
+    /// Runs a tool with the calling MCP client recorded as the actor of the changes it makes.
+    ///
+    /// A tool the client cancelled before it started isn't run, and one still awaiting
+    /// something (a plugin, say) when the cancellation arrives is dropped at that point.
+    /// Changes a tool already made stay made.
+    async fn call_tool(
+        &self,
+        request: CallToolRequestParam,
+        context: rmcp::service::RequestContext<rmcp::RoleServer>,
+    ) -> Result<CallToolResult, McpError> {
+        let cancelled = context.ct.clone();
+        if cancelled.is_cancelled() {
+            return Err(request_cancelled());
+        }
+        tokio::select! {
+            biased;
+            _ = cancelled.cancelled() => Err(request_cancelled()),
+            result = self.run_tool(request, context) => result,
+        }
+    }
+
     fn get_info(&self) -> ServerInfo {
         ServerInfo {
             protocol_version: ProtocolVersion::default(),
@@ -428,8 +2667,15 @@ impl rmcp::ServerHandler for ScatterbrainMcpServer {
             instructions: Some(
                 "Scatterbrain MCP Server - Hierarchical planning and task management through MCP.\n\
                  Provides tools for plan management, task operations, navigation, and notes management.\n\
-                 Use plan_id to specify which plan to work with, and index format like '0,1,2' for task navigation.\n\
-                 Start with the `get_guide()` tool to get started."
+                 Start with the `get_guide()` tool to get started.\n\
+                 \n\
+                 Parameter formats (each tool's input schema has descriptions and examples):\n\
+                 - plan_id: a number from 0 to 255, as returned by create_plan or list_plans, e.g. 42\n\
+                 - index: a task's zero-based position from the root, comma-separated without spaces or brackets. \
+                 '0' is the first top-level task, '0,2' its third subtask. Pass it as a string, not an array. \
+                 A task's ID, e.g. 't-3f9a2c1b', works anywhere an index does and stays the same when tasks before it are removed\n\
+                 - level_index: a zero-based level number, 0 being the most abstract level, e.g. 1\n\
+                 - times: RFC 3339, e.g. '2025-01-31T09:00:00Z'"
                     .into(),
             ),
         }
@@ -577,6 +2823,69 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_task_errors_carry_structured_data() {
+        let error = to_mcp_result::<()>(Err(ClientError::Task(TaskError::LeaseMismatch {
+            index: vec![0, 1],
+            provided: Some(3),
+            required: 7,
+        })))
+        .unwrap_err();
+        assert_eq!(error.code, rmcp::model::ErrorCode::INVALID_REQUEST);
+        assert_eq!(
+            error.data,
+            Some(json!({
+                "task_error": {
+                    "LeaseMismatch": { "index": [0, 1], "provided": 3, "required": 7 }
+                }
+            }))
+        );
+
+        let error = to_mcp_result::<()>(Err(ClientError::Task(TaskError::SummaryRequired {
+            index: vec![2],
+        })))
+        .unwrap_err();
+        assert_eq!(error.code, rmcp::model::ErrorCode::INVALID_PARAMS);
+        assert_eq!(
+            error.data,
+            Some(json!({ "task_error": { "SummaryRequired": { "index": [2] } } }))
+        );
+    }
+
+    #[test]
+    fn test_client_errors_map_to_mcp_codes() {
+        use rmcp::model::ErrorCode;
+
+        let error =
+            to_mcp_result::<()>(Err(ClientError::PlanNotFound(models::Lease::new(7)))).unwrap_err();
+        assert_eq!(error.code, ErrorCode::RESOURCE_NOT_FOUND);
+        assert_eq!(error.data, Some(json!({ "plan_id": 7 })));
+
+        let error = to_mcp_result::<()>(Err(PlanError::TemplateNotFound("bug".to_string()).into()))
+            .unwrap_err();
+        assert_eq!(error.code, ErrorCode::INVALID_PARAMS);
+
+        let error = parse_index("[0,1]").unwrap_err();
+        assert_eq!(error.code, ErrorCode::INVALID_PARAMS);
+        assert_eq!(error.data.unwrap()["index"], json!("[0,1]"));
+
+        let http = |status| {
+            to_mcp_result::<()>(Err(ClientError::Http {
+                status,
+                message: "failed".to_string(),
+            }))
+            .unwrap_err()
+            .code
+        };
+        assert_eq!(http(StatusCode::NOT_FOUND), ErrorCode::RESOURCE_NOT_FOUND);
+        assert_eq!(http(StatusCode::CONFLICT), ErrorCode::INVALID_REQUEST);
+        assert_eq!(http(StatusCode::BAD_REQUEST), ErrorCode::INVALID_PARAMS);
+        assert_eq!(http(StatusCode::BAD_GATEWAY), ErrorCode::INTERNAL_ERROR);
+
+        let error = to_mcp_result::<()>(Err(PlanError::LockError.into())).unwrap_err();
+        assert_eq!(error.code, ErrorCode::INTERNAL_ERROR);
+    }
+
     #[tokio::test]
     async fn test_actual_tool_call_simulation() {
         // This test is commented out due to compilation issues
@@ -585,6 +2894,30 @@ mod tests {
         println!("Tool call simulation test skipped - needs proper MCP setup");
     }
 
+    #[test]
+    fn test_parameter_schemas() {
+        let tool_attr = ScatterbrainMcpServer::complete_task_tool_attr();
+        let properties = tool_attr.input_schema["properties"].as_object().unwrap();
+        assert_eq!(properties["index"]["pattern"], json!(INDEX_PATTERN));
+        assert_eq!(properties["index"]["examples"], json!(["0,2"]));
+        assert_eq!(properties["plan_id"]["maximum"], json!(255.0));
+        assert_eq!(properties["plan_id"]["examples"], json!([42]));
+
+        // Every parameter of every tool tells the client what to send
+        for tool in ScatterbrainMcpServer::tool_box().list() {
+            let Some(properties) = tool.input_schema.get("properties") else {
+                continue;
+            };
+            for (name, schema) in properties.as_object().unwrap() {
+                assert!(
+                    schema.get("description").is_some(),
+                    "{}.{name} has no description",
+                    tool.name
+                );
+            }
+        }
+    }
+
     #[test]
     fn test_schema_generation() {
         // Test the schema generation for our create_plan function
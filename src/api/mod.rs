@@ -4,10 +4,20 @@
 //! including the server, client, and data models.
 
 pub mod client;
+pub mod config;
+pub mod hooks;
 pub mod mcp;
+pub mod plugins;
+pub mod rpc;
+pub mod schema;
 pub mod server;
+pub mod services;
+pub mod storage;
+pub mod webhook;
 
 // Re-export commonly used types
 pub use client::{Client, ClientConfig, ClientError, HttpClientImpl};
+pub use config::{PlanDefaults, ServerConfig, WorkspaceConfig};
 pub use mcp::ScatterbrainMcpServer;
-pub use server::{serve, ServerConfig};
+pub use server::serve;
+pub use services::{run_services, ServiceOptions};
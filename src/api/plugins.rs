@@ -0,0 +1,107 @@
+//! Plugins
+//!
+//! Programs embedding scatterbrain can extend the MCP server and the HTTP API without
+//! forking the crate: a [`Plugin`] registered with [`Core::register_plugin`] adds MCP
+//! tools and HTTP routes that work on the same [`Core`] as the built-in ones.
+
+use async_trait::async_trait;
+use axum::Router;
+use rmcp::model::{CallToolResult, JsonObject, Tool};
+use rmcp::Error as McpError;
+
+use crate::Core;
+
+/// A set of custom MCP tools and HTTP routes
+///
+/// ```
+/// # use scatterbrain::api::plugins::Plugin;
+/// # use scatterbrain::Core;
+/// use async_trait::async_trait;
+/// use axum::{extract::State, routing::get, Router};
+/// use rmcp::model::{CallToolResult, Content, JsonObject, Tool};
+/// use std::sync::Arc;
+///
+/// struct PlanCount;
+///
+/// #[async_trait]
+/// impl Plugin for PlanCount {
+///     fn name(&self) -> &str {
+///         "plan-count"
+///     }
+///
+///     fn tools(&self) -> Vec<Tool> {
+///         let schema = serde_json::json!({"type": "object", "properties": {}});
+///         vec![Tool::new("count_plans", "Count the plans", schema.as_object().unwrap().clone())]
+///     }
+///
+///     async fn call_tool(
+///         &self,
+///         core: &Core,
+///         _name: &str,
+///         _arguments: JsonObject,
+///     ) -> Result<CallToolResult, rmcp::Error> {
+///         let count = core.list_plans().map_or(0, |plans| plans.len());
+///         Ok(CallToolResult::success(vec![Content::text(count.to_string())]))
+///     }
+///
+///     fn routes(&self) -> Router<Core> {
+///         // Served at /api/plugins/plan-count/count
+///         Router::new().route(
+///             "/count",
+///             get(|State(core): State<Core>| async move {
+///                 core.list_plans().map_or(0, |plans| plans.len()).to_string()
+///             }),
+///         )
+///     }
+/// }
+///
+/// let core = Core::new();
+/// core.register_plugin(Arc::new(PlanCount)).unwrap();
+/// assert!(core.register_plugin(Arc::new(PlanCount)).is_err());
+/// ```
+#[async_trait]
+pub trait Plugin: Send + Sync {
+    /// Name of the plugin, made of lowercase letters, digits, `-` and `_`. Its routes
+    /// are served under `/api/plugins/<name>`.
+    fn name(&self) -> &str;
+
+    /// MCP tools the plugin adds. Tools named like a built-in tool, or like a tool of a
+    /// plugin registered earlier, are left out.
+    fn tools(&self) -> Vec<Tool> {
+        Vec::new()
+    }
+
+    /// Runs the tool called `name`, one of those returned by [`Plugin::tools`]
+    async fn call_tool(
+        &self,
+        core: &Core,
+        name: &str,
+        arguments: JsonObject,
+    ) -> Result<CallToolResult, McpError> {
+        let _ = (core, arguments);
+        Err(McpError::invalid_params(
+            format!("plugin '{}' has no tool '{name}'", self.name()),
+            None,
+        ))
+    }
+
+    /// HTTP routes the plugin adds, relative to `/api/plugins/<name>`. They are read when
+    /// the server's router is built, so plugins must be registered before serving.
+    /// Like every `/api` route, they require the auth token when one is set.
+    fn routes(&self) -> Router<Core> {
+        Router::new()
+    }
+}
+
+/// Checks that a plugin name can be used as a path segment
+pub(crate) fn check_name(name: &str) -> Result<(), String> {
+    let valid = name
+        .chars()
+        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_');
+    if name.is_empty() || !valid {
+        return Err(format!(
+            "plugin name '{name}' must be lowercase letters, digits, '-' or '_'"
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,605 @@
+//! JSON-RPC over stdio
+//!
+//! This module serves the operations of the [`Client`] trait as JSON-RPC 2.0 methods, one
+//! request per line on the input and one response per line on the output. Editors and
+//! scripts can drive scatterbrain this way without speaking HTTP or doing the MCP
+//! handshake.
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt};
+
+use super::client::{Client, ClientError};
+use crate::models::{Index, TaskRef, DEFAULT_SUMMARY_LEVEL};
+
+/// Standard JSON-RPC error codes
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+/// Server-defined codes for scatterbrain failures
+const SERVER_ERROR: i64 = -32000;
+const PLAN_NOT_FOUND: i64 = -32001;
+const TASK_REJECTED: i64 = -32002;
+
+/// A JSON-RPC request. Requests without an `id` are notifications and get no response.
+#[derive(Debug, Deserialize)]
+struct Request {
+    jsonrpc: String,
+    #[serde(default)]
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Option<Value>,
+}
+
+#[derive(Debug, Serialize)]
+struct Response {
+    jsonrpc: &'static str,
+    id: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<RpcError>,
+}
+
+/// The `error` member of a failed response
+#[derive(Debug, Serialize)]
+struct RpcError {
+    code: i64,
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data: Option<Value>,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            data: None,
+        }
+    }
+}
+
+impl From<ClientError> for RpcError {
+    fn from(error: ClientError) -> Self {
+        match error {
+            ClientError::PlanNotFound(_) => Self::new(PLAN_NOT_FOUND, error.to_string()),
+            ClientError::Task(e) => Self {
+                code: TASK_REJECTED,
+                message: e.to_string(),
+                data: serde_json::to_value(&e)
+                    .ok()
+                    .map(|task_error| json!({ "task_error": task_error })),
+            },
+            error => Self::new(SERVER_ERROR, error.to_string()),
+        }
+    }
+}
+
+/// Serves JSON-RPC requests read from `input` until it ends, writing responses to `output`
+///
+/// ```
+/// # use scatterbrain::api::{rpc, ScatterbrainMcpServer};
+/// # use scatterbrain::Core;
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let client = ScatterbrainMcpServer::new(Core::new());
+/// let input = br#"{"jsonrpc": "2.0", "id": 1, "method": "create_plan", "params": {"prompt": "Ship it"}}"#;
+/// let mut output = Vec::new();
+/// rpc::serve_rpc(&client, &input[..], &mut output).await.unwrap();
+/// let response: serde_json::Value = serde_json::from_slice(&output).unwrap();
+/// assert!(response["result"].is_u64());
+/// # });
+/// ```
+pub async fn serve_rpc<C, R, W>(client: &C, input: R, mut output: W) -> std::io::Result<()>
+where
+    C: Client + Sync,
+    R: AsyncBufRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let mut lines = input.lines();
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+        if let Some(response) = handle_line(client, &line).await {
+            let mut encoded = serde_json::to_vec(&response)?;
+            encoded.push(b'\n');
+            output.write_all(&encoded).await?;
+            output.flush().await?;
+        }
+    }
+    Ok(())
+}
+
+/// Answers one line of input, or returns `None` for a notification
+async fn handle_line<C: Client + Sync>(client: &C, line: &str) -> Option<Response> {
+    let value: Value = match serde_json::from_str(line) {
+        Ok(value) => value,
+        Err(e) => {
+            return Some(failure(
+                Value::Null,
+                RpcError::new(PARSE_ERROR, e.to_string()),
+            ))
+        }
+    };
+    let id = value.get("id").cloned().unwrap_or(Value::Null);
+    let request = match serde_json::from_value::<Request>(value) {
+        Ok(request) if request.jsonrpc == "2.0" => request,
+        Ok(_) => {
+            let error = RpcError::new(INVALID_REQUEST, "jsonrpc must be \"2.0\"");
+            return Some(failure(id, error));
+        }
+        Err(e) => return Some(failure(id, RpcError::new(INVALID_REQUEST, e.to_string()))),
+    };
+
+    let result = dispatch(client, &request.method, request.params).await;
+    let id = request.id?;
+    Some(match result {
+        Ok(result) => Response {
+            jsonrpc: "2.0",
+            id,
+            result: Some(result),
+            error: None,
+        },
+        Err(error) => failure(id, error),
+    })
+}
+
+fn failure(id: Value, error: RpcError) -> Response {
+    Response {
+        jsonrpc: "2.0",
+        id,
+        result: None,
+        error: Some(error),
+    }
+}
+
+/// Named parameters of a request
+struct Params(Map<String, Value>);
+
+impl Params {
+    fn parse(params: Option<Value>) -> Result<Self, RpcError> {
+        match params {
+            None | Some(Value::Null) => Ok(Self(Map::new())),
+            Some(Value::Object(map)) => Ok(Self(map)),
+            Some(_) => Err(RpcError::new(
+                INVALID_PARAMS,
+                "params must be an object of named parameters",
+            )),
+        }
+    }
+
+    /// A required parameter
+    fn req<T: DeserializeOwned>(&self, name: &str) -> Result<T, RpcError> {
+        let value = self
+            .0
+            .get(name)
+            .ok_or_else(|| RpcError::new(INVALID_PARAMS, format!("missing parameter '{name}'")))?;
+        serde_json::from_value(value.clone())
+            .map_err(|e| RpcError::new(INVALID_PARAMS, format!("invalid parameter '{name}': {e}")))
+    }
+
+    /// An optional parameter, defaulted when missing or null
+    fn opt<T: DeserializeOwned + Default>(&self, name: &str) -> Result<T, RpcError> {
+        match self.0.get(name) {
+            None | Some(Value::Null) => Ok(T::default()),
+            Some(_) => self.req(name),
+        }
+    }
+
+    /// A required task parameter, given as an index or an ID, resolved to the task's
+    /// current index in plan `id`
+    async fn task<C: Client + Sync>(&self, client: &C, name: &str) -> Result<Index, RpcError> {
+        let task: TaskRef = self.req(name)?;
+        Ok(client.resolve_task(self.req("id")?, task).await?)
+    }
+
+    /// An optional task parameter, see [`Params::task`]
+    async fn opt_task<C: Client + Sync>(
+        &self,
+        client: &C,
+        name: &str,
+    ) -> Result<Option<Index>, RpcError> {
+        match self.opt::<Option<TaskRef>>(name)? {
+            Some(task) => Ok(Some(client.resolve_task(self.req("id")?, task).await?)),
+            None => Ok(None),
+        }
+    }
+}
+
+fn reply<T: Serialize>(result: Result<T, ClientError>) -> Result<Value, RpcError> {
+    let value = result?;
+    serde_json::to_value(value).map_err(|e| RpcError::new(SERVER_ERROR, e.to_string()))
+}
+
+/// Calls the [`Client`] method named `method`, taking its arguments from `params` by name
+async fn dispatch<C: Client + Sync>(
+    client: &C,
+    method: &str,
+    params: Option<Value>,
+) -> Result<Value, RpcError> {
+    let p = Params::parse(params)?;
+    match method {
+        "get_plan" => reply(client.get_plan(p.req("id")?).await),
+        "get_plan_at" => reply(client.get_plan_at(p.req("id")?, p.req("at")?).await),
+        "get_current" => reply(client.get_current(p.req("id")?).await),
+        "get_progress" => reply(client.get_progress(p.req("id")?).await),
+        "get_plan_stats" => reply(client.get_plan_stats(p.req("id")?).await),
+        "get_effort_report" => reply(client.get_effort_report(p.req("id")?).await),
+        "get_history" => reply(client.get_history(p.req("id")?).await),
+        "lint_plan" => reply(client.lint_plan(p.req("id")?).await),
+        "get_distilled_context" => reply(client.get_distilled_context(p.req("id")?).await),
+        "add_task" => reply(
+            client
+                .add_task(
+                    p.req("id")?,
+                    p.req("description")?,
+                    p.req("level_index")?,
+                    p.opt("notes")?,
+                )
+                .await,
+        ),
+        "add_task_from_template" => reply(
+            client
+                .add_task_from_template(p.req("id")?, p.req("template")?, p.opt("params")?)
+                .await,
+        ),
+        "complete_task" => reply(
+            client
+                .complete_task(
+                    p.req("id")?,
+                    p.task(client, "index").await?,
+                    p.opt("lease")?,
+                    p.opt("force")?,
+                    p.opt("cascade")?,
+                    p.opt("summary")?,
+                )
+                .await,
+        ),
+        "complete_current_task" => reply(
+            client
+                .complete_current_task(
+                    p.req("id")?,
+                    p.opt("lease")?,
+                    p.opt("force")?,
+                    p.opt("cascade")?,
+                    p.opt("summary")?,
+                )
+                .await,
+        ),
+        "move_to" => reply(
+            client
+                .move_to(p.req("id")?, p.task(client, "index").await?)
+                .await,
+        ),
+        "change_level" => reply(
+            client
+                .change_level(
+                    p.req("id")?,
+                    p.task(client, "index").await?,
+                    p.req("level_index")?,
+                )
+                .await,
+        ),
+        "set_task_weight" => reply(
+            client
+                .set_task_weight(
+                    p.req("id")?,
+                    p.task(client, "index").await?,
+                    p.opt("weight")?,
+                )
+                .await,
+        ),
+        "tag_task" => reply(
+            client
+                .tag_task(p.req("id")?, p.task(client, "index").await?, p.req("tags")?)
+                .await,
+        ),
+        "untag_task" => reply(
+            client
+                .untag_task(p.req("id")?, p.task(client, "index").await?, p.req("tags")?)
+                .await,
+        ),
+        "set_task_priority" => reply(
+            client
+                .set_task_priority(
+                    p.req("id")?,
+                    p.task(client, "index").await?,
+                    p.opt("priority")?,
+                )
+                .await,
+        ),
+        "set_task_estimate" => reply(
+            client
+                .set_task_estimate(
+                    p.req("id")?,
+                    p.task(client, "index").await?,
+                    p.opt("minutes")?,
+                )
+                .await,
+        ),
+        "log_task_time" => reply(
+            client
+                .log_task_time(
+                    p.req("id")?,
+                    p.task(client, "index").await?,
+                    p.req("minutes")?,
+                )
+                .await,
+        ),
+        "generate_lease" => reply(
+            client
+                .generate_lease(
+                    p.req("id")?,
+                    p.task(client, "index").await?,
+                    p.opt("single_use")?,
+                )
+                .await,
+        ),
+        "get_task" => reply(client.get_task(p.req("id")?, p.req("index")?).await),
+        "remove_task" => reply(
+            client
+                .remove_task(p.req("id")?, p.task(client, "index").await?)
+                .await,
+        ),
+        "update_task" => reply(
+            client
+                .update_task_description(
+                    p.req("id")?,
+                    p.task(client, "index").await?,
+                    p.req("description")?,
+                )
+                .await,
+        ),
+        "move_task" => reply(
+            client
+                .move_task(
+                    p.req("id")?,
+                    p.task(client, "index").await?,
+                    p.opt_task(client, "parent").await?.unwrap_or_default(),
+                )
+                .await,
+        ),
+        "add_dependency" => reply(
+            client
+                .add_dependency(
+                    p.req("id")?,
+                    p.task(client, "index").await?,
+                    p.task(client, "on").await?,
+                )
+                .await,
+        ),
+        "remove_dependency" => reply(
+            client
+                .remove_dependency(
+                    p.req("id")?,
+                    p.task(client, "index").await?,
+                    p.task(client, "on").await?,
+                )
+                .await,
+        ),
+        "export_subtree" => reply(
+            client
+                .export_subtree(p.req("id")?, p.task(client, "index").await?)
+                .await,
+        ),
+        "import_subtree" => reply(
+            client
+                .import_subtree(
+                    p.req("id")?,
+                    p.opt_task(client, "parent").await?.unwrap_or_default(),
+                    p.req("subtree")?,
+                )
+                .await,
+        ),
+        "get_task_notes" => reply(
+            client
+                .get_task_notes(p.req("id")?, p.task(client, "index").await?)
+                .await,
+        ),
+        "set_task_notes" => reply(
+            client
+                .set_task_notes(
+                    p.req("id")?,
+                    p.task(client, "index").await?,
+                    p.req("notes")?,
+                    p.opt("append")?,
+                )
+                .await,
+        ),
+        "delete_task_notes" => reply(
+            client
+                .delete_task_notes(p.req("id")?, p.task(client, "index").await?)
+                .await,
+        ),
+        "get_note_sections" => reply(
+            client
+                .get_note_sections(
+                    p.req("id")?,
+                    p.task(client, "index").await?,
+                    p.opt("section")?,
+                )
+                .await,
+        ),
+        "set_note_section" => reply(
+            client
+                .set_note_section(
+                    p.req("id")?,
+                    p.task(client, "index").await?,
+                    p.req("section")?,
+                    p.opt("content")?,
+                    p.opt("append")?,
+                )
+                .await,
+        ),
+        "get_notes_history" => reply(
+            client
+                .get_notes_history(p.req("id")?, p.task(client, "index").await?)
+                .await,
+        ),
+        "revert_task_notes" => reply(
+            client
+                .revert_task_notes(
+                    p.req("id")?,
+                    p.task(client, "index").await?,
+                    p.req("version")?,
+                )
+                .await,
+        ),
+        "start_task" => reply(
+            client
+                .start_task(p.req("id")?, p.task(client, "index").await?)
+                .await,
+        ),
+        "uncomplete_task" => reply(
+            client
+                .uncomplete_task(p.req("id")?, p.task(client, "index").await?)
+                .await,
+        ),
+        "request_review" => reply(
+            client
+                .request_review(
+                    p.req("id")?,
+                    p.task(client, "index").await?,
+                    p.req("summary")?,
+                )
+                .await,
+        ),
+        "approve_task" => reply(
+            client
+                .approve_task(p.req("id")?, p.task(client, "index").await?)
+                .await,
+        ),
+        "get_review_queue" => reply(client.get_review_queue(p.req("id")?).await),
+        "get_changelog" => reply(
+            client
+                .get_changelog(p.req("id")?, p.opt("from")?, p.opt("to")?)
+                .await,
+        ),
+        "get_summaries" => {
+            let level: Option<usize> = p.opt("level")?;
+            let level = level.unwrap_or(DEFAULT_SUMMARY_LEVEL);
+            reply(client.get_summaries(p.req("id")?, level).await)
+        }
+        "list_tasks" => reply(client.list_tasks(p.req("id")?, p.opt("filter")?).await),
+        "search_tasks" => reply(client.search_tasks(p.req("id")?, p.req("query")?).await),
+        "reject_task" => reply(
+            client
+                .reject_task(
+                    p.req("id")?,
+                    p.task(client, "index").await?,
+                    p.opt("reason")?,
+                )
+                .await,
+        ),
+        "set_plan_status" => reply(
+            client
+                .set_plan_status(p.req("id")?, p.req("status")?, p.opt("force")?)
+                .await,
+        ),
+        "set_plan_locked" => reply(client.set_plan_locked(p.req("id")?, p.req("locked")?).await),
+        "set_task_order" => reply(client.set_task_order(p.req("id")?, p.req("order")?).await),
+        "set_tombstones" => reply(client.set_tombstones(p.req("id")?, p.req("enabled")?).await),
+        "share_plan" => reply(client.share_plan(p.req("id")?).await),
+        "unshare_plan" => reply(client.unshare_plan(p.req("id")?).await),
+        "update_plan" => reply(
+            client
+                .update_plan_metadata(p.req("id")?, p.opt("goal")?, p.opt("notes")?)
+                .await,
+        ),
+        "set_usage_summary" => reply(
+            client
+                .set_usage_summary(p.req("id")?, p.opt("usage_summary")?)
+                .await,
+        ),
+        "set_focus" => reply(
+            client
+                .set_focus(
+                    p.req("id")?,
+                    p.opt_task(client, "index").await?,
+                    p.opt("strict")?,
+                )
+                .await,
+        ),
+        "start_session" => reply(
+            client
+                .start_session(p.req("id")?, p.opt("reminder_minutes")?)
+                .await,
+        ),
+        "stop_session" => reply(client.stop_session(p.req("id")?).await),
+        "set_plan_badge" => reply(client.set_plan_badge(p.req("id")?, p.req("badge")?).await),
+        "set_completion_policy" => reply(
+            client
+                .set_completion_policy(p.req("id")?, p.req("policy")?)
+                .await,
+        ),
+        "add_question" => reply(client.add_question(p.req("id")?, p.req("question")?).await),
+        "list_questions" => reply(client.list_questions(p.req("id")?).await),
+        "answer_question" => reply(
+            client
+                .answer_question(p.req("id")?, p.req("question_id")?, p.req("answer")?)
+                .await,
+        ),
+        "remove_question" => reply(
+            client
+                .remove_question(p.req("id")?, p.req("question_id")?)
+                .await,
+        ),
+        "create_plan" => reply(client.create_plan(p.req("prompt")?, p.opt("notes")?).await),
+        "create_plan_from_spec" => reply(client.create_plan_from_spec(p.req("spec")?).await),
+        "delete_plan" => reply(client.delete_plan(p.req("id")?).await),
+        "list_plans" => reply(client.list_plans().await),
+        "list_plan_summaries" => reply(client.list_plan_summaries().await),
+        "list_templates" => reply(client.list_templates().await),
+        "save_template" => reply(client.save_template(p.req("template")?).await),
+        "remove_template" => reply(client.remove_template(p.req("name")?).await),
+        method => Err(RpcError::new(
+            METHOD_NOT_FOUND,
+            format!("unknown method '{method}'"),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::ScatterbrainMcpServer;
+    use crate::Core;
+
+    #[tokio::test]
+    async fn test_serve_rpc() {
+        let client = ScatterbrainMcpServer::new(Core::new());
+        let input = [
+            r#"{"jsonrpc": "2.0", "id": 1, "method": "create_plan", "params": {"prompt": "RPC"}}"#,
+            r#"{"jsonrpc": "2.0", "method": "list_plans"}"#,
+            r#"{"jsonrpc": "2.0", "id": "a", "method": "complete_task", "params": {"id": 99, "index": [0]}}"#,
+            r#"{"jsonrpc": "2.0", "id": 3, "method": "add_task", "params": {"id": 1}}"#,
+            r#"{"jsonrpc": "2.0", "id": 4, "method": "fly"}"#,
+            "not json",
+        ]
+        .join("\n");
+        let mut output = Vec::new();
+        serve_rpc(&client, input.as_bytes(), &mut output)
+            .await
+            .unwrap();
+
+        // The notification gets no response; everything else gets one, in order
+        let responses: Vec<Value> = output
+            .split(|b| *b == b'\n')
+            .filter(|line| !line.is_empty())
+            .map(|line| serde_json::from_slice(line).unwrap())
+            .collect();
+        assert_eq!(responses.len(), 5);
+        assert_eq!(responses[0]["id"], 1);
+        assert!(responses[0]["result"].is_u64());
+        assert_eq!(responses[1]["id"], "a");
+        assert_eq!(responses[1]["error"]["code"], PLAN_NOT_FOUND);
+        assert_eq!(responses[2]["error"]["code"], INVALID_PARAMS);
+        assert_eq!(responses[3]["error"]["code"], METHOD_NOT_FOUND);
+        assert_eq!(responses[4]["error"]["code"], PARSE_ERROR);
+        assert_eq!(responses[4]["id"], Value::Null);
+    }
+}
@@ -0,0 +1,136 @@
+//! Versioned schema for persisted plans and exports
+//!
+//! Everything scatterbrain writes out (the storage `plans.json`, admin export archives
+//! and backups) carries a `version` field. Readers detect the version, run the
+//! migrations in order up to [`SCHEMA_VERSION`], and only then deserialize into the
+//! current types, so a change to `Task` or `Plan` ships with a migration instead of
+//! turning old files into deserialization failures.
+//!
+//! To change the schema: bump [`SCHEMA_VERSION`], append a migration from the previous
+//! version to `MIGRATIONS`, and add a test with a document in the old shape.
+
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+
+/// Version of the documents this build writes
+pub const SCHEMA_VERSION: u32 = 2;
+
+/// Errors that can occur while reading a versioned document
+#[derive(Debug, thiserror::Error)]
+pub enum SchemaError {
+    #[error(
+        "Unsupported schema version {found} (this build reads versions 1 to {SCHEMA_VERSION})"
+    )]
+    UnsupportedVersion { found: u64 },
+
+    #[error("Failed to migrate from schema version {from}: {reason}")]
+    Migration { from: u32, reason: String },
+
+    #[error("Invalid document: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+impl From<SchemaError> for std::io::Error {
+    fn from(error: SchemaError) -> Self {
+        std::io::Error::new(std::io::ErrorKind::InvalidData, error)
+    }
+}
+
+/// Upgrades a document by one version
+type Migration = fn(Value) -> Result<Value, String>;
+
+/// Migrations in order: `MIGRATIONS[n]` upgrades a document from version `n + 1`.
+const MIGRATIONS: [Migration; SCHEMA_VERSION as usize - 1] = [v1_to_v2];
+
+/// Returns the schema version of a document. Documents from before versioning was
+/// introduced have no `version` field and count as version 1.
+pub fn version_of(document: &Value) -> Result<u32, SchemaError> {
+    match document.get("version") {
+        None => Ok(1),
+        Some(version) => {
+            let found = version.as_u64().unwrap_or(0);
+            match u32::try_from(found) {
+                Ok(version) if (1..=SCHEMA_VERSION).contains(&version) => Ok(version),
+                _ => Err(SchemaError::UnsupportedVersion { found }),
+            }
+        }
+    }
+}
+
+/// Migrates a document of any supported version to [`SCHEMA_VERSION`].
+pub fn migrate(mut document: Value) -> Result<Value, SchemaError> {
+    let mut version = version_of(&document)?;
+    while version < SCHEMA_VERSION {
+        let migration = MIGRATIONS[version as usize - 1];
+        document = migration(document).map_err(|reason| SchemaError::Migration {
+            from: version,
+            reason,
+        })?;
+        version += 1;
+        if let Value::Object(map) = &mut document {
+            map.insert("version".to_string(), json!(version));
+        }
+    }
+    Ok(document)
+}
+
+/// Migrates a document to the current version and deserializes it.
+pub fn from_value<T: DeserializeOwned>(document: Value) -> Result<T, SchemaError> {
+    Ok(serde_json::from_value(migrate(document)?)?)
+}
+
+/// Parses, migrates and deserializes a JSON document.
+///
+/// # Examples
+/// ```
+/// # use scatterbrain::api::schema::{self, SCHEMA_VERSION};
+/// # use serde_json::Value;
+/// // Version 1 storage files were a bare array of plans
+/// let document: Value = schema::from_str("[]").unwrap();
+/// assert_eq!(document["version"], SCHEMA_VERSION);
+/// assert!(document["plans"].as_array().unwrap().is_empty());
+/// ```
+pub fn from_str<T: DeserializeOwned>(contents: &str) -> Result<T, SchemaError> {
+    from_value(serde_json::from_str(contents)?)
+}
+
+/// Version 1 had no `version` field, and the storage `plans.json` was a bare array of
+/// plans rather than an object with a `plans` field.
+fn v1_to_v2(document: Value) -> Result<Value, String> {
+    match document {
+        Value::Array(plans) => Ok(json!({ "plans": plans })),
+        Value::Object(map) => Ok(Value::Object(map)),
+        other => Err(format!("expected an object or array, found {other}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_migrations() {
+        // Unversioned archives gain a version and keep their contents
+        let archive = json!({ "exported_at": "2025-01-01T00:00:00Z", "plans": [] });
+        let migrated = migrate(archive).unwrap();
+        assert_eq!(migrated["version"], SCHEMA_VERSION);
+        assert_eq!(migrated["exported_at"], "2025-01-01T00:00:00Z");
+
+        // Current documents pass through untouched
+        let current = json!({ "version": SCHEMA_VERSION, "plans": [] });
+        assert_eq!(migrate(current.clone()).unwrap(), current);
+
+        // Documents from a newer build are refused rather than misread
+        let future = json!({ "version": SCHEMA_VERSION + 1, "plans": [] });
+        assert!(matches!(
+            migrate(future),
+            Err(SchemaError::UnsupportedVersion { .. })
+        ));
+
+        // A v1 document that is neither an object nor an array can't be migrated
+        assert!(matches!(
+            migrate(json!("plans")),
+            Err(SchemaError::Migration { from: 1, .. })
+        ));
+    }
+}
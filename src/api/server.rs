@@ -2,25 +2,40 @@
 //!
 //! This module provides the HTTP API server functionality for the scatterbrain tool.
 
+use std::collections::{BTreeMap, HashMap, VecDeque};
 use std::convert::Infallible;
-use std::net::SocketAddr;
+use std::hash::{DefaultHasher, Hash, Hasher};
 use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
 use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
 
 use axum::{
-    extract::{Path, State},
-    http::StatusCode,
+    async_trait,
+    extract::{DefaultBodyLimit, FromRequest, OriginalUri, Path, Query, Request, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    middleware::{self, Next},
     response::{Html, IntoResponse, Redirect, Response},
-    routing::{delete, get, post},
-    Json, Router,
+    routing::{delete, get, patch, post},
+    Extension, Json, Router,
 };
+use chrono::Datelike;
 use futures::Stream;
 use html_escape;
 use serde::{Deserialize, Serialize};
 use tokio::net::TcpListener;
-use tower_http::cors::{Any, CorsLayer};
+use tower_http::compression::CompressionLayer;
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
 
-use crate::models::{self, parse_index, Index, PlanError, PlanResponse};
+pub use super::config::ServerConfig;
+use super::config::{
+    ConfigError, ConfigReloader, ConfigSource, ReloadReport, RequestLimits, SharedConfig,
+};
+use super::storage::{PlanArchive, PlanExport};
+use crate::models::{self, parse_index, Index, PlanError, PlanResponse, TaskError, TaskRef};
+use crate::quotas::QuotaReport;
+use crate::spec::{PlanSpec, TaskSpec};
 use crate::Core;
 
 /// Request to add a new task
@@ -34,35 +49,172 @@ pub struct AddTaskRequest {
 /// Request to move to a specific task
 #[derive(Serialize, Deserialize)]
 pub struct MoveToRequest {
-    pub index: Index,
+    pub index: TaskRef,
+}
+
+/// Request to move a task, with its subtasks, under another task
+#[derive(Serialize, Deserialize)]
+pub struct MoveTaskRequest {
+    pub index: TaskRef,
+    /// Task it becomes the last subtask of; empty for the root
+    #[serde(default)]
+    pub parent: TaskRef,
+}
+
+/// Request to add or drop the dependency of one task on another
+#[derive(Serialize, Deserialize)]
+pub struct DependencyRequest {
+    pub index: TaskRef,
+    /// Task it depends on
+    pub on: TaskRef,
+}
+
+/// Request to add tags to a task or remove them
+#[derive(Serialize, Deserialize)]
+pub struct TagTaskRequest {
+    pub index: TaskRef,
+    pub tags: Vec<String>,
+}
+
+/// Request to set how much a task counts toward plan progress; no weight resets it
+#[derive(Serialize, Deserialize)]
+pub struct SetTaskWeightRequest {
+    pub index: TaskRef,
+    #[serde(default)]
+    pub weight: Option<u32>,
+}
+
+/// Request to set how urgent a task is; no priority clears it
+#[derive(Serialize, Deserialize)]
+pub struct SetTaskPriorityRequest {
+    pub index: TaskRef,
+    #[serde(default)]
+    pub priority: Option<u8>,
+}
+
+/// Request to set how many minutes a task is estimated to take; no minutes clears it
+#[derive(Serialize, Deserialize)]
+pub struct SetTaskEstimateRequest {
+    pub index: TaskRef,
+    #[serde(default)]
+    pub minutes: Option<u32>,
+}
+
+/// Request to log minutes of work on a task
+#[derive(Serialize, Deserialize)]
+pub struct LogTaskTimeRequest {
+    pub index: TaskRef,
+    pub minutes: u32,
+}
+
+/// Request to change the order a plan's subtasks are listed in
+#[derive(Serialize, Deserialize)]
+pub struct SetTaskOrderRequest {
+    pub order: models::TaskOrder,
 }
 
 /// Request to change a task's abstraction level
 #[derive(Serialize, Deserialize)]
 pub struct ChangeLevelRequest {
-    pub index: Index,
+    pub index: TaskRef,
     pub level_index: usize,
 }
 
 /// Request to complete a task, possibly with lease
 #[derive(Serialize, Deserialize)]
 pub struct CompleteTaskRequest {
-    pub index: Index,
+    pub index: TaskRef,
+    pub lease: Option<u8>,
+    pub force: bool,
+    /// Complete incomplete subtasks too, or reject the completion while any remain;
+    /// the plan's completion policy decides when unset
+    #[serde(default)]
+    pub cascade: Option<bool>,
+    pub summary: Option<String>,
+}
+
+/// Request to copy an exported subtree into a plan
+#[derive(Serialize, Deserialize)]
+pub struct ImportSubtreeRequest {
+    /// Task the subtree is added under; empty for the root
+    #[serde(default)]
+    pub parent: TaskRef,
+    pub subtree: TaskSpec,
+}
+
+/// Request to complete the task under the plan's cursor
+#[derive(Serialize, Deserialize)]
+pub struct CompleteCurrentRequest {
     pub lease: Option<u8>,
+    #[serde(default)]
     pub force: bool,
+    #[serde(default)]
+    pub cascade: Option<bool>,
     pub summary: Option<String>,
 }
 
 /// Request to generate a lease for a task
 #[derive(Serialize, Deserialize)]
 pub struct LeaseRequest {
-    pub index: Index,
+    pub index: TaskRef,
+    /// Reject the lease once it has completed the task
+    #[serde(default)]
+    pub single_use: bool,
+}
+
+/// Header identifying the client session; leases generated with it can only be used by
+/// requests from the same session
+pub const SESSION_HEADER: &str = "x-scatterbrain-session";
+
+fn session_of(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(SESSION_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|session| !session.is_empty())
+}
+
+/// Request to mark a task as in progress
+#[derive(Serialize, Deserialize)]
+pub struct StartTaskRequest {
+    pub index: TaskRef,
 }
 
 /// Request to uncomplete a task
 #[derive(Serialize, Deserialize)]
 pub struct UncompleteTaskRequest {
-    pub index: Index,
+    pub index: TaskRef,
+}
+
+/// Request to submit a task for review
+#[derive(Serialize, Deserialize)]
+pub struct RequestReviewRequest {
+    pub index: TaskRef,
+    pub summary: String,
+}
+
+/// Request to approve a task awaiting review
+#[derive(Serialize, Deserialize)]
+pub struct ApproveTaskRequest {
+    pub index: TaskRef,
+}
+
+/// Request to reject a task awaiting review
+#[derive(Serialize, Deserialize)]
+pub struct RejectTaskRequest {
+    pub index: TaskRef,
+    pub reason: Option<String>,
+}
+
+/// Request to record a new open question for a plan
+#[derive(Serialize, Deserialize)]
+pub struct AddQuestionRequest {
+    pub question: String,
+}
+
+/// Request to answer a tracked question
+#[derive(Serialize, Deserialize)]
+pub struct AnswerQuestionRequest {
+    pub answer: String,
 }
 
 /// Request to create a new plan with a required prompt
@@ -72,24 +224,187 @@ pub struct CreatePlanRequest {
     pub notes: Option<String>, // Add optional notes field
 }
 
+/// Request to change a plan's lifecycle status
+#[derive(Serialize, Deserialize)]
+pub struct SetPlanStatusRequest {
+    pub status: models::PlanStatus,
+    #[serde(default)]
+    pub force: bool,
+}
+
+/// Request to turn tombstones for removed tasks on or off
+#[derive(Serialize, Deserialize)]
+pub struct SetTombstonesRequest {
+    pub enabled: bool,
+}
+
+/// Request to replace a plan's goal or notes; a missing field is left as it is, and
+/// empty notes clear them
+#[derive(Serialize, Deserialize)]
+pub struct UpdatePlanRequest {
+    #[serde(default)]
+    pub goal: Option<String>,
+    #[serde(default)]
+    pub notes: Option<String>,
+}
+
+/// Request to set or clear a plan's own usage summary
+#[derive(Serialize, Deserialize)]
+pub struct SetUsageSummaryRequest {
+    pub usage_summary: Option<String>,
+}
+
+/// Request to pin a plan's work to a task, or clear the focus (`index: None`)
+#[derive(Serialize, Deserialize)]
+pub struct SetFocusRequest {
+    pub index: Option<TaskRef>,
+    /// Reject operations outside the focused subtree instead of warning about them
+    #[serde(default)]
+    pub strict: bool,
+}
+
+/// Request to start timing a working session on a plan
+#[derive(Serialize, Deserialize)]
+pub struct StartSessionRequest {
+    /// Replaces the plan's minutes between reminders; 0 turns them off
+    #[serde(default)]
+    pub reminder_minutes: Option<u32>,
+}
+
+/// Request to replace a task's description
+#[derive(Serialize, Deserialize)]
+pub struct UpdateTaskRequest {
+    pub description: String,
+}
+
 /// Request to set notes for a task
 #[derive(Serialize, Deserialize)]
 pub struct SetTaskNotesRequest {
     pub notes: String,
+    /// Append a timestamped block instead of replacing the existing notes
+    #[serde(default)]
+    pub append: bool,
+}
+
+/// Request to revert a task's notes to a previous version
+#[derive(Serialize, Deserialize)]
+pub struct RevertNotesRequest {
+    pub version: usize,
 }
 
-/// Server configuration
-#[derive(Clone, Debug)]
-pub struct ServerConfig {
-    pub address: SocketAddr,
+/// Request to set, append to, or remove (`content: None`) a named notes section
+#[derive(Serialize, Deserialize)]
+pub struct SetNoteSectionRequest {
+    pub section: String,
+    pub content: Option<String>,
+    #[serde(default)]
+    pub append: bool,
 }
 
-impl Default for ServerConfig {
-    fn default() -> Self {
-        Self {
-            address: ([127, 0, 0, 1], 3000).into(),
-        }
-    }
+/// Query parameters for fetching notes sections
+#[derive(Serialize, Deserialize)]
+pub struct NoteSectionsQuery {
+    pub section: Option<String>,
+}
+
+/// Query parameters for listing a plan's tasks, see [`models::TaskFilter`]
+#[derive(Serialize, Deserialize)]
+pub struct ListTasksQuery {
+    #[serde(default)]
+    pub incomplete_only: bool,
+    pub level: Option<usize>,
+    pub depth: Option<usize>,
+    /// Index or ID of the task to list below, e.g. "0,2" or "t-3f9a2c1b"
+    pub subtree: Option<String>,
+    /// Only list tasks with this tag
+    pub tag: Option<String>,
+}
+
+/// Query parameters for searching a plan's tasks
+#[derive(Serialize, Deserialize)]
+pub struct SearchQuery {
+    /// Words every matching task contains, each possibly as the start of a longer word
+    pub q: String,
+}
+
+/// Query parameters for rolling up a plan's completion summaries
+#[derive(Serialize, Deserialize)]
+pub struct SummariesQuery {
+    /// Level of the tasks to group summaries under, 1 by default
+    pub level: Option<usize>,
+}
+
+/// Query parameters for viewing a plan as it was at some time
+#[derive(Serialize, Deserialize)]
+pub struct PlanAtQuery {
+    /// RFC 3339 time, e.g. `2025-06-01T22:00:00Z`
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+}
+
+/// Query parameters for listing the tasks completed in a window of a plan's history
+#[derive(Serialize, Deserialize)]
+pub struct ChangelogQuery {
+    /// RFC 3339 start of the window; the plan's whole history by default
+    pub from: Option<chrono::DateTime<chrono::Utc>>,
+    /// RFC 3339 end of the window; now by default
+    pub to: Option<chrono::DateTime<chrono::Utc>>,
+    /// `markdown` returns release notes instead of the usual JSON response
+    pub format: Option<String>,
+}
+
+/// Query parameters for long-polling a plan's changes
+#[derive(Serialize, Deserialize)]
+pub struct ChangesQuery {
+    /// Sequence number of the first transition wanted; the next one by default
+    pub since: Option<usize>,
+    /// How long to wait for a change, e.g. `30s` or `30`; at most [`MAX_POLL_TIMEOUT`]
+    pub timeout: Option<String>,
+}
+
+/// Transitions returned by a long poll
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PlanChanges {
+    /// Transitions since the requested position, oldest first; empty if the poll timed out
+    pub changes: Vec<models::HistoryRecord>,
+    /// Sequence number to pass as `since` in the next poll
+    pub next_seq: usize,
+}
+
+/// Query parameters for a plan's distilled context
+#[derive(Serialize, Deserialize)]
+pub struct DistilledQuery {
+    /// `compact` returns dense plain text for prompts instead of the usual JSON response
+    pub format: Option<String>,
+}
+
+/// Query parameters for exporting a plan's history
+#[derive(Serialize, Deserialize)]
+pub struct HistoryQuery {
+    /// `jsonl` streams one record per line instead of the usual JSON response
+    pub format: Option<String>,
+}
+
+/// Request to add a task from a template
+#[derive(Serialize, Deserialize)]
+pub struct AddTaskFromTemplateRequest {
+    pub template: String,
+    #[serde(default)]
+    pub params: models::TemplateParams,
+}
+
+/// Quota usage of the default plans and of each workspace
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct QuotaOverview {
+    pub default: QuotaReport,
+    pub workspaces: BTreeMap<String, QuotaReport>,
+}
+
+/// Request to compact a plan's history
+#[derive(Serialize, Deserialize, Default)]
+pub struct CompactHistoryRequest {
+    /// How many notes versions to keep per task
+    #[serde(default)]
+    pub keep_note_versions: usize,
 }
 
 /// API responses
@@ -100,6 +415,9 @@ pub struct ApiResponse<T: Serialize> {
     data: Option<T>,
     #[serde(skip_serializing_if = "Option::is_none")]
     error: Option<String>,
+    /// Why a task operation was rejected, so clients can tell failures apart
+    #[serde(skip_serializing_if = "Option::is_none")]
+    task_error: Option<TaskError>,
 }
 
 pub type JSONResp<T> = Json<ApiResponse<PlanResponse<T>>>;
@@ -110,6 +428,7 @@ impl<T: Serialize> ApiResponse<T> {
             success: true,
             data: Some(data),
             error: None,
+            task_error: None,
         }
     }
 
@@ -118,33 +437,87 @@ impl<T: Serialize> ApiResponse<T> {
             success: false,
             data: None,
             error: Some(message),
+            task_error: None,
+        }
+    }
+
+    pub fn rejected(error: TaskError) -> Self {
+        Self {
+            success: false,
+            data: None,
+            error: Some(error.to_string()),
+            task_error: Some(error),
         }
     }
 }
 
+/// Resolves a task a request refers to by index or ID to its current index, or the error
+/// response to send back
+fn resolve_task(core: &Core, plan_id: &models::PlanId, task: &TaskRef) -> Result<Index, Response> {
+    core.resolve_task(plan_id, task)
+        .map_err(|e| map_core_result_simple::<()>(Err(e)))
+}
+
+/// Parses and resolves a task given in a path or query string, e.g. "0,2" or "t-3f9a2c1b"
+fn task_from_path(core: &Core, plan_id: &models::PlanId, task: &str) -> Result<Index, Response> {
+    let task = task.parse::<TaskRef>().map_err(|e| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(ApiResponse::<()>::error(format!(
+                "Invalid index format: {e}"
+            ))),
+        )
+            .into_response()
+    })?;
+    resolve_task(core, plan_id, &task)
+}
+
+/// HTTP status for a rejected task operation
+fn task_error_status(error: &TaskError) -> StatusCode {
+    match error {
+        TaskError::OutOfBounds { .. }
+        | TaskError::UnknownTaskId { .. }
+        | TaskError::QuestionNotFound { .. }
+        | TaskError::NotesVersionNotFound { .. } => StatusCode::NOT_FOUND,
+        TaskError::LevelViolation(_)
+        | TaskError::SummaryRejected { .. }
+        | TaskError::UnknownValidator { .. } => StatusCode::UNPROCESSABLE_ENTITY,
+        TaskError::RootTask
+        | TaskError::MoveIntoSubtree { .. }
+        | TaskError::DependencyCycle { .. }
+        | TaskError::InvalidPriority { .. }
+        | TaskError::InvalidTag { .. }
+        | TaskError::SummaryRequired { .. }
+        | TaskError::EmptySectionName
+        | TaskError::InvalidEmoji { .. } => StatusCode::BAD_REQUEST,
+        TaskError::PlanLocked => StatusCode::LOCKED,
+        TaskError::QuotaExceeded { .. } => StatusCode::FORBIDDEN,
+        TaskError::PlanArchived
+        | TaskError::OutsideFocus { .. }
+        | TaskError::NoCurrentTask
+        | TaskError::LeaseRequired { .. }
+        | TaskError::LeaseMismatch { .. }
+        | TaskError::LeaseSpent { .. }
+        | TaskError::LeaseSessionMismatch { .. }
+        | TaskError::AlreadyCompleted { .. }
+        | TaskError::NotCompleted { .. }
+        | TaskError::NotAwaitingReview { .. }
+        | TaskError::ReviewPending { .. }
+        | TaskError::TransitionNotAllowed { .. }
+        | TaskError::ChangedDuringValidation { .. }
+        | TaskError::IncompleteTasks
+        | TaskError::IncompleteSubtasks { .. }
+        | TaskError::BlockedByDependencies { .. }
+        | TaskError::NotLeaf { .. }
+        | TaskError::CompletionRejected { .. } => StatusCode::CONFLICT,
+    }
+}
+
 /// Helper function to map Core results to Axum responses
 fn map_core_result_to_response<T: Serialize>(
     result: Result<PlanResponse<T>, PlanError>,
 ) -> Response {
-    match result {
-        Ok(plan_response) => {
-            (StatusCode::OK, Json(ApiResponse::success(plan_response))).into_response()
-        }
-        Err(PlanError::PlanNotFound(token)) => (
-            StatusCode::NOT_FOUND,
-            Json(ApiResponse::<PlanResponse<T>>::error(format!(
-                "Plan '{token}' not found"
-            ))),
-        )
-            .into_response(),
-        Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(ApiResponse::<PlanResponse<T>>::error(format!(
-                "Internal server error: {e}"
-            ))),
-        )
-            .into_response(),
-    }
+    map_core_result_simple(result)
 }
 
 /// Helper function to map Core results (without PlanResponse) to Axum responses
@@ -156,6 +529,30 @@ fn map_core_result_simple<T: Serialize>(result: Result<T, PlanError>) -> Respons
             Json(ApiResponse::<T>::error(format!("Plan '{token}' not found"))),
         )
             .into_response(),
+        Err(e @ (PlanError::TemplateNotFound(_) | PlanError::RevisionNotFound { .. })) => (
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::<T>::error(e.to_string())),
+        )
+            .into_response(),
+        // The watcher missed transitions that are gone, and has to reload the plan
+        Err(e @ PlanError::HistoryGap { .. }) => (
+            StatusCode::GONE,
+            Json(ApiResponse::<T>::error(e.to_string())),
+        )
+            .into_response(),
+        Err(e @ (PlanError::InvalidTemplate(_) | PlanError::InvalidWindow { .. })) => (
+            StatusCode::BAD_REQUEST,
+            Json(ApiResponse::<T>::error(e.to_string())),
+        )
+            .into_response(),
+        Err(e @ PlanError::InvalidSpec(_)) => (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(ApiResponse::<T>::error(e.to_string())),
+        )
+            .into_response(),
+        Err(PlanError::Task(e)) => {
+            (task_error_status(&e), Json(ApiResponse::<T>::rejected(e))).into_response()
+        }
         Err(e) => (
             StatusCode::INTERNAL_SERVER_ERROR,
             Json(ApiResponse::<T>::error(format!(
@@ -166,957 +563,3666 @@ fn map_core_result_simple<T: Serialize>(result: Result<T, PlanError>) -> Respons
     }
 }
 
-/// Starts the API server
-pub async fn serve(core: Core, config: ServerConfig) -> Result<(), Box<dyn std::error::Error>> {
-    // CORS configuration
-    let cors = CorsLayer::new()
-        .allow_origin(Any)
-        .allow_methods(Any)
-        .allow_headers(Any);
+/// Starts the API server, running until the process is interrupted or terminated.
+///
+/// When a `source` is given, the configuration can be reloaded from it at runtime via
+/// `SIGHUP` or `POST /api/admin/reload`.
+pub async fn serve(
+    core: Core,
+    config: ServerConfig,
+    source: Option<ConfigSource>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    serve_until(core, config, source, shutdown_signal()).await
+}
 
-    // Build application with routes
-    let app = Router::new()
-        // --- Redirect root to the new plan listing UI --- //
-        .route("/", get(|| async { Redirect::temporary("/ui") })) // Redirect to /ui
-        // --- Plan Management --- //
-        .route(
-            "/api/plans",
-            get(list_plans_handler).post(create_plan_handler),
-        )
-        .route("/api/plans/:id", delete(delete_plan_handler))
-        // --- Existing Endpoints (now id-scoped) --- //
-        .route("/api/plans/:id/plan", get(get_plan))
-        .route("/api/plans/:id/current", get(get_current))
-        .route("/api/plans/:id/distilled", get(get_distilled_context))
-        .route("/api/plans/:id/task", post(add_task))
-        .route("/api/plans/:id/task/complete", post(complete_task))
-        .route("/api/plans/:id/task/level", post(change_level))
-        .route("/api/plans/:id/task/lease", post(generate_lease))
-        .route("/api/plans/:id/task/uncomplete", post(uncomplete_task))
-        .route("/api/plans/:id/move", post(move_to))
-        .route("/api/plans/:id/tasks/*index", delete(remove_task_handler))
-        // --- Notes Endpoints --- //
-        .route(
-            "/api/plans/:id/notes/*index",
-            get(get_notes_handler)
-                .post(set_notes_handler)
-                .delete(delete_notes_handler),
-        )
-        // --- UI --- //
-        .route("/ui", get(list_plans_ui_handler)) // New route for listing plans
-        .route("/ui/:id", get(ui_handler)) // Specific plan UI using ID
-        .route("/ui/events/:id", get(events_handler)) // ID-scoped events
-        .layer(cors)
-        .with_state(core);
+/// How long open connections, such as UI event streams, get to finish after shutdown starts
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(5);
 
-    // Start server
-    tracing::info!("Starting server on {}", config.address);
-    let listener = TcpListener::bind(config.address).await?;
-    axum::serve(listener, app).await?;
+/// Resolves when the process receives `SIGINT` (Ctrl-C) or, on Unix, `SIGTERM`
+pub async fn shutdown_signal() {
+    let interrupt = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            tracing::warn!("Can't listen for Ctrl-C: {e}");
+            std::future::pending::<()>().await;
+        }
+    };
 
-    Ok(())
-}
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminations) => {
+                terminations.recv().await;
+            }
+            Err(e) => {
+                tracing::warn!("Can't listen for SIGTERM: {e}");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
 
-// --- Plan Management Handlers --- //
+    tokio::select! {
+        _ = interrupt => {}
+        _ = terminate => {}
+    }
+}
 
-async fn list_plans_handler(State(core): State<Core>) -> impl IntoResponse {
-    let result = core.list_plans();
-    map_core_result_simple(result) // Returns Vec<Lease> (PlanId)
+/// Starts the API server and runs it until `shutdown` resolves.
+///
+/// Shutting down stops accepting connections and waits up to [`SHUTDOWN_GRACE`] for open
+/// ones to finish. With a storage path, the plans are then saved one last time, so the
+/// next start doesn't have to replay the journal.
+pub async fn serve_until(
+    core: Core,
+    config: ServerConfig,
+    source: Option<ConfigSource>,
+    shutdown: impl std::future::Future<Output = ()> + Send + 'static,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let listener = TcpListener::bind(config.address).await?;
+    serve_with_listener(listener, core, config, source, shutdown).await
 }
 
-// --- New UI Handler for Listing Plans --- //
+/// Like [`serve_until`], accepting connections on `listener` instead of binding
+/// `config.address`, e.g. one bound to port 0 so the system picks a free port
+pub async fn serve_with_listener(
+    listener: TcpListener,
+    core: Core,
+    config: ServerConfig,
+    source: Option<ConfigSource>,
+    shutdown: impl std::future::Future<Output = ()> + Send + 'static,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // Apply plan defaults before any plan gets created
+    if let Some(levels) = config.plan_defaults.levels.clone() {
+        core.set_default_levels(levels)?;
+    }
+    for template in config.templates.clone() {
+        core.save_template(template)?;
+    }
+    for (name, validator) in config.validators.clone() {
+        core.register_validator(name, Arc::new(validator))?;
+    }
+    for (name, engine) in config.suggestions.clone() {
+        core.register_suggestion_engine(name, Arc::new(engine))?;
+    }
+    core.set_usage_summaries(config.usage_summary.clone())?;
+    core.set_quotas(config.quotas)?;
+    core.set_redactor(config.redactor()?)?;
 
-async fn list_plans_ui_handler(State(core): State<Core>) -> impl IntoResponse {
-    match core.list_plans() {
-        Ok(plan_ids) => {
-            let mut html_content = String::new();
-            html_content.push_str(
-                "<!DOCTYPE html><html><head><title>Scatterbrain Plans</title></head><body>",
-            );
-            html_content.push_str("<h1>Available Scatterbrain Plans</h1>");
+    let storage_path = config.storage_path.clone();
+    let reloader = ConfigReloader::new(core.clone(), config, source);
 
-            if plan_ids.is_empty() {
-                html_content.push_str("<p>No plans found. Create one using the CLI: <code>scatterbrain plan create</code></p>");
-            } else {
-                html_content.push_str("<ul>");
-                for id in plan_ids {
-                    let id_val = id.value();
-                    html_content.push_str(&format!(
-                        "<li><a href=\"/ui/{id_val}\">Plan {id_val}</a></li>"
-                    ));
-                }
-                html_content.push_str("</ul>");
-            }
+    // Each workspace keeps its plans in a directory of its own under the storage path
+    let mut served = vec![(core.clone(), storage_path.clone())];
+    for (name, workspace_core) in reloader.workspaces() {
+        let dir = storage_path
+            .as_ref()
+            .map(|dir| dir.join("workspaces").join(name));
+        served.push((workspace_core.clone(), dir));
+    }
 
-            html_content.push_str("</body></html>");
-            Html(html_content)
+    let mut persisted = Vec::new();
+    for (core, dir) in served {
+        if let Some(dir) = dir {
+            let journal = restore_and_persist(&core, &dir)?;
+            // Take scheduled snapshots next to the persisted plans
+            super::storage::spawn_snapshotter(&core, dir.clone(), reloader.config());
+            persisted.push((core.clone(), dir, journal));
+        } else {
+            // Without a journal to read them back from, past states are kept in memory
+            spawn_revision_compressor(core.clone());
         }
-        Err(e) => {
-            // Log the error on the server
-            tracing::error!("Failed to list plans for UI: {}", e);
-            // Return a user-friendly HTML error page
-            Html(format!(
-                "<!DOCTYPE html><html><head><title>Error</title></head><body><h1>Error</h1><p>Could not load plan list: {e}</p></body></html>"
-            ))
+
+        // Forward plan lifecycle events to the webhook, if one is (or later gets) configured
+        super::webhook::spawn_webhook_forwarder(&core, reloader.config());
+        super::hooks::spawn_hook_runner(&core, reloader.config());
+        spawn_lease_collector(core);
+    }
+
+    #[cfg(unix)]
+    spawn_sighup_reloader(reloader.clone())?;
+
+    let app = router(reloader);
+
+    // Start server
+    tracing::info!("Starting server on {}", listener.local_addr()?);
+    let (stopping_tx, stopping_rx) = tokio::sync::oneshot::channel();
+    let server = axum::serve(listener, app).with_graceful_shutdown(async move {
+        shutdown.await;
+        tracing::info!("Shutting down, waiting for open requests to finish");
+        let _ = stopping_tx.send(());
+    });
+    let grace_over = async {
+        if stopping_rx.await.is_ok() {
+            tokio::time::sleep(SHUTDOWN_GRACE).await;
+        } else {
+            std::future::pending::<()>().await;
         }
+    };
+    tokio::select! {
+        result = server => result?,
+        _ = grace_over => tracing::warn!("Closing connections still open after {SHUTDOWN_GRACE:?}"),
+    }
+
+    for (core, dir, journal) in persisted {
+        super::storage::checkpoint(&core, &dir, &journal)?;
+        tracing::info!("Saved plans to {}", dir.display());
     }
+
+    Ok(())
 }
 
-async fn create_plan_handler(
-    State(core): State<Core>,
-    // Require Json extractor for the request body since prompt is now required
-    Json(payload): Json<CreatePlanRequest>,
-) -> impl IntoResponse {
-    // Call core.create_plan with the prompt and notes
-    let result = core.create_plan(payload.prompt, payload.notes);
-    map_core_result_simple(result) // Returns Lease (PlanId)
+/// Restores the plans persisted in `dir`, replaying changes journaled since the last
+/// save, and keeps saving them as they change
+fn restore_and_persist(
+    core: &Core,
+    dir: &std::path::Path,
+) -> Result<Arc<super::storage::FileJournal>, Box<dyn std::error::Error>> {
+    let loaded = super::storage::load_plans(core, dir)?;
+    let replayed = super::storage::replay_journal(core, dir)?;
+    tracing::info!(
+        "Loaded {loaded} plans from {}, replayed {replayed} journaled changes",
+        dir.display()
+    );
+    let journal = Arc::new(super::storage::FileJournal::open(dir)?);
+    journal.backfill_history(core)?;
+    super::storage::checkpoint(core, dir, &journal)?;
+    core.set_journal(journal.clone())?;
+    super::storage::spawn_persister(core, dir.to_path_buf(), journal.clone());
+    Ok(journal)
 }
 
-async fn delete_plan_handler(
-    State(core): State<Core>,
-    Path(id): Path<u8>, // Use u8 ID from path
-) -> impl IntoResponse {
-    let plan_id = models::Lease::new(id); // Use constructor
-    let result = core.delete_plan(&plan_id);
-    map_core_result_simple(result) // Use simple mapper as it returns ()
-}
-
-// --- Existing Handler Implementations (Updated) --- //
+/// How often leases left on missing or completed tasks are dropped
+const LEASE_GC_INTERVAL: Duration = Duration::from_secs(300);
 
-async fn get_plan(State(core): State<Core>, Path(id): Path<u8>) -> impl IntoResponse {
-    let plan_id = models::Lease::new(id); // Use constructor
-    let result = core.get_plan(&plan_id);
-    map_core_result_to_response(result)
+/// Periodically drops orphaned leases from every plan
+fn spawn_lease_collector(core: Core) {
+    tokio::spawn(async move {
+        let mut ticks = tokio::time::interval(LEASE_GC_INTERVAL);
+        loop {
+            ticks.tick().await;
+            match core.collect_orphaned_leases() {
+                Ok(0) => {}
+                Ok(dropped) => tracing::info!("Dropped {dropped} orphaned leases"),
+                Err(e) => tracing::warn!("Orphaned lease collection failed: {e}"),
+            }
+        }
+    });
 }
 
-async fn get_current(State(core): State<Core>, Path(id): Path<u8>) -> impl IntoResponse {
-    let plan_id = models::Lease::new(id); // Use constructor
-    let response = core.current(&plan_id);
-    map_core_result_to_response(response)
-}
+/// How often revisions kept in memory are compressed
+const REVISION_COMPRESSION_INTERVAL: Duration = Duration::from_secs(30);
 
-async fn get_distilled_context(State(core): State<Core>, Path(id): Path<u8>) -> impl IntoResponse {
-    let plan_id = models::Lease::new(id); // Use constructor
-    let response = core.distilled_context(&plan_id);
-    map_core_result_to_response(response)
+/// Periodically compresses the revisions recorded in memory since the last run, on the
+/// blocking thread pool rather than in the requests that recorded them
+fn spawn_revision_compressor(core: Core) {
+    tokio::spawn(async move {
+        let mut ticks = tokio::time::interval(REVISION_COMPRESSION_INTERVAL);
+        loop {
+            ticks.tick().await;
+            let compressing = core.clone();
+            if let Err(e) = run_blocking(move || compressing.compress_revisions()).await {
+                tracing::warn!("Compressing revisions failed: {e}");
+            }
+        }
+    });
 }
 
-async fn add_task(
-    State(core): State<Core>,
-    Path(id): Path<u8>,
-    Json(payload): Json<AddTaskRequest>,
-) -> impl IntoResponse {
-    let plan_id = models::Lease::new(id); // Use constructor
-    let response = core.add_task(
-        &plan_id,
-        payload.description,
-        payload.level_index,
-        payload.notes,
-    );
-    map_core_result_to_response(response)
+/// Reloads the configuration whenever the process receives `SIGHUP`
+#[cfg(unix)]
+fn spawn_sighup_reloader(reloader: ConfigReloader) -> std::io::Result<()> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangups = signal(SignalKind::hangup())?;
+    tokio::spawn(async move {
+        while hangups.recv().await.is_some() {
+            log_reload(&reloader.reload());
+        }
+    });
+    Ok(())
 }
 
-async fn complete_task(
-    State(core): State<Core>,
-    Path(id): Path<u8>,
-    Json(payload): Json<CompleteTaskRequest>,
-) -> impl IntoResponse {
-    let plan_id = models::Lease::new(id); // Use constructor
-    let response = core.complete_task(
-        &plan_id,
-        payload.index,
-        payload.lease, // Already Option<u8>
-        payload.force,
-        payload.summary,
-    );
-    // Custom handling for the bool inside PlanResponse<bool>
-    match response {
-        Ok(plan_response) => {
-            if *plan_response.inner() {
-                (StatusCode::OK, Json(ApiResponse::success(plan_response))).into_response()
-            } else {
-                // Use the distilled context from the response even on failure
-                (
-                    StatusCode::BAD_REQUEST, // Or another suitable code
-                    Json(ApiResponse::<PlanResponse<bool>>::error("Failed to complete task (lease mismatch, already complete, or other issue)".to_string())),
-                )
-                    .into_response()
+fn log_reload(result: &Result<ReloadReport, ConfigError>) {
+    match result {
+        Ok(report) => {
+            tracing::info!("Reloaded config, applied: {:?}", report.applied);
+            if !report.requires_restart.is_empty() {
+                tracing::warn!(
+                    "Config changes that require a restart: {:?}",
+                    report.requires_restart
+                );
             }
         }
-        Err(e) => map_core_result_to_response::<bool>(Err(e)), // Use helper for PlanErrors
+        Err(e) => tracing::error!("Config reload failed: {e}"),
     }
 }
 
-async fn change_level(
-    State(core): State<Core>,
-    Path(id): Path<u8>,
-    Json(payload): Json<ChangeLevelRequest>,
-) -> impl IntoResponse {
-    let plan_id = models::Lease::new(id); // Use constructor
-    let response = core.change_level(&plan_id, payload.index, payload.level_index);
-    // Handle the Result<(), String> inside PlanResponse
-    match response {
-        Ok(plan_response) => match plan_response.inner() {
-            Ok(_) => (StatusCode::OK, Json(ApiResponse::success(plan_response))).into_response(),
-            Err(e) => (
-                StatusCode::BAD_REQUEST,
-                Json(ApiResponse::<PlanResponse<Result<(), String>>>::error(
-                    e.clone(),
-                )),
-            )
-                .into_response(),
-        },
-        Err(e) => map_core_result_to_response::<Result<(), String>>(Err(e)), // Use helper for PlanErrors
-    }
+/// Admin routes acting on the plans of one core, relative to the `/api/admin` prefix
+/// they are served under for the default workspace, and to
+/// `/api/admin/workspaces/<name>` for each configured workspace
+fn admin_plan_routes() -> Router<Core> {
+    Router::new()
+        .route("/export", get(export_plans_handler))
+        // Backup archives hold every plan, so they are exempt from the body limit
+        .route(
+            "/import",
+            post(import_plans_handler).layer(DefaultBodyLimit::disable()),
+        )
+        .route("/plans/:id/unlock", post(force_unlock_handler))
+        .route(
+            "/plans/:id/orphaned-leases",
+            delete(clear_orphaned_leases_handler),
+        )
+        .route("/plans/:id/compact", post(compact_history_handler))
 }
 
-async fn generate_lease(
-    State(core): State<Core>,
-    Path(id): Path<u8>,
-    Json(payload): Json<LeaseRequest>,
-) -> impl IntoResponse {
-    let plan_id = models::Lease::new(id); // Use constructor
-    let response = core.generate_lease(&plan_id, payload.index);
-    map_core_result_to_response(response)
+/// Routes for plans and templates, relative to the `/api` prefix they are served
+/// under, once for the default workspace and once per configured workspace
+fn plan_routes() -> Router<Core> {
+    Router::new()
+        // --- Plan Management --- //
+        .route("/plans", get(list_plans_handler).post(create_plan_handler))
+        .route("/plans/summaries", get(list_plan_summaries_handler))
+        .route("/plans/spec", post(create_plan_from_spec_handler))
+        .route("/plans/import", post(import_plan_handler))
+        .route(
+            "/plans/:id",
+            patch(update_plan_handler).delete(delete_plan_handler),
+        )
+        .route("/plans/:id/status", post(set_plan_status_handler))
+        .route("/plans/:id/tombstones", post(set_tombstones_handler))
+        .route("/plans/:id/task-order", post(set_task_order_handler))
+        .route("/plans/:id/lock", post(lock_plan_handler))
+        .route("/plans/:id/unlock", post(unlock_plan_handler))
+        .route(
+            "/plans/:id/share",
+            post(share_plan_handler).delete(unshare_plan_handler),
+        )
+        .route("/plans/:id/policy", post(set_completion_policy_handler))
+        .route("/plans/:id/badge", post(set_plan_badge_handler))
+        .route("/plans/:id/usage-summary", post(set_usage_summary_handler))
+        .route("/plans/:id/focus", post(set_focus_handler))
+        .route(
+            "/plans/:id/session",
+            post(start_session_handler).delete(stop_session_handler),
+        )
+        // --- Existing Endpoints (now id-scoped) --- //
+        .route("/plans/:id/plan", get(get_plan))
+        .route("/plans/:id/export", get(export_plan_handler))
+        .route("/plans/:id/at", get(get_plan_at))
+        .route("/plans/:id/current", get(get_current))
+        .route("/plans/:id/progress", get(get_progress))
+        .route("/plans/:id/stats", get(get_plan_stats))
+        .route("/plans/:id/effort", get(get_effort_report))
+        .route("/plans/:id/history", get(get_history))
+        .route("/plans/:id/changes", get(get_changes))
+        .route("/plans/:id/lint", get(lint_plan))
+        .route("/plans/:id/current/complete", post(complete_current_task))
+        .route("/plans/:id/distilled", get(get_distilled_context))
+        .route("/plans/:id/task", post(add_task))
+        .route("/plans/:id/task/template", post(add_task_from_template))
+        // --- Templates --- //
+        .route(
+            "/templates",
+            get(list_templates_handler).post(save_template_handler),
+        )
+        .route("/templates/:name", delete(remove_template_handler))
+        .route("/plans/:id/task/complete", post(complete_task))
+        .route("/plans/:id/task/move", post(move_task))
+        .route("/plans/:id/task/dependency", post(add_dependency))
+        .route("/plans/:id/task/dependency/remove", post(remove_dependency))
+        .route("/plans/:id/task/tags", post(tag_task))
+        .route("/plans/:id/task/tags/remove", post(untag_task))
+        .route("/plans/:id/task/level", post(change_level))
+        .route("/plans/:id/task/weight", post(set_task_weight))
+        .route("/plans/:id/task/priority", post(set_task_priority))
+        .route("/plans/:id/task/estimate", post(set_task_estimate))
+        .route("/plans/:id/task/time", post(log_task_time))
+        .route("/plans/:id/task/lease", post(generate_lease))
+        .route("/plans/:id/task/start", post(start_task))
+        .route("/plans/:id/task/uncomplete", post(uncomplete_task))
+        .route("/plans/:id/task/review", post(request_review))
+        .route("/plans/:id/task/approve", post(approve_task))
+        .route("/plans/:id/task/reject", post(reject_task))
+        .route("/plans/:id/review-queue", get(get_review_queue))
+        .route("/plans/:id/summaries", get(get_summaries))
+        .route("/plans/:id/changelog", get(get_changelog))
+        // --- Questions Endpoints --- //
+        .route(
+            "/plans/:id/questions",
+            get(list_questions_handler).post(add_question_handler),
+        )
+        .route(
+            "/plans/:id/questions/:question_id",
+            delete(remove_question_handler),
+        )
+        .route(
+            "/plans/:id/questions/:question_id/answer",
+            post(answer_question_handler),
+        )
+        .route("/plans/:id/move", post(move_to))
+        .route("/plans/:id/tasks", get(list_tasks_handler))
+        .route("/plans/:id/search", get(search_tasks_handler))
+        .route(
+            "/plans/:id/tasks/*index",
+            get(get_task_handler)
+                .patch(update_task_handler)
+                .delete(remove_task_handler),
+        )
+        .route("/plans/:id/subtree", post(import_subtree_handler))
+        .route("/plans/:id/subtree/*index", get(export_subtree_handler))
+        // --- Notes Endpoints --- //
+        .route(
+            "/plans/:id/notes/*index",
+            get(get_notes_handler)
+                .post(set_notes_handler)
+                .delete(delete_notes_handler),
+        )
+        .route(
+            "/plans/:id/notes-history/*index",
+            get(get_notes_history_handler).post(revert_notes_handler),
+        )
+        .route(
+            "/plans/:id/note-sections/*index",
+            get(get_note_sections_handler).post(set_note_section_handler),
+        )
 }
 
-async fn uncomplete_task(
-    State(core): State<Core>,
-    Path(id): Path<u8>,
-    Json(payload): Json<UncompleteTaskRequest>,
-) -> impl IntoResponse {
-    let plan_id = models::Lease::new(id); // Use constructor
-    let response = core.uncomplete_task(&plan_id, payload.index);
-    // Handle the Result<bool, String> inside PlanResponse
-    match response {
-        Ok(plan_response) => match plan_response.inner() {
-            Ok(true) => (StatusCode::OK, Json(ApiResponse::success(plan_response))).into_response(),
-            Ok(false) => (
-                StatusCode::BAD_REQUEST, // Should ideally not happen
-                Json(ApiResponse::<PlanResponse<Result<bool, String>>>::error(
-                    "Task was already incomplete or uncompletion failed silently".to_string(),
-                )),
-            )
-                .into_response(),
-            Err(e) => (
-                StatusCode::BAD_REQUEST,
-                Json(ApiResponse::<PlanResponse<Result<bool, String>>>::error(
-                    e.clone(),
-                )),
+/// Builds the application router with all routes and the layers the reloader's config asks for
+pub fn router(reloader: ConfigReloader) -> Router {
+    let config = reloader.config();
+    let core = reloader.core().clone();
+    let metrics_enabled = reloader.current().metrics;
+    let compression_enabled = reloader.current().compression;
+    let max_body_bytes = reloader.current().limits.max_body_bytes;
+
+    // CORS origins are checked per request so reloads take effect immediately
+    let cors_config = config.clone();
+    let allow_origin = AllowOrigin::predicate(move |origin: &HeaderValue, _| {
+        let Ok(config) = cors_config.read() else {
+            return false;
+        };
+        config.cors_origins.is_empty()
+            || config
+                .cors_origins
+                .iter()
+                .any(|allowed| allowed.as_bytes() == origin.as_bytes())
+    });
+    let cors = CorsLayer::new()
+        .allow_origin(allow_origin)
+        .allow_methods(Any)
+        .allow_headers(Any);
+
+    let metrics = Arc::new(Metrics::default());
+
+    // Build application with routes
+    let mut app = Router::new()
+        // --- Redirect root to the new plan listing UI --- //
+        .route("/", get(|| async { Redirect::temporary("/ui") })) // Redirect to /ui
+        // --- Admin --- //
+        .route("/api/admin/reload", post(reload_config_handler))
+        .route("/api/admin/quotas", get(quotas_handler))
+        .nest("/api/admin", admin_plan_routes())
+        // --- Plans and templates, of the default workspace --- //
+        .nest("/api", plan_routes())
+        // --- Workspaces --- //
+        .route("/api/workspaces", get(list_workspaces_handler))
+        // --- UI --- //
+        .route("/ui", get(list_plans_ui_handler)) // New route for listing plans
+        .route("/ui/:id", get(ui_handler)) // Specific plan UI using ID
+        .route("/ui/:id/task/:index", get(task_link_handler))
+        .route("/ui/:id/print", get(print_handler))
+        .route("/ui/:id/embed", get(embed_handler))
+        .route("/join/:code", get(join_handler))
+        .route("/ui/events/:id", get(events_handler)); // ID-scoped events
+
+    // Each workspace serves the same plan and admin routes on a core of its own
+    for (name, workspace_core) in reloader.workspaces() {
+        app = app
+            .nest(
+                &format!("/api/workspaces/{name}"),
+                plan_routes().with_state(workspace_core.clone()),
             )
-                .into_response(),
-        },
-        Err(e) => map_core_result_to_response::<Result<bool, String>>(Err(e)), // Use helper for PlanErrors
+            .nest(
+                &format!("/api/admin/workspaces/{name}"),
+                admin_plan_routes().with_state(workspace_core.clone()),
+            );
+    }
+
+    // Nested before the layers below, so plugin routes get auth, limits and actors too
+    for plugin in core.plugins().unwrap_or_default() {
+        app = app.nest(&format!("/api/plugins/{}", plugin.name()), plugin.routes());
+    }
+
+    let idempotency = Arc::new(IdempotencyCache::new(config.clone()));
+    app = app
+        .layer(middleware::from_fn(record_actor))
+        .layer(middleware::from_fn_with_state(
+            idempotency,
+            replay_idempotent_requests,
+        ))
+        .layer(DefaultBodyLimit::max(max_body_bytes))
+        .layer(middleware::from_fn_with_state(
+            max_body_bytes,
+            reject_oversized_bodies,
+        ))
+        .layer(middleware::from_fn_with_state(config.clone(), require_auth));
+
+    // Added after the auth layer so rejected requests are counted too
+    if metrics_enabled {
+        app = app
+            .route("/metrics", get(metrics_handler))
+            .layer(middleware::from_fn_with_state(
+                metrics.clone(),
+                count_requests,
+            ));
+    }
+
+    // Plan JSON compresses well; the UI event stream is never compressed
+    if compression_enabled {
+        app = app.layer(CompressionLayer::new());
     }
+
+    app.layer(Extension(config))
+        .layer(Extension(reloader))
+        .layer(Extension(metrics))
+        .layer(cors)
+        .with_state(core)
 }
 
-async fn move_to(
-    State(core): State<Core>,
-    Path(id): Path<u8>,
-    Json(payload): Json<MoveToRequest>,
-) -> impl IntoResponse {
-    let plan_id = models::Lease::new(id); // Use constructor
-    let response = core.move_to(&plan_id, payload.index);
-    // Handle Option<String> inside PlanResponse
-    match response {
-        Ok(plan_response) => {
-            if plan_response.inner().is_some() {
-                (StatusCode::OK, Json(ApiResponse::success(plan_response))).into_response()
-            } else {
-                (
-                    StatusCode::BAD_REQUEST, // Or NOT_FOUND?
-                    Json(ApiResponse::<PlanResponse<Option<String>>>::error(
-                        "Failed to move: Task index not found".to_string(),
+// --- Middleware --- //
+
+/// Rejects `/api` requests without the configured bearer token. `/api/admin` routes need
+/// the admin token (falling back to the auth token) and are refused when neither is set;
+/// a workspace's routes need its own token, if it has one.
+async fn require_auth(
+    State(config): State<SharedConfig>,
+    request: Request,
+    next: Next,
+) -> Response {
+    // Read the tokens per request so reloads take effect immediately
+    let path = request.uri().path();
+    let (auth_token, admin_token) = match config.read() {
+        Ok(config) => {
+            // Unknown workspaces fall through to the server's token, then a 404
+            let workspace_token = path
+                .strip_prefix("/api/workspaces/")
+                .and_then(|rest| rest.split('/').next())
+                .and_then(|name| config.workspace(name))
+                .and_then(|workspace| workspace.auth_token.clone());
+            (
+                workspace_token.or_else(|| config.auth_token.clone()),
+                config.admin_token.clone(),
+            )
+        }
+        Err(_) => (None, None),
+    };
+    let expected = if path.starts_with("/api/admin/") {
+        match admin_token.or(auth_token) {
+            Some(token) => token,
+            None => {
+                return (
+                    StatusCode::FORBIDDEN,
+                    Json(ApiResponse::<()>::error(
+                        "Admin API is disabled; set admin_token or auth_token".to_string(),
                     )),
                 )
                     .into_response()
             }
         }
-        Err(e) => map_core_result_to_response::<Option<String>>(Err(e)), // Use helper for PlanErrors
+    } else if path.starts_with("/api/") {
+        match auth_token {
+            Some(token) => token,
+            None => return next.run(request).await,
+        }
+    } else {
+        return next.run(request).await;
+    };
+
+    let provided = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    if provided == Some(expected.as_str()) {
+        next.run(request).await
+    } else {
+        (
+            StatusCode::UNAUTHORIZED,
+            Json(ApiResponse::<()>::error(
+                "Missing or invalid bearer token".to_string(),
+            )),
+        )
+            .into_response()
     }
 }
 
-async fn remove_task_handler(
-    State(core): State<Core>,
-    Path((id, index_str)): Path<(u8, String)>, // Extract id (u8) and index string
-) -> impl IntoResponse {
-    // Parse the index string (from the wildcard path)
-    let index = match parse_index(&index_str) {
-        Ok(idx) => idx,
-        Err(e) => {
-            return (
-                StatusCode::BAD_REQUEST,
-                Json(ApiResponse::<()>::error(format!(
-                    "Invalid index format: {e}"
-                ))),
-            )
-                .into_response();
-        }
+/// Header naming the agent behind a request, recorded in the plan's transition history
+pub const AGENT_HEADER: &str = "x-scatterbrain-agent";
+/// Header carrying the request's ID; one is generated when the client sends none
+const REQUEST_ID_HEADER: &str = "x-request-id";
+/// `User-Agent` prefix of the scatterbrain CLI's HTTP client
+pub const CLI_USER_AGENT_PREFIX: &str = "scatterbrain/";
+
+/// Attributes the transitions logged while handling a request to its caller, and echoes the
+/// request ID back in the response
+async fn record_actor(request: Request, next: Next) -> Response {
+    let headers = request.headers();
+    let header = |name: &str| {
+        headers
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .filter(|value| !value.is_empty())
+            .map(str::to_string)
+    };
+    let source = match header(header::USER_AGENT.as_str()) {
+        Some(agent) if agent.starts_with(CLI_USER_AGENT_PREFIX) => models::ActorSource::Cli,
+        _ => models::ActorSource::Http,
+    };
+    let request_id =
+        header(REQUEST_ID_HEADER).unwrap_or_else(|| format!("{:016x}", rand::random::<u64>()));
+    let actor = models::ActorInfo {
+        source,
+        agent: header(AGENT_HEADER),
+        request_id: Some(request_id.clone()),
     };
 
-    let plan_id = models::Lease::new(id); // Use constructor
-    let response = core.remove_task(&plan_id, index);
-    // Simplify: Use the mapping helper directly instead of custom match logic
-    map_core_result_to_response::<Result<models::Task, String>>(response)
+    let mut response = actor.scope(next.run(request)).await;
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    response
 }
 
-// --- Notes Handlers --- //
+/// Answers requests whose declared `Content-Length` exceeds the body limit with a JSON
+/// `413` before reading them. Bodies without a length are cut off by [`DefaultBodyLimit`].
+async fn reject_oversized_bodies(
+    State(max_body_bytes): State<usize>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let length = request
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<usize>().ok());
+    match length {
+        Some(length) if length > max_body_bytes && !is_archive_import(request.uri().path()) => (
+            StatusCode::PAYLOAD_TOO_LARGE,
+            Json(ApiResponse::<()>::error(format!(
+                "Request body is {length} bytes, the limit is {max_body_bytes}"
+            ))),
+        )
+            .into_response(),
+        _ => next.run(request).await,
+    }
+}
 
-async fn get_notes_handler(
-    State(core): State<Core>,
-    Path((id, index_str)): Path<(u8, String)>, // Extract id (u8) and index string
-) -> impl IntoResponse {
-    let index = match parse_index(&index_str) {
-        Ok(idx) => idx,
-        Err(e) => {
-            // Return error using the standard helper, ensuring consistency
-            // The type parameter here doesn't matter much as data will be None
-            return map_core_result_to_response::<()>(Err(PlanError::Internal(format!(
-                "Invalid index format: {e}"
-            ))));
-        }
-    };
-    let plan_id = models::Lease::new(id);
+/// Whether `path` imports a backup archive, into the default workspace or another one
+fn is_archive_import(path: &str) -> bool {
+    path.strip_prefix("/api/admin/").is_some_and(|rest| {
+        rest == "import" || (rest.starts_with("workspaces/") && rest.ends_with("/import"))
+    })
+}
 
-    // Call core logic
-    let response = core.get_task_notes(&plan_id, index);
+/// Header clients set on POST requests to make retries safe
+const IDEMPOTENCY_KEY: &str = "idempotency-key";
+/// Header marking a response as a replay of an earlier one
+const IDEMPOTENT_REPLAYED: &str = "idempotent-replayed";
 
-    // Pass the result directly to the standard mapping function
-    map_core_result_to_response(response)
+/// Responses to POST requests that carried an `Idempotency-Key`, kept for the configured
+/// window and up to `limits.max_idempotency_keys` at a time
+struct IdempotencyCache {
+    config: SharedConfig,
+    entries: Mutex<IdempotencyEntries>,
 }
 
-async fn set_notes_handler(
-    State(core): State<Core>,
-    Path((id, index_str)): Path<(u8, String)>,
-    Json(payload): Json<SetTaskNotesRequest>,
-) -> impl IntoResponse {
-    let index = match parse_index(&index_str) {
-        Ok(idx) => idx,
-        Err(e) => {
-            return (
-                StatusCode::BAD_REQUEST,
-                Json(ApiResponse::<()>::error(format!(
-                    "Invalid index format: {e}"
-                ))),
-            )
-                .into_response();
+/// A client's key: the same key sent with other credentials or to another workspace is a
+/// different key, so one caller can't be replayed another's responses
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct IdempotencyScope {
+    /// Hash of the request's `Authorization` header
+    principal: u64,
+    /// Workspace the request was for; `None` for the default one
+    workspace: Option<String>,
+    key: String,
+}
+
+impl IdempotencyScope {
+    fn of(request: &Request, key: String) -> Self {
+        let mut hasher = DefaultHasher::new();
+        request
+            .headers()
+            .get(header::AUTHORIZATION)
+            .map(HeaderValue::as_bytes)
+            .hash(&mut hasher);
+        let workspace = request
+            .uri()
+            .path()
+            .strip_prefix("/api/workspaces/")
+            .and_then(|rest| rest.split('/').next())
+            .map(str::to_string);
+        Self {
+            principal: hasher.finish(),
+            workspace,
+            key,
         }
-    };
-    let plan_id = models::Lease::new(id);
-    let response = core.set_task_notes(&plan_id, index, payload.notes);
-    // Handle Result<(), String> inside PlanResponse
-    match response {
-        Ok(plan_response) => match plan_response.inner() {
-            Ok(_) => (StatusCode::OK, Json(ApiResponse::success(plan_response))).into_response(),
-            Err(e) => (
-                StatusCode::BAD_REQUEST, // e.g., task not found
-                Json(ApiResponse::<PlanResponse<Result<(), String>>>::error(
-                    e.clone(),
-                )),
-            )
-                .into_response(),
-        },
-        Err(e) => map_core_result_to_response::<Result<(), String>>(Err(e)),
     }
 }
 
-async fn delete_notes_handler(
-    State(core): State<Core>,
-    Path((id, index_str)): Path<(u8, String)>,
-) -> impl IntoResponse {
-    let index = match parse_index(&index_str) {
-        Ok(idx) => idx,
-        Err(e) => {
-            // Consistent error handling for invalid index
-            return (
-                StatusCode::BAD_REQUEST,
-                Json(ApiResponse::<()>::error(format!(
-                    "Invalid index format: {e}"
-                ))),
-            )
-                .into_response();
+#[derive(Default)]
+struct IdempotencyEntries {
+    entries: HashMap<IdempotencyScope, IdempotencyEntry>,
+    /// Keys in the order they were stored, so the oldest are dropped first; a key is queued
+    /// again when its response is stored, and entries whose `stored_at` no longer matches
+    /// are skipped
+    expiry: VecDeque<(Instant, IdempotencyScope)>,
+}
+
+impl IdempotencyEntries {
+    /// Drops the entries older than `window`, then the oldest ones until fewer than
+    /// `capacity` are left
+    fn evict(&mut self, window: Duration, capacity: usize) {
+        while let Some((stored_at, scope)) = self.expiry.front() {
+            let stale = self
+                .entries
+                .get(scope)
+                .is_none_or(|entry| entry.stored_at != *stored_at);
+            if !stale && stored_at.elapsed() < window && self.entries.len() < capacity {
+                break;
+            }
+            let (_, scope) = self.expiry.pop_front().expect("front exists");
+            if !stale {
+                self.entries.remove(&scope);
+            }
         }
-    };
-    let plan_id = models::Lease::new(id);
-    let response = core.delete_task_notes(&plan_id, index);
-    // Handle Result<(), String> inside PlanResponse
-    match response {
-        Ok(plan_response) => match plan_response.inner() {
-            Ok(_) => (StatusCode::OK, Json(ApiResponse::success(plan_response))).into_response(),
-            Err(e) => (
-                StatusCode::BAD_REQUEST, // e.g., task not found
-                Json(ApiResponse::<PlanResponse<Result<(), String>>>::error(
-                    e.clone(),
-                )),
-            )
-                .into_response(),
-        },
-        Err(e) => map_core_result_to_response::<Result<(), String>>(Err(e)),
+    }
+
+    fn store(&mut self, scope: IdempotencyScope, entry: IdempotencyEntry) {
+        self.expiry.push_back((entry.stored_at, scope.clone()));
+        self.entries.insert(scope, entry);
     }
 }
 
-// --- UI and Event Handlers (Updated for PlanId) --- //
+struct IdempotencyEntry {
+    /// Hash of the method, path and body the key was first used with
+    fingerprint: u64,
+    stored_at: Instant,
+    /// `None` while the first request is still being handled
+    response: Option<CachedResponse>,
+}
 
-async fn events_handler(
-    State(core): State<Core>,
-    Path(id): Path<u8>, // Accept u8 ID from path
-) -> impl IntoResponse {
-    let receiver = core.subscribe();
-    // Pass the specific PlanId to the EventStream
-    let plan_id = models::Lease::new(id); // Use constructor
-    let stream = EventStream::new(core.clone(), receiver, plan_id);
+#[derive(Clone)]
+struct CachedResponse {
+    status: StatusCode,
+    content_type: Option<HeaderValue>,
+    body: axum::body::Bytes,
+}
 
-    // Set headers for event stream
-    let headers = [
-        (
-            axum::http::header::CONTENT_TYPE,
-            axum::http::HeaderValue::from_static("text/event-stream"),
-        ),
-        (
-            axum::http::header::CACHE_CONTROL,
-            axum::http::HeaderValue::from_static("no-cache"),
-        ),
-    ];
-
-    // Return response with headers and stream body
-    (headers, axum::body::Body::from_stream(stream))
+impl CachedResponse {
+    fn replay(self) -> Response {
+        let mut response = (self.status, self.body).into_response();
+        let headers = response.headers_mut();
+        match self.content_type {
+            Some(content_type) => headers.insert(header::CONTENT_TYPE, content_type),
+            None => headers.remove(header::CONTENT_TYPE),
+        };
+        headers.insert(IDEMPOTENT_REPLAYED, HeaderValue::from_static("true"));
+        response
+    }
 }
 
-struct EventStream {
-    core: Core,
-    receiver: tokio::sync::broadcast::Receiver<models::PlanId>,
-    plan_id: models::PlanId,
+/// What to do with a request carrying an idempotency key
+enum Claim {
+    /// First use of the key: handle the request and store the response
+    New,
+    Replay(CachedResponse),
+    InProgress,
+    Mismatch,
 }
 
-impl EventStream {
-    // Accept and store the plan_id
-    fn new(
-        core: Core,
-        receiver: tokio::sync::broadcast::Receiver<models::PlanId>,
-        plan_id: models::PlanId,
-    ) -> Self {
+impl IdempotencyCache {
+    fn new(config: SharedConfig) -> Self {
         Self {
-            core,
-            receiver,
-            plan_id,
+            config,
+            entries: Mutex::new(IdempotencyEntries::default()),
         }
     }
-}
 
-impl Stream for EventStream {
-    type Item = Result<String, Infallible>;
+    /// How long responses are kept, and how many at most
+    fn bounds(&self) -> (Duration, usize) {
+        self.config
+            .read()
+            .map(|config| {
+                (
+                    Duration::from_secs(config.idempotency_window_secs),
+                    config.limits.max_idempotency_keys,
+                )
+            })
+            .unwrap_or_default()
+    }
 
-    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        // Try to receive from the broadcast channel with a non-blocking approach
-        match self.receiver.try_recv() {
-            Ok(id) => {
-                if id == self.plan_id {
-                    // Successfully received an update notification, send event to client
-                    Poll::Ready(Some(Ok("event: update\ndata: change\n\n".to_string())))
-                } else {
-                    Poll::Pending
-                }
-            }
-            Err(tokio::sync::broadcast::error::TryRecvError::Empty) => {
-                // No updates available now, register the waker to be notified later
-                // Create a task to wake this future when the receiver might have data
-                let waker = cx.waker().clone();
-                tokio::spawn(async move {
-                    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
-                    waker.wake();
-                });
-                Poll::Pending
-            }
-            Err(tokio::sync::broadcast::error::TryRecvError::Lagged(_)) => {
-                // Some messages were missed, but that's okay
-                // Just notify the client that there was a change
-                Poll::Ready(Some(Ok("event: update\ndata: change\n\n".to_string())))
-            }
-            Err(tokio::sync::broadcast::error::TryRecvError::Closed) => {
-                // Channel closed, try to resubscribe
-                self.receiver = self.core.subscribe();
-                Poll::Pending
+    fn claim(
+        &self,
+        scope: &IdempotencyScope,
+        fingerprint: u64,
+        (window, capacity): (Duration, usize),
+    ) -> Claim {
+        let Ok(mut entries) = self.entries.lock() else {
+            return Claim::New;
+        };
+        entries.evict(window, usize::MAX);
+        match entries.entries.get(scope) {
+            Some(entry) if entry.fingerprint != fingerprint => Claim::Mismatch,
+            Some(entry) => match &entry.response {
+                Some(response) => Claim::Replay(response.clone()),
+                None => Claim::InProgress,
+            },
+            None => {
+                entries.evict(window, capacity);
+                entries.store(
+                    scope.clone(),
+                    IdempotencyEntry {
+                        fingerprint,
+                        stored_at: Instant::now(),
+                        response: None,
+                    },
+                );
+                Claim::New
             }
         }
     }
+
+    /// Stores the response for `scope`, or forgets the key so a retry runs again
+    fn finish(&self, scope: &IdempotencyScope, response: Option<CachedResponse>) {
+        let Ok(mut entries) = self.entries.lock() else {
+            return;
+        };
+        let Some(entry) = entries.entries.remove(scope) else {
+            return;
+        };
+        if let Some(response) = response {
+            entries.store(
+                scope.clone(),
+                IdempotencyEntry {
+                    stored_at: Instant::now(),
+                    response: Some(response),
+                    ..entry
+                },
+            );
+        }
+    }
 }
 
-// TODO: Update ui_handler to accept token and render for that plan
-async fn ui_handler(State(core): State<Core>, Path(id): Path<u8>) -> impl IntoResponse {
-    // Fetch all plan IDs for tabs
-    let all_ids = match core.list_plans() {
-        Ok(ids) => ids,
-        Err(e) => {
-            return Html(format!("<h1>Error loading plan list: {e}</h1>")).into_response();
+/// Replays the stored response when a POST is retried with the same `Idempotency-Key`, so a
+/// client that timed out can retry without adding a task twice. Reusing a key for a different
+/// request is refused with `422`, and retrying while the first request is still running with
+/// `409`. Server errors are not stored, and admin routes are never replayed.
+async fn replay_idempotent_requests(
+    State(cache): State<Arc<IdempotencyCache>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let (window, capacity) = cache.bounds();
+    let key = request
+        .headers()
+        .get(IDEMPOTENCY_KEY)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    let path = request.uri().path().to_string();
+    let scope = match key {
+        Some(key)
+            if request.method() == axum::http::Method::POST
+                && !window.is_zero()
+                && capacity > 0
+                && path.starts_with("/api/")
+                && !path.starts_with("/api/admin/") =>
+        {
+            IdempotencyScope::of(&request, key)
         }
+        _ => return next.run(request).await,
     };
-    let current_plan_id = models::Lease::new(id); // Use constructor
 
-    // Fetch data for the requested plan id
-    match core.get_plan(&current_plan_id) {
-        Ok(plan_response) => {
-            let plan = plan_response.inner();
-            // Fetch current and distilled for the current plan id
-            let current = core
-                .current(&current_plan_id)
-                .ok()
-                .and_then(|pr| pr.into_inner());
-            let distilled_context_res = core.distilled_context(&current_plan_id);
+    let (parts, body) = request.into_parts();
+    let limit = parts
+        .extensions
+        .get::<SharedConfig>()
+        .and_then(|config| config.read().ok().map(|c| c.limits.max_body_bytes))
+        .unwrap_or(usize::MAX);
+    let body = match axum::body::to_bytes(body, limit).await {
+        Ok(body) => body,
+        Err(e) => {
+            return (
+                StatusCode::PAYLOAD_TOO_LARGE,
+                Json(ApiResponse::<()>::error(e.to_string())),
+            )
+                .into_response()
+        }
+    };
+    let mut hasher = DefaultHasher::new();
+    parts.method.as_str().hash(&mut hasher);
+    path.hash(&mut hasher);
+    body.hash(&mut hasher);
+    let fingerprint = hasher.finish();
 
-            match distilled_context_res {
-                Ok(distilled_response) => {
-                    let distilled_context = distilled_response.context(); // Extract the context
-                    Html(render_ui_template(
-                        &current_plan_id, // Pass current PlanId
-                        &all_ids,         // Pass all PlanIds
-                        plan,
-                        current.as_ref(),
-                        &distilled_context,
-                    ))
-                    .into_response()
-                }
-                Err(e) => {
-                    // Handle error fetching distilled context for the specific plan
-                    Html(format!(
-                        "<h1>Error loading context for plan {current_plan_id:?}: {e}</h1>"
-                    ))
-                    .into_response()
-                }
-            }
+    match cache.claim(&scope, fingerprint, (window, capacity)) {
+        Claim::New => {}
+        Claim::Replay(response) => return response.replay(),
+        Claim::InProgress => {
+            return (
+                StatusCode::CONFLICT,
+                Json(ApiResponse::<()>::error(format!(
+                    "A request with Idempotency-Key '{}' is still in progress",
+                    scope.key
+                ))),
+            )
+                .into_response()
         }
-        Err(PlanError::PlanNotFound(_)) => {
-            Html(format!("<h1>Plan {current_plan_id:?} not found</h1>")).into_response()
+        Claim::Mismatch => {
+            return (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Json(ApiResponse::<()>::error(format!(
+                    "Idempotency-Key '{}' was already used for a different request",
+                    scope.key
+                ))),
+            )
+                .into_response()
         }
+    }
+
+    let response = next
+        .run(Request::from_parts(parts, axum::body::Body::from(body)))
+        .await;
+    if response.status().is_server_error() {
+        cache.finish(&scope, None);
+        return response;
+    }
+    let (parts, body) = response.into_parts();
+    let body = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(body) => body,
         Err(e) => {
-            // Handle other errors fetching the plan itself
-            Html(format!(
-                "<h1>Error loading plan {current_plan_id:?}: {e}</h1>"
-            ))
-            .into_response()
+            cache.finish(&scope, None);
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse::<()>::error(e.to_string())),
+            )
+                .into_response();
         }
-    }
+    };
+    cache.finish(
+        &scope,
+        Some(CachedResponse {
+            status: parts.status,
+            content_type: parts.headers.get(header::CONTENT_TYPE).cloned(),
+            body: body.clone(),
+        }),
+    );
+    Response::from_parts(parts, axum::body::Body::from(body))
 }
 
-// --- Template Rendering (Needs Update for PlanId) --- //
+// --- Payload Validation --- //
 
-fn render_ui_template(
-    current_plan_id: &models::PlanId,
-    all_ids: &[models::PlanId],
-    plan: &crate::models::Plan,
-    current: Option<&crate::models::Current>,
-    distilled_context: &crate::models::DistilledContext,
-) -> String {
-    let mut html = String::from(HTML_TEMPLATE_HEADER);
+/// Request bodies whose fields are checked against the configured [`RequestLimits`]
+trait Validate {
+    fn validate(&self, limits: &RequestLimits) -> Result<(), String>;
+}
 
-    // --- Plan Tab Navigation ---
-    html.push_str("<nav class='plan-tabs'>");
-    if all_ids.is_empty() {
-        html.push_str("<span class='no-plans'>No plans loaded.</span>");
-    } else {
-        for id in all_ids {
-            let class = if id == current_plan_id { "active" } else { "" };
-            // Use id.value() for the URL and display text
-            html.push_str(&format!(
-                "<a href='/ui/{}' class='{}'>Plan {}</a>&nbsp;",
-                id.value(),
-                class,
-                id.value()
-            ));
-        }
-    }
-    html.push_str("</nav>");
-    // --- End Plan Tab Navigation ---
+/// JSON body extractor that rejects payloads over the field limits with `422`, and
+/// malformed or oversized bodies with a JSON error instead of plain text
+struct ValidJson<T>(T);
 
-    // --- Display Plan Goal ---
-    if let Some(goal) = &plan.goal {
-        html.push_str(&format!(
-            "<div class='plan-goal'><h2>Goal: {}</h2></div>",
-            html_escape::encode_text(goal)
-        ));
+#[async_trait]
+impl<S, T> FromRequest<S> for ValidJson<T>
+where
+    S: Send + Sync,
+    T: serde::de::DeserializeOwned + Validate,
+{
+    type Rejection = Response;
+
+    async fn from_request(request: Request, state: &S) -> Result<Self, Self::Rejection> {
+        // Read the limits per request so reloads take effect immediately
+        let limits = request
+            .extensions()
+            .get::<SharedConfig>()
+            .and_then(|config| config.read().ok().map(|config| config.limits.clone()))
+            .unwrap_or_default();
+        let Json(payload) = Json::<T>::from_request(request, state)
+            .await
+            .map_err(|rejection| {
+                (
+                    rejection.status(),
+                    Json(ApiResponse::<()>::error(rejection.body_text())),
+                )
+                    .into_response()
+            })?;
+        payload.validate(&limits).map_err(|message| {
+            (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Json(ApiResponse::<()>::error(message)),
+            )
+                .into_response()
+        })?;
+        Ok(Self(payload))
     }
-    // --- End Display Plan Goal ---
+}
 
-    // --- Display Plan Notes ---
-    if let Some(notes) = &plan.notes {
-        html.push_str("<div class='plan-notes'>");
-        html.push_str("<h3>Notes:</h3>");
-        let formatted_notes = html_escape::encode_text(notes).replace("\n", "<br>");
-        html.push_str(&format!("<p>{formatted_notes}</p>"));
-        html.push_str("</div>");
+impl Validate for AddTaskRequest {
+    fn validate(&self, limits: &RequestLimits) -> Result<(), String> {
+        limits.check_description("description", &self.description)?;
+        if let Some(notes) = &self.notes {
+            limits.check_notes("notes", notes)?;
+        }
+        Ok(())
     }
-    // --- End Display Plan Notes ---
+}
 
-    // Add level legend
-    html.push_str("<div class='level-legend'>");
-    html.push_str("<h3>Abstraction Levels</h3>");
+impl Validate for AddTaskFromTemplateRequest {
+    fn validate(&self, limits: &RequestLimits) -> Result<(), String> {
+        limits.check_batch("params", self.params.len())?;
+        for value in self.params.values() {
+            limits.check_description("param value", value)?;
+        }
+        Ok(())
+    }
+}
 
-    for (i, level) in plan.levels().iter().enumerate() {
-        html.push_str(&format!(
-            "<div class='level-item'><span class='task-level level-{i}'>{i}</span>"
-        ));
-        html.push_str(&format!(
-            "<div class='level-description'><strong>{}</strong>",
-            level.description()
-        ));
-        html.push_str(&format!(
-            "<div class='level-focus'>{}</div></div></div>",
-            level.abstraction_focus()
-        ));
+impl Validate for models::TaskTemplate {
+    fn validate(&self, limits: &RequestLimits) -> Result<(), String> {
+        limits.check_description("description", &self.description)?;
+        if let Some(notes) = &self.notes {
+            limits.check_notes("notes", notes)?;
+        }
+        limits.check_batch("checklist", self.checklist.len())?;
+        for item in &self.checklist {
+            limits.check_description("checklist item", item)?;
+        }
+        Ok(())
     }
-    html.push_str("</div>");
+}
 
-    // Add plan data
-    html.push_str("<div class='plan-section'>");
-    html.push_str("<h2>Plan</h2>");
+impl Validate for SetUsageSummaryRequest {
+    fn validate(&self, limits: &RequestLimits) -> Result<(), String> {
+        if let Some(usage_summary) = &self.usage_summary {
+            limits.check_notes("usage_summary", usage_summary)?;
+        }
+        Ok(())
+    }
+}
 
-    // Render tasks hierarchically
-    render_tasks_html(&mut html, plan.root().subtasks(), current, plan, Vec::new());
+impl Validate for CreatePlanRequest {
+    fn validate(&self, limits: &RequestLimits) -> Result<(), String> {
+        limits.check_description("prompt", &self.prompt)?;
+        if let Some(notes) = &self.notes {
+            limits.check_notes("notes", notes)?;
+        }
+        Ok(())
+    }
+}
 
-    html.push_str("</div>");
+impl Validate for PlanSpec {
+    fn validate(&self, limits: &RequestLimits) -> Result<(), String> {
+        limits.check_description("goal", &self.goal)?;
+        if let Some(notes) = &self.notes {
+            limits.check_notes("notes", notes)?;
+        }
+        if let Some(usage_summary) = &self.usage_summary {
+            limits.check_notes("usage_summary", usage_summary)?;
+        }
+        if let Some(levels) = &self.levels {
+            limits.check_batch("levels", levels.len())?;
+        }
+        limits.check_batch("tasks", self.tasks.len())?;
+        for task in &self.tasks {
+            task.validate(limits)?;
+        }
+        Ok(())
+    }
+}
 
-    // Add current task highlight if exists
-    if let Some(curr) = current {
-        html.push_str("<div class='current-section'>");
-        html.push_str("<h2>Current Task</h2>");
-        html.push_str(&format!(
-            "<div class='current-task'><h3>{}</h3>",
-            curr.task.description()
-        ));
-        html.push_str(&format!(
-            "<p><strong>Status:</strong> {}</p>",
-            if curr.task.is_completed() {
-                "Completed"
-            } else {
-                "In Progress"
-            }
-        ));
-        html.push_str(&format!(
-            "<p><strong>Level:</strong> {} - {}</p>",
-            curr.task.level_index().unwrap_or(curr.index.len() - 1),
-            curr.level.description()
-        ));
-        html.push_str(&format!(
-            "<p><strong>Index:</strong> {}</p>",
-            curr.index
+impl Validate for TaskSpec {
+    fn validate(&self, limits: &RequestLimits) -> Result<(), String> {
+        for (_, task) in self.tasks() {
+            limits.check_description("description", &task.description)?;
+            limits.check_batch("tasks", task.tasks.len())?;
+            limits.check_batch("sections", task.sections.len())?;
+            let notes = task
+                .notes
                 .iter()
-                .map(|i| i.to_string())
-                .collect::<Vec<_>>()
-                .join(",")
-        ));
-
-        // Show subtasks if any
-        if !curr.task.subtasks().is_empty() {
-            html.push_str("<div class='subtasks'>");
-            html.push_str("<h4>Subtasks:</h4>");
-            html.push_str("<ul>");
-            for subtask in curr.task.subtasks() {
-                let status_class = if subtask.is_completed() {
-                    "completed"
-                } else {
-                    "pending"
-                };
-                html.push_str(&format!(
-                    "<li class='{}'>{}</li>",
-                    status_class,
-                    subtask.description()
-                ));
+                .chain(&task.summary)
+                .chain(task.sections.values());
+            for text in notes {
+                limits.check_notes("notes", text)?;
             }
-            html.push_str("</ul>");
-            html.push_str("</div>");
         }
+        Ok(())
+    }
+}
 
-        html.push_str("</div></div>");
+impl Validate for ImportSubtreeRequest {
+    fn validate(&self, limits: &RequestLimits) -> Result<(), String> {
+        self.subtree.validate(limits)
     }
+}
 
-    // Add History Panel (moved inside the container)
-    html.push_str("<div class='history-panel'>");
-    html.push_str("<h2>Transition History</h2>");
-    html.push_str("<ul class='history-list'>");
-    if distilled_context.transition_history.is_empty() {
-        html.push_str("<li>No history yet.</li>");
-    } else {
-        // Iterate in reverse to show newest first
-        for entry in distilled_context.transition_history.iter().rev() {
-            let timestamp_str = entry.timestamp.format("%Y-%m-%d %H:%M:%S UTC").to_string();
-            let details_str = entry.details.as_deref().unwrap_or("");
-            html.push_str(&format!(
-                "<li class='history-item'><span class='history-ts'>{}</span><span class='history-action'>{}</span><span class='history-details'>{}</span></li>",
-                timestamp_str,
-                entry.action,
-                details_str
-            ));
+impl Validate for CompleteTaskRequest {
+    fn validate(&self, limits: &RequestLimits) -> Result<(), String> {
+        match &self.summary {
+            Some(summary) => limits.check_notes("summary", summary),
+            None => Ok(()),
         }
     }
-    html.push_str("</ul></div>");
+}
 
-    // Embed the current plan id value for use in JavaScript
-    html.push_str(&format!(
-        "<script>const CURRENT_PLAN_ID = {};</script>",
-        current_plan_id.value()
-    ));
+impl Validate for CompleteCurrentRequest {
+    fn validate(&self, limits: &RequestLimits) -> Result<(), String> {
+        match &self.summary {
+            Some(summary) => limits.check_notes("summary", summary),
+            None => Ok(()),
+        }
+    }
+}
 
-    html.push_str(HTML_TEMPLATE_FOOTER); // Footer now only contains closing tags and script
-    html
+impl Validate for RequestReviewRequest {
+    fn validate(&self, limits: &RequestLimits) -> Result<(), String> {
+        limits.check_notes("summary", &self.summary)
+    }
 }
 
-// Helper function to render tasks hierarchically
-fn render_tasks_html(
-    html: &mut String,
-    tasks: &[crate::models::Task],
-    current: Option<&crate::models::Current>,
-    _plan: &crate::models::Plan,
-    path: Vec<usize>,
-) {
-    if tasks.is_empty() {
-        html.push_str("<p>No tasks yet.</p>");
-        return;
+impl Validate for RejectTaskRequest {
+    fn validate(&self, limits: &RequestLimits) -> Result<(), String> {
+        match &self.reason {
+            Some(reason) => limits.check_notes("reason", reason),
+            None => Ok(()),
+        }
     }
+}
 
-    html.push_str("<ul class='task-tree'>");
-    for (i, task) in tasks.iter().enumerate() {
-        let mut current_path = path.clone();
-        current_path.push(i);
-
-        // Check if this is the current task
-        let is_current = if let Some(curr) = current {
-            curr.index == current_path
-        } else {
-            false
-        };
+impl Validate for AddQuestionRequest {
+    fn validate(&self, limits: &RequestLimits) -> Result<(), String> {
+        limits.check_description("question", &self.question)
+    }
+}
 
-        // Determine the effective level (explicit or derived from position)
-        let level_idx = task.level_index().unwrap_or(current_path.len());
+impl Validate for AnswerQuestionRequest {
+    fn validate(&self, limits: &RequestLimits) -> Result<(), String> {
+        limits.check_notes("answer", &self.answer)
+    }
+}
 
-        let class = if is_current {
-            if task.is_completed() {
-                "current completed"
-            } else {
-                "current"
+impl Validate for UpdatePlanRequest {
+    fn validate(&self, limits: &RequestLimits) -> Result<(), String> {
+        if let Some(goal) = &self.goal {
+            if goal.trim().is_empty() {
+                return Err("goal must not be empty".to_string());
             }
-        } else if task.is_completed() {
-            "completed"
-        } else {
-            ""
-        };
+            limits.check_description("goal", goal)?;
+        }
+        if let Some(notes) = &self.notes {
+            limits.check_notes("notes", notes)?;
+        }
+        Ok(())
+    }
+}
 
-        html.push_str(&format!("<li class='{class}'><div class='task-item'>"));
+impl Validate for UpdateTaskRequest {
+    fn validate(&self, limits: &RequestLimits) -> Result<(), String> {
+        limits.check_description("description", &self.description)
+    }
+}
 
-        // Level indicator
-        html.push_str(&format!(
-            "<span class='task-level level-{level_idx}'>{level_idx}</span>"
-        ));
+impl Validate for SetTaskNotesRequest {
+    fn validate(&self, limits: &RequestLimits) -> Result<(), String> {
+        limits.check_notes("notes", &self.notes)
+    }
+}
 
-        // Path identifier (e.g., 0.1.2)
-        html.push_str(&format!(
-            "<span class='task-path'>{}</span>",
-            current_path
-                .iter()
-                .map(|i| i.to_string())
-                .collect::<Vec<_>>()
-                .join(".")
-        ));
+impl Validate for SetNoteSectionRequest {
+    fn validate(&self, limits: &RequestLimits) -> Result<(), String> {
+        limits.check_description("section", &self.section)?;
+        match &self.content {
+            Some(content) => limits.check_notes("content", content),
+            None => Ok(()),
+        }
+    }
+}
 
-        // Task description
-        html.push_str(&format!(
-            "<span class='task-desc'>{}</span>",
-            task.description()
-        ));
+// --- Workspace Handlers --- //
 
-        // Add completion summary if available
-        if task.is_completed() {
-            if let Some(summary) = task.completion_summary() {
-                html.push_str(&format!("<span class='task-summary'>{summary}</span>"));
-            }
-        }
+/// Lists the names of the configured workspaces
+async fn list_workspaces_handler(Extension(reloader): Extension<ConfigReloader>) -> Response {
+    let names: Vec<String> = reloader.workspaces().keys().cloned().collect();
+    (StatusCode::OK, Json(ApiResponse::success(names))).into_response()
+}
 
-        // Task status
-        html.push_str(&format!(
-            "<span class='task-status'>{}</span>",
-            if task.is_completed() { "✓" } else { "○" }
-        ));
+// --- Admin Handlers --- //
 
-        html.push_str("</div>"); // Close task-item div
+/// Re-reads the config file and applies the settings that don't need a restart
+async fn reload_config_handler(Extension(reloader): Extension<ConfigReloader>) -> Response {
+    let result = reloader.reload();
+    log_reload(&result);
+    match result {
+        Ok(report) => (StatusCode::OK, Json(ApiResponse::success(report))).into_response(),
+        Err(ConfigError::NoSource) => (
+            StatusCode::CONFLICT,
+            Json(ApiResponse::<ReloadReport>::error(
+                ConfigError::NoSource.to_string(),
+            )),
+        )
+            .into_response(),
+        Err(e) => (
+            StatusCode::BAD_REQUEST,
+            Json(ApiResponse::<ReloadReport>::error(e.to_string())),
+        )
+            .into_response(),
+    }
+}
 
-        // Render notes if they exist
-        if let Some(notes) = task.notes() {
-            // Escape HTML entities in notes
-            let escaped_notes = html_escape::encode_text(notes);
-            html.push_str(&format!("<div class='task-notes'>{escaped_notes}</div>"));
+/// Reports the quotas in effect and how much of them the plans use, per workspace
+async fn quotas_handler(Extension(reloader): Extension<ConfigReloader>) -> Response {
+    let overview = || -> Result<QuotaOverview, PlanError> {
+        let mut workspaces = BTreeMap::new();
+        for (name, core) in reloader.workspaces() {
+            workspaces.insert(name.clone(), core.quota_report()?);
         }
+        Ok(QuotaOverview {
+            default: reloader.core().quota_report()?,
+            workspaces,
+        })
+    };
+    map_core_result_simple(overview())
+}
 
-        // Render subtasks recursively
-        if !task.subtasks().is_empty() {
-            render_tasks_html(html, task.subtasks(), current, _plan, current_path);
+/// Returns every plan as a backup archive
+async fn export_plans_handler(State(core): State<Core>) -> Response {
+    map_core_result_simple(super::storage::export_archive(&core))
+}
+
+/// Loads every plan from a backup archive, replacing plans with the same ID. Archives
+/// from older schema versions are migrated first.
+async fn import_plans_handler(
+    State(core): State<Core>,
+    Json(document): Json<serde_json::Value>,
+) -> Response {
+    let archive: PlanArchive = match super::schema::from_value(document) {
+        Ok(archive) => archive,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(ApiResponse::<usize>::error(e.to_string())),
+            )
+                .into_response()
         }
+    };
+    map_core_result_simple(super::storage::import_archive(&core, archive))
+}
 
-        html.push_str("</li>");
-    }
-    html.push_str("</ul>");
+/// Returns a plan with its cursor, history and leases, for importing on another server
+async fn export_plan_handler(State(core): State<Core>, Path(id): Path<u8>) -> Response {
+    let plan_id = models::Lease::new(id);
+    map_core_result_simple(super::storage::export_plan(&core, &plan_id))
 }
 
-// HTML template header with CSS styles and EventSource JavaScript
-const HTML_TEMPLATE_HEADER: &str = r#"<!DOCTYPE html>
-<html lang="en">
-<head>
-    <meta charset="UTF-8">
-    <meta name="viewport" content="width=device-width, initial-scale=1.0">
-    <title>Scatterbrain UI</title>
-    <style>
-        body {
-            font-family: -apple-system, BlinkMacSystemFont, 'Segoe UI', Roboto, Oxygen, Ubuntu, Cantarell, 'Open Sans', 'Helvetica Neue', sans-serif;
-            line-height: 1.6;
-            color: #333;
-            max-width: 1200px;
-            margin: 0 auto;
-            padding: 20px;
-            background-color: #f7f9fc;
-        }
-        h1 {
-            color: #2c3e50;
-            border-bottom: 2px solid #3498db;
-            padding-bottom: 10px;
-        }
-        h2 {
-            color: #3498db;
-            margin-top: 30px;
-        }
-        .container {
-            display: flex;
-            flex-wrap: wrap;
-            gap: 20px;
-        }
-        .plan-section,
-        .current-section,
-        .history-panel {
-            flex: 1;
-            min-width: 300px;
-            background: white;
-            padding: 20px;
-            border-radius: 8px;
-            box-shadow: 0 2px 10px rgba(0,0,0,0.1);
-            align-self: flex-start;
-        }
-        .plan-section {
-            /* Specific styles for plan if needed */
+/// Creates a plan from an export under a new ID. Exports from older schema versions are
+/// migrated first.
+async fn import_plan_handler(
+    State(core): State<Core>,
+    Json(document): Json<serde_json::Value>,
+) -> Response {
+    let export: PlanExport = match super::schema::from_value(document) {
+        Ok(export) => export,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(ApiResponse::<models::PlanId>::error(e.to_string())),
+            )
+                .into_response()
         }
-        .current-section {
-            /* Specific styles for current task if needed */
-             /* Ensure it aligns even if not always present */
-             order: 1;
+    };
+    map_core_result_simple(super::storage::import_plan(&core, export))
+}
+
+/// Releases every lease on a plan
+async fn force_unlock_handler(State(core): State<Core>, Path(id): Path<u8>) -> Response {
+    let plan_id = models::Lease::new(id);
+    map_core_result_to_response(core.force_unlock(&plan_id))
+}
+
+/// Releases a plan's orphaned leases
+async fn clear_orphaned_leases_handler(State(core): State<Core>, Path(id): Path<u8>) -> Response {
+    let plan_id = models::Lease::new(id);
+    map_core_result_to_response(core.clear_orphaned_leases(&plan_id))
+}
+
+/// Drops a plan's transition log and trims its notes histories
+async fn compact_history_handler(
+    State(core): State<Core>,
+    Path(id): Path<u8>,
+    Json(payload): Json<CompactHistoryRequest>,
+) -> Response {
+    let plan_id = models::Lease::new(id);
+    map_core_result_to_response(core.compact_history(&plan_id, payload.keep_note_versions))
+}
+
+/// Request counters exposed on `/metrics`
+#[derive(Default)]
+struct Metrics {
+    requests: AtomicU64,
+}
+
+async fn count_requests(
+    State(metrics): State<Arc<Metrics>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    metrics.requests.fetch_add(1, Ordering::Relaxed);
+    next.run(request).await
+}
+
+/// Reports request and plan counters in the Prometheus text format
+async fn metrics_handler(
+    State(core): State<Core>,
+    Extension(metrics): Extension<Arc<Metrics>>,
+) -> Response {
+    let summaries = match core.list_plan_summaries() {
+        Ok(summaries) => summaries,
+        Err(e) => {
+            return (StatusCode::INTERNAL_SERVER_ERROR, format!("# error: {e}\n")).into_response()
         }
-        .history-panel {
-             order: 2;
+    };
+    let completed = summaries
+        .iter()
+        .filter(|summary| summary.status == models::PlanStatus::Completed)
+        .count();
+
+    let body = format!(
+        "# TYPE scatterbrain_http_requests_total counter\n\
+         scatterbrain_http_requests_total {}\n\
+         # TYPE scatterbrain_plans gauge\n\
+         scatterbrain_plans {}\n\
+         # TYPE scatterbrain_plans_completed gauge\n\
+         scatterbrain_plans_completed {}\n",
+        metrics.requests.load(Ordering::Relaxed),
+        summaries.len(),
+        completed,
+    );
+    ([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], body).into_response()
+}
+
+// --- Plan Management Handlers --- //
+
+async fn list_plans_handler(State(core): State<Core>) -> impl IntoResponse {
+    let result = core.list_plans();
+    map_core_result_simple(result) // Returns Vec<Lease> (PlanId)
+}
+
+// --- New UI Handler for Listing Plans --- //
+
+async fn list_plans_ui_handler(State(core): State<Core>) -> impl IntoResponse {
+    match core.list_plan_summaries() {
+        Ok(summaries) => {
+            let mut html_content = String::new();
+            html_content.push_str(
+                "<!DOCTYPE html><html lang='en'><head><meta charset='utf-8'><title>Scatterbrain Plans</title></head><body><main>",
+            );
+            html_content.push_str("<h1>Available Scatterbrain Plans</h1>");
+
+            if summaries.is_empty() {
+                html_content.push_str("<p>No plans found. Create one using the CLI: <code>scatterbrain plan create</code></p>");
+            } else {
+                html_content.push_str("<ul>");
+                let today = chrono::Utc::now().date_naive();
+                for summary in summaries {
+                    let id_val = summary.id.value();
+                    let heatmap = core
+                        .daily_activity(&summary.id, heatmap_start(today))
+                        .map(|activity| render_activity_heatmap(&activity, today))
+                        .unwrap_or_default();
+                    let goal = summary
+                        .goal
+                        .as_deref()
+                        .map(|goal| format!(" &mdash; {}", html_escape::encode_text(goal)))
+                        .unwrap_or_default();
+                    let goal = format!("{goal}{heatmap}");
+                    let badge = render_plan_badge(&summary.badge);
+                    match (summary.status, summary.completed_at) {
+                        (models::PlanStatus::Completed, Some(completed_at)) => html_content.push_str(&format!(
+                            "<li style=\"color: #27ae60;\">{badge}<a href=\"/ui/{id_val}\"><s>Plan {id_val}</s></a>{goal} &#10003; completed {}</li>",
+                            completed_at.format("%Y-%m-%d %H:%M UTC")
+                        )),
+                        (models::PlanStatus::Abandoned, _) => html_content.push_str(&format!(
+                            "<li style=\"color: #999;\">{badge}<a href=\"/ui/{id_val}\"><s>Plan {id_val}</s></a>{goal} (abandoned)</li>"
+                        )),
+                        (models::PlanStatus::Draft, _) => html_content.push_str(&format!(
+                            "<li>{badge}<a href=\"/ui/{id_val}\">Plan {id_val}</a>{goal} <em>(draft)</em></li>"
+                        )),
+                        _ => html_content.push_str(&format!(
+                            "<li>{badge}<a href=\"/ui/{id_val}\">Plan {id_val}</a>{goal}</li>"
+                        )),
+                    }
+                }
+                html_content.push_str("</ul>");
+            }
+
+            html_content.push_str("</main></body></html>");
+            Html(html_content)
         }
-        .task-tree {
-            list-style-type: none;
-            padding-left: 20px;
+        Err(e) => {
+            // Log the error on the server
+            tracing::error!("Failed to list plans for UI: {}", e);
+            // Return a user-friendly HTML error page
+            Html(format!(
+                "<!DOCTYPE html><html><head><title>Error</title></head><body><h1>Error</h1><p>Could not load plan list: {e}</p></body></html>"
+            ))
         }
-        .task-item {
-            display: flex;
-            align-items: center;
-            padding: 8px 0;
-            gap: 10px;
+    }
+}
+
+/// Weeks of activity shown per plan on the plan list
+const HEATMAP_WEEKS: i64 = 12;
+
+/// Heatmap colors, from no activity to the busiest days
+const HEATMAP_COLORS: [&str; 5] = ["#ebedf0", "#9be9a8", "#40c463", "#30a14e", "#216e39"];
+
+/// First day shown in the heatmap: the Sunday starting its first week
+fn heatmap_start(today: chrono::NaiveDate) -> chrono::NaiveDate {
+    let weekday = i64::from(today.weekday().num_days_from_sunday());
+    today - chrono::Duration::days(weekday + (HEATMAP_WEEKS - 1) * 7)
+}
+
+/// Renders a plan's changes per day as a GitHub-style calendar heatmap: an inline SVG
+/// with one column per week and one row per weekday, shaded relative to the busiest day
+fn render_activity_heatmap(
+    activity: &std::collections::BTreeMap<chrono::NaiveDate, usize>,
+    today: chrono::NaiveDate,
+) -> String {
+    const CELL: i64 = 10;
+    const STEP: i64 = CELL + 2;
+    let busiest = activity.values().copied().max().unwrap_or(0);
+    let total: usize = activity.values().sum();
+    let start = heatmap_start(today);
+
+    let mut svg = format!(
+        "<svg class='activity-heatmap' width='{}' height='{}' role='img' \
+         aria-label='{total} changes in the last {HEATMAP_WEEKS} weeks' \
+         style='display: block; margin: 4px 0 8px;'>",
+        HEATMAP_WEEKS * STEP,
+        7 * STEP
+    );
+    let mut day = start;
+    while day <= today {
+        let offset = (day - start).num_days();
+        let count = activity.get(&day).copied().unwrap_or(0);
+        // Shade 0 is reserved for days without changes
+        let shade = if count == 0 {
+            0
+        } else {
+            (count * 4).div_ceil(busiest).clamp(1, 4)
+        };
+        svg.push_str(&format!(
+            "<rect x='{}' y='{}' width='{CELL}' height='{CELL}' rx='2' fill='{}'>\
+             <title>{}: {count} change{}</title></rect>",
+            offset / 7 * STEP,
+            offset % 7 * STEP,
+            HEATMAP_COLORS[shade],
+            day.format("%Y-%m-%d"),
+            if count == 1 { "" } else { "s" }
+        ));
+        day += chrono::Duration::days(1);
+    }
+    svg.push_str("</svg>");
+    svg
+}
+
+async fn list_plan_summaries_handler(State(core): State<Core>) -> impl IntoResponse {
+    let result = core.list_plan_summaries();
+    map_core_result_simple(result)
+}
+
+async fn create_plan_handler(
+    State(core): State<Core>,
+    // Require Json extractor for the request body since prompt is now required
+    ValidJson(payload): ValidJson<CreatePlanRequest>,
+) -> impl IntoResponse {
+    // Call core.create_plan with the prompt and notes
+    let result = core.create_plan(payload.prompt, payload.notes);
+    map_core_result_simple(result) // Returns Lease (PlanId)
+}
+
+/// Creates a plan from a [`PlanSpec`], sent as YAML or JSON
+async fn create_plan_from_spec_handler(
+    State(core): State<Core>,
+    Extension(config): Extension<SharedConfig>,
+    body: String,
+) -> Response {
+    let spec = match PlanSpec::from_yaml(&body) {
+        Ok(spec) => spec,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(ApiResponse::<()>::error(format!("Invalid plan spec: {e}"))),
+            )
+                .into_response()
         }
-        .task-path {
-            font-family: monospace;
-            color: #7f8c8d;
-            min-width: 50px;
-        }
-        .task-desc {
-            flex-grow: 1;
-        }
-        .task-status {
-            color: #7f8c8d;
-            font-weight: bold;
-        }
-        .task-level {
-            display: inline-block;
-            width: 24px;
-            height: 24px;
-            border-radius: 12px;
-            color: white;
-            text-align: center;
-            line-height: 24px;
-            font-size: 12px;
-            font-weight: bold;
-            margin-right: 8px;
-        }
-        .level-0 {
-            background-color: #3498db; /* Blue - High Level */
-            border: 2px solid #2980b9;
-        }
-        .level-1 {
-            background-color: #9b59b6; /* Purple - Isolation */
-            border: 2px solid #8e44ad;
-        }
-        .level-2 {
-            background-color: #2ecc71; /* Green - Ordering */
-            border: 2px solid #27ae60;
-        }
-        .level-3 {
-            background-color: #e67e22; /* Orange - Implementation */
-            border: 2px solid #d35400;
-        }
-        .current {
-            background-color: #e8f4fc;
-            border-left: 4px solid #3498db;
-            padding-left: 10px;
-            margin-left: -14px;
-        }
-        .completed .task-status {
-            color: #27ae60;
-            text-decoration: none !important; /* Ensure status icon is never struck through */
-        }
-        .task-summary {
-            font-style: italic;
-            color: #555;
-            font-size: 0.9em;
-            margin-left: 10px;
-            flex-basis: 100%; /* Ensure summary wraps if needed */
-            order: 2; /* Place summary after main task items */
-        }
-        .current-task {
-            background-color: #f8f9fa;
-            padding: 15px;
-            border-radius: 5px;
-            border-left: 4px solid #3498db;
-        }
-        .subtasks ul {
-            margin-top: 5px;
-            padding-left: 20px;
-        }
-        .subtasks li {
-            margin-bottom: 5px;
-        }
-        .controls {
-            margin-top: 30px;
-            padding: 15px;
-            background: #f0f4f8;
-            border-radius: 5px;
-        }
-        .reactive-status {
-            display: flex;
-            align-items: center;
-            gap: 10px;
-            margin-top: 10px;
-        }
-        .status-indicator {
-            display: inline-block;
-            width: 10px;
-            height: 10px;
-            border-radius: 50%;
-            background-color: #95a5a6;
+    };
+    let limits = config
+        .read()
+        .map(|config| config.limits.clone())
+        .unwrap_or_default();
+    if let Err(message) = spec.validate(&limits) {
+        return (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(ApiResponse::<()>::error(message)),
+        )
+            .into_response();
+    }
+    map_core_result_simple(core.create_plan_from_spec(spec))
+}
+
+async fn delete_plan_handler(
+    State(core): State<Core>,
+    Path(id): Path<u8>, // Use u8 ID from path
+) -> impl IntoResponse {
+    let plan_id = models::Lease::new(id); // Use constructor
+    let result = core.delete_plan(&plan_id);
+    map_core_result_simple(result) // Use simple mapper as it returns ()
+}
+
+async fn set_plan_status_handler(
+    State(core): State<Core>,
+    Path(id): Path<u8>,
+    Json(payload): Json<SetPlanStatusRequest>,
+) -> impl IntoResponse {
+    let plan_id = models::Lease::new(id);
+    let response = core.set_plan_status(&plan_id, payload.status, payload.force);
+    map_core_result_to_response(response)
+}
+
+/// Turns tombstones for removed tasks on or off, keeping sibling indices stable
+async fn set_tombstones_handler(
+    State(core): State<Core>,
+    Path(id): Path<u8>,
+    Json(payload): Json<SetTombstonesRequest>,
+) -> Response {
+    let plan_id = models::Lease::new(id);
+    map_core_result_to_response(core.set_tombstones(&plan_id, payload.enabled))
+}
+
+/// Changes the order the plan's subtasks are listed in
+async fn set_task_order_handler(
+    State(core): State<Core>,
+    Path(id): Path<u8>,
+    Json(payload): Json<SetTaskOrderRequest>,
+) -> Response {
+    let plan_id = models::Lease::new(id);
+    map_core_result_to_response(core.set_task_order(&plan_id, payload.order))
+}
+
+async fn set_plan_badge_handler(
+    State(core): State<Core>,
+    Path(id): Path<u8>,
+    Json(badge): Json<models::PlanBadge>,
+) -> Response {
+    let plan_id = models::Lease::new(id);
+    map_core_result_to_response(core.set_plan_badge(&plan_id, badge))
+}
+
+async fn set_completion_policy_handler(
+    State(core): State<Core>,
+    Path(id): Path<u8>,
+    Json(policy): Json<models::CompletionPolicy>,
+) -> Response {
+    let plan_id = models::Lease::new(id);
+    map_core_result_to_response(core.set_completion_policy(&plan_id, policy))
+}
+
+async fn update_plan_handler(
+    State(core): State<Core>,
+    Path(id): Path<u8>,
+    ValidJson(payload): ValidJson<UpdatePlanRequest>,
+) -> Response {
+    let plan_id = models::Lease::new(id);
+    map_core_result_to_response(core.update_plan_metadata(&plan_id, payload.goal, payload.notes))
+}
+
+async fn set_usage_summary_handler(
+    State(core): State<Core>,
+    Path(id): Path<u8>,
+    ValidJson(payload): ValidJson<SetUsageSummaryRequest>,
+) -> Response {
+    let plan_id = models::Lease::new(id);
+    map_core_result_to_response(core.set_plan_usage_summary(&plan_id, payload.usage_summary))
+}
+
+async fn set_focus_handler(
+    State(core): State<Core>,
+    Path(id): Path<u8>,
+    Json(payload): Json<SetFocusRequest>,
+) -> Response {
+    let plan_id = models::Lease::new(id);
+    let index = match payload.index {
+        Some(task) => match resolve_task(&core, &plan_id, &task) {
+            Ok(index) => Some(index),
+            Err(response) => return response,
+        },
+        None => None,
+    };
+    map_core_result_to_response(core.set_focus(&plan_id, index, payload.strict))
+}
+
+async fn start_session_handler(
+    State(core): State<Core>,
+    Path(id): Path<u8>,
+    Json(payload): Json<StartSessionRequest>,
+) -> Response {
+    let plan_id = models::Lease::new(id);
+    map_core_result_to_response(core.start_session(&plan_id, payload.reminder_minutes))
+}
+
+async fn stop_session_handler(State(core): State<Core>, Path(id): Path<u8>) -> Response {
+    let plan_id = models::Lease::new(id);
+    map_core_result_to_response(core.stop_session(&plan_id))
+}
+
+async fn share_plan_handler(State(core): State<Core>, Path(id): Path<u8>) -> Response {
+    let plan_id = models::Lease::new(id);
+    map_core_result_to_response(core.share_plan(&plan_id))
+}
+
+async fn unshare_plan_handler(State(core): State<Core>, Path(id): Path<u8>) -> Response {
+    let plan_id = models::Lease::new(id);
+    map_core_result_to_response(core.unshare_plan(&plan_id))
+}
+
+/// Makes a plan read-only until it is unlocked
+async fn lock_plan_handler(State(core): State<Core>, Path(id): Path<u8>) -> Response {
+    let plan_id = models::Lease::new(id);
+    map_core_result_to_response(core.set_plan_locked(&plan_id, true))
+}
+
+async fn unlock_plan_handler(State(core): State<Core>, Path(id): Path<u8>) -> Response {
+    let plan_id = models::Lease::new(id);
+    map_core_result_to_response(core.set_plan_locked(&plan_id, false))
+}
+
+// --- Existing Handler Implementations (Updated) --- //
+
+async fn get_plan(State(core): State<Core>, Path(id): Path<u8>) -> impl IntoResponse {
+    let plan_id = models::Lease::new(id); // Use constructor
+    let result = core.get_plan(&plan_id);
+    map_core_result_to_response(result)
+}
+
+async fn get_plan_at(
+    State(core): State<Core>,
+    Path(id): Path<u8>,
+    Query(query): Query<PlanAtQuery>,
+) -> impl IntoResponse {
+    let plan_id = models::Lease::new(id);
+    // Past states and history may be read back from the journal's files
+    let response = run_blocking(move || core.plan_at(&plan_id, query.timestamp)).await;
+    map_core_result_to_response(response)
+}
+
+async fn get_changelog(
+    State(core): State<Core>,
+    Path(id): Path<u8>,
+    Query(query): Query<ChangelogQuery>,
+) -> Response {
+    let plan_id = models::Lease::new(id);
+    let (from, to) = (query.from, query.to);
+    let response = run_blocking(move || core.changelog(&plan_id, from, to)).await;
+    match (query.format.as_deref(), response) {
+        (None | Some("json"), response) => map_core_result_to_response(response),
+        (Some("markdown"), Ok(response)) => (
+            [(header::CONTENT_TYPE, "text/markdown; charset=utf-8")],
+            response.into_inner().to_markdown(),
+        )
+            .into_response(),
+        (Some("markdown"), Err(e)) => map_core_result_simple::<()>(Err(e)),
+        (Some(format), _) => (
+            StatusCode::BAD_REQUEST,
+            Json(ApiResponse::<()>::error(format!(
+                "Unknown changelog format '{format}', expected json or markdown"
+            ))),
+        )
+            .into_response(),
+    }
+}
+
+async fn get_current(State(core): State<Core>, Path(id): Path<u8>) -> impl IntoResponse {
+    let plan_id = models::Lease::new(id); // Use constructor
+    let response = core.current(&plan_id);
+    map_core_result_to_response(response)
+}
+
+async fn get_progress(State(core): State<Core>, Path(id): Path<u8>) -> impl IntoResponse {
+    let plan_id = models::Lease::new(id);
+    map_core_result_simple(core.progress(&plan_id))
+}
+
+async fn get_plan_stats(State(core): State<Core>, Path(id): Path<u8>) -> impl IntoResponse {
+    let plan_id = models::Lease::new(id);
+    map_core_result_simple(core.stats(&plan_id))
+}
+
+async fn get_effort_report(State(core): State<Core>, Path(id): Path<u8>) -> impl IntoResponse {
+    let plan_id = models::Lease::new(id);
+    map_core_result_simple(core.effort_report(&plan_id))
+}
+
+async fn lint_plan(State(core): State<Core>, Path(id): Path<u8>) -> Response {
+    let plan_id = models::Lease::new(id);
+    map_core_result_to_response(core.lint_plan(&plan_id))
+}
+
+async fn get_history(
+    State(core): State<Core>,
+    Path(id): Path<u8>,
+    Query(query): Query<HistoryQuery>,
+) -> Response {
+    let result = run_blocking(move || core.history(&models::Lease::new(id))).await;
+    match (query.format.as_deref(), result) {
+        (None | Some("json"), result) => map_core_result_simple(result),
+        (Some("jsonl"), Ok(records)) => {
+            let lines = records.into_iter().map(|record| {
+                let mut line = serde_json::to_vec(&record)?;
+                line.push(b'\n');
+                Ok::<_, serde_json::Error>(line)
+            });
+            (
+                [(header::CONTENT_TYPE, "application/x-ndjson")],
+                axum::body::Body::from_stream(futures::stream::iter(lines)),
+            )
+                .into_response()
         }
-        .status-indicator.connected {
-            background-color: #2ecc71;
+        (Some("jsonl"), Err(e)) => map_core_result_simple::<()>(Err(e)),
+        (Some(format), _) => (
+            StatusCode::BAD_REQUEST,
+            Json(ApiResponse::<()>::error(format!(
+                "Unknown history format '{format}', expected json or jsonl"
+            ))),
+        )
+            .into_response(),
+    }
+}
+
+/// How long a long poll waits for a change when no timeout is given
+const DEFAULT_POLL_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Longest a long poll may wait, so connections don't pile up
+const MAX_POLL_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Parses a poll timeout given in seconds, with or without an `s` suffix
+fn parse_poll_timeout(timeout: &str) -> Option<Duration> {
+    let secs = timeout.trim().strip_suffix('s').unwrap_or(timeout.trim());
+    secs.parse::<u64>().ok().map(Duration::from_secs)
+}
+
+/// Waits until the plan logs a transition at or after `since`, or the timeout passes,
+/// and returns the transitions since then. A simpler alternative to the event stream.
+/// Answers `410 Gone` when transitions since `since` were compacted away.
+async fn get_changes(
+    State(core): State<Core>,
+    Path(id): Path<u8>,
+    Query(query): Query<ChangesQuery>,
+) -> Response {
+    let plan_id = models::Lease::new(id);
+    let timeout = match query.timeout.as_deref().map(parse_poll_timeout) {
+        None => DEFAULT_POLL_TIMEOUT,
+        Some(Some(timeout)) => timeout.min(MAX_POLL_TIMEOUT),
+        Some(None) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(ApiResponse::<()>::error(format!(
+                    "Invalid timeout '{}', expected seconds such as 30s",
+                    query.timeout.unwrap_or_default()
+                ))),
+            )
+                .into_response()
         }
-        .status-indicator.updating {
-            background-color: #f39c12;
+    };
+
+    // Subscribe before looking, so a change made in between isn't missed
+    let mut receiver = match core.subscribe_plan(plan_id) {
+        Ok(receiver) => receiver,
+        Err(e) => return map_core_result_simple::<()>(Err(e)),
+    };
+    let since = match query.since {
+        Some(since) => since,
+        None => match core.next_seq(&plan_id) {
+            Ok(len) => len,
+            Err(e) => return map_core_result_simple::<()>(Err(e)),
+        },
+    };
+    let deadline = tokio::time::Instant::now() + timeout;
+    loop {
+        let changes = match core.changes_since(&plan_id, since) {
+            Ok(changes) => changes,
+            Err(e) => return map_core_result_simple::<()>(Err(e)),
+        };
+        // A change that logged nothing (or a deleted plan) is looked at again
+        if !changes.is_empty()
+            || tokio::time::timeout_at(deadline, receiver.recv())
+                .await
+                .is_err()
+        {
+            let next_seq = changes.last().map_or(since, |record| record.seq + 1);
+            return map_core_result_simple(Ok(PlanChanges { changes, next_seq }));
         }
-        .status-text {
+    }
+}
+
+async fn get_distilled_context(
+    State(core): State<Core>,
+    Path(id): Path<u8>,
+    Query(query): Query<DistilledQuery>,
+) -> Response {
+    let plan_id = models::Lease::new(id); // Use constructor
+                                          // Suggestion engines may run plugins for as long as their fuel lasts
+    let response = run_blocking(move || core.distilled_context(&plan_id)).await;
+    match (query.format.as_deref(), response) {
+        (None | Some("json"), response) => map_core_result_to_response(response),
+        (Some("compact"), Ok(response)) => (
+            [(header::CONTENT_TYPE, "text/plain; charset=utf-8")],
+            response.distilled_context.to_compact(),
+        )
+            .into_response(),
+        (Some("compact"), Err(e)) => map_core_result_simple::<()>(Err(e)),
+        (Some(format), _) => (
+            StatusCode::BAD_REQUEST,
+            Json(ApiResponse::<()>::error(format!(
+                "Unknown context format '{format}', expected json or compact"
+            ))),
+        )
+            .into_response(),
+    }
+}
+
+async fn add_task(
+    State(core): State<Core>,
+    Path(id): Path<u8>,
+    ValidJson(payload): ValidJson<AddTaskRequest>,
+) -> impl IntoResponse {
+    let plan_id = models::Lease::new(id); // Use constructor
+    let response = core.add_task(
+        &plan_id,
+        payload.description,
+        payload.level_index,
+        payload.notes,
+    );
+    map_core_result_to_response(response)
+}
+
+async fn add_task_from_template(
+    State(core): State<Core>,
+    Path(id): Path<u8>,
+    ValidJson(payload): ValidJson<AddTaskFromTemplateRequest>,
+) -> Response {
+    let plan_id = models::Lease::new(id);
+    let response = core.add_task_from_template(&plan_id, &payload.template, &payload.params);
+    map_core_result_to_response(response)
+}
+
+// --- Template Handlers --- //
+
+async fn list_templates_handler(State(core): State<Core>) -> Response {
+    map_core_result_simple(core.list_templates())
+}
+
+async fn save_template_handler(
+    State(core): State<Core>,
+    ValidJson(template): ValidJson<models::TaskTemplate>,
+) -> Response {
+    map_core_result_simple(core.save_template(template))
+}
+
+async fn remove_template_handler(State(core): State<Core>, Path(name): Path<String>) -> Response {
+    map_core_result_simple(core.remove_template(&name))
+}
+
+async fn complete_task(
+    State(core): State<Core>,
+    Path(id): Path<u8>,
+    headers: HeaderMap,
+    ValidJson(payload): ValidJson<CompleteTaskRequest>,
+) -> impl IntoResponse {
+    let plan_id = models::Lease::new(id); // Use constructor
+    let index = match resolve_task(&core, &plan_id, &payload.index) {
+        Ok(index) => index,
+        Err(response) => return response,
+    };
+    let session = session_of(&headers).map(str::to_string);
+    let response = run_blocking(move || {
+        core.complete_task(
+            &plan_id,
+            index,
+            payload.lease, // Already Option<u8>
+            session.as_deref(),
+            payload.force,
+            payload.cascade,
+            payload.summary,
+        )
+    })
+    .await;
+    map_core_result_to_response(response)
+}
+
+async fn complete_current_task(
+    State(core): State<Core>,
+    Path(id): Path<u8>,
+    headers: HeaderMap,
+    ValidJson(payload): ValidJson<CompleteCurrentRequest>,
+) -> impl IntoResponse {
+    let plan_id = models::Lease::new(id);
+    let session = session_of(&headers).map(str::to_string);
+    let response = run_blocking(move || {
+        core.complete_current_task(
+            &plan_id,
+            payload.lease,
+            session.as_deref(),
+            payload.force,
+            payload.cascade,
+            payload.summary,
+        )
+    })
+    .await;
+    map_core_result_to_response(response)
+}
+
+/// Runs a core call that may block for a while, such as a completion waiting on the
+/// plan's validators, on the blocking thread pool so it doesn't hold up other requests
+pub(crate) async fn run_blocking<T: Send + 'static>(
+    f: impl FnOnce() -> Result<T, PlanError> + Send + 'static,
+) -> Result<T, PlanError> {
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| PlanError::Internal(format!("blocking call failed: {e}")))?
+}
+
+async fn change_level(
+    State(core): State<Core>,
+    Path(id): Path<u8>,
+    Json(payload): Json<ChangeLevelRequest>,
+) -> impl IntoResponse {
+    let plan_id = models::Lease::new(id); // Use constructor
+    let index = match resolve_task(&core, &plan_id, &payload.index) {
+        Ok(index) => index,
+        Err(response) => return response,
+    };
+    let response = core.change_level(&plan_id, index, payload.level_index);
+    map_core_result_to_response(response)
+}
+
+async fn set_task_weight(
+    State(core): State<Core>,
+    Path(id): Path<u8>,
+    Json(payload): Json<SetTaskWeightRequest>,
+) -> impl IntoResponse {
+    let plan_id = models::Lease::new(id);
+    let index = match resolve_task(&core, &plan_id, &payload.index) {
+        Ok(index) => index,
+        Err(response) => return response,
+    };
+    let response = core.set_task_weight(&plan_id, index, payload.weight);
+    map_core_result_to_response(response)
+}
+
+async fn set_task_priority(
+    State(core): State<Core>,
+    Path(id): Path<u8>,
+    Json(payload): Json<SetTaskPriorityRequest>,
+) -> impl IntoResponse {
+    let plan_id = models::Lease::new(id);
+    let index = match resolve_task(&core, &plan_id, &payload.index) {
+        Ok(index) => index,
+        Err(response) => return response,
+    };
+    let response = core.set_task_priority(&plan_id, index, payload.priority);
+    map_core_result_to_response(response)
+}
+
+async fn set_task_estimate(
+    State(core): State<Core>,
+    Path(id): Path<u8>,
+    Json(payload): Json<SetTaskEstimateRequest>,
+) -> impl IntoResponse {
+    let plan_id = models::Lease::new(id);
+    let index = match resolve_task(&core, &plan_id, &payload.index) {
+        Ok(index) => index,
+        Err(response) => return response,
+    };
+    let response = core.set_task_estimate(&plan_id, index, payload.minutes);
+    map_core_result_to_response(response)
+}
+
+async fn log_task_time(
+    State(core): State<Core>,
+    Path(id): Path<u8>,
+    Json(payload): Json<LogTaskTimeRequest>,
+) -> impl IntoResponse {
+    let plan_id = models::Lease::new(id);
+    let index = match resolve_task(&core, &plan_id, &payload.index) {
+        Ok(index) => index,
+        Err(response) => return response,
+    };
+    let response = core.log_task_time(&plan_id, index, payload.minutes);
+    map_core_result_to_response(response)
+}
+
+async fn generate_lease(
+    State(core): State<Core>,
+    Path(id): Path<u8>,
+    headers: HeaderMap,
+    Json(payload): Json<LeaseRequest>,
+) -> impl IntoResponse {
+    let plan_id = models::Lease::new(id); // Use constructor
+    let index = match resolve_task(&core, &plan_id, &payload.index) {
+        Ok(index) => index,
+        Err(response) => return response,
+    };
+    let options = models::LeaseOptions {
+        single_use: payload.single_use,
+        session: session_of(&headers).map(str::to_string),
+    };
+    let response = core.generate_lease(&plan_id, index, options);
+    map_core_result_to_response(response)
+}
+
+async fn start_task(
+    State(core): State<Core>,
+    Path(id): Path<u8>,
+    Json(payload): Json<StartTaskRequest>,
+) -> impl IntoResponse {
+    let plan_id = models::Lease::new(id);
+    let index = match resolve_task(&core, &plan_id, &payload.index) {
+        Ok(index) => index,
+        Err(response) => return response,
+    };
+    let response = core.start_task(&plan_id, index);
+    map_core_result_to_response(response)
+}
+
+async fn uncomplete_task(
+    State(core): State<Core>,
+    Path(id): Path<u8>,
+    Json(payload): Json<UncompleteTaskRequest>,
+) -> impl IntoResponse {
+    let plan_id = models::Lease::new(id); // Use constructor
+    let index = match resolve_task(&core, &plan_id, &payload.index) {
+        Ok(index) => index,
+        Err(response) => return response,
+    };
+    let response = core.uncomplete_task(&plan_id, index);
+    map_core_result_to_response(response)
+}
+
+async fn request_review(
+    State(core): State<Core>,
+    Path(id): Path<u8>,
+    ValidJson(payload): ValidJson<RequestReviewRequest>,
+) -> impl IntoResponse {
+    let plan_id = models::Lease::new(id);
+    let index = match resolve_task(&core, &plan_id, &payload.index) {
+        Ok(index) => index,
+        Err(response) => return response,
+    };
+    let response = core.request_review(&plan_id, index, payload.summary);
+    map_core_result_to_response(response)
+}
+
+async fn approve_task(
+    State(core): State<Core>,
+    Path(id): Path<u8>,
+    Json(payload): Json<ApproveTaskRequest>,
+) -> impl IntoResponse {
+    let plan_id = models::Lease::new(id);
+    let index = match resolve_task(&core, &plan_id, &payload.index) {
+        Ok(index) => index,
+        Err(response) => return response,
+    };
+    // The plan's validators check the approval like any other completion
+    let response = run_blocking(move || core.approve_task(&plan_id, index)).await;
+    map_core_result_to_response(response)
+}
+
+async fn get_review_queue(State(core): State<Core>, Path(id): Path<u8>) -> impl IntoResponse {
+    let plan_id = models::Lease::new(id);
+    let response = core.review_queue(&plan_id);
+    map_core_result_to_response(response)
+}
+
+async fn get_summaries(
+    State(core): State<Core>,
+    Path(id): Path<u8>,
+    Query(query): Query<SummariesQuery>,
+) -> Response {
+    let plan_id = models::Lease::new(id);
+    let level = query.level.unwrap_or(models::DEFAULT_SUMMARY_LEVEL);
+    map_core_result_to_response(core.summaries(&plan_id, level))
+}
+
+async fn list_tasks_handler(
+    State(core): State<Core>,
+    Path(id): Path<u8>,
+    Query(query): Query<ListTasksQuery>,
+) -> impl IntoResponse {
+    let plan_id = models::Lease::new(id);
+    let subtree = match query.subtree.as_deref() {
+        Some(subtree) => match task_from_path(&core, &plan_id, subtree) {
+            Ok(index) => index,
+            Err(response) => return response,
+        },
+        None => Index::new(),
+    };
+    let filter = models::TaskFilter {
+        incomplete_only: query.incomplete_only,
+        level: query.level,
+        depth: query.depth,
+        subtree,
+        tag: query.tag,
+    };
+    map_core_result_to_response(core.list_tasks(&plan_id, &filter))
+}
+
+async fn search_tasks_handler(
+    State(core): State<Core>,
+    Path(id): Path<u8>,
+    Query(query): Query<SearchQuery>,
+) -> impl IntoResponse {
+    let plan_id = models::Lease::new(id);
+    map_core_result_to_response(core.search_tasks(&plan_id, &query.q))
+}
+
+async fn reject_task(
+    State(core): State<Core>,
+    Path(id): Path<u8>,
+    ValidJson(payload): ValidJson<RejectTaskRequest>,
+) -> impl IntoResponse {
+    let plan_id = models::Lease::new(id);
+    let index = match resolve_task(&core, &plan_id, &payload.index) {
+        Ok(index) => index,
+        Err(response) => return response,
+    };
+    let response = core.reject_task(&plan_id, index, payload.reason);
+    map_core_result_to_response(response)
+}
+
+// --- Questions Handlers --- //
+
+async fn list_questions_handler(State(core): State<Core>, Path(id): Path<u8>) -> impl IntoResponse {
+    let plan_id = models::Lease::new(id);
+    let response = core.list_questions(&plan_id);
+    map_core_result_to_response(response)
+}
+
+async fn add_question_handler(
+    State(core): State<Core>,
+    Path(id): Path<u8>,
+    ValidJson(payload): ValidJson<AddQuestionRequest>,
+) -> impl IntoResponse {
+    let plan_id = models::Lease::new(id);
+    let response = core.add_question(&plan_id, payload.question);
+    map_core_result_to_response(response)
+}
+
+async fn answer_question_handler(
+    State(core): State<Core>,
+    Path((id, question_id)): Path<(u8, usize)>,
+    ValidJson(payload): ValidJson<AnswerQuestionRequest>,
+) -> impl IntoResponse {
+    let plan_id = models::Lease::new(id);
+    let response = core.answer_question(&plan_id, question_id, payload.answer);
+    map_core_result_to_response(response)
+}
+
+async fn remove_question_handler(
+    State(core): State<Core>,
+    Path((id, question_id)): Path<(u8, usize)>,
+) -> impl IntoResponse {
+    let plan_id = models::Lease::new(id);
+    let response = core.remove_question(&plan_id, question_id);
+    map_core_result_to_response(response)
+}
+
+async fn move_to(
+    State(core): State<Core>,
+    Path(id): Path<u8>,
+    Json(payload): Json<MoveToRequest>,
+) -> impl IntoResponse {
+    let plan_id = models::Lease::new(id); // Use constructor
+    let index = match resolve_task(&core, &plan_id, &payload.index) {
+        Ok(index) => index,
+        Err(response) => return response,
+    };
+    let response = core.move_to(&plan_id, index);
+    map_core_result_to_response(response)
+}
+
+/// Gets a task by index ("0,2") or ID ("t-3f9a2c1b"), along with its current index
+async fn get_task_handler(
+    State(core): State<Core>,
+    Path((id, task)): Path<(u8, String)>,
+) -> Response {
+    let plan_id = models::Lease::new(id);
+    match task.parse::<TaskRef>() {
+        Ok(task) => map_core_result_to_response(core.get_task(&plan_id, &task)),
+        Err(e) => (
+            StatusCode::BAD_REQUEST,
+            Json(ApiResponse::<()>::error(format!(
+                "Invalid index format: {e}"
+            ))),
+        )
+            .into_response(),
+    }
+}
+
+async fn update_task_handler(
+    State(core): State<Core>,
+    Path((id, index_str)): Path<(u8, String)>,
+    ValidJson(payload): ValidJson<UpdateTaskRequest>,
+) -> Response {
+    let plan_id = models::Lease::new(id);
+    let index = match task_from_path(&core, &plan_id, &index_str) {
+        Ok(index) => index,
+        Err(response) => return response,
+    };
+    map_core_result_to_response(core.update_task_description(&plan_id, index, payload.description))
+}
+
+async fn move_task(
+    State(core): State<Core>,
+    Path(id): Path<u8>,
+    Json(payload): Json<MoveTaskRequest>,
+) -> Response {
+    let plan_id = models::Lease::new(id);
+    let index = match resolve_task(&core, &plan_id, &payload.index) {
+        Ok(index) => index,
+        Err(response) => return response,
+    };
+    let parent = match resolve_task(&core, &plan_id, &payload.parent) {
+        Ok(parent) => parent,
+        Err(response) => return response,
+    };
+    map_core_result_to_response(core.move_task(&plan_id, index, parent))
+}
+
+async fn add_dependency(
+    State(core): State<Core>,
+    Path(id): Path<u8>,
+    Json(payload): Json<DependencyRequest>,
+) -> Response {
+    let plan_id = models::Lease::new(id);
+    let (index, on) = match resolve_dependency(&core, &plan_id, &payload) {
+        Ok(resolved) => resolved,
+        Err(response) => return response,
+    };
+    map_core_result_to_response(core.add_dependency(&plan_id, index, on))
+}
+
+async fn remove_dependency(
+    State(core): State<Core>,
+    Path(id): Path<u8>,
+    Json(payload): Json<DependencyRequest>,
+) -> Response {
+    let plan_id = models::Lease::new(id);
+    let (index, on) = match resolve_dependency(&core, &plan_id, &payload) {
+        Ok(resolved) => resolved,
+        Err(response) => return response,
+    };
+    map_core_result_to_response(core.remove_dependency(&plan_id, index, on))
+}
+
+/// Resolves both tasks of a dependency request to their indices
+async fn tag_task(
+    State(core): State<Core>,
+    Path(id): Path<u8>,
+    Json(payload): Json<TagTaskRequest>,
+) -> Response {
+    let plan_id = models::Lease::new(id);
+    let index = match resolve_task(&core, &plan_id, &payload.index) {
+        Ok(index) => index,
+        Err(response) => return response,
+    };
+    map_core_result_to_response(core.tag_task(&plan_id, index, payload.tags))
+}
+
+async fn untag_task(
+    State(core): State<Core>,
+    Path(id): Path<u8>,
+    Json(payload): Json<TagTaskRequest>,
+) -> Response {
+    let plan_id = models::Lease::new(id);
+    let index = match resolve_task(&core, &plan_id, &payload.index) {
+        Ok(index) => index,
+        Err(response) => return response,
+    };
+    map_core_result_to_response(core.untag_task(&plan_id, index, payload.tags))
+}
+
+fn resolve_dependency(
+    core: &Core,
+    plan_id: &models::PlanId,
+    payload: &DependencyRequest,
+) -> Result<(Index, Index), Response> {
+    let index = resolve_task(core, plan_id, &payload.index)?;
+    let on = resolve_task(core, plan_id, &payload.on)?;
+    Ok((index, on))
+}
+
+async fn remove_task_handler(
+    State(core): State<Core>,
+    Path((id, index_str)): Path<(u8, String)>, // Extract id (u8) and index string
+) -> impl IntoResponse {
+    let plan_id = models::Lease::new(id);
+    // The wildcard path holds an index or a task ID
+    let index = match task_from_path(&core, &plan_id, &index_str) {
+        Ok(index) => index,
+        Err(response) => return response,
+    };
+    let response = core.remove_task(&plan_id, index);
+    // Simplify: Use the mapping helper directly instead of custom match logic
+    map_core_result_to_response(response)
+}
+
+// --- Notes Handlers --- //
+
+async fn export_subtree_handler(
+    State(core): State<Core>,
+    Path((id, index_str)): Path<(u8, String)>,
+) -> impl IntoResponse {
+    let plan_id = models::Lease::new(id);
+    let index = match task_from_path(&core, &plan_id, &index_str) {
+        Ok(index) => index,
+        Err(response) => return response,
+    };
+    map_core_result_to_response(core.export_subtree(&plan_id, index))
+}
+
+async fn import_subtree_handler(
+    State(core): State<Core>,
+    Path(id): Path<u8>,
+    ValidJson(payload): ValidJson<ImportSubtreeRequest>,
+) -> impl IntoResponse {
+    let plan_id = models::Lease::new(id);
+    let parent = match resolve_task(&core, &plan_id, &payload.parent) {
+        Ok(index) => index,
+        Err(response) => return response,
+    };
+    map_core_result_to_response(core.import_subtree(&plan_id, parent, payload.subtree))
+}
+
+async fn get_notes_handler(
+    State(core): State<Core>,
+    Path((id, index_str)): Path<(u8, String)>, // Extract id (u8) and index string
+) -> impl IntoResponse {
+    let plan_id = models::Lease::new(id);
+    let index = match task_from_path(&core, &plan_id, &index_str) {
+        Ok(index) => index,
+        Err(response) => return response,
+    };
+
+    // Call core logic
+    let response = core.get_task_notes(&plan_id, index);
+
+    // Pass the result directly to the standard mapping function
+    map_core_result_to_response(response)
+}
+
+async fn set_notes_handler(
+    State(core): State<Core>,
+    Path((id, index_str)): Path<(u8, String)>,
+    ValidJson(payload): ValidJson<SetTaskNotesRequest>,
+) -> impl IntoResponse {
+    let plan_id = models::Lease::new(id);
+    let index = match task_from_path(&core, &plan_id, &index_str) {
+        Ok(index) => index,
+        Err(response) => return response,
+    };
+    let response = if payload.append {
+        core.append_task_notes(&plan_id, index, payload.notes)
+    } else {
+        core.set_task_notes(&plan_id, index, payload.notes)
+    };
+    map_core_result_to_response(response)
+}
+
+async fn delete_notes_handler(
+    State(core): State<Core>,
+    Path((id, index_str)): Path<(u8, String)>,
+) -> impl IntoResponse {
+    let plan_id = models::Lease::new(id);
+    let index = match task_from_path(&core, &plan_id, &index_str) {
+        Ok(index) => index,
+        Err(response) => return response,
+    };
+    let response = core.delete_task_notes(&plan_id, index);
+    map_core_result_to_response(response)
+}
+
+async fn get_notes_history_handler(
+    State(core): State<Core>,
+    Path((id, index_str)): Path<(u8, String)>,
+) -> impl IntoResponse {
+    let plan_id = models::Lease::new(id);
+    let index = match task_from_path(&core, &plan_id, &index_str) {
+        Ok(index) => index,
+        Err(response) => return response,
+    };
+    let response = core.get_notes_history(&plan_id, index);
+    // Unwrap the response so clients receive the version list directly
+    map_core_result_simple(response.map(PlanResponse::into_inner))
+}
+
+async fn revert_notes_handler(
+    State(core): State<Core>,
+    Path((id, index_str)): Path<(u8, String)>,
+    Json(payload): Json<RevertNotesRequest>,
+) -> impl IntoResponse {
+    let plan_id = models::Lease::new(id);
+    let index = match task_from_path(&core, &plan_id, &index_str) {
+        Ok(index) => index,
+        Err(response) => return response,
+    };
+    let response = core.revert_task_notes(&plan_id, index, payload.version);
+    map_core_result_to_response(response)
+}
+
+async fn get_note_sections_handler(
+    State(core): State<Core>,
+    Path((id, index_str)): Path<(u8, String)>,
+    Query(query): Query<NoteSectionsQuery>,
+) -> impl IntoResponse {
+    let plan_id = models::Lease::new(id);
+    let index = match task_from_path(&core, &plan_id, &index_str) {
+        Ok(index) => index,
+        Err(response) => return response,
+    };
+    let response = core.get_note_sections(&plan_id, index, query.section);
+    // Unwrap the response so clients receive the sections map directly
+    map_core_result_simple(response.map(PlanResponse::into_inner))
+}
+
+async fn set_note_section_handler(
+    State(core): State<Core>,
+    Path((id, index_str)): Path<(u8, String)>,
+    ValidJson(payload): ValidJson<SetNoteSectionRequest>,
+) -> impl IntoResponse {
+    let plan_id = models::Lease::new(id);
+    let index = match task_from_path(&core, &plan_id, &index_str) {
+        Ok(index) => index,
+        Err(response) => return response,
+    };
+    let response = core.set_note_section(
+        &plan_id,
+        index,
+        payload.section,
+        payload.content,
+        payload.append,
+    );
+    map_core_result_to_response(response)
+}
+
+// --- UI and Event Handlers (Updated for PlanId) --- //
+
+async fn events_handler(
+    State(core): State<Core>,
+    Extension(config): Extension<SharedConfig>,
+    Path(id): Path<u8>, // Accept u8 ID from path
+    headers: HeaderMap,
+) -> Response {
+    // Only this plan's updates reach the stream
+    let plan_id = models::Lease::new(id); // Use constructor
+    let receiver = match core.subscribe_plan(plan_id) {
+        Ok(receiver) => receiver,
+        Err(e) => return map_core_result_simple::<()>(Err(e)),
+    };
+    let keep_alive_secs = config.read().map(|c| c.sse_keep_alive_secs).unwrap_or(0);
+    let keep_alive = (keep_alive_secs > 0).then(|| Duration::from_secs(keep_alive_secs));
+    // A reconnecting client names the last transition it saw, and is sent the ones after it
+    let last_event_id = headers
+        .get(LAST_EVENT_ID)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse::<usize>().ok());
+    let stream = EventStream::new(core, plan_id, receiver, last_event_id, keep_alive);
+
+    // Set headers for event stream
+    let headers = [
+        (
+            axum::http::header::CONTENT_TYPE,
+            axum::http::HeaderValue::from_static("text/event-stream"),
+        ),
+        (
+            axum::http::header::CACHE_CONTROL,
+            axum::http::HeaderValue::from_static("no-cache"),
+        ),
+    ];
+
+    // Return response with headers and stream body
+    (headers, axum::body::Body::from_stream(stream)).into_response()
+}
+
+/// Header a reconnecting `EventSource` sends with the id of the last event it received
+const LAST_EVENT_ID: &str = "last-event-id";
+
+/// Sent whenever the plan changes; the UI reloads on it
+const UPDATE_FRAME: &str = "event: update\ndata: change\n\n";
+
+/// Sent after `sse_keep_alive_secs` without other frames, so proxies keep the connection
+const PING_FRAME: &str = "event: ping\ndata: {}\n\n";
+
+/// Sent when transitions the client hasn't seen were compacted away; it has to reload the
+/// plan instead of relying on the `transition` events
+const RESYNC_FRAME: &str = "event: resync\ndata: {}\n\n";
+
+/// Server-sent events for one plan, ending when the plan is deleted.
+///
+/// Each change sends the transitions it logged as `transition` events, with their
+/// sequence number as the event id, followed by an `update` event.
+struct EventStream {
+    core: Core,
+    plan_id: models::PlanId,
+    receiver: tokio::sync::broadcast::Receiver<()>,
+    // Sequence number of the next transition to send
+    next_seq: usize,
+    // Frames waiting to be sent, oldest first
+    pending: VecDeque<String>,
+    // Interval for pings, so idle connections aren't dropped by proxies
+    keep_alive: Option<Duration>,
+    last_sent: Instant,
+}
+
+impl EventStream {
+    /// Starts after the transition `last_event_id` when reconnecting, replaying the ones
+    /// missed since, and after the newest transition otherwise
+    fn new(
+        core: Core,
+        plan_id: models::PlanId,
+        receiver: tokio::sync::broadcast::Receiver<()>,
+        last_event_id: Option<usize>,
+        keep_alive: Option<Duration>,
+    ) -> Self {
+        let mut stream = Self {
+            next_seq: core.next_seq(&plan_id).unwrap_or(0),
+            core,
+            plan_id,
+            receiver,
+            pending: VecDeque::new(),
+            keep_alive,
+            last_sent: Instant::now(),
+        };
+        if let Some(last) = last_event_id {
+            stream.next_seq = last + 1;
+            if stream.catch_up() {
+                stream.pending.push_back(UPDATE_FRAME.to_string());
+            }
+        }
+        stream
+    }
+
+    /// Queues the transitions logged since the last one sent, returning whether there were
+    /// any. When some of them were compacted away, a `resync` event is queued instead and
+    /// the stream carries on from the next transition.
+    fn catch_up(&mut self) -> bool {
+        let records = match self.core.changes_since(&self.plan_id, self.next_seq) {
+            Ok(records) => records,
+            Err(PlanError::HistoryGap { next, .. }) => {
+                self.pending.push_back(RESYNC_FRAME.to_string());
+                self.next_seq = next;
+                return true;
+            }
+            Err(_) => Vec::new(),
+        };
+        for record in &records {
+            let Ok(data) = serde_json::to_string(record) else {
+                continue;
+            };
+            self.pending.push_back(format!(
+                "id: {}\nevent: transition\ndata: {data}\n\n",
+                record.seq
+            ));
+            self.next_seq = record.seq + 1;
+        }
+        !records.is_empty()
+    }
+}
+
+impl Stream for EventStream {
+    type Item = Result<String, Infallible>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            if let Some(frame) = self.pending.pop_front() {
+                self.last_sent = Instant::now();
+                return Poll::Ready(Some(Ok(frame)));
+            }
+
+            // Try to receive from the broadcast channel with a non-blocking approach
+            match self.receiver.try_recv() {
+                // Missed notifications lose nothing: the log has every transition
+                Ok(()) | Err(tokio::sync::broadcast::error::TryRecvError::Lagged(_)) => {
+                    self.catch_up();
+                    self.pending.push_back(UPDATE_FRAME.to_string());
+                }
+                Err(tokio::sync::broadcast::error::TryRecvError::Empty) => {
+                    if let Some(keep_alive) = self.keep_alive {
+                        if self.last_sent.elapsed() >= keep_alive {
+                            self.last_sent = Instant::now();
+                            return Poll::Ready(Some(Ok(PING_FRAME.to_string())));
+                        }
+                    }
+                    // No updates available now, register the waker to be notified later
+                    // Create a task to wake this future when the receiver might have data
+                    let waker = cx.waker().clone();
+                    tokio::spawn(async move {
+                        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+                        waker.wake();
+                    });
+                    return Poll::Pending;
+                }
+                Err(tokio::sync::broadcast::error::TryRecvError::Closed) => {
+                    // The plan was deleted
+                    return Poll::Ready(None);
+                }
+            }
+        }
+    }
+}
+
+/// Path of a plan's UI page scrolled to and highlighting the task at `index`, e.g.
+/// `/ui/3#task-0-2-1`
+pub fn task_ui_path(plan_id: u8, index: &[usize]) -> String {
+    format!("/ui/{plan_id}#{}", task_anchor(index))
+}
+
+/// HTML id of a task's entry in the UI's task tree
+fn task_anchor(index: &[usize]) -> String {
+    let parts: Vec<String> = index.iter().map(|i| i.to_string()).collect();
+    format!("task-{}", parts.join("-"))
+}
+
+/// Path that opens the UI of the plan shared under `code`
+pub fn join_path(code: &str) -> String {
+    format!("/join/{code}")
+}
+
+/// Redirects a join code to the UI of the plan shared under it
+async fn join_handler(State(core): State<Core>, Path(code): Path<String>) -> Response {
+    match core.shared_plan(&code) {
+        Ok(Some(plan_id)) => {
+            Redirect::temporary(&format!("/ui/{}", plan_id.value())).into_response()
+        }
+        Ok(None) => (
+            StatusCode::NOT_FOUND,
+            Html(format!(
+                "<h1>No plan is shared under '{}'</h1>",
+                html_escape::encode_text(&code)
+            )),
+        )
+            .into_response(),
+        Err(e) => Html(format!("<h1>Error finding the shared plan: {e}</h1>")).into_response(),
+    }
+}
+
+/// Redirects `/ui/:id/task/0,2,1` to the task's anchor on the plan page
+async fn task_link_handler(Path((id, index)): Path<(u8, String)>) -> Response {
+    match parse_index(&index) {
+        Ok(index) => Redirect::temporary(&task_ui_path(id, &index)).into_response(),
+        Err(e) => (
+            StatusCode::BAD_REQUEST,
+            Html(format!(
+                "<h1>Invalid task index '{}': {}</h1>",
+                html_escape::encode_text(&index),
+                html_escape::encode_text(&e.to_string())
+            )),
+        )
+            .into_response(),
+    }
+}
+
+/// A static, print-friendly page of the plan's tree with summaries and notes
+async fn print_handler(State(core): State<Core>, Path(id): Path<u8>) -> Response {
+    let plan_id = models::Lease::new(id);
+    match core.get_plan(&plan_id) {
+        Ok(plan_response) => {
+            let progress = core.progress(&plan_id).ok();
+            Html(render_print_page(
+                id,
+                plan_response.inner(),
+                progress.as_ref(),
+            ))
+            .into_response()
+        }
+        Err(PlanError::PlanNotFound(_)) => (
+            StatusCode::NOT_FOUND,
+            Html(format!("<h1>Plan {id} not found</h1>")),
+        )
+            .into_response(),
+        Err(e) => Html(format!("<h1>Error loading plan {id}: {e}</h1>")).into_response(),
+    }
+}
+
+/// A minimal, read-only page of the plan's tree that reloads on updates, for iframes
+async fn embed_handler(State(core): State<Core>, Path(id): Path<u8>) -> Response {
+    let plan_id = models::Lease::new(id);
+    match core.get_plan(&plan_id) {
+        Ok(plan_response) => {
+            let progress = core.progress(&plan_id).ok();
+            let current = core
+                .current(&plan_id)
+                .ok()
+                .and_then(|current| current.into_inner());
+            Html(render_embed_page(
+                id,
+                plan_response.inner(),
+                progress.as_ref(),
+                current.as_ref().map(|current| current.index.as_slice()),
+            ))
+            .into_response()
+        }
+        Err(PlanError::PlanNotFound(_)) => (
+            StatusCode::NOT_FOUND,
+            Html(format!("<p>Plan {id} not found</p>")),
+        )
+            .into_response(),
+        Err(e) => Html(format!("<p>Error loading plan {id}: {e}</p>")).into_response(),
+    }
+}
+
+// TODO: Update ui_handler to accept token and render for that plan
+/// The API prefix of the workspace a UI page at `path` belongs to: `/api` for the default
+/// workspace's `/ui/...` pages, `/api/workspaces/<name>` for `/ui/workspaces/<name>/...`
+fn workspace_api_base(path: &str) -> String {
+    match path
+        .strip_prefix("/ui/workspaces/")
+        .and_then(|rest| rest.split('/').next())
+        .filter(|name| !name.is_empty())
+    {
+        Some(name) => format!("/api/workspaces/{name}"),
+        None => "/api".to_string(),
+    }
+}
+
+async fn ui_handler(
+    State(core): State<Core>,
+    OriginalUri(uri): OriginalUri,
+    Path(id): Path<u8>,
+) -> impl IntoResponse {
+    // Fetch all plans for tabs
+    let all_plans = match core.list_plan_summaries() {
+        Ok(summaries) => summaries,
+        Err(e) => {
+            return Html(format!("<h1>Error loading plan list: {e}</h1>")).into_response();
+        }
+    };
+    let current_plan_id = models::Lease::new(id); // Use constructor
+
+    // Fetch data for the requested plan id
+    match core.get_plan(&current_plan_id) {
+        Ok(plan_response) => {
+            let plan = plan_response.inner();
+            // Fetch current and distilled for the current plan id
+            let current = core
+                .current(&current_plan_id)
+                .ok()
+                .and_then(|pr| pr.into_inner());
+            let review_queue = core
+                .review_queue(&current_plan_id)
+                .map(|pr| pr.into_inner())
+                .unwrap_or_default();
+            let distilled_context_res = {
+                let (core, plan_id) = (core.clone(), current_plan_id.clone());
+                run_blocking(move || core.distilled_context(&plan_id)).await
+            };
+
+            match distilled_context_res {
+                Ok(distilled_response) => {
+                    let distilled_context = distilled_response.context(); // Extract the context
+                    Html(render_ui_template(
+                        &current_plan_id, // Pass current PlanId
+                        &all_plans,       // Pass all plans for the tabs
+                        plan,
+                        current.as_ref(),
+                        &review_queue,
+                        &distilled_context,
+                        &workspace_api_base(uri.path()),
+                    ))
+                    .into_response()
+                }
+                Err(e) => {
+                    // Handle error fetching distilled context for the specific plan
+                    Html(format!(
+                        "<h1>Error loading context for plan {current_plan_id:?}: {e}</h1>"
+                    ))
+                    .into_response()
+                }
+            }
+        }
+        Err(PlanError::PlanNotFound(_)) => {
+            Html(format!("<h1>Plan {current_plan_id:?} not found</h1>")).into_response()
+        }
+        Err(e) => {
+            // Handle other errors fetching the plan itself
+            Html(format!(
+                "<h1>Error loading plan {current_plan_id:?}: {e}</h1>"
+            ))
+            .into_response()
+        }
+    }
+}
+
+// --- Template Rendering (Needs Update for PlanId) --- //
+
+fn render_print_page(
+    id: u8,
+    plan: &crate::models::Plan,
+    progress: Option<&models::ProgressSummary>,
+) -> String {
+    let title = plan
+        .goal
+        .as_deref()
+        .map(|goal| html_escape::encode_text(goal).to_string())
+        .unwrap_or_else(|| format!("Plan {id}"));
+    let mut html = format!(
+        "<!DOCTYPE html><html lang='en'><head><meta charset='utf-8'><title>{title}</title>{PRINT_STYLE}</head><body><main>"
+    );
+    html.push_str(&format!("<h1>{title}</h1>"));
+
+    let mut details = vec![format!("Plan {id}"), plan.status().to_string()];
+    if let Some(progress) = progress {
+        details.push(format!(
+            "{}/{} tasks done",
+            progress.completed, progress.total
+        ));
+    }
+    if let Some(completed_at) = plan.completed_at() {
+        details.push(format!(
+            "completed {}",
+            completed_at.format("%Y-%m-%d %H:%M UTC")
+        ));
+    }
+    details.push(format!(
+        "printed {}",
+        chrono::Utc::now().format("%Y-%m-%d %H:%M UTC")
+    ));
+    html.push_str(&format!(
+        "<p class='details'>{}</p>",
+        details.join(" &middot; ")
+    ));
+
+    if let Some(notes) = &plan.notes {
+        html.push_str(&format!(
+            "<div class='notes'>{}</div>",
+            html_escape::encode_text(notes)
+        ));
+    }
+
+    if plan.root().live_subtasks().next().is_none() {
+        html.push_str("<p>No tasks yet.</p>");
+    } else {
+        render_static_tasks(&mut html, plan.root().subtasks(), &[], None, true);
+    }
+    html.push_str("</main></body></html>");
+    html
+}
+
+fn render_embed_page(
+    id: u8,
+    plan: &crate::models::Plan,
+    progress: Option<&models::ProgressSummary>,
+    current: Option<&[usize]>,
+) -> String {
+    let title = plan
+        .goal
+        .as_deref()
+        .map(|goal| html_escape::encode_text(goal).to_string())
+        .unwrap_or_else(|| format!("Plan {id}"));
+    let mut html = format!(
+        "<!DOCTYPE html><html lang='en'><head><meta charset='utf-8'><title>{title}</title>{EMBED_STYLE}</head><body><main>"
+    );
+    // Links leave the iframe for the full UI
+    html.push_str(&format!(
+        "<header><a href='/ui/{id}' target='_top'>{title}</a> <span class='status'>{}</span>",
+        plan.status()
+    ));
+    if let Some(progress) = progress {
+        html.push_str(&format!(
+            " <progress value='{}' max='{}' aria-label='Tasks done'></progress> <span class='count'>{}/{} done</span>",
+            progress.weighted_completed,
+            progress.weighted_total.max(1),
+            progress.completed,
+            progress.total
+        ));
+    }
+    html.push_str("</header>");
+
+    if plan.root().live_subtasks().next().is_none() {
+        html.push_str("<p>No tasks yet.</p>");
+    } else {
+        render_static_tasks(&mut html, plan.root().subtasks(), &[], current, false);
+    }
+    html.push_str(&format!(
+        "</main><script>new EventSource('/ui/events/{id}').addEventListener('update', () => window.location.reload());</script></body></html>"
+    ));
+    html
+}
+
+/// Styles of the embed view: small and borderless, to sit inside another page
+const EMBED_STYLE: &str = r#"<style>
+    body { font-family: -apple-system, BlinkMacSystemFont, 'Segoe UI', Roboto, sans-serif; font-size: 13px; margin: 8px; color: #2c3e50; background: transparent; }
+    header { margin-bottom: 6px; }
+    header a { font-weight: bold; color: inherit; }
+    .status, .count { color: #7f8c8d; }
+    progress { vertical-align: middle; width: 8em; }
+    ul { list-style: none; margin: 0; padding-left: 1.2em; }
+    main > ul { padding-left: 0; }
+    .index { font-family: monospace; color: #7f8c8d; }
+    li.done > .task { color: #7f8c8d; text-decoration: line-through; }
+    li.current > .task { font-weight: bold; }
+    li.current > .task::after { content: " \2190 current"; font-weight: normal; color: #2980b9; }
+</style>"#;
+
+/// Renders tasks as plain nested lists for the print and embed views, marking the task
+/// at `current` and, with `details`, adding completion summaries and notes
+fn render_static_tasks(
+    html: &mut String,
+    tasks: &[crate::models::Task],
+    path: &[usize],
+    current: Option<&[usize]>,
+    details: bool,
+) {
+    html.push_str("<ul>");
+    for (i, task) in tasks.iter().enumerate() {
+        if task.is_removed() {
+            continue;
+        }
+        let mut index = path.to_vec();
+        index.push(i);
+        let (mut class, mark, status) = if task.is_completed() {
+            ("done".to_string(), "&#10003;", "completed")
+        } else {
+            ("open".to_string(), "&#9675;", "incomplete")
+        };
+        let is_current = current == Some(index.as_slice());
+        if is_current {
+            class.push_str(" current");
+        }
+        let index_label = index
+            .iter()
+            .map(|i| i.to_string())
+            .collect::<Vec<_>>()
+            .join(".");
+        html.push_str(&format!(
+            "<li class='{class}'{}><div class='task'><span class='mark' role='img' aria-label='{status}'>{mark}</span> <span class='index'>{index_label}</span> {}</div>",
+            if is_current { " aria-current='true'" } else { "" },
+            html_escape::encode_text(task.description())
+        ));
+        if details {
+            if let Some(summary) = task.completion_summary() {
+                html.push_str(&format!(
+                    "<div class='summary'>{}</div>",
+                    html_escape::encode_text(summary)
+                ));
+            }
+            if let Some(notes) = task.notes() {
+                html.push_str(&format!(
+                    "<div class='notes'>{}</div>",
+                    html_escape::encode_text(notes)
+                ));
+            }
+        }
+        if task.live_subtasks().next().is_some() {
+            render_static_tasks(html, task.subtasks(), &index, current, details);
+        }
+        html.push_str("</li>");
+    }
+    html.push_str("</ul>");
+}
+
+/// Styles of the print view: plain black on white, with tasks kept whole across pages
+const PRINT_STYLE: &str = r#"<style>
+    body { font-family: Georgia, serif; color: #000; background: #fff; max-width: 50em; margin: 2em auto; line-height: 1.4; }
+    h1 { font-size: 1.6em; margin-bottom: 0.2em; }
+    .details { color: #555; margin-top: 0; }
+    ul { list-style: none; padding-left: 1.5em; }
+    main > ul { padding-left: 0; }
+    li { margin: 0.4em 0; }
+    .task { break-inside: avoid; }
+    .index { font-family: monospace; color: #555; }
+    li.done > .task { color: #444; }
+    .summary { margin-left: 1.8em; font-style: italic; }
+    .summary::before { content: "Summary: "; font-style: normal; font-weight: bold; }
+    .notes { margin: 0.2em 0 0.2em 1.8em; padding-left: 0.6em; border-left: 2px solid #ccc; white-space: pre-wrap; font-size: 0.9em; break-inside: avoid; }
+    @media print {
+        body { margin: 0; max-width: none; font-size: 11pt; }
+        a { color: inherit; text-decoration: none; }
+    }
+</style>"#;
+
+/// A plan's color as a dot followed by its emoji, each only when set
+fn render_plan_badge(badge: &models::PlanBadge) -> String {
+    let mut html = String::new();
+    if let Some(color) = badge.color {
+        html.push_str(&format!(
+            "<span class='plan-badge' style='color: {};' title='{color}' role='img' aria-label='{color}'>&#9679;</span> ",
+            color.css()
+        ));
+    }
+    if let Some(emoji) = &badge.emoji {
+        html.push_str(&format!("{} ", html_escape::encode_text(emoji)));
+    }
+    html
+}
+
+fn render_ui_template(
+    current_plan_id: &models::PlanId,
+    all_plans: &[models::PlanSummary],
+    plan: &crate::models::Plan,
+    current: Option<&crate::models::Current>,
+    review_queue: &[models::ReviewItem],
+    distilled_context: &crate::models::DistilledContext,
+    api_base: &str,
+) -> String {
+    let mut html = String::from(HTML_TEMPLATE_HEADER);
+
+    // --- Plan Tab Navigation ---
+    html.push_str("<nav class='plan-tabs' aria-label='Plans'>");
+    if all_plans.is_empty() {
+        html.push_str("<span class='no-plans'>No plans loaded.</span>");
+    } else {
+        for summary in all_plans {
+            let id = summary.id;
+            let (class, current) = if id == *current_plan_id {
+                ("active", " aria-current='page'")
+            } else {
+                ("", "")
+            };
+            // Use id.value() for the URL and display text
+            html.push_str(&format!(
+                "<a href='/ui/{}' class='{}'{current}>{}Plan {}</a>&nbsp;",
+                id.value(),
+                class,
+                render_plan_badge(&summary.badge),
+                id.value()
+            ));
+        }
+    }
+    html.push_str("</nav>");
+    // --- End Plan Tab Navigation ---
+
+    // --- Display Plan Goal ---
+    if let Some(goal) = &plan.goal {
+        html.push_str(&format!(
+            "<div class='plan-goal'><h2>Goal: {}</h2></div>",
+            html_escape::encode_text(goal)
+        ));
+    }
+    // --- End Display Plan Goal ---
+
+    // --- Display Plan Status ---
+    match (plan.status(), plan.completed_at()) {
+        (models::PlanStatus::Completed, Some(completed_at)) => html.push_str(&format!(
+            "<div class='plan-complete'>&#10003; Plan complete &mdash; completed at {}</div>",
+            completed_at.format("%Y-%m-%d %H:%M:%S UTC")
+        )),
+        (models::PlanStatus::Abandoned, _) => {
+            html.push_str("<div class='plan-status plan-abandoned'>Plan abandoned</div>")
+        }
+        (models::PlanStatus::Draft, _) => {
+            html.push_str("<div class='plan-status'>Draft plan &mdash; add tasks to start</div>")
+        }
+        _ => {}
+    }
+
+    // --- Display Plan Notes ---
+    if let Some(notes) = &plan.notes {
+        html.push_str("<div class='plan-notes'>");
+        html.push_str("<h3>Notes:</h3>");
+        let formatted_notes = html_escape::encode_text(notes).replace("\n", "<br>");
+        html.push_str(&format!("<p>{formatted_notes}</p>"));
+        html.push_str("</div>");
+    }
+    // --- End Display Plan Notes ---
+
+    // Add level legend
+    html.push_str("<section class='level-legend' aria-labelledby='levels-heading'>");
+    html.push_str("<h3 id='levels-heading'>Abstraction Levels</h3>");
+
+    for (i, level) in plan.levels().iter().enumerate() {
+        html.push_str(&format!(
+            "<div class='level-item'><span class='task-level level-{i}'>{i}</span>"
+        ));
+        html.push_str(&format!(
+            "<div class='level-description'><strong>{}</strong>",
+            level.description()
+        ));
+        html.push_str(&format!(
+            "<div class='level-focus'>{}</div></div></div>",
+            level.abstraction_focus()
+        ));
+    }
+    html.push_str("</section>");
+
+    // Add plan data
+    html.push_str("<section class='plan-section' aria-labelledby='tasks-heading'>");
+    html.push_str(&format!(
+        "<h2 id='tasks-heading' tabindex='-1'>Plan <a class='print-link' href='/ui/{}/print'>Print view</a></h2>",
+        current_plan_id.value()
+    ));
+
+    // The chain of blocked tasks that holds up the rest of the plan, linking to each
+    let critical_path = plan.critical_path();
+    if !critical_path.is_empty() {
+        let links: Vec<String> = critical_path
+            .iter()
+            .map(|index| {
+                let label = index
+                    .iter()
+                    .map(|i| i.to_string())
+                    .collect::<Vec<_>>()
+                    .join(".");
+                format!("<a href='#{}'>{label}</a>", task_anchor(index))
+            })
+            .collect();
+        html.push_str(&format!(
+            "<div class='critical-path'><strong>Critical path:</strong> {} &mdash; {} tasks, each blocked by the one before</div>",
+            links.join(" &rarr; "),
+            critical_path.len()
+        ));
+    }
+
+    // Render tasks hierarchically
+    render_tasks_html(
+        &mut html,
+        plan.root().subtasks(),
+        current,
+        plan,
+        &critical_path,
+        Vec::new(),
+    );
+
+    html.push_str("</section>");
+
+    // Add current task highlight if exists
+    if let Some(curr) = current {
+        html.push_str("<section class='current-section' aria-labelledby='current-heading'>");
+        html.push_str("<h2 id='current-heading'>Current Task</h2>");
+        html.push_str(&format!(
+            "<div class='current-task'><h3>{}</h3>",
+            curr.task.description()
+        ));
+        html.push_str(&format!(
+            "<p><strong>Status:</strong> {}</p>",
+            if curr.task.is_completed() {
+                "Completed"
+            } else {
+                "In Progress"
+            }
+        ));
+        html.push_str(&format!(
+            "<p><strong>Level:</strong> {} - {}</p>",
+            curr.task.level_index().unwrap_or(curr.index.len() - 1),
+            curr.level.description()
+        ));
+        html.push_str(&format!(
+            "<p><strong>Index:</strong> {}</p>",
+            curr.index
+                .iter()
+                .map(|i| i.to_string())
+                .collect::<Vec<_>>()
+                .join(",")
+        ));
+
+        // Show subtasks if any
+        if curr.task.live_subtasks().next().is_some() {
+            html.push_str("<div class='subtasks'>");
+            html.push_str("<h4>Subtasks:</h4>");
+            html.push_str("<ul>");
+            for (_, subtask) in curr.task.live_subtasks() {
+                let status_class = if subtask.is_completed() {
+                    "completed"
+                } else {
+                    "pending"
+                };
+                html.push_str(&format!(
+                    "<li class='{}'>{}</li>",
+                    status_class,
+                    subtask.description()
+                ));
+            }
+            html.push_str("</ul>");
+            html.push_str("</div>");
+        }
+
+        html.push_str("</div></section>");
+    }
+
+    // Add Review Queue Panel
+    render_review_queue_html(&mut html, review_queue);
+
+    // Add History Panel (moved inside the container)
+    html.push_str("<section class='history-panel' aria-labelledby='history-heading'>");
+    html.push_str("<h2 id='history-heading'>Transition History</h2>");
+    html.push_str("<ul class='history-list'>");
+    if distilled_context.transition_history.is_empty() {
+        html.push_str("<li>No history yet.</li>");
+    } else {
+        // Iterate in reverse to show newest first
+        for entry in distilled_context.transition_history.iter().rev() {
+            let timestamp_str = entry.timestamp.format("%Y-%m-%d %H:%M:%S UTC").to_string();
+            let details_str = entry.details.as_deref().unwrap_or("");
+            let actor_str = entry
+                .actor
+                .as_ref()
+                .map(|actor| actor.to_string())
+                .unwrap_or_default();
+            html.push_str(&format!(
+                "<li class='history-item'><span class='history-ts'>{}</span><span class='history-action'>{}</span><span class='history-details'>{}</span><span class='history-actor'>{}</span></li>",
+                timestamp_str,
+                entry.action,
+                details_str,
+                html_escape::encode_text(&actor_str)
+            ));
+        }
+    }
+    html.push_str("</ul></section>");
+
+    // Embed the current plan id value, and the API its workspace is served under, for use
+    // in JavaScript
+    html.push_str(&format!(
+        "<script>const CURRENT_PLAN_ID = {}; const API_BASE = {};</script>",
+        current_plan_id.value(),
+        serde_json::to_string(api_base).unwrap_or_else(|_| "\"/api\"".to_string())
+    ));
+
+    html.push_str(HTML_TEMPLATE_FOOTER); // Footer now only contains closing tags and script
+    html
+}
+
+// Helper function to render the tasks awaiting review with approve/reject controls
+fn render_review_queue_html(html: &mut String, review_queue: &[models::ReviewItem]) {
+    html.push_str("<section class='review-panel' aria-labelledby='review-heading'>");
+    html.push_str("<h2 id='review-heading'>Review Queue</h2>");
+    if review_queue.is_empty() {
+        html.push_str("<p>No tasks awaiting review.</p>");
+    } else {
+        html.push_str("<ul class='review-list'>");
+        for item in review_queue {
+            let index_json = serde_json::to_string(&item.index).unwrap_or_default();
+            let label_id = format!("review-{}", task_anchor(&item.index));
+            html.push_str("<li class='review-item'>");
+            html.push_str(&format!(
+                "<div id='{label_id}'><span class='task-path'>{}</span> <strong>{}</strong></div>",
+                item.index
+                    .iter()
+                    .map(|i| i.to_string())
+                    .collect::<Vec<_>>()
+                    .join("."),
+                html_escape::encode_text(&item.description)
+            ));
+            if let Some(summary) = &item.summary {
+                html.push_str(&format!(
+                    "<div class='task-summary'>{}</div>",
+                    html_escape::encode_text(summary)
+                ));
+            }
+            if let Some(notes) = &item.notes {
+                html.push_str(&format!(
+                    "<div class='task-notes'>{}</div>",
+                    html_escape::encode_text(notes)
+                ));
+            }
+            html.push_str(&format!(
+                "<div class='review-actions'><button onclick='reviewTask({index_json}, true)' aria-describedby='{label_id}'>Approve</button> <button onclick='reviewTask({index_json}, false)' aria-describedby='{label_id}'>Reject</button></div>"
+            ));
+            html.push_str("</li>");
+        }
+        html.push_str("</ul>");
+    }
+    html.push_str("</section>");
+}
+
+// Helper function to render tasks hierarchically
+fn render_tasks_html(
+    html: &mut String,
+    tasks: &[crate::models::Task],
+    current: Option<&crate::models::Current>,
+    plan: &crate::models::Plan,
+    critical_path: &[Index],
+    path: Vec<usize>,
+) {
+    if tasks.iter().all(|task| task.is_removed()) {
+        html.push_str("<p>No tasks yet.</p>");
+        return;
+    }
+
+    // The top level is the tree, each nested list a group of treeitems
+    if path.is_empty() {
+        html.push_str("<ul class='task-tree' role='tree' aria-labelledby='tasks-heading'>");
+    } else {
+        html.push_str("<ul class='task-tree' role='group'>");
+    }
+    for (i, task) in tasks.iter().enumerate() {
+        if task.is_removed() {
+            continue;
+        }
+        let mut current_path = path.clone();
+        current_path.push(i);
+
+        // Check if this is the current task
+        let is_current = if let Some(curr) = current {
+            curr.index == current_path
+        } else {
+            false
+        };
+
+        // Determine the effective level (explicit or derived from position)
+        let level_idx = task.level_index().unwrap_or(current_path.len());
+
+        let class = if is_current {
+            if task.is_completed() {
+                "current completed"
+            } else {
+                "current"
+            }
+        } else if task.is_completed() {
+            "completed"
+        } else {
+            ""
+        };
+
+        let anchor = task_anchor(&current_path);
+        // Only the first task is a tab stop until keyboard navigation selects another;
+        // the item is named by its own row rather than by its subtasks too
+        let tab_index = if current_path == [0] { 0 } else { -1 };
+        let expanded = if task.live_subtasks().next().is_none() {
+            ""
+        } else {
+            " aria-expanded='true'"
+        };
+        let current_attr = if is_current {
+            " aria-current='true'"
+        } else {
+            ""
+        };
+        html.push_str(&format!(
+            "<li id='{anchor}' class='{class}' role='treeitem' aria-level='{}' aria-selected='false' aria-labelledby='{anchor}-label' tabindex='{tab_index}'{expanded}{current_attr}><div class='task-item' id='{anchor}-label'>",
+            current_path.len()
+        ));
+
+        // Level indicator
+        html.push_str(&format!(
+            "<span class='task-level level-{level_idx}'><span class='visually-hidden'>Level </span>{level_idx}</span>"
+        ));
+
+        // Path identifier (e.g., 0.1.2), linking to the task
+        html.push_str(&format!(
+            "<a class='task-path' href='#{anchor}'>{}</a>",
+            current_path
+                .iter()
+                .map(|i| i.to_string())
+                .collect::<Vec<_>>()
+                .join(".")
+        ));
+
+        // Task description
+        html.push_str(&format!(
+            "<span class='task-desc'>{}</span>",
+            task.description()
+        ));
+
+        // Add completion summary if available
+        if task.is_completed() {
+            if let Some(summary) = task.completion_summary() {
+                html.push_str(&format!("<span class='task-summary'>{summary}</span>"));
+            }
+        }
+
+        // Review state
+        if task.is_awaiting_review() {
+            html.push_str("<span class='task-review'>awaiting review</span>");
+        }
+
+        for tag in task.tags() {
+            html.push_str(&format!(
+                "<span class='task-tag'>#{}</span>",
+                html_escape::encode_text(tag)
+            ));
+        }
+
+        // Incomplete dependencies, linking to each
+        let blockers = plan.blockers(task);
+        if !blockers.is_empty() {
+            let links: Vec<String> = blockers
+                .iter()
+                .map(|index| {
+                    let label = index
+                        .iter()
+                        .map(|i| i.to_string())
+                        .collect::<Vec<_>>()
+                        .join(".");
+                    format!("<a href='#{}'>{label}</a>", task_anchor(index))
+                })
+                .collect();
+            html.push_str(&format!(
+                "<span class='task-blocked'>blocked by {}</span>",
+                links.join(", ")
+            ));
+        }
+        if critical_path.contains(&current_path) {
+            html.push_str("<span class='task-critical'>critical path</span>");
+        }
+
+        // When the task was completed or last changed, with when it was added on hover
+        let stamp = match (task.is_completed(), task.completed_at()) {
+            (true, Some(at)) => Some(("done", at)),
+            _ => task.updated_at().map(|at| ("updated", at)),
+        };
+        if let Some((verb, at)) = stamp {
+            let added = task
+                .created_at()
+                .map(|created| format!(" title='added {}'", created.format("%Y-%m-%d %H:%M UTC")))
+                .unwrap_or_default();
+            html.push_str(&format!(
+                "<time class='task-time' datetime='{}'{added}>{verb} {}</time>",
+                at.to_rfc3339(),
+                at.format("%Y-%m-%d %H:%M UTC")
+            ));
+        }
+
+        // Task status
+        let (mark, status) = if task.is_completed() {
+            ("✓", "completed")
+        } else {
+            ("○", "incomplete")
+        };
+        html.push_str(&format!(
+            "<span class='task-status'><span aria-hidden='true'>{mark}</span><span class='visually-hidden'>{status}</span></span>"
+        ));
+
+        html.push_str("</div>"); // Close task-item div
+
+        // Render notes if they exist
+        if let Some(notes) = task.notes() {
+            // Escape HTML entities in notes
+            let escaped_notes = html_escape::encode_text(notes);
+            html.push_str(&format!("<div class='task-notes'>{escaped_notes}</div>"));
+        }
+
+        // Render subtasks recursively
+        if task.live_subtasks().next().is_some() {
+            render_tasks_html(
+                html,
+                task.subtasks(),
+                current,
+                plan,
+                critical_path,
+                current_path,
+            );
+        }
+
+        html.push_str("</li>");
+    }
+    html.push_str("</ul>");
+}
+
+// HTML template header with CSS styles and EventSource JavaScript
+const HTML_TEMPLATE_HEADER: &str = r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta charset="UTF-8">
+    <meta name="viewport" content="width=device-width, initial-scale=1.0">
+    <title>Scatterbrain UI</title>
+    <style>
+        body {
+            font-family: -apple-system, BlinkMacSystemFont, 'Segoe UI', Roboto, Oxygen, Ubuntu, Cantarell, 'Open Sans', 'Helvetica Neue', sans-serif;
+            line-height: 1.6;
+            color: #333;
+            max-width: 1200px;
+            margin: 0 auto;
+            padding: 20px;
+            background-color: #f7f9fc;
+        }
+        h1 {
+            color: #2c3e50;
+            border-bottom: 2px solid #3498db;
+            padding-bottom: 10px;
+        }
+        h2 {
+            color: #3498db;
+            margin-top: 30px;
+        }
+        .container {
+            display: flex;
+            flex-wrap: wrap;
+            gap: 20px;
+        }
+        .plan-section,
+        .current-section,
+        .history-panel {
+            flex: 1;
+            min-width: 300px;
+            background: white;
+            padding: 20px;
+            border-radius: 8px;
+            box-shadow: 0 2px 10px rgba(0,0,0,0.1);
+            align-self: flex-start;
+        }
+        .plan-section {
+            /* Specific styles for plan if needed */
+        }
+        .current-section {
+            /* Specific styles for current task if needed */
+             /* Ensure it aligns even if not always present */
+             order: 1;
+        }
+        .history-panel {
+             order: 2;
+        }
+        .review-panel {
+            flex: 1;
+            min-width: 300px;
+            background: white;
+            padding: 20px;
+            border-radius: 8px;
+            box-shadow: 0 2px 10px rgba(0,0,0,0.1);
+            align-self: flex-start;
+            order: 1;
+        }
+        .review-list {
+            list-style-type: none;
+            padding-left: 0;
+        }
+        .review-item {
+            border-bottom: 1px solid #eee;
+            padding: 8px 0;
+        }
+        .review-actions {
+            margin-top: 5px;
+        }
+        .task-tree {
+            list-style-type: none;
+            padding-left: 20px;
+        }
+        .task-item {
+            display: flex;
+            align-items: center;
+            padding: 8px 0;
+            gap: 10px;
+        }
+        .task-path {
+            font-family: monospace;
+            color: #7f8c8d;
+            min-width: 50px;
+            text-decoration: none;
+        }
+        li:target {
+            scroll-margin-top: 80px;
+        }
+        li:target > .task-item {
+            background-color: #fff3bf;
+            outline: 2px solid #f1c40f;
+            border-radius: 4px;
+        }
+        .task-desc {
+            flex-grow: 1;
+        }
+        .task-status {
+            color: #7f8c8d;
+            font-weight: bold;
+        }
+        .task-review {
+            font-size: 0.8em;
+            color: #d35400;
+            border: 1px solid #d35400;
+            border-radius: 4px;
+            padding: 0 4px;
+        }
+        .task-blocked {
+            font-size: 0.8em;
+            color: #c0392b;
+            border: 1px solid #c0392b;
+            border-radius: 4px;
+            padding: 0 4px;
+        }
+        .task-critical {
+            font-size: 0.8em;
+            color: #fff;
+            background: #c0392b;
+            border-radius: 4px;
+            padding: 0 4px;
+        }
+        .critical-path {
+            margin: 10px 0;
+            padding: 8px 12px;
+            background: #fdedec;
+            border-left: 4px solid #c0392b;
+            border-radius: 4px;
+        }
+        .task-tag {
+            font-size: 0.8em;
+            color: #2980b9;
+            background: #eaf2f8;
+            border-radius: 4px;
+            padding: 0 4px;
+        }
+        .task-time {
+            font-size: 0.8em;
+            color: #7f8c8d;
+            white-space: nowrap;
+        }
+        .task-level {
+            display: inline-block;
+            width: 24px;
+            height: 24px;
+            border-radius: 12px;
+            color: white;
+            text-align: center;
+            line-height: 24px;
+            font-size: 12px;
+            font-weight: bold;
+            margin-right: 8px;
+        }
+        .level-0 {
+            background-color: #3498db; /* Blue - High Level */
+            border: 2px solid #2980b9;
+        }
+        .level-1 {
+            background-color: #9b59b6; /* Purple - Isolation */
+            border: 2px solid #8e44ad;
+        }
+        .level-2 {
+            background-color: #2ecc71; /* Green - Ordering */
+            border: 2px solid #27ae60;
+        }
+        .level-3 {
+            background-color: #e67e22; /* Orange - Implementation */
+            border: 2px solid #d35400;
+        }
+        .current {
+            background-color: #e8f4fc;
+            border-left: 4px solid #3498db;
+            padding-left: 10px;
+            margin-left: -14px;
+        }
+        .completed .task-status {
+            color: #27ae60;
+            text-decoration: none !important; /* Ensure status icon is never struck through */
+        }
+        .task-summary {
+            font-style: italic;
+            color: #555;
+            font-size: 0.9em;
+            margin-left: 10px;
+            flex-basis: 100%; /* Ensure summary wraps if needed */
+            order: 2; /* Place summary after main task items */
+        }
+        .current-task {
+            background-color: #f8f9fa;
+            padding: 15px;
+            border-radius: 5px;
+            border-left: 4px solid #3498db;
+        }
+        .subtasks ul {
+            margin-top: 5px;
+            padding-left: 20px;
+        }
+        .subtasks li {
+            margin-bottom: 5px;
+        }
+        .controls {
+            margin-top: 30px;
+            padding: 15px;
+            background: #f0f4f8;
+            border-radius: 5px;
+        }
+        .reactive-status {
+            display: flex;
+            align-items: center;
+            gap: 10px;
+            margin-top: 10px;
+        }
+        .status-indicator {
+            display: inline-block;
+            width: 10px;
+            height: 10px;
+            border-radius: 50%;
+            background-color: #95a5a6;
+        }
+        .status-indicator.connected {
+            background-color: #2ecc71;
+        }
+        .status-indicator.updating {
+            background-color: #f39c12;
+        }
+        .status-text {
             font-size: 14px;
             color: #7f8c8d;
         }
+        .print-link {
+            font-size: 13px;
+            font-weight: normal;
+            margin-left: 10px;
+        }
+        .keyboard-help {
+            font-size: 13px;
+            color: #7f8c8d;
+        }
+        .keyboard-help kbd {
+            padding: 1px 5px;
+            border: 1px solid #ccc;
+            border-radius: 3px;
+            background-color: #f7f7f7;
+        }
+        #keyboard-dialog textarea {
+            width: 100%;
+            min-width: 400px;
+            box-sizing: border-box;
+        }
+        #keyboard-dialog .dialog-actions {
+            margin-top: 10px;
+            text-align: right;
+        }
         .manual-refresh {
             margin-left: auto;
         }
@@ -1127,514 +4233,2761 @@ const HTML_TEMPLATE_HEADER: &str = r#"<!DOCTYPE html>
             border-radius: 5px;
             box-shadow: 0 2px 5px rgba(0,0,0,0.1);
         }
-        .level-legend h3 {
-            margin-top: 0;
-            border-bottom: 1px solid #eee;
-            padding-bottom: 8px;
+        .level-legend h3 {
+            margin-top: 0;
+            border-bottom: 1px solid #eee;
+            padding-bottom: 8px;
+        }
+        .level-item {
+            display: flex;
+            align-items: center;
+            margin-bottom: 10px;
+        }
+        .level-description {
+            margin-left: 10px;
+        }
+        .level-focus {
+            font-size: 0.9em;
+            color: #666;
+            margin-top: 5px;
+        }
+        .history-list {
+            list-style-type: none;
+            padding-left: 0;
+            max-height: 400px;
+            overflow-y: auto;
+        }
+        .history-item {
+            border-bottom: 1px solid #eee;
+            padding: 8px 0;
+            font-size: 0.9em;
+            display: flex;
+            gap: 10px;
+        }
+        .history-ts {
+            color: #7f8c8d;
+            min-width: 160px;
+            white-space: nowrap;
+        }
+        .history-action {
+            font-weight: bold;
+            color: #3498db;
+        }
+        .history-details {
+            color: #555;
+            flex-grow: 1;
+        }
+        .history-actor {
+            color: #7f8c8d;
+            white-space: nowrap;
+        }
+        /* Style completed task description */
+        .completed .task-desc {
+            color: #7f8c8d;
+            text-decoration: line-through;
+        }
+        /* Style completed subtask description */
+        .subtasks li.completed .task-desc {
+            color: #7f8c8d;
+            text-decoration: line-through;
+        }
+        .task-notes {
+            font-size: 0.9em;
+            color: #666;
+            margin-left: 30px; /* Indent notes slightly */
+            margin-top: 5px;
+            padding: 5px 8px;
+            background-color: #f0f0f0;
+            border-left: 3px solid #ccc;
+            white-space: pre-wrap; /* Preserve whitespace and wrap */
+            word-break: break-word;
+        }
+        .plan-complete {
+             background: #e9f7ef;
+             color: #1e8449;
+             font-weight: bold;
+             padding: 10px 15px;
+             border-radius: 5px;
+             margin-bottom: 20px;
+             border-left: 4px solid #27ae60;
+        }
+        .plan-status {
+             background: #f4f6f6;
+             color: #566573;
+             padding: 10px 15px;
+             border-radius: 5px;
+             margin-bottom: 20px;
+             border-left: 4px solid #aab7b8;
+        }
+        .plan-abandoned {
+             color: #922b21;
+             border-left-color: #c0392b;
+        }
+        .plan-goal, .plan-notes { /* Add plan-notes styles */
+             background: #eaf2f8; /* Light blue background */
+             padding: 15px;
+             border-radius: 5px;
+             margin-bottom: 20px;
+             border-left: 4px solid #aed6f1; /* Lighter blue border */
+        }
+        .plan-notes h3 {
+             margin-top: 0;
+             color: #2980b9; /* Darker blue for notes title */
+        }
+        .plan-notes p {
+            white-space: pre-wrap; /* Preserve whitespace and wrap */
+            word-break: break-word;
+            color: #34495e; /* Dark grey text */
+        }
+        a:focus-visible,
+        button:focus-visible,
+        textarea:focus-visible {
+            outline: 3px solid #2980b9;
+            outline-offset: 2px;
+        }
+        li[role='treeitem']:focus {
+            outline: none;
+        }
+        li[role='treeitem']:focus-visible > .task-item {
+            outline: 3px solid #2980b9;
+            border-radius: 4px;
+        }
+        .visually-hidden {
+            position: absolute;
+            width: 1px;
+            height: 1px;
+            overflow: hidden;
+            clip: rect(0 0 0 0);
+            white-space: nowrap;
+        }
+        .skip-link {
+            position: absolute;
+            left: -9999px;
+        }
+        .skip-link:focus {
+            left: 10px;
+            top: 10px;
+            padding: 8px 12px;
+            background: white;
+            z-index: 10;
+        }
+    </style>
+</head>
+<body>
+    <a class="skip-link" href='#tasks-heading'>Skip to tasks</a>
+    <header>
+    <h1>Scatterbrain UI</h1>
+    <div class="controls">
+        <p>Use the CLI to interact with tasks:</p>
+        <code>$ scatterbrain task add "New task"</code> | 
+        <code>$ scatterbrain move 0,1</code> | 
+        <code>$ scatterbrain task complete</code> |
+        <code>$ scatterbrain task change-level 1</code>
+        <p class="keyboard-help">Keyboard: <kbd>j</kbd>/<kbd>k</kbd> next/previous task &middot;
+            <kbd>h</kbd>/<kbd>l</kbd> parent/first subtask &middot; <kbd>c</kbd> complete &middot;
+            <kbd>n</kbd> edit notes</p>
+        <div class="reactive-status" role="status" aria-live="polite">
+            <span class="status-indicator" id="connection-status" aria-hidden="true"></span>
+            <span class="status-text" id="status-text">Waiting to connect...</span>
+        </div>
+    </div>
+    </header>
+    <main class="container">
+        <div class="plan-section">
+"#;
+
+// HTML template footer with EventSource JavaScript for reactive refreshing
+const HTML_TEMPLATE_FOOTER: &str = r#"
+        </div>
+    </main>
+    <dialog id="keyboard-dialog" aria-labelledby="keyboard-dialog-title">
+        <form method="dialog">
+            <h3 id="keyboard-dialog-title"></h3>
+            <textarea id="keyboard-dialog-text" rows="6" aria-labelledby="keyboard-dialog-title"></textarea>
+            <div class="dialog-actions">
+                <button value="cancel">Cancel</button>
+                <button value="save">Save (Ctrl+Enter)</button>
+            </div>
+        </form>
+    </dialog>
+    <script>
+        // EventSource for reactive updates
+        const statusIndicator = document.getElementById('connection-status');
+        const statusText = document.getElementById('status-text');
+        let eventSource;
+
+        // The page reloads on every update; remember that it did so the status live
+        // region can announce the update once the new page connects
+        const UPDATED_KEY = 'scatterbrain-updated';
+        const wasUpdated = sessionStorage.getItem(UPDATED_KEY) !== null;
+        sessionStorage.removeItem(UPDATED_KEY);
+        
+        function connectEvents() {
+            // Use the CURRENT_PLAN_ID injected by the template
+            if (typeof CURRENT_PLAN_ID === 'undefined') {
+                console.error('CURRENT_PLAN_ID is not defined.');
+                statusText.textContent = 'Error: Plan ID missing.';
+                return;
+            }
+            const eventSourceUrl = '/ui/events/' + CURRENT_PLAN_ID;
+            console.log('Connecting to SSE:', eventSourceUrl); 
+            eventSource = new EventSource(eventSourceUrl);
+            
+            eventSource.onopen = () => {
+                statusIndicator.classList.add('connected');
+                statusText.textContent = wasUpdated
+                    ? 'Plan updated. Listening for changes'
+                    : 'Connected: Listening for changes';
+            };
+            
+            eventSource.addEventListener('update', (event) => {
+                // Show updating status
+                statusIndicator.classList.remove('connected');
+                statusIndicator.classList.add('updating');
+                statusText.textContent = 'Updating...';
+                
+                // Reload the page to reflect changes
+                sessionStorage.setItem(UPDATED_KEY, '1');
+                window.location.reload();
+            });
+            
+            eventSource.addEventListener('ping', (event) => {
+                // Just keep the connection alive
+            });
+            
+            eventSource.onerror = () => {
+                statusIndicator.classList.remove('connected');
+                statusIndicator.classList.remove('updating');
+                statusText.textContent = 'Connection lost. Reconnecting...';
+                
+                // Close connection and try again after a delay
+                eventSource.close();
+                setTimeout(connectEvents, 3000);
+            };
+        }
+        
+        // POST to the current plan's API, in the workspace the page was served for,
+        // alerting with `failure` if it is rejected. The page reloads on the update event
+        // that follows a change.
+        function postToPlan(path, body, failure) {
+            fetch(API_BASE + '/plans/' + CURRENT_PLAN_ID + path, {
+                method: 'POST',
+                headers: { 'Content-Type': 'application/json' },
+                body: JSON.stringify(body),
+            })
+                .then((resp) => resp.json())
+                .then((data) => {
+                    if (!data.success) {
+                        window.alert(failure + ': ' + data.error);
+                    }
+                })
+                .catch((err) => window.alert(failure + ': ' + err));
         }
-        .level-item {
-            display: flex;
-            align-items: center;
-            margin-bottom: 10px;
+
+        // Approve or reject a task awaiting review
+        function reviewTask(index, approve) {
+            const body = { index: index };
+            if (!approve) {
+                const reason = window.prompt('Reason for rejection (optional):');
+                if (reason === null) {
+                    return;
+                }
+                body.reason = reason || null;
+            }
+            const action = approve ? 'approve' : 'reject';
+            postToPlan('/task/' + action, body, 'Review failed');
         }
-        .level-description {
-            margin-left: 10px;
+
+        // --- Keyboard navigation --- //
+        // The selected task is the one the URL's #task-... anchor points at, so the
+        // selection survives the reload after each update.
+        const TASK_SELECTOR = 'li[id^="task-"]';
+
+        function selectedTask() {
+            const id = window.location.hash.slice(1);
+            return id.startsWith('task-') ? document.getElementById(id) : null;
         }
-        .level-focus {
-            font-size: 0.9em;
-            color: #666;
-            margin-top: 5px;
+
+        // Tells assistive technology which task is selected, and makes it the tree's
+        // single tab stop
+        function markSelected(task) {
+            document.querySelectorAll(TASK_SELECTOR).forEach((item) => {
+                item.setAttribute('aria-selected', item === task ? 'true' : 'false');
+                item.tabIndex = item === task ? 0 : -1;
+            });
         }
-        .history-list {
-            list-style-type: none;
-            padding-left: 0;
-            max-height: 400px;
-            overflow-y: auto;
+
+        function selectTask(task) {
+            if (task) {
+                window.location.replace('#' + task.id);
+                markSelected(task);
+                task.focus();
+            }
         }
-        .history-item {
-            border-bottom: 1px solid #eee;
-            padding: 8px 0;
-            font-size: 0.9em;
-            display: flex;
-            gap: 10px;
+
+        window.addEventListener('hashchange', () => {
+            const task = selectedTask();
+            if (task) {
+                markSelected(task);
+            }
+        });
+        if (selectedTask()) {
+            markSelected(selectedTask());
         }
-        .history-ts {
-            color: #7f8c8d;
-            min-width: 160px;
-            white-space: nowrap;
+
+        function taskIndex(task) {
+            return task.id.slice('task-'.length).split('-').map(Number);
         }
-        .history-action {
-            font-weight: bold;
-            color: #3498db;
+
+        function taskDescription(task) {
+            return task.querySelector(':scope > .task-item .task-desc').textContent;
         }
-        .history-details {
-            color: #555;
-            flex-grow: 1;
+
+        // Asks for text in the keyboard dialog and passes it to `done` unless cancelled
+        function askText(title, value, done) {
+            const dialog = document.getElementById('keyboard-dialog');
+            const text = document.getElementById('keyboard-dialog-text');
+            document.getElementById('keyboard-dialog-title').textContent = title;
+            text.value = value;
+            dialog.returnValue = '';
+            dialog.onclose = () => {
+                if (dialog.returnValue === 'save') {
+                    done(text.value);
+                }
+            };
+            dialog.showModal();
+            text.focus();
         }
-        /* Style completed task description */
-        .completed .task-desc {
-            color: #7f8c8d;
-            text-decoration: line-through;
+
+        function completeTask(task) {
+            const title = 'Complete "' + taskDescription(task) + '" (summary, optional)';
+            askText(title, '', (summary) => {
+                const body = {
+                    index: taskIndex(task),
+                    lease: null,
+                    force: false,
+                    summary: summary.trim() || null,
+                };
+                postToPlan('/task/complete', body, 'Completion failed');
+            });
         }
-        /* Style completed subtask description */
-        .subtasks li.completed .task-desc {
-            color: #7f8c8d;
-            text-decoration: line-through;
+
+        function editNotes(task) {
+            const notes = task.querySelector(':scope > .task-notes');
+            askText('Notes for "' + taskDescription(task) + '"', notes ? notes.textContent : '', (text) => {
+                const path = '/notes/' + taskIndex(task).join(',');
+                postToPlan(path, { notes: text }, 'Saving notes failed');
+            });
         }
-        .task-notes {
-            font-size: 0.9em;
-            color: #666;
-            margin-left: 30px; /* Indent notes slightly */
-            margin-top: 5px;
-            padding: 5px 8px;
-            background-color: #f0f0f0;
-            border-left: 3px solid #ccc;
-            white-space: pre-wrap; /* Preserve whitespace and wrap */
-            word-break: break-word;
+
+        document.getElementById('keyboard-dialog-text').addEventListener('keydown', (event) => {
+            if (event.key === 'Enter' && (event.ctrlKey || event.metaKey)) {
+                document.getElementById('keyboard-dialog').close('save');
+            }
+        });
+
+        document.addEventListener('keydown', (event) => {
+            const target = event.target;
+            if (event.ctrlKey || event.metaKey || event.altKey
+                || target.closest('dialog')
+                || ['INPUT', 'TEXTAREA', 'SELECT'].includes(target.tagName)) {
+                return;
+            }
+            // Arrow keys move through the tree as well, but only while it has focus so
+            // they still scroll the page elsewhere
+            const inTree = target.closest('[role="tree"]') !== null;
+            const key = inTree
+                ? ({ ArrowDown: 'j', ArrowUp: 'k', ArrowLeft: 'h', ArrowRight: 'l' }[event.key] || event.key)
+                : event.key;
+            const tasks = Array.from(document.querySelectorAll(TASK_SELECTOR));
+            const selected = selectedTask() || (inTree ? target.closest(TASK_SELECTOR) : null);
+            const position = tasks.indexOf(selected);
+            switch (key) {
+                case 'j':
+                    selectTask(tasks[position + 1]);
+                    break;
+                case 'k':
+                    selectTask(tasks[Math.max(position - 1, 0)]);
+                    break;
+                case 'h':
+                    selectTask(selected && selected.parentElement.closest(TASK_SELECTOR));
+                    break;
+                case 'l':
+                    selectTask(selected && selected.querySelector(TASK_SELECTOR));
+                    break;
+                case 'c':
+                    if (selected) {
+                        completeTask(selected);
+                    }
+                    break;
+                case 'n':
+                    if (selected) {
+                        editNotes(selected);
+                    }
+                    break;
+                default:
+                    return;
+            }
+            event.preventDefault();
+        });
+
+        // Start event connection when page loads
+        window.addEventListener('load', connectEvents);
+        
+        // Clean up on unload
+        window.addEventListener('beforeunload', () => {
+            if (eventSource) {
+                eventSource.close();
+            }
+        });
+    </script>
+</body>
+</html>
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*; // Import items from parent module (server)
+    use crate::models::{Index, PlanId, PlanResponse}; // Added Index, PlanResponse
+    use crate::search::{MatchField, SearchHit};
+    use axum::{
+        body::Body,
+        http::{Request, StatusCode},
+    };
+    use http_body_util::BodyExt; // for `collect`
+    use serde::de::DeserializeOwned; // Added missing import
+    use serde_json::json;
+    use tower::ServiceExt; // for `oneshot`
+
+    // Type aliases to simplify complex types in tests
+    type NotesResponse = PlanResponse<Option<String>>;
+    type NotesResult = (StatusCode, Option<NotesResponse>);
+    type UnitResult = Result<(StatusCode, Option<PlanResponse<()>>), String>;
+
+    // Helper to create a test Core and Router
+    fn setup_test_app() -> (Core, Router) {
+        let core = Core::new();
+        let app = Router::new()
+            // Add all routes needed for testing
+            .route("/api/plans", post(create_plan_handler))
+            .route("/api/plans/:id/task", post(add_task))
+            .route(
+                "/api/plans/:id/notes/*index",
+                // Define only GET and POST here
+                get(get_notes_handler).post(set_notes_handler),
+            )
+            // Explicitly define the DELETE route
+            .route("/api/plans/:id/notes/*index", delete(delete_notes_handler))
+            .with_state(core.clone());
+        (core, app)
+    }
+
+    // Helper to make requests and deserialize JSON response data
+    async fn request_json<T: DeserializeOwned + Serialize>(
+        app: &Router,
+        method: &str,
+        uri: &str,
+        body: Body,
+    ) -> Result<(StatusCode, Option<T>), String> {
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method(method)
+                    .uri(uri)
+                    .header("Content-Type", "application/json") // Add Content-Type header
+                    .body(body)
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        let status = response.status();
+        let body_bytes = response.into_body().collect().await.unwrap().to_bytes();
+        let body_str = String::from_utf8_lossy(&body_bytes);
+
+        if status.is_success() {
+            let deserialize_result = serde_json::from_slice::<ApiResponse<T>>(&body_bytes);
+
+            match deserialize_result {
+                Ok(api_resp) => {
+                    if api_resp.success {
+                        // Return the Option<T> directly
+                        Ok((status, api_resp.data))
+                    } else {
+                        Err(format!(
+                            "API Error: {} (Status: {})",
+                            api_resp.error.unwrap_or_default(),
+                            status
+                        ))
+                    }
+                }
+                Err(e) => Err(format!(
+                    "Failed to parse success response: {e}. Body: {body_str}"
+                )),
+            }
+        } else {
+            // Attempt to parse as error response
+            match serde_json::from_slice::<ApiResponse<()>>(&body_bytes) {
+                Ok(api_resp) => Err(format!(
+                    "API Error: {} (Status: {})",
+                    api_resp.error.unwrap_or_default(),
+                    status
+                )),
+                Err(_) => Err(format!("HTTP Error: {status} Body: {body_str}")),
+            }
         }
-        .plan-goal, .plan-notes { /* Add plan-notes styles */
-             background: #eaf2f8; /* Light blue background */
-             padding: 15px;
-             border-radius: 5px;
-             margin-bottom: 20px;
-             border-left: 4px solid #aed6f1; /* Lighter blue border */
+    }
+
+    #[tokio::test]
+    async fn test_event_stream_catch_up() {
+        use futures::StreamExt;
+
+        let core = Core::new();
+        let plan_id = core.create_plan("Events".to_string(), None).unwrap();
+        core.add_task(&plan_id, "Seen".to_string(), 0, None)
+            .unwrap();
+        let seen = core.next_seq(&plan_id).unwrap() - 1;
+        core.add_task(&plan_id, "Missed".to_string(), 0, None)
+            .unwrap();
+        let missed_from = seen + 1;
+        async fn next(stream: &mut EventStream) -> Option<String> {
+            tokio::time::timeout(Duration::from_secs(1), stream.next())
+                .await
+                .expect("a frame")
+                .map(Result::unwrap)
         }
-        .plan-notes h3 {
-             margin-top: 0;
-             color: #2980b9; /* Darker blue for notes title */
+
+        // Reconnecting replays what was logged after the last event the client saw
+        let receiver = core.subscribe_plan(plan_id).unwrap();
+        let mut stream = EventStream::new(core.clone(), plan_id, receiver, Some(seen), None);
+        let frame = next(&mut stream).await.unwrap();
+        assert!(frame.starts_with(&format!("id: {missed_from}\nevent: transition\ndata: {{")));
+        assert!(frame.contains("\"action\":\"add_task\""));
+        assert_eq!(next(&mut stream).await.as_deref(), Some(UPDATE_FRAME));
+
+        // Later changes arrive as they happen, numbered on from there
+        core.move_to(&plan_id, vec![0]).unwrap();
+        let frame = next(&mut stream).await.unwrap();
+        assert!(frame.starts_with(&format!("id: {}\n", missed_from + 1)));
+        assert_eq!(next(&mut stream).await.as_deref(), Some(UPDATE_FRAME));
+
+        // Reconnecting after the missed transitions were compacted away asks for a resync,
+        // and numbering carries on from there
+        core.compact_history(&plan_id, 1).unwrap();
+        let receiver = core.subscribe_plan(plan_id).unwrap();
+        let mut stream = EventStream::new(core.clone(), plan_id, receiver, Some(seen), None);
+        assert_eq!(next(&mut stream).await.as_deref(), Some(RESYNC_FRAME));
+        assert_eq!(next(&mut stream).await.as_deref(), Some(UPDATE_FRAME));
+        let next_seq = core.next_seq(&plan_id).unwrap();
+        core.move_to(&plan_id, Vec::new()).unwrap();
+        let frame = next(&mut stream).await.unwrap();
+        assert!(frame.starts_with(&format!("id: {next_seq}\n")));
+        assert_eq!(next(&mut stream).await.as_deref(), Some(UPDATE_FRAME));
+
+        // A fresh connection starts at the newest transition, pings while idle, and ends
+        // once the plan is deleted
+        let receiver = core.subscribe_plan(plan_id).unwrap();
+        let mut fresh = EventStream::new(
+            core.clone(),
+            plan_id,
+            receiver,
+            None,
+            Some(Duration::from_millis(10)),
+        );
+        assert_eq!(next(&mut fresh).await.as_deref(), Some(PING_FRAME));
+        core.delete_plan(&plan_id).unwrap();
+        assert_eq!(next(&mut fresh).await.as_deref(), Some(UPDATE_FRAME));
+        assert_eq!(next(&mut fresh).await, None);
+    }
+
+    #[tokio::test]
+    async fn test_long_poll_changes() {
+        let core = Core::new();
+        let app = Router::new()
+            .route("/api/plans/:id/changes", get(get_changes))
+            .with_state(core.clone());
+        let plan_id = core.create_plan("Changes".to_string(), None).unwrap();
+        core.add_task(&plan_id, "First".to_string(), 0, None)
+            .unwrap();
+        let len = core.next_seq(&plan_id).unwrap();
+        let id = plan_id.value();
+
+        // Changes already logged are returned at once
+        let (status, changes) = request_json::<PlanChanges>(
+            &app,
+            "GET",
+            &format!("/api/plans/{id}/changes?since=0"),
+            Body::empty(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(status, StatusCode::OK);
+        let changes = changes.unwrap();
+        assert_eq!(changes.changes.len(), len);
+        assert_eq!(changes.next_seq, len);
+
+        // Otherwise the poll waits for the next change
+        let waiting = {
+            let app = app.clone();
+            let uri = format!("/api/plans/{id}/changes?since={len}&timeout=5s");
+            tokio::spawn(async move {
+                request_json::<PlanChanges>(&app, "GET", &uri, Body::empty()).await
+            })
+        };
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        core.add_task(&plan_id, "Second".to_string(), 0, None)
+            .unwrap();
+        let (_, changes) = waiting.await.unwrap().unwrap();
+        let changes = changes.unwrap();
+        assert_eq!(changes.changes[0].seq, len);
+        assert_eq!(changes.changes[0].entry.action, "add_task");
+
+        // or gives up empty-handed
+        let (_, changes) = request_json::<PlanChanges>(
+            &app,
+            "GET",
+            &format!("/api/plans/{id}/changes?timeout=0"),
+            Body::empty(),
+        )
+        .await
+        .unwrap();
+        let changes = changes.unwrap();
+        assert!(changes.changes.is_empty());
+        assert_eq!(changes.next_seq, core.next_seq(&plan_id).unwrap());
+
+        // Transitions compacted away can't be returned, so the client has to resync
+        core.compact_history(&plan_id, 1).unwrap();
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri(format!("/api/plans/{id}/changes?since=0"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::GONE);
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri(format!("/api/plans/{id}/changes?timeout=soon"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_slow_validator_holds_up_nothing_else() {
+        use crate::validators::{CompletionValidator, PendingCompletion};
+        use std::sync::mpsc;
+
+        // Passes once the test opens it
+        struct Gate(Mutex<mpsc::Receiver<()>>);
+        impl CompletionValidator for Gate {
+            fn validate(&self, _: &PendingCompletion) -> Result<(), String> {
+                let gate = self.0.lock().unwrap();
+                gate.recv_timeout(Duration::from_secs(5))
+                    .map_err(|e| e.to_string())
+            }
         }
-        .plan-notes p {
-            white-space: pre-wrap; /* Preserve whitespace and wrap */
-            word-break: break-word;
-            color: #34495e; /* Dark grey text */
+
+        let core = Core::new();
+        let plan_id = core.create_plan("Gated".to_string(), None).unwrap();
+        core.add_task(&plan_id, "Parser".to_string(), 0, None)
+            .unwrap();
+        let (open, gate) = mpsc::channel();
+        core.register_validator("gate".to_string(), Arc::new(Gate(Mutex::new(gate))))
+            .unwrap();
+        let policy = models::CompletionPolicy {
+            validators: vec!["gate".to_string()],
+            ..Default::default()
+        };
+        core.set_completion_policy(&plan_id, policy).unwrap();
+        let app = router(ConfigReloader::new(
+            core.clone(),
+            ServerConfig::default(),
+            None,
+        ));
+        let id = plan_id.value();
+
+        let completing = {
+            let app = app.clone();
+            let uri = format!("/api/plans/{id}/task/complete");
+            let body = json!({ "index": [0], "force": false }).to_string();
+            tokio::spawn(async move {
+                request_json::<PlanResponse<()>>(&app, "POST", &uri, Body::from(body)).await
+            })
+        };
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        // The test runtime has a single thread, which the waiting validator would otherwise
+        // hold, and the plan isn't locked while it waits
+        let added = tokio::time::timeout(
+            Duration::from_secs(2),
+            request_json::<serde_json::Value>(
+                &app,
+                "POST",
+                &format!("/api/plans/{id}/task"),
+                Body::from(json!({ "description": "Lexer", "level_index": 0 }).to_string()),
+            ),
+        )
+        .await
+        .expect("requests should be served while a validator runs");
+        assert!(added.is_ok(), "{added:?}");
+        assert!(!completing.is_finished());
+
+        open.send(()).unwrap();
+        completing.await.unwrap().unwrap();
+        let plan = core.get_plan(&plan_id).unwrap().into_inner();
+        assert!(plan.root().subtasks()[0].is_completed());
+    }
+
+    #[tokio::test]
+    async fn test_notes_api_crud() {
+        let (_core, app) = setup_test_app();
+
+        // 1. Create Plan
+        let create_body = Body::from(json!({ "prompt": "Notes API Test" }).to_string());
+        let (_, plan_id_resp_opt): (_, Option<PlanId>) =
+            request_json(&app, "POST", "/api/plans", create_body)
+                .await
+                .expect("Failed to create plan");
+        let plan_id = plan_id_resp_opt.expect("Plan ID should be present").value();
+
+        // 2. Add Task (without notes initially)
+        let add_task_body = Body::from(
+            json!({
+                "description": "Task for Notes",
+                "level_index": 0,
+                "notes": null // Explicitly send null for None
+            })
+            .to_string(),
+        );
+        let add_uri = format!("/api/plans/{plan_id}/task");
+        let (_, add_resp_opt): (_, Option<PlanResponse<(models::Task, Index)>>) =
+            request_json(&app, "POST", &add_uri, add_task_body)
+                .await
+                .expect("Failed to add task");
+        let task_index = add_resp_opt
+            .expect("Add task response should be present")
+            .inner()
+            .1
+            .clone();
+
+        // Levels that don't exist are rejected without adding a task
+        let bad_level_body = Body::from(
+            json!({ "description": "Nowhere", "level_index": 99, "notes": null }).to_string(),
+        );
+        let err = request_json::<PlanResponse<(models::Task, Index)>>(
+            &app,
+            "POST",
+            &add_uri,
+            bad_level_body,
+        )
+        .await
+        .unwrap_err();
+        assert!(
+            err.contains("out of bounds") && err.contains("422"),
+            "{err}"
+        );
+
+        let task_index_str = task_index
+            .iter()
+            .map(|i| i.to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+
+        // 3. Get Notes (should be null/None initially)
+        let get_uri = format!("/api/plans/{plan_id}/notes/{task_index_str}");
+        let (status_get1, notes_resp1_opt): NotesResult =
+            request_json(&app, "GET", &get_uri, Body::empty())
+                .await
+                .expect("Failed to get initial notes");
+        assert_eq!(status_get1, StatusCode::OK);
+        assert_eq!(
+            notes_resp1_opt
+                .expect("Get notes response missing")
+                .into_inner(),
+            None
+        );
+
+        // 4. Set Notes
+        let notes_content = "These are my notes.\nWith a newline.".to_string();
+        let set_body = Body::from(json!({ "notes": notes_content }).to_string());
+        let set_uri = format!("/api/plans/{plan_id}/notes/{task_index_str}");
+        let (status_set, set_resp_opt): (_, Option<PlanResponse<()>>) =
+            request_json(&app, "POST", &set_uri, set_body)
+                .await
+                .expect("Failed to set notes");
+        assert_eq!(status_set, StatusCode::OK);
+        set_resp_opt.expect("Set response missing");
+
+        // 5. Get Notes (should be the set content)
+        let (status_get2, notes_resp2_opt): NotesResult =
+            request_json(&app, "GET", &get_uri, Body::empty())
+                .await
+                .expect("Failed to get set notes");
+        assert_eq!(status_get2, StatusCode::OK);
+        assert_eq!(
+            notes_resp2_opt
+                .expect("Get notes 2 response missing")
+                .into_inner(),
+            Some(notes_content.clone())
+        );
+
+        // 5.5 Check HTML UI for notes -- REMOVED FOR DEBUGGING
+        /*
+        // NOTE: This UI check, when run before the DELETE step below,
+        // somehow causes the subsequent DELETE request in this test to fail with 404.
+        // The exact reason is unclear, possibly related to test router state or oneshot interaction.
+        // The DELETE functionality is verified separately in test_notes_api_delete.
+        let ui_uri = format!("/ui/{}", plan_id);
+        let ui_response = app
+            .clone()
+            .oneshot(Request::builder().uri(ui_uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(ui_response.status(), StatusCode::OK);
+        let ui_body_bytes = ui_response.into_body().collect().await.unwrap().to_bytes();
+        let ui_html = String::from_utf8_lossy(&ui_body_bytes);
+        assert!(
+            ui_html.contains(&format!("<div class='task-notes'>{}</div>", notes_content)),
+            "HTML UI should contain the task notes"
+        );
+        */
+
+        // 6. Delete Notes
+        let delete_uri = format!("/api/plans/{plan_id}/notes/{task_index_str}");
+        let (status_delete, delete_resp_opt): (_, Option<PlanResponse<()>>) =
+            // Add Content-Length: 0 header to the DELETE request
+            app.clone()
+                .oneshot(
+                    Request::builder()
+                        .method("DELETE")
+                        .uri(&delete_uri)
+                        .header("Content-Type", "application/json") // Keep this
+                        .header("Content-Length", "0") // Add explicit content length
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .map(|response| {
+                    let status = response.status();
+                    let body_bytes = futures::executor::block_on(response.into_body().collect()).unwrap().to_bytes();
+                    let data_opt = if status.is_success() {
+                        serde_json::from_slice::<ApiResponse<PlanResponse<()>>>(&body_bytes)
+                            .ok()
+                            .and_then(|resp| resp.data)
+                    } else {
+                        None
+                    };
+                    (status, data_opt)
+                })
+                .expect("Failed to delete notes");
+
+        assert_eq!(status_delete, StatusCode::OK);
+        delete_resp_opt.expect("Delete response missing");
+
+        // 7. Get Notes (should be null/None again)
+        let (status_get3, notes_resp3_opt): NotesResult =
+            request_json(&app, "GET", &get_uri, Body::empty())
+                .await
+                .expect("Failed to get notes after delete");
+        assert_eq!(status_get3, StatusCode::OK);
+        assert_eq!(
+            notes_resp3_opt
+                .expect("Get notes 3 response missing")
+                .into_inner(),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_review_queue_api() {
+        let core = Core::new();
+        let app = Router::new()
+            .route("/api/plans/:id/task/review", post(request_review))
+            .route("/api/plans/:id/task/approve", post(approve_task))
+            .route("/api/plans/:id/review-queue", get(get_review_queue))
+            .with_state(core.clone());
+
+        let plan_id = core.create_plan("Review Test".to_string(), None).unwrap();
+        core.add_task(&plan_id, "Task to review".to_string(), 0, None)
+            .unwrap();
+        let id = plan_id.value();
+        let queue_uri = format!("/api/plans/{id}/review-queue");
+
+        // 1. Queue starts empty
+        let (_, queue_opt): (_, Option<PlanResponse<Vec<models::ReviewItem>>>) =
+            request_json(&app, "GET", &queue_uri, Body::empty())
+                .await
+                .expect("Failed to get review queue");
+        assert!(queue_opt.unwrap().into_inner().is_empty());
+
+        // 2. Submit the task for review
+        let review_body = Body::from(json!({ "index": [0], "summary": "Ready to go" }).to_string());
+        let review_uri = format!("/api/plans/{id}/task/review");
+        let (status, _): (_, Option<PlanResponse<()>>) =
+            request_json(&app, "POST", &review_uri, review_body)
+                .await
+                .expect("Failed to request review");
+        assert_eq!(status, StatusCode::OK);
+
+        // 3. Queue lists the task with its summary
+        let (_, queue_opt): (_, Option<PlanResponse<Vec<models::ReviewItem>>>) =
+            request_json(&app, "GET", &queue_uri, Body::empty())
+                .await
+                .expect("Failed to get review queue");
+        let queue = queue_opt.unwrap().into_inner();
+        assert_eq!(queue.len(), 1);
+        assert_eq!(queue[0].index, vec![0]);
+        assert_eq!(queue[0].summary, Some("Ready to go".to_string()));
+
+        // 4. Approving twice fails the second time
+        let approve_uri = format!("/api/plans/{id}/task/approve");
+        let approve_body = || Body::from(json!({ "index": [0] }).to_string());
+        let first: UnitResult = request_json(&app, "POST", &approve_uri, approve_body()).await;
+        assert!(first.is_ok());
+        let second: UnitResult = request_json(&app, "POST", &approve_uri, approve_body()).await;
+        assert!(second.unwrap_err().contains("not awaiting review"));
+    }
+
+    #[tokio::test]
+    async fn test_notes_api_delete() {
+        let (_core, app) = setup_test_app();
+
+        // 1. Create Plan
+        let create_body = Body::from(json!({ "prompt": "Delete Notes Test" }).to_string());
+        let (_, plan_id_resp_opt): (_, Option<PlanId>) =
+            request_json(&app, "POST", "/api/plans", create_body)
+                .await
+                .expect("Failed to create plan");
+        let plan_id = plan_id_resp_opt.expect("Plan ID should be present").value();
+
+        // 2. Add Task
+        let add_task_body = Body::from(
+            json!({
+                "description": "Task to delete notes from",
+                "level_index": 0,
+                "notes": "Initial notes for delete test"
+            })
+            .to_string(),
+        );
+        let add_uri = format!("/api/plans/{plan_id}/task");
+        let (_, add_resp_opt): (_, Option<PlanResponse<(models::Task, Index)>>) =
+            request_json(&app, "POST", &add_uri, add_task_body)
+                .await
+                .expect("Failed to add task");
+        let task_index = add_resp_opt
+            .expect("Add task response should be present")
+            .inner()
+            .1
+            .clone();
+        let task_index_str = task_index
+            .iter()
+            .map(|i| i.to_string())
+            .collect::<Vec<_>>()
+            .join(","); // Should be "0"
+
+        // --- Test 1: Delete Existing Notes ---
+        let delete_uri = format!("/api/plans/{plan_id}/notes/{task_index_str}");
+        let response1 = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("DELETE")
+                    .uri(&delete_uri)
+                    .header("Content-Type", "application/json")
+                    .header("Content-Length", "0")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            response1.status(),
+            StatusCode::OK,
+            "Test 1 Failed: First DELETE should return OK"
+        );
+
+        // --- Test 2: Delete Again (Idempotency) ---
+        let response2 = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("DELETE")
+                    .uri(&delete_uri) // Same URI
+                    .header("Content-Type", "application/json")
+                    .header("Content-Length", "0")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        // Core::delete_task_notes always returns Ok(()) if the task exists, even if notes are already None.
+        assert_eq!(
+            response2.status(),
+            StatusCode::OK,
+            "Test 2 Failed: Second DELETE should also return OK"
+        );
+
+        // --- Test 3: Delete Non-existent Notes (Bad Index) ---
+        let bad_delete_uri = format!("/api/plans/{plan_id}/notes/99"); // Invalid index
+        let response3 = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("DELETE")
+                    .uri(&bad_delete_uri)
+                    .header("Content-Type", "application/json")
+                    .header("Content-Length", "0")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        // A task that doesn't exist is reported as NOT_FOUND
+        assert_eq!(
+            response3.status(),
+            StatusCode::NOT_FOUND,
+            "Test 3 Failed: DELETE with bad index should return NOT_FOUND"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_history_export_jsonl() {
+        let core = Core::new();
+        let plan_id = core.create_plan("Exported".to_string(), None).unwrap();
+        core.add_task(&plan_id, "Task".to_string(), 0, None)
+            .unwrap();
+        let app = router(ConfigReloader::new(
+            core.clone(),
+            ServerConfig::default(),
+            None,
+        ));
+        let get = |query: &str| {
+            let request = Request::builder()
+                .uri(format!("/api/plans/{}/history{query}", plan_id.value()))
+                .body(Body::empty())
+                .unwrap();
+            app.clone().oneshot(request)
+        };
+
+        let response = get("?format=jsonl").await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            "application/x-ndjson"
+        );
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let records: Vec<models::HistoryRecord> = String::from_utf8(body.to_vec())
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(records.len(), core.history(&plan_id).unwrap().len());
+        assert_eq!(records.last().unwrap().entry.action, "add_task");
+        assert!(records.iter().all(|r| r.plan_id == plan_id));
+
+        let response = get("?format=csv").await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_activity_heatmap() {
+        let today = chrono::NaiveDate::from_ymd_opt(2025, 6, 4).unwrap(); // A Wednesday
+        let start = heatmap_start(today);
+        assert_eq!(start.weekday(), chrono::Weekday::Sun);
+        assert_eq!((today - start).num_days(), (HEATMAP_WEEKS - 1) * 7 + 3);
+
+        let activity = [(today, 8), (today - chrono::Duration::days(1), 1)]
+            .into_iter()
+            .collect();
+        let svg = render_activity_heatmap(&activity, today);
+        assert_eq!(
+            svg.matches("<rect").count() as i64,
+            (today - start).num_days() + 1
+        );
+        assert!(svg.contains("9 changes in the last 12 weeks"));
+        assert!(svg.contains(&format!(
+            "fill='{}'><title>2025-06-04: 8 changes",
+            HEATMAP_COLORS[4]
+        )));
+        assert!(svg.contains(&format!(
+            "fill='{}'><title>2025-06-03: 1 change<",
+            HEATMAP_COLORS[1]
+        )));
+
+        // The plan list shows a heatmap for each plan
+        let core = Core::new();
+        let plan_id = core.create_plan("Busy".to_string(), None).unwrap();
+        core.add_task(&plan_id, "Task".to_string(), 0, None)
+            .unwrap();
+        let now = chrono::Utc::now().date_naive();
+        let activity = core.daily_activity(&plan_id, heatmap_start(now)).unwrap();
+        assert_eq!(activity.keys().collect::<Vec<_>>(), vec![&now]);
+        let app = router(ConfigReloader::new(core, ServerConfig::default(), None));
+        let request = Request::builder().uri("/ui").body(Body::empty()).unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        assert!(String::from_utf8_lossy(&body).contains("<svg class='activity-heatmap'"));
+    }
+
+    #[tokio::test]
+    async fn test_task_deep_links() {
+        let core = Core::new();
+        let plan_id = core.create_plan("Linked".to_string(), None).unwrap();
+        core.add_task(&plan_id, "Parent".to_string(), 0, None)
+            .unwrap();
+        core.move_to(&plan_id, vec![0]).unwrap();
+        core.add_task(&plan_id, "Child".to_string(), 1, None)
+            .unwrap();
+        let id = plan_id.value();
+        let app = router(ConfigReloader::new(core, ServerConfig::default(), None));
+
+        let get = |uri: String| {
+            let app = app.clone();
+            async move {
+                let request = Request::builder().uri(uri).body(Body::empty()).unwrap();
+                app.oneshot(request).await.unwrap()
+            }
+        };
+        let response = get(format!("/ui/{id}/task/0,0")).await;
+        assert_eq!(response.status(), StatusCode::TEMPORARY_REDIRECT);
+        assert_eq!(
+            response.headers()[header::LOCATION],
+            format!("/ui/{id}#task-0-0").as_str()
+        );
+        let response = get(format!("/ui/{id}/task/zero")).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        // Every task in the tree carries the anchor the link points at
+        let response = get(format!("/ui/{id}")).await;
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let html = String::from_utf8_lossy(&body);
+        assert!(html.contains("<li id='task-0-0'"));
+        assert!(html.contains("<a class='task-path' href='#task-0-0'>0.0</a>"));
+        // ... which keyboard navigation moves between
+        assert!(html.contains("<dialog id=\"keyboard-dialog\""));
+    }
+
+    #[test]
+    fn test_ui_posts_to_its_workspace() {
+        assert_eq!(workspace_api_base("/ui/3"), "/api");
+        assert_eq!(
+            workspace_api_base("/ui/workspaces/infra/3"),
+            "/api/workspaces/infra"
+        );
+
+        let plan = models::Plan::new(crate::default_levels(), None, None);
+        let context = models::DistilledContext::builder().build();
+        let html = render_ui_template(
+            &models::Lease::new(3),
+            &[],
+            &plan,
+            None,
+            &[],
+            &context,
+            "/api/workspaces/infra",
+        );
+        assert!(html.contains("const API_BASE = \"/api/workspaces/infra\";"));
+        assert!(html.contains("fetch(API_BASE + '/plans/' + CURRENT_PLAN_ID + path"));
+    }
+
+    #[tokio::test]
+    async fn test_ui_accessibility() {
+        let core = Core::new();
+        let plan_id = core.create_plan("Accessible".to_string(), None).unwrap();
+        core.add_task(&plan_id, "Parent".to_string(), 0, None)
+            .unwrap();
+        core.move_to(&plan_id, vec![0]).unwrap();
+        core.add_task(&plan_id, "Child".to_string(), 1, None)
+            .unwrap();
+        core.move_to(&plan_id, vec![0, 0]).unwrap();
+        let id = plan_id.value();
+        let app = router(ConfigReloader::new(core, ServerConfig::default(), None));
+
+        let request = Request::builder()
+            .uri(format!("/ui/{id}"))
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let html = String::from_utf8_lossy(&body);
+
+        // Landmarks, with the status line announced as it changes
+        assert!(html.contains("<main class=\"container\">") && html.contains("</main>"));
+        assert!(html.contains("<nav class='plan-tabs' aria-label='Plans'>"));
+        assert!(html.contains(&format!("class='active' aria-current='page'>Plan {id}</a>")));
+        assert!(html.contains("role=\"status\" aria-live=\"polite\""));
+
+        // The task list is a tree, named by its heading, whose items are named by their rows
+        assert!(html.contains("role='tree' aria-labelledby='tasks-heading'"));
+        assert!(html.contains("<h2 id='tasks-heading'"));
+        assert!(html.contains(
+            "<li id='task-0' class='' role='treeitem' aria-level='1' aria-selected='false' \
+             aria-labelledby='task-0-label' tabindex='0' aria-expanded='true'>"
+        ));
+        assert!(html.contains("<ul class='task-tree' role='group'>"));
+        assert!(html.contains(
+            "role='treeitem' aria-level='2' aria-selected='false' aria-labelledby='task-0-0-label' \
+             tabindex='-1' aria-current='true'>"
+        ));
+        assert!(html.contains("<span class='visually-hidden'>incomplete</span>"));
+    }
+
+    #[tokio::test]
+    async fn test_embed_view() {
+        let core = Core::new();
+        let plan_id = core.create_plan("Embedded".to_string(), None).unwrap();
+        core.add_task(
+            &plan_id,
+            "Done".to_string(),
+            0,
+            Some("Private notes".to_string()),
+        )
+        .unwrap();
+        core.add_task(&plan_id, "Next".to_string(), 0, None)
+            .unwrap();
+        core.complete_task(&plan_id, vec![0], None, None, true, None, None)
+            .unwrap();
+        core.move_to(&plan_id, vec![1]).unwrap();
+        let id = plan_id.value();
+        let app = router(ConfigReloader::new(core, ServerConfig::default(), None));
+
+        let request = Request::builder()
+            .uri(format!("/ui/{id}/embed"))
+            .body(Body::empty())
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        // Nothing stops the page from being framed
+        assert!(!response.headers().contains_key("x-frame-options"));
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let html = String::from_utf8_lossy(&body);
+        assert!(html.contains("<progress value='1' max='2'"));
+        assert!(html.contains("<li class='open current' aria-current='true'>"));
+        assert!(html.contains(&format!("new EventSource('/ui/events/{id}')")));
+        assert!(!html.contains("Private notes"));
+        assert!(!html.contains("<button"));
+
+        let request = Request::builder()
+            .uri(format!("/ui/{}/embed", plan_id.value().wrapping_add(1)))
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_print_view() {
+        let core = Core::new();
+        let plan_id = core
+            .create_plan("Ship <it>".to_string(), Some("Plan notes".to_string()))
+            .unwrap();
+        core.add_task(&plan_id, "Design".to_string(), 0, None)
+            .unwrap();
+        core.move_to(&plan_id, vec![0]).unwrap();
+        core.add_task(
+            &plan_id,
+            "Sketch".to_string(),
+            1,
+            Some("Use paper".to_string()),
+        )
+        .unwrap();
+        core.complete_task(
+            &plan_id,
+            vec![0, 0],
+            None,
+            None,
+            true,
+            None,
+            Some("Sketched it".to_string()),
+        )
+        .unwrap();
+        let id = plan_id.value();
+        let app = router(ConfigReloader::new(core, ServerConfig::default(), None));
+
+        let request = Request::builder()
+            .uri(format!("/ui/{id}/print"))
+            .body(Body::empty())
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let html = String::from_utf8_lossy(&body);
+        assert!(html.contains("<h1>Ship &lt;it&gt;</h1>"));
+        assert!(html.contains("1/2 tasks done"));
+        assert!(html.contains("<span class='index'>0.0</span> Sketch"));
+        assert!(html.contains("<div class='summary'>Sketched it</div>"));
+        assert!(html.contains("<div class='notes'>Use paper</div>"));
+        // Nothing interactive or live
+        assert!(!html.contains("<script"));
+        assert!(!html.contains("<button"));
+
+        let request = Request::builder()
+            .uri(format!("/ui/{}/print", plan_id.value().wrapping_add(1)))
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_plan_badges_in_ui() {
+        let core = Core::new();
+        let plan_id = core.create_plan("Badged".to_string(), None).unwrap();
+        let badge = models::PlanBadge {
+            color: Some(models::PlanColor::Red),
+            emoji: Some("🔥".to_string()),
+        };
+        core.set_plan_badge(&plan_id, badge).unwrap();
+        let id = plan_id.value();
+        let app = router(ConfigReloader::new(core, ServerConfig::default(), None));
+
+        let badge_html =
+            "<span class='plan-badge' style='color: #e74c3c;' title='red' role='img' aria-label='red'>&#9679;</span> 🔥 ";
+        let expected = [
+            (
+                "/ui".to_string(),
+                format!("{badge_html}<a href=\"/ui/{id}\">Plan {id}</a>"),
+            ),
+            (format!("/ui/{id}"), format!("{badge_html}Plan {id}</a>")),
+        ];
+        for (uri, expected) in expected {
+            let request = Request::builder().uri(uri).body(Body::empty()).unwrap();
+            let response = app.clone().oneshot(request).await.unwrap();
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            assert!(String::from_utf8_lossy(&body).contains(&expected));
         }
-    </style>
-</head>
-<body>
-    <h1>Scatterbrain UI</h1>
-    <div class="controls">
-        <p>Use the CLI to interact with tasks:</p>
-        <code>$ scatterbrain task add "New task"</code> | 
-        <code>$ scatterbrain move 0,1</code> | 
-        <code>$ scatterbrain task complete</code> |
-        <code>$ scatterbrain task change-level 1</code>
-        <div class="reactive-status">
-            <span class="status-indicator" id="connection-status"></span>
-            <span class="status-text" id="status-text">Waiting to connect...</span>
-        </div>
-    </div>
-    <div class="container">
-        <!-- HISTORY PANEL -->
+    }
 
-        <div class="plan-section">
-"#;
+    #[tokio::test]
+    async fn test_join_codes() {
+        let core = Core::new();
+        let plan_id = core.create_plan("Shared".to_string(), None).unwrap();
+        let id = plan_id.value();
+        let app = router(ConfigReloader::new(core, ServerConfig::default(), None));
 
-// HTML template footer with EventSource JavaScript for reactive refreshing
-const HTML_TEMPLATE_FOOTER: &str = r#"
-    </div>
-    <script>
-        // EventSource for reactive updates
-        const statusIndicator = document.getElementById('connection-status');
-        const statusText = document.getElementById('status-text');
-        let eventSource;
-        
-        function connectEvents() {
-            // Use the CURRENT_PLAN_ID injected by the template
-            if (typeof CURRENT_PLAN_ID === 'undefined') {
-                console.error('CURRENT_PLAN_ID is not defined.');
-                statusText.textContent = 'Error: Plan ID missing.';
-                return;
+        let send = |method: &'static str, uri: String| {
+            let app = app.clone();
+            async move {
+                let request = Request::builder()
+                    .method(method)
+                    .uri(uri)
+                    .body(Body::empty())
+                    .unwrap();
+                app.oneshot(request).await.unwrap()
+            }
+        };
+        let response = send("POST", format!("/api/plans/{id}/share")).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let code = serde_json::from_slice::<ApiResponse<PlanResponse<String>>>(&body)
+            .unwrap()
+            .data
+            .unwrap()
+            .res;
+
+        let response = send("GET", join_path(&code)).await;
+        assert_eq!(response.status(), StatusCode::TEMPORARY_REDIRECT);
+        assert_eq!(
+            response.headers()[header::LOCATION],
+            format!("/ui/{id}").as_str()
+        );
+
+        send("DELETE", format!("/api/plans/{id}/share")).await;
+        let response = send("GET", join_path(&code)).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_plugin_routes() {
+        struct PlanCount;
+        impl crate::api::plugins::Plugin for PlanCount {
+            fn name(&self) -> &str {
+                "plan-count"
+            }
+            fn routes(&self) -> Router<Core> {
+                Router::new().route(
+                    "/count",
+                    get(|State(core): State<Core>| async move {
+                        map_core_result_simple(core.list_plans().map(|plans| plans.len()))
+                    }),
+                )
             }
-            const eventSourceUrl = '/ui/events/' + CURRENT_PLAN_ID;
-            console.log('Connecting to SSE:', eventSourceUrl); 
-            eventSource = new EventSource(eventSourceUrl);
-            
-            eventSource.onopen = () => {
-                statusIndicator.classList.add('connected');
-                statusText.textContent = 'Connected: Listening for changes';
-            };
-            
-            eventSource.addEventListener('update', (event) => {
-                // Show updating status
-                statusIndicator.classList.remove('connected');
-                statusIndicator.classList.add('updating');
-                statusText.textContent = 'Updating...';
-                
-                // Reload the page to reflect changes
-                window.location.reload();
-            });
-            
-            eventSource.addEventListener('ping', (event) => {
-                // Just keep the connection alive
-            });
-            
-            eventSource.onerror = () => {
-                statusIndicator.classList.remove('connected');
-                statusIndicator.classList.remove('updating');
-                statusText.textContent = 'Connection lost. Reconnecting...';
-                
-                // Close connection and try again after a delay
-                eventSource.close();
-                setTimeout(connectEvents, 3000);
-            };
         }
-        
-        // Start event connection when page loads
-        window.addEventListener('load', connectEvents);
-        
-        // Clean up on unload
-        window.addEventListener('beforeunload', () => {
-            if (eventSource) {
-                eventSource.close();
+
+        let core = Core::new();
+        core.register_plugin(Arc::new(PlanCount)).unwrap();
+        core.create_plan("Plugged in".to_string(), None).unwrap();
+        let config = ServerConfig {
+            auth_token: Some("secret".to_string()),
+            ..Default::default()
+        };
+        let app = router(ConfigReloader::new(core.clone(), config, None));
+        let send = |token: &str| {
+            let request = Request::builder()
+                .uri("/api/plugins/plan-count/count")
+                .header("Authorization", format!("Bearer {token}"))
+                .body(Body::empty())
+                .unwrap();
+            app.clone().oneshot(request)
+        };
+
+        // Plugin routes are served next to the built-in ones, behind the same auth
+        assert_eq!(
+            send("wrong").await.unwrap().status(),
+            StatusCode::UNAUTHORIZED
+        );
+        let response = send("secret").await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["data"], 1);
+
+        // Names double as path segments
+        struct Unnamed;
+        impl crate::api::plugins::Plugin for Unnamed {
+            fn name(&self) -> &str {
+                "Not a path/segment"
             }
-        });
-    </script>
-</body>
-</html>
-"#;
+        }
+        assert!(matches!(
+            core.register_plugin(Arc::new(Unnamed)),
+            Err(PlanError::InvalidPlugin(_))
+        ));
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::*; // Import items from parent module (server)
-    use crate::models::{Index, PlanId, PlanResponse}; // Added Index, PlanResponse
-    use axum::{
-        body::Body,
-        http::{Request, StatusCode},
-    };
-    use http_body_util::BodyExt; // for `collect`
-    use serde::de::DeserializeOwned; // Added missing import
-    use serde_json::json;
-    use tower::ServiceExt; // for `oneshot`
+    #[tokio::test]
+    async fn test_workspaces_have_their_own_plans_and_tokens() {
+        let core = Core::new();
+        let default_plan = core.create_plan("Default".to_string(), None).unwrap();
+        let config = ServerConfig::from_toml_str(
+            r#"
+            auth_token = "server"
 
-    // Type aliases to simplify complex types in tests
-    type NotesResponse = PlanResponse<Result<Option<String>, String>>;
-    type NotesResult = (StatusCode, Option<NotesResponse>);
+            [[workspaces]]
+            name = "infra"
+            auth_token = "infra-secret"
+
+            [[workspaces]]
+            name = "docs"
+            "#,
+        )
+        .unwrap();
+        let reloader = ConfigReloader::new(core.clone(), config, None);
+        let infra = reloader.workspaces()["infra"].clone();
+        let infra_plan = infra.create_plan("Infra".to_string(), None).unwrap();
+        let app = router(reloader);
+
+        let send = |method: &str, uri: &str, token: &str, body: Body| {
+            app.clone().oneshot(
+                Request::builder()
+                    .method(method)
+                    .uri(uri)
+                    .header("Authorization", format!("Bearer {token}"))
+                    .header("Content-Type", "application/json")
+                    .body(body)
+                    .unwrap(),
+            )
+        };
+        async fn listed(response: Response) -> Vec<serde_json::Value> {
+            assert_eq!(response.status(), StatusCode::OK);
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            let parsed: ApiResponse<Vec<serde_json::Value>> =
+                serde_json::from_slice(&body).unwrap();
+            parsed.data.unwrap()
+        }
+
+        // Each workspace lists only its own plans
+        let response = send("GET", "/api/plans", "server", Body::empty())
+            .await
+            .unwrap();
+        assert_eq!(listed(response).await, vec![json!(default_plan)]);
+        let response = send(
+            "GET",
+            "/api/workspaces/infra/plans",
+            "infra-secret",
+            Body::empty(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(listed(response).await, vec![json!(infra_plan)]);
+
+        // Plans created in a workspace stay there
+        let body = Body::from(json!({ "prompt": "Docs" }).to_string());
+        let response = send("POST", "/api/workspaces/docs/plans", "server", body)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(core.list_plans().unwrap().len(), 1);
+        let response = send("GET", "/api/workspaces/docs/plans", "server", Body::empty())
+            .await
+            .unwrap();
+        assert_eq!(listed(response).await.len(), 1);
+
+        // A workspace with a token of its own only accepts that one
+        let response = send(
+            "GET",
+            "/api/workspaces/infra/plans",
+            "server",
+            Body::empty(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let response = send("GET", "/api/plans", "infra-secret", Body::empty())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        // Unknown workspaces don't exist
+        let response = send("GET", "/api/workspaces/nope/plans", "server", Body::empty())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let response = send("GET", "/api/workspaces", "server", Body::empty())
+            .await
+            .unwrap();
+        assert_eq!(listed(response).await, vec![json!("docs"), json!("infra")]);
+    }
 
-    // Helper to create a test Core and Router
-    fn setup_test_app() -> (Core, Router) {
+    #[tokio::test]
+    async fn test_quotas_are_enforced_and_reported() {
         let core = Core::new();
-        let app = Router::new()
-            // Add all routes needed for testing
-            .route("/api/plans", post(create_plan_handler))
-            .route("/api/plans/:id/task", post(add_task))
-            .route(
-                "/api/plans/:id/notes/*index",
-                // Define only GET and POST here
-                get(get_notes_handler).post(set_notes_handler),
-            )
-            // Explicitly define the DELETE route
-            .route("/api/plans/:id/notes/*index", delete(delete_notes_handler))
-            .with_state(core.clone());
-        (core, app)
-    }
+        let config = ServerConfig::from_toml_str(
+            r#"
+            admin_token = "admin"
 
-    // Helper to make requests and deserialize JSON response data
-    async fn request_json<T: DeserializeOwned + Serialize>(
-        app: &Router,
-        method: &str,
-        uri: &str,
-        body: Body,
-    ) -> Result<(StatusCode, Option<T>), String> {
-        let response = app
-            .clone()
-            .oneshot(
+            [quotas]
+            max_plans = 1
+
+            [[workspaces]]
+            name = "infra"
+            quotas = { max_tasks_per_plan = 10 }
+            "#,
+        )
+        .unwrap();
+        core.set_quotas(config.quotas).unwrap();
+        let plan_id = core.create_plan("Only".to_string(), None).unwrap();
+        let app = router(ConfigReloader::new(core, config, None));
+        let send = |method: &str, uri: &str, body: Body| {
+            app.clone().oneshot(
                 Request::builder()
                     .method(method)
                     .uri(uri)
-                    .header("Content-Type", "application/json") // Add Content-Type header
+                    .header("Authorization", "Bearer admin")
+                    .header("Content-Type", "application/json")
                     .body(body)
                     .unwrap(),
             )
+        };
+
+        // Going over a quota is refused with the quota that was hit
+        let body = Body::from(json!({ "prompt": "Another" }).to_string());
+        let response = send("POST", "/api/plans", body).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let parsed: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            parsed["task_error"],
+            json!({ "QuotaExceeded": { "quota": "plans", "limit": 1, "requested": 2 } })
+        );
+
+        // Workspaces take the server's quotas they don't set themselves
+        let response = send("GET", "/api/admin/quotas", Body::empty())
             .await
             .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let parsed: ApiResponse<QuotaOverview> = serde_json::from_slice(&body).unwrap();
+        let overview = parsed.data.unwrap();
+        assert_eq!(overview.default.plans, 1);
+        assert_eq!(overview.default.usage[0].plan_id, plan_id);
+        let infra = &overview.workspaces["infra"];
+        assert_eq!(infra.quotas.max_plans, Some(1));
+        assert_eq!(infra.quotas.max_tasks_per_plan, Some(10));
+        assert_eq!(infra.plans, 0);
+    }
 
-        let status = response.status();
-        let body_bytes = response.into_body().collect().await.unwrap().to_bytes();
-        let body_str = String::from_utf8_lossy(&body_bytes);
+    #[tokio::test]
+    async fn test_auth_token_and_metrics() {
+        let core = Core::new();
+        let config = ServerConfig {
+            auth_token: Some("secret".to_string()),
+            metrics: true,
+            ..Default::default()
+        };
+        let app = router(ConfigReloader::new(core.clone(), config, None));
 
-        if status.is_success() {
-            let deserialize_result = serde_json::from_slice::<ApiResponse<T>>(&body_bytes);
+        let send = |uri: &str, token: Option<&str>| {
+            let mut builder = Request::builder().method("GET").uri(uri);
+            if let Some(token) = token {
+                builder = builder.header("Authorization", format!("Bearer {token}"));
+            }
+            app.clone().oneshot(builder.body(Body::empty()).unwrap())
+        };
 
-            match deserialize_result {
-                Ok(api_resp) => {
-                    if api_resp.success {
-                        // Return the Option<T> directly
-                        Ok((status, api_resp.data))
-                    } else {
-                        Err(format!(
-                            "API Error: {} (Status: {})",
-                            api_resp.error.unwrap_or_default(),
-                            status
-                        ))
-                    }
-                }
-                Err(e) => Err(format!(
-                    "Failed to parse success response: {e}. Body: {body_str}"
-                )),
+        // API routes require the token
+        let response = send("/api/plans", None).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let response = send("/api/plans", Some("wrong")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let response = send("/api/plans", Some("secret")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // Metrics are not behind the API token and count every request
+        core.create_plan("Metrics".to_string(), None).unwrap();
+        let response = send("/metrics", None).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let text = String::from_utf8(body.to_vec()).unwrap();
+        assert!(
+            text.contains("scatterbrain_http_requests_total 4"),
+            "{text}"
+        );
+        assert!(text.contains("scatterbrain_plans 1"), "{text}");
+    }
+
+    #[tokio::test]
+    async fn test_response_compression() {
+        let core = Core::new();
+        for _ in 0..20 {
+            core.create_plan("A plan with a fairly repetitive goal".to_string(), None)
+                .unwrap();
+        }
+        let encoding = |compression: bool| {
+            let config = ServerConfig {
+                compression,
+                ..Default::default()
+            };
+            let app = router(ConfigReloader::new(core.clone(), config, None));
+            async move {
+                let request = Request::builder()
+                    .uri("/api/plans/summaries")
+                    .header("Accept-Encoding", "gzip")
+                    .body(Body::empty())
+                    .unwrap();
+                let response = app.oneshot(request).await.unwrap();
+                assert_eq!(response.status(), StatusCode::OK);
+                response.headers().get("content-encoding").cloned()
             }
-        } else {
-            // Attempt to parse as error response
-            match serde_json::from_slice::<ApiResponse<()>>(&body_bytes) {
-                Ok(api_resp) => Err(format!(
-                    "API Error: {} (Status: {})",
-                    api_resp.error.unwrap_or_default(),
-                    status
-                )),
-                Err(_) => Err(format!("HTTP Error: {status} Body: {body_str}")),
+        };
+
+        assert_eq!(encoding(true).await.unwrap(), "gzip");
+        assert_eq!(encoding(false).await, None);
+    }
+
+    #[tokio::test]
+    async fn test_create_plan_from_spec() {
+        let core = Core::new();
+        let app = router(ConfigReloader::new(
+            core.clone(),
+            ServerConfig::default(),
+            None,
+        ));
+        let post = |body: &'static str| {
+            let app = app.clone();
+            async move {
+                let request = Request::builder()
+                    .method("POST")
+                    .uri("/api/plans/spec")
+                    .header("Content-Type", "application/yaml")
+                    .body(Body::from(body))
+                    .unwrap();
+                let response = app.oneshot(request).await.unwrap();
+                let status = response.status();
+                let body = response.into_body().collect().await.unwrap().to_bytes();
+                let parsed: ApiResponse<serde_json::Value> = serde_json::from_slice(&body).unwrap();
+                (status, parsed)
             }
+        };
+
+        let (status, response) = post(
+            "goal: From spec\ntasks:\n  - description: Root\n    level: 0\n    tasks:\n      - { description: Child, level: 1, completed: true }\n",
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let plan_id: models::PlanId = serde_json::from_value(response.data.unwrap()).unwrap();
+        let progress = core.progress(&plan_id).unwrap();
+        assert_eq!((progress.completed, progress.total), (1, 2));
+
+        // Specs that break the level rules are rejected like any other task change
+        let (status, response) = post(
+            "goal: Bad levels\ntasks:\n  - description: Detail\n    level: 2\n    tasks:\n      - { description: Vision, level: 0 }\n",
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert!(response.task_error.is_some());
+        assert_eq!(core.list_plans().unwrap().len(), 1);
+
+        // Unknown fields are a malformed spec
+        let (status, _) = post("goal: Typo\ntaks: []\n").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_tasks_by_id() {
+        let core = Core::new();
+        let id = core.create_plan("IDs".to_string(), None).unwrap();
+        for description in ["First", "Second"] {
+            core.add_task(&id, description.to_string(), 0, None)
+                .unwrap();
         }
+        let plan = core.get_plan(&id).unwrap().into_inner();
+        let second = plan.root().subtasks()[1].id().clone();
+        core.remove_task(&id, vec![0]).unwrap();
+        let app = router(ConfigReloader::new(
+            core.clone(),
+            ServerConfig::default(),
+            None,
+        ));
+
+        let (status, found) = request_json::<PlanResponse<(models::Task, Index)>>(
+            &app,
+            "GET",
+            &format!("/api/plans/{}/tasks/{second}", id.value()),
+            Body::empty(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(status, StatusCode::OK);
+        let (task, index) = found.unwrap().into_inner();
+        assert_eq!((task.description(), index), ("Second", vec![0]));
+
+        // Request bodies take an ID wherever they take an index
+        request_json::<PlanResponse<()>>(
+            &app,
+            "POST",
+            &format!("/api/plans/{}/task/complete", id.value()),
+            Body::from(json!({ "index": second, "force": true, "summary": "Done" }).to_string()),
+        )
+        .await
+        .unwrap();
+        let plan = core.get_plan(&id).unwrap().into_inner();
+        assert!(plan.root().subtasks()[0].is_completed());
+
+        let error = request_json::<PlanResponse<models::Task>>(
+            &app,
+            "DELETE",
+            &format!("/api/plans/{}/tasks/t-00000000", id.value()),
+            Body::empty(),
+        )
+        .await
+        .unwrap_err();
+        assert!(error.contains("404"), "{error}");
+        let error = request_json::<PlanResponse<models::Task>>(
+            &app,
+            "GET",
+            &format!("/api/plans/{}/tasks/t-first", id.value()),
+            Body::empty(),
+        )
+        .await
+        .unwrap_err();
+        assert!(error.contains("400"), "{error}");
     }
 
     #[tokio::test]
-    async fn test_notes_api_crud() {
-        let (_core, app) = setup_test_app();
+    async fn test_update_plan_metadata() {
+        let core = Core::new();
+        let id = core.create_plan("Draft goal".to_string(), None).unwrap();
+        let app = router(ConfigReloader::new(
+            core.clone(),
+            ServerConfig::default(),
+            None,
+        ));
 
-        // 1. Create Plan
-        let create_body = Body::from(json!({ "prompt": "Notes API Test" }).to_string());
-        let (_, plan_id_resp_opt): (_, Option<PlanId>) =
-            request_json(&app, "POST", "/api/plans", create_body)
-                .await
-                .expect("Failed to create plan");
-        let plan_id = plan_id_resp_opt.expect("Plan ID should be present").value();
+        let (status, _) = request_json::<PlanResponse<()>>(
+            &app,
+            "PATCH",
+            &format!("/api/plans/{}", id.value()),
+            Body::from(json!({ "goal": "Refined goal", "notes": "Learned a lot" }).to_string()),
+        )
+        .await
+        .unwrap();
+        assert_eq!(status, StatusCode::OK);
+        let plan = core.get_plan(&id).unwrap().into_inner();
+        assert_eq!(plan.goal.as_deref(), Some("Refined goal"));
+        assert_eq!(plan.notes.as_deref(), Some("Learned a lot"));
 
-        // 2. Add Task (without notes initially)
-        let add_task_body = Body::from(
-            json!({
-                "description": "Task for Notes",
-                "level_index": 0,
-                "notes": null // Explicitly send null for None
-            })
-            .to_string(),
+        let error = request_json::<PlanResponse<()>>(
+            &app,
+            "PATCH",
+            &format!("/api/plans/{}", id.value()),
+            Body::from(json!({ "goal": " " }).to_string()),
+        )
+        .await
+        .unwrap_err();
+        assert!(error.contains("400"), "{error}");
+    }
+
+    #[tokio::test]
+    async fn test_update_task_description() {
+        let core = Core::new();
+        let id = core.create_plan("Edits".to_string(), None).unwrap();
+        core.add_task(&id, "Fix typo in teh docs".to_string(), 0, None)
+            .unwrap();
+        let app = router(ConfigReloader::new(
+            core.clone(),
+            ServerConfig::default(),
+            None,
+        ));
+
+        let (status, updated) = request_json::<PlanResponse<models::Task>>(
+            &app,
+            "PATCH",
+            &format!("/api/plans/{}/tasks/0", id.value()),
+            Body::from(json!({ "description": "Fix typo in the docs" }).to_string()),
+        )
+        .await
+        .unwrap();
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            updated.unwrap().into_inner().description(),
+            "Fix typo in the docs"
         );
-        let add_uri = format!("/api/plans/{plan_id}/task");
-        let (_, add_resp_opt): (_, Option<PlanResponse<(models::Task, Index)>>) =
-            request_json(&app, "POST", &add_uri, add_task_body)
-                .await
-                .expect("Failed to add task");
-        let task_index = add_resp_opt
-            .expect("Add task response should be present")
-            .inner()
-            .1
-            .clone();
-        let task_index_str = task_index
-            .iter()
-            .map(|i| i.to_string())
-            .collect::<Vec<_>>()
-            .join(",");
 
-        // 3. Get Notes (should be null/None initially)
-        let get_uri = format!("/api/plans/{plan_id}/notes/{task_index_str}");
-        let (status_get1, notes_resp1_opt): NotesResult =
-            request_json(&app, "GET", &get_uri, Body::empty())
-                .await
-                .expect("Failed to get initial notes");
-        assert_eq!(status_get1, StatusCode::OK);
+        let error = request_json::<PlanResponse<models::Task>>(
+            &app,
+            "PATCH",
+            &format!("/api/plans/{}/tasks/4", id.value()),
+            Body::from(json!({ "description": "Missing" }).to_string()),
+        )
+        .await
+        .unwrap_err();
+        assert!(error.contains("404"), "{error}");
+    }
+
+    #[tokio::test]
+    async fn test_move_task() {
+        let core = Core::new();
+        let id = core.create_plan("Moves".to_string(), None).unwrap();
+        for description in ["First", "Second"] {
+            core.add_task(&id, description.to_string(), 0, None)
+                .unwrap();
+        }
+        let app = router(ConfigReloader::new(
+            core.clone(),
+            ServerConfig::default(),
+            None,
+        ));
+
+        let (status, moved) = request_json::<PlanResponse<Index>>(
+            &app,
+            "POST",
+            &format!("/api/plans/{}/task/move", id.value()),
+            Body::from(json!({ "index": [0], "parent": [1] }).to_string()),
+        )
+        .await
+        .unwrap();
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(moved.unwrap().into_inner(), vec![0, 0]);
+        let plan = core.get_plan(&id).unwrap().into_inner();
+        assert_eq!(
+            plan.root().subtasks()[0].subtasks()[0].description(),
+            "First"
+        );
+
+        let error = request_json::<PlanResponse<Index>>(
+            &app,
+            "POST",
+            &format!("/api/plans/{}/task/move", id.value()),
+            Body::from(json!({ "index": [0], "parent": [0, 0] }).to_string()),
+        )
+        .await
+        .unwrap_err();
+        assert!(error.contains("400"), "{error}");
+    }
+
+    #[tokio::test]
+    async fn test_tombstones_keep_indices_on_removal() {
+        let core = Core::new();
+        let id = core.create_plan("Tombstones".to_string(), None).unwrap();
+        for description in ["First", "Second"] {
+            core.add_task(&id, description.to_string(), 0, None)
+                .unwrap();
+        }
+        let app = router(ConfigReloader::new(
+            core.clone(),
+            ServerConfig::default(),
+            None,
+        ));
+
+        let (status, changed) = request_json::<PlanResponse<bool>>(
+            &app,
+            "POST",
+            &format!("/api/plans/{}/tombstones", id.value()),
+            Body::from(json!({ "enabled": true }).to_string()),
+        )
+        .await
+        .unwrap();
+        assert_eq!(status, StatusCode::OK);
+        assert!(changed.unwrap().into_inner());
+
+        let (status, _) = request_json::<PlanResponse<models::Task>>(
+            &app,
+            "DELETE",
+            &format!("/api/plans/{}/tasks/0", id.value()),
+            Body::empty(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(status, StatusCode::OK);
+        let (_, found) = request_json::<PlanResponse<(models::Task, Index)>>(
+            &app,
+            "GET",
+            &format!("/api/plans/{}/tasks/1", id.value()),
+            Body::empty(),
+        )
+        .await
+        .unwrap();
+        let (task, index) = found.unwrap().into_inner();
+        assert_eq!((task.description(), index), ("Second", vec![1]));
+    }
+
+    #[tokio::test]
+    async fn test_dependency_blocks_completion() {
+        let core = Core::new();
+        let id = core.create_plan("Dependencies".to_string(), None).unwrap();
+        for description in ["Design", "Build"] {
+            core.add_task(&id, description.to_string(), 0, None)
+                .unwrap();
+        }
+        let app = router(ConfigReloader::new(
+            core.clone(),
+            ServerConfig::default(),
+            None,
+        ));
+
+        let (status, added) = request_json::<PlanResponse<bool>>(
+            &app,
+            "POST",
+            &format!("/api/plans/{}/task/dependency", id.value()),
+            Body::from(json!({ "index": [1], "on": [0] }).to_string()),
+        )
+        .await
+        .unwrap();
+        assert_eq!(status, StatusCode::OK);
+        assert!(added.unwrap().into_inner());
+
+        // The UI leads with the chain and marks the tasks on it
+        let request = Request::builder()
+            .uri(format!("/ui/{}", id.value()))
+            .body(Body::empty())
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let html = String::from_utf8_lossy(&body);
+        assert!(html.contains(
+            "<strong>Critical path:</strong> <a href='#task-0'>0</a> &rarr; <a href='#task-1'>1</a>"
+        ));
+        assert_eq!(html.matches("<span class='task-critical'>").count(), 2);
+
+        let error = request_json::<PlanResponse<()>>(
+            &app,
+            "POST",
+            &format!("/api/plans/{}/task/complete", id.value()),
+            Body::from(json!({ "index": [1], "force": false, "summary": "Done" }).to_string()),
+        )
+        .await
+        .unwrap_err();
+        assert!(error.contains("409"), "{error}");
+        let error = request_json::<PlanResponse<bool>>(
+            &app,
+            "POST",
+            &format!("/api/plans/{}/task/dependency", id.value()),
+            Body::from(json!({ "index": [0], "on": [1] }).to_string()),
+        )
+        .await
+        .unwrap_err();
+        assert!(error.contains("400"), "{error}");
+
+        let (_, removed) = request_json::<PlanResponse<bool>>(
+            &app,
+            "POST",
+            &format!("/api/plans/{}/task/dependency/remove", id.value()),
+            Body::from(json!({ "index": [1], "on": [0] }).to_string()),
+        )
+        .await
+        .unwrap();
+        assert!(removed.unwrap().into_inner());
+    }
+
+    #[tokio::test]
+    async fn test_list_tasks_by_tag() {
+        let core = Core::new();
+        let id = core.create_plan("Tags".to_string(), None).unwrap();
+        for description in ["API", "Landing page"] {
+            core.add_task(&id, description.to_string(), 0, None)
+                .unwrap();
+        }
+        let app = router(ConfigReloader::new(
+            core.clone(),
+            ServerConfig::default(),
+            None,
+        ));
+
+        let (status, tags) = request_json::<PlanResponse<Vec<String>>>(
+            &app,
+            "POST",
+            &format!("/api/plans/{}/task/tags", id.value()),
+            Body::from(json!({ "index": [0], "tags": ["Backend"] }).to_string()),
+        )
+        .await
+        .unwrap();
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(tags.unwrap().into_inner(), vec!["backend".to_string()]);
+        let error = request_json::<PlanResponse<Vec<String>>>(
+            &app,
+            "POST",
+            &format!("/api/plans/{}/task/tags", id.value()),
+            Body::from(json!({ "index": [1], "tags": ["front end"] }).to_string()),
+        )
+        .await
+        .unwrap_err();
+        assert!(error.contains("400"), "{error}");
+
+        let (_, listed) = request_json::<PlanResponse<Vec<models::TaskListing>>>(
+            &app,
+            "GET",
+            &format!("/api/plans/{}/tasks?tag=backend", id.value()),
+            Body::empty(),
+        )
+        .await
+        .unwrap();
+        let listed = listed.unwrap().into_inner();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].description, "API");
+    }
+
+    #[tokio::test]
+    async fn test_search_tasks() {
+        let core = Core::new();
+        let id = core.create_plan("Search".to_string(), None).unwrap();
+        core.add_task(
+            &id,
+            "Write the parser".to_string(),
+            0,
+            Some("Hand-rolled recursive descent".to_string()),
+        )
+        .unwrap();
+        core.add_task(&id, "Write the lexer".to_string(), 0, None)
+            .unwrap();
+        let app = router(ConfigReloader::new(
+            core.clone(),
+            ServerConfig::default(),
+            None,
+        ));
+
+        let (status, hits) = request_json::<PlanResponse<Vec<SearchHit>>>(
+            &app,
+            "GET",
+            &format!("/api/plans/{}/search?q=recursive%20WRITE", id.value()),
+            Body::empty(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(status, StatusCode::OK);
+        let hits = hits.unwrap().into_inner();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].index, vec![0]);
+        assert_eq!(hits[0].field, MatchField::Description);
+
+        // The index is rebuilt once the plan changes
+        core.add_task(
+            &id,
+            "Recursive includes".to_string(),
+            0,
+            Some("Writes nothing".to_string()),
+        )
+        .unwrap();
+        let (_, hits) = request_json::<PlanResponse<Vec<SearchHit>>>(
+            &app,
+            "GET",
+            &format!("/api/plans/{}/search?q=recursive+write", id.value()),
+            Body::empty(),
+        )
+        .await
+        .unwrap();
+        let indices: Vec<Index> = hits
+            .unwrap()
+            .into_inner()
+            .into_iter()
+            .map(|hit| hit.index)
+            .collect();
+        assert_eq!(indices, vec![vec![0], vec![2]]);
+
+        let error = request_json::<PlanResponse<Vec<SearchHit>>>(
+            &app,
+            "GET",
+            &format!("/api/plans/{}/search", id.value()),
+            Body::empty(),
+        )
+        .await
+        .unwrap_err();
+        assert!(error.contains("400"), "{error}");
+    }
+
+    #[tokio::test]
+    async fn test_plan_export_and_import() {
+        let source = Core::new();
+        let id = source.create_plan("Moving".to_string(), None).unwrap();
+        source
+            .add_task(&id, "Carried along".to_string(), 0, None)
+            .unwrap();
+        let target = Core::new();
+        let app = |core: &Core| {
+            router(ConfigReloader::new(
+                core.clone(),
+                ServerConfig::default(),
+                None,
+            ))
+        };
+
+        let (status, export) = request_json::<PlanExport>(
+            &app(&source),
+            "GET",
+            &format!("/api/plans/{}/export", id.value()),
+            Body::empty(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(status, StatusCode::OK);
+        let export = export.unwrap();
+        assert_eq!(export.id, id.value());
+
+        let (_, imported) = request_json::<PlanId>(
+            &app(&target),
+            "POST",
+            "/api/plans/import",
+            Body::from(serde_json::to_string(&export).unwrap()),
+        )
+        .await
+        .unwrap();
+        let plan = target.get_plan(&imported.unwrap()).unwrap().into_inner();
+        assert_eq!(plan.goal.as_deref(), Some("Moving"));
+        assert_eq!(plan.root().subtasks()[0].description(), "Carried along");
+
+        // Anything that isn't an export is rejected without creating a plan
+        let error = request_json::<PlanId>(
+            &app(&target),
+            "POST",
+            "/api/plans/import",
+            Body::from(json!({ "version": 99 }).to_string()),
+        )
+        .await
+        .unwrap_err();
+        assert!(error.contains("400"), "{error}");
+        assert_eq!(target.list_plans().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_request_limits() {
+        let core = Core::new();
+        let plan_id = core.create_plan("Limits".to_string(), None).unwrap();
+        let config = ServerConfig {
+            limits: RequestLimits {
+                max_body_bytes: 1024,
+                max_description_chars: 20,
+                max_notes_bytes: 64,
+                max_batch_size: 2,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let app = router(ConfigReloader::new(core.clone(), config, None));
+        let post = |uri: String, body: serde_json::Value, content_length: bool| {
+            let body = body.to_string();
+            let mut builder = Request::builder()
+                .method("POST")
+                .uri(uri)
+                .header("Content-Type", "application/json");
+            if content_length {
+                builder = builder.header("Content-Length", body.len());
+            }
+            let app = app.clone();
+            async move {
+                let response = app
+                    .oneshot(builder.body(Body::from(body)).unwrap())
+                    .await
+                    .unwrap();
+                let status = response.status();
+                let body = response.into_body().collect().await.unwrap().to_bytes();
+                let parsed: ApiResponse<serde_json::Value> = serde_json::from_slice(&body).unwrap();
+                (status, parsed.error)
+            }
+        };
+        let task_uri = format!("/api/plans/{}/task", plan_id.value());
+
+        let (status, _) = post(
+            task_uri.clone(),
+            json!({"description": "Short", "level_index": 0, "notes": "Fine"}),
+            false,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+
+        // Oversized fields are refused with 422
+        let (status, error) = post(
+            task_uri.clone(),
+            json!({"description": "x".repeat(21), "level_index": 0}),
+            false,
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(
+            error.unwrap(),
+            "description is 21 characters long, the limit is 20"
+        );
+        let (status, _) = post(
+            format!("/api/plans/{}/notes/0", plan_id.value()),
+            json!({"notes": "x".repeat(65)}),
+            false,
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        let (status, error) = post(
+            "/api/templates".to_string(),
+            json!({"name": "t", "description": "T", "level_index": 0, "checklist": ["a", "b", "c"]}),
+            false,
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(error.unwrap(), "checklist has 3 items, the limit is 2");
+
+        // Oversized bodies are refused with 413, whether or not they declare a length
+        let huge = json!({"description": "Short", "level_index": 0, "notes": "x".repeat(2048)});
+        for content_length in [true, false] {
+            let (status, error) = post(task_uri.clone(), huge.clone(), content_length).await;
+            assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+            assert!(error.is_some());
+        }
         assert_eq!(
-            notes_resp1_opt
-                .expect("Get notes response missing")
-                .into_inner()
-                .expect("Inner get notes result failed"),
-            None
+            core.get_plan(&plan_id)
+                .unwrap()
+                .inner()
+                .root()
+                .subtasks()
+                .len(),
+            1
         );
+    }
 
-        // 4. Set Notes
-        let notes_content = "These are my notes.\nWith a newline.".to_string();
-        let set_body = Body::from(json!({ "notes": notes_content }).to_string());
-        let set_uri = format!("/api/plans/{plan_id}/notes/{task_index_str}");
-        let (status_set, set_resp_opt): (_, Option<PlanResponse<Result<(), String>>>) =
-            request_json(&app, "POST", &set_uri, set_body)
-                .await
-                .expect("Failed to set notes");
-        assert_eq!(status_set, StatusCode::OK);
-        assert!(set_resp_opt
-            .expect("Set response missing")
-            .into_inner()
-            .is_ok());
+    #[tokio::test]
+    async fn test_idempotency_keys() {
+        let core = Core::new();
+        let plan_id = core.create_plan("Retries".to_string(), None).unwrap();
+        let app = router(ConfigReloader::new(
+            core.clone(),
+            ServerConfig::default(),
+            None,
+        ));
+        let uri = format!("/api/plans/{}/task", plan_id.value());
+        let post = |key: Option<&str>, description: &str| {
+            let mut builder = Request::builder()
+                .method("POST")
+                .uri(&uri)
+                .header("Content-Type", "application/json");
+            if let Some(key) = key {
+                builder = builder.header("Idempotency-Key", key);
+            }
+            let body = json!({"description": description, "level_index": 0}).to_string();
+            app.clone().oneshot(builder.body(Body::from(body)).unwrap())
+        };
+        let task_count = || {
+            core.get_plan(&plan_id)
+                .unwrap()
+                .inner()
+                .root()
+                .subtasks()
+                .len()
+        };
 
-        // 5. Get Notes (should be the set content)
-        let (status_get2, notes_resp2_opt): NotesResult =
-            request_json(&app, "GET", &get_uri, Body::empty())
-                .await
-                .expect("Failed to get set notes");
-        assert_eq!(status_get2, StatusCode::OK);
+        let first = post(Some("retry-1"), "Once").await.unwrap();
+        assert_eq!(first.status(), StatusCode::OK);
+        assert!(first.headers().get("idempotent-replayed").is_none());
+        let first_body = first.into_body().collect().await.unwrap().to_bytes();
+
+        // A retry with the same key gets the stored response and changes nothing
+        let retry = post(Some("retry-1"), "Once").await.unwrap();
+        assert_eq!(retry.status(), StatusCode::OK);
+        assert_eq!(retry.headers()["idempotent-replayed"], "true");
         assert_eq!(
-            notes_resp2_opt
-                .expect("Get notes 2 response missing")
-                .into_inner()
-                .expect("Inner get notes 2 result failed"),
-            Some(notes_content.clone())
+            retry.into_body().collect().await.unwrap().to_bytes(),
+            first_body
         );
+        assert_eq!(task_count(), 1);
 
-        // 5.5 Check HTML UI for notes -- REMOVED FOR DEBUGGING
-        /*
-        // NOTE: This UI check, when run before the DELETE step below,
-        // somehow causes the subsequent DELETE request in this test to fail with 404.
-        // The exact reason is unclear, possibly related to test router state or oneshot interaction.
-        // The DELETE functionality is verified separately in test_notes_api_delete.
-        let ui_uri = format!("/ui/{}", plan_id);
-        let ui_response = app
-            .clone()
-            .oneshot(Request::builder().uri(ui_uri).body(Body::empty()).unwrap())
-            .await
+        // Reusing the key for another request is refused
+        let reused = post(Some("retry-1"), "Something else").await.unwrap();
+        assert_eq!(reused.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(task_count(), 1);
+
+        // Requests without a key, or with a fresh one, always run
+        post(None, "Once").await.unwrap();
+        post(Some("retry-2"), "Once").await.unwrap();
+        assert_eq!(task_count(), 3);
+
+        // Another caller's use of the same key is its own
+        let other = Request::builder()
+            .method("POST")
+            .uri(&uri)
+            .header("Content-Type", "application/json")
+            .header("Authorization", "Bearer other-agent")
+            .header("Idempotency-Key", "retry-1")
+            .body(Body::from(
+                json!({"description": "Once", "level_index": 0}).to_string(),
+            ))
             .unwrap();
-        assert_eq!(ui_response.status(), StatusCode::OK);
-        let ui_body_bytes = ui_response.into_body().collect().await.unwrap().to_bytes();
-        let ui_html = String::from_utf8_lossy(&ui_body_bytes);
-        assert!(
-            ui_html.contains(&format!("<div class='task-notes'>{}</div>", notes_content)),
-            "HTML UI should contain the task notes"
-        );
-        */
+        let other = app.clone().oneshot(other).await.unwrap();
+        assert_eq!(other.status(), StatusCode::OK);
+        assert!(other.headers().get("idempotent-replayed").is_none());
+        assert_eq!(task_count(), 4);
+    }
 
-        // 6. Delete Notes
-        let delete_uri = format!("/api/plans/{plan_id}/notes/{task_index_str}");
-        let (status_delete, delete_resp_opt): (_, Option<PlanResponse<Result<(), String>>>) =
-            // Add Content-Length: 0 header to the DELETE request
-            app.clone()
-                .oneshot(
-                    Request::builder()
-                        .method("DELETE")
-                        .uri(&delete_uri)
-                        .header("Content-Type", "application/json") // Keep this
-                        .header("Content-Length", "0") // Add explicit content length
-                        .body(Body::empty())
-                        .unwrap(),
-                )
-                .await
-                .map(|response| {
-                    let status = response.status();
-                    let body_bytes = futures::executor::block_on(response.into_body().collect()).unwrap().to_bytes();
-                    let data_opt = if status.is_success() {
-                        serde_json::from_slice::<ApiResponse<PlanResponse<Result<(), String>>>>(&body_bytes)
-                            .ok()
-                            .and_then(|resp| resp.data)
-                    } else {
-                        None
-                    };
-                    (status, data_opt)
-                })
-                .expect("Failed to delete notes");
+    #[test]
+    fn test_idempotency_cache_bounds() {
+        let cache =
+            IdempotencyCache::new(Arc::new(std::sync::RwLock::new(ServerConfig::default())));
+        let scope = |key: &str| IdempotencyScope {
+            principal: 0,
+            workspace: None,
+            key: key.to_string(),
+        };
+        let stored = || CachedResponse {
+            status: StatusCode::OK,
+            content_type: None,
+            body: axum::body::Bytes::from_static(b"{}"),
+        };
+        let window = Duration::from_secs(60);
 
-        assert_eq!(status_delete, StatusCode::OK);
-        assert!(delete_resp_opt
-            .expect("Delete response missing")
-            .into_inner()
-            .is_ok());
+        // Past the capacity the key stored longest ago is dropped; storing a response
+        // counts as storing the key again
+        assert!(matches!(
+            cache.claim(&scope("a"), 1, (window, 2)),
+            Claim::New
+        ));
+        assert!(matches!(
+            cache.claim(&scope("b"), 1, (window, 2)),
+            Claim::New
+        ));
+        cache.finish(&scope("a"), Some(stored()));
+        assert!(matches!(
+            cache.claim(&scope("c"), 1, (window, 2)),
+            Claim::New
+        ));
+        assert!(matches!(
+            cache.claim(&scope("a"), 1, (window, 2)),
+            Claim::Replay(_)
+        ));
+        assert!(matches!(
+            cache.claim(&scope("b"), 2, (window, 3)),
+            Claim::New
+        ));
 
-        // 7. Get Notes (should be null/None again)
-        let (status_get3, notes_resp3_opt): NotesResult =
-            request_json(&app, "GET", &get_uri, Body::empty())
-                .await
-                .expect("Failed to get notes after delete");
-        assert_eq!(status_get3, StatusCode::OK);
+        // Entries older than the window are dropped, however few there are
+        assert!(matches!(
+            cache.claim(&scope("a"), 1, (Duration::ZERO, 10)),
+            Claim::New
+        ));
+        assert_eq!(cache.entries.lock().unwrap().entries.len(), 1);
+
+        // Keys are scoped by the caller's credentials and the workspace
+        let request = |uri: &str, token: Option<&str>| {
+            let mut builder = Request::builder().method("POST").uri(uri);
+            if let Some(token) = token {
+                builder = builder.header("Authorization", format!("Bearer {token}"));
+            }
+            IdempotencyScope::of(&builder.body(Body::empty()).unwrap(), "k".to_string())
+        };
+        let default = request("/api/plans/0/task", Some("a"));
+        assert!(default.workspace.is_none());
+        assert!(default == request("/api/plans/1/task", Some("a")));
+        assert!(default != request("/api/plans/0/task", Some("b")));
+        assert!(default != request("/api/plans/0/task", None));
+        let team = request("/api/workspaces/team/plans/0/task", Some("a"));
+        assert_eq!(team.workspace.as_deref(), Some("team"));
+        assert!(default != team);
+    }
+
+    #[tokio::test]
+    async fn test_transitions_record_actor() {
+        let core = Core::new();
+        let plan_id = core.create_plan("Audit".to_string(), None).unwrap();
+        let app = router(ConfigReloader::new(
+            core.clone(),
+            ServerConfig::default(),
+            None,
+        ));
+        let add = |headers: &[(&str, &str)]| {
+            let mut builder = Request::builder()
+                .method("POST")
+                .uri(format!("/api/plans/{}/task", plan_id.value()))
+                .header("Content-Type", "application/json");
+            for (name, value) in headers {
+                builder = builder.header(*name, *value);
+            }
+            let body = json!({"description": "Audited", "level_index": 0}).to_string();
+            app.clone().oneshot(builder.body(Body::from(body)).unwrap())
+        };
+        let last_actor = || {
+            let (_, snapshot) = core
+                .export_plans()
+                .unwrap()
+                .into_iter()
+                .find(|(id, _)| *id == plan_id)
+                .unwrap();
+            snapshot.history.last().unwrap().actor.clone().unwrap()
+        };
+
+        let response = add(&[
+            ("X-Scatterbrain-Agent", "planner"),
+            ("X-Request-Id", "req-42"),
+            ("User-Agent", "scatterbrain/1.0"),
+        ])
+        .await
+        .unwrap();
+        assert_eq!(response.headers()["x-request-id"], "req-42");
         assert_eq!(
-            notes_resp3_opt
-                .expect("Get notes 3 response missing")
-                .into_inner()
-                .expect("Inner get notes 3 result failed"),
-            None
+            last_actor(),
+            models::ActorInfo {
+                source: models::ActorSource::Cli,
+                agent: Some("planner".to_string()),
+                request_id: Some("req-42".to_string()),
+            }
         );
+
+        // Other clients are recorded as plain HTTP, with a generated request ID
+        let response = add(&[]).await.unwrap();
+        let request_id = response.headers()["x-request-id"]
+            .to_str()
+            .unwrap()
+            .to_string();
+        let actor = last_actor();
+        assert_eq!(actor.source, models::ActorSource::Http);
+        assert_eq!(actor.agent, None);
+        assert_eq!(actor.request_id, Some(request_id));
+
+        // Changes made outside a request have no actor
+        core.add_task(&plan_id, "Local".to_string(), 0, None)
+            .unwrap();
+        let (_, snapshot) = core
+            .export_plans()
+            .unwrap()
+            .into_iter()
+            .find(|(id, _)| *id == plan_id)
+            .unwrap();
+        assert!(snapshot.history.last().unwrap().actor.is_none());
     }
 
     #[tokio::test]
-    async fn test_notes_api_delete() {
-        let (_core, app) = setup_test_app();
-
-        // 1. Create Plan
-        let create_body = Body::from(json!({ "prompt": "Delete Notes Test" }).to_string());
-        let (_, plan_id_resp_opt): (_, Option<PlanId>) =
-            request_json(&app, "POST", "/api/plans", create_body)
+    async fn test_serve_until_shuts_down_and_saves() {
+        let dir =
+            std::env::temp_dir().join(format!("scatterbrain-shutdown-{}", rand::random::<u64>()));
+        let config = ServerConfig {
+            address: ([127, 0, 0, 1], 0).into(),
+            storage_path: Some(dir.clone()),
+            ..Default::default()
+        };
+        let core = Core::new();
+        let (stop_tx, stop_rx) = tokio::sync::oneshot::channel::<()>();
+        let server = tokio::spawn({
+            let core = core.clone();
+            async move {
+                serve_until(core, config, None, async move {
+                    let _ = stop_rx.await;
+                })
                 .await
-                .expect("Failed to create plan");
-        let plan_id = plan_id_resp_opt.expect("Plan ID should be present").value();
+                .map_err(|e| e.to_string())
+            }
+        });
 
-        // 2. Add Task
-        let add_task_body = Body::from(
-            json!({
-                "description": "Task to delete notes from",
-                "level_index": 0,
-                "notes": "Initial notes for delete test"
-            })
-            .to_string(),
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let plan_id = core.create_plan("Keep me".to_string(), None).unwrap();
+        stop_tx.send(()).unwrap();
+        tokio::time::timeout(Duration::from_secs(2), server)
+            .await
+            .expect("server stops without waiting out the grace period")
+            .unwrap()
+            .unwrap();
+
+        let restored = Core::new();
+        super::super::storage::load_plans(&restored, &dir).unwrap();
+        assert!(restored.get_plan(&plan_id).is_ok());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_config_reload() {
+        let path = std::env::temp_dir().join(format!(
+            "scatterbrain-reload-{}.toml",
+            rand::random::<u64>()
+        ));
+        std::fs::write(&path, "admin_token = \"admin\"\n").unwrap();
+        let source = ConfigSource {
+            path: Some(path.clone()),
+            ..Default::default()
+        };
+        let core = Core::new();
+        let reloader = ConfigReloader::new(core.clone(), source.load().unwrap(), Some(source));
+        let app = router(reloader);
+
+        let send = |method: &str, uri: &str, token: Option<&str>| {
+            let mut builder = Request::builder().method(method).uri(uri);
+            if let Some(token) = token {
+                builder = builder.header("Authorization", format!("Bearer {token}"));
+            }
+            app.clone().oneshot(builder.body(Body::empty()).unwrap())
+        };
+        assert_eq!(
+            send("GET", "/api/plans", None).await.unwrap().status(),
+            StatusCode::OK
         );
-        let add_uri = format!("/api/plans/{plan_id}/task");
-        let (_, add_resp_opt): (_, Option<PlanResponse<(models::Task, Index)>>) =
-            request_json(&app, "POST", &add_uri, add_task_body)
+
+        // Turning on auth applies immediately; the port change needs a restart
+        std::fs::write(
+            &path,
+            "admin_token = \"admin\"\naddress = \"127.0.0.1:4000\"\nauth_token = \"new\"\n",
+        )
+        .unwrap();
+        let response = send("POST", "/api/admin/reload", Some("admin"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let parsed: ApiResponse<ReloadReport> = serde_json::from_slice(&body).unwrap();
+        let report = parsed.data.unwrap();
+        assert_eq!(report.applied, vec!["auth_token"]);
+        assert_eq!(report.requires_restart, vec!["address"]);
+
+        assert_eq!(
+            send("GET", "/api/plans", None).await.unwrap().status(),
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            send("GET", "/api/plans", Some("new"))
                 .await
-                .expect("Failed to add task");
-        let task_index = add_resp_opt
-            .expect("Add task response should be present")
-            .inner()
-            .1
-            .clone();
-        let task_index_str = task_index
-            .iter()
-            .map(|i| i.to_string())
-            .collect::<Vec<_>>()
-            .join(","); // Should be "0"
+                .unwrap()
+                .status(),
+            StatusCode::OK
+        );
 
-        // --- Test 1: Delete Existing Notes ---
-        let delete_uri = format!("/api/plans/{plan_id}/notes/{task_index_str}");
-        let response1 = app
-            .clone()
+        // A broken file is rejected and leaves the running config alone
+        std::fs::write(&path, "auth_token = [").unwrap();
+        let response = send("POST", "/api/admin/reload", Some("admin"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            send("GET", "/api/plans", Some("new"))
+                .await
+                .unwrap()
+                .status(),
+            StatusCode::OK
+        );
+        std::fs::remove_file(&path).unwrap();
+
+        // Servers started without a config source refuse to reload
+        let config = ServerConfig {
+            admin_token: Some("admin".to_string()),
+            ..Default::default()
+        };
+        let app = router(ConfigReloader::new(core, config, None));
+        let response = app
             .oneshot(
                 Request::builder()
-                    .method("DELETE")
-                    .uri(&delete_uri)
-                    .header("Content-Type", "application/json")
-                    .header("Content-Length", "0")
+                    .method("POST")
+                    .uri("/api/admin/reload")
+                    .header("Authorization", "Bearer admin")
                     .body(Body::empty())
                     .unwrap(),
             )
             .await
             .unwrap();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+    }
+
+    #[tokio::test]
+    async fn test_task_templates() {
+        let core = Core::new();
+        let plan_id = core.create_plan("Templates".to_string(), None).unwrap();
+        let id = plan_id.value();
+        let app = router(ConfigReloader::new(
+            core.clone(),
+            ServerConfig::default(),
+            None,
+        ));
+
+        let template = json!({
+            "name": "api-endpoint",
+            "description": "Add /{name} endpoint",
+            "level_index": 0,
+            "checklist": ["Handler for /{name}", "Tests"],
+        });
+        let (status, _) = request_json::<()>(
+            &app,
+            "POST",
+            "/api/templates",
+            Body::from(template.to_string()),
+        )
+        .await
+        .unwrap();
+        assert_eq!(status, StatusCode::OK);
+        let (_, templates) =
+            request_json::<Vec<models::TaskTemplate>>(&app, "GET", "/api/templates", Body::empty())
+                .await
+                .unwrap();
+        assert_eq!(templates.unwrap()[0].name, "api-endpoint");
+
+        let body = json!({ "template": "api-endpoint", "params": { "name": "users" } });
+        let (status, response) = request_json::<PlanResponse<(models::Task, Index)>>(
+            &app,
+            "POST",
+            &format!("/api/plans/{id}/task/template"),
+            Body::from(body.to_string()),
+        )
+        .await
+        .unwrap();
+        assert_eq!(status, StatusCode::OK);
+        let (task, index) = response.unwrap().into_inner();
+        assert_eq!(task.description(), "Add /users endpoint");
+        let sections = core
+            .get_note_sections(&plan_id, index, Some("checklist".to_string()))
+            .unwrap()
+            .into_inner();
         assert_eq!(
-            response1.status(),
-            StatusCode::OK,
-            "Test 1 Failed: First DELETE should return OK"
+            sections["checklist"],
+            "- [ ] Handler for /users\n- [ ] Tests"
         );
 
-        // --- Test 2: Delete Again (Idempotency) ---
-        let response2 = app
-            .clone()
+        // Missing parameters are a bad request, unknown templates are not found
+        let body = json!({ "template": "api-endpoint" });
+        let err = request_json::<PlanResponse<(models::Task, Index)>>(
+            &app,
+            "POST",
+            &format!("/api/plans/{id}/task/template"),
+            Body::from(body.to_string()),
+        )
+        .await
+        .unwrap_err();
+        assert!(err.contains("400"), "{err}");
+        let err = request_json::<models::TaskTemplate>(
+            &app,
+            "DELETE",
+            "/api/templates/missing",
+            Body::empty(),
+        )
+        .await
+        .unwrap_err();
+        assert!(err.contains("404"), "{err}");
+
+        let (status, _) = request_json::<models::TaskTemplate>(
+            &app,
+            "DELETE",
+            "/api/templates/api-endpoint",
+            Body::empty(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(status, StatusCode::OK);
+        assert!(core.list_templates().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_admin_api() {
+        let core = Core::new();
+        let plan_id = core.create_plan("Admin".to_string(), None).unwrap();
+        core.add_task(&plan_id, "Stuck".to_string(), 0, None)
+            .unwrap();
+        core.generate_lease(&plan_id, vec![0], models::LeaseOptions::default())
+            .unwrap();
+        let id = plan_id.value();
+
+        // Admin routes are disabled until a token is configured
+        let app = router(ConfigReloader::new(
+            core.clone(),
+            ServerConfig::default(),
+            None,
+        ));
+        let response = app
             .oneshot(
                 Request::builder()
-                    .method("DELETE")
-                    .uri(&delete_uri) // Same URI
-                    .header("Content-Type", "application/json")
-                    .header("Content-Length", "0")
+                    .method("POST")
+                    .uri(format!("/api/admin/plans/{id}/unlock"))
                     .body(Body::empty())
                     .unwrap(),
             )
             .await
             .unwrap();
-        // Core::delete_task_notes always returns Ok(()) if the task exists, even if notes are already None.
-        assert_eq!(
-            response2.status(),
-            StatusCode::OK,
-            "Test 2 Failed: Second DELETE should also return OK"
-        );
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
 
-        // --- Test 3: Delete Non-existent Notes (Bad Index) ---
-        let bad_delete_uri = format!("/api/plans/{plan_id}/notes/99"); // Invalid index
-        let response3 = app
-            .clone()
-            .oneshot(
+        let config = ServerConfig {
+            admin_token: Some("admin".to_string()),
+            ..Default::default()
+        };
+        let app = router(ConfigReloader::new(core.clone(), config, None));
+        let send = |method: &str, uri: String, body: Body| {
+            app.clone().oneshot(
                 Request::builder()
-                    .method("DELETE")
-                    .uri(&bad_delete_uri)
+                    .method(method)
+                    .uri(uri)
+                    .header("Authorization", "Bearer admin")
                     .header("Content-Type", "application/json")
-                    .header("Content-Length", "0")
-                    .body(Body::empty())
+                    .body(body)
                     .unwrap(),
             )
+        };
+        async fn data<T: DeserializeOwned + Serialize>(response: Response) -> T {
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            let parsed: ApiResponse<T> = serde_json::from_slice(&body).unwrap();
+            parsed.data.unwrap()
+        }
+
+        let response = send(
+            "POST",
+            format!("/api/admin/plans/{id}/unlock"),
+            Body::empty(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(data::<PlanResponse<usize>>(response).await.into_inner(), 1);
+
+        let response = send(
+            "POST",
+            format!("/api/admin/plans/{id}/compact"),
+            Body::from(json!({ "keep_note_versions": 0 }).to_string()),
+        )
+        .await
+        .unwrap();
+        let compaction = data::<PlanResponse<models::HistoryCompaction>>(response)
+            .await
+            .into_inner();
+        assert!(compaction.transitions_removed > 0);
+
+        // Export, wipe, and import the archive again
+        let response = send("GET", "/api/admin/export".to_string(), Body::empty())
             .await
             .unwrap();
-        // delete_notes_handler returns BAD_REQUEST if the inner core result is Err (e.g., task not found)
-        assert_eq!(
-            response3.status(),
-            StatusCode::BAD_REQUEST,
-            "Test 3 Failed: DELETE with bad index should return BAD_REQUEST"
-        );
+        let archive: PlanArchive = data(response).await;
+        assert_eq!(archive.plans.len(), 1);
+        core.delete_plan(&plan_id).unwrap();
+
+        let response = send(
+            "POST",
+            "/api/admin/import".to_string(),
+            Body::from(serde_json::to_string(&archive).unwrap()),
+        )
+        .await
+        .unwrap();
+        assert_eq!(data::<usize>(response).await, 1);
+        let plan = core.get_plan(&plan_id).unwrap().into_inner();
+        assert_eq!(plan.goal.as_deref(), Some("Admin"));
     }
 }
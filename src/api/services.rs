@@ -0,0 +1,385 @@
+//! Combined MCP and HTTP serving
+//!
+//! This module runs the MCP server together with the HTTP API and web UI, all on one
+//! [`Core`], the way `scatterbrain mcp --expose` does, so programs embedding scatterbrain
+//! get the same combined mode. Either service stopping stops the other.
+
+use std::future::Future;
+
+use rmcp::service::QuitReason;
+use rmcp::transport::IntoTransport;
+use rmcp::{RoleServer, ServiceExt};
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+
+use super::config::ServerConfig;
+use super::mcp::ScatterbrainMcpServer;
+use super::server::{serve_until, shutdown_signal};
+use crate::Core;
+
+/// What [`run_services`] serves besides MCP
+///
+/// ```
+/// # use scatterbrain::api::{ServerConfig, ServiceOptions};
+/// let options = ServiceOptions {
+///     http: Some(ServerConfig {
+///         address: ([127, 0, 0, 1], 8080).into(),
+///         ..Default::default()
+///     }),
+/// };
+/// assert!(options.http.is_some());
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct ServiceOptions {
+    /// Also serve the HTTP API and web UI with this configuration
+    pub http: Option<ServerConfig>,
+}
+
+/// The HTTP server running next to MCP and the way to stop it
+struct HttpService {
+    stop: oneshot::Sender<()>,
+    handle: JoinHandle<Result<(), String>>,
+}
+
+impl HttpService {
+    fn spawn(core: Core, config: ServerConfig) -> Self {
+        let (stop, stopped) = oneshot::channel::<()>();
+        let handle = tokio::spawn(async move {
+            serve_until(core, config, None, async move {
+                let _ = stopped.await;
+            })
+            .await
+            .map_err(|e| format!("HTTP server error: {e}"))
+        });
+        Self { stop, handle }
+    }
+
+    /// Stops the server, letting open requests finish, and reports how it ended
+    async fn shut_down(self) -> Result<(), String> {
+        let _ = self.stop.send(());
+        self.handle
+            .await
+            .map_err(|e| format!("HTTP server task error: {e}"))?
+    }
+}
+
+/// Serves MCP over stdio, and the HTTP API if `options` ask for it, until the client
+/// closes stdin or the process gets `SIGINT` or `SIGTERM`.
+///
+/// Returns why MCP stopped: [`QuitReason::Closed`] when the client went away and
+/// [`QuitReason::Cancelled`] on a signal or when the HTTP server failed. The HTTP server
+/// is shut down before this returns.
+pub async fn run_services(
+    core: Core,
+    options: ServiceOptions,
+) -> Result<QuitReason, Box<dyn std::error::Error>> {
+    run_services_with(
+        core,
+        options,
+        rmcp::transport::io::stdio(),
+        shutdown_signal(),
+    )
+    .await
+}
+
+/// Like [`run_services`], serving MCP over `transport` and stopping when `shutdown`
+/// resolves instead of on a signal
+pub async fn run_services_with<T, E, A>(
+    core: Core,
+    options: ServiceOptions,
+    transport: T,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+) -> Result<QuitReason, Box<dyn std::error::Error>>
+where
+    T: IntoTransport<RoleServer, E, A>,
+    E: std::error::Error + From<std::io::Error> + Send + Sync + 'static,
+{
+    let mut http = options.http.map(|config| {
+        tracing::info!("Also exposing HTTP API server on {}", config.address);
+        HttpService::spawn(core.clone(), config)
+    });
+
+    // Watch for the shutdown before the handshake, so it can't be missed while the client
+    // is still connecting
+    let stop = CancellationToken::new();
+    let watcher = tokio::spawn({
+        let stop = stop.clone();
+        async move {
+            shutdown.await;
+            stop.cancel();
+        }
+    });
+
+    let service = match ScatterbrainMcpServer::new(core)
+        .serve_with_ct(transport, stop.clone())
+        .await
+    {
+        Ok(service) => service,
+        Err(e) => {
+            watcher.abort();
+            if let Some(http) = http {
+                if let Err(http_error) = http.shut_down().await {
+                    tracing::error!("{http_error}");
+                }
+            }
+            return Err(e.into());
+        }
+    };
+    tracing::info!("MCP server started. Waiting for connections");
+
+    let waiting = service.waiting();
+    tokio::pin!(waiting);
+    let mut http_result = None;
+    let reason = match http.as_mut() {
+        Some(running) => tokio::select! {
+            reason = &mut waiting => reason,
+            // The HTTP server only stops by itself when it fails, e.g. its port is taken
+            result = &mut running.handle => {
+                stop.cancel();
+                http_result = Some(
+                    result.map_err(|e| format!("HTTP server task error: {e}")).and_then(|r| r),
+                );
+                waiting.await
+            }
+        },
+        None => waiting.await,
+    };
+    watcher.abort();
+    let reason = reason?;
+    tracing::info!("MCP server stopped: {reason:?}");
+
+    let http_result = match (http, http_result) {
+        (_, Some(result)) => result,
+        (Some(http), None) => http.shut_down().await,
+        (None, None) => Ok(()),
+    };
+    http_result?;
+    Ok(reason)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{Client, ClientConfig, HttpClientImpl};
+    use serde_json::{json, Value};
+    use tokio::io::{
+        AsyncBufReadExt, AsyncWriteExt, BufReader, DuplexStream, Lines, ReadHalf, WriteHalf,
+    };
+
+    /// An MCP client speaking newline-delimited JSON-RPC over an in-memory pipe
+    struct TestMcpClient {
+        lines: Lines<BufReader<ReadHalf<DuplexStream>>>,
+        writer: WriteHalf<DuplexStream>,
+        next_id: u64,
+    }
+
+    impl TestMcpClient {
+        /// Connects to a server on the other end of a new pipe, returned for the server
+        fn connect() -> (Self, DuplexStream) {
+            let (client, server) = tokio::io::duplex(1 << 16);
+            let (reader, writer) = tokio::io::split(client);
+            let client = Self {
+                lines: BufReader::new(reader).lines(),
+                writer,
+                next_id: 0,
+            };
+            (client, server)
+        }
+
+        async fn send(&mut self, message: Value) {
+            let line = format!("{message}\n");
+            self.writer.write_all(line.as_bytes()).await.unwrap();
+        }
+
+        /// Sends a request without waiting for the response, returning its ID
+        async fn start(&mut self, method: &str, params: Value) -> u64 {
+            self.next_id += 1;
+            let id = self.next_id;
+            self.send(json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }))
+                .await;
+            id
+        }
+
+        /// Reads the next response
+        async fn response(&mut self) -> Value {
+            loop {
+                let line = self.lines.next_line().await.unwrap().unwrap();
+                let message: Value = serde_json::from_str(&line).unwrap();
+                if message.get("id").is_some() {
+                    return message;
+                }
+            }
+        }
+
+        async fn initialize(&mut self) {
+            let params = json!({
+                "protocolVersion": "2024-11-05",
+                "capabilities": {},
+                "clientInfo": { "name": "test", "version": "1" },
+            });
+            self.start("initialize", params).await;
+            self.response().await;
+            self.send(json!({ "jsonrpc": "2.0", "method": "notifications/initialized" }))
+                .await;
+        }
+
+        /// Calls a tool and parses the JSON it answered with
+        async fn call_tool(&mut self, name: &str, arguments: Value) -> Value {
+            let id = self
+                .start(
+                    "tools/call",
+                    json!({ "name": name, "arguments": arguments }),
+                )
+                .await;
+            let response = self.response().await;
+            assert_eq!(response["id"], json!(id));
+            tool_output(&response)
+        }
+    }
+
+    fn tool_output(response: &Value) -> Value {
+        let text = response["result"]["content"][0]["text"]
+            .as_str()
+            .unwrap_or_else(|| panic!("tool call failed: {response}"));
+        serde_json::from_str(text).unwrap()
+    }
+
+    /// A local port nothing is listening on
+    fn free_port() -> u16 {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.local_addr().unwrap().port()
+    }
+
+    fn http_options(port: u16) -> ServiceOptions {
+        ServiceOptions {
+            http: Some(ServerConfig {
+                address: ([127, 0, 0, 1], port).into(),
+                ..Default::default()
+            }),
+        }
+    }
+
+    fn http_client(port: u16) -> HttpClientImpl {
+        HttpClientImpl::with_config(ClientConfig {
+            base_url: format!("http://127.0.0.1:{port}"),
+            ..Default::default()
+        })
+    }
+
+    #[tokio::test]
+    async fn test_mcp_and_http_see_the_same_plans() {
+        let core = Core::new();
+        let plan_id = core.create_plan("Shared".to_string(), None).unwrap();
+        let id = plan_id.value();
+        let port = free_port();
+        let (mut mcp, transport) = TestMcpClient::connect();
+        let (stop_tx, stop_rx) = oneshot::channel::<()>();
+        let services = tokio::spawn(async move {
+            run_services_with(core, http_options(port), transport, async move {
+                let _ = stop_rx.await;
+            })
+            .await
+            .map_err(|e| e.to_string())
+        });
+        mcp.initialize().await;
+        let http = http_client(port);
+        for _ in 0..100 {
+            if http.get_progress(id).await.is_ok() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+
+        // Add tasks through both at once: MCP calls pipelined, HTTP calls concurrent
+        let mut pending = Vec::new();
+        for n in 0..5 {
+            let arguments = json!({
+                "plan_id": id,
+                "description": format!("mcp {n}"),
+                "level_index": 0,
+            });
+            let params = json!({ "name": "add_task", "arguments": arguments });
+            pending.push(mcp.start("tools/call", params).await);
+        }
+        let http_adds = futures::future::join_all(
+            (0..5).map(|n| http.add_task(id, format!("http {n}"), 0, None)),
+        );
+        let mcp_adds = async {
+            let mut responses = Vec::new();
+            for _ in 0..pending.len() {
+                responses.push(mcp.response().await);
+            }
+            responses
+        };
+        let (http_results, mcp_responses) = tokio::join!(http_adds, mcp_adds);
+        assert!(http_results.iter().all(|result| result.is_ok()));
+        for response in &mcp_responses {
+            assert!(pending.contains(&response["id"].as_u64().unwrap()));
+            tool_output(response);
+        }
+
+        let progress = mcp
+            .call_tool("get_progress", json!({ "plan_id": id }))
+            .await;
+        assert_eq!(progress["total"], json!(10));
+        assert_eq!(http.get_progress(id).await.unwrap().total, 10);
+
+        // A change through either is seen by the other
+        let arguments = json!({
+            "plan_id": id,
+            "index": "0",
+            "force": true,
+            "summary": "Done over MCP",
+        });
+        mcp.call_tool("complete_task", arguments).await;
+        assert_eq!(http.get_progress(id).await.unwrap().completed, 1);
+        http.complete_task(
+            id,
+            vec![1],
+            None,
+            true,
+            None,
+            Some("Done over HTTP".to_string()),
+        )
+        .await
+        .unwrap();
+        let progress = mcp
+            .call_tool("get_progress", json!({ "plan_id": id }))
+            .await;
+        assert_eq!(progress["completed"], json!(2));
+
+        // Shutting down stops both
+        stop_tx.send(()).unwrap();
+        let reason = services.await.unwrap().unwrap();
+        assert_eq!(reason, QuitReason::Cancelled);
+        assert!(http.get_progress(id).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_client_disconnect_stops_http() {
+        let core = Core::new();
+        let plan_id = core.create_plan("Short".to_string(), None).unwrap();
+        let port = free_port();
+        let (mut mcp, transport) = TestMcpClient::connect();
+        let services = tokio::spawn(async move {
+            run_services_with(core, http_options(port), transport, std::future::pending())
+                .await
+                .map_err(|e| e.to_string())
+        });
+        mcp.initialize().await;
+        let http = http_client(port);
+        for _ in 0..100 {
+            if http.get_progress(plan_id.value()).await.is_ok() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        assert!(http.get_progress(plan_id.value()).await.is_ok());
+
+        drop(mcp);
+        let reason = services.await.unwrap().unwrap();
+        assert_eq!(reason, QuitReason::Closed);
+        assert!(http.get_progress(plan_id.value()).await.is_err());
+    }
+}
@@ -0,0 +1,1478 @@
+//! Plan persistence
+//!
+//! This module saves all plans to a JSON file inside the configured storage
+//! directory shortly after plans change, and loads them back on startup. It also
+//! reads and writes `.tar.gz` backups of every plan for moving between machines,
+//! and takes scheduled per-plan snapshots as recovery points.
+//!
+//! Between saves, every change is first appended to a write-ahead journal, so a crash
+//! before the next save can't lose a change that was already acknowledged. The journal
+//! holds what each change did rather than the whole plan's history, and keeps every
+//! transition in a history log per plan, so plans only keep the latest in memory.
+//! Segments a save covers are compressed into a per-day archive, which past states of
+//! plans are read back from.
+
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use flate2::{
+    read::{GzDecoder, MultiGzDecoder},
+    write::GzEncoder,
+    Compression,
+};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast::error::{RecvError, TryRecvError};
+use tokio::time::Instant;
+
+use super::config::SharedConfig;
+use super::schema::{self, SCHEMA_VERSION};
+use crate::models::{
+    HistoryRecord, Index, Lease, LeaseRecord, Level, Plan, PlanChange, PlanError, PlanId,
+    PlanJournal, PlanSnapshot, PlanUpdate, TaskTemplate, TransitionLogEntry,
+};
+use crate::Core;
+
+/// Name of the file plans are stored in, relative to the storage directory
+pub const PLANS_FILE: &str = "plans.json";
+
+/// Directory automatic snapshots are written to, relative to the storage directory
+pub const SNAPSHOTS_DIR: &str = "snapshots";
+
+/// Directory holding the write-ahead journal segments, relative to the storage directory
+pub const JOURNAL_DIR: &str = "journal";
+
+/// Directory holding each plan's history log, relative to the storage directory
+pub const HISTORY_DIR: &str = "history";
+
+/// Directory journal segments are archived to once saved, relative to the journal directory
+pub const ARCHIVE_DIR: &str = "archive";
+
+/// Days of archived journal kept, i.e. how far back past states of plans can be read
+pub const ARCHIVE_RETENTION_DAYS: usize = 7;
+
+/// How long the persister waits after a change before saving, so that a burst of
+/// changes is saved once
+const CHECKPOINT_DELAY: Duration = Duration::from_secs(10);
+
+/// How often the snapshotter looks at the config again while interval snapshots are off
+const SNAPSHOT_IDLE_CHECK: Duration = Duration::from_secs(60);
+
+/// Name of the manifest inside a backup archive
+const BACKUP_MANIFEST: &str = "manifest.json";
+
+/// Directory holding one JSON file per plan inside a backup archive
+const BACKUP_PLANS_DIR: &str = "plans";
+
+/// A plan as written to disk, keyed by its ID
+#[derive(Clone, Serialize, Deserialize)]
+pub struct StoredPlan {
+    pub id: u8,
+    #[serde(flatten)]
+    pub snapshot: PlanSnapshot,
+}
+
+/// Server-wide settings carried along in an archive
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ArchiveSettings {
+    /// Levels used for newly created plans
+    pub default_levels: Vec<Level>,
+    /// Task templates defined on the server
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub templates: Vec<TaskTemplate>,
+}
+
+/// Contents of the storage `plans.json`
+#[derive(Serialize, Deserialize)]
+struct StoredPlans {
+    version: u32,
+    plans: Vec<StoredPlan>,
+}
+
+/// A backup of every plan on a server, as produced by the admin export endpoint.
+///
+/// Archives read from outside (files, import requests) should go through
+/// [`schema::from_value`] so older versions are migrated first.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PlanArchive {
+    /// Schema version, see [`schema::SCHEMA_VERSION`]
+    pub version: u32,
+    pub exported_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub settings: Option<ArchiveSettings>,
+    pub plans: Vec<StoredPlan>,
+}
+
+/// Captures every plan in `core`, with its cursor and history, as an archive
+pub fn export_archive(core: &Core) -> Result<PlanArchive, PlanError> {
+    let plans = core
+        .export_plans()?
+        .into_iter()
+        .map(|(id, snapshot)| StoredPlan {
+            id: id.value(),
+            snapshot,
+        })
+        .collect();
+    Ok(PlanArchive {
+        version: SCHEMA_VERSION,
+        exported_at: Utc::now(),
+        settings: Some(ArchiveSettings {
+            default_levels: core.default_levels()?,
+            templates: core.list_templates()?,
+        }),
+        plans,
+    })
+}
+
+/// Loads every plan in `archive` into `core`, replacing plans with the same ID, and
+/// applies the archived settings. Returns how many plans were imported.
+pub fn import_archive(core: &Core, archive: PlanArchive) -> Result<usize, PlanError> {
+    if let Some(settings) = archive.settings {
+        core.set_default_levels(settings.default_levels)?;
+        for template in settings.templates {
+            core.save_template(template)?;
+        }
+    }
+    let count = archive.plans.len();
+    for StoredPlan { id, snapshot } in archive.plans {
+        core.import_plan(Lease::new(id), snapshot)?;
+    }
+    Ok(count)
+}
+
+/// A single plan, with its cursor, history and leases, as produced by the plan export
+/// endpoint for moving it to another server.
+///
+/// Like archives, exports read from outside should go through [`schema::from_value`].
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PlanExport {
+    /// Schema version, see [`schema::SCHEMA_VERSION`]
+    pub version: u32,
+    pub exported_at: DateTime<Utc>,
+    /// ID of the plan on the server it was exported from
+    pub id: u8,
+    #[serde(flatten)]
+    pub snapshot: PlanSnapshot,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub leases: Vec<LeaseRecord>,
+}
+
+/// Captures a single plan in `core`, with its cursor, history and leases
+pub fn export_plan(core: &Core, id: &PlanId) -> Result<PlanExport, PlanError> {
+    let (snapshot, leases) = core.export_plan_with_leases(id)?;
+    Ok(PlanExport {
+        version: SCHEMA_VERSION,
+        exported_at: Utc::now(),
+        id: id.value(),
+        snapshot,
+        leases,
+    })
+}
+
+/// Creates the exported plan in `core` under a new ID, which is returned. The ID it had
+/// where it was exported from may already be taken here.
+pub fn import_plan(core: &Core, export: PlanExport) -> Result<PlanId, PlanError> {
+    core.import_plan_as_new(export.snapshot, export.leases)
+}
+
+/// Contents of `manifest.json` in a backup archive
+#[derive(Serialize, Deserialize)]
+struct BackupManifest {
+    version: u32,
+    exported_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    settings: Option<ArchiveSettings>,
+    plan_ids: Vec<u8>,
+}
+
+/// Writes `archive` to `path` as a `.tar.gz` holding a manifest and one file per plan.
+pub fn write_backup(archive: &PlanArchive, path: &Path) -> std::io::Result<()> {
+    let file = std::fs::File::create(path)?;
+    let mut builder = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+    let mtime = archive.exported_at.timestamp().max(0) as u64;
+
+    let mut append = |name: String, contents: Vec<u8>| -> std::io::Result<()> {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(contents.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(mtime);
+        header.set_cksum();
+        builder.append_data(&mut header, name, contents.as_slice())
+    };
+
+    let manifest = BackupManifest {
+        version: archive.version,
+        exported_at: archive.exported_at,
+        settings: archive.settings.clone(),
+        plan_ids: archive.plans.iter().map(|stored| stored.id).collect(),
+    };
+    append(
+        BACKUP_MANIFEST.to_string(),
+        serde_json::to_vec_pretty(&manifest)?,
+    )?;
+    for stored in &archive.plans {
+        append(
+            format!("{BACKUP_PLANS_DIR}/{}.json", stored.id),
+            serde_json::to_vec_pretty(stored)?,
+        )?;
+    }
+
+    builder.into_inner()?.finish()?;
+    Ok(())
+}
+
+/// Reads a backup written by [`write_backup`], migrating it to the current schema
+/// version. A `.json` file is read as a plain [`PlanArchive`], as produced by the admin
+/// export endpoint.
+pub fn read_backup(path: &Path) -> std::io::Result<PlanArchive> {
+    if path.extension().is_some_and(|ext| ext == "json") {
+        let contents = std::fs::read_to_string(path)?;
+        return Ok(schema::from_str(&contents)?);
+    }
+
+    let file = std::fs::File::open(path)?;
+    let mut tar = tar::Archive::new(GzDecoder::new(file));
+    // Read as untyped JSON so the reassembled archive can be migrated as a whole
+    let mut manifest: Option<serde_json::Value> = None;
+    let mut plans: Vec<serde_json::Value> = Vec::new();
+
+    for entry in tar.entries()? {
+        let mut entry = entry?;
+        let name = entry.path()?.to_string_lossy().into_owned();
+        let mut contents = String::new();
+        entry.read_to_string(&mut contents)?;
+
+        if name == BACKUP_MANIFEST {
+            manifest = Some(serde_json::from_str(&contents)?);
+        } else if name.starts_with(BACKUP_PLANS_DIR) && name.ends_with(".json") {
+            plans.push(serde_json::from_str(&contents)?);
+        }
+    }
+
+    let Some(serde_json::Value::Object(mut archive)) = manifest else {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("{} has no valid {BACKUP_MANIFEST}", path.display()),
+        ));
+    };
+    archive.remove("plan_ids");
+    archive.insert("plans".to_string(), plans.into());
+
+    let mut archive: PlanArchive = schema::from_value(archive.into())?;
+    archive.plans.sort_by_key(|stored| stored.id);
+    Ok(archive)
+}
+
+/// Writes a backup of every plan in `core` to `path`.
+pub fn backup_to_file(core: &Core, path: &Path) -> std::io::Result<usize> {
+    let archive = export_archive(core).map_err(|e| std::io::Error::other(e.to_string()))?;
+    write_backup(&archive, path)?;
+    Ok(archive.plans.len())
+}
+
+/// Restores every plan from the backup at `path` into `core`, returning how many were restored.
+pub fn restore_from_file(core: &Core, path: &Path) -> std::io::Result<usize> {
+    let archive = read_backup(path)?;
+    import_archive(core, archive).map_err(|e| std::io::Error::other(e.to_string()))
+}
+
+/// Path of the plans file inside `dir`
+pub fn plans_file(dir: &Path) -> PathBuf {
+    dir.join(PLANS_FILE)
+}
+
+/// Loads previously saved plans from `dir` into `core`, returning how many were loaded.
+///
+/// A missing plans file is not an error; nothing is loaded in that case.
+pub fn load_plans(core: &Core, dir: &Path) -> std::io::Result<usize> {
+    let path = plans_file(dir);
+    if !path.exists() {
+        return Ok(0);
+    }
+    let contents = std::fs::read_to_string(&path)?;
+    let stored: StoredPlans = schema::from_str(&contents)?;
+    let count = stored.plans.len();
+    for StoredPlan { id, snapshot } in stored.plans {
+        core.import_plan(Lease::new(id), snapshot)
+            .map_err(|e| std::io::Error::other(e.to_string()))?;
+    }
+    Ok(count)
+}
+
+/// Writes all plans in `core` to `dir`, replacing the previous plans file atomically.
+///
+/// Only the transitions plans keep in memory are saved; the journal's history logs have
+/// the older ones.
+pub fn save_plans(core: &Core, dir: &Path) -> std::io::Result<()> {
+    let stored = StoredPlans {
+        version: SCHEMA_VERSION,
+        plans: core
+            .retained_snapshots()
+            .map_err(|e| std::io::Error::other(e.to_string()))?
+            .into_iter()
+            .map(|(id, snapshot)| StoredPlan {
+                id: id.value(),
+                snapshot,
+            })
+            .collect(),
+    };
+    let json = serde_json::to_string_pretty(&stored)?;
+
+    std::fs::create_dir_all(dir)?;
+    let tmp_path = dir.join(format!("{PLANS_FILE}.tmp"));
+    std::fs::write(&tmp_path, json)?;
+    std::fs::rename(tmp_path, plans_file(dir))
+}
+
+/// One line of a journal segment: plans as they were when created or imported, changes
+/// to plans, and plans deleted
+#[derive(Default, Serialize, Deserialize)]
+struct JournalEntry {
+    version: u32,
+    /// When the changes were made
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    plans: Vec<StoredPlan>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    changes: Vec<StoredUpdate>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    deleted: Vec<u8>,
+}
+
+/// A change to a plan as written to the journal, keyed by the plan's ID
+#[derive(Serialize, Deserialize)]
+struct StoredUpdate {
+    id: u8,
+    #[serde(flatten)]
+    update: PlanUpdate,
+}
+
+/// Write-ahead journal kept in numbered segment files under `<dir>/journal`, with a
+/// history log per plan under `<dir>/history`
+///
+/// Changes are recorded and written out by [`flush`](PlanJournal::flush) under the plans
+/// lock, each synced to the current segment before it is made. [`checkpoint`] starts a new segment, saves the
+/// plans file, and then moves the segments the plans file now covers into the archive.
+/// History logs are only appended to, except when a plan's history is compacted or
+/// replaced. Past states are found through an index of where each plan's changes are,
+/// built the first time one is looked up, so a lookup reads a single change.
+pub struct FileJournal {
+    dir: PathBuf,
+    history_dir: PathBuf,
+    /// Number and file of the segment being appended to
+    segment: Mutex<(u64, File)>,
+    /// Changes recorded but not written out yet, in order
+    pending: Mutex<Vec<PendingChange>>,
+    /// Sequence number after the last transition in each plan's history log, once known
+    logged: Mutex<HashMap<u8, usize>>,
+    /// Where each plan's changes are, once a past state has been looked up
+    index: Mutex<Option<RevisionIndex>>,
+}
+
+/// Where each plan's changes are in the journal's files
+#[derive(Default)]
+struct RevisionIndex {
+    /// Each plan's changes, in the order they were written
+    plans: HashMap<u8, Vec<IndexedChange>>,
+    /// Length of each archived day once decompressed, where the next segment archived
+    /// into it starts
+    archive_lens: HashMap<PathBuf, u64>,
+}
+
+/// A journal line changing a plan
+#[derive(Clone)]
+struct IndexedChange {
+    at: DateTime<Utc>,
+    /// Whether the change deleted the plan
+    deleted: bool,
+    file: JournalFile,
+    /// Where the line starts in the file, once decompressed
+    offset: u64,
+    len: usize,
+}
+
+#[derive(Clone, PartialEq, Eq)]
+enum JournalFile {
+    Segment(u64),
+    Archive(PathBuf),
+}
+
+impl RevisionIndex {
+    /// Indexes the changes in `contents`, the decompressed contents of `file`
+    fn add_file(&mut self, file: &JournalFile, contents: &[u8]) {
+        let mut offset = 0;
+        for line in contents.split_inclusive(|byte| *byte == b'\n') {
+            let entry = std::str::from_utf8(line)
+                .ok()
+                .and_then(|line| schema::from_str::<JournalEntry>(line.trim_end()).ok());
+            if let Some(entry) = entry {
+                self.add_entry(&entry, file, offset, line.len());
+            }
+            offset += line.len() as u64;
+        }
+        if let JournalFile::Archive(path) = file {
+            self.archive_lens.insert(path.clone(), offset);
+        }
+    }
+
+    fn add_entry(&mut self, entry: &JournalEntry, file: &JournalFile, offset: u64, len: usize) {
+        let Some(at) = entry.at else {
+            return;
+        };
+        let mut ids: Vec<u8> = (entry.plans.iter().map(|plan| plan.id))
+            .chain(entry.changes.iter().map(|change| change.id))
+            .chain(entry.deleted.iter().copied())
+            .collect();
+        ids.sort_unstable();
+        ids.dedup();
+        for id in ids {
+            self.plans.entry(id).or_default().push(IndexedChange {
+                at,
+                deleted: entry.deleted.contains(&id),
+                file: file.clone(),
+                offset,
+                len,
+            });
+        }
+    }
+
+    /// Points the changes in segment `number` at where they are now, at the end of the
+    /// archived day `archive`
+    fn archive_segment(&mut self, number: u64, archive: &Path, len: u64) {
+        let base = self.archive_lens.entry(archive.to_path_buf()).or_insert(0);
+        for change in self.plans.values_mut().flatten() {
+            if change.file == JournalFile::Segment(number) {
+                change.file = JournalFile::Archive(archive.to_path_buf());
+                change.offset += *base;
+            }
+        }
+        *base += len;
+    }
+
+    /// Forgets the changes in an archived day that was deleted
+    fn remove_archive(&mut self, archive: &Path) {
+        let file = JournalFile::Archive(archive.to_path_buf());
+        for changes in self.plans.values_mut() {
+            changes.retain(|change| change.file != file);
+        }
+        self.archive_lens.remove(archive);
+    }
+}
+
+/// A recorded change waiting to be flushed
+struct PendingChange {
+    id: u8,
+    /// The change's journal line
+    line: Vec<u8>,
+    entry: JournalEntry,
+    history: HistoryWrite,
+}
+
+/// What a change does to its plan's history log
+enum HistoryWrite {
+    /// Appends the transitions the log doesn't have yet
+    Append(Vec<HistoryRecord>),
+    /// Starts the log over with these transitions
+    Replace(Vec<HistoryRecord>),
+    Remove,
+}
+
+fn locked<T>(mutex: &Mutex<T>) -> std::io::Result<MutexGuard<'_, T>> {
+    mutex
+        .lock()
+        .map_err(|_| std::io::Error::other("journal lock poisoned"))
+}
+
+/// `transitions` as records of plan `id`, numbered from `first_seq`
+fn history_records(
+    id: PlanId,
+    first_seq: usize,
+    transitions: &[TransitionLogEntry],
+) -> Vec<HistoryRecord> {
+    transitions
+        .iter()
+        .enumerate()
+        .map(|(i, entry)| HistoryRecord {
+            plan_id: id,
+            seq: first_seq + i,
+            entry: entry.clone(),
+        })
+        .collect()
+}
+
+impl FileJournal {
+    /// Opens a journal in the storage directory `dir`, starting a new segment after any
+    /// left over from a previous run.
+    pub fn open(dir: &Path) -> std::io::Result<Self> {
+        let history_dir = dir.join(HISTORY_DIR);
+        std::fs::create_dir_all(&history_dir)?;
+        let dir = dir.join(JOURNAL_DIR);
+        std::fs::create_dir_all(&dir)?;
+        let next = journal_segments(&dir)?
+            .last()
+            .map_or(0, |(number, _)| number + 1);
+        let file = open_segment(&dir, next)?;
+        Ok(Self {
+            dir,
+            history_dir,
+            segment: Mutex::new((next, file)),
+            pending: Mutex::new(Vec::new()),
+            logged: Mutex::new(HashMap::new()),
+            index: Mutex::new(None),
+        })
+    }
+
+    /// Logs the transitions plans in `core` keep in memory that their history logs
+    /// don't have yet, e.g. from a plans file saved before history logs existed, or
+    /// ones a failed write left out. Call this before any trimming drops them.
+    pub fn backfill_history(&self, core: &Core) -> std::io::Result<()> {
+        let snapshots = core
+            .retained_snapshots()
+            .map_err(|e| std::io::Error::other(e.to_string()))?;
+        for (id, snapshot) in snapshots {
+            let records = history_records(id, snapshot.history_start, &snapshot.history);
+            self.write_history(id.value(), HistoryWrite::Append(records))?;
+        }
+        Ok(())
+    }
+
+    /// Path of the history log of plan `id`
+    fn history_file(&self, id: u8) -> PathBuf {
+        self.history_dir.join(format!("{id}.jsonl"))
+    }
+
+    /// Applies a change to the history log of plan `id`
+    fn write_history(&self, id: u8, write: HistoryWrite) -> std::io::Result<()> {
+        let path = self.history_file(id);
+        let mut logged = locked(&self.logged)?;
+        let next = match write {
+            HistoryWrite::Remove => {
+                logged.remove(&id);
+                return match std::fs::remove_file(&path) {
+                    Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+                    _ => Ok(()),
+                };
+            }
+            HistoryWrite::Replace(records) => {
+                let tmp_path = path.with_extension("jsonl.tmp");
+                let mut file = File::create(&tmp_path)?;
+                file.write_all(&history_lines(&records)?)?;
+                file.sync_data()?;
+                std::fs::rename(tmp_path, &path)?;
+                records.last().map(|last| last.seq + 1)
+            }
+            HistoryWrite::Append(records) => {
+                let next = match logged.get(&id) {
+                    Some(next) => *next,
+                    None => read_history_log(&path)?
+                        .last()
+                        .map_or(0, |record| record.seq + 1),
+                };
+                let new: Vec<HistoryRecord> = records
+                    .into_iter()
+                    .filter(|record| record.seq >= next)
+                    .collect();
+                if !new.is_empty() {
+                    let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+                    file.write_all(&history_lines(&new)?)?;
+                    file.sync_data()?;
+                }
+                Some(new.last().map_or(next, |last| last.seq + 1))
+            }
+        };
+        match next {
+            Some(next) => logged.insert(id, next),
+            // An empty log is read again next time, to find where it starts
+            None => logged.remove(&id),
+        };
+        Ok(())
+    }
+
+    /// Switches to a new segment and returns its number; earlier segments are no
+    /// longer written to.
+    fn rotate(&self) -> std::io::Result<u64> {
+        let mut segment = locked(&self.segment)?;
+        let next = segment.0 + 1;
+        *segment = (next, open_segment(&self.dir, next)?);
+        Ok(next)
+    }
+
+    /// Compresses the segments numbered below `number` into today's archive, then
+    /// deletes them and the archived days past [`ARCHIVE_RETENTION_DAYS`]
+    fn archive_segments_before(&self, number: u64) -> std::io::Result<()> {
+        let archive_dir = self.dir.join(ARCHIVE_DIR);
+        std::fs::create_dir_all(&archive_dir)?;
+        let today = archive_dir.join(format!("{}.jsonl.gz", Utc::now().format("%Y-%m-%d")));
+        for (segment, path) in journal_segments(&self.dir)? {
+            if segment >= number {
+                continue;
+            }
+            let contents = std::fs::read(&path)?;
+            if !contents.is_empty() {
+                // Each segment is a gzip member of its own; together they read as one
+                let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(&contents)?;
+                let mut archive = OpenOptions::new().create(true).append(true).open(&today)?;
+                archive.write_all(&encoder.finish()?)?;
+                archive.sync_data()?;
+            }
+            // The index points at the archive before the segment is gone
+            let mut index = locked(&self.index)?;
+            if let Some(index) = index.as_mut() {
+                index.archive_segment(segment, &today, contents.len() as u64);
+            }
+            std::fs::remove_file(path)?;
+        }
+
+        let days = archived_days(&archive_dir)?;
+        for path in &days[..days.len().saturating_sub(ARCHIVE_RETENTION_DAYS)] {
+            let mut index = locked(&self.index)?;
+            std::fs::remove_file(path)?;
+            if let Some(index) = index.as_mut() {
+                index.remove_archive(path);
+            }
+        }
+        Ok(())
+    }
+
+    /// Runs `f` on the index of where plans' changes are, building it first if this is
+    /// the first lookup. Changes aren't written out while it's built, so none are
+    /// missed or indexed twice.
+    fn with_index<T>(
+        &self,
+        f: impl FnOnce(&RevisionIndex) -> std::io::Result<T>,
+    ) -> std::io::Result<T> {
+        // Same lock order as flush: the segment, then the index
+        let _segment;
+        let mut index = locked(&self.index)?;
+        if index.is_none() {
+            drop(index);
+            _segment = locked(&self.segment)?;
+            index = locked(&self.index)?;
+        }
+        if index.is_none() {
+            let mut built = RevisionIndex::default();
+            for path in archived_days(&self.dir.join(ARCHIVE_DIR))? {
+                let contents = read_journal_bytes(&path)?;
+                built.add_file(&JournalFile::Archive(path), &contents);
+            }
+            for (number, path) in journal_segments(&self.dir)? {
+                built.add_file(&JournalFile::Segment(number), &std::fs::read(path)?);
+            }
+            *index = Some(built);
+        }
+        f(index.as_ref().expect("index was just built"))
+    }
+
+    /// Reads the journal line an indexed change is on
+    fn read_change(&self, change: &IndexedChange) -> std::io::Result<JournalEntry> {
+        let mut line = vec![0; change.len];
+        match &change.file {
+            JournalFile::Segment(number) => {
+                let mut file = File::open(segment_path(&self.dir, *number))?;
+                file.seek(SeekFrom::Start(change.offset))?;
+                file.read_exact(&mut line)?;
+            }
+            JournalFile::Archive(path) => {
+                let mut decoder = MultiGzDecoder::new(File::open(path)?);
+                std::io::copy(
+                    &mut (&mut decoder).take(change.offset),
+                    &mut std::io::sink(),
+                )?;
+                decoder.read_exact(&mut line)?;
+            }
+        }
+        schema::from_str(String::from_utf8_lossy(&line).trim_end())
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+    }
+}
+
+impl PlanJournal for FileJournal {
+    fn record(&self, id: PlanId, change: PlanChange<'_>) -> std::io::Result<()> {
+        let mut entry = JournalEntry {
+            version: SCHEMA_VERSION,
+            at: Some(Utc::now()),
+            ..Default::default()
+        };
+        let history = match change {
+            PlanChange::Replaced(snapshot) => {
+                entry.plans.push(StoredPlan {
+                    id: id.value(),
+                    snapshot: snapshot.clone(),
+                });
+                HistoryWrite::Replace(history_records(
+                    id,
+                    snapshot.history_start,
+                    &snapshot.history,
+                ))
+            }
+            PlanChange::Updated(update) => {
+                entry.changes.push(StoredUpdate {
+                    id: id.value(),
+                    update: update.clone(),
+                });
+                let records = history_records(id, update.first_seq, &update.transitions);
+                if update.compacted {
+                    HistoryWrite::Replace(records)
+                } else {
+                    HistoryWrite::Append(records)
+                }
+            }
+            PlanChange::Deleted => {
+                entry.deleted.push(id.value());
+                HistoryWrite::Remove
+            }
+        };
+        let mut line = serde_json::to_vec(&entry)?;
+        line.push(b'\n');
+
+        locked(&self.pending)?.push(PendingChange {
+            id: id.value(),
+            line,
+            entry,
+            history,
+        });
+        Ok(())
+    }
+
+    fn flush(&self) -> std::io::Result<()> {
+        // Holding the segment lock throughout keeps changes in the order they were
+        // recorded, in the segment and in the history logs
+        let mut segment = locked(&self.segment)?;
+        let pending = std::mem::take(&mut *locked(&self.pending)?);
+        if pending.is_empty() {
+            return Ok(());
+        }
+        let start = segment.1.metadata()?.len();
+        let written = pending
+            .iter()
+            .try_for_each(|change| segment.1.write_all(&change.line))
+            .and_then(|()| segment.1.sync_data());
+        if let Err(e) = written {
+            // The changes are rejected, so whatever part of them made it to the segment
+            // is cut off again rather than replayed on the next start
+            if let Err(e) = segment.1.set_len(start) {
+                tracing::warn!("Failed to truncate journal segment {}: {e}", segment.0);
+            }
+            return Err(e);
+        }
+
+        // The changes are written, so a poisoned index only fails later lookups
+        if let Ok(mut index) = self.index.lock() {
+            let file = JournalFile::Segment(segment.0);
+            let mut offset = start;
+            for change in &pending {
+                if let Some(index) = index.as_mut() {
+                    index.add_entry(&change.entry, &file, offset, change.line.len());
+                }
+                offset += change.line.len() as u64;
+            }
+        }
+        for change in pending {
+            // The segment has the change, and the transitions a history log is missing
+            // are logged again from memory on the next start
+            if let Err(e) = self.write_history(change.id, change.history) {
+                tracing::warn!(
+                    "Failed to update the history log of plan {}: {e}",
+                    change.id
+                );
+            }
+        }
+        Ok(())
+    }
+
+    fn revision(&self, id: PlanId, at: DateTime<Utc>) -> std::io::Result<Option<(Plan, Index)>> {
+        let id = id.value();
+        // Each change carries the plan's whole state, so the last one before `at` is it.
+        // It's read outside the index lock; a segment archived in the meantime is looked
+        // up again where it is now.
+        let mut attempts = 0;
+        let entry = loop {
+            let change = self.with_index(|index| {
+                Ok(index
+                    .plans
+                    .get(&id)
+                    .and_then(|changes| changes.iter().rev().find(|change| change.at <= at))
+                    .filter(|change| !change.deleted)
+                    .cloned())
+            })?;
+            let Some(change) = change else {
+                return Ok(None);
+            };
+            attempts += 1;
+            match self.read_change(&change) {
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound && attempts < 3 => continue,
+                entry => break entry?,
+            }
+        };
+        let plan = entry
+            .changes
+            .into_iter()
+            .rfind(|change| change.id == id)
+            .map(|StoredUpdate { update, .. }| (update.plan, update.cursor))
+            .or_else(|| {
+                entry
+                    .plans
+                    .into_iter()
+                    .rfind(|plan| plan.id == id)
+                    .map(|StoredPlan { snapshot, .. }| (snapshot.plan, snapshot.cursor))
+            });
+        Ok(plan)
+    }
+
+    fn oldest_revision(&self, id: PlanId) -> std::io::Result<Option<DateTime<Utc>>> {
+        let id = id.value();
+        self.with_index(|index| {
+            Ok(index.plans.get(&id).and_then(|changes| {
+                changes
+                    .iter()
+                    .find(|change| !change.deleted)
+                    .map(|change| change.at)
+            }))
+        })
+    }
+
+    fn history(&self, id: PlanId, seqs: Range<usize>) -> std::io::Result<Vec<HistoryRecord>> {
+        // Transitions just trimmed from memory may not be written out yet
+        self.flush()?;
+        let _logged = locked(&self.logged)?;
+        Ok(read_history_log(&self.history_file(id.value()))?
+            .into_iter()
+            .filter(|record| seqs.contains(&record.seq))
+            .collect())
+    }
+}
+
+/// Lists the archived days in an archive directory, oldest first
+fn archived_days(archive_dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    if !archive_dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut days: Vec<PathBuf> = std::fs::read_dir(archive_dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.to_string_lossy().ends_with(".jsonl.gz"))
+        .collect();
+    // Named by date, so they sort by it
+    days.sort();
+    Ok(days)
+}
+
+/// The contents of a segment or archived day, decompressed. What comes before damage to
+/// an archive, from a crash while it was being written, is still read.
+fn read_journal_bytes(path: &Path) -> std::io::Result<Vec<u8>> {
+    let mut contents = Vec::new();
+    if path.extension().is_some_and(|ext| ext == "gz") {
+        if let Err(e) = MultiGzDecoder::new(File::open(path)?).read_to_end(&mut contents) {
+            tracing::warn!("Failed to read all of {}: {e}", path.display());
+        }
+    } else {
+        File::open(path)?.read_to_end(&mut contents)?;
+    }
+    Ok(contents)
+}
+
+/// `records` as lines of a history log
+fn history_lines(records: &[HistoryRecord]) -> std::io::Result<Vec<u8>> {
+    let mut lines = Vec::new();
+    for record in records {
+        serde_json::to_writer(&mut lines, record)?;
+        lines.push(b'\n');
+    }
+    Ok(lines)
+}
+
+/// Every transition in the history log at `path`, oldest first. A missing log is empty,
+/// and a line cut short at the end was never fully logged, so it is skipped.
+fn read_history_log(path: &Path) -> std::io::Result<Vec<HistoryRecord>> {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let lines: Vec<&str> = contents.lines().filter(|l| !l.trim().is_empty()).collect();
+    let mut records = Vec::with_capacity(lines.len());
+    for (n, line) in lines.iter().enumerate() {
+        match serde_json::from_str(line) {
+            Ok(record) => records.push(record),
+            Err(_) if n + 1 == lines.len() => break,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(records)
+}
+
+fn segment_path(journal_dir: &Path, number: u64) -> PathBuf {
+    journal_dir.join(format!("{number:08}.jsonl"))
+}
+
+fn open_segment(journal_dir: &Path, number: u64) -> std::io::Result<File> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(segment_path(journal_dir, number))
+}
+
+/// Lists the segments in a journal directory by number, oldest first
+fn journal_segments(journal_dir: &Path) -> std::io::Result<Vec<(u64, PathBuf)>> {
+    if !journal_dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut segments: Vec<(u64, PathBuf)> = std::fs::read_dir(journal_dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "jsonl"))
+        .filter_map(|path| {
+            let number = path.file_stem()?.to_str()?.parse().ok()?;
+            Some((number, path))
+        })
+        .collect();
+    segments.sort();
+    Ok(segments)
+}
+
+/// Applies the changes journaled in `dir` on top of the plans already loaded into
+/// `core`, returning how many changes were replayed.
+///
+/// Call this after [`load_plans`] and before attaching a journal. A line cut short at the
+/// end of a segment is a change that was being written when the server stopped, and was
+/// never acknowledged, so it is skipped.
+pub fn replay_journal(core: &Core, dir: &Path) -> std::io::Result<usize> {
+    let mut replayed = 0;
+    for (_, path) in journal_segments(&dir.join(JOURNAL_DIR))? {
+        let contents = std::fs::read_to_string(&path)?;
+        let lines: Vec<&str> = contents.lines().filter(|l| !l.trim().is_empty()).collect();
+        for (n, line) in lines.iter().enumerate() {
+            let entry: JournalEntry = match schema::from_str(line) {
+                Ok(entry) => entry,
+                Err(e) if n + 1 == lines.len() => {
+                    tracing::warn!("Skipping torn journal entry in {}: {e}", path.display());
+                    break;
+                }
+                Err(e) => return Err(e.into()),
+            };
+            for StoredPlan { id, snapshot } in entry.plans {
+                core.import_plan(Lease::new(id), snapshot)
+                    .map_err(|e| std::io::Error::other(e.to_string()))?;
+            }
+            for StoredUpdate { id, update } in entry.changes {
+                // The plan may have been deleted before the plans file was saved
+                match core.apply_update(Lease::new(id), update) {
+                    Ok(()) | Err(PlanError::PlanNotFound(_)) => {}
+                    Err(e) => return Err(std::io::Error::other(e.to_string())),
+                }
+            }
+            for id in entry.deleted {
+                // The plan may never have made it into the plans file
+                match core.delete_plan(&Lease::new(id)) {
+                    Ok(()) | Err(PlanError::PlanNotFound(_)) => {}
+                    Err(e) => return Err(std::io::Error::other(e.to_string())),
+                }
+            }
+            replayed += 1;
+        }
+    }
+    Ok(replayed)
+}
+
+/// Saves all plans to `dir` and archives the journal segments the save covers.
+pub fn checkpoint(core: &Core, dir: &Path, journal: &FileJournal) -> std::io::Result<()> {
+    // Changes written before the rotation are applied by the time the plans are
+    // exported, since both happen under the plans lock. Ones written after it land in
+    // the new segment, where replaying them again changes nothing.
+    let first_kept = journal.rotate()?;
+    save_plans(core, dir)?;
+    journal.archive_segments_before(first_kept)
+}
+
+/// Spawns a background task that checkpoints all plans to `dir` a little while after
+/// plans change. The journal covers the changes made in between.
+pub fn spawn_persister(
+    core: &Core,
+    dir: PathBuf,
+    journal: Arc<FileJournal>,
+) -> tokio::task::JoinHandle<()> {
+    let mut receiver = core.subscribe();
+    let core = core.clone();
+
+    tokio::spawn(async move {
+        // Lagging only means several updates arrived at once; one save covers them
+        while let Ok(_) | Err(RecvError::Lagged(_)) = receiver.recv().await {
+            tokio::time::sleep(CHECKPOINT_DELAY).await;
+            // The save covers the updates made while waiting as well
+            while let Ok(_) | Err(TryRecvError::Lagged(_)) = receiver.try_recv() {}
+
+            let (core, saved_dir, journal) = (core.clone(), dir.clone(), journal.clone());
+            let saved =
+                tokio::task::spawn_blocking(move || checkpoint(&core, &saved_dir, &journal))
+                    .await
+                    .unwrap_or_else(|e| Err(std::io::Error::other(e)));
+            if let Err(e) = saved {
+                tracing::error!("Failed to save plans to {}: {e}", dir.display());
+            }
+        }
+    })
+}
+
+/// Directory holding the snapshots of plan `id` inside `dir`
+pub fn snapshots_dir(dir: &Path, id: u8) -> PathBuf {
+    dir.join(SNAPSHOTS_DIR).join(id.to_string())
+}
+
+/// Lists the snapshots of plan `id` in `dir`, oldest first
+pub fn list_snapshots(dir: &Path, id: u8) -> std::io::Result<Vec<PathBuf>> {
+    let plan_dir = snapshots_dir(dir, id);
+    if !plan_dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut snapshots: Vec<PathBuf> = std::fs::read_dir(plan_dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+    // Names are UTC timestamps, so they sort chronologically
+    snapshots.sort();
+    Ok(snapshots)
+}
+
+/// Writes a snapshot of plan `id` to `dir` and deletes the oldest snapshots beyond
+/// `retain` (0 keeps all). Returns the path written.
+///
+/// Each snapshot is a one-plan [`PlanArchive`], so it can be brought back with `restore`.
+pub fn write_snapshot(
+    core: &Core,
+    dir: &Path,
+    id: PlanId,
+    retain: usize,
+) -> std::io::Result<PathBuf> {
+    let snapshot = core
+        .export_plan(&id)
+        .map_err(|e| std::io::Error::other(e.to_string()))?;
+    let exported_at = Utc::now();
+    let archive = PlanArchive {
+        version: SCHEMA_VERSION,
+        exported_at,
+        settings: None,
+        plans: vec![StoredPlan {
+            id: id.value(),
+            snapshot,
+        }],
+    };
+
+    let plan_dir = snapshots_dir(dir, id.value());
+    std::fs::create_dir_all(&plan_dir)?;
+    let path = plan_dir.join(format!("{}.json", exported_at.format("%Y%m%dT%H%M%S%.9fZ")));
+    std::fs::write(&path, serde_json::to_string_pretty(&archive)?)?;
+
+    if retain > 0 {
+        let snapshots = list_snapshots(dir, id.value())?;
+        let excess = snapshots.len().saturating_sub(retain);
+        for old in &snapshots[..excess] {
+            std::fs::remove_file(old)?;
+        }
+    }
+    Ok(path)
+}
+
+/// Spawns a background task that snapshots plans to `dir` according to the configured
+/// [`SnapshotPolicy`](super::config::SnapshotPolicy).
+///
+/// Only plans that changed since their last snapshot are snapshotted, once
+/// `every_mutations` changes have piled up or when the `interval_secs` timer fires,
+/// whichever comes first. The policy is read again on every change and timer tick, so
+/// config reloads take effect immediately.
+pub fn spawn_snapshotter(
+    core: &Core,
+    dir: PathBuf,
+    config: SharedConfig,
+) -> tokio::task::JoinHandle<()> {
+    let mut receiver = core.subscribe();
+    let core = core.clone();
+
+    tokio::spawn(async move {
+        // Changes to each plan since its last snapshot
+        let mut pending: HashMap<PlanId, u64> = HashMap::new();
+        let policy = || {
+            config
+                .read()
+                .map(|c| c.snapshots.clone())
+                .unwrap_or_default()
+        };
+        let next_tick = |interval_secs: u64| {
+            Instant::now()
+                + match interval_secs {
+                    0 => SNAPSHOT_IDLE_CHECK,
+                    secs => Duration::from_secs(secs),
+                }
+        };
+        let mut due = next_tick(policy().interval_secs);
+
+        loop {
+            tokio::select! {
+                received = receiver.recv() => {
+                    let policy = policy();
+                    match received {
+                        Ok(id) => {
+                            let changes = pending.entry(id).or_default();
+                            *changes += 1;
+                            if policy.every_mutations > 0 && *changes >= policy.every_mutations {
+                                pending.remove(&id);
+                                snapshot(&core, &dir, id, policy.retain);
+                            }
+                        }
+                        // Some updates were missed, so any plan may have changed
+                        Err(RecvError::Lagged(_)) => {
+                            for summary in core.list_plan_summaries().unwrap_or_default() {
+                                pending.entry(summary.id).or_default();
+                            }
+                        }
+                        Err(RecvError::Closed) => break,
+                    }
+                }
+                _ = tokio::time::sleep_until(due) => {
+                    let policy = policy();
+                    if policy.interval_secs > 0 {
+                        for (id, _) in pending.drain() {
+                            snapshot(&core, &dir, id, policy.retain);
+                        }
+                    }
+                    due = next_tick(policy.interval_secs);
+                }
+            }
+        }
+    })
+}
+
+fn snapshot(core: &Core, dir: &Path, id: PlanId, retain: usize) {
+    match write_snapshot(core, dir, id, retain) {
+        Ok(path) => tracing::debug!("Snapshotted plan {} to {}", id.value(), path.display()),
+        // The plan may have been deleted since it changed
+        Err(e) => tracing::warn!("Failed to snapshot plan {}: {e}", id.value()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::MAX_RETAINED_TRANSITIONS;
+
+    #[test]
+    fn test_save_and_load_plans() {
+        let dir =
+            std::env::temp_dir().join(format!("scatterbrain-storage-{}", rand::random::<u64>()));
+
+        let core = Core::new();
+        let id = core.create_plan("Persisted".to_string(), None).unwrap();
+        core.add_task(&id, "Survives a restart".to_string(), 0, None)
+            .unwrap();
+        save_plans(&core, &dir).unwrap();
+
+        let restored = Core::new();
+        assert_eq!(load_plans(&restored, &dir).unwrap(), 1);
+        let plan = restored.get_plan(&id).unwrap().into_inner();
+        assert_eq!(plan.goal.as_deref(), Some("Persisted"));
+        assert_eq!(plan.root().subtasks().len(), 1);
+
+        // Backups carry the cursor and history along with the plan
+        core.move_to(&id, vec![0]).unwrap();
+        let backup = dir.join("backup.tar.gz");
+        assert_eq!(backup_to_file(&core, &backup).unwrap(), 1);
+        let restored = Core::new();
+        assert_eq!(restore_from_file(&restored, &backup).unwrap(), 1);
+        let current = restored.current(&id).unwrap().into_inner().unwrap();
+        assert_eq!(current.index, vec![0]);
+        let history = restored.export_plans().unwrap().remove(0).1.history;
+        assert!(history.iter().any(|entry| entry.action == "move_to"));
+
+        // Plans files from before schema versioning (a bare array) still load
+        let legacy = serde_json::json!([{
+            "id": 7,
+            "plan": serde_json::to_value(&plan).unwrap(),
+        }]);
+        std::fs::write(plans_file(&dir), legacy.to_string()).unwrap();
+        let restored = Core::new();
+        assert_eq!(load_plans(&restored, &dir).unwrap(), 1);
+        assert!(restored.get_plan(&Lease::new(7)).is_ok());
+
+        // A directory without a plans file loads nothing
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(load_plans(&Core::new(), &dir).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_plan_export_moves_leases_along() {
+        let source = Core::new();
+        let id = source.create_plan("Moving".to_string(), None).unwrap();
+        source.add_task(&id, "Leased".to_string(), 0, None).unwrap();
+        source.move_to(&id, vec![0]).unwrap();
+        let (lease, _) = source
+            .generate_lease(&id, vec![0], Default::default())
+            .unwrap()
+            .into_inner();
+
+        // Round-trip through JSON and the schema reader, as the CLI does with files
+        let json = serde_json::to_string(&export_plan(&source, &id).unwrap()).unwrap();
+        let export: PlanExport = schema::from_str(&json).unwrap();
+        assert_eq!(export.id, id.value());
+
+        let target = Core::new();
+        let imported = import_plan(&target, export).unwrap();
+        let current = target.current(&imported).unwrap().into_inner().unwrap();
+        assert_eq!(current.index, vec![0]);
+        assert!(target
+            .complete_task(&imported, vec![0], None, None, false, None, None)
+            .is_err());
+        target
+            .complete_task(
+                &imported,
+                vec![0],
+                Some(lease.value()),
+                None,
+                false,
+                None,
+                None,
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn test_journal_replay() {
+        let dir =
+            std::env::temp_dir().join(format!("scatterbrain-journal-{}", rand::random::<u64>()));
+
+        // Changes only reach the journal; the plans file is never saved
+        let core = Core::new();
+        let journal = Arc::new(FileJournal::open(&dir).unwrap());
+        core.set_journal(journal.clone()).unwrap();
+        let kept = core.create_plan("Kept".to_string(), None).unwrap();
+        core.add_task(&kept, "Acknowledged".to_string(), 0, None)
+            .unwrap();
+        let dropped = core.create_plan("Dropped".to_string(), None).unwrap();
+        core.delete_plan(&dropped).unwrap();
+
+        // A write cut short by the crash is ignored
+        let segment = journal_segments(&dir.join(JOURNAL_DIR))
+            .unwrap()
+            .remove(0)
+            .1;
+        let mut file = OpenOptions::new().append(true).open(segment).unwrap();
+        file.write_all(b"{\"version\": 2, \"plans\": [{\"id\"")
+            .unwrap();
+        drop(core);
+
+        let restored = Core::new();
+        assert_eq!(load_plans(&restored, &dir).unwrap(), 0);
+        assert_eq!(replay_journal(&restored, &dir).unwrap(), 4);
+        let plan = restored.get_plan(&kept).unwrap().into_inner();
+        assert_eq!(plan.root().subtasks().len(), 1);
+        assert!(restored.get_plan(&dropped).is_err());
+
+        // A checkpoint moves everything into the plans file and empties the journal
+        let journal = FileJournal::open(&dir).unwrap();
+        checkpoint(&restored, &dir, &journal).unwrap();
+        assert_eq!(journal_segments(&dir.join(JOURNAL_DIR)).unwrap().len(), 1);
+        let reloaded = Core::new();
+        assert_eq!(load_plans(&reloaded, &dir).unwrap(), 1);
+        assert_eq!(replay_journal(&reloaded, &dir).unwrap(), 0);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_history_log() {
+        let dir =
+            std::env::temp_dir().join(format!("scatterbrain-history-{}", rand::random::<u64>()));
+        let core = Core::new();
+        let journal = Arc::new(FileJournal::open(&dir).unwrap());
+        core.set_journal(journal.clone()).unwrap();
+        let id = core.create_plan("Long-running".to_string(), None).unwrap();
+        core.add_task(&id, "Revisited".to_string(), 0, None)
+            .unwrap();
+        for _ in 0..MAX_RETAINED_TRANSITIONS + 200 {
+            core.move_to(&id, vec![0]).unwrap();
+        }
+
+        // The plan keeps only its latest transitions in memory, but none is lost
+        let next = core.next_seq(&id).unwrap();
+        let retained = core.retained_snapshots().unwrap().remove(0).1;
+        assert!(retained.history.len() < MAX_RETAINED_TRANSITIONS + 200);
+        let history = core.history(&id).unwrap();
+        assert!(history.iter().map(|record| record.seq).eq(0..next));
+        assert_eq!(core.changes_since(&id, 0).unwrap().len(), next);
+        assert_eq!(core.export_plan(&id).unwrap().history.len(), next);
+
+        // Each change journals only the transitions it logged
+        let segment = journal_segments(&dir.join(JOURNAL_DIR))
+            .unwrap()
+            .remove(0)
+            .1;
+        let contents = std::fs::read_to_string(segment).unwrap();
+        let last: JournalEntry = serde_json::from_str(contents.lines().last().unwrap()).unwrap();
+        assert_eq!(last.changes[0].update.transitions.len(), 1);
+        assert_eq!(last.changes[0].update.first_seq, next - 1);
+
+        // After a restart, the plans file, the journal and the history log add up again
+        checkpoint(&core, &dir, &journal).unwrap();
+        core.move_to(&id, vec![0]).unwrap();
+        drop((core, journal));
+        let restored = Core::new();
+        assert_eq!(load_plans(&restored, &dir).unwrap(), 1);
+        assert_eq!(replay_journal(&restored, &dir).unwrap(), 1);
+        let journal = Arc::new(FileJournal::open(&dir).unwrap());
+        journal.backfill_history(&restored).unwrap();
+        restored.set_journal(journal).unwrap();
+        let history = restored.history(&id).unwrap();
+        assert!(history.iter().map(|record| record.seq).eq(0..next + 1));
+
+        // Compacting empties the log, and deleting the plan removes it
+        let log = dir.join(HISTORY_DIR).join(format!("{}.jsonl", id.value()));
+        restored.compact_history(&id, 1).unwrap();
+        assert_eq!(restored.history(&id).unwrap().len(), 1);
+        assert_eq!(std::fs::read_to_string(&log).unwrap().lines().count(), 1);
+        restored.delete_plan(&id).unwrap();
+        assert!(!log.exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_revisions_from_journal() {
+        let dir =
+            std::env::temp_dir().join(format!("scatterbrain-revisions-{}", rand::random::<u64>()));
+        let core = Core::new();
+        let journal = Arc::new(FileJournal::open(&dir).unwrap());
+        core.set_journal(journal.clone()).unwrap();
+        let before = Utc::now() - chrono::Duration::seconds(1);
+        let id = core.create_plan("Past".to_string(), None).unwrap();
+        core.add_task(&id, "Task 0".to_string(), 0, None).unwrap();
+        let between = Utc::now();
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        core.add_task(&id, "Task 1".to_string(), 0, None).unwrap();
+
+        let subtasks = |core: &Core, at| {
+            core.plan_at(&id, at)
+                .unwrap()
+                .into_inner()
+                .root()
+                .subtasks()
+                .len()
+        };
+        assert_eq!(subtasks(&core, between), 1);
+        assert_eq!(subtasks(&core, Utc::now()), 2);
+
+        // Saved segments are archived, and past states are found in the archive, before
+        // and after a restart
+        checkpoint(&core, &dir, &journal).unwrap();
+        let archive_dir = dir.join(JOURNAL_DIR).join(ARCHIVE_DIR);
+        assert_eq!(archived_days(&archive_dir).unwrap().len(), 1);
+        assert_eq!(subtasks(&core, between), 1);
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        let archived = Utc::now();
+        core.add_task(&id, "Task 2".to_string(), 0, None).unwrap();
+        checkpoint(&core, &dir, &journal).unwrap();
+        assert_eq!(subtasks(&core, between), 1);
+        assert_eq!(subtasks(&core, archived), 2);
+        assert_eq!(subtasks(&core, Utc::now()), 3);
+        drop((core, journal));
+        let restored = Core::new();
+        assert_eq!(load_plans(&restored, &dir).unwrap(), 1);
+        restored
+            .set_journal(Arc::new(FileJournal::open(&dir).unwrap()))
+            .unwrap();
+        assert_eq!(subtasks(&restored, between), 1);
+        assert_eq!(subtasks(&restored, archived), 2);
+        assert_eq!(subtasks(&restored, Utc::now()), 3);
+        assert!(matches!(
+            restored.plan_at(&id, before),
+            Err(PlanError::RevisionNotFound {
+                oldest: Some(_),
+                ..
+            })
+        ));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_revisions_before_journal() {
+        let dir =
+            std::env::temp_dir().join(format!("scatterbrain-revisions-{}", rand::random::<u64>()));
+        let core = Core::new();
+        let id = core.create_plan("Past".to_string(), None).unwrap();
+        core.add_task(&id, "Task 0".to_string(), 0, None).unwrap();
+        let unjournaled = Utc::now();
+        std::thread::sleep(std::time::Duration::from_millis(5));
+
+        // Revisions kept in memory still cover the time before the journal
+        core.set_journal(Arc::new(FileJournal::open(&dir).unwrap()))
+            .unwrap();
+        core.add_task(&id, "Task 1".to_string(), 0, None).unwrap();
+        let subtasks = |at| {
+            core.plan_at(&id, at)
+                .unwrap()
+                .into_inner()
+                .root()
+                .subtasks()
+                .len()
+        };
+        assert_eq!(subtasks(unjournaled), 1);
+        assert_eq!(subtasks(Utc::now()), 2);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_snapshot_retention() {
+        let dir =
+            std::env::temp_dir().join(format!("scatterbrain-snapshots-{}", rand::random::<u64>()));
+
+        let core = Core::new();
+        let id = core.create_plan("Snapshotted".to_string(), None).unwrap();
+        let first = write_snapshot(&core, &dir, id, 2).unwrap();
+        for n in 0..3 {
+            core.add_task(&id, format!("Task {n}"), 0, None).unwrap();
+            write_snapshot(&core, &dir, id, 2).unwrap();
+        }
+
+        // Only the newest two are kept, and the latest one has every task
+        let snapshots = list_snapshots(&dir, id.value()).unwrap();
+        assert_eq!(snapshots.len(), 2);
+        assert!(!first.exists());
+        let restored = Core::new();
+        assert_eq!(
+            restore_from_file(&restored, snapshots.last().unwrap()).unwrap(),
+            1
+        );
+        let plan = restored.get_plan(&id).unwrap().into_inner();
+        assert_eq!(plan.root().subtasks().len(), 3);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_after_mutations() {
+        let dir =
+            std::env::temp_dir().join(format!("scatterbrain-snapshots-{}", rand::random::<u64>()));
+        let mut config = crate::api::ServerConfig::default();
+        config.snapshots.every_mutations = 2;
+        let config = std::sync::Arc::new(std::sync::RwLock::new(config));
+
+        let core = Core::new();
+        let id = core.create_plan("Busy".to_string(), None).unwrap();
+        let snapshotter = spawn_snapshotter(&core, dir.clone(), config);
+
+        core.add_task(&id, "One".to_string(), 0, None).unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(list_snapshots(&dir, id.value()).unwrap().is_empty());
+
+        core.add_task(&id, "Two".to_string(), 0, None).unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(list_snapshots(&dir, id.value()).unwrap().len(), 1);
+
+        snapshotter.abort();
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,50 @@
+//! Webhook delivery
+//!
+//! This module forwards plan completion events to a configured URL as JSON `POST`
+//! requests. Task-level events are left to [event hooks](super::hooks).
+
+use tokio::sync::broadcast::error::RecvError;
+
+use super::config::SharedConfig;
+use crate::models::{EventKind, PlanEvent};
+use crate::Core;
+
+/// Spawns a background task that posts every plan completion to the configured webhook URL.
+///
+/// The URL is looked up for each event, so config reloads take effect immediately;
+/// events are dropped while no URL is configured. Delivery is best effort: failures
+/// are logged and the event is dropped.
+pub fn spawn_webhook_forwarder(core: &Core, config: SharedConfig) -> tokio::task::JoinHandle<()> {
+    let mut receiver = core.subscribe_events();
+    let client = reqwest::Client::new();
+
+    tokio::spawn(async move {
+        loop {
+            match receiver.recv().await {
+                Ok(event) if event.kind() == EventKind::PlanComplete => {
+                    let url = config.read().ok().and_then(|c| c.webhook_url.clone());
+                    if let Some(url) = url {
+                        deliver(&client, &url, &event).await;
+                    }
+                }
+                Ok(_) => {}
+                Err(RecvError::Lagged(skipped)) => {
+                    tracing::warn!("Webhook forwarder lagged, dropped {skipped} events");
+                }
+                Err(RecvError::Closed) => break,
+            }
+        }
+    })
+}
+
+async fn deliver(client: &reqwest::Client, url: &str, event: &PlanEvent) {
+    match client.post(url).json(event).send().await {
+        Ok(response) if response.status().is_success() => {
+            tracing::debug!("Delivered {event:?} to webhook {url}");
+        }
+        Ok(response) => {
+            tracing::warn!("Webhook {url} responded with {}", response.status());
+        }
+        Err(e) => tracing::warn!("Failed to deliver webhook to {url}: {e}"),
+    }
+}
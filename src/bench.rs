@@ -0,0 +1,559 @@
+//! Benchmarks and load generation
+//!
+//! A [`Workload`] fills an in-process [`Core`] with plans of a configurable size and runs
+//! single operations against them. The criterion benches in `benches/core.rs` time those
+//! operations one at a time, and [`run_load`], behind `scatterbrain bench`, drives them
+//! from several threads at once in a configurable mix, reporting latency percentiles for
+//! each kind of operation. Numbers taken before and after a change show what it cost.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::models::{Index, PlanError, PlanId, TaskFilter};
+use crate::spec::{PlanSpec, TaskSpec};
+use crate::Core;
+
+/// Most plans a workload may have; plan IDs are a single byte
+pub const MAX_PLANS: usize = 200;
+
+/// Most tasks a workload may put in each plan
+pub const MAX_TASKS_PER_PLAN: usize = 100_000;
+
+/// Errors setting up or running a benchmark
+#[derive(Debug, Error)]
+pub enum BenchError {
+    #[error("Invalid benchmark configuration: {0}")]
+    InvalidConfig(String),
+    #[error("Invalid operation mix '{0}': expected e.g. get_plan=4,add_task=1")]
+    InvalidMix(String),
+    #[error(transparent)]
+    Plan(#[from] PlanError),
+}
+
+/// An operation a benchmark runs against a plan
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Operation {
+    /// Read the whole plan
+    GetPlan,
+    /// Read the plan's distilled context
+    Distilled,
+    /// List every task in the plan
+    ListTasks,
+    /// Add a task under the cursor
+    AddTask,
+    /// Force-complete a random task and its subtasks
+    CompleteTask,
+    /// Replace a random task's notes
+    SetNotes,
+    /// Move the cursor to a random task
+    MoveTo,
+}
+
+impl Operation {
+    /// Every operation, reads first
+    pub const ALL: [Operation; 7] = [
+        Operation::GetPlan,
+        Operation::Distilled,
+        Operation::ListTasks,
+        Operation::AddTask,
+        Operation::CompleteTask,
+        Operation::SetNotes,
+        Operation::MoveTo,
+    ];
+
+    /// Name used in operation mixes and reports
+    pub fn name(&self) -> &'static str {
+        match self {
+            Operation::GetPlan => "get_plan",
+            Operation::Distilled => "distilled",
+            Operation::ListTasks => "list_tasks",
+            Operation::AddTask => "add_task",
+            Operation::CompleteTask => "complete_task",
+            Operation::SetNotes => "set_notes",
+            Operation::MoveTo => "move_to",
+        }
+    }
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Operation {
+    type Err = BenchError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Operation::ALL
+            .into_iter()
+            .find(|operation| operation.name() == s)
+            .ok_or_else(|| BenchError::InvalidMix(s.to_string()))
+    }
+}
+
+/// How often each operation is picked, as relative weights
+///
+/// ```
+/// # use scatterbrain::bench::{Operation, OperationMix};
+/// let mix: OperationMix = "get_plan=3,add_task=1".parse().unwrap();
+/// assert_eq!(mix.weight(Operation::GetPlan), 3);
+/// assert_eq!(mix.weight(Operation::MoveTo), 0);
+/// assert!("get_plan=0".parse::<OperationMix>().is_err());
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OperationMix {
+    weights: BTreeMap<Operation, u32>,
+}
+
+impl Default for OperationMix {
+    /// Mostly reads, the way agents use a plan: read context, then change a task
+    fn default() -> Self {
+        Self {
+            weights: BTreeMap::from([
+                (Operation::GetPlan, 3),
+                (Operation::Distilled, 3),
+                (Operation::ListTasks, 2),
+                (Operation::AddTask, 1),
+                (Operation::CompleteTask, 1),
+                (Operation::SetNotes, 1),
+                (Operation::MoveTo, 1),
+            ]),
+        }
+    }
+}
+
+impl OperationMix {
+    /// Weight of `operation`; 0 when it isn't run
+    pub fn weight(&self, operation: Operation) -> u32 {
+        self.weights.get(&operation).copied().unwrap_or(0)
+    }
+
+    /// Picks an operation with probability proportional to its weight
+    fn pick(&self, rng: &mut impl Rng) -> Operation {
+        let total: u32 = self.weights.values().sum();
+        let mut roll = rng.gen_range(0..total);
+        for (operation, weight) in &self.weights {
+            if roll < *weight {
+                return *operation;
+            }
+            roll -= weight;
+        }
+        unreachable!("roll is below the total weight")
+    }
+}
+
+impl FromStr for OperationMix {
+    type Err = BenchError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || BenchError::InvalidMix(s.to_string());
+        let mut weights = BTreeMap::new();
+        for entry in s
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+        {
+            let (name, weight) = entry.split_once('=').ok_or_else(invalid)?;
+            let operation = name.trim().parse::<Operation>().map_err(|_| invalid())?;
+            let weight = weight.trim().parse::<u32>().map_err(|_| invalid())?;
+            if weight > 0 {
+                weights.insert(operation, weight);
+            }
+        }
+        if weights.is_empty() {
+            return Err(invalid());
+        }
+        Ok(Self { weights })
+    }
+}
+
+impl fmt::Display for OperationMix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let entries: Vec<String> = self
+            .weights
+            .iter()
+            .map(|(operation, weight)| format!("{operation}={weight}"))
+            .collect();
+        f.write_str(&entries.join(","))
+    }
+}
+
+/// What a benchmark builds and runs
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BenchConfig {
+    /// Plans to create and spread operations over
+    pub plans: usize,
+    /// Levels of tasks below each plan's root
+    pub depth: usize,
+    /// Subtasks of each task above the bottom level
+    pub fanout: usize,
+    /// Operations to run in total
+    pub operations: usize,
+    /// Threads running operations at once
+    pub threads: usize,
+    /// How often each operation is run
+    pub mix: OperationMix,
+    /// Seed for picking operations and tasks, so runs can be repeated
+    pub seed: u64,
+}
+
+impl Default for BenchConfig {
+    fn default() -> Self {
+        Self {
+            plans: 4,
+            depth: 3,
+            fanout: 4,
+            operations: 10_000,
+            threads: 4,
+            mix: OperationMix::default(),
+            seed: 0,
+        }
+    }
+}
+
+impl BenchConfig {
+    /// Tasks each plan starts with, not counting its root
+    ///
+    /// ```
+    /// # use scatterbrain::bench::BenchConfig;
+    /// let config = BenchConfig { depth: 3, fanout: 4, ..Default::default() };
+    /// assert_eq!(config.tasks_per_plan(), 4 + 16 + 64);
+    /// ```
+    pub fn tasks_per_plan(&self) -> usize {
+        (1..=self.depth)
+            .map(|level| self.fanout.saturating_pow(level as u32))
+            .fold(0, usize::saturating_add)
+    }
+
+    fn check(&self) -> Result<(), BenchError> {
+        let invalid = |message: String| Err(BenchError::InvalidConfig(message));
+        if self.plans == 0 || self.plans > MAX_PLANS {
+            return invalid(format!("plans must be between 1 and {MAX_PLANS}"));
+        }
+        if self.depth == 0 || self.fanout == 0 {
+            return invalid("depth and fanout must be at least 1".to_string());
+        }
+        if self.tasks_per_plan() > MAX_TASKS_PER_PLAN {
+            return invalid(format!(
+                "a depth of {} and fanout of {} make more than {MAX_TASKS_PER_PLAN} tasks per plan",
+                self.depth, self.fanout
+            ));
+        }
+        if self.threads == 0 {
+            return invalid("threads must be at least 1".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Plans in an in-process [`Core`] for benchmarks to run operations against
+///
+/// ```
+/// # use scatterbrain::bench::{BenchConfig, Operation, Workload};
+/// # use rand::SeedableRng;
+/// let config = BenchConfig { plans: 2, depth: 2, fanout: 3, ..Default::default() };
+/// let workload = Workload::new(&config).unwrap();
+/// assert_eq!(workload.core().list_plans().unwrap().len(), 2);
+///
+/// let mut rng = rand::rngs::StdRng::seed_from_u64(7);
+/// workload.run(Operation::AddTask, &mut rng).unwrap();
+/// workload.run(Operation::Distilled, &mut rng).unwrap();
+/// ```
+#[derive(Clone)]
+pub struct Workload {
+    core: Core,
+    plans: Vec<PlanId>,
+    /// Index of every task the plans start with, the same in each plan
+    indexes: Vec<Index>,
+    /// Level tasks are added at; the most detailed one fits under any task
+    add_level: usize,
+}
+
+impl Workload {
+    /// Creates `config.plans` plans, each a full tree `config.depth` levels deep with
+    /// `config.fanout` subtasks per task. Levels below the most detailed one reuse it.
+    pub fn new(config: &BenchConfig) -> Result<Self, BenchError> {
+        config.check()?;
+        let core = Core::new();
+        let add_level = core.default_levels()?.len().saturating_sub(1);
+        let tasks = tree(config.depth, config.fanout, add_level, 0);
+        let mut plans = Vec::with_capacity(config.plans);
+        for n in 0..config.plans {
+            let spec = PlanSpec {
+                goal: format!("Benchmark plan {n}"),
+                notes: Some("Created by scatterbrain bench".to_string()),
+                levels: None,
+                usage_summary: None,
+                tasks: tasks.clone(),
+            };
+            plans.push(core.create_plan_from_spec(spec)?);
+        }
+        let mut indexes = Vec::with_capacity(config.tasks_per_plan());
+        collect_indexes(&tasks, &mut Vec::new(), &mut indexes);
+        Ok(Self {
+            core,
+            plans,
+            indexes,
+            add_level,
+        })
+    }
+
+    /// The core holding the plans
+    pub fn core(&self) -> &Core {
+        &self.core
+    }
+
+    /// Runs `operation` against a random plan, on a random task where it needs one
+    pub fn run(&self, operation: Operation, rng: &mut impl Rng) -> Result<(), PlanError> {
+        let id = &self.plans[rng.gen_range(0..self.plans.len())];
+        let mut index = || self.indexes[rng.gen_range(0..self.indexes.len())].clone();
+        match operation {
+            Operation::GetPlan => self.core.get_plan(id).map(drop),
+            Operation::Distilled => self.core.distilled_context(id).map(drop),
+            Operation::ListTasks => self.core.list_tasks(id, &TaskFilter::default()).map(drop),
+            Operation::AddTask => self
+                .core
+                .add_task(id, "Benchmark task".to_string(), self.add_level, None)
+                .map(drop),
+            Operation::CompleteTask => self
+                .core
+                .complete_task(
+                    id,
+                    index(),
+                    None,
+                    None,
+                    true,
+                    Some(true),
+                    Some("Completed by the benchmark".to_string()),
+                )
+                .map(drop),
+            Operation::SetNotes => self
+                .core
+                .set_task_notes(id, index(), "Notes written by the benchmark".to_string())
+                .map(drop),
+            Operation::MoveTo => self.core.move_to(id, index()).map(drop),
+        }
+    }
+}
+
+/// A full tree of task specs `depth` levels deep
+fn tree(depth: usize, fanout: usize, max_level: usize, level: usize) -> Vec<TaskSpec> {
+    if depth == 0 {
+        return Vec::new();
+    }
+    (0..fanout)
+        .map(|n| TaskSpec {
+            description: format!("Level {level} task {n}"),
+            level: level.min(max_level),
+            notes: None,
+            sections: Default::default(),
+            completed: false,
+            summary: None,
+            weight: None,
+            priority: None,
+            tags: Default::default(),
+            tasks: tree(depth - 1, fanout, max_level, level + 1),
+        })
+        .collect()
+}
+
+fn collect_indexes(tasks: &[TaskSpec], parent: &mut Index, indexes: &mut Vec<Index>) {
+    for (n, task) in tasks.iter().enumerate() {
+        parent.push(n);
+        indexes.push(parent.clone());
+        collect_indexes(&task.tasks, parent, indexes);
+        parent.pop();
+    }
+}
+
+/// Latencies of one kind of operation in a [`BenchReport`], in microseconds
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct OperationStats {
+    pub operation: Operation,
+    pub count: usize,
+    /// Operations that returned an error; they are timed like the others
+    pub errors: usize,
+    pub mean_us: f64,
+    pub p50_us: f64,
+    pub p90_us: f64,
+    pub p99_us: f64,
+    pub max_us: f64,
+}
+
+impl OperationStats {
+    fn new(operation: Operation, mut latencies: Vec<Duration>, errors: usize) -> Self {
+        latencies.sort_unstable();
+        let micros = |duration: Duration| duration.as_secs_f64() * 1e6;
+        let total: Duration = latencies.iter().sum();
+        Self {
+            operation,
+            count: latencies.len(),
+            errors,
+            mean_us: micros(total) / latencies.len().max(1) as f64,
+            p50_us: micros(percentile(&latencies, 50.0)),
+            p90_us: micros(percentile(&latencies, 90.0)),
+            p99_us: micros(percentile(&latencies, 99.0)),
+            max_us: micros(latencies.last().copied().unwrap_or_default()),
+        }
+    }
+}
+
+/// The nearest-rank percentile of sorted `latencies`
+fn percentile(latencies: &[Duration], percent: f64) -> Duration {
+    if latencies.is_empty() {
+        return Duration::ZERO;
+    }
+    let rank = (percent / 100.0 * latencies.len() as f64).ceil() as usize;
+    latencies[rank.clamp(1, latencies.len()) - 1]
+}
+
+/// What a [`run_load`] run did and how long its operations took
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BenchReport {
+    pub plans: usize,
+    /// Tasks each plan started with
+    pub tasks_per_plan: usize,
+    pub threads: usize,
+    pub mix: String,
+    pub operations: usize,
+    pub elapsed_ms: f64,
+    /// Operations per second over all threads
+    pub throughput: f64,
+    /// Latencies of each operation that ran, in the order of [`Operation::ALL`]
+    pub results: Vec<OperationStats>,
+}
+
+/// Builds a [`Workload`] for `config` and runs `config.operations` operations against it,
+/// split over `config.threads` threads, timing each one
+///
+/// ```
+/// # use scatterbrain::bench::{run_load, BenchConfig};
+/// let config = BenchConfig { operations: 200, threads: 2, ..Default::default() };
+/// let report = run_load(&config).unwrap();
+/// let count: usize = report.results.iter().map(|stats| stats.count).sum();
+/// assert_eq!(count, 200);
+/// ```
+pub fn run_load(config: &BenchConfig) -> Result<BenchReport, BenchError> {
+    let workload = Workload::new(config)?;
+    let started = Instant::now();
+    let samples: Vec<Vec<(Operation, Duration, bool)>> = std::thread::scope(|scope| {
+        let workers: Vec<_> = (0..config.threads)
+            .map(|thread| {
+                // Spread the remainder over the first threads
+                let operations = config.operations / config.threads
+                    + usize::from(thread < config.operations % config.threads);
+                let workload = &workload;
+                let mut rng = StdRng::seed_from_u64(config.seed.wrapping_add(thread as u64));
+                scope.spawn(move || {
+                    (0..operations)
+                        .map(|_| {
+                            let operation = config.mix.pick(&mut rng);
+                            let start = Instant::now();
+                            let ok = workload.run(operation, &mut rng).is_ok();
+                            (operation, start.elapsed(), ok)
+                        })
+                        .collect()
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .map(|worker| worker.join().expect("benchmark thread panicked"))
+            .collect()
+    });
+    let elapsed = started.elapsed();
+
+    let mut latencies: BTreeMap<Operation, (Vec<Duration>, usize)> = BTreeMap::new();
+    for (operation, latency, ok) in samples.into_iter().flatten() {
+        let (durations, errors) = latencies.entry(operation).or_default();
+        durations.push(latency);
+        *errors += usize::from(!ok);
+    }
+    let results = latencies
+        .into_iter()
+        .map(|(operation, (durations, errors))| OperationStats::new(operation, durations, errors))
+        .collect();
+    Ok(BenchReport {
+        plans: config.plans,
+        tasks_per_plan: config.tasks_per_plan(),
+        threads: config.threads,
+        mix: config.mix.to_string(),
+        operations: config.operations,
+        elapsed_ms: elapsed.as_secs_f64() * 1e3,
+        throughput: config.operations as f64 / elapsed.as_secs_f64().max(f64::EPSILON),
+        results,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percentile_uses_nearest_rank() {
+        let latencies: Vec<Duration> = (1..=100).map(Duration::from_micros).collect();
+        assert_eq!(percentile(&latencies, 50.0), Duration::from_micros(50));
+        assert_eq!(percentile(&latencies, 99.0), Duration::from_micros(99));
+        assert_eq!(percentile(&latencies, 100.0), Duration::from_micros(100));
+        assert_eq!(percentile(&latencies[..1], 90.0), Duration::from_micros(1));
+        assert_eq!(percentile(&[], 50.0), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_load_runs_the_mix_on_every_thread() {
+        let config = BenchConfig {
+            plans: 3,
+            depth: 5,
+            fanout: 2,
+            operations: 301,
+            threads: 3,
+            mix: "get_plan=1,complete_task=1,set_notes=1,move_to=1,add_task=1"
+                .parse()
+                .unwrap(),
+            seed: 42,
+        };
+        let report = run_load(&config).unwrap();
+        assert_eq!(report.tasks_per_plan, 62);
+        let operations: Vec<Operation> = report.results.iter().map(|s| s.operation).collect();
+        assert_eq!(
+            operations,
+            vec![
+                Operation::GetPlan,
+                Operation::AddTask,
+                Operation::CompleteTask,
+                Operation::SetNotes,
+                Operation::MoveTo,
+            ]
+        );
+        assert_eq!(report.results.iter().map(|s| s.count).sum::<usize>(), 301);
+        for stats in &report.results {
+            assert_eq!(stats.errors, 0, "{} failed", stats.operation);
+            assert!(stats.p50_us <= stats.p90_us && stats.p90_us <= stats.p99_us);
+            assert!(stats.p99_us <= stats.max_us);
+        }
+    }
+
+    #[test]
+    fn test_config_is_checked() {
+        let too_big = BenchConfig {
+            depth: 10,
+            fanout: 10,
+            ..Default::default()
+        };
+        assert!(matches!(
+            Workload::new(&too_big),
+            Err(BenchError::InvalidConfig(_))
+        ));
+        assert!("get_plan=1,fly=2".parse::<OperationMix>().is_err());
+        assert!("get_plan".parse::<OperationMix>().is_err());
+    }
+}
@@ -1,8 +1,16 @@
 //! Binary entrypoint for the scatterbrain tool
 
-use scatterbrain::cli;
+use std::process::ExitCode;
+
+use scatterbrain::cli::{self, Failure};
 
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    cli::run().await
+async fn main() -> ExitCode {
+    match cli::run().await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            Failure::of(e.as_ref()).exit_code()
+        }
+    }
 }
@@ -4,19 +4,41 @@
 
 use clap::{CommandFactory, Parser, Subcommand};
 use clap_complete::{generate, Shell};
-use colored::Colorize;
-use std::io; // Import env module // Import the Colorize trait
+use colored::{Color, ColoredString, Colorize};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::io::{self, Write}; // Import env module // Import the Colorize trait
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
 
 use crate::{
     api::{
-        serve, Client, ClientConfig, ClientError, HttpClientImpl, ScatterbrainMcpServer,
-        ServerConfig,
+        config::{ConfigError, ConfigOverrides, ConfigSource},
+        rpc, run_services, schema, serve,
+        server::{join_path, task_ui_path},
+        storage, Client, ClientConfig, ClientError, HttpClientImpl, ScatterbrainMcpServer,
+        ServerConfig, ServiceOptions,
     },
-    models::{parse_index, Core, Current, PlanError, PlanId, DEFAULT_PLAN_ID},
+    bench::{run_load, BenchConfig, BenchReport, OperationMix},
+    examples::{self, ExampleConfig, ExampleSize},
+    guide::{GuideError, GuideMode},
+    models::{
+        parse_index, CompletionPolicy, Core, Current, EffortComparison, EffortReport, Focus, Index,
+        InvalidTaskRef, PlanBadge, PlanColor, PlanError, PlanId, PlanStats, PlanStatus,
+        ProgressSummary, SessionStatus, StatusTransition, TaskError, TaskOrder, TaskRef,
+        DEFAULT_PLAN_ID, DEFAULT_SUMMARY_LEVEL,
+    },
+    quotas::QuotaReport,
+    search::{MatchField, SearchHit},
+    spec::{PlanSpec, TaskSpec},
+    templates::{parse_param, TaskTemplate},
 };
 
 // Define the constant here
 const PLAN_ID_ENV_VAR: &str = "SCATTERBRAIN_PLAN_ID";
+/// Overrides where the CLI config file is read from
+const CLI_CONFIG_ENV_VAR: &str = "SCATTERBRAIN_CLI_CONFIG";
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
@@ -29,34 +51,178 @@ pub struct Cli {
     #[arg(long, global = true)]
     plan: Option<u8>,
 
+    /// Bearer token for servers started with an auth token
+    #[arg(
+        long,
+        global = true,
+        env = "SCATTERBRAIN_TOKEN",
+        hide_env_values = true
+    )]
+    token: Option<String>,
+
+    /// Session leases are bound to; leases generated with it only work when completing
+    /// with the same session
+    #[arg(long, global = true, env = "SCATTERBRAIN_SESSION")]
+    session: Option<String>,
+
+    /// Agent name recorded in the transition history of plans this command changes
+    #[arg(long, global = true, env = "SCATTERBRAIN_AGENT")]
+    agent: Option<String>,
+
+    /// Workspace to work in, on servers hosting several; the server's default plans
+    /// are used without one
+    #[arg(long, global = true, env = "SCATTERBRAIN_WORKSPACE")]
+    workspace: Option<String>,
+
+    /// Server to read from when --server can't be reached (repeatable, tried in order);
+    /// changes are never sent to it
+    #[arg(
+        long = "fallback-server",
+        value_name = "URL",
+        global = true,
+        env = "SCATTERBRAIN_FALLBACK_SERVERS",
+        value_delimiter = ','
+    )]
+    fallback_servers: Vec<String>,
+
+    /// Disable colored output (also disabled when NO_COLOR is set)
+    #[arg(long, global = true)]
+    no_color: bool,
+
+    /// Print long output directly instead of through $PAGER
+    #[arg(long, global = true)]
+    no_pager: bool,
+
     #[command(subcommand)]
     command: Commands,
 }
 
+/// Flags for filling a plan with a generated example task tree
+#[derive(clap::Args, Clone, Debug)]
+struct ExampleArgs {
+    /// Populate with an example task tree, for demos and UI testing
+    #[arg(long)]
+    example: bool,
+
+    /// Size of the example tree: small, medium or large
+    #[arg(long, value_name = "SIZE", requires = "example")]
+    example_size: Option<ExampleSize>,
+
+    /// Seed the example tree is generated from; the same seed builds the same tree
+    #[arg(long, value_name = "SEED", requires = "example")]
+    example_seed: Option<u64>,
+}
+
+impl ExampleArgs {
+    /// The example tree asked for, if any
+    fn config(&self) -> Option<ExampleConfig> {
+        self.example.then(|| ExampleConfig {
+            size: self.example_size.unwrap_or_default(),
+            seed: self.example_seed.unwrap_or_default(),
+        })
+    }
+}
+
 #[derive(Subcommand)]
 enum Commands {
     /// Start the scatterbrain API server
     Serve {
-        /// Port to listen on
-        #[arg(short, long, default_value_t = 3000)]
-        port: u16,
+        /// Path to a server config file (defaults to ./server.toml if present)
+        #[arg(long)]
+        config: Option<std::path::PathBuf>,
+
+        /// Port to listen on (default 3000)
+        #[arg(short, long)]
+        port: Option<u16>,
+
+        #[command(flatten)]
+        example: ExampleArgs,
 
-        /// Populate with example task tree for UI testing
+        /// URL to POST plan lifecycle events (e.g. plan completion) to as JSON
         #[arg(long)]
-        example: bool,
+        webhook_url: Option<String>,
+
+        /// Directory to persist plans in
+        #[arg(long)]
+        storage_path: Option<std::path::PathBuf>,
+
+        /// Require this bearer token on all API requests
+        #[arg(long, env = "SCATTERBRAIN_AUTH_TOKEN", hide_env_values = true)]
+        auth_token: Option<String>,
+
+        /// Origin allowed to make cross-origin requests (repeatable; any origin when unset)
+        #[arg(long = "cors-origin")]
+        cors_origins: Vec<String>,
+
+        /// Expose request and plan counters on /metrics
+        #[arg(long)]
+        metrics: bool,
+
+        /// Don't compress responses, even for clients that accept gzip or deflate
+        #[arg(long)]
+        no_compression: bool,
+
+        /// Seconds without other frames before the UI event stream sends a ping (0 disables them)
+        #[arg(long)]
+        sse_keep_alive_secs: Option<u64>,
     },
 
     /// Start the scatterbrain MCP server
     Mcp {
-        /// Populate with example task tree for testing
-        #[arg(long)]
-        example: bool,
+        #[command(flatten)]
+        example: ExampleArgs,
 
         /// Optionally expose HTTP API server on the specified port
         #[arg(long)]
         expose: Option<u16>,
     },
 
+    /// Serve the client operations as JSON-RPC 2.0 over stdio, one message per line
+    Rpc {
+        #[command(flatten)]
+        example: ExampleArgs,
+
+        /// Forward requests to the server at --server instead of an in-process planner
+        #[arg(long, conflicts_with = "example")]
+        remote: bool,
+    },
+
+    /// Measure operation latencies against an in-process planner under load
+    Bench {
+        /// Plans to create and spread operations over
+        #[arg(long, default_value_t = 4)]
+        plans: usize,
+
+        /// Levels of tasks in each plan
+        #[arg(long, default_value_t = 3)]
+        depth: usize,
+
+        /// Subtasks of each task above the bottom level
+        #[arg(long, default_value_t = 4)]
+        fanout: usize,
+
+        /// Operations to run in total
+        #[arg(long, default_value_t = 10_000)]
+        operations: usize,
+
+        /// Threads running operations at once
+        #[arg(long, default_value_t = 4)]
+        threads: usize,
+
+        /// Relative weight of each operation, e.g. get_plan=4,add_task=1 (operations:
+        /// get_plan, distilled, list_tasks, add_task, complete_task, set_notes, move_to)
+        #[arg(long)]
+        mix: Option<OperationMix>,
+
+        /// Seed for picking operations and tasks, so runs can be repeated
+        #[arg(long, default_value_t = 0)]
+        seed: u64,
+
+        /// Print the report as JSON, e.g. to compare runs
+        #[arg(long)]
+        json: bool,
+    },
+
     /// Task management commands
     Task {
         #[command(subcommand)]
@@ -65,15 +231,36 @@ enum Commands {
 
     /// Move to a task at the given index
     Move {
-        /// Task index (e.g., 0 or 0,1,2 for nested tasks)
+        /// Task index (e.g., 0 or 0,1,2 for nested tasks) or ID (e.g., t-3f9a2c1b)
         index: String,
     },
 
     /// Get the current task
     Current,
 
+    /// Show the plan's progress: status, current task and completed/total tasks
+    Status {
+        /// Print a single line, e.g. for shell prompts and tmux status lines
+        #[arg(long)]
+        short: bool,
+    },
+
     /// Get a distilled context of the current planning state
-    Distilled,
+    Distilled {
+        /// Only include these notes sections (comma-separated, e.g., findings,decisions)
+        #[arg(long, value_delimiter = ',')]
+        sections: Vec<String>,
+        /// Print dense plain text for pasting into a prompt
+        #[arg(long)]
+        compact: bool,
+    },
+
+    /// Find tasks by the words in their descriptions, notes and summaries
+    Search {
+        /// Words every matching task contains; "pars" also matches "parser"
+        #[arg(required = true)]
+        query: Vec<String>,
+    },
 
     /// Interactive guide on how to use this tool
     Guide,
@@ -85,32 +272,144 @@ enum Commands {
         shell: Shell,
     },
 
+    /// Track open questions for the plan
+    Question {
+        #[command(subcommand)]
+        command: QuestionCommands,
+    },
+
+    /// Manage reusable task templates
+    Template {
+        #[command(subcommand)]
+        command: TemplateCommands,
+    },
+
+    /// Server administration (requires the server's admin token via --token)
+    Admin {
+        #[command(subcommand)]
+        command: AdminCommands,
+    },
+
+    /// Back up every plan on the server, with histories and settings, to a .tar.gz archive
+    Backup {
+        /// Archive to write, e.g. scatterbrain-backup.tar.gz
+        file: std::path::PathBuf,
+    },
+
+    /// Restore every plan from a backup archive, replacing plans with the same IDs
+    Restore {
+        /// Archive written by `backup` (or a JSON archive from `admin export`)
+        file: std::path::PathBuf,
+    },
+
     /// Plan management commands
     #[command(name = "plan", subcommand)] // Add plan subcommand
     PlanCmd(PlanCommands), // Use a different name to avoid conflict with the "Plan" viewing command
 }
 
+/// How `task export` prints a subtree
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum SubtreeFormat {
+    Json,
+    Yaml,
+}
+
+/// How `plan export` writes a plan
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum PlanExportFormat {
+    /// The plan with its cursor, history and leases, for `plan import`
+    Json,
+    /// A Graphviz digraph of the task tree, colored by task status
+    Dot,
+}
+
+/// How `plan show` orders each task's subtasks
+#[derive(Clone, Copy, PartialEq, clap::ValueEnum)]
+enum TaskSort {
+    /// The order they were added in
+    Position,
+    /// Most recently changed first, so stalled work sinks to the bottom
+    Recent,
+}
+
+/// How `plan history export` prints the log
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum HistoryFormat {
+    /// One JSON record per line
+    Jsonl,
+    /// A single JSON array
+    Json,
+}
+
+#[derive(Subcommand)]
+enum HistoryCommands {
+    /// Print the current plan's full transition log, oldest first
+    Export {
+        /// Output format
+        #[arg(long, value_enum, default_value_t = HistoryFormat::Jsonl)]
+        format: HistoryFormat,
+    },
+}
+
+#[derive(Subcommand)]
+enum SessionCommands {
+    /// Start timing a working session, restarting any running one
+    Start {
+        /// Minutes between reminders to re-read the goal and update notes; 0 turns them off.
+        /// Kept for later sessions on the plan
+        #[arg(long)]
+        remind_every: Option<u32>,
+    },
+    /// Stop the running session and show how long it ran
+    Stop,
+    /// Show how long the running session has been going
+    Status,
+}
+
 #[derive(Subcommand)]
 enum TaskCommands {
     /// Add a new task
     Add {
         /// Task description
-        description: String,
+        #[arg(required_unless_present = "template")]
+        description: Option<String>,
 
         /// Level index (starting from 0, lower index = higher abstraction level)
-        #[arg(short, long)]
-        level: usize,
+        #[arg(short, long, required_unless_present = "template")]
+        level: Option<usize>,
 
         /// Optional notes for the task
-        #[arg(long)]
-        notes: String,
+        #[arg(long, required_unless_present = "template")]
+        notes: Option<String>,
+
+        /// Create the task from a server-side template (see `template list`)
+        #[arg(long, conflicts_with_all = ["description", "level", "notes"])]
+        template: Option<String>,
+
+        /// Record the plan goal, and where to find the parent task's notes, in the new
+        /// task's "context" notes section
+        #[arg(long, default_value_t = false, conflicts_with = "template")]
+        inherit_context: bool,
+
+        /// Template parameter as key=value; repeat for each placeholder
+        #[arg(
+            long = "param",
+            requires = "template",
+            conflicts_with_all = ["description", "level", "notes"],
+            value_parser = parse_param
+        )]
+        params: Vec<(String, String)>,
     },
 
     /// Complete the current task or the task at the specified index
     Complete {
-        /// Task index (e.g., 0 or 0,1,2 for nested tasks)
-        #[arg(short, long)]
-        index: String,
+        /// Task index (e.g., 0 or 0,1,2 for nested tasks) or ID (e.g., t-3f9a2c1b)
+        #[arg(short, long, required_unless_present = "current")]
+        index: Option<String>,
+
+        /// Complete the current task instead of passing its index
+        #[arg(long, conflicts_with = "index")]
+        current: bool,
 
         /// The lease required to complete the task
         #[arg(long)]
@@ -120,7 +419,15 @@ enum TaskCommands {
         #[arg(long, default_value_t = false)]
         force: bool,
 
-        /// Optional summary for completing the task
+        /// Also complete any incomplete subtasks
+        #[arg(long, conflicts_with = "no_cascade")]
+        cascade: bool,
+
+        /// Fail if any subtask is incomplete, whatever the plan's policy
+        #[arg(long)]
+        no_cascade: bool,
+
+        /// Optional summary for completing the task, or "-" to read it from stdin
         #[arg(long)]
         summary: Option<String>,
     },
@@ -133,22 +440,149 @@ enum TaskCommands {
         level_index: usize,
     },
 
+    /// Set how much a task counts toward plan progress (default 1)
+    Weight {
+        /// Task index (e.g., 0 or 0,1,2 for nested tasks) or ID (e.g., t-3f9a2c1b)
+        index: String,
+        /// The task's weight; omit to reset it to the default
+        weight: Option<u32>,
+    },
+
+    /// Set how urgent a task is, from 0 (most) to 9 (least)
+    #[command(name = "set-priority")]
+    SetPriority {
+        /// Task index (e.g., 0 or 0,1,2 for nested tasks) or ID (e.g., t-3f9a2c1b)
+        index: String,
+        /// The task's priority; omit to clear it
+        #[arg(value_parser = clap::value_parser!(u8).range(0..=9))]
+        priority: Option<u8>,
+    },
+
+    /// Set how many minutes of work a task is estimated to take, subtasks included
+    Estimate {
+        /// Task index (e.g., 0 or 0,1,2 for nested tasks) or ID (e.g., t-3f9a2c1b)
+        index: String,
+        /// Estimated minutes; omit to clear the estimate
+        minutes: Option<u32>,
+    },
+
+    /// Log minutes of work on a task
+    #[command(name = "log-time")]
+    LogTime {
+        /// Task index (e.g., 0 or 0,1,2 for nested tasks) or ID (e.g., t-3f9a2c1b)
+        index: String,
+        /// Minutes to add to the time logged on the task
+        minutes: u32,
+    },
+
     /// Generate a lease for the task at the given index
     Lease {
-        /// Task index (e.g., 0 or 0,1,2 for nested tasks)
+        /// Task index (e.g., 0 or 0,1,2 for nested tasks) or ID (e.g., t-3f9a2c1b)
+        index: String,
+
+        /// Reject the lease once it has completed the task
+        #[arg(long)]
+        single_use: bool,
+    },
+
+    /// Show a task's ID, current index and state, looked up by index or ID
+    Show {
+        /// Task index (e.g., 0 or 0,1,2 for nested tasks) or ID (e.g., t-3f9a2c1b)
         index: String,
     },
 
     /// Remove a task by its index
     Remove {
-        /// Task index (e.g., 0 or 0,1,2 for nested tasks)
+        /// Task index (e.g., 0 or 0,1,2 for nested tasks) or ID (e.g., t-3f9a2c1b)
+        index: String,
+    },
+
+    /// Replace a task's description, keeping its subtasks and notes
+    Edit {
+        /// Task index (e.g., 0 or 0,1,2 for nested tasks) or ID (e.g., t-3f9a2c1b)
+        index: String,
+        /// The new description
+        description: String,
+    },
+
+    /// Move a task, with its subtasks, under another task
+    Move {
+        /// Task index (e.g., 0 or 0,1,2 for nested tasks) or ID (e.g., t-3f9a2c1b)
+        index: String,
+        /// Index or ID of the new parent; the task becomes its last subtask
+        #[arg(long)]
+        parent: Option<String>,
+    },
+
+    /// Make a task wait for another: it can't be completed before it without --force
+    Depend {
+        /// Task index (e.g., 0 or 0,1,2 for nested tasks) or ID (e.g., t-3f9a2c1b)
+        index: String,
+        /// Index or ID of the task it depends on
+        on: String,
+        /// Drop the dependency instead
+        #[arg(long, default_value_t = false)]
+        remove: bool,
+    },
+
+    /// Mark a task as in progress
+    Start {
+        /// Task index (e.g., 0 or 0,1,2 for nested tasks) or ID (e.g., t-3f9a2c1b)
         index: String,
     },
 
     /// Uncomplete a task by its index
     Uncomplete {
-        /// Task index (e.g., 0 or 0,1,2 for nested tasks)
+        /// Task index (e.g., 0 or 0,1,2 for nested tasks) or ID (e.g., t-3f9a2c1b)
+        index: String,
+    },
+
+    /// Submit a task for review instead of completing it directly
+    Review {
+        /// Task index (e.g., 0 or 0,1,2 for nested tasks) or ID (e.g., t-3f9a2c1b)
+        index: String,
+
+        /// Summary of the work done, shown to the reviewer, or "-" to read it from stdin
+        #[arg(long)]
+        summary: String,
+    },
+
+    /// List the tasks awaiting review
+    #[command(name = "review-queue")]
+    ReviewQueue,
+
+    /// Approve a task awaiting review, marking it complete
+    Approve {
+        /// Task index (e.g., 0 or 0,1,2 for nested tasks) or ID (e.g., t-3f9a2c1b)
+        index: String,
+    },
+
+    /// Reject a task awaiting review, leaving it incomplete
+    Reject {
+        /// Task index (e.g., 0 or 0,1,2 for nested tasks) or ID (e.g., t-3f9a2c1b)
         index: String,
+
+        /// Optional reason for the rejection, shown to the agent
+        #[arg(long)]
+        reason: Option<String>,
+    },
+
+    /// Print the task at an index and everything below it, to copy into another plan
+    Export {
+        /// Task index (e.g., 0 or 0,1,2 for nested tasks) or ID (e.g., t-3f9a2c1b)
+        index: String,
+        /// Output format
+        #[arg(long, value_enum, default_value_t = SubtreeFormat::Json)]
+        format: SubtreeFormat,
+    },
+
+    /// Add a subtree written by `task export` as the last child of a task
+    Import {
+        /// Parent task index (e.g., 0 or 0,1) or ID; omit to import at the root
+        parent: Option<String>,
+        /// File holding the exported subtree (JSON or YAML), or "-" to read it from stdin
+        #[arg(long)]
+        file: String,
     },
 
     /// Manage notes for a specific task
@@ -156,6 +590,37 @@ enum TaskCommands {
         #[command(subcommand)]
         command: TaskNotesSubcommand,
     },
+
+    /// Tag tasks to slice a plan by concern (see `plan show --tag`)
+    Tag {
+        #[command(subcommand)]
+        command: TaskTagSubcommand,
+    },
+}
+
+#[derive(Subcommand)]
+enum TaskTagSubcommand {
+    /// Add tags to a task
+    Add {
+        /// Task index (e.g., 0 or 0,1,2 for nested tasks) or ID (e.g., t-3f9a2c1b)
+        index: String,
+        /// Tags to add, e.g. backend; they're lowercased and can't contain whitespace or commas
+        #[arg(required = true)]
+        tags: Vec<String>,
+    },
+    /// Remove tags from a task
+    Remove {
+        /// Task index (e.g., 0 or 0,1,2 for nested tasks) or ID (e.g., t-3f9a2c1b)
+        index: String,
+        /// Tags to remove
+        #[arg(required = true)]
+        tags: Vec<String>,
+    },
+    /// List a task's tags, or every tag in the plan with how many tasks have it
+    List {
+        /// Task index (e.g., 0 or 0,1,2 for nested tasks) or ID (e.g., t-3f9a2c1b)
+        index: Option<String>,
+    },
 }
 
 // Define TaskNotesSubcommand Enum
@@ -163,21 +628,132 @@ enum TaskCommands {
 enum TaskNotesSubcommand {
     /// View notes for a task
     View {
-        /// Task index (e.g., 0 or 0,1,2 for nested tasks)
+        /// Task index (e.g., 0 or 0,1,2 for nested tasks) or ID (e.g., t-3f9a2c1b)
         index: String,
+        /// Named notes section (e.g., "findings", "decisions") instead of the main notes
+        #[arg(long)]
+        section: Option<String>,
     },
     /// Set notes for a task
     Set {
-        /// Task index (e.g., 0 or 0,1,2 for nested tasks)
+        /// Task index (e.g., 0 or 0,1,2 for nested tasks) or ID (e.g., t-3f9a2c1b)
+        index: String,
+        /// The notes content, or "-" to read it from stdin
+        notes: String,
+        /// Named notes section (e.g., "findings", "decisions") instead of the main notes
+        #[arg(long)]
+        section: Option<String>,
+    },
+    /// Append a timestamped block to the notes for a task, keeping existing notes
+    Append {
+        /// Task index (e.g., 0 or 0,1,2 for nested tasks) or ID (e.g., t-3f9a2c1b)
         index: String,
-        /// The notes content
+        /// The text to append, or "-" to read it from stdin
         notes: String,
+        /// Named notes section (e.g., "findings", "decisions") instead of the main notes
+        #[arg(long)]
+        section: Option<String>,
     },
     /// Delete notes for a task
     Delete {
-        /// Task index (e.g., 0 or 0,1,2 for nested tasks)
+        /// Task index (e.g., 0 or 0,1,2 for nested tasks) or ID (e.g., t-3f9a2c1b)
+        index: String,
+        /// Named notes section (e.g., "findings", "decisions") instead of the main notes
+        #[arg(long)]
+        section: Option<String>,
+    },
+    /// Show previous versions of the notes for a task
+    History {
+        /// Task index (e.g., 0 or 0,1,2 for nested tasks) or ID (e.g., t-3f9a2c1b)
+        index: String,
+    },
+    /// Restore the notes for a task to a previous version
+    Revert {
+        /// Task index (e.g., 0 or 0,1,2 for nested tasks) or ID (e.g., t-3f9a2c1b)
         index: String,
+        /// The version number to restore (see `task notes history`)
+        #[arg(long)]
+        version: usize,
+    },
+}
+
+#[derive(Subcommand)]
+enum QuestionCommands {
+    /// Record a new open question
+    Add {
+        /// The question text
+        question: String,
+    },
+    /// List open and answered questions
+    List,
+    /// Answer a question, marking it answered
+    Answer {
+        /// The question ID (see `question list`)
+        id: usize,
+        /// The answer text
+        answer: String,
+    },
+    /// Remove a question
+    Remove {
+        /// The question ID (see `question list`)
+        id: usize,
+    },
+}
+
+#[derive(Subcommand)]
+enum TemplateCommands {
+    /// List the templates available on the server
+    List,
+    /// Create or replace a template; text may contain {param} placeholders
+    Add {
+        /// Template name, used with `task add --template`
+        name: String,
+        /// Task description pattern, e.g. "Add /{name} endpoint"
+        description: String,
+        /// Level index for tasks created from the template
+        #[arg(short, long)]
+        level: usize,
+        /// Notes skeleton for created tasks
+        #[arg(long)]
+        notes: Option<String>,
+        /// Checklist item; repeat for each item
+        #[arg(long = "checklist")]
+        checklist: Vec<String>,
+    },
+    /// Remove a template
+    Remove {
+        /// Template name
+        name: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum AdminCommands {
+    /// Release every lease on the current plan
+    Unlock,
+    /// Release the current plan's leases on missing or completed tasks
+    #[command(name = "gc-leases")]
+    GcLeases,
+    /// Drop the current plan's transition log and trim its notes histories
+    Compact {
+        /// Number of notes versions to keep per task
+        #[arg(long, default_value_t = 0)]
+        keep_note_versions: usize,
+    },
+    /// Export every plan on the server as a JSON archive
+    Export {
+        /// File to write the archive to (stdout when omitted)
+        file: Option<std::path::PathBuf>,
     },
+    /// Import a JSON archive, replacing plans with the same IDs
+    Import {
+        /// Archive file produced by `admin export`
+        file: std::path::PathBuf,
+    },
+    /// Make the server re-read its config file
+    Reload,
+    /// Show the server's quotas and how much of them each workspace's plans use
+    Quotas,
 }
 
 // Define PlanCommands Enum
@@ -187,52 +763,377 @@ enum PlanCommands {
     #[command(arg_required_else_help = true)] // Require the prompt argument
     Create {
         /// The initial high-level goal or prompt for the plan
-        #[arg(index = 1)]
-        prompt: String,
+        #[arg(index = 1, required_unless_present = "from_spec")]
+        prompt: Option<String>,
         /// Optional longer-form notes or description for the plan
         #[arg(long)] // Add the optional notes argument
         notes: Option<String>,
+        /// Create the plan, with its levels and tasks, from a YAML spec file instead
+        #[arg(long, value_name = "FILE", conflicts_with_all = ["prompt", "notes"])]
+        from_spec: Option<PathBuf>,
     },
     /// Delete a plan by its ID
     Delete {
         /// The ID (0-255) of the plan to delete
         id: u8,
     },
+    /// Export a plan with its cursor, history and leases, to move it to another server
+    Export {
+        /// The ID (0-255) of the plan to export
+        id: u8,
+        /// File to write the plan to (stdout when omitted)
+        #[arg(long)]
+        file: Option<PathBuf>,
+        /// Output format; only json can be imported again
+        #[arg(long, value_enum, default_value_t = PlanExportFormat::Json)]
+        format: PlanExportFormat,
+    },
+    /// Create a plan from a file written by `plan export`, under a new ID
+    Import {
+        /// File produced by `plan export`
+        #[arg(long)]
+        file: PathBuf,
+    },
     /// List all available plan IDs
     List,
-    /// Show the details of the current plan (tasks, levels)
-    Show,
+    /// Show the details of the current plan (tasks, levels), or a filtered slice of its tasks
+    Show {
+        /// Only show incomplete tasks
+        #[arg(long)]
+        incomplete_only: bool,
+        /// Only show tasks at this level index
+        #[arg(long)]
+        level: Option<usize>,
+        /// Only show tasks this many levels down (1 shows direct subtasks)
+        #[arg(long)]
+        depth: Option<usize>,
+        /// Only show tasks below this index (e.g., 0 or 0,2) or task ID
+        #[arg(long)]
+        subtree: Option<String>,
+        /// Only show tasks with this tag
+        #[arg(long)]
+        tag: Option<String>,
+        /// Show the plan as it was at this time: an RFC 3339 timestamp, or a duration ago
+        /// such as 90m, 12h or 2d
+        #[arg(long, value_name = "TIME", value_parser = parse_time,
+              conflicts_with_all = ["incomplete_only", "level", "depth", "subtree", "tag"])]
+        at: Option<chrono::DateTime<chrono::Utc>>,
+        /// Order of each task's subtasks
+        #[arg(long, value_enum, default_value_t = TaskSort::Position,
+              conflicts_with_all = ["incomplete_only", "level", "depth", "subtree", "tag"])]
+        sort: TaskSort,
+    },
+    /// Set the status of the current plan (draft, active, completed, abandoned)
+    #[command(name = "set-status")]
+    SetStatus {
+        /// The new status
+        status: PlanStatus,
+        /// Mark the plan completed even if some tasks are incomplete
+        #[arg(long, default_value_t = false)]
+        force: bool,
+    },
+    /// Make the current plan read-only; every change is rejected until it is unlocked
+    Lock,
+    /// Make a locked plan writable again
+    Unlock,
+    /// Make removed and moved tasks leave tombstones, so their siblings keep their indices
+    Tombstones {
+        /// Renumber siblings on removal again
+        #[arg(long, default_value_t = false)]
+        off: bool,
+    },
+    /// List the current plan's subtasks by position or by priority
+    Order {
+        /// position or priority
+        order: TaskOrder,
+    },
+    /// Share the current plan under a short join code that opens its web UI
+    Share {
+        /// Also print the join URL as a QR code for scanning from a phone
+        #[arg(long, default_value_t = false)]
+        qr: bool,
+        /// Stop sharing the plan; its join code no longer resolves
+        #[arg(long, default_value_t = false, conflicts_with = "qr")]
+        revoke: bool,
+    },
+    /// Check the current plan for inconsistencies, such as orphaned leases
+    Lint,
+    /// Print the tasks completed in a window of the current plan's history as markdown
+    /// release notes
+    Changelog {
+        /// Start of the window: an RFC 3339 timestamp, or a duration ago such as 90m, 12h
+        /// or 2d. Defaults to the plan's start
+        #[arg(long, value_name = "TIME", value_parser = parse_time)]
+        from: Option<chrono::DateTime<chrono::Utc>>,
+        /// End of the window, in the same forms. Defaults to now
+        #[arg(long, value_name = "TIME", value_parser = parse_time)]
+        to: Option<chrono::DateTime<chrono::Utc>>,
+    },
+    /// Show counts of the current plan's tasks by state and level, and recent completions
+    Stats,
+    /// Compare completed tasks' estimates with the time logged on them
+    Effort,
+    /// Print completion summaries grouped under each task at a level, as a change log
+    Summaries {
+        /// Level of the tasks to group summaries under
+        #[arg(long, default_value_t = DEFAULT_SUMMARY_LEVEL)]
+        level: usize,
+    },
+    /// Show the current plan's focus, or pin work to a task (e.g., "0,1")
+    Focus {
+        /// The task to focus on; omit to show the current focus
+        #[arg(conflicts_with = "clear")]
+        index: Option<String>,
+        /// Reject work outside the focused task instead of warning about it
+        #[arg(long, default_value_t = false, requires = "index")]
+        strict: bool,
+        /// Remove the focus
+        #[arg(long, default_value_t = false)]
+        clear: bool,
+    },
+    /// Time a working session on the current plan, with reminders to step back
+    Session {
+        #[command(subcommand)]
+        command: SessionCommands,
+    },
+    /// Work with the current plan's transition history
+    History {
+        #[command(subcommand)]
+        command: HistoryCommands,
+    },
+    /// Replace the current plan's goal or notes
+    Edit {
+        /// New goal for the plan
+        #[arg(long, required_unless_present = "notes")]
+        goal: Option<String>,
+        /// New notes for the plan; "-" reads them from stdin, and "" clears them
+        #[arg(long)]
+        notes: Option<String>,
+    },
+    /// Show the usage summary in the current plan's context, or replace it
+    #[command(name = "usage-summary")]
+    UsageSummary {
+        /// Summary shown to agents working on this plan instead of the server's
+        #[arg(conflicts_with = "clear")]
+        text: Option<String>,
+        /// Go back to the server's usage summary
+        #[arg(long, default_value_t = false)]
+        clear: bool,
+    },
+    /// Show the color and emoji the current plan is listed with, or change them
+    Badge {
+        /// Color to mark the plan with: red, orange, yellow, green, teal, blue, purple,
+        /// pink or gray
+        #[arg(long)]
+        color: Option<PlanColor>,
+        /// Emoji to show before the plan's ID; "" removes it
+        #[arg(long)]
+        emoji: Option<String>,
+        /// Remove the color and emoji before applying the other options
+        #[arg(long, default_value_t = false)]
+        clear: bool,
+    },
+    /// Show the current plan's completion policy, or change it
+    Policy {
+        /// Whether completing a task also completes its incomplete subtasks by default
+        #[arg(long)]
+        cascade: Option<bool>,
+        /// Whether only tasks without subtasks may be completed, with parents completed
+        /// once all their subtasks are
+        #[arg(long)]
+        leaf_only: Option<bool>,
+        /// Fewest characters a completion summary may have (0 disables the check)
+        #[arg(long, value_name = "N")]
+        summary_min_chars: Option<usize>,
+        /// Keyword or section, such as "Tested:", every completion summary must mention;
+        /// repeat for several, or pass "" to require none
+        #[arg(long, value_name = "TEXT")]
+        summary_require: Option<Vec<String>>,
+        /// Validator, defined in the server config, every completion must pass; repeat
+        /// for several, or pass "" to require none
+        #[arg(long, value_name = "NAME")]
+        validator: Option<Vec<String>>,
+        /// Status moves tasks may make: "kanban", "any" to allow every move, or a list
+        /// such as "todo>in_progress,in_progress>done"
+        #[arg(long, value_name = "RULES", value_parser = parse_transitions)]
+        transitions: Option<TransitionRules>,
+    },
+}
+
+/// Classes of failure, each exiting with its own code so scripts and agent harnesses can
+/// branch on the outcome without parsing stderr. Invalid command lines exit with clap's
+/// usage code, 2.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Failure {
+    /// Any failure not covered below
+    Other = 1,
+    /// The plan, task, question or template doesn't exist
+    NotFound = 3,
+    /// The task is leased, and no lease or another lease was given
+    Lease = 4,
+    /// The input was rejected, e.g. a malformed index or a level the task can't take
+    Invalid = 5,
+    /// The plan or task is in the wrong state, e.g. completing a completed task
+    Conflict = 6,
+    /// The server couldn't be reached
+    Network = 7,
+    /// A quota on the number or size of plans would be exceeded
+    Quota = 8,
+}
+
+impl Failure {
+    /// Classifies an error returned by [`run`]
+    pub fn of(error: &(dyn std::error::Error + 'static)) -> Self {
+        if let Some(error) = error.downcast_ref::<ClientError>() {
+            return match error {
+                ClientError::Request(_) => Self::Network,
+                ClientError::PlanNotFound(_) => Self::NotFound,
+                ClientError::Task(e) => Self::of_task(e),
+                ClientError::Http { status, .. } => match status.as_u16() {
+                    404 => Self::NotFound,
+                    400 | 413 | 422 => Self::Invalid,
+                    409 => Self::Conflict,
+                    _ => Self::Other,
+                },
+                ClientError::Api(_) | ClientError::Serialization(_) | ClientError::Internal(_) => {
+                    Self::Other
+                }
+            };
+        }
+        if let Some(error) = error.downcast_ref::<PlanError>() {
+            return match error {
+                PlanError::PlanNotFound(_)
+                | PlanError::TemplateNotFound(_)
+                | PlanError::RevisionNotFound { .. } => Self::NotFound,
+                PlanError::Task(e) => Self::of_task(e),
+                PlanError::InvalidTemplate(_)
+                | PlanError::InvalidSpec(_)
+                | PlanError::InvalidPlugin(_)
+                | PlanError::InvalidWindow { .. } => Self::Invalid,
+                PlanError::LockError | PlanError::Internal(_) | PlanError::Journal(_) => {
+                    Self::Other
+                }
+            };
+        }
+        if error.is::<std::num::ParseIntError>()
+            || error.is::<InvalidTaskRef>()
+            || error.is::<serde_yaml::Error>()
+        {
+            return Self::Invalid;
+        }
+        Self::Other
+    }
+
+    fn of_task(error: &TaskError) -> Self {
+        match error {
+            TaskError::OutOfBounds { .. }
+            | TaskError::UnknownTaskId { .. }
+            | TaskError::QuestionNotFound { .. }
+            | TaskError::NotesVersionNotFound { .. } => Self::NotFound,
+            TaskError::LeaseRequired { .. }
+            | TaskError::LeaseMismatch { .. }
+            | TaskError::LeaseSpent { .. }
+            | TaskError::LeaseSessionMismatch { .. } => Self::Lease,
+            TaskError::LevelViolation(_)
+            | TaskError::RootTask
+            | TaskError::MoveIntoSubtree { .. }
+            | TaskError::DependencyCycle { .. }
+            | TaskError::InvalidPriority { .. }
+            | TaskError::InvalidTag { .. }
+            | TaskError::SummaryRequired { .. }
+            | TaskError::SummaryRejected { .. }
+            | TaskError::UnknownValidator { .. }
+            | TaskError::EmptySectionName
+            | TaskError::InvalidEmoji { .. } => Self::Invalid,
+            TaskError::PlanArchived
+            | TaskError::PlanLocked
+            | TaskError::NoCurrentTask
+            | TaskError::AlreadyCompleted { .. }
+            | TaskError::NotCompleted { .. }
+            | TaskError::NotAwaitingReview { .. }
+            | TaskError::ReviewPending { .. }
+            | TaskError::TransitionNotAllowed { .. }
+            | TaskError::ChangedDuringValidation { .. }
+            | TaskError::IncompleteTasks
+            | TaskError::IncompleteSubtasks { .. }
+            | TaskError::BlockedByDependencies { .. }
+            | TaskError::NotLeaf { .. }
+            | TaskError::CompletionRejected { .. }
+            | TaskError::OutsideFocus { .. } => Self::Conflict,
+            TaskError::QuotaExceeded { .. } => Self::Quota,
+        }
+    }
+
+    pub fn exit_code(self) -> std::process::ExitCode {
+        std::process::ExitCode::from(self as u8)
+    }
 }
 
 /// Run the CLI application
 pub async fn run() -> Result<(), Box<dyn std::error::Error>> {
-    let cli = Cli::parse();
+    let config = CliConfig::load()?;
+    let cli = Cli::parse_from(expand_aliases(
+        std::env::args_os().collect(),
+        &config.aliases,
+    ));
+
+    if cli.no_color || std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty()) {
+        colored::control::set_override(false);
+    }
 
-    if !matches!(&cli.command, Commands::Mcp { .. }) {
+    if !matches!(&cli.command, Commands::Mcp { .. } | Commands::Rpc { .. }) {
         // Initialize tracing, only for non-MCP commands
-        // as MCP expects clean stdout
+        // as MCP and RPC expect clean stdout
         tracing_subscriber::fmt::init();
     }
 
     match &cli.command {
-        Commands::Serve { port, example } => {
-            tracing::info!("Starting scatterbrain API server on port {port}");
+        Commands::Serve {
+            config: config_path,
+            port,
+            example,
+            webhook_url,
+            storage_path,
+            auth_token,
+            cors_origins,
+            metrics,
+            no_compression,
+            sse_keep_alive_secs,
+        } => {
+            // Load the config file, then let explicitly passed flags override it. The
+            // source is kept so SIGHUP or /api/admin/reload can re-read it later.
+            let source = ConfigSource {
+                path: config_path.clone(),
+                overrides: ConfigOverrides {
+                    port: *port,
+                    example: example.example,
+                    example_size: example.example_size,
+                    example_seed: example.example_seed,
+                    webhook_url: webhook_url.clone(),
+                    storage_path: storage_path.clone(),
+                    auth_token: auth_token.clone(),
+                    cors_origins: cors_origins.clone(),
+                    metrics: *metrics,
+                    no_compression: *no_compression,
+                    sse_keep_alive_secs: *sse_keep_alive_secs,
+                },
+            };
+            let config = source.load()?;
+
+            tracing::info!("Starting scatterbrain API server on {}", config.address);
 
-            // Core::new() now initializes the default plan
             let core = Core::new();
-            // Add example tasks if requested (needs adjustment if Core API changes)
-            if *example {
-                tracing::info!("Populating with example task tree for UI testing");
-                create_example_tasks(&core);
+            core.set_interface(GuideMode::Http)?;
+            if let Some(levels) = config.plan_defaults.levels.clone() {
+                core.set_default_levels(levels)?;
+            }
+            if let Some(example) = config.plan_defaults.example_config() {
+                tracing::info!("Populating with a {} example task tree", example.size);
+                examples::populate(&core, &DEFAULT_PLAN_ID, &example)?;
             }
-
-            // Create a server configuration with the specified port
-            let config = ServerConfig {
-                address: ([127, 0, 0, 1], *port).into(),
-            };
 
             // Start the API server
-            serve(core, config).await?;
+            serve(core, config, Some(source)).await?;
             Ok(())
         }
 
@@ -241,107 +1142,164 @@ pub async fn run() -> Result<(), Box<dyn std::error::Error>> {
 
             // Core::new() now initializes the default plan
             let core = Core::new();
+            core.set_interface(GuideMode::Mcp)?;
 
-            // Add example tasks if requested
-            if *example {
-                tracing::info!("Populating with example task tree for testing");
-                // Create a default plan first
-                match core.create_plan(
+            if let Some(example) = example.config() {
+                tracing::info!("Populating with a {} example task tree", example.size);
+                let plan_id = core.create_plan(
                     "Example MCP Plan".to_string(),
                     Some("Example plan for testing MCP server functionality".to_string()),
-                ) {
-                    Ok(plan_id) => {
-                        create_example_tasks_for_plan(&core, &plan_id);
-                    }
-                    Err(e) => {
-                        tracing::error!("Error creating default plan: {e}");
-                    }
-                }
+                )?;
+                examples::populate(&core, &plan_id, &example)?;
             }
 
-            // Create the MCP server
-            let mcp_server = ScatterbrainMcpServer::new(core.clone());
-
-            // If expose flag is provided, start HTTP server concurrently
-            if let Some(port) = expose {
-                tracing::info!("Also exposing HTTP API server on port {port}");
-
-                // Create server configuration
-                let config = ServerConfig {
-                    address: ([127, 0, 0, 1], *port).into(),
-                };
-
-                // Start both servers concurrently
-                use rmcp::{transport::io::stdio, ServiceExt};
-                let mcp_service = mcp_server.serve(stdio());
-                let http_server = serve(core, config);
-
-                tracing::info!("MCP server started with HTTP API exposed on port {port}. Waiting for connections");
-
-                // Run both servers concurrently
-                let mcp_handle = tokio::spawn(async move {
-                    match mcp_service.await {
-                        Ok(service) => service
-                            .waiting()
-                            .await
-                            .map(|_| ())
-                            .map_err(|e| format!("MCP service error: {e}")),
-                        Err(e) => Err(format!("MCP server error: {e}")),
-                    }
-                });
-
-                let http_handle = tokio::spawn(async move {
-                    http_server
-                        .await
-                        .map_err(|e| format!("HTTP server error: {e}"))
-                });
-
-                // Wait for either server to complete (or fail)
-                tokio::select! {
-                    result = mcp_handle => {
-                        match result {
-                            Ok(Ok(())) => tracing::info!("MCP server completed successfully"),
-                            Ok(Err(e)) => tracing::error!("MCP server error: {e}"),
-                            Err(e) => tracing::error!("MCP server task error: {e}"),
-                        }
-                    }
-                    result = http_handle => {
-                        match result {
-                            Ok(Ok(())) => tracing::info!("HTTP server completed successfully"),
-                            Ok(Err(e)) => tracing::error!("HTTP server error: {e}"),
-                            Err(e) => tracing::error!("HTTP server task error: {e}"),
-                        }
-                    }
-                }
+            // Serve MCP over stdio, with the HTTP API alongside if asked, until the client
+            // closes stdin or the process is told to stop
+            let options = ServiceOptions {
+                http: expose.map(|port| ServerConfig {
+                    address: ([127, 0, 0, 1], port).into(),
+                    ..Default::default()
+                }),
+            };
+            let reason = run_services(core, options).await?;
+
+            // Stopped by a signal, the stdin reader is still blocked on a read that can't be
+            // interrupted, and the runtime would wait for it before exiting
+            if reason == rmcp::service::QuitReason::Cancelled {
+                std::process::exit(0);
+            }
+            Ok(())
+        }
+
+        Commands::Rpc { example, remote } => {
+            let input = tokio::io::BufReader::new(tokio::io::stdin());
+            let output = tokio::io::stdout();
+            if *remote {
+                rpc::serve_rpc(&create_client(&cli), input, output).await?;
             } else {
-                // Start only the MCP server with stdio transport
-                use rmcp::{transport::io::stdio, ServiceExt};
-                let service = mcp_server.serve(stdio()).await?;
+                let core = Core::new();
+                if let Some(example) = example.config() {
+                    let plan_id = core.create_plan(
+                        "Example RPC Plan".to_string(),
+                        Some("Example plan for testing the RPC mode".to_string()),
+                    )?;
+                    examples::populate(&core, &plan_id, &example)?;
+                }
+                let client = ScatterbrainMcpServer::new(core);
+                rpc::serve_rpc(&client, input, output).await?;
+            }
+            Ok(())
+        }
 
-                tracing::info!("MCP server started. Waiting for connections");
-                service.waiting().await?;
+        Commands::Bench {
+            plans,
+            depth,
+            fanout,
+            operations,
+            threads,
+            mix,
+            seed,
+            json,
+        } => {
+            let config = BenchConfig {
+                plans: *plans,
+                depth: *depth,
+                fanout: *fanout,
+                operations: *operations,
+                threads: *threads,
+                mix: mix.clone().unwrap_or_default(),
+                seed: *seed,
+            };
+            let report = tokio::task::spawn_blocking(move || run_load(&config)).await??;
+            if *json {
+                println!("{}", serde_json::to_string_pretty(&report)?);
+            } else {
+                print_bench_report(&report);
             }
             Ok(())
         }
 
         Commands::Task { command } => {
-            let client = create_client(&cli.server);
+            let client = create_client(&cli);
             let id = get_plan_id(&cli)?; // id is PlanId
 
             let result = match command {
+                TaskCommands::Add {
+                    template: Some(template),
+                    params,
+                    ..
+                } => {
+                    let params = params.iter().cloned().collect();
+                    let response = client
+                        .add_task_from_template(id.value(), template.clone(), params)
+                        .await?;
+                    print_response(&response, |(task, index)| {
+                        println!(
+                            "Added task: \"{}\" from template '{template}' at index: {index:?} (ID {})",
+                            task.description(),
+                            task.id()
+                        );
+                        println!("View: {}", task_link(&cli, id.value(), index));
+                    });
+                    Ok(())
+                }
+
                 TaskCommands::Add {
                     description,
                     level,
                     notes,
+                    inherit_context,
+                    ..
                 } => {
-                    // Pass id.value() and notes.clone() to client method
+                    // clap requires these whenever --template is absent
+                    let description = description.clone().unwrap_or_default();
+                    let level = level.unwrap_or_default();
                     let response = client
-                        .add_task(id.value(), description.clone(), *level, Some(notes.clone()))
+                        .add_task(id.value(), description.clone(), level, notes.clone())
                         .await?;
-                    let (_task, index) = response.inner();
+                    let (task, index) = response.inner();
                     println!(
-                        "Added task: \"{description}\" with level {level} at index: {index:?}"
+                        "Added task: \"{description}\" with level {level} at index: {index:?} (ID {})",
+                        task.id()
                     );
+                    let inherited = match inherit_context {
+                        true => inherited_context(&response.distilled_context, index),
+                        false => None,
+                    };
+                    if let Some(context) = inherited {
+                        client
+                            .set_note_section(
+                                id.value(),
+                                index.clone(),
+                                INHERITED_CONTEXT_SECTION.to_string(),
+                                Some(context),
+                                false,
+                            )
+                            .await?;
+                        println!("Recorded the plan's context in its '{INHERITED_CONTEXT_SECTION}' notes section");
+                    }
+                    println!("View: {}", task_link(&cli, id.value(), index));
+                    Ok(())
+                }
+
+                TaskCommands::Complete {
+                    current: true,
+                    lease,
+                    force,
+                    cascade,
+                    no_cascade,
+                    summary,
+                    ..
+                } => {
+                    let cascade = cascade_choice(*cascade, *no_cascade);
+                    let summary = read_text_opt(summary)?;
+                    let response = client
+                        .complete_current_task(id.value(), *lease, *force, cascade, summary)
+                        .await?;
+                    print_response(&response, |index| {
+                        println!("Completed current task at index: {index:?}");
+                        println!("View: {}", task_link(&cli, id.value(), index));
+                    });
                     Ok(())
                 }
 
@@ -349,16 +1307,15 @@ pub async fn run() -> Result<(), Box<dyn std::error::Error>> {
                     index,
                     lease,
                     force,
+                    cascade,
+                    no_cascade,
                     summary,
+                    ..
                 } => {
+                    // clap requires --index whenever --current is absent
+                    let index = index.as_deref().unwrap_or_default();
                     // Determine the target index
-                    let target_index = match parse_index(index) {
-                        Ok(idx) => idx,
-                        Err(e) => {
-                            tracing::error!("Error parsing index: {e}");
-                            return Err(e);
-                        }
-                    };
+                    let target_index = resolve_index(&client, id, index).await?;
 
                     // Pass id.value() and lease (Option<u8>) to client method
                     let response = client
@@ -367,21 +1324,19 @@ pub async fn run() -> Result<(), Box<dyn std::error::Error>> {
                             target_index.clone(),
                             *lease,
                             *force,
-                            summary.clone(),
+                            cascade_choice(*cascade, *no_cascade),
+                            read_text_opt(summary)?,
                         )
                         .await?;
 
-                    print_response(&response, |success| {
-                        if *success {
-                            let index_display = target_index
-                                .iter()
-                                .map(|i| i.to_string())
-                                .collect::<Vec<_>>()
-                                .join(",");
-                            println!("Completed task at index: [{index_display}]");
-                        } else {
-                            println!("Failed to complete task (lease mismatch? already complete? check server logs)");
-                        }
+                    print_response(&response, |_| {
+                        let index_display = target_index
+                            .iter()
+                            .map(|i| i.to_string())
+                            .collect::<Vec<_>>()
+                            .join(",");
+                        println!("Completed task at index: [{index_display}]");
+                        println!("View: {}", task_link(&cli, id.value(), &target_index));
                     });
                     Ok(())
                 }
@@ -402,10 +1357,59 @@ pub async fn run() -> Result<(), Box<dyn std::error::Error>> {
                     Ok(())
                 }
 
-                TaskCommands::Lease { index } => {
-                    let parsed_index = parse_index(index)?;
+                TaskCommands::Weight { index, weight } => {
+                    let parsed_index = resolve_index(&client, id, index).await?;
+                    let response = client
+                        .set_task_weight(id.value(), parsed_index, *weight)
+                        .await?;
+                    print_response(&response, |_| match weight {
+                        Some(weight) => println!("Set weight of task {index} to {weight}"),
+                        None => println!("Reset weight of task {index}"),
+                    });
+                    Ok(())
+                }
+
+                TaskCommands::SetPriority { index, priority } => {
+                    let parsed_index = resolve_index(&client, id, index).await?;
+                    let response = client
+                        .set_task_priority(id.value(), parsed_index, *priority)
+                        .await?;
+                    print_response(&response, |_| match priority {
+                        Some(priority) => println!("Set priority of task {index} to {priority}"),
+                        None => println!("Cleared priority of task {index}"),
+                    });
+                    Ok(())
+                }
+
+                TaskCommands::Estimate { index, minutes } => {
+                    let parsed_index = resolve_index(&client, id, index).await?;
+                    let response = client
+                        .set_task_estimate(id.value(), parsed_index, *minutes)
+                        .await?;
+                    print_response(&response, |_| match minutes {
+                        Some(minutes) => println!("Estimated task {index} at {minutes} minutes"),
+                        None => println!("Cleared estimate of task {index}"),
+                    });
+                    Ok(())
+                }
+
+                TaskCommands::LogTime { index, minutes } => {
+                    let parsed_index = resolve_index(&client, id, index).await?;
+                    let response = client
+                        .log_task_time(id.value(), parsed_index, *minutes)
+                        .await?;
+                    print_response(&response, |total| {
+                        println!("Logged {minutes} minutes on task {index}, {total} in total")
+                    });
+                    Ok(())
+                }
+
+                TaskCommands::Lease { index, single_use } => {
+                    let parsed_index = resolve_index(&client, id, index).await?;
                     // Pass id.value() to client method
-                    let response = client.generate_lease(id.value(), parsed_index).await?;
+                    let response = client
+                        .generate_lease(id.value(), parsed_index, *single_use)
+                        .await?;
                     let (lease, suggestions) = response.inner();
                     println!(
                         // Use lease.value() for printing
@@ -422,101 +1426,401 @@ pub async fn run() -> Result<(), Box<dyn std::error::Error>> {
                     Ok(())
                 }
 
+                TaskCommands::Show { index } => {
+                    let task = index.parse::<TaskRef>()?;
+                    let response = client.get_task(id.value(), task).await?;
+                    print_response(&response, |(task, index)| {
+                        let status = if task.is_completed() {
+                            "completed"
+                        } else if task.is_awaiting_review() {
+                            "awaiting review"
+                        } else {
+                            "open"
+                        };
+                        println!("{}", task.description());
+                        println!("  ID: {}", task.id());
+                        println!("  Index: {}", format_index(index));
+                        println!("  Status: {status}");
+                        if let Some(level) = task.level_index() {
+                            println!("  Level: {level}");
+                        }
+                        if !task.tags().is_empty() {
+                            println!("  Tags: {}", format_tags(task.tags()));
+                        }
+                        println!("View: {}", task_link(&cli, id.value(), index));
+                    });
+                    Ok(())
+                }
+
                 TaskCommands::Remove { index } => {
-                    let parsed_index = parse_index(index)?;
+                    let parsed_index = resolve_index(&client, id, index).await?;
                     // Pass id.value() to client method
-                    match client.remove_task(id.value(), parsed_index).await {
-                        Ok(response) => {
-                            // Handle the nested Result<Task, String>
-                            print_response(&response, |result| match result {
-                                Ok(removed_task) => println!(
-                                    "Removed task: \"{}\" at index: {}",
-                                    removed_task.description(),
-                                    index // Use original string for display
-                                ),
-                                Err(e) => {
-                                    tracing::error!(
-                                        "Server error removing task at index {index}: {e}"
-                                    )
-                                }
-                            });
+                    let response = client.remove_task(id.value(), parsed_index).await?;
+                    print_response(&response, |removed_task| {
+                        println!(
+                            "Removed task: \"{}\" at index: {}",
+                            removed_task.description(),
+                            index // Use original string for display
+                        )
+                    });
+                    Ok(())
+                }
+
+                TaskCommands::Edit { index, description } => {
+                    let parsed_index = resolve_index(&client, id, index).await?;
+                    let response = client
+                        .update_task_description(id.value(), parsed_index, description.clone())
+                        .await?;
+                    print_response(&response, |task| {
+                        println!("Updated task {index}: \"{}\"", task.description())
+                    });
+                    Ok(())
+                }
+
+                TaskCommands::Move { index, parent } => {
+                    let parsed_index = resolve_index(&client, id, index).await?;
+                    let parent = match parent {
+                        Some(parent) => resolve_index(&client, id, parent).await?,
+                        None => Vec::new(),
+                    };
+                    let response = client.move_task(id.value(), parsed_index, parent).await?;
+                    print_response(&response, |new_index| {
+                        println!("Moved task {index} to index: {}", format_index(new_index));
+                        println!("View: {}", task_link(&cli, id.value(), new_index));
+                    });
+                    Ok(())
+                }
+
+                TaskCommands::Depend { index, on, remove } => {
+                    let parsed_index = resolve_index(&client, id, index).await?;
+                    let parsed_on = resolve_index(&client, id, on).await?;
+                    let response = if *remove {
+                        client
+                            .remove_dependency(id.value(), parsed_index, parsed_on)
+                            .await?
+                    } else {
+                        client
+                            .add_dependency(id.value(), parsed_index, parsed_on)
+                            .await?
+                    };
+                    print_response(&response, |changed| match (*remove, *changed) {
+                        (false, true) => println!("Task {index} now depends on {on}"),
+                        (false, false) => println!("Task {index} already depends on {on}"),
+                        (true, true) => println!("Task {index} no longer depends on {on}"),
+                        (true, false) => println!("Task {index} didn't depend on {on}"),
+                    });
+                    Ok(())
+                }
+
+                TaskCommands::Tag { command } => match command {
+                    TaskTagSubcommand::Add { index, tags } => {
+                        let parsed_index = resolve_index(&client, id, index).await?;
+                        let response = client
+                            .tag_task(id.value(), parsed_index, tags.clone())
+                            .await?;
+                        print_response(&response, |tags| {
+                            println!("Task {index} is tagged {}", format_tags(tags))
+                        });
+                        Ok(())
+                    }
+                    TaskTagSubcommand::Remove { index, tags } => {
+                        let parsed_index = resolve_index(&client, id, index).await?;
+                        let response = client
+                            .untag_task(id.value(), parsed_index, tags.clone())
+                            .await?;
+                        print_response(&response, |tags| match tags.is_empty() {
+                            true => println!("Task {index} has no tags left"),
+                            false => println!("Task {index} is tagged {}", format_tags(tags)),
+                        });
+                        Ok(())
+                    }
+                    TaskTagSubcommand::List { index: Some(index) } => {
+                        let task = index.parse::<TaskRef>()?;
+                        let response = client.get_task(id.value(), task).await?;
+                        let (task, _) = response.inner();
+                        match task.tags().is_empty() {
+                            true => println!("Task {index} has no tags"),
+                            false => println!("{}", format_tags(task.tags())),
                         }
-                        Err(e) => {
-                            tracing::error!("Client error removing task: {e}");
+                        Ok(())
+                    }
+                    TaskTagSubcommand::List { index: None } => {
+                        let response = client.get_plan(id.value()).await?;
+                        let counts = tag_counts(response.inner().root());
+                        if counts.is_empty() {
+                            println!("No tasks are tagged yet.");
                         }
-                    };
+                        for (tag, count) in counts {
+                            let tasks = if count == 1 { "task" } else { "tasks" };
+                            println!("#{tag}  {count} {tasks}");
+                        }
+                        Ok(())
+                    }
+                },
+
+                TaskCommands::Start { index } => {
+                    let parsed_index = resolve_index(&client, id, index).await?;
+                    let response = client.start_task(id.value(), parsed_index).await?;
+                    print_response(&response, |_| println!("Started task at index: {index}"));
                     Ok(())
                 }
 
                 TaskCommands::Uncomplete { index } => {
-                    let parsed_index = parse_index(index)?;
+                    let parsed_index = resolve_index(&client, id, index).await?;
                     // Pass id.value() to client method
-                    match client.uncomplete_task(id.value(), parsed_index).await {
-                        Ok(response) => {
-                            print_response(&response, |result| match result {
-                                Ok(true) => println!("Uncompleted task at index: {index}"),
-                                Ok(false) => {
-                                    println!("Task at index {index} was already incomplete.")
-                                }
-                                Err(e) => tracing::error!(
-                                    "Server error uncompleting task at index {index}: {e}"
-                                ),
-                            });
+                    let response = client.uncomplete_task(id.value(), parsed_index).await?;
+                    print_response(&response, |_| {
+                        println!("Uncompleted task at index: {index}")
+                    });
+                    Ok(())
+                }
+
+                TaskCommands::Review { index, summary } => {
+                    let parsed_index = resolve_index(&client, id, index).await?;
+                    let response = client
+                        .request_review(id.value(), parsed_index, read_text(summary)?)
+                        .await?;
+                    print_response(&response, |_| {
+                        println!("Task at index {index} submitted for review.")
+                    });
+                    Ok(())
+                }
+
+                TaskCommands::ReviewQueue => {
+                    let response = client.get_review_queue(id.value()).await?;
+                    print_response(&response, |items| {
+                        if items.is_empty() {
+                            println!("No tasks awaiting review.");
                         }
-                        Err(e) => {
-                            tracing::error!("Client error uncompleting task: {e}");
+                        for item in items {
+                            println!("[{}] {}", format_index(&item.index), item.description);
+                            if let Some(summary) = &item.summary {
+                                println!("  Summary: {summary}");
+                            }
+                            if let Some(notes) = &item.notes {
+                                println!("  Notes: {}", notes.replace('\n', "\n         "));
+                            }
+                        }
+                    });
+                    Ok(())
+                }
+
+                TaskCommands::Approve { index } => {
+                    let parsed_index = resolve_index(&client, id, index).await?;
+                    let response = client.approve_task(id.value(), parsed_index).await?;
+                    print_response(&response, |_| {
+                        println!("Approved and completed task at index: {index}")
+                    });
+                    Ok(())
+                }
+
+                TaskCommands::Reject { index, reason } => {
+                    let parsed_index = resolve_index(&client, id, index).await?;
+                    let response = client
+                        .reject_task(id.value(), parsed_index, reason.clone())
+                        .await?;
+                    print_response(&response, |_| {
+                        println!("Rejected review for task at index: {index}")
+                    });
+                    Ok(())
+                }
+
+                TaskCommands::Export { index, format } => {
+                    let parsed_index = resolve_index(&client, id, index).await?;
+                    let subtree = client
+                        .export_subtree(id.value(), parsed_index)
+                        .await?
+                        .into_inner();
+                    match format {
+                        SubtreeFormat::Json => {
+                            println!("{}", serde_json::to_string_pretty(&subtree)?)
                         }
+                        SubtreeFormat::Yaml => print!("{}", serde_yaml::to_string(&subtree)?),
+                    }
+                    Ok(())
+                }
+
+                TaskCommands::Import { parent, file } => {
+                    let parent = match parent {
+                        Some(parent) => resolve_index(&client, id, parent).await?,
+                        None => Vec::new(),
                     };
+                    let contents = match file.as_str() {
+                        "-" => read_text(file)?,
+                        path => std::fs::read_to_string(path)?,
+                    };
+                    let subtree: TaskSpec = serde_yaml::from_str(&contents)?;
+                    let response = client.import_subtree(id.value(), parent, subtree).await?;
+                    print_response(&response, |index| {
+                        println!("Imported subtree at index: {}", format_index(index))
+                    });
                     Ok(())
                 }
 
                 TaskCommands::Notes { command } => {
                     match command {
-                        TaskNotesSubcommand::View { index } => {
-                            let parsed_index = parse_index(index)?;
+                        TaskNotesSubcommand::View {
+                            index,
+                            section: None,
+                        } => {
+                            let parsed_index = resolve_index(&client, id, index).await?;
                             // Call client.get_task_notes directly
-                            match client.get_task_notes(id.value(), parsed_index).await {
-                                Ok(notes_opt) => {
-                                    if let Some(notes) = notes_opt {
-                                        println!("Notes for task at index {index}:\n{notes}");
-                                    } else {
-                                        println!("No notes found for task at index {index}.");
-                                    }
-                                }
-                                Err(e) => {
-                                    tracing::error!("Error getting notes: {e}");
+                            match client.get_task_notes(id.value(), parsed_index).await? {
+                                Some(notes) => {
+                                    println!("Notes for task at index {index}:\n{notes}")
                                 }
+                                None => println!("No notes found for task at index {index}."),
+                            }
+                            Ok(())
+                        }
+                        TaskNotesSubcommand::View {
+                            index,
+                            section: Some(section),
+                        } => {
+                            let parsed_index = resolve_index(&client, id, index).await?;
+                            let sections = client
+                                .get_note_sections(id.value(), parsed_index, Some(section.clone()))
+                                .await?;
+                            match sections.values().next() {
+                                Some(content) => println!(
+                                    "Notes section '{section}' for task at index {index}:\n{content}"
+                                ),
+                                None => println!(
+                                    "No notes section '{section}' found for task at index {index}."
+                                ),
                             }
                             Ok(())
                         }
-                        TaskNotesSubcommand::Set { index, notes } => {
-                            let parsed_index = parse_index(index)?;
+                        TaskNotesSubcommand::Set {
+                            index,
+                            notes,
+                            section: Some(section),
+                        }
+                        | TaskNotesSubcommand::Append {
+                            index,
+                            notes,
+                            section: Some(section),
+                        } => {
+                            let append = matches!(command, TaskNotesSubcommand::Append { .. });
+                            let parsed_index = resolve_index(&client, id, index).await?;
                             let response = client
-                                .set_task_notes(id.value(), parsed_index, notes.clone())
+                                .set_note_section(
+                                    id.value(),
+                                    parsed_index,
+                                    section.clone(),
+                                    Some(read_text(notes)?),
+                                    append,
+                                )
                                 .await?;
-                            // Handle the Result<(), String> within PlanResponse
-                            print_response(&response, |res| match res {
-                                Ok(_) => {
-                                    println!("Notes for task at index {index} set successfully.")
-                                }
-                                Err(e) => {
-                                    tracing::error!("Error setting notes for task {index}: {e}")
-                                }
+                            print_response(&response, |_| {
+                                println!(
+                                    "Notes section '{section}' for task at index {index} updated successfully."
+                                )
+                            });
+                            Ok(())
+                        }
+                        TaskNotesSubcommand::Delete {
+                            index,
+                            section: Some(section),
+                        } => {
+                            let parsed_index = resolve_index(&client, id, index).await?;
+                            let response = client
+                                .set_note_section(
+                                    id.value(),
+                                    parsed_index,
+                                    section.clone(),
+                                    None,
+                                    false,
+                                )
+                                .await?;
+                            print_response(&response, |_| {
+                                println!(
+                                    "Notes section '{section}' for task at index {index} deleted successfully."
+                                )
+                            });
+                            Ok(())
+                        }
+                        TaskNotesSubcommand::Set {
+                            index,
+                            notes,
+                            section: None,
+                        } => {
+                            let parsed_index = resolve_index(&client, id, index).await?;
+                            let response = client
+                                .set_task_notes(id.value(), parsed_index, read_text(notes)?, false)
+                                .await?;
+                            print_response(&response, |_| {
+                                println!("Notes for task at index {index} set successfully.")
+                            });
+                            Ok(())
+                        }
+                        TaskNotesSubcommand::Append {
+                            index,
+                            notes,
+                            section: None,
+                        } => {
+                            let parsed_index = resolve_index(&client, id, index).await?;
+                            let response = client
+                                .set_task_notes(id.value(), parsed_index, read_text(notes)?, true)
+                                .await?;
+                            print_response(&response, |_| {
+                                println!("Notes for task at index {index} appended successfully.")
                             });
                             Ok(())
                         }
-                        TaskNotesSubcommand::Delete { index } => {
-                            let parsed_index = parse_index(index)?;
+                        TaskNotesSubcommand::Delete {
+                            index,
+                            section: None,
+                        } => {
+                            let parsed_index = resolve_index(&client, id, index).await?;
                             let response =
                                 client.delete_task_notes(id.value(), parsed_index).await?;
-                            // Handle the Result<(), String> within PlanResponse
-                            print_response(&response, |res| match res {
-                                Ok(_) => println!(
-                                    "Notes for task at index {index} deleted successfully."
-                                ),
-                                Err(e) => {
-                                    tracing::error!("Error deleting notes for task {index}: {e}")
+                            print_response(&response, |_| {
+                                println!("Notes for task at index {index} deleted successfully.")
+                            });
+                            Ok(())
+                        }
+                        TaskNotesSubcommand::History { index } => {
+                            let parsed_index = resolve_index(&client, id, index).await?;
+                            let history =
+                                client.get_notes_history(id.value(), parsed_index).await?;
+                            let mut output = Vec::new();
+                            if history.is_empty() {
+                                writeln!(
+                                    output,
+                                    "No previous notes versions for task at index {index}."
+                                )?;
+                            } else {
+                                writeln!(output, "Notes history for task at index {index}:")?;
+                                for version in history {
+                                    writeln!(
+                                        output,
+                                        "\n  Version {} (replaced {}):",
+                                        version.version,
+                                        version.timestamp.format("%Y-%m-%d %H:%M:%S UTC")
+                                    )?;
+                                    match version.notes {
+                                        Some(notes) => writeln!(
+                                            output,
+                                            "    {}",
+                                            notes.replace('\n', "\n    ")
+                                        )?,
+                                        None => writeln!(output, "    (no notes)")?,
+                                    }
                                 }
+                            }
+                            page(&output, cli.no_pager)?;
+                            Ok(())
+                        }
+                        TaskNotesSubcommand::Revert { index, version } => {
+                            let parsed_index = resolve_index(&client, id, index).await?;
+                            let response = client
+                                .revert_task_notes(id.value(), parsed_index, *version)
+                                .await?;
+                            print_response(&response, |_| {
+                                println!(
+                                    "Notes for task at index {index} reverted to version {version}."
+                                )
                             });
                             Ok(())
                         }
@@ -527,36 +1831,49 @@ pub async fn run() -> Result<(), Box<dyn std::error::Error>> {
         }
 
         Commands::Move { index } => {
-            let client = create_client(&cli.server);
+            let client = create_client(&cli);
             let id = get_plan_id(&cli)?; // id is PlanId
-            let parsed_index = parse_index(index)?;
+            let parsed_index = resolve_index(&client, id, index).await?;
 
             // Pass id.value() to client method
             let response = client.move_to(id.value(), parsed_index).await?;
-            print_response(&response, |description: &Option<String>| {
-                println!(
-                    "Moved to task: \"{}\" at index: {}",
-                    description.as_deref().unwrap_or("Unknown"),
-                    index
-                );
+            print_response(&response, |description: &String| {
+                println!("Moved to task: \"{description}\" at index: {index}");
             });
             Ok(())
         }
 
         Commands::Current => {
-            let client = create_client(&cli.server);
+            let client = create_client(&cli);
             let id = get_plan_id(&cli)?; // id is PlanId
             let response = client.get_current(id.value()).await?;
             print_response(&response, |current: &Option<Current>| {
                 if let Some(current) = current {
                     println!("Current Task for Plan ID: {}", id.value()); // Use id.value() for display
+                    if !current.history.is_empty() {
+                        println!("  Path: {}", current.history.join(" > "));
+                    }
                     println!("  Description: {}", current.task.description());
                     println!("  Completed: {}", current.task.is_completed());
                     println!("  Level: {}", current.level.description());
                     println!("  Index: {:?}", current.index);
-                    if !current.task.subtasks().is_empty() {
+                    if let Some(notes) = &current.parent_notes {
+                        println!("\nParent Notes:\n  {}", notes.replace('\n', "\n  "));
+                    }
+                    if !current.siblings.is_empty() {
+                        println!("\nSiblings:");
+                        for sibling in &current.siblings {
+                            println!(
+                                "  [{}] {} (completed: {})",
+                                format_index(&sibling.index),
+                                sibling.description,
+                                sibling.completed
+                            );
+                        }
+                    }
+                    if current.task.live_subtasks().next().is_some() {
                         println!("\nSubtasks:");
-                        for (i, subtask) in current.task.subtasks().iter().enumerate() {
+                        for (i, subtask) in current.task.live_subtasks() {
                             println!(
                                 "  {}. {} (completed: {})",
                                 i,
@@ -572,23 +1889,83 @@ pub async fn run() -> Result<(), Box<dyn std::error::Error>> {
             Ok(())
         }
 
-        Commands::Distilled => {
-            let client = create_client(&cli.server);
+        Commands::Status { short } => {
+            let client = create_client(&cli);
+            let id = get_plan_id(&cli)?;
+            let progress = client.get_progress(id.value()).await?;
+            if *short {
+                println!("{}", format_status_line(id.value(), &progress));
+            } else {
+                println!("Plan {} ({})", id.value(), progress.status);
+                match &progress.description {
+                    Some(description) => println!(
+                        "  Current: [{}] {description}",
+                        format_index(&progress.index)
+                    ),
+                    None => println!("  Current: root"),
+                }
+                println!(
+                    "  Progress: {}/{} tasks completed",
+                    progress.completed, progress.total
+                );
+            }
+            Ok(())
+        }
+
+        Commands::Distilled { sections, compact } => {
+            let client = create_client(&cli);
             let id = get_plan_id(&cli)?; // id is PlanId
-            let response = client.get_distilled_context(id.value()).await?;
-            print_distilled_context_response(&response);
+            let mut response = client.get_distilled_context(id.value()).await?;
+            if !sections.is_empty() {
+                response.distilled_context.retain_note_sections(sections);
+            }
+            if *compact {
+                print!("{}", response.distilled_context.to_compact());
+                return Ok(());
+            }
+            write_distilled_context_response(&mut io::stdout(), &response)?;
+            Ok(())
+        }
+
+        Commands::Search { query } => {
+            let client = create_client(&cli);
+            let id = get_plan_id(&cli)?;
+            let query = query.join(" ");
+            let response = client.search_tasks(id.value(), query.clone()).await?;
+            print_response(&response, |hits: &Vec<SearchHit>| {
+                if hits.is_empty() {
+                    println!("No tasks match \"{query}\".");
+                }
+                for hit in hits {
+                    println!("{}  {}", format_index(&hit.index), hit.description);
+                    let label = match &hit.field {
+                        MatchField::Description => continue,
+                        MatchField::Notes => "notes",
+                        MatchField::Section(section) => section.as_str(),
+                        MatchField::Summary => "summary",
+                    };
+                    println!("    {}: {}", label.dimmed(), hit.snippet);
+                }
+            });
             Ok(())
         }
 
         Commands::Guide => {
-            print_guide();
+            print_guide()?;
             // Attempt to get context for default plan ID 0 using the flag logic
             let default_id_cli = Cli {
                 command: Commands::Guide, // Placeholder
                 server: cli.server.clone(),
                 plan: Some(0), // Specify default ID 0
+                token: cli.token.clone(),
+                session: cli.session.clone(),
+                agent: cli.agent.clone(),
+                workspace: cli.workspace.clone(),
+                fallback_servers: cli.fallback_servers.clone(),
+                no_color: cli.no_color,
+                no_pager: cli.no_pager,
             };
-            let client = create_client(&cli.server);
+            let client = create_client(&cli);
             match get_plan_id(&default_id_cli) {
                 // Use the modified cli struct with PlanId
                 Ok(default_id) => {
@@ -599,7 +1976,10 @@ pub async fn run() -> Result<(), Box<dyn std::error::Error>> {
                                 "\nDistilled Context for Default Plan (ID {}):\n",
                                 default_id.value()
                             );
-                            print_distilled_context_response(&distilled_response);
+                            write_distilled_context_response(
+                                &mut io::stdout(),
+                                &distilled_response,
+                            )?;
                         }
                         Err(e) => {
                             tracing::warn!(
@@ -622,65 +2002,680 @@ pub async fn run() -> Result<(), Box<dyn std::error::Error>> {
             Ok(())
         }
 
-        Commands::PlanCmd(plan_command) => {
-            let client = create_client(&cli.server);
-            match plan_command {
-                PlanCommands::Create { prompt, notes } => {
-                    // Pass the prompt and notes to the updated client method
-                    match client.create_plan(prompt.clone(), notes.clone()).await {
-                        Ok(lease) => {
-                            let new_id = lease.value(); // lease is PlanId
-                            println!("Created new plan with ID: {new_id}");
-                            println!(
-                                "\nIMPORTANT: Set the environment variable to work with this plan:"
-                            );
-                            println!("  export {PLAN_ID_ENV_VAR}={new_id}");
-                            println!("\n---");
-                            println!("You will update scatterbrain after each point of progress or new discovery. This includes initial surveying of relevant code, formulating a plan of attack, successful additions, realizations that you've hit a dead end, etc. Do not stray from this path; it is important to use the plan to drive your efforts and keep it as an auditable record. This will also help you discover patterns in your approach and improve your ability to reason about the problem space.");
-                            println!("Do not stop unless you have questions you can't reasonably answer yourself. Use your best judgment, do not ask for my approval. Continue using scatterbrain to refine and adjust your approach and move forward with solving our goal.");
-                            println!("---\n");
-                            print_guide(); // Print the full guide
-                        }
-                        Err(e) => tracing::error!("Error creating plan: {e}"),
-                    }
-                    Ok(())
+        Commands::Question { command } => {
+            let client = create_client(&cli);
+            let id = get_plan_id(&cli)?; // id is PlanId
+
+            match command {
+                QuestionCommands::Add { question } => {
+                    let response = client.add_question(id.value(), question.clone()).await?;
+                    print_response(&response, |q| {
+                        println!("Added question {}: {}", q.id, q.question)
+                    });
                 }
-                PlanCommands::Delete { id } => {
-                    // id is u8, convert to PlanId
-                    let _plan_id_to_delete = PlanId::new(*id);
-                    // Pass the u8 value *id* to client.delete_plan
-                    match client.delete_plan(*id).await {
-                        Ok(_) => println!("Deleted plan with ID: {id}"),
-                        Err(ClientError::PlanNotFound(_)) => {
-                            tracing::error!("Plan with ID '{id}' not found")
-                        }
-                        Err(e) => tracing::error!("Error deleting plan '{id}': {e}"),
+                QuestionCommands::List => {
+                    let response = client.list_questions(id.value()).await?;
+                    let questions = response.inner();
+                    if questions.is_empty() {
+                        println!("No questions tracked for this plan.");
+                    } else {
+                        write_questions(&mut io::stdout(), questions)?;
                     }
-                    Ok(())
                 }
-                PlanCommands::List => {
-                    match client.list_plans().await {
-                        Ok(ids) => {
-                            println!("Available plan IDs:");
-                            if ids.is_empty() {
-                                println!("  (No plans found - use 'plan create' to start)");
-                            } else {
-                                for lease in ids {
-                                    // lease is PlanId
-                                    println!("  - {}", lease.value());
-                                }
-                            }
-                        }
-                        Err(e) => tracing::error!("Error listing plans: {e}"),
+                QuestionCommands::Answer {
+                    id: question_id,
+                    answer,
+                } => {
+                    let response = client
+                        .answer_question(id.value(), *question_id, answer.clone())
+                        .await?;
+                    print_response(&response, |q| {
+                        println!("Answered question {}: {}", q.id, q.question)
+                    });
+                }
+                QuestionCommands::Remove { id: question_id } => {
+                    let response = client.remove_question(id.value(), *question_id).await?;
+                    print_response(&response, |q| {
+                        println!("Removed question {}: {}", q.id, q.question)
+                    });
+                }
+            }
+            Ok(())
+        }
+
+        Commands::Template { command } => {
+            let client = create_client(&cli);
+            match command {
+                TemplateCommands::List => {
+                    let templates = client.list_templates().await?;
+                    if templates.is_empty() {
+                        println!("No templates defined.");
+                    }
+                    for template in templates {
+                        println!(
+                            "{} (level {}): {}",
+                            template.name.bold(),
+                            template.level_index,
+                            template.description
+                        );
+                        for item in &template.checklist {
+                            println!("    - [ ] {item}");
+                        }
+                    }
+                }
+                TemplateCommands::Add {
+                    name,
+                    description,
+                    level,
+                    notes,
+                    checklist,
+                } => {
+                    let template = TaskTemplate {
+                        name: name.clone(),
+                        description: description.clone(),
+                        level_index: *level,
+                        notes: notes.clone(),
+                        checklist: checklist.clone(),
+                    };
+                    let params = template.parameters();
+                    client.save_template(template).await?;
+                    if params.is_empty() {
+                        println!("Saved template '{name}'");
+                    } else {
+                        let params: Vec<_> = params.into_iter().collect();
+                        println!(
+                            "Saved template '{name}' with parameters: {}",
+                            params.join(", ")
+                        );
+                    }
+                }
+                TemplateCommands::Remove { name } => {
+                    let template = client.remove_template(name.clone()).await?;
+                    println!("Removed template '{}'", template.name);
+                }
+            }
+            Ok(())
+        }
+
+        Commands::Admin { command } => {
+            let client = create_client(&cli);
+            match command {
+                AdminCommands::Unlock => {
+                    let id = get_plan_id(&cli)?;
+                    let response = client.force_unlock(id.value()).await?;
+                    print_response(&response, |released| {
+                        println!("Released {released} leases on plan {}", id.value())
+                    });
+                }
+                AdminCommands::GcLeases => {
+                    let id = get_plan_id(&cli)?;
+                    let response = client.clear_orphaned_leases(id.value()).await?;
+                    print_response(&response, |cleared| {
+                        println!(
+                            "Released {} orphaned leases on plan {}",
+                            cleared.len(),
+                            id.value()
+                        );
+                        for index in cleared {
+                            println!("  {}", format_index(index));
+                        }
+                    });
+                }
+                AdminCommands::Compact { keep_note_versions } => {
+                    let id = get_plan_id(&cli)?;
+                    let response = client
+                        .compact_history(id.value(), *keep_note_versions)
+                        .await?;
+                    print_response(&response, |compaction| {
+                        println!(
+                            "Removed {} transitions and {} note versions from plan {}",
+                            compaction.transitions_removed,
+                            compaction.note_versions_removed,
+                            id.value()
+                        )
+                    });
+                }
+                AdminCommands::Export { file } => {
+                    let archive = client.export_plans().await?;
+                    let json = serde_json::to_string_pretty(&archive)?;
+                    match file {
+                        Some(path) => {
+                            std::fs::write(path, json)?;
+                            println!(
+                                "Exported {} plans to {}",
+                                archive.plans.len(),
+                                path.display()
+                            );
+                        }
+                        None => println!("{json}"),
+                    }
+                }
+                AdminCommands::Import { file } => {
+                    let archive = storage::read_backup(file)?;
+                    let imported = client.import_plans(&archive).await?;
+                    println!("Imported {imported} plans from {}", file.display());
+                }
+                AdminCommands::Quotas => {
+                    let overview = client.quotas().await?;
+                    print_quota_report("default", &overview.default);
+                    for (name, report) in &overview.workspaces {
+                        print_quota_report(name, report);
+                    }
+                }
+                AdminCommands::Reload => {
+                    let report = client.reload_config().await?;
+                    if report.applied.is_empty() {
+                        println!("Config reloaded, nothing changed");
+                    } else {
+                        println!("Applied: {}", report.applied.join(", "));
+                    }
+                    if !report.requires_restart.is_empty() {
+                        println!(
+                            "{}",
+                            format!("Requires restart: {}", report.requires_restart.join(", "))
+                                .yellow()
+                        );
+                    }
+                }
+            }
+            Ok(())
+        }
+
+        Commands::Backup { file } => {
+            let client = create_client(&cli);
+            let archive = client.export_plans().await?;
+            storage::write_backup(&archive, file)?;
+            println!(
+                "Backed up {} plans to {}",
+                archive.plans.len(),
+                file.display()
+            );
+            Ok(())
+        }
+
+        Commands::Restore { file } => {
+            let client = create_client(&cli);
+            let archive = storage::read_backup(file)?;
+            let restored = client.import_plans(&archive).await?;
+            println!(
+                "Restored {restored} plans from {} (backed up {})",
+                file.display(),
+                archive.exported_at.format("%Y-%m-%d %H:%M UTC")
+            );
+            Ok(())
+        }
+
+        Commands::PlanCmd(plan_command) => {
+            let client = create_client(&cli);
+            match plan_command {
+                PlanCommands::Create {
+                    prompt,
+                    notes,
+                    from_spec,
+                } => {
+                    let created = match (from_spec, prompt) {
+                        (Some(path), _) => {
+                            let spec = PlanSpec::from_yaml(&std::fs::read_to_string(path)?)?;
+                            client.create_plan_from_spec(spec).await
+                        }
+                        // Pass the prompt and notes to the updated client method
+                        (None, Some(prompt)) => {
+                            client.create_plan(prompt.clone(), notes.clone()).await
+                        }
+                        (None, None) => unreachable!("clap requires a prompt without --from-spec"),
+                    };
+                    match created {
+                        Ok(lease) => {
+                            let new_id = lease.value(); // lease is PlanId
+                            println!("Created new plan with ID: {new_id}");
+                            println!(
+                                "\nIMPORTANT: Set the environment variable to work with this plan:"
+                            );
+                            println!("  export {PLAN_ID_ENV_VAR}={new_id}");
+                            println!("\n---");
+                            println!("You will update scatterbrain after each point of progress or new discovery. This includes initial surveying of relevant code, formulating a plan of attack, successful additions, realizations that you've hit a dead end, etc. Do not stray from this path; it is important to use the plan to drive your efforts and keep it as an auditable record. This will also help you discover patterns in your approach and improve your ability to reason about the problem space.");
+                            println!("Do not stop unless you have questions you can't reasonably answer yourself. Use your best judgment, do not ask for my approval. Continue using scatterbrain to refine and adjust your approach and move forward with solving our goal.");
+                            println!("---\n");
+                            print_guide()?; // Print the full guide
+                        }
+                        Err(e) => return Err(e.into()),
                     }
                     Ok(())
                 }
-                PlanCommands::Show => {
+                PlanCommands::Export { id, file, format } => {
+                    let output = match format {
+                        PlanExportFormat::Json => {
+                            serde_json::to_string_pretty(&client.export_plan(*id).await?)?
+                        }
+                        PlanExportFormat::Dot => client.get_plan(*id).await?.into_inner().to_dot(),
+                    };
+                    match file {
+                        Some(path) => {
+                            std::fs::write(path, output)?;
+                            println!("Exported plan {id} to {}", path.display());
+                        }
+                        None => println!("{}", output.trim_end()),
+                    }
+                    Ok(())
+                }
+                PlanCommands::Import { file } => {
+                    let export: storage::PlanExport =
+                        schema::from_str(&std::fs::read_to_string(file)?)?;
+                    let new_id = client.import_plan(&export).await?.value();
+                    println!(
+                        "Imported plan {} from {} as plan {new_id}",
+                        export.id,
+                        file.display()
+                    );
+                    println!("  export {PLAN_ID_ENV_VAR}={new_id}");
+                    Ok(())
+                }
+                PlanCommands::Delete { id } => {
+                    // id is u8, convert to PlanId
+                    let _plan_id_to_delete = PlanId::new(*id);
+                    // Pass the u8 value *id* to client.delete_plan
+                    client.delete_plan(*id).await?;
+                    println!("Deleted plan with ID: {id}");
+                    Ok(())
+                }
+                PlanCommands::List => {
+                    match client.list_plan_summaries().await {
+                        Ok(summaries) => {
+                            println!("Available plan IDs:");
+                            if summaries.is_empty() {
+                                println!("  (No plans found - use 'plan create' to start)");
+                            } else {
+                                for summary in summaries {
+                                    let goal = summary.goal.as_deref().unwrap_or("");
+                                    let locked = if summary.locked { " [locked]" } else { "" };
+                                    let label = plan_label(summary.id.value(), &summary.badge);
+                                    match (summary.status, summary.completed_at) {
+                                        (PlanStatus::Completed, Some(completed_at)) => println!(
+                                            "  - {} {} {}{}",
+                                            label,
+                                            goal.dimmed(),
+                                            format!(
+                                                "[completed {}]",
+                                                completed_at.format("%Y-%m-%d %H:%M UTC")
+                                            )
+                                            .green(),
+                                            locked.red()
+                                        ),
+                                        (PlanStatus::Abandoned | PlanStatus::Draft, _) => println!(
+                                            "  - {} {} {}{}",
+                                            label,
+                                            goal.dimmed(),
+                                            format!("[{}]", summary.status).yellow(),
+                                            locked.red()
+                                        ),
+                                        _ => println!("  - {} {}{}", label, goal, locked.red()),
+                                    }
+                                }
+                            }
+                        }
+                        Err(e) => return Err(e.into()),
+                    }
+                    Ok(())
+                }
+                PlanCommands::Show {
+                    incomplete_only: false,
+                    level: None,
+                    depth: None,
+                    subtree: None,
+                    tag: None,
+                    at,
+                    sort,
+                } => {
                     // Handler for Show
-                    let client = create_client(&cli.server);
+                    let client = create_client(&cli);
                     let id = get_plan_id(&cli)?; // id is PlanId
-                    let response = client.get_plan(id.value()).await?;
-                    print_plan_response(&response);
+                    let response = match at {
+                        Some(at) => client.get_plan_at(id.value(), *at).await?,
+                        None => client.get_plan(id.value()).await?,
+                    };
+                    let mut output = Vec::new();
+                    write_plan_response(&mut output, &response, *sort)?;
+                    page(&output, cli.no_pager)?;
+                    Ok(())
+                }
+                PlanCommands::Show {
+                    incomplete_only,
+                    level,
+                    depth,
+                    subtree,
+                    tag,
+                    at: _,
+                    sort: _,
+                } => {
+                    let id = get_plan_id(&cli)?;
+                    let filter = crate::models::TaskFilter {
+                        incomplete_only: *incomplete_only,
+                        level: *level,
+                        depth: *depth,
+                        subtree: match subtree {
+                            Some(subtree) => resolve_index(&client, id, subtree).await?,
+                            None => Vec::new(),
+                        },
+                        tag: tag.clone(),
+                    };
+                    let base_depth = filter.subtree.len();
+                    let response = client.list_tasks(id.value(), filter).await?;
+                    let mut output = Vec::new();
+                    if response.inner().is_empty() {
+                        writeln!(output, "No matching tasks.")?;
+                    }
+                    let width = terminal_width();
+                    for task in response.inner() {
+                        write_task_listing(&mut output, task, base_depth, width)?;
+                    }
+                    write_response_footer(&mut output, &response)?;
+                    page(&output, cli.no_pager)?;
+                    Ok(())
+                }
+                PlanCommands::SetStatus { status, force } => {
+                    let id = get_plan_id(&cli)?;
+                    let response = client.set_plan_status(id.value(), *status, *force).await?;
+                    print_response(&response, |_| {
+                        println!("Plan {} status set to {status}", id.value())
+                    });
+                    Ok(())
+                }
+                PlanCommands::Tombstones { off } => {
+                    let id = get_plan_id(&cli)?;
+                    let response = client.set_tombstones(id.value(), !off).await?;
+                    print_response(&response, |changed| {
+                        let state = if *off { "off" } else { "on" };
+                        if *changed {
+                            println!("Tombstones turned {state} for plan {}", id.value());
+                        } else {
+                            println!("Tombstones were already {state} for plan {}", id.value());
+                        }
+                    });
+                    Ok(())
+                }
+                PlanCommands::Order { order } => {
+                    let id = get_plan_id(&cli)?;
+                    let response = client.set_task_order(id.value(), *order).await?;
+                    print_response(&response, |changed| {
+                        if *changed {
+                            println!("Plan {} now lists tasks by {order}", id.value());
+                        } else {
+                            println!("Plan {} already lists tasks by {order}", id.value());
+                        }
+                    });
+                    Ok(())
+                }
+                PlanCommands::Lock | PlanCommands::Unlock => {
+                    let id = get_plan_id(&cli)?;
+                    let locked = matches!(plan_command, PlanCommands::Lock);
+                    let response = client.set_plan_locked(id.value(), locked).await?;
+                    print_response(&response, |changed| {
+                        let state = if locked { "locked" } else { "unlocked" };
+                        if *changed {
+                            println!("Plan {} {state}", id.value());
+                        } else {
+                            println!("Plan {} was already {state}", id.value());
+                        }
+                    });
+                    Ok(())
+                }
+                PlanCommands::Share { qr, revoke } => {
+                    let id = get_plan_id(&cli)?;
+                    if *revoke {
+                        let response = client.unshare_plan(id.value()).await?;
+                        print_response(&response, |changed| {
+                            if *changed {
+                                println!("Plan {} is no longer shared", id.value());
+                            } else {
+                                println!("Plan {} was not shared", id.value());
+                            }
+                        });
+                        return Ok(());
+                    }
+                    let response = client.share_plan(id.value()).await?;
+                    print_response(&response, |code| {
+                        let url =
+                            format!("{}{}", cli.server.trim_end_matches('/'), join_path(code));
+                        println!("Join code: {code}");
+                        println!("URL: {url}");
+                        if *qr {
+                            match qrcode::QrCode::new(url.as_bytes()) {
+                                Ok(qr) => println!(
+                                    "\n{}",
+                                    qr.render::<qrcode::render::unicode::Dense1x2>()
+                                        .quiet_zone(true)
+                                        .build()
+                                ),
+                                Err(e) => eprintln!("Could not render a QR code: {e}"),
+                            }
+                        }
+                    });
+                    Ok(())
+                }
+                PlanCommands::Changelog { from, to } => {
+                    let id = get_plan_id(&cli)?;
+                    let response = client.get_changelog(id.value(), *from, *to).await?;
+                    print_response(&response, |changelog| print!("{}", changelog.to_markdown()));
+                    Ok(())
+                }
+                PlanCommands::Stats => {
+                    let id = get_plan_id(&cli)?;
+                    let stats = client.get_plan_stats(id.value()).await?;
+                    print_stats(id.value(), &stats);
+                    Ok(())
+                }
+                PlanCommands::Effort => {
+                    let id = get_plan_id(&cli)?;
+                    let report = client.get_effort_report(id.value()).await?;
+                    print_effort_report(id.value(), &report);
+                    Ok(())
+                }
+                PlanCommands::Summaries { level } => {
+                    let id = get_plan_id(&cli)?;
+                    let response = client.get_summaries(id.value(), *level).await?;
+                    print_response(&response, |groups| {
+                        if groups.is_empty() {
+                            println!("No completed work under level-{level} tasks yet");
+                        }
+                        for (i, group) in groups.iter().enumerate() {
+                            if i > 0 {
+                                println!();
+                            }
+                            let done = if group.completed { " (done)" } else { "" };
+                            println!(
+                                "## [{}] {}{done}",
+                                format_index(&group.index),
+                                group.description
+                            );
+                            for item in &group.summaries {
+                                println!(
+                                    "- [{}] {}: {}",
+                                    format_index(&item.index),
+                                    item.description,
+                                    item.summary.replace('\n', "\n  ")
+                                );
+                            }
+                        }
+                    });
+                    Ok(())
+                }
+                PlanCommands::Lint => {
+                    let id = get_plan_id(&cli)?;
+                    let response = client.lint_plan(id.value()).await?;
+                    print_response(&response, |lint| {
+                        if lint.is_clean() {
+                            println!("No problems found in plan {}", id.value());
+                        }
+                        for index in &lint.orphaned_leases {
+                            println!(
+                                "Orphaned lease on {}: the task there is missing or completed",
+                                format_index(index)
+                            );
+                        }
+                    });
+                    Ok(())
+                }
+                PlanCommands::Focus {
+                    index,
+                    strict,
+                    clear,
+                } => {
+                    let id = get_plan_id(&cli)?;
+                    if index.is_none() && !*clear {
+                        let response = client.get_distilled_context(id.value()).await?;
+                        match &response.distilled_context.focus {
+                            Some(focus) => println!("{}", describe_focus(focus)),
+                            None => println!("Plan {} has no focus", id.value()),
+                        }
+                        return Ok(());
+                    }
+                    let parsed_index = match index {
+                        Some(index) => Some(resolve_index(&client, id, index).await?),
+                        None => None,
+                    };
+                    let response = client.set_focus(id.value(), parsed_index, *strict).await?;
+                    print_response(&response, |focus| match focus {
+                        Some(focus) => println!("{}", describe_focus(focus)),
+                        None => println!("Focus cleared"),
+                    });
+                    Ok(())
+                }
+                PlanCommands::Session { command } => {
+                    let id = get_plan_id(&cli)?;
+                    match command {
+                        SessionCommands::Start { remind_every } => {
+                            let response = client.start_session(id.value(), *remind_every).await?;
+                            print_response(&response, |status| {
+                                println!("Session started on plan {}", id.value());
+                                println!("{}", describe_session(status));
+                            });
+                        }
+                        SessionCommands::Stop => {
+                            let response = client.stop_session(id.value()).await?;
+                            print_response(&response, |status| match status {
+                                Some(status) => println!(
+                                    "Session stopped after {} minutes",
+                                    status.elapsed_minutes
+                                ),
+                                None => println!("No session was running on plan {}", id.value()),
+                            });
+                        }
+                        SessionCommands::Status => {
+                            let response = client.get_distilled_context(id.value()).await?;
+                            match &response.distilled_context.session {
+                                Some(status) => println!("{}", describe_session(status)),
+                                None => println!("No session is running on plan {}", id.value()),
+                            }
+                        }
+                    }
+                    Ok(())
+                }
+                PlanCommands::History {
+                    command: HistoryCommands::Export { format },
+                } => {
+                    let id = get_plan_id(&cli)?;
+                    let records = client.get_history(id.value()).await?;
+                    let mut output = io::stdout().lock();
+                    match format {
+                        HistoryFormat::Jsonl => {
+                            for record in &records {
+                                writeln!(output, "{}", serde_json::to_string(record)?)?;
+                            }
+                        }
+                        HistoryFormat::Json => {
+                            writeln!(output, "{}", serde_json::to_string_pretty(&records)?)?
+                        }
+                    }
+                    Ok(())
+                }
+                PlanCommands::Edit { goal, notes } => {
+                    let id = get_plan_id(&cli)?;
+                    let response = client
+                        .update_plan_metadata(id.value(), goal.clone(), read_text_opt(notes)?)
+                        .await?;
+                    print_response(&response, |_| {
+                        println!("Updated plan {}", id.value());
+                        if let Some(goal) = goal {
+                            println!("  Goal: {goal}");
+                        }
+                    });
+                    Ok(())
+                }
+                PlanCommands::UsageSummary { text, clear } => {
+                    let id = get_plan_id(&cli)?;
+                    if text.is_none() && !*clear {
+                        let context = client.get_distilled_context(id.value()).await?.context();
+                        println!("{}", context.usage_summary);
+                        return Ok(());
+                    }
+                    let response = client.set_usage_summary(id.value(), text.clone()).await?;
+                    print_response(&response, |_| match text {
+                        Some(_) => println!("Usage summary of plan {} set", id.value()),
+                        None => println!("Plan {} uses the server's usage summary", id.value()),
+                    });
+                    Ok(())
+                }
+                PlanCommands::Badge {
+                    color,
+                    emoji,
+                    clear,
+                } => {
+                    let id = get_plan_id(&cli)?;
+                    let plan = client.get_plan(id.value()).await?;
+                    let current = plan.inner().badge().clone();
+                    let mut badge = if *clear {
+                        PlanBadge::default()
+                    } else {
+                        current.clone()
+                    };
+                    badge.color = color.or(badge.color);
+                    if let Some(emoji) = emoji {
+                        badge.emoji = Some(emoji.clone()).filter(|emoji| !emoji.is_empty());
+                    }
+                    if badge != current {
+                        let response = client.set_plan_badge(id.value(), badge).await?;
+                        print_response(&response, |badge| print_badge(id.value(), badge));
+                    } else {
+                        print_badge(id.value(), &badge);
+                    }
+                    Ok(())
+                }
+                PlanCommands::Policy {
+                    cascade,
+                    leaf_only,
+                    summary_min_chars,
+                    summary_require,
+                    validator,
+                    transitions,
+                } => {
+                    let id = get_plan_id(&cli)?;
+                    let plan = client.get_plan(id.value()).await?;
+                    let current = plan.inner().completion_policy().clone();
+                    let mut policy = current.clone();
+                    policy.cascade = cascade.unwrap_or(policy.cascade);
+                    policy.leaf_only = leaf_only.unwrap_or(policy.leaf_only);
+                    policy.summary.min_chars =
+                        summary_min_chars.unwrap_or(policy.summary.min_chars);
+                    if let Some(required) = summary_require {
+                        policy.summary.required =
+                            required.iter().filter(|k| !k.is_empty()).cloned().collect();
+                    }
+                    if let Some(validators) = validator {
+                        policy.validators = validators
+                            .iter()
+                            .filter(|v| !v.is_empty())
+                            .cloned()
+                            .collect();
+                    }
+                    if let Some(transitions) = transitions {
+                        policy.transitions = transitions.0.clone();
+                    }
+                    if policy != current {
+                        let response = client.set_completion_policy(id.value(), policy).await?;
+                        print_response(&response, |policy| print_policy(id.value(), policy));
+                    } else {
+                        print_policy(id.value(), &policy);
+                    }
                     Ok(())
                 }
             }
@@ -688,9 +2683,330 @@ pub async fn run() -> Result<(), Box<dyn std::error::Error>> {
     }
 }
 
-fn create_client(server_url: &str) -> HttpClientImpl {
+/// Status moves given to `plan policy --transitions`; empty allows every move
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransitionRules(pub Vec<StatusTransition>);
+
+fn parse_transitions(value: &str) -> Result<TransitionRules, String> {
+    let rules = match value.trim() {
+        "" | "any" => Vec::new(),
+        "kanban" => StatusTransition::kanban(),
+        list => list
+            .split(',')
+            .map(|rule| rule.trim().parse())
+            .collect::<Result<_, _>>()?,
+    };
+    Ok(TransitionRules(rules))
+}
+
+/// Parses an RFC 3339 timestamp, or a duration before now such as `90m`, `12h` or `2d`
+fn parse_time(value: &str) -> Result<chrono::DateTime<chrono::Utc>, String> {
+    if let Ok(time) = chrono::DateTime::parse_from_rfc3339(value) {
+        return Ok(time.into());
+    }
+    let split = value.len() - value.chars().last().map_or(0, char::len_utf8);
+    let (amount, unit) = value.split_at(split);
+    let amount: i64 = amount
+        .parse()
+        .map_err(|_| format!("'{value}' is neither an RFC 3339 time nor a duration like 12h"))?;
+    let ago = match unit {
+        "s" => chrono::Duration::seconds(amount),
+        "m" => chrono::Duration::minutes(amount),
+        "h" => chrono::Duration::hours(amount),
+        "d" => chrono::Duration::days(amount),
+        _ => return Err(format!("unknown unit in '{value}', use s, m, h or d")),
+    };
+    Ok(chrono::Utc::now() - ago)
+}
+
+/// Client-side settings, read from `$XDG_CONFIG_HOME/scatterbrain/cli.toml`
+/// (`~/.config/scatterbrain/cli.toml`) or the path in `SCATTERBRAIN_CLI_CONFIG`
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct CliConfig {
+    /// Command aliases, e.g. `c = "task complete --current"`
+    aliases: BTreeMap<String, String>,
+}
+
+impl CliConfig {
+    fn path() -> Option<PathBuf> {
+        if let Some(path) = std::env::var_os(CLI_CONFIG_ENV_VAR) {
+            return Some(PathBuf::from(path));
+        }
+        let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+        Some(config_dir.join("scatterbrain").join("cli.toml"))
+    }
+
+    /// Loads the config file, falling back to the defaults if it doesn't exist
+    fn load() -> Result<Self, ConfigError> {
+        let Some(path) = Self::path() else {
+            return Ok(Self::default());
+        };
+        match std::fs::read_to_string(path) {
+            Ok(contents) => Ok(toml::from_str(&contents)?),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// Replaces an aliased command with its expansion, leaving global options in place.
+///
+/// Only the command word is expanded, once, and built-in commands can't be shadowed.
+/// Expansions are split on whitespace.
+fn expand_aliases(args: Vec<OsString>, aliases: &BTreeMap<String, String>) -> Vec<OsString> {
+    let mut cli = Cli::command();
+    cli.build();
+    // Global options whose value comes in the next argument, e.g. `--workspace infra`
+    let value_flags: Vec<String> = cli
+        .get_arguments()
+        .filter(|arg| arg.get_action().takes_values())
+        .flat_map(|arg| {
+            let long = arg.get_long().map(|long| format!("--{long}"));
+            let short = arg.get_short().map(|short| format!("-{short}"));
+            long.into_iter().chain(short)
+        })
+        .collect();
+
+    let mut position = 1;
+    while let Some(arg) = args.get(position).and_then(|arg| arg.to_str()) {
+        match arg {
+            "--" => return args,
+            _ if value_flags.iter().any(|flag| flag == arg) => position += 2,
+            _ if arg.starts_with('-') => position += 1,
+            _ => break,
+        }
+    }
+
+    let Some(command) = args.get(position).and_then(|arg| arg.to_str()) else {
+        return args;
+    };
+    if command == "help" || cli.find_subcommand(command).is_some() {
+        return args;
+    }
+    let Some(expansion) = aliases.get(command) else {
+        return args;
+    };
+
+    let mut expanded = args[..position].to_vec();
+    expanded.extend(expansion.split_whitespace().map(OsString::from));
+    expanded.extend_from_slice(&args[position + 1..]);
+    expanded
+}
+
+/// Returns `text`, or everything on stdin (minus trailing newlines) when it is "-", so
+/// long or generated text doesn't have to survive shell quoting
+fn read_text(text: &str) -> io::Result<String> {
+    if text != "-" {
+        return Ok(text.to_string());
+    }
+    let input = io::read_to_string(io::stdin())?;
+    Ok(input.trim_end_matches(['\n', '\r']).to_string())
+}
+
+fn read_text_opt(text: &Option<String>) -> io::Result<Option<String>> {
+    text.as_deref().map(read_text).transpose()
+}
+
+/// Maps `--cascade`/`--no-cascade` to a per-call choice; neither defers to the plan's policy
+fn cascade_choice(cascade: bool, no_cascade: bool) -> Option<bool> {
+    match (cascade, no_cascade) {
+        (true, _) => Some(true),
+        (_, true) => Some(false),
+        _ => None,
+    }
+}
+
+fn print_effort_report(id: u8, report: &EffortReport) {
+    fn line(label: &str, effort: &EffortComparison) {
+        let ratio = effort
+            .ratio()
+            .map(|ratio| format!(" ({ratio:.2}x)"))
+            .unwrap_or_default();
+        println!(
+            "  {label}: {} task(s), {} min estimated, {} min logged{ratio}",
+            effort.tasks, effort.estimated_minutes, effort.actual_minutes
+        );
+    }
+
+    if report.levels.is_empty() {
+        println!("Plan {id}: no completed tasks have estimates yet");
+        return;
+    }
+    println!("Plan {id}: estimated vs. logged effort of completed tasks");
+    for level in &report.levels {
+        let label = match &level.name {
+            Some(name) => format!("Level {} {name}", level.level),
+            None => format!("Level {}", level.level),
+        };
+        line(&label, &level.effort);
+    }
+    for subtree in &report.subtrees {
+        let label = format!("[{}] {}", format_index(&subtree.index), subtree.description);
+        line(&label, &subtree.effort);
+    }
+}
+
+fn print_stats(id: u8, stats: &PlanStats) {
+    println!(
+        "Plan {id} ({}): {}/{} tasks done ({}%)",
+        stats.status, stats.completed, stats.total, stats.percent_complete
+    );
+    match &stats.description {
+        Some(description) => println!("  Current: [{}] {description}", format_index(&stats.index)),
+        None => println!("  Current: root"),
+    }
+    for level in &stats.levels {
+        let name = level.name.as_deref().unwrap_or("");
+        println!(
+            "  Level {} {name}: {}/{} done",
+            level.level, level.completed, level.total
+        );
+    }
+    println!(
+        "  Awaiting review: {}, leased: {}, open questions: {}",
+        stats.awaiting_review, stats.leased, stats.open_questions
+    );
+    println!(
+        "  Completed: {} in the last day, {} in the last week",
+        stats.completed_last_day, stats.completed_last_week
+    );
+    if let Some(last_activity) = stats.last_activity {
+        println!(
+            "  Last activity: {}",
+            last_activity.format("%Y-%m-%d %H:%M UTC")
+        );
+    }
+}
+
+fn print_badge(id: u8, badge: &PlanBadge) {
+    if badge.is_empty() {
+        println!("Plan {id} has no badge");
+        return;
+    }
+    let color = badge.color.map(|color| format!(" ({color})"));
+    println!(
+        "Plan {}{}",
+        plan_label(id, badge),
+        color.unwrap_or_default()
+    );
+}
+
+/// A plan's ID in its badge color, preceded by its emoji
+fn plan_label(id: u8, badge: &PlanBadge) -> String {
+    let id = match badge.color {
+        Some(color) => {
+            let (r, g, b) = color.rgb();
+            id.to_string().truecolor(r, g, b).bold().to_string()
+        }
+        None => id.to_string(),
+    };
+    match &badge.emoji {
+        Some(emoji) => format!("{emoji} {id}"),
+        None => id,
+    }
+}
+
+fn print_policy(id: u8, policy: &CompletionPolicy) {
+    let cascade = if policy.cascade {
+        "on (completing a task also completes its incomplete subtasks)"
+    } else {
+        "off (completing a task with incomplete subtasks fails without --cascade)"
+    };
+    let leaf_only = if policy.leaf_only {
+        "on (only tasks without subtasks can be completed; parents complete with them)"
+    } else {
+        "off"
+    };
+    println!("Plan {id} completion policy:");
+    println!("  cascade: {cascade}");
+    println!("  leaf-only: {leaf_only}");
+    if policy.summary.is_enabled() {
+        let rules = &policy.summary;
+        println!("  summaries: at least {} characters", rules.min_chars);
+        for required in &rules.required {
+            println!("    must mention \"{required}\"");
+        }
+    } else {
+        println!("  summaries: any non-empty summary");
+    }
+    if !policy.validators.is_empty() {
+        println!("  validators: {}", policy.validators.join(", "));
+    }
+    if policy.transitions.is_empty() {
+        println!("  transitions: any");
+    } else {
+        let rules: Vec<String> = policy.transitions.iter().map(|t| t.to_string()).collect();
+        println!("  transitions: {}", rules.join(", "));
+    }
+}
+
+/// Prints a workspace's quotas with its plan count and each plan's usage
+fn print_quota_report(workspace: &str, report: &QuotaReport) {
+    let limit = |limit: Option<usize>| limit.map_or("no limit".to_string(), |l| l.to_string());
+    println!("{}", format!("Workspace {workspace}").bold());
+    println!(
+        "  plans: {} (max_plans: {})",
+        report.plans,
+        limit(report.quotas.max_plans)
+    );
+    println!(
+        "  max_tasks_per_plan: {}, max_notes_bytes: {}",
+        limit(report.quotas.max_tasks_per_plan),
+        limit(report.quotas.max_notes_bytes)
+    );
+    for plan in &report.usage {
+        println!(
+            "  - plan {}: {} tasks, {} bytes of notes",
+            plan.plan_id.value(),
+            plan.usage.tasks,
+            plan.usage.notes_bytes
+        );
+    }
+}
+
+fn print_bench_report(report: &BenchReport) {
+    println!(
+        "{} plans of {} tasks, {} threads, mix {}",
+        report.plans, report.tasks_per_plan, report.threads, report.mix
+    );
+    println!(
+        "{} operations in {:.1} ms ({:.0} ops/s)",
+        report.operations, report.elapsed_ms, report.throughput
+    );
+    println!(
+        "{}",
+        format!(
+            "{:<14} {:>7} {:>6} {:>10} {:>10} {:>10} {:>10} {:>10}",
+            "operation", "count", "errors", "mean µs", "p50 µs", "p90 µs", "p99 µs", "max µs"
+        )
+        .bold()
+    );
+    for stats in &report.results {
+        println!(
+            "{:<14} {:>7} {:>6} {:>10.1} {:>10.1} {:>10.1} {:>10.1} {:>10.1}",
+            stats.operation.name(),
+            stats.count,
+            stats.errors,
+            stats.mean_us,
+            stats.p50_us,
+            stats.p90_us,
+            stats.p99_us,
+            stats.max_us
+        );
+    }
+}
+
+fn create_client(cli: &Cli) -> HttpClientImpl {
     let config = ClientConfig {
-        base_url: server_url.to_string(),
+        base_url: cli.server.clone(),
+        auth_token: cli.token.clone(),
+        session: cli.session.clone(),
+        agent: cli.agent.clone(),
+        workspace: cli.workspace.clone(),
+        fallback_urls: cli.fallback_servers.clone(),
     };
     HttpClientImpl::with_config(config)
 }
@@ -702,114 +3018,408 @@ where
     F: FnOnce(&T),
 {
     print_inner(response.inner());
+    write_response_footer(&mut io::stdout(), response).expect("failed printing to stdout");
+}
+
+/// Writes what follows the result of every PlanResponse: suggested next steps, the
+/// reminder and the distilled context
+fn write_response_footer<T>(
+    out: &mut impl Write,
+    response: &crate::models::PlanResponse<T>,
+) -> io::Result<()> {
     if !response.suggested_followups.is_empty() {
-        println!("\nSuggested next steps:");
+        writeln!(out, "\nSuggested next steps:")?;
         for suggestion in &response.suggested_followups {
-            println!("  • {suggestion}");
+            writeln!(out, "  • {suggestion}")?;
         }
     }
     if let Some(reminder) = &response.reminder {
-        println!("\nReminder: {reminder}");
+        writeln!(out, "\nReminder: {reminder}")?;
     }
-    print_distilled_context_response(response);
+    write_distilled_context_response(out, response)
 }
 
-fn print_plan_response(response: &crate::models::PlanResponse<crate::models::Plan>) {
+/// Prints `output`, through `$PAGER` (`less` by default) when stdout is a terminal and
+/// the output is taller than it, like git does. Falls back to printing directly if the
+/// pager can't be started.
+fn page(output: &[u8], no_pager: bool) -> io::Result<()> {
+    let fits = match terminal_size::terminal_size() {
+        Some((_, terminal_size::Height(height))) => {
+            output.iter().filter(|&&b| b == b'\n').count() < height as usize
+        }
+        None => true,
+    };
+    if no_pager || fits {
+        return io::stdout().write_all(output);
+    }
+
+    let pager = std::env::var("PAGER")
+        .ok()
+        .filter(|pager| !pager.trim().is_empty())
+        .unwrap_or_else(|| "less".to_string());
+    let mut command = Command::new("sh");
+    command.arg("-c").arg(&pager).stdin(Stdio::piped());
+    if std::env::var_os("LESS").is_none() {
+        // Keep colors, and quit right away if the output fits after all
+        command.env("LESS", "FRX");
+    }
+    let Ok(mut child) = command.spawn() else {
+        return io::stdout().write_all(output);
+    };
+    if let Some(mut stdin) = child.stdin.take() {
+        // The pager closes its input when the user quits before reaching the end
+        match stdin.write_all(output) {
+            Err(e) if e.kind() != io::ErrorKind::BrokenPipe => return Err(e),
+            _ => {}
+        }
+    }
+    child.wait()?;
+    Ok(())
+}
+
+fn write_plan_response(
+    out: &mut impl Write,
+    response: &crate::models::PlanResponse<crate::models::Plan>,
+    sort: TaskSort,
+) -> io::Result<()> {
     let plan = response.inner();
-    println!("Scatterbrain Plan:");
+    writeln!(out, "Scatterbrain Plan:")?;
     // Print Goal if it exists
     if let Some(goal) = &plan.goal {
         // Access goal directly
-        println!("Goal: {}", goal.bright_blue());
+        writeln!(out, "Goal: {}", goal.bright_blue())?;
+    }
+    writeln!(out, "Status: {}", plan.status())?;
+    if let Some(completed_at) = plan.completed_at() {
+        writeln!(
+            out,
+            "{}",
+            format!(
+                "Plan complete (completed at {})",
+                completed_at.format("%Y-%m-%d %H:%M:%S UTC")
+            )
+            .green()
+        )?;
     }
     // Print Notes if they exist
     if let Some(notes) = &plan.notes {
         // Access notes directly
-        println!("Notes:\n{notes}");
-        println!("---"); // Add a separator
+        writeln!(out, "Notes:\n{notes}")?;
+        writeln!(out, "---")?; // Add a separator
     }
 
-    println!("Levels: {}", plan.levels().len());
-    println!("\nRoot Tasks:");
-    if plan.root().subtasks().is_empty() {
-        println!("  No tasks yet. Add some with 'scatterbrain task add'");
+    writeln!(out, "Levels: {}", plan.levels().len())?;
+    writeln!(out, "\nRoot Tasks:")?;
+    let subtasks = sorted_subtasks(plan.root(), sort);
+    if subtasks.is_empty() {
+        writeln!(out, "  No tasks yet. Add some with 'scatterbrain task add'")?;
     } else {
-        for (i, task) in plan.root().subtasks().iter().enumerate() {
-            print_task(task, vec![i]);
+        let width = terminal_width();
+        for (n, &(i, task)) in subtasks.iter().enumerate() {
+            write_task(out, task, vec![i], "", n + 1 == subtasks.len(), width, sort)?;
         }
     }
-    println!("\nAvailable Levels:");
+    writeln!(out, "\nAvailable Levels:")?;
     for (i, level) in plan.levels().iter().enumerate() {
-        println!("  {}. {}", i, level.get_guidance());
+        writeln!(out, "  {}. {}", i, level.get_guidance())?;
     }
-    print_distilled_context_response(response);
+    write_distilled_context_response(out, response)?;
+    Ok(())
 }
 
-/// Recursively prints a task and its subtasks with proper indentation
-fn print_task(task: &crate::models::Task, index: Vec<usize>) {
-    let indent = "  ".repeat(index.len());
-    let index_str = index
-        .iter()
-        .map(|i| i.to_string())
-        .collect::<Vec<_>>()
-        .join(".");
+/// Writes tracked questions, open ones first
+fn write_questions(out: &mut impl Write, questions: &[crate::models::Question]) -> io::Result<()> {
+    let (open, answered): (Vec<_>, Vec<_>) = questions.iter().partition(|q| q.is_open());
+    for q in open {
+        writeln!(out, "  [{}] (open) {}", q.id, q.question)?;
+    }
+    for q in answered {
+        writeln!(out, "  [{}] (answered) {}", q.id, q.question)?;
+        if let Some(answer) = &q.answer {
+            writeln!(out, "      -> {answer}")?;
+        }
+    }
+    Ok(())
+}
+
+/// Width of the terminal stdout is attached to, or `None` when it isn't a terminal so
+/// piped output is never truncated
+fn terminal_width() -> Option<usize> {
+    terminal_size::terminal_size().map(|(terminal_size::Width(width), _)| width as usize)
+}
+
+/// Shortens `text` to at most `width` characters, marking the cut with "…"
+fn truncate(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        return text.to_string();
+    }
+    let mut truncated: String = text.chars().take(width.saturating_sub(1)).collect();
+    truncated.push('…');
+    truncated
+}
 
-    let level_str = if let Some(level_index) = task.level_index() {
-        format!("level: {level_index}")
+/// Box-drawing guides for a tree row: the connector in front of the row, and the prefix
+/// for the lines below it (its notes and subtasks)
+fn tree_guides(prefix: &str, is_last: bool) -> (String, String) {
+    if is_last {
+        (format!("{prefix}└── "), format!("{prefix}    "))
     } else {
-        "level: unknown".to_string()
+        (format!("{prefix}├── "), format!("{prefix}│   "))
+    }
+}
+
+/// A badge like "L2" for a task's level, colored by level so siblings at the same
+/// level line up visually
+fn level_badge(level_index: Option<usize>) -> ColoredString {
+    const LEVEL_COLORS: [Color; 5] = [
+        Color::Blue,
+        Color::Cyan,
+        Color::Green,
+        Color::Yellow,
+        Color::Magenta,
+    ];
+    match level_index {
+        Some(level) => format!("L{level}")
+            .color(LEVEL_COLORS[level % LEVEL_COLORS.len()])
+            .bold(),
+        None => "L?".dimmed(),
+    }
+}
+
+/// Formats one task row: completion box, index, level badge and description. The
+/// description is truncated so the row fits in `width` columns.
+fn format_task_row(
+    lead: &str,
+    completed: bool,
+    index: &[usize],
+    level_index: Option<usize>,
+    description: &str,
+    suffix: &str,
+    width: Option<usize>,
+) -> String {
+    let status = if completed {
+        "[✓]".green()
+    } else {
+        "[ ]".normal()
     };
+    let index_str = format_index(index);
+    let badge = level_badge(level_index);
+    let suffix_str = if suffix.is_empty() {
+        suffix.normal()
+    } else {
+        suffix.yellow()
+    };
+    let description = match width {
+        Some(width) => {
+            // Everything but the description, as it shows on screen
+            let used = lead.chars().count()
+                + index_str.len()
+                + badge.chars().count()
+                + suffix.chars().count()
+                + 7;
+            truncate(description, width.saturating_sub(used).max(16))
+        }
+        None => description.to_string(),
+    };
+    format!(
+        "{lead}{status} {} {badge} {description}{suffix_str}",
+        index_str.dimmed()
+    )
+}
 
-    println!(
-        "{}(index: [{}]) ({}), {} (completed: {})",
-        indent,
-        index_str,
-        level_str,
-        task.description(),
-        task.is_completed()
-    );
+/// Writes multi-line text below a tree row, each line behind `prefix`
+fn write_tree_text(
+    out: &mut impl Write,
+    prefix: &str,
+    label: Option<&str>,
+    text: &str,
+) -> io::Result<()> {
+    let label = label.map(|label| format!("[{label}] ")).unwrap_or_default();
+    for (i, line) in text.lines().enumerate() {
+        let label = if i == 0 { label.as_str() } else { "" };
+        writeln!(out, "{prefix}{}", format!("{label}{line}").dimmed())?;
+    }
+    Ok(())
+}
+
+/// Writes a listed task like a [`write_task`] row, indented by its depth below
+/// `base_depth`
+fn write_task_listing(
+    out: &mut impl Write,
+    task: &crate::models::TaskListing,
+    base_depth: usize,
+    width: Option<usize>,
+) -> io::Result<()> {
+    let indent = "  ".repeat(task.index.len() - base_depth);
+    let mut suffix = String::new();
+    if !task.tags.is_empty() {
+        suffix.push_str(&format!(" {}", format_tags(&task.tags)));
+    }
+    if task.awaiting_review {
+        suffix.push_str(" (awaiting review)");
+    }
+    writeln!(
+        out,
+        "{}",
+        format_task_row(
+            &indent,
+            task.completed,
+            &task.index,
+            task.level_index,
+            &task.description,
+            &suffix,
+            width,
+        )
+    )?;
+    if let Some(notes) = &task.notes {
+        write_tree_text(out, &format!("{indent}    "), None, notes)?;
+    }
+    Ok(())
+}
+
+/// Recursively writes a task and its subtasks, drawing tree guides behind `prefix`
+fn write_task(
+    out: &mut impl Write,
+    task: &crate::models::Task,
+    index: Vec<usize>,
+    prefix: &str,
+    is_last: bool,
+    width: Option<usize>,
+    sort: TaskSort,
+) -> io::Result<()> {
+    let (lead, child_prefix) = tree_guides(prefix, is_last);
+    let mut suffix = String::new();
+    if !task.tags().is_empty() {
+        suffix.push_str(&format!(" {}", format_tags(task.tags())));
+    }
+    if task.is_awaiting_review() {
+        suffix.push_str(" (awaiting review)");
+    }
+    let stamp = match (task.is_completed(), task.completed_at()) {
+        (true, Some(at)) => Some(("done", at)),
+        _ => task.updated_at().map(|at| ("updated", at)),
+    };
+    if let Some((verb, at)) = stamp {
+        let age = format_age(chrono::Utc::now() - at);
+        suffix.push_str(&format!(" · {verb} {age}"));
+    }
+    writeln!(
+        out,
+        "{}",
+        format_task_row(
+            &lead,
+            task.is_completed(),
+            &index,
+            task.level_index(),
+            task.description(),
+            &suffix,
+            width,
+        )
+    )?;
 
-    // Print notes if they exist
+    // Keep the guide to the subtasks running alongside the notes
+    let subtasks = sorted_subtasks(task, sort);
+    let text_prefix = if subtasks.is_empty() {
+        format!("{child_prefix}    ")
+    } else {
+        format!("{child_prefix}│   ")
+    };
+    if let Some(feedback) = task.review_feedback() {
+        write_tree_text(out, &text_prefix, Some("review feedback"), feedback)?;
+    }
     if let Some(notes) = task.notes() {
-        let notes_indent = "  ".repeat(index.len() + 1); // Extra indent for notes
-        println!(
-            "{}{}",
-            notes_indent,
-            notes.replace('\n', &format!("\n{notes_indent}"))
-        ); // Indent multi-line notes
+        write_tree_text(out, &text_prefix, None, notes)?;
+    }
+    for (section, content) in task.note_sections() {
+        write_tree_text(out, &text_prefix, Some(section), content)?;
     }
 
-    for (i, subtask) in task.subtasks().iter().enumerate() {
+    for (n, &(i, subtask)) in subtasks.iter().enumerate() {
         let mut subtask_index = index.clone();
         subtask_index.push(i);
-        print_task(subtask, subtask_index);
+        write_task(
+            out,
+            subtask,
+            subtask_index,
+            &child_prefix,
+            n + 1 == subtasks.len(),
+            width,
+            sort,
+        )?;
     }
+    Ok(())
 }
 
-/// Generates the guide string with formatted values.
-fn get_guide_string() -> String {
-    crate::guide::get_guide_string(crate::guide::GuideMode::Cli)
+/// The live subtasks of `task` with their positions, in `sort` order. Tasks that were
+/// never timestamped sort last under [`TaskSort::Recent`].
+fn sorted_subtasks(
+    task: &crate::models::Task,
+    sort: TaskSort,
+) -> Vec<(usize, &crate::models::Task)> {
+    let mut subtasks: Vec<_> = task.live_subtasks().collect();
+    if sort == TaskSort::Recent {
+        subtasks.sort_by_key(|(_, subtask)| std::cmp::Reverse(subtask.updated_at()));
+    }
+    subtasks
 }
 
-fn print_guide() {
-    let guide_text = get_guide_string();
+/// Formats tags as hashtags, e.g. "#backend #db"
+fn format_tags(tags: &std::collections::BTreeSet<String>) -> String {
+    tags.iter()
+        .map(|tag| format!("#{tag}"))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Every tag used by the live tasks below `task`, with how many tasks have it
+fn tag_counts(task: &crate::models::Task) -> std::collections::BTreeMap<String, usize> {
+    let mut counts = std::collections::BTreeMap::new();
+    for (_, subtask) in task.live_subtasks() {
+        for tag in subtask.tags() {
+            *counts.entry(tag.clone()).or_insert(0) += 1;
+        }
+        for (tag, count) in tag_counts(subtask) {
+            *counts.entry(tag).or_insert(0) += count;
+        }
+    }
+    counts
+}
+
+/// How long ago something happened, in its largest whole unit, e.g. "3h ago"
+fn format_age(age: chrono::Duration) -> String {
+    if age.num_minutes() < 1 {
+        "just now".to_string()
+    } else if age.num_hours() < 1 {
+        format!("{}m ago", age.num_minutes())
+    } else if age.num_days() < 1 {
+        format!("{}h ago", age.num_hours())
+    } else {
+        format!("{}d ago", age.num_days())
+    }
+}
+
+/// Prints the guide, with any custom sections from `SCATTERBRAIN_GUIDE`
+fn print_guide() -> Result<(), GuideError> {
+    let guide_text = crate::guide::load_guide_string(GuideMode::Cli)?;
     println!("{guide_text}");
+    Ok(())
 }
 
-/// Print a distilled context from any PlanResponse
-fn print_distilled_context_response<T>(response: &crate::models::PlanResponse<T>) {
+/// Write a distilled context from any PlanResponse
+fn write_distilled_context_response<T>(
+    out: &mut impl Write,
+    response: &crate::models::PlanResponse<T>,
+) -> io::Result<()> {
     let context = &response.distilled_context;
     let truncation_limit = 400;
 
-    println!("\n--- Current Context ---");
-
-    // Find the current node in the tree to get its index string
-    let current_node_opt = find_current_node(&context.task_tree);
+    writeln!(out, "\n--- Current Context ---")?;
 
     // Print the overall plan goal if it exists
     if let Some(goal) = &context.goal {
-        println!("Goal: {}", goal.bright_blue());
+        writeln!(out, "Goal: {}", goal.bright_blue())?;
     }
+    writeln!(out, "Plan Status: {}", context.plan_status)?;
 
     // Print Plan Notes (truncated)
     if let Some(notes) = &context.plan_notes {
@@ -817,13 +3427,14 @@ fn print_distilled_context_response<T>(response: &crate::models::PlanResponse<T>
         if notes.len() > truncation_limit {
             // Truncate and add indicator
             let truncated_notes: String = notes.chars().take(truncation_limit).collect();
-            println!(
+            writeln!(
+                out,
                 "{}... (use 'plan show' for full notes)",
                 truncated_notes.trim()
-            );
+            )?;
         } else {
             // Print full notes if short enough
-            println!("{notes}");
+            writeln!(out, "{notes}")?;
         }
     }
 
@@ -831,15 +3442,24 @@ fn print_distilled_context_response<T>(response: &crate::models::PlanResponse<T>
     if let Some(task) = &context.current_task {
         print!(
             "Current Task: [{}] {}",
-            current_node_opt.map_or_else(|| "?".to_string(), |node| format_index(&node.index)), // Get index from tree node
+            format_index(&context.cursor),
             task.description()
         );
         if let Some(level) = task.level_index() {
             print!(" (level: {level})");
         }
-        println!();
+        writeln!(out)?;
     } else {
-        println!("No current task selected");
+        writeln!(out, "No current task selected")?;
+    }
+    if let Some(focus) = &context.focus {
+        writeln!(out, "{}", describe_focus(focus))?;
+    }
+    if let Some(session) = &context.session {
+        writeln!(out, "{}", describe_session(session))?;
+    }
+    if let Some(critical_path) = context.critical_path_summary() {
+        writeln!(out, "Critical path: {critical_path}")?;
     }
 
     if let Some(level_info) = &context.current_level {
@@ -849,228 +3469,135 @@ fn print_distilled_context_response<T>(response: &crate::models::PlanResponse<T>
             .iter()
             .position(|l| l.name() == level_info.name());
         if let Some(idx) = level_index {
-            println!(
+            writeln!(
+                out,
                 "CURRENT LEVEL DETAILS (Level {}: {}):",
                 idx,
                 level_info.name()
-            );
-            println!("  Focus: {}", level_info.abstraction_focus());
+            )?;
+            writeln!(out, "  Focus: {}", level_info.abstraction_focus())?;
 
             let questions = level_info.questions();
             if !questions.is_empty() {
-                println!("  Sample Questions:");
+                writeln!(out, "  Sample Questions:")?;
                 for q in questions {
-                    println!("    - {q}");
+                    writeln!(out, "    - {q}")?;
                 }
             }
 
-            println!("  Guidance: {}", level_info.get_guidance());
+            writeln!(out, "  Guidance: {}", level_info.get_guidance())?;
         } else {
             // Fallback if the current_level isn't found in the main list (shouldn't happen)
-            println!(
+            writeln!(
+                out,
                 "CURRENT LEVEL DETAILS (Unknown Index: {}):",
                 level_info.name()
-            );
-            println!("  Focus: {}", level_info.abstraction_focus());
+            )?;
+            writeln!(out, "  Focus: {}", level_info.abstraction_focus())?;
         }
     } else {
         // This case handles when there's no specific current task, but we might be at root
         // or the current task doesn't have an explicit level set.
         // We rely on context.current_level which should be set even at root.
-        println!(
+        writeln!(
+            out,
             "CURRENT LEVEL DETAILS: No specific level context available for the current task."
-        );
-    }
-
-    println!("\n");
-
-    println!("TASK TREE (slim, see `plan show` for full tree):");
-    // Helper function to find the current node recursively
-    fn find_current_node(
-        nodes: &[crate::models::TaskTreeNode],
-    ) -> Option<&crate::models::TaskTreeNode> {
-        for node in nodes {
-            if node.is_current {
-                return Some(node);
-            }
-            if let Some(found) = find_current_node(&node.children) {
-                return Some(found);
-            }
-        }
-        None
-    }
-    print_task_tree(&context.task_tree, 0);
-    println!("\n");
-
-    println!("AVAILABLE LEVELS (more level information availabe via the `plan` command):");
-    let level_summary = context
-        .levels
-        .iter()
-        .enumerate()
-        .map(|(idx, level)| format!("{}:{}", idx, level.name()))
-        .collect::<Vec<_>>()
-        .join(" | ");
-    println!("  {level_summary}");
-    println!("\n");
-
-    if !response.suggested_followups.is_empty() {
-        println!("Suggested next steps:");
-        for followup in &response.suggested_followups {
-            println!("  • {followup}");
-        }
-        println!("\n");
+        )?;
     }
 
-    if let Some(reminder) = &response.reminder {
-        println!("Reminder: {reminder}");
-        println!("\n");
-    }
-}
-
-fn print_task_tree(_nodes: &[crate::models::TaskTreeNode], indent: usize) {
-    for node in _nodes {
-        let index_str = node
-            .index
-            .iter()
-            .map(|i| i.to_string())
-            .collect::<Vec<_>>()
-            .join(".");
-
-        let indent_str = "  ".repeat(indent);
-        let current_indicator = if node.is_current { "→ " } else { "  " };
-        let completion_status = if node.completed { "[✓]" } else { "[ ]" };
-
-        println!(
-            "{}{}{} {} {}",
-            indent_str, current_indicator, completion_status, index_str, node.description
-        );
-
-        // Print notes if they exist
-        if let Some(notes) = &node.notes {
-            let notes_indent = "  ".repeat(indent + 1); // Extra indent for notes
-            println!(
-                "{}> {}",
-                notes_indent,
-                notes.replace('\n', &format!("\n{notes_indent}> "))
-            ); // Indent multi-line notes
-        }
-
-        if !node.children.is_empty() {
-            print_task_tree(&node.children, indent + 1);
-        }
-    }
-}
-
-/// Creates an example task tree for UI testing, operating on the default plan within the Core.
-fn create_example_tasks(core: &Core) {
-    create_example_tasks_for_plan(core, &DEFAULT_PLAN_ID);
-}
-
-/// Creates an example task tree for the specified plan ID.
-fn create_example_tasks_for_plan(core: &Core, plan_id: &PlanId) {
-    // Access the context for the specified plan ID
-    let result: Result<Result<(), PlanError>, PlanError> =
-        core.with_plan_context(plan_id, |context| {
-            // Create top-level tasks (level 0 - Business Strategy)
-            let result = context.add_task("Build Web Application".to_string(), 0, None);
-            let (_, idx_root) = result.into_inner(); // Keep root index
-            context.move_to(idx_root.clone()).inner();
-
-            // Level 1 - Project Planning
-            let result = context.add_task("Implement Frontend".to_string(), 1, None);
-            let (_, idx_frontend) = result.into_inner();
-            context.move_to(idx_frontend.clone()).inner();
-
-            // Level 2 - Implementation
-            let result = context.add_task("Design UI Components".to_string(), 2, None);
-            let (_, idx_ui_components) = result.into_inner();
-            context.move_to(idx_ui_components.clone()).inner();
-
-            // Level 3 - Implementation Details
-            let result = context.add_task("Implement User Authentication UI".to_string(), 3, None);
-            let (_, idx_auth_ui) = result.into_inner();
-            // -- Complete this task --
-            context
-                .complete_task(idx_auth_ui, None, true, Some("Auth UI done.".to_string()))
-                .inner();
-
-            // Move back up to "Implement Frontend"
-            context.move_to(idx_frontend.clone()).inner();
-
-            // Add another subtask to "Implement Frontend"
-            let result = context.add_task("Set up State Management".to_string(), 2, None);
-            let (_, idx_state_mgmt) = result.into_inner(); // Keep this index for final cursor
-
-            // Move back to root
-            context.move_to(idx_root.clone()).inner();
-
-            // Add "Implement Backend" as subtask of "Build Web Application"
-            let result = context.add_task("Implement Backend".to_string(), 1, None);
-            let (_, idx_backend) = result.into_inner();
-            context.move_to(idx_backend.clone()).inner();
-
-            // Add backend tasks
-            let result = context.add_task("Set up Database".to_string(), 2, None);
-            let (_, idx_db) = result.into_inner();
-            context.move_to(idx_db.clone()).inner();
-
-            // Add some API endpoint tasks
-            let result = context.add_task("Create API Endpoints".to_string(), 3, None);
-            let (_, idx_api) = result.into_inner();
-            // -- Complete this task --
-            context
-                .complete_task(
-                    idx_api,
-                    None,
-                    true,
-                    Some("Basic CRUD endpoints added.".to_string()),
-                )
-                .inner();
-
-            context
-                .add_task("Implement Authentication Logic".to_string(), 3, None)
-                .into_inner();
-            context
-                .add_task("Create Data Models".to_string(), 3, None)
-                .into_inner();
-
-            // Move back to "Set up Database"
-            context.move_to(idx_db.clone()).inner();
+    writeln!(out, "\n")?;
 
-            // Add database schema tasks
-            let result = context.add_task("Product Model".to_string(), 3, None);
-            let (_, idx_prod_model) = result.into_inner();
-            context.move_to(idx_prod_model.clone()).inner();
+    if !context.questions.is_empty() {
+        writeln!(out, "QUESTIONS (see `question list`):")?;
+        write_questions(out, &context.questions)?;
+        writeln!(out, "\n")?;
+    }
 
-            // Add some fields
-            context
-                .add_task("Define Product Fields".to_string(), 3, None)
-                .into_inner();
-            context
-                .add_task("Implement Relationships".to_string(), 3, None)
-                .into_inner();
+    writeln!(out, "TASK TREE (slim, see `plan show` for full tree):")?;
+    write_task_tree(out, &context.task_tree, "", terminal_width())?;
+    writeln!(out, "\n")?;
 
-            // Move back to root level
-            context.move_to(idx_root.clone()).inner();
+    writeln!(
+        out,
+        "AVAILABLE LEVELS (more level information availabe via the `plan` command):"
+    )?;
+    let level_summary = context
+        .levels
+        .iter()
+        .enumerate()
+        .map(|(idx, level)| format!("{}:{}", idx, level.name()))
+        .collect::<Vec<_>>()
+        .join(" | ");
+    writeln!(out, "  {level_summary}")?;
+    writeln!(out, "\n")?;
 
-            // Add a few more top level tasks
-            context
-                .add_task("Write Documentation".to_string(), 0, None)
-                .into_inner();
-            context
-                .add_task("Test Application".to_string(), 0, None)
-                .into_inner();
+    if !response.suggested_followups.is_empty() {
+        writeln!(out, "Suggested next steps:")?;
+        for followup in &response.suggested_followups {
+            writeln!(out, "  • {followup}")?;
+        }
+        writeln!(out, "\n")?;
+    }
 
-            // Set final cursor position to the incomplete "Set up State Management" task
-            context.move_to(idx_state_mgmt).inner();
+    if let Some(reminder) = &response.reminder {
+        writeln!(out, "Reminder: {reminder}")?;
+        writeln!(out, "\n")?;
+    }
+    Ok(())
+}
 
-            Ok::<(), PlanError>(()) // Specify the full type for Ok variant
-        });
+/// Writes the slim task tree of a distilled context, marking the current task
+fn write_task_tree(
+    out: &mut impl Write,
+    nodes: &[crate::models::TaskTreeNode],
+    prefix: &str,
+    width: Option<usize>,
+) -> io::Result<()> {
+    for (i, node) in nodes.iter().enumerate() {
+        let (lead, child_prefix) = tree_guides(prefix, i + 1 == nodes.len());
+        let mut suffix = String::new();
+        if let Some(priority) = node.priority {
+            suffix.push_str(&format!(" P{priority}"));
+        }
+        if !node.tags.is_empty() {
+            suffix.push_str(&format!(" {}", format_tags(&node.tags)));
+        }
+        if !node.blocked_by.is_empty() {
+            let blockers: Vec<String> = node.blocked_by.iter().map(|i| format_index(i)).collect();
+            suffix.push_str(&format!(" (blocked by {})", blockers.join(", ")));
+        }
+        if node.is_current {
+            suffix.push_str(" ← current");
+        }
+        writeln!(
+            out,
+            "{}",
+            format_task_row(
+                &lead,
+                node.completed,
+                &node.index,
+                node.level_index,
+                &node.description,
+                &suffix,
+                width,
+            )
+        )?;
+
+        let text_prefix = if node.children.is_empty() {
+            format!("{child_prefix}    ")
+        } else {
+            format!("{child_prefix}│   ")
+        };
+        if let Some(notes) = &node.notes {
+            write_tree_text(out, &text_prefix, None, notes)?;
+        }
+        for (section, content) in &node.note_sections {
+            write_tree_text(out, &text_prefix, Some(section), content)?;
+        }
 
-    // Handle potential error from with_plan_context (e.g., PlanNotFound)
-    if let Err(e) = result {
-        eprintln!("Error creating example tasks: {e}");
+        write_task_tree(out, &node.children, &child_prefix, width)?;
     }
+    Ok(())
 }
 
 // Re-add get_plan_id function definition here
@@ -1098,6 +3625,90 @@ fn get_plan_id(cli: &Cli) -> Result<PlanId, Box<dyn std::error::Error>> {
     Ok(PlanId::new(id_val))
 }
 
+/// Resolves a task argument, an index like "0,2" or an ID like "t-3f9a2c1b", to the task's
+/// current index
+async fn resolve_index(
+    client: &(impl Client + Sync),
+    id: PlanId,
+    task: &str,
+) -> Result<Index, Box<dyn std::error::Error>> {
+    let task: TaskRef = task.parse()?;
+    Ok(client.resolve_task(id.value(), task).await?)
+}
+
+/// Formats a plan's progress as one line, e.g. "plan 42 • 0,3,1 • Implement parser • 12/30 done"
+fn format_status_line(id: u8, progress: &ProgressSummary) -> String {
+    let position = if progress.index.is_empty() {
+        "root".to_string()
+    } else {
+        progress
+            .index
+            .iter()
+            .map(|i| i.to_string())
+            .collect::<Vec<_>>()
+            .join(",")
+    };
+    let mut parts = vec![format!("plan {id}"), position];
+    parts.extend(progress.description.clone());
+    let mut done = format!("{}/{} done", progress.completed, progress.total);
+    // Only worth a mention once weights make the counts misleading
+    if (progress.weighted_completed, progress.weighted_total)
+        != (progress.completed as u64, progress.total as u64)
+    {
+        done.push_str(&format!(" ({}% by weight)", progress.percent_complete()));
+    }
+    parts.push(done);
+    parts.join(" • ")
+}
+
+/// URL of the task at `index` in the web UI of the server the CLI talks to
+fn task_link(cli: &Cli, plan_id: u8, index: &[usize]) -> String {
+    let base = cli.server.trim_end_matches('/');
+    format!("{base}{}", task_ui_path(plan_id, index))
+}
+
+fn describe_focus(focus: &Focus) -> String {
+    let mode = if focus.strict { " (strict)" } else { "" };
+    format!("Focused task: {}{mode}", format_index(&focus.index))
+}
+
+fn describe_session(status: &SessionStatus) -> String {
+    let reminders = match status.reminder_minutes {
+        0 => "no reminders".to_string(),
+        minutes => format!("reminders every {minutes} minutes"),
+    };
+    format!(
+        "Session: running for {} minutes ({reminders})",
+        status.elapsed_minutes
+    )
+}
+
+/// Notes section `task add --inherit-context` records the plan's context in
+const INHERITED_CONTEXT_SECTION: &str = "context";
+
+/// The context a task added at `index` inherits: the plan goal, and the ID to look up
+/// the parent's notes by when it has any. `context` is the one returned by adding the
+/// task, whose cursor is on the parent.
+fn inherited_context(context: &crate::models::DistilledContext, index: &[usize]) -> Option<String> {
+    let mut lines = Vec::new();
+    if let Some(goal) = &context.goal {
+        lines.push(format!("Plan goal: {goal}"));
+    }
+    let parent = context
+        .current_task
+        .as_ref()
+        .filter(|task| task.notes().is_some())
+        .filter(|_| index.len() == context.cursor.len() + 1 && index.starts_with(&context.cursor));
+    if let Some(parent) = parent {
+        lines.push(format!(
+            "Parent notes: see task {} (scatterbrain task notes view {})",
+            format_index(&context.cursor),
+            parent.id()
+        ));
+    }
+    (!lines.is_empty()).then(|| lines.join("\n"))
+}
+
 /// Helper function to format an index vector like [0, 1, 2] into "0.1.2"
 fn format_index(index: &[usize]) -> String {
     index
@@ -1110,12 +3721,13 @@ fn format_index(index: &[usize]) -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::models::TaskStatus;
     use clap::Parser;
     use pretty_assertions::assert_eq;
 
     #[test]
     fn test_get_guide_string_formatting() {
-        let guide = get_guide_string();
+        let guide = crate::guide::get_guide_string(GuideMode::Cli);
         let env_var = PLAN_ID_ENV_VAR; // Use the constant value directly
 
         // Check specific formatted parts by searching for the final expected string
@@ -1185,10 +3797,13 @@ mod tests {
                     description,
                     level,
                     notes,
+                    template,
+                    ..
                 } => {
-                    assert_eq!(description, "New task desc");
-                    assert_eq!(level, 0);
-                    assert_eq!(notes, "Some notes here");
+                    assert_eq!(description.as_deref(), Some("New task desc"));
+                    assert_eq!(level, Some(0));
+                    assert_eq!(notes.as_deref(), Some("Some notes here"));
+                    assert_eq!(template, None);
                 }
                 _ => panic!("Expected TaskCommands::Add"),
             },
@@ -1201,8 +3816,9 @@ mod tests {
         match cli_view.command {
             Commands::Task { command } => match command {
                 TaskCommands::Notes { command: notes_cmd } => match notes_cmd {
-                    TaskNotesSubcommand::View { index } => {
+                    TaskNotesSubcommand::View { index, section } => {
                         assert_eq!(index, "0,1");
+                        assert_eq!(section, None);
                     }
                     _ => panic!("Expected TaskNotesSubcommand::View"),
                 },
@@ -1224,9 +3840,39 @@ mod tests {
         match cli_set.command {
             Commands::Task { command } => match command {
                 TaskCommands::Notes { command: notes_cmd } => match notes_cmd {
-                    TaskNotesSubcommand::Set { index, notes } => {
+                    TaskNotesSubcommand::Set {
+                        index,
+                        notes,
+                        section,
+                    } => {
                         assert_eq!(index, "1");
                         assert_eq!(notes, "New notes content");
+                        assert_eq!(section, None);
+                    }
+                    _ => panic!("Expected TaskNotesSubcommand::Set"),
+                },
+                _ => panic!("Expected TaskCommands::Notes"),
+            },
+            _ => panic!("Expected Commands::Task"),
+        }
+
+        // Test task notes set with a section
+        let args_section = vec![
+            "scatterbrain",
+            "task",
+            "notes",
+            "set",
+            "1",
+            "Use a BTreeMap",
+            "--section",
+            "decisions",
+        ];
+        let cli_section = try_parse_args(&args_section).unwrap();
+        match cli_section.command {
+            Commands::Task { command } => match command {
+                TaskCommands::Notes { command: notes_cmd } => match notes_cmd {
+                    TaskNotesSubcommand::Set { section, .. } => {
+                        assert_eq!(section.as_deref(), Some("decisions"));
                     }
                     _ => panic!("Expected TaskNotesSubcommand::Set"),
                 },
@@ -1235,13 +3881,30 @@ mod tests {
             _ => panic!("Expected Commands::Task"),
         }
 
+        // Test task notes append
+        let args_append = vec!["scatterbrain", "task", "notes", "append", "0", "Finding"];
+        let cli_append = try_parse_args(&args_append).unwrap();
+        match cli_append.command {
+            Commands::Task { command } => match command {
+                TaskCommands::Notes { command: notes_cmd } => match notes_cmd {
+                    TaskNotesSubcommand::Append { index, notes, .. } => {
+                        assert_eq!(index, "0");
+                        assert_eq!(notes, "Finding");
+                    }
+                    _ => panic!("Expected TaskNotesSubcommand::Append"),
+                },
+                _ => panic!("Expected TaskCommands::Notes"),
+            },
+            _ => panic!("Expected Commands::Task"),
+        }
+
         // Test task notes delete
         let args_delete = vec!["scatterbrain", "task", "notes", "delete", "0,0,0"];
         let cli_delete = try_parse_args(&args_delete).unwrap();
         match cli_delete.command {
             Commands::Task { command } => match command {
                 TaskCommands::Notes { command: notes_cmd } => match notes_cmd {
-                    TaskNotesSubcommand::Delete { index } => {
+                    TaskNotesSubcommand::Delete { index, .. } => {
                         assert_eq!(index, "0,0,0");
                     }
                     _ => panic!("Expected TaskNotesSubcommand::Delete"),
@@ -1252,6 +3915,787 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_cli_question_parsing() {
+        let args = vec!["scatterbrain", "question", "answer", "3", "Use Postgres"];
+        let cli = try_parse_args(&args).unwrap();
+        match cli.command {
+            Commands::Question { command } => match command {
+                QuestionCommands::Answer { id, answer } => {
+                    assert_eq!(id, 3);
+                    assert_eq!(answer, "Use Postgres");
+                }
+                _ => panic!("Expected QuestionCommands::Answer"),
+            },
+            _ => panic!("Expected Commands::Question"),
+        }
+    }
+
+    #[test]
+    fn test_failure_exit_codes() {
+        let failure = |error: Box<dyn std::error::Error>| Failure::of(error.as_ref());
+
+        let lease = TaskError::LeaseMismatch {
+            index: vec![0],
+            provided: Some(1),
+            required: 2,
+        };
+        assert_eq!(failure(ClientError::Task(lease).into()), Failure::Lease);
+        assert_eq!(
+            failure(ClientError::PlanNotFound(PlanId::new(7)).into()),
+            Failure::NotFound
+        );
+        assert_eq!(
+            failure(
+                ClientError::Http {
+                    status: reqwest::StatusCode::CONFLICT,
+                    message: "conflict".to_string()
+                }
+                .into()
+            ),
+            Failure::Conflict
+        );
+        assert_eq!(
+            failure(PlanError::Task(TaskError::SummaryRequired { index: vec![0] }).into()),
+            Failure::Invalid
+        );
+        assert_eq!(failure(parse_index("0,x").unwrap_err()), Failure::Invalid);
+        let task = "t-nothex".parse::<TaskRef>().unwrap_err();
+        assert_eq!(failure(task.into()), Failure::Invalid);
+        assert_eq!(failure("anything else".into()), Failure::Other);
+        assert_eq!(Failure::Network as u8, 7);
+    }
+
+    #[test]
+    fn test_expand_aliases() {
+        let config: CliConfig = toml::from_str(
+            r#"
+            [aliases]
+            c = "task complete --current"
+            a = "task add --level"
+            current = "task complete --current"
+            "#,
+        )
+        .unwrap();
+        let expand = |args: &[&str]| {
+            let args = args.iter().map(OsString::from).collect();
+            expand_aliases(args, &config.aliases)
+        };
+
+        assert_eq!(
+            expand(&["scatterbrain", "--plan", "3", "c", "--summary", "Done"]),
+            [
+                "scatterbrain",
+                "--plan",
+                "3",
+                "task",
+                "complete",
+                "--current",
+                "--summary",
+                "Done"
+            ]
+        );
+        assert_eq!(
+            expand(&["scatterbrain", "a", "1", "Parse input"]),
+            ["scatterbrain", "task", "add", "--level", "1", "Parse input"]
+        );
+        // Built-in commands win over aliases, and unknown words are left for clap to reject
+        assert_eq!(
+            expand(&["scatterbrain", "current"]),
+            ["scatterbrain", "current"]
+        );
+        assert_eq!(expand(&["scatterbrain", "x"]), ["scatterbrain", "x"]);
+
+        // Every global option's value is skipped, however the option is given
+        for global in [
+            ["-s", "http://planner:3000"],
+            ["--server", "http://planner:3000"],
+            ["--token", "secret"],
+            ["--session", "s-1"],
+            ["--agent", "reviewer"],
+            ["--workspace", "infra"],
+            ["--fallback-server", "http://standby:3000"],
+        ] {
+            let mut args = vec!["scatterbrain"];
+            args.extend(global);
+            args.push("c");
+            let mut expected = args[..3].to_vec();
+            expected.extend(["task", "complete", "--current"]);
+            assert_eq!(expand(&args), expected, "{global:?}");
+        }
+        assert_eq!(
+            expand(&["scatterbrain", "--workspace=infra", "c"]),
+            [
+                "scatterbrain",
+                "--workspace=infra",
+                "task",
+                "complete",
+                "--current"
+            ]
+        );
+
+        let cli = Cli::parse_from(expand(&["scatterbrain", "c", "--force"]));
+        assert!(matches!(
+            cli.command,
+            Commands::Task {
+                command: TaskCommands::Complete {
+                    current: true,
+                    force: true,
+                    ..
+                }
+            }
+        ));
+    }
+
+    #[test]
+    fn test_cli_plan_show_filter_parsing() {
+        let cli = Cli::parse_from([
+            "scatterbrain",
+            "plan",
+            "show",
+            "--incomplete-only",
+            "--depth",
+            "2",
+            "--subtree",
+            "0,2",
+        ]);
+        match cli.command {
+            Commands::PlanCmd(PlanCommands::Show {
+                incomplete_only,
+                level,
+                depth,
+                subtree,
+                at,
+                ..
+            }) => {
+                assert!(incomplete_only);
+                assert_eq!(at, None);
+                assert_eq!(level, None);
+                assert_eq!(depth, Some(2));
+                assert_eq!(subtree.as_deref(), Some("0,2"));
+            }
+            _ => panic!("Expected plan show command"),
+        }
+
+        let cli = Cli::parse_from(["scatterbrain", "plan", "show", "--sort", "recent"]);
+        assert!(matches!(
+            cli.command,
+            Commands::PlanCmd(PlanCommands::Show {
+                sort: TaskSort::Recent,
+                ..
+            })
+        ));
+        // Filtered listings aren't a tree to sort
+        assert!(Cli::try_parse_from([
+            "scatterbrain",
+            "plan",
+            "show",
+            "--sort",
+            "recent",
+            "--incomplete-only"
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn test_cli_plan_export_import_parsing() {
+        let cli = Cli::parse_from(["scatterbrain", "plan", "export", "7", "--file", "plan.json"]);
+        match cli.command {
+            Commands::PlanCmd(PlanCommands::Export { id, file, format }) => {
+                assert_eq!(id, 7);
+                assert_eq!(file, Some(PathBuf::from("plan.json")));
+                assert!(matches!(format, PlanExportFormat::Json));
+            }
+            _ => panic!("Expected plan export command"),
+        }
+
+        let cli = Cli::parse_from(["scatterbrain", "plan", "export", "7", "--format", "dot"]);
+        assert!(matches!(
+            cli.command,
+            Commands::PlanCmd(PlanCommands::Export {
+                format: PlanExportFormat::Dot,
+                ..
+            })
+        ));
+
+        let cli = Cli::parse_from(["scatterbrain", "plan", "import", "--file", "plan.json"]);
+        match cli.command {
+            Commands::PlanCmd(PlanCommands::Import { file }) => {
+                assert_eq!(file, PathBuf::from("plan.json"))
+            }
+            _ => panic!("Expected plan import command"),
+        }
+        assert!(try_parse_args(&["scatterbrain", "plan", "import"]).is_err());
+    }
+
+    #[test]
+    fn test_cli_task_show_parsing() {
+        let cli = Cli::parse_from(["scatterbrain", "task", "show", "t-3f9a2c1b"]);
+        match cli.command {
+            Commands::Task {
+                command: TaskCommands::Show { index },
+            } => assert_eq!(index.parse::<TaskRef>().unwrap().to_string(), "t-3f9a2c1b"),
+            _ => panic!("Expected task show command"),
+        }
+    }
+
+    #[test]
+    fn test_cli_plan_edit_parsing() {
+        let cli = Cli::parse_from(["scatterbrain", "plan", "edit", "--goal", "Ship v2"]);
+        match cli.command {
+            Commands::PlanCmd(PlanCommands::Edit { goal, notes }) => {
+                assert_eq!(goal.as_deref(), Some("Ship v2"));
+                assert_eq!(notes, None);
+            }
+            _ => panic!("Expected plan edit command"),
+        }
+        assert!(try_parse_args(&["scatterbrain", "plan", "edit", "--notes", ""]).is_ok());
+        assert!(try_parse_args(&["scatterbrain", "plan", "edit"]).is_err());
+    }
+
+    #[test]
+    fn test_cli_plan_tombstones_parsing() {
+        let cli = Cli::parse_from(["scatterbrain", "plan", "tombstones"]);
+        assert!(matches!(
+            cli.command,
+            Commands::PlanCmd(PlanCommands::Tombstones { off: false })
+        ));
+        let cli = Cli::parse_from(["scatterbrain", "plan", "tombstones", "--off"]);
+        assert!(matches!(
+            cli.command,
+            Commands::PlanCmd(PlanCommands::Tombstones { off: true })
+        ));
+    }
+
+    #[test]
+    fn test_cli_task_priority_parsing() {
+        let cli = Cli::parse_from(["scatterbrain", "task", "set-priority", "0,1", "2"]);
+        match cli.command {
+            Commands::Task {
+                command: TaskCommands::SetPriority { index, priority },
+            } => {
+                assert_eq!(index, "0,1");
+                assert_eq!(priority, Some(2));
+            }
+            _ => panic!("Expected task set-priority command"),
+        }
+        assert!(Cli::try_parse_from(["scatterbrain", "task", "set-priority", "0", "10"]).is_err());
+
+        let cli = Cli::parse_from(["scatterbrain", "plan", "order", "priority"]);
+        assert!(matches!(
+            cli.command,
+            Commands::PlanCmd(PlanCommands::Order {
+                order: TaskOrder::Priority
+            })
+        ));
+    }
+
+    #[test]
+    fn test_cli_effort_parsing() {
+        let cli = Cli::parse_from(["scatterbrain", "task", "estimate", "0,1", "90"]);
+        assert!(matches!(
+            cli.command,
+            Commands::Task {
+                command: TaskCommands::Estimate { ref index, minutes: Some(90) }
+            } if index == "0,1"
+        ));
+        let cli = Cli::parse_from(["scatterbrain", "task", "log-time", "0,1", "25"]);
+        assert!(matches!(
+            cli.command,
+            Commands::Task {
+                command: TaskCommands::LogTime { minutes: 25, .. }
+            }
+        ));
+        assert!(Cli::try_parse_from(["scatterbrain", "task", "log-time", "0"]).is_err());
+
+        let cli = Cli::parse_from(["scatterbrain", "plan", "effort"]);
+        assert!(matches!(
+            cli.command,
+            Commands::PlanCmd(PlanCommands::Effort)
+        ));
+    }
+
+    #[test]
+    fn test_cli_status_parsing() {
+        let cli = Cli::parse_from(["scatterbrain", "task", "start", "0,1"]);
+        assert!(matches!(
+            cli.command,
+            Commands::Task {
+                command: TaskCommands::Start { ref index }
+            } if index == "0,1"
+        ));
+
+        let cli = Cli::parse_from(["scatterbrain", "plan", "policy", "--transitions", "kanban"]);
+        match cli.command {
+            Commands::PlanCmd(PlanCommands::Policy { transitions, .. }) => {
+                assert_eq!(
+                    transitions,
+                    Some(TransitionRules(StatusTransition::kanban()))
+                )
+            }
+            _ => panic!("Expected plan policy command"),
+        }
+        let cli = Cli::parse_from([
+            "scatterbrain",
+            "plan",
+            "policy",
+            "--transitions",
+            "todo>in_progress, in_progress>done",
+        ]);
+        match cli.command {
+            Commands::PlanCmd(PlanCommands::Policy { transitions, .. }) => assert_eq!(
+                transitions,
+                Some(TransitionRules(vec![
+                    StatusTransition {
+                        from: TaskStatus::Todo,
+                        to: TaskStatus::InProgress
+                    },
+                    StatusTransition {
+                        from: TaskStatus::InProgress,
+                        to: TaskStatus::Done
+                    },
+                ]))
+            ),
+            _ => panic!("Expected plan policy command"),
+        }
+        let cli = Cli::parse_from(["scatterbrain", "plan", "policy", "--transitions", "any"]);
+        assert!(matches!(
+            cli.command,
+            Commands::PlanCmd(PlanCommands::Policy { transitions: Some(ref t), .. }) if t.0.is_empty()
+        ));
+        assert!(Cli::try_parse_from([
+            "scatterbrain",
+            "plan",
+            "policy",
+            "--transitions",
+            "todo>finished"
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn test_cli_search_parsing() {
+        let cli = Cli::parse_from(["scatterbrain", "search", "recursive", "parser"]);
+        match cli.command {
+            Commands::Search { query } => assert_eq!(query, vec!["recursive", "parser"]),
+            _ => panic!("Expected search command"),
+        }
+        assert!(Cli::try_parse_from(["scatterbrain", "search"]).is_err());
+    }
+
+    #[test]
+    fn test_cli_task_tag_parsing() {
+        let cli = Cli::parse_from(["scatterbrain", "task", "tag", "add", "0,1", "backend", "db"]);
+        match cli.command {
+            Commands::Task {
+                command:
+                    TaskCommands::Tag {
+                        command: TaskTagSubcommand::Add { index, tags },
+                    },
+            } => {
+                assert_eq!(index, "0,1");
+                assert_eq!(tags, vec!["backend", "db"]);
+            }
+            _ => panic!("Expected task tag add command"),
+        }
+        assert!(Cli::try_parse_from(["scatterbrain", "task", "tag", "remove", "0"]).is_err());
+
+        let cli = Cli::parse_from(["scatterbrain", "task", "tag", "list"]);
+        assert!(matches!(
+            cli.command,
+            Commands::Task {
+                command: TaskCommands::Tag {
+                    command: TaskTagSubcommand::List { index: None }
+                }
+            }
+        ));
+
+        let cli = Cli::parse_from(["scatterbrain", "plan", "show", "--tag", "backend"]);
+        match cli.command {
+            Commands::PlanCmd(PlanCommands::Show { tag, .. }) => {
+                assert_eq!(tag.as_deref(), Some("backend"))
+            }
+            _ => panic!("Expected plan show command"),
+        }
+        assert!(Cli::try_parse_from([
+            "scatterbrain",
+            "plan",
+            "show",
+            "--tag",
+            "backend",
+            "--at",
+            "2026-01-01T00:00:00Z",
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn test_cli_task_depend_parsing() {
+        let cli = Cli::parse_from(["scatterbrain", "task", "depend", "1", "0,2", "--remove"]);
+        match cli.command {
+            Commands::Task {
+                command: TaskCommands::Depend { index, on, remove },
+            } => {
+                assert_eq!((index.as_str(), on.as_str()), ("1", "0,2"));
+                assert!(remove);
+            }
+            _ => panic!("Expected task depend command"),
+        }
+    }
+
+    #[test]
+    fn test_cli_task_edit_parsing() {
+        let cli = Cli::parse_from(["scatterbrain", "task", "edit", "0,1", "Fix the parser"]);
+        match cli.command {
+            Commands::Task {
+                command: TaskCommands::Edit { index, description },
+            } => assert_eq!(
+                (index.as_str(), description.as_str()),
+                ("0,1", "Fix the parser")
+            ),
+            _ => panic!("Expected task edit command"),
+        }
+    }
+
+    #[test]
+    fn test_inherited_context() {
+        let core = Core::new();
+        let id = core.create_plan("Ship v2".to_string(), None).unwrap();
+        let (_, parent) = core
+            .add_task(&id, "Parser".to_string(), 0, Some("Use nom".to_string()))
+            .unwrap()
+            .into_inner();
+        let response = core.add_task(&id, "Lexer".to_string(), 0, None).unwrap();
+        assert_eq!(
+            inherited_context(&response.distilled_context, &response.inner().1),
+            Some("Plan goal: Ship v2".to_string())
+        );
+
+        core.move_to(&id, parent.clone()).unwrap();
+        let response = core.add_task(&id, "Tokens".to_string(), 1, None).unwrap();
+        let parent_id = core.get_plan(&id).unwrap().into_inner().root().subtasks()[0]
+            .id()
+            .clone();
+        assert_eq!(
+            inherited_context(&response.distilled_context, &response.inner().1),
+            Some(format!(
+                "Plan goal: Ship v2\nParent notes: see task 0 (scatterbrain task notes view {parent_id})"
+            ))
+        );
+    }
+
+    #[test]
+    fn test_cli_task_move_parsing() {
+        let cli = Cli::parse_from(["scatterbrain", "task", "move", "2", "--parent", "0,1"]);
+        match cli.command {
+            Commands::Task {
+                command: TaskCommands::Move { index, parent },
+            } => {
+                assert_eq!(index, "2");
+                assert_eq!(parent.as_deref(), Some("0,1"));
+            }
+            _ => panic!("Expected task move command"),
+        }
+
+        let cli = Cli::parse_from(["scatterbrain", "task", "move", "t-3f9a2c1b"]);
+        assert!(matches!(
+            cli.command,
+            Commands::Task {
+                command: TaskCommands::Move { parent: None, .. }
+            }
+        ));
+    }
+
+    #[test]
+    fn test_tree_rendering_helpers() {
+        assert_eq!(truncate("Implement parser", 16), "Implement parser");
+        assert_eq!(truncate("Implement parser", 10), "Implement…");
+        assert_eq!(truncate("✓✓✓", 2), "✓…");
+
+        assert_eq!(
+            tree_guides("│   ", false),
+            ("│   ├── ".to_string(), "│   │   ".to_string())
+        );
+        assert_eq!(
+            tree_guides("", true),
+            ("└── ".to_string(), "    ".to_string())
+        );
+    }
+
+    #[test]
+    fn test_format_age() {
+        assert_eq!(format_age(chrono::Duration::seconds(-5)), "just now");
+        assert_eq!(format_age(chrono::Duration::seconds(59)), "just now");
+        assert_eq!(format_age(chrono::Duration::minutes(42)), "42m ago");
+        assert_eq!(format_age(chrono::Duration::minutes(150)), "2h ago");
+        assert_eq!(format_age(chrono::Duration::days(9)), "9d ago");
+    }
+
+    #[test]
+    fn test_format_status_line() {
+        let mut progress = ProgressSummary {
+            status: PlanStatus::Active,
+            index: vec![0, 3, 1],
+            description: Some("Implement parser".to_string()),
+            completed: 12,
+            total: 30,
+            weighted_completed: 12,
+            weighted_total: 30,
+        };
+        assert_eq!(
+            format_status_line(42, &progress),
+            "plan 42 • 0,3,1 • Implement parser • 12/30 done"
+        );
+
+        progress.index.clear();
+        progress.description = None;
+        assert_eq!(
+            format_status_line(42, &progress),
+            "plan 42 • root • 12/30 done"
+        );
+
+        progress.weighted_total = 48;
+        assert_eq!(
+            format_status_line(42, &progress),
+            "plan 42 • root • 12/30 done (25% by weight)"
+        );
+    }
+
+    #[test]
+    fn test_cli_complete_current_parsing() {
+        let cli =
+            try_parse_args(&["scatterbrain", "task", "complete", "--current", "--force"]).unwrap();
+        match cli.command {
+            Commands::Task {
+                command:
+                    TaskCommands::Complete {
+                        index,
+                        current,
+                        force,
+                        ..
+                    },
+            } => {
+                assert_eq!(index, None);
+                assert!(current);
+                assert!(force);
+            }
+            _ => panic!("Expected TaskCommands::Complete"),
+        }
+
+        // One of --index or --current is required, but not both
+        assert!(try_parse_args(&["scatterbrain", "task", "complete", "--force"]).is_err());
+        assert!(try_parse_args(&[
+            "scatterbrain",
+            "task",
+            "complete",
+            "--current",
+            "--index",
+            "0"
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn test_cli_template_parsing() {
+        let args = vec![
+            "scatterbrain",
+            "task",
+            "add",
+            "--template",
+            "api-endpoint",
+            "--param",
+            "name=users",
+            "--param",
+            "method=GET",
+        ];
+        let cli = try_parse_args(&args).unwrap();
+        match cli.command {
+            Commands::Task {
+                command:
+                    TaskCommands::Add {
+                        description,
+                        template,
+                        params,
+                        ..
+                    },
+            } => {
+                assert_eq!(description, None);
+                assert_eq!(template.as_deref(), Some("api-endpoint"));
+                assert_eq!(
+                    params,
+                    vec![
+                        ("name".to_string(), "users".to_string()),
+                        ("method".to_string(), "GET".to_string())
+                    ]
+                );
+            }
+            _ => panic!("Expected TaskCommands::Add"),
+        }
+
+        // Without a template, description, level and notes are still required
+        assert!(try_parse_args(&["scatterbrain", "task", "add", "desc", "--level", "0"]).is_err());
+        // Params only make sense with a template, and must be key=value
+        assert!(try_parse_args(&[
+            "scatterbrain",
+            "task",
+            "add",
+            "desc",
+            "--level",
+            "0",
+            "--notes",
+            "n",
+            "--param",
+            "name=users"
+        ])
+        .is_err());
+        assert!(try_parse_args(&[
+            "scatterbrain",
+            "task",
+            "add",
+            "--template",
+            "t",
+            "--param",
+            "users"
+        ])
+        .is_err());
+
+        let args = vec![
+            "scatterbrain",
+            "template",
+            "add",
+            "api-endpoint",
+            "Add /{name} endpoint",
+            "--level",
+            "2",
+            "--checklist",
+            "Handler",
+            "--checklist",
+            "Tests",
+        ];
+        let cli = try_parse_args(&args).unwrap();
+        match cli.command {
+            Commands::Template {
+                command:
+                    TemplateCommands::Add {
+                        name,
+                        level,
+                        checklist,
+                        ..
+                    },
+            } => {
+                assert_eq!(name, "api-endpoint");
+                assert_eq!(level, 2);
+                assert_eq!(checklist, vec!["Handler", "Tests"]);
+            }
+            _ => panic!("Expected TemplateCommands::Add"),
+        }
+    }
+
+    #[test]
+    fn test_cli_admin_parsing() {
+        let args = vec![
+            "scatterbrain",
+            "admin",
+            "compact",
+            "--keep-note-versions",
+            "2",
+        ];
+        let cli = try_parse_args(&args).unwrap();
+        match cli.command {
+            Commands::Admin {
+                command: AdminCommands::Compact { keep_note_versions },
+            } => assert_eq!(keep_note_versions, 2),
+            _ => panic!("Expected AdminCommands::Compact"),
+        }
+
+        let args = vec!["scatterbrain", "admin", "export"];
+        let cli = try_parse_args(&args).unwrap();
+        match cli.command {
+            Commands::Admin {
+                command: AdminCommands::Export { file },
+            } => assert_eq!(file, None),
+            _ => panic!("Expected AdminCommands::Export"),
+        }
+    }
+
+    #[test]
+    fn test_cli_serve_parsing() {
+        let args = vec![
+            "scatterbrain",
+            "serve",
+            "--config",
+            "custom.toml",
+            "--cors-origin",
+            "http://a.test",
+            "--cors-origin",
+            "http://b.test",
+            "--metrics",
+        ];
+        let cli = try_parse_args(&args).unwrap();
+        match cli.command {
+            Commands::Serve {
+                config,
+                port,
+                cors_origins,
+                metrics,
+                sse_keep_alive_secs,
+                ..
+            } => {
+                assert_eq!(config, Some(std::path::PathBuf::from("custom.toml")));
+                // Unset flags stay None so the config file value wins
+                assert_eq!(port, None);
+                assert_eq!(sse_keep_alive_secs, None);
+                assert_eq!(cors_origins, vec!["http://a.test", "http://b.test"]);
+                assert!(metrics);
+            }
+            _ => panic!("Expected Commands::Serve"),
+        }
+    }
+
+    #[test]
+    fn test_cli_example_flags() {
+        let args = [
+            "scatterbrain",
+            "serve",
+            "--example",
+            "--example-size",
+            "large",
+            "--example-seed",
+            "42",
+        ];
+        match try_parse_args(&args).unwrap().command {
+            Commands::Serve { example, .. } => assert_eq!(
+                example.config(),
+                Some(ExampleConfig {
+                    size: ExampleSize::Large,
+                    seed: 42
+                })
+            ),
+            _ => panic!("Expected Commands::Serve"),
+        }
+
+        match try_parse_args(&["scatterbrain", "rpc", "--example"])
+            .unwrap()
+            .command
+        {
+            Commands::Rpc { example, .. } => {
+                assert_eq!(example.config(), Some(ExampleConfig::default()))
+            }
+            _ => panic!("Expected Commands::Rpc"),
+        }
+
+        // The size and seed only make sense with --example
+        assert!(try_parse_args(&["scatterbrain", "mcp", "--example-size", "small"]).is_err());
+        assert!(try_parse_args(&[
+            "scatterbrain",
+            "serve",
+            "--example",
+            "--example-size",
+            "huge"
+        ])
+        .is_err());
+    }
+
     #[test]
     fn test_cli_mcp_expose_flag() {
         // Test MCP command without expose flag
@@ -1259,7 +4703,7 @@ mod tests {
         let cli_no_expose = try_parse_args(&args_no_expose).unwrap();
         match cli_no_expose.command {
             Commands::Mcp { example, expose } => {
-                assert!(example);
+                assert!(example.example);
                 assert_eq!(expose, None);
             }
             _ => panic!("Expected Commands::Mcp"),
@@ -1270,7 +4714,7 @@ mod tests {
         let cli_with_expose = try_parse_args(&args_with_expose).unwrap();
         match cli_with_expose.command {
             Commands::Mcp { example, expose } => {
-                assert!(example);
+                assert!(example.example);
                 assert_eq!(expose, Some(8080));
             }
             _ => panic!("Expected Commands::Mcp"),
@@ -1281,7 +4725,7 @@ mod tests {
         let cli_only_expose = try_parse_args(&args_only_expose).unwrap();
         match cli_only_expose.command {
             Commands::Mcp { example, expose } => {
-                assert!(!example);
+                assert!(!example.example);
                 assert_eq!(expose, Some(3001));
             }
             _ => panic!("Expected Commands::Mcp"),
@@ -0,0 +1,276 @@
+//! Example plans
+//!
+//! `--example` fills a plan with a generated task tree for demos, UI testing and load
+//! testing. Trees are random but reproducible: the same [`ExampleConfig`] always builds
+//! the same tree, with descriptions, notes and completion summaries drawn from a fixed
+//! vocabulary and some areas of the plan further along than others.
+
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::models::{Index, PlanError, PlanId};
+use crate::spec::TaskSpec;
+use crate::Core;
+
+const VERBS: [&str; 8] = [
+    "Design",
+    "Implement",
+    "Test",
+    "Document",
+    "Refactor",
+    "Review",
+    "Migrate",
+    "Benchmark",
+];
+
+const SUBJECTS: [&str; 12] = [
+    "authentication",
+    "billing",
+    "search index",
+    "API gateway",
+    "session storage",
+    "notification service",
+    "admin dashboard",
+    "data importer",
+    "rate limiter",
+    "audit log",
+    "caching layer",
+    "onboarding flow",
+];
+
+const SUMMARIES: [&str; 4] = [
+    "Landed with unit tests",
+    "Merged after review",
+    "Verified in staging",
+    "Covered by integration tests",
+];
+
+/// Share of tasks that get notes
+const NOTES_PROBABILITY: f64 = 0.3;
+
+/// How big a generated example tree is
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExampleSize {
+    /// Three levels deep, up to 3 subtasks per task: a few dozen tasks at most
+    #[default]
+    Small,
+    /// Four levels deep, 2 to 4 subtasks per task
+    Medium,
+    /// Five levels deep, 3 to 6 subtasks per task: around two thousand tasks
+    Large,
+}
+
+impl ExampleSize {
+    /// Levels of tasks, and how many subtasks each task above the bottom level has
+    fn shape(self) -> (usize, RangeInclusive<usize>) {
+        match self {
+            ExampleSize::Small => (3, 1..=3),
+            ExampleSize::Medium => (4, 2..=4),
+            ExampleSize::Large => (5, 3..=6),
+        }
+    }
+}
+
+impl fmt::Display for ExampleSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ExampleSize::Small => "small",
+            ExampleSize::Medium => "medium",
+            ExampleSize::Large => "large",
+        };
+        write!(f, "{name}")
+    }
+}
+
+impl FromStr for ExampleSize {
+    type Err = String;
+
+    /// Parses a size name case-insensitively.
+    ///
+    /// # Examples
+    /// ```
+    /// # use scatterbrain::examples::ExampleSize;
+    /// assert_eq!("Large".parse::<ExampleSize>(), Ok(ExampleSize::Large));
+    /// assert!("huge".parse::<ExampleSize>().is_err());
+    /// ```
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "small" => Ok(ExampleSize::Small),
+            "medium" => Ok(ExampleSize::Medium),
+            "large" => Ok(ExampleSize::Large),
+            other => Err(format!(
+                "Unknown example size '{other}' (expected small, medium or large)"
+            )),
+        }
+    }
+}
+
+/// What example tree to generate
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ExampleConfig {
+    pub size: ExampleSize,
+    /// Seed the tree is generated from; the same seed builds the same tree
+    pub seed: u64,
+}
+
+/// The root-level tasks of an example tree for a plan with `levels` levels. Levels below
+/// the most detailed one reuse it.
+///
+/// ```
+/// # use scatterbrain::examples::{example_tasks, ExampleConfig, ExampleSize};
+/// let config = ExampleConfig { size: ExampleSize::Medium, seed: 42 };
+/// assert_eq!(example_tasks(&config, 4), example_tasks(&config, 4));
+/// ```
+pub fn example_tasks(config: &ExampleConfig, levels: usize) -> Vec<TaskSpec> {
+    let mut rng = StdRng::seed_from_u64(config.seed);
+    let (depth, fanout) = config.size.shape();
+    let max_level = levels.saturating_sub(1);
+    let count = rng.gen_range(fanout.clone());
+    (0..count)
+        .map(|_| {
+            // Each area of the plan gets its own pace, so some are nearly done
+            let progress = rng.gen_range(0.0..=1.0);
+            generate(&mut rng, depth - 1, &fanout, max_level, 0, progress)
+        })
+        .collect()
+}
+
+/// A task at `level` with `below` levels of subtasks under it. Tasks without subtasks are
+/// completed with probability `progress`, and the others once all their subtasks are.
+fn generate(
+    rng: &mut StdRng,
+    below: usize,
+    fanout: &RangeInclusive<usize>,
+    max_level: usize,
+    level: usize,
+    progress: f64,
+) -> TaskSpec {
+    let pick = |rng: &mut StdRng, words: &[&'static str]| *words.choose(rng).unwrap();
+    let description = format!("{} {}", pick(rng, &VERBS), pick(rng, &SUBJECTS));
+    let notes = if rng.gen_bool(NOTES_PROBABILITY) {
+        Some(format!(
+            "Coordinate with the {} work before starting.",
+            pick(rng, &SUBJECTS)
+        ))
+    } else {
+        None
+    };
+    let tasks: Vec<TaskSpec> = if below == 0 {
+        Vec::new()
+    } else {
+        let count = rng.gen_range(fanout.clone());
+        (0..count)
+            .map(|_| generate(rng, below - 1, fanout, max_level, level + 1, progress))
+            .collect()
+    };
+    let completed = if tasks.is_empty() {
+        rng.gen_bool(progress)
+    } else {
+        tasks.iter().all(|task| task.completed)
+    };
+    let summary = completed.then(|| pick(rng, &SUMMARIES).to_string());
+    TaskSpec {
+        description,
+        level: level.min(max_level),
+        notes,
+        sections: Default::default(),
+        completed,
+        summary,
+        weight: None,
+        priority: None,
+        tags: Default::default(),
+        tasks,
+    }
+}
+
+/// Adds an example tree under the root of plan `id`, then moves the cursor to the first
+/// incomplete task without subtasks, where an agent would pick up the work
+pub fn populate(core: &Core, id: &PlanId, config: &ExampleConfig) -> Result<(), PlanError> {
+    let levels = core.get_plan(id)?.into_inner().level_count();
+    let tasks = example_tasks(config, levels);
+    let cursor = first_open_leaf(&tasks, &mut Vec::new());
+    for task in tasks {
+        core.import_subtree(id, Vec::new(), task)?;
+    }
+    if let Some(cursor) = cursor {
+        core.move_to(id, cursor)?;
+    }
+    Ok(())
+}
+
+fn first_open_leaf(tasks: &[TaskSpec], parent: &mut Index) -> Option<Index> {
+    for (n, task) in tasks.iter().enumerate() {
+        if task.completed {
+            continue;
+        }
+        parent.push(n);
+        if task.tasks.is_empty() {
+            return Some(parent.clone());
+        }
+        if let Some(leaf) = first_open_leaf(&task.tasks, parent) {
+            return Some(leaf);
+        }
+        parent.pop();
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_example_trees_are_reproducible() {
+        let config = |size, seed| ExampleConfig { size, seed };
+        let tasks = example_tasks(&config(ExampleSize::Medium, 42), 4);
+        assert_eq!(tasks, example_tasks(&config(ExampleSize::Medium, 42), 4));
+        assert_ne!(tasks, example_tasks(&config(ExampleSize::Medium, 43), 4));
+
+        // Every size keeps its depth, and only has summaries on completed tasks
+        for size in [ExampleSize::Small, ExampleSize::Medium, ExampleSize::Large] {
+            let tasks = example_tasks(&config(size, 7), 4);
+            let spec = TaskSpec {
+                description: "Root".to_string(),
+                level: 0,
+                notes: None,
+                sections: Default::default(),
+                completed: false,
+                summary: None,
+                weight: None,
+                priority: None,
+                tags: Default::default(),
+                tasks,
+            };
+            spec.check().unwrap();
+            let depth = spec.tasks().map(|(path, _)| path.split(',').count()).max();
+            assert_eq!(depth, Some(size.shape().0));
+        }
+    }
+
+    #[test]
+    fn test_populate_fills_the_plan_and_places_the_cursor() {
+        let core = Core::new();
+        let id = core.create_plan("Example".to_string(), None).unwrap();
+        let config = ExampleConfig {
+            size: ExampleSize::Small,
+            seed: 3,
+        };
+        populate(&core, &id, &config).unwrap();
+
+        let tasks = example_tasks(&config, 4);
+        let total = tasks.iter().map(|task| task.tasks().count()).sum::<usize>();
+        assert_eq!(core.progress(&id).unwrap().total, total);
+        let cursor = core.get_current_index(&id).unwrap().into_inner();
+        assert_eq!(
+            cursor,
+            first_open_leaf(&tasks, &mut Vec::new()).unwrap_or_default()
+        );
+    }
+}
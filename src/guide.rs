@@ -1,55 +1,217 @@
 //! Guide module for generating context-appropriate help content
 //!
 //! This module provides a unified way to generate guide content for different interfaces
-//! (CLI vs MCP) while keeping the content DRY through string interpolation.
+//! (CLI vs MCP vs HTTP) while keeping the content DRY through string interpolation.
+//!
+//! The guide is a list of sections. Organizations can add their own, such as branch
+//! naming or review rules, from a file named by `SCATTERBRAIN_GUIDE` (see [`GuideContent`]).
+
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+/// Environment variable naming a TOML file with custom guide sections
+pub const GUIDE_ENV_VAR: &str = "SCATTERBRAIN_GUIDE";
+
+/// Errors that can occur while loading custom guide content
+#[derive(Debug, thiserror::Error)]
+pub enum GuideError {
+    #[error("Failed to read guide file {}: {source}", path.display())]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("Failed to parse guide file {}: {source}", path.display())]
+    Parse {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+}
 
 /// Mode for guide generation
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum GuideMode {
     /// Command-line interface mode
     Cli,
     /// Model Context Protocol mode
     Mcp,
+    /// HTTP API mode
+    Http,
 }
 
-/// Generate a comprehensive guide string for the specified mode
+/// Generate a comprehensive guide string for the specified mode, from the built-in
+/// sections only
 pub fn get_guide_string(mode: GuideMode) -> String {
+    GuideContent::default().render(mode)
+}
+
+/// Generate the guide for the specified mode, with the custom sections from the file
+/// named by `SCATTERBRAIN_GUIDE`, if it is set
+pub fn load_guide_string(mode: GuideMode) -> Result<String, GuideError> {
+    let content = match std::env::var_os(GUIDE_ENV_VAR) {
+        Some(path) => GuideContent::load(Path::new(&path))?,
+        None => GuideContent::default(),
+    };
+    Ok(content.render(mode))
+}
+
+/// The built-in sections of the guide for the specified mode, in order
+pub fn builtin_sections(mode: GuideMode) -> Vec<String> {
     let config = match mode {
-        GuideMode::Cli => GuideConfig::cli(),
+        // The CLI is a thin wrapper over the HTTP API, so they share a guide
+        GuideMode::Cli | GuideMode::Http => GuideConfig::cli(),
         GuideMode::Mcp => GuideConfig::mcp(),
     };
 
-    format!(
-        r#"=== {title} ===
-
-{overview}
+    vec![
+        format!("=== {} ===", config.title),
+        get_overview_section().to_string(),
+        config.getting_started.clone(),
+        get_abstraction_levels_section().to_string(),
+        get_transitioning_levels_section().to_string(),
+        config.workflow_guide.clone(),
+        config.command_reference.clone(),
+        get_best_practices_section(&config),
+        config.additional_sections.clone(),
+        config.closing_message.to_string(),
+    ]
+}
 
-{getting_started}
+/// A custom section of the guide
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GuideSection {
+    /// Text of the section, by convention starting with an `== HEADING ==` line
+    pub text: String,
+    /// Modes whose guide shows the section; all of them when empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub modes: Vec<GuideMode>,
+}
 
-{abstraction_levels}
+/// What the guide is made of: the built-in sections, followed by custom ones
+///
+/// ```
+/// # use scatterbrain::guide::{GuideContent, GuideMode};
+/// let content = GuideContent::from_toml_str(r#"
+///     [[sections]]
+///     text = "== TEAM CONVENTIONS ==\nName branches plan/<id>-<task index>."
+///
+///     [[sections]]
+///     modes = ["mcp"]
+///     text = "== REVIEWS ==\nRequest a review instead of completing Level 0 tasks."
+/// "#).unwrap();
+/// let guide = content.render(GuideMode::Cli);
+/// assert!(guide.starts_with("=== SCATTERBRAIN GUIDE ==="));
+/// assert!(guide.ends_with("Name branches plan/<id>-<task index>."));
+/// assert!(!guide.contains("== REVIEWS =="));
+/// assert!(content.render(GuideMode::Mcp).contains("== REVIEWS =="));
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GuideContent {
+    /// Leave out the built-in sections, so the custom ones make up the whole guide
+    pub replace_builtin: bool,
+    /// Sections added after the built-in ones, in order
+    pub sections: Vec<GuideSection>,
+}
 
-{transitioning_levels}
+impl GuideContent {
+    /// Parses guide content from TOML text
+    pub fn from_toml_str(contents: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(contents)
+    }
 
-{workflow_guide}
+    /// Loads guide content from a TOML file
+    pub fn load(path: &Path) -> Result<Self, GuideError> {
+        let contents = std::fs::read_to_string(path).map_err(|source| GuideError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        Self::from_toml_str(&contents).map_err(|source| GuideError::Parse {
+            path: path.to_path_buf(),
+            source,
+        })
+    }
 
-{command_reference}
+    /// Renders the guide for the specified mode
+    pub fn render(&self, mode: GuideMode) -> String {
+        let builtin = if self.replace_builtin {
+            Vec::new()
+        } else {
+            builtin_sections(mode)
+        };
+        let custom = self
+            .sections
+            .iter()
+            .filter(|section| section.modes.is_empty() || section.modes.contains(&mode))
+            .map(|section| section.text.trim().to_string());
+        builtin
+            .into_iter()
+            .chain(custom)
+            .filter(|section| !section.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    }
+}
 
-{best_practices}
+/// Generate the short summary of scatterbrain included in every distilled context,
+/// naming the commands, tools or endpoints of the given mode
+pub fn usage_summary(mode: GuideMode) -> String {
+    let operations = match mode {
+        GuideMode::Cli => {
+            "Use 'task add' to add tasks, 'move <index>' to navigate, and 'task complete' to \
+             mark tasks as done. Use '--help' on any command (e.g., `scatterbrain task --help`) \
+             for more details."
+        }
+        GuideMode::Mcp => {
+            "Use the `add_task` tool to add tasks, `move_to` to navigate, and \
+             `complete_current_task` to mark tasks as done. Call `get_guide` for the full \
+             workflow."
+        }
+        GuideMode::Http => {
+            "POST to /api/plans/{id}/task to add tasks, /api/plans/{id}/move to navigate, and \
+             /api/plans/{id}/task/complete to mark tasks as done. The `scatterbrain` CLI wraps \
+             these endpoints; run `scatterbrain guide` for the full workflow."
+        }
+    };
+    format!(
+        "Scatterbrain is a hierarchical planning tool that helps break down complex tasks into \
+         manageable pieces. {operations} Tasks are organized in levels from high-level planning \
+         to specific implementation details."
+    )
+}
 
-{additional_sections}
+/// Usage summaries replacing the built-in ones, per mode, e.g. to point agents at an
+/// organization's own conventions
+///
+/// ```
+/// # use scatterbrain::guide::{GuideMode, UsageSummaries};
+/// let summaries: UsageSummaries = toml::from_str(r#"
+///     mcp = "Plan with scatterbrain; see CONTRIBUTING.md for branch naming."
+/// "#).unwrap();
+/// assert!(summaries.get(GuideMode::Mcp).contains("CONTRIBUTING.md"));
+/// assert!(summaries.get(GuideMode::Cli).contains("'task add'"));
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UsageSummaries {
+    pub cli: Option<String>,
+    pub mcp: Option<String>,
+    pub http: Option<String>,
+}
 
-{closing_message}"#,
-        title = config.title,
-        overview = get_overview_section(),
-        getting_started = config.getting_started,
-        abstraction_levels = get_abstraction_levels_section(),
-        transitioning_levels = get_transitioning_levels_section(),
-        workflow_guide = config.workflow_guide,
-        command_reference = config.command_reference,
-        best_practices = get_best_practices_section(&config),
-        additional_sections = config.additional_sections,
-        closing_message = config.closing_message
-    )
+impl UsageSummaries {
+    /// The summary for `mode`: its replacement if one is set, the built-in one otherwise
+    pub fn get(&self, mode: GuideMode) -> String {
+        let custom = match mode {
+            GuideMode::Cli => &self.cli,
+            GuideMode::Mcp => &self.mcp,
+            GuideMode::Http => &self.http,
+        };
+        custom.clone().unwrap_or_else(|| usage_summary(mode))
+    }
 }
 
 /// Configuration for different guide modes
@@ -126,7 +288,7 @@ Scatterbrain organizes work into separate "plans". Each command needs to know wh
      $ scatterbrain distilled
 
    - Complete tasks when finished:
-     $ scatterbrain task complete --summary "Implemented the feature"
+     $ scatterbrain task complete --current --summary "Implemented the feature"
 
    - Complete tasks requiring a lease:
      Some tasks require a 'lease' token for completion, ensuring only one agent
@@ -135,11 +297,13 @@ Scatterbrain organizes work into separate "plans". Each command needs to know wh
         $ scatterbrain task lease <INDEX>  # e.g., scatterbrain task lease 0,1,2
         > Generated lease 123 for task at index: 0,1,2
      2. Complete the task using the generated lease ID and provide a summary:
-        $ scatterbrain task complete --lease 123 --summary "Completed task with lease"
+        $ scatterbrain task complete --index 0,1,2 --lease 123 --summary "Completed task with lease"
 
      Note: If the lease doesn't match, completion will fail unless you use --force.
+     With --session (or SCATTERBRAIN_SESSION) set, a lease only works for that session, and
+     `task lease --single-use` makes it reject reuse once the task is completed.
      Using --force bypasses both lease and summary checks; use it sparingly.
-     $ scatterbrain task complete --force
+     $ scatterbrain task complete --current --force
 
    - Move between tasks to adapt to changing priorities:
      $ scatterbrain move 1,2
@@ -155,34 +319,118 @@ Scatterbrain organizes work into separate "plans". Each command needs to know wh
 GLOBAL FLAGS:
   --plan=<id>                                            Specify the plan ID for this command (overrides env var)
   --server=<url>                                         Specify the server URL (default: http://localhost:3000)
+  --token=<token>                                        Bearer token for servers that require one (or SCATTERBRAIN_TOKEN)
+  --session=<id>                                         Session leases are bound to (or SCATTERBRAIN_SESSION)
+  --agent=<name>                                         Agent name recorded in plan history (or SCATTERBRAIN_AGENT)
+  --no-color                                             Disable colored output (or set NO_COLOR)
+  --no-pager                                             Print long output directly instead of through $PAGER
 
 PLAN MANAGEMENT (scatterbrain plan ...):
   $ scatterbrain plan create "<prompt>" [--notes <TEXT>] Create a new plan. Use a short prompt/title and add details via --notes. Prints ID and guide.
+  $ scatterbrain plan create --from-spec <FILE>          Create a plan with its levels and tasks from a YAML spec
   $ scatterbrain plan delete <id>                        Delete a plan by its ID
   $ scatterbrain plan list                               List available plan IDs
   $ scatterbrain plan show                               View the full plan with all tasks
+  $ scatterbrain plan show --incomplete-only --depth 2   Show a filtered slice (also --level N, --subtree 0,2)
+  $ scatterbrain plan show --at <TIME>                   Show the plan as it was at a time (RFC 3339, or 12h ago)
+  $ scatterbrain plan show --sort recent                 List recently changed tasks first, stalled ones last
+  $ scatterbrain plan show --tag <TAG>                   Show only the tasks with a tag
+  $ scatterbrain plan set-status <STATUS> [--force]      Set plan status: draft, active, completed, abandoned
+                                                         (completed requires all tasks complete unless --force)
+  $ scatterbrain plan lock                               Make the current plan read-only
+  $ scatterbrain plan unlock                             Make a locked plan writable again
+  $ scatterbrain plan tombstones [--off]                 Keep sibling indices stable when tasks are removed
+  $ scatterbrain plan order <position|priority>          List subtasks in position or priority order
+  $ scatterbrain plan share --qr                         Print a join code and QR code for the plan's web UI
+  $ scatterbrain plan stats                              Task counts, per-level progress, reviews, leases and recent activity
+  $ scatterbrain plan effort                             Compare completed tasks' estimates with time logged
+  $ scatterbrain plan lint                               Check the plan for problems such as orphaned leases
+  $ scatterbrain plan summaries [--level 1]              Completion summaries grouped by component
+  $ scatterbrain plan changelog --from 1d [--to TIME]    Tasks completed in a window, as release notes
+  $ scatterbrain plan focus 0,1 [--strict]               Pin work to a task; --clear removes the focus
+  $ scatterbrain plan session start|stop|status          Time a work session with reminders to step back
+  $ scatterbrain plan policy [OPTIONS]                   Show or change how tasks may be completed and summarized
+  $ scatterbrain plan badge [--color] [--emoji]          Mark the plan with a color and emoji in lists and the UI
+  $ scatterbrain plan edit [--goal <GOAL>] [--notes <NOTES>] Replace the plan's goal or notes
+  $ scatterbrain plan usage-summary [<TEXT>] [--clear]   Show or replace the usage summary in the plan's context
+  $ scatterbrain plan history export [--format jsonl]    Print the plan's full transition log
 
 TASK MANAGEMENT (scatterbrain task ...):
   $ scatterbrain task add --level <LEVEL> --notes <TEXT> "Description" Create new task (level required, notes required)
                                                          Note: Adding a subtask marks parents incomplete.
-  $ scatterbrain task complete --index <INDEX> [--lease <ID>] [--force] [--summary <TEXT>] Complete task at specified index (summary required unless --force)
+                                                         --inherit-context records the plan goal and parent notes in it
+  $ scatterbrain task complete --index <INDEX> [--lease <ID>] [--force] [--cascade] [--summary <TEXT>] Complete task at specified index (summary required unless --force)
+  $ scatterbrain task complete --current [OPTIONS]       Complete the current task, same options as --index
   $ scatterbrain task change-level <LEVEL_INDEX>         Change current task's abstraction level
-  $ scatterbrain task lease <INDEX>                      Generate a lease for a task
+  $ scatterbrain task weight <INDEX> [WEIGHT]            Set how much a task counts toward progress (default 1)
+  $ scatterbrain task set-priority <INDEX> [PRIORITY]    Set how urgent a task is, 0 (most) to 9
+  $ scatterbrain task estimate <INDEX> [MINUTES]         Estimate a task's effort in minutes
+  $ scatterbrain task log-time <INDEX> <MINUTES>         Log minutes of work on a task
+  $ scatterbrain task tag add <INDEX> <TAG>...           Tag a task (also remove, and list [INDEX])
+  $ scatterbrain task lease <INDEX> [--single-use]       Generate a lease for a task
+  $ scatterbrain task edit <INDEX> <DESCRIPTION>         Replace a task's description, keeping its subtasks
   $ scatterbrain task remove <INDEX>                     Remove a task by its index (e.g., 0,1,2)
+  $ scatterbrain task move <INDEX> [--parent <INDEX>]    Move a task and its subtasks under another task
+  $ scatterbrain task depend <INDEX> <ON> [--remove]     Make a task wait for another before it can be completed
+  $ scatterbrain task start <INDEX>                      Mark a task as in progress
   $ scatterbrain task uncomplete <INDEX>                 Uncomplete a task by its index
+  $ scatterbrain task review <INDEX> --summary <TEXT>    Submit a task for review instead of completing it
+  $ scatterbrain task review-queue                       List tasks awaiting review
+  $ scatterbrain task approve <INDEX>                    Approve a reviewed task, marking it complete
+  $ scatterbrain task reject <INDEX> [--reason <TEXT>]   Reject a reviewed task, leaving it incomplete
   $ scatterbrain task notes view <INDEX>                 View notes for a specific task
   $ scatterbrain task notes set <INDEX> "<NOTES>"        Set notes for a specific task
+  $ scatterbrain task notes append <INDEX> "<TEXT>"      Append a timestamped block to a task's notes
   $ scatterbrain task notes delete <INDEX>               Delete notes for a specific task
+  $ scatterbrain task notes history <INDEX>              Show previous versions of a task's notes
+  $ scatterbrain task notes set <INDEX> "<TEXT>" --section <NAME> Set a named notes section (e.g., findings, decisions)
+                                                         --section also works with view, append and delete
+  $ scatterbrain task notes revert <INDEX> --version <N> Restore a previous version of a task's notes
+  $ scatterbrain task export <INDEX> [--format yaml]     Print a task and its subtasks to copy into another plan
+  $ scatterbrain task import [PARENT] --file <FILE>      Add an exported subtree under a task
+
+OPEN QUESTIONS (scatterbrain question ...):
+  $ scatterbrain question add "<QUESTION>"               Record an open question for the plan
+  $ scatterbrain question list                           List open and answered questions
+  $ scatterbrain question answer <ID> "<ANSWER>"         Answer a question, marking it answered
+  $ scatterbrain question remove <ID>                    Remove a question
+
+TEMPLATES (scatterbrain template ...):
+  $ scatterbrain template add <NAME> "<DESC>" --level <N> Save a template; text may use {param} placeholders
+                                                         Add --notes <TEXT> and --checklist <ITEM> (repeatable)
+  $ scatterbrain template list                           List templates on the server
+  $ scatterbrain template remove <NAME>                  Remove a template
+  $ scatterbrain task add --template <NAME> --param k=v  Create a task from a template
 
 NAVIGATION & VIEWING (scatterbrain ...):
   $ scatterbrain move <INDEX>                            Navigate to a task (e.g., 0 or 0,1,2)
   $ scatterbrain current                                 View details of the current task
+  $ scatterbrain status --short                          One-line progress summary for shell prompts
   $ scatterbrain distilled                               View a distilled context of your plan
+  $ scatterbrain distilled --sections <A,B>              Only include the named notes sections
+  $ scatterbrain search <QUERY>                          Find tasks by words in their descriptions, notes and summaries
 
 SERVER MANAGEMENT (scatterbrain serve ...):
   $ scatterbrain serve                                   Start API server (default port 3000)
   $ scatterbrain serve --port <PORT>                     Start API server on a custom port
   $ scatterbrain serve --example                         Start with example task tree (plan ID 0)
+  $ scatterbrain serve --example --example-size large --example-seed 42
+                                                         Start with a bigger, reproducible example tree
+  $ scatterbrain serve --webhook-url <URL>               POST plan events (e.g. plan completion) to a URL
+  $ scatterbrain serve --config <PATH>                   Load settings from a TOML file (default ./server.toml)
+  $ scatterbrain serve --storage-path <DIR>              Persist plans to a directory
+  $ scatterbrain serve --auth-token <TOKEN>              Require a bearer token on API requests
+
+ADMINISTRATION (scatterbrain admin ..., needs the server's admin token via --token):
+  $ scatterbrain admin unlock                            Release every lease on the current plan
+  $ scatterbrain admin gc-leases                         Release leases on missing or completed tasks
+  $ scatterbrain admin compact [--keep-note-versions <N>]
+                                                         Drop the transition log, trim notes history
+  $ scatterbrain admin export [<FILE>]                   Export all plans as a JSON archive
+  $ scatterbrain admin import <FILE>                     Import an archive, replacing plans with the same IDs
+  $ scatterbrain admin reload                            Make the server re-read its config file
+  $ scatterbrain backup <FILE.tar.gz>                    Back up all plans with histories and settings
+  $ scatterbrain restore <FILE>                          Restore plans from a backup archive
 
 HELP & UTILITIES (scatterbrain ...):
   $ scatterbrain guide                                   Show this guide
@@ -262,10 +510,13 @@ Scatterbrain organizes work into separate "plans". Each MCP tool call needs to s
    a) Add notes to tasks:
       mcp_scatterbrain_set_task_notes(plan_id=42, index="0,1", notes="Implementation details...")
       
-   b) View task notes:
+   b) Accumulate findings without overwriting existing notes:
+      mcp_scatterbrain_set_task_notes(plan_id=42, index="0,1", notes="Found the bug in parser.rs", append=true)
+
+   c) View task notes:
       mcp_scatterbrain_get_task_notes(plan_id=42, index="0,1")
       
-   c) Delete task notes:
+   d) Delete task notes:
       mcp_scatterbrain_delete_task_notes(plan_id=42, index="0,1")"#.to_string(),
             command_reference: r#"== MCP TOOL REFERENCE ==
 
@@ -274,24 +525,64 @@ PLAN MANAGEMENT:
   mcp_scatterbrain_delete_plan(plan_id)           Delete a plan by its ID
   mcp_scatterbrain_list_plans()                   List all available plan IDs
   mcp_scatterbrain_get_plan(plan_id)              Get full plan details
+  mcp_scatterbrain_update_plan(plan_id, goal?, notes?) Refine the plan's goal or notes
+  mcp_scatterbrain_set_plan_status(plan_id, status, force?) Set plan status: draft, active, completed, abandoned
 
 NAVIGATION & VIEWING:
   mcp_scatterbrain_get_current(plan_id)           Get details of the current task
-  mcp_scatterbrain_get_distilled_context(plan_id) Get distilled context of the plan
+  mcp_scatterbrain_get_progress(plan_id)          Get status, current task and task counts
+  mcp_scatterbrain_get_effort_report(plan_id)     Compare completed tasks' estimates with time logged
+  mcp_scatterbrain_get_distilled_context(plan_id, sections?) Get distilled context of the plan (sections="findings,decisions" limits notes sections)
   mcp_scatterbrain_move_to(plan_id, index)        Navigate to a specific task (e.g., "0,1,2")
+  mcp_scatterbrain_search_tasks(plan_id, query)   Find tasks by words in their text; search before adding a task
 
 TASK MANAGEMENT:
   mcp_scatterbrain_add_task(plan_id, description, level_index, notes?) Create new task at specified level
-  mcp_scatterbrain_complete_task(plan_id, index, lease?, force?, summary?) Complete a task
+  mcp_scatterbrain_complete_task(plan_id, index, lease?, force?, cascade?, summary?) Complete a task
+  mcp_scatterbrain_complete_current_task(plan_id, lease?, force?, cascade?, summary?) Complete the current task
+  mcp_scatterbrain_start_task(plan_id, index)     Mark a task as in progress
   mcp_scatterbrain_uncomplete_task(plan_id, index) Uncomplete a task
+  mcp_scatterbrain_get_task(plan_id, index)       Get a task by index or ID, with its current index
+  mcp_scatterbrain_update_task(plan_id, index, description) Replace a task's description
   mcp_scatterbrain_remove_task(plan_id, index)    Remove a task by its index
+  mcp_scatterbrain_move_task(plan_id, index, parent_index?) Move a task and its subtasks under another task
   mcp_scatterbrain_change_level(plan_id, index, level_index) Change task's abstraction level
-  mcp_scatterbrain_generate_lease(plan_id, index) Generate a lease token for task completion
+  mcp_scatterbrain_set_task_weight(plan_id, index, weight?) Set how much a task counts toward progress
+  mcp_scatterbrain_set_task_priority(plan_id, index, priority?) Set how urgent a task is, 0 (most) to 9
+  mcp_scatterbrain_set_task_estimate(plan_id, index, minutes?) Estimate a task's effort in minutes
+  mcp_scatterbrain_log_task_time(plan_id, index, minutes) Log minutes of work on a task
+  mcp_scatterbrain_tag_task(plan_id, index, tags) Tag a task (untag_task removes tags)
+  mcp_scatterbrain_generate_lease(plan_id, index, single_use?) Generate a lease token bound to this session
+  mcp_scatterbrain_export_subtree(plan_id, index) Export a task and its subtasks as a spec
+  mcp_scatterbrain_import_subtree(plan_id, subtree, parent_index?) Add an exported subtree under a task
+  mcp_scatterbrain_request_review(plan_id, index, summary) Submit a task for review instead of completing it
+  mcp_scatterbrain_get_review_queue(plan_id)      List tasks awaiting review
+  mcp_scatterbrain_get_summaries(plan_id, level?) Completion summaries grouped under each task at a level
+  mcp_scatterbrain_get_changelog(plan_id, from?, to?, format?) Tasks completed between two times
+  mcp_scatterbrain_list_tasks(plan_id, ...)       List tasks, filtered by completion, level, depth, subtree or tag
+  mcp_scatterbrain_approve_task(plan_id, index)   Approve a reviewed task, marking it complete
+  mcp_scatterbrain_reject_task(plan_id, index, reason?) Reject a reviewed task, leaving it incomplete
 
 NOTES MANAGEMENT:
   mcp_scatterbrain_get_task_notes(plan_id, index) Get notes for a specific task
-  mcp_scatterbrain_set_task_notes(plan_id, index, notes) Set notes for a specific task
+  mcp_scatterbrain_set_task_notes(plan_id, index, notes, append?) Set notes (append=true adds a timestamped block)
   mcp_scatterbrain_delete_task_notes(plan_id, index) Delete notes for a specific task
+  mcp_scatterbrain_get_note_sections(plan_id, index, section?) Get structured notes sections for a task
+  mcp_scatterbrain_set_note_section(plan_id, index, section, content?, append?) Set a notes section (omit content to remove it)
+  mcp_scatterbrain_get_notes_history(plan_id, index) Show previous versions of a task's notes
+  mcp_scatterbrain_revert_task_notes(plan_id, index, version) Restore a previous version of a task's notes
+
+OPEN QUESTIONS:
+  mcp_scatterbrain_add_question(plan_id, question) Record an open question for the plan
+  mcp_scatterbrain_list_questions(plan_id)        List open and answered questions
+  mcp_scatterbrain_answer_question(plan_id, question_id, answer) Answer a question
+  mcp_scatterbrain_remove_question(plan_id, question_id) Remove a question
+
+TEMPLATES:
+  mcp_scatterbrain_list_templates()               List task templates
+  mcp_scatterbrain_save_template(name, description, level_index, notes?, checklist?) Save a template
+  mcp_scatterbrain_add_task_from_template(plan_id, template, params?) Create a task from a template (params: ["key=value"])
+  mcp_scatterbrain_remove_template(name)          Remove a template
 
 HELP:
   mcp_scatterbrain_get_guide()                    Show this comprehensive guide"#.to_string(),
@@ -302,6 +593,10 @@ Task indices use comma-separated format to represent the hierarchical path:
 - "0,1" = Second subtask of the first top-level task
 - "0,1,2" = Third subtask of the second subtask of the first top-level task
 
+Every task also has an ID, e.g. "t-3f9a2c1b", shown as `id` wherever tasks are returned.
+It works anywhere an index does, and unlike an index it doesn't change when tasks before
+it are removed, so hold on to IDs of tasks you'll come back to.
+
 == TASK COMPLETION AND LEASES ==
 
 Some tasks may require a 'lease' token for completion, ensuring proper coordination:
@@ -309,6 +604,8 @@ Some tasks may require a 'lease' token for completion, ensuring proper coordinat
 1. Generate a lease:
    mcp_scatterbrain_generate_lease(plan_id=42, index="0,1,2")
    Returns: lease token (e.g., 123) and verification suggestions
+   The lease only works from this MCP session; pass single_use=true to have it
+   rejected if it is ever presented again after completing the task.
 
 2. Complete with lease:
    mcp_scatterbrain_complete_task(plan_id=42, index="0,1,2", lease=123, summary="Completed task")
@@ -6,10 +6,23 @@
 
 // Declare public modules
 pub mod api;
+pub mod bench;
 pub mod cli;
+pub mod examples;
 pub mod guide;
 pub mod levels;
 pub mod models;
+pub mod quotas;
+pub mod redaction;
+pub mod revisions;
+pub mod search;
+pub mod spec;
+pub mod suggestions;
+pub mod templates;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+pub mod validators;
+pub mod wasm;
 
 // Re-export the most commonly used types
 pub use api::serve;
@@ -0,0 +1,132 @@
+//! Resource quotas
+//!
+//! A server shared by several agents can cap how much its plans may hold, so one runaway
+//! agent can't exhaust it. [`Core`](crate::Core) checks its [`Quotas`] on every change:
+//! a change that would take a plan, or the number of plans, past a limit is rejected with
+//! [`TaskError::QuotaExceeded`] and leaves everything as it was. Plans already over a
+//! limit, e.g. after the limit was lowered, can still shrink.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::models::{PlanId, TaskError};
+
+/// Limits on what a [`Core`](crate::Core) holds; unset limits don't apply
+///
+/// ```
+/// # use scatterbrain::quotas::Quotas;
+/// let quotas: Quotas = toml::from_str("max_plans = 20\nmax_tasks_per_plan = 500").unwrap();
+/// assert_eq!(quotas.max_plans, Some(20));
+/// assert_eq!(quotas.max_notes_bytes, None);
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Quotas {
+    /// Most plans that may exist at once
+    pub max_plans: Option<usize>,
+    /// Most tasks a plan may have
+    pub max_tasks_per_plan: Option<usize>,
+    /// Most bytes of notes a plan may hold: its own and its tasks' notes and note sections
+    pub max_notes_bytes: Option<usize>,
+}
+
+impl Quotas {
+    /// These quotas, with the limits they leave unset taken from `fallback`
+    pub fn or(self, fallback: Quotas) -> Quotas {
+        Quotas {
+            max_plans: self.max_plans.or(fallback.max_plans),
+            max_tasks_per_plan: self.max_tasks_per_plan.or(fallback.max_tasks_per_plan),
+            max_notes_bytes: self.max_notes_bytes.or(fallback.max_notes_bytes),
+        }
+    }
+
+    /// Whether any limit on the contents of a single plan is set
+    pub(crate) fn limits_plans(&self) -> bool {
+        self.max_tasks_per_plan.is_some() || self.max_notes_bytes.is_some()
+    }
+
+    /// Checks there is room for another plan next to `plans`
+    pub(crate) fn check_new_plan(&self, plans: usize) -> Result<(), TaskError> {
+        check(Quota::Plans, self.max_plans, plans, plans + 1)
+    }
+
+    /// Checks a plan that went from `before` to `after`; only growth past a limit is
+    /// rejected
+    pub(crate) fn check_plan(
+        &self,
+        before: &PlanUsage,
+        after: &PlanUsage,
+    ) -> Result<(), TaskError> {
+        check(
+            Quota::TasksPerPlan,
+            self.max_tasks_per_plan,
+            before.tasks,
+            after.tasks,
+        )?;
+        check(
+            Quota::NotesBytes,
+            self.max_notes_bytes,
+            before.notes_bytes,
+            after.notes_bytes,
+        )
+    }
+}
+
+fn check(quota: Quota, limit: Option<usize>, before: usize, after: usize) -> Result<(), TaskError> {
+    match limit {
+        Some(limit) if after > limit && after > before => Err(TaskError::QuotaExceeded {
+            quota,
+            limit,
+            requested: after,
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// One of the limits in [`Quotas`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Quota {
+    Plans,
+    TasksPerPlan,
+    NotesBytes,
+}
+
+impl fmt::Display for Quota {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Quota::Plans => "max_plans",
+            Quota::TasksPerPlan => "max_tasks_per_plan",
+            Quota::NotesBytes => "max_notes_bytes",
+        };
+        f.write_str(name)
+    }
+}
+
+/// What a plan holds, in the units [`Quotas`] limit
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlanUsage {
+    /// Tasks in the plan, not counting its root
+    pub tasks: usize,
+    /// Bytes of the plan's notes and its tasks' notes and note sections
+    pub notes_bytes: usize,
+}
+
+/// A plan's usage, for [`QuotaReport`]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlanQuotaUsage {
+    pub plan_id: PlanId,
+    #[serde(flatten)]
+    pub usage: PlanUsage,
+}
+
+/// The quotas in effect and how much of them each plan uses
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuotaReport {
+    pub quotas: Quotas,
+    /// Number of plans, counted against `max_plans`
+    pub plans: usize,
+    /// Usage of each plan, largest task count first
+    pub usage: Vec<PlanQuotaUsage>,
+}
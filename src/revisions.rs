@@ -0,0 +1,189 @@
+//! Plan revisions
+//!
+//! The transition log records what happened to a plan but not what the plan looked like
+//! afterwards. With a journal attached, every change it records carries the plan's state,
+//! so past states are read back from the journal. Without one, a [`RevisionLog`] keeps a
+//! copy of the plan after each change in memory, so the plan can be shown as it was at
+//! any time since it was created or loaded. Copies are recorded as plain JSON and
+//! compressed later, outside the log's lock, so no change waits for the compression.
+
+use std::collections::VecDeque;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::io::{Read, Write};
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+
+use crate::models::{Index, Plan};
+
+/// Most revisions kept per plan; the oldest are dropped first
+pub const MAX_REVISIONS: usize = 1000;
+
+/// JSON of a plan and cursor
+#[derive(Debug, Clone)]
+enum State {
+    /// As recorded, until it is compressed
+    Json(Arc<Vec<u8>>),
+    Gzip(Vec<u8>),
+}
+
+/// The state of a plan right after one change
+#[derive(Debug, Clone)]
+struct Revision {
+    at: DateTime<Utc>,
+    state: State,
+    /// Hash of the uncompressed JSON, to skip recording changes that changed nothing
+    digest: u64,
+}
+
+/// Revisions of one plan, oldest first
+#[derive(Debug, Clone, Default)]
+pub struct RevisionLog {
+    revisions: VecDeque<Revision>,
+}
+
+impl RevisionLog {
+    /// Records the plan as of `at`, unless it is unchanged since the last revision
+    pub fn record(
+        &mut self,
+        at: DateTime<Utc>,
+        plan: &Plan,
+        cursor: &Index,
+    ) -> std::io::Result<()> {
+        let json = serde_json::to_vec(&(plan, cursor))?;
+        let mut hasher = DefaultHasher::new();
+        json.hash(&mut hasher);
+        let digest = hasher.finish();
+        if self
+            .revisions
+            .back()
+            .is_some_and(|last| last.digest == digest)
+        {
+            return Ok(());
+        }
+
+        if self.revisions.len() == MAX_REVISIONS {
+            self.revisions.pop_front();
+        }
+        self.revisions.push_back(Revision {
+            at,
+            state: State::Json(Arc::new(json)),
+            digest,
+        });
+        Ok(())
+    }
+
+    /// The revisions not compressed yet, by when they were recorded
+    pub fn uncompressed(&self) -> Vec<(DateTime<Utc>, Arc<Vec<u8>>)> {
+        self.revisions
+            .iter()
+            .filter_map(|revision| match &revision.state {
+                State::Json(json) => Some((revision.at, json.clone())),
+                State::Gzip(_) => None,
+            })
+            .collect()
+    }
+
+    /// Gzips the JSON of a revision, see [`uncompressed`](Self::uncompressed)
+    pub fn compress(json: &[u8]) -> std::io::Result<Vec<u8>> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::fast());
+        encoder.write_all(json)?;
+        encoder.finish()
+    }
+
+    /// Replaces the JSON recorded at `at` with its compressed form, returning whether the
+    /// revision was still kept
+    pub fn set_compressed(
+        &mut self,
+        at: DateTime<Utc>,
+        json: &Arc<Vec<u8>>,
+        gzip: Vec<u8>,
+    ) -> bool {
+        let revision = self.revisions.iter_mut().find(|revision| {
+            revision.at == at
+                && matches!(&revision.state, State::Json(kept) if Arc::ptr_eq(kept, json))
+        });
+        match revision {
+            Some(revision) => {
+                revision.state = State::Gzip(gzip);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// When the oldest revision kept was recorded
+    pub fn oldest(&self) -> Option<DateTime<Utc>> {
+        self.revisions.front().map(|revision| revision.at)
+    }
+
+    /// The plan and cursor as they were at `at`, or `None` if that is before the oldest
+    /// revision kept
+    pub fn at(&self, at: DateTime<Utc>) -> Option<std::io::Result<(Plan, Index)>> {
+        let revision = self
+            .revisions
+            .iter()
+            .rev()
+            .find(|revision| revision.at <= at)?;
+        let decoded = match &revision.state {
+            State::Json(json) => serde_json::from_slice(json).map_err(Into::into),
+            State::Gzip(gzip) => {
+                let mut json = Vec::new();
+                GzDecoder::new(gzip.as_slice())
+                    .read_to_end(&mut json)
+                    .and_then(|_| Ok(serde_json::from_slice(&json)?))
+            }
+        };
+        Some(decoded)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::levels::default_levels;
+    use chrono::Duration;
+
+    #[test]
+    fn test_revision_log() {
+        let start = Utc::now();
+        let mut plan = Plan::new(default_levels(), Some("First".to_string()), None);
+        let mut log = RevisionLog::default();
+        log.record(start, &plan, &vec![]).unwrap();
+        // An unchanged plan isn't recorded again
+        log.record(start + Duration::seconds(1), &plan, &vec![])
+            .unwrap();
+        assert_eq!(log.revisions.len(), 1);
+
+        plan = Plan::new(default_levels(), Some("Second".to_string()), None);
+        log.record(start + Duration::seconds(10), &plan, &vec![0])
+            .unwrap();
+
+        let goal = |log: &RevisionLog, at| {
+            let (plan, _) = log.at(at).unwrap().unwrap();
+            plan.goal
+        };
+        assert_eq!(
+            goal(&log, start + Duration::seconds(5)).as_deref(),
+            Some("First")
+        );
+        assert!(log.at(start - Duration::seconds(1)).is_none());
+        assert_eq!(log.oldest(), Some(start));
+
+        // Compressed revisions read back the same
+        for (at, json) in log.uncompressed() {
+            let gzip = RevisionLog::compress(&json).unwrap();
+            assert!(log.set_compressed(at, &json, gzip));
+        }
+        assert!(log.uncompressed().is_empty());
+        assert_eq!(
+            goal(&log, start + Duration::seconds(5)).as_deref(),
+            Some("First")
+        );
+        assert_eq!(
+            goal(&log, start + Duration::seconds(10)).as_deref(),
+            Some("Second")
+        );
+    }
+}
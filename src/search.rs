@@ -0,0 +1,376 @@
+//! Full-text search
+//!
+//! Searching a plan by scanning the text of every task gets slow on a server hosting
+//! hundreds of large plans. A [`PlanIndex`] is an inverted index from each word in a
+//! plan's task descriptions, notes and completion summaries to the tasks it appears in.
+//! [`Core`](crate::models::Core) builds one per plan on its first search and keeps it up
+//! to date as the plan changes, re-indexing only the tasks whose text changed.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::models::{Index, Plan, Task, TaskId};
+
+/// Part of a task that a search can match
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchField {
+    Description,
+    Notes,
+    /// A named notes section
+    Section(String),
+    /// The completion summary, or the summary submitted for review
+    Summary,
+}
+
+/// The searchable text of one task
+#[derive(Debug, Clone)]
+pub struct IndexedTask {
+    /// Where the task was when the index was last updated
+    pub index: Index,
+    pub description: String,
+    pub notes: Option<String>,
+    pub sections: BTreeMap<String, String>,
+    pub summary: Option<String>,
+}
+
+/// Words kept on either side of the first matching word in a [`SearchHit`] snippet
+pub const SNIPPET_CONTEXT_WORDS: usize = 6;
+
+/// A task found by a search
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SearchHit {
+    pub index: Index,
+    pub description: String,
+    /// The first field of the task a query word matched in
+    pub field: MatchField,
+    /// The words around the first match in `field`, with "…" where the text was cut
+    pub snippet: String,
+}
+
+impl IndexedTask {
+    /// Captures the text of `task`, found at `index`
+    pub fn new(index: Index, task: &Task) -> Self {
+        Self {
+            index,
+            description: task.description().to_string(),
+            notes: task.notes().map(str::to_string),
+            sections: task.note_sections().clone(),
+            summary: task.completion_summary().cloned(),
+        }
+    }
+
+    /// Each field the task has, in the order of [`MatchField`]
+    pub fn fields(&self) -> impl Iterator<Item = (MatchField, &str)> {
+        std::iter::once((MatchField::Description, self.description.as_str()))
+            .chain(
+                self.notes
+                    .as_deref()
+                    .map(|notes| (MatchField::Notes, notes)),
+            )
+            .chain(
+                self.sections
+                    .iter()
+                    .map(|(name, text)| (MatchField::Section(name.clone()), text.as_str())),
+            )
+            .chain(
+                self.summary
+                    .as_deref()
+                    .map(|summary| (MatchField::Summary, summary)),
+            )
+    }
+
+    /// The text of every field
+    fn texts(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.description.as_str())
+            .chain(self.notes.as_deref())
+            .chain(self.sections.values().map(String::as_str))
+            .chain(self.summary.as_deref())
+    }
+
+    /// Whether this still holds the text of `task`
+    fn is_current(&self, task: &Task) -> bool {
+        self.description == task.description()
+            && self.notes.as_deref() == task.notes()
+            && &self.sections == task.note_sections()
+            && self.summary.as_ref() == task.completion_summary()
+    }
+
+    /// Describes this task as a result of searching for `query`
+    pub fn hit(&self, query: &str) -> SearchHit {
+        let terms: Vec<String> = words(query).collect();
+        let (field, snippet) = self
+            .fields()
+            .find_map(|(field, text)| snippet(text, &terms).map(|s| (field, s)))
+            .unwrap_or_else(|| (MatchField::Description, self.description.clone()));
+        SearchHit {
+            index: self.index.clone(),
+            description: self.description.clone(),
+            field,
+            snippet,
+        }
+    }
+}
+
+/// The whitespace-separated words of `text` around the first one containing a word that
+/// starts with one of `terms`, if any does
+fn snippet(text: &str, terms: &[String]) -> Option<String> {
+    let tokens: Vec<&str> = text.split_whitespace().collect();
+    let first = tokens.iter().position(|token| {
+        words(token).any(|word| terms.iter().any(|term| word.starts_with(term.as_str())))
+    })?;
+    let start = first.saturating_sub(SNIPPET_CONTEXT_WORDS);
+    let end = (first + SNIPPET_CONTEXT_WORDS + 1).min(tokens.len());
+    let mut snippet = tokens[start..end].join(" ");
+    if start > 0 {
+        snippet.insert_str(0, "… ");
+    }
+    if end < tokens.len() {
+        snippet.push_str(" …");
+    }
+    Some(snippet)
+}
+
+/// Inverted index of the live tasks of one plan
+#[derive(Debug, Clone, Default)]
+pub struct PlanIndex {
+    /// The indexed tasks, by ID
+    tasks: HashMap<TaskId, IndexedTask>,
+    /// Each word to the tasks containing it
+    postings: BTreeMap<String, BTreeSet<TaskId>>,
+}
+
+/// Splits text into the lowercase words it is indexed and searched by
+pub fn words(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+}
+
+impl PlanIndex {
+    /// Indexes every live task of `plan`
+    pub fn build(plan: &Plan) -> Self {
+        let mut index = PlanIndex::default();
+        index.update(plan);
+        index
+    }
+
+    /// Brings the index up to date with `plan`. Tasks that moved only get their new index;
+    /// the words of a task are indexed again only when its text changed, and removed tasks
+    /// are dropped.
+    pub fn update(&mut self, plan: &Plan) {
+        let mut live = HashSet::new();
+        self.update_subtasks(plan.root(), &[], &mut live);
+        let removed: Vec<TaskId> = self
+            .tasks
+            .keys()
+            .filter(|id| !live.contains(*id))
+            .cloned()
+            .collect();
+        for id in removed {
+            self.remove(&id);
+        }
+    }
+
+    fn update_subtasks(
+        &mut self,
+        parent: &Task,
+        parent_index: &[usize],
+        live: &mut HashSet<TaskId>,
+    ) {
+        for (i, task) in parent.live_subtasks() {
+            let mut task_index = parent_index.to_vec();
+            task_index.push(i);
+            live.insert(task.id().clone());
+
+            let unchanged = self
+                .tasks
+                .get_mut(task.id())
+                .filter(|indexed| indexed.is_current(task));
+            if let Some(indexed) = unchanged {
+                indexed.index = task_index.clone();
+            } else {
+                self.remove(task.id());
+                let indexed = IndexedTask::new(task_index.clone(), task);
+                for text in indexed.texts() {
+                    for word in words(text) {
+                        self.postings
+                            .entry(word)
+                            .or_default()
+                            .insert(task.id().clone());
+                    }
+                }
+                self.tasks.insert(task.id().clone(), indexed);
+            }
+            self.update_subtasks(task, &task_index, live);
+        }
+    }
+
+    /// Drops a task and its words from the index
+    fn remove(&mut self, id: &TaskId) {
+        let Some(indexed) = self.tasks.remove(id) else {
+            return;
+        };
+        for text in indexed.texts() {
+            for word in words(text) {
+                if let Some(ids) = self.postings.get_mut(&word) {
+                    ids.remove(id);
+                    if ids.is_empty() {
+                        self.postings.remove(&word);
+                    }
+                }
+            }
+        }
+    }
+
+    /// The tasks containing every word of `query`, in tree order. Each query word also
+    /// matches longer words it starts, so "pars" finds "parser". A query without words
+    /// matches nothing.
+    pub fn search(&self, query: &str) -> Vec<&IndexedTask> {
+        let mut found: Option<BTreeSet<&TaskId>> = None;
+        for term in words(query) {
+            let matching: BTreeSet<&TaskId> = self
+                .postings
+                .range(term.clone()..)
+                .take_while(|(word, _)| word.starts_with(&term))
+                .flat_map(|(_, ids)| ids.iter())
+                .collect();
+            let narrowed = match found {
+                Some(found) => found.intersection(&matching).copied().collect(),
+                None => matching,
+            };
+            if narrowed.is_empty() {
+                return Vec::new();
+            }
+            found = Some(narrowed);
+        }
+        let mut tasks: Vec<&IndexedTask> = found
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|id| self.tasks.get(id))
+            .collect();
+        // Index paths sort in tree order
+        tasks.sort_by(|a, b| a.index.cmp(&b.index));
+        tasks
+    }
+
+    /// Number of tasks indexed
+    pub fn len(&self) -> usize {
+        self.tasks.len()
+    }
+
+    /// Whether the plan had no live tasks to index
+    pub fn is_empty(&self) -> bool {
+        self.tasks.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::levels::default_levels;
+    use crate::models::Context;
+
+    #[test]
+    fn test_plan_index_search() {
+        let plan = Plan::new(default_levels(), Some("Compiler".to_string()), None);
+        let mut context = Context::new(plan);
+        context
+            .add_task(
+                "Write the parser".to_string(),
+                0,
+                Some("Use nom".to_string()),
+            )
+            .unwrap();
+        context.move_to(vec![0]).unwrap();
+        context
+            .add_task("Parse expressions".to_string(), 1, None)
+            .unwrap();
+        context.move_to(Vec::new()).unwrap();
+        context
+            .add_task("Write the lexer".to_string(), 0, None)
+            .unwrap();
+
+        let index = PlanIndex::build(context.get_plan().inner());
+        assert_eq!(index.len(), 3);
+        let found = |query| -> Vec<Index> {
+            index
+                .search(query)
+                .into_iter()
+                .map(|task| task.index.clone())
+                .collect()
+        };
+        // Prefixes match, case doesn't matter and results come in tree order
+        assert_eq!(found("PARS"), vec![vec![0], vec![0, 0]]);
+        assert_eq!(found("write"), vec![vec![0], vec![1]]);
+        // Every word has to match, in any field
+        assert_eq!(found("nom parser"), vec![vec![0]]);
+        assert!(found("nom lexer").is_empty());
+        assert!(found(" ,. ").is_empty());
+    }
+
+    #[test]
+    fn test_plan_index_update() {
+        let plan = Plan::new(default_levels(), Some("Compiler".to_string()), None);
+        let mut context = Context::new(plan);
+        for description in ["Write the parser", "Write the lexer", "Write docs"] {
+            context.add_task(description.to_string(), 0, None).unwrap();
+        }
+        let mut index = PlanIndex::build(context.get_plan().inner());
+        let found = |index: &PlanIndex, query| -> Vec<Index> {
+            index
+                .search(query)
+                .into_iter()
+                .map(|task| task.index.clone())
+                .collect()
+        };
+
+        // Changed text is indexed again, and the old words stop matching
+        context
+            .update_task_description(vec![1], "Write the tokenizer".to_string())
+            .unwrap();
+        context
+            .set_task_notes(vec![2], "Cover the lexer too".to_string())
+            .unwrap();
+        index.update(context.get_plan().inner());
+        assert_eq!(found(&index, "tokenizer"), vec![vec![1]]);
+        assert_eq!(found(&index, "lexer"), vec![vec![2]]);
+
+        // Removed tasks are dropped and the others follow their new index
+        context.remove_task(vec![0]).unwrap();
+        index.update(context.get_plan().inner());
+        assert_eq!(index.len(), 2);
+        assert!(found(&index, "parser").is_empty());
+        assert_eq!(found(&index, "write"), vec![vec![0], vec![1]]);
+        assert!(index.postings.keys().all(|word| word != "parser"));
+    }
+
+    #[test]
+    fn test_search_hit_snippets() {
+        let task = IndexedTask {
+            index: vec![2],
+            description: "Write the parser".to_string(),
+            notes: Some(
+                "one two three four five six seven Nom-based eight nine ten eleven twelve thirteen"
+                    .to_string(),
+            ),
+            sections: BTreeMap::new(),
+            summary: None,
+        };
+
+        let hit = task.hit("nom");
+        assert_eq!(hit.index, vec![2]);
+        assert_eq!(hit.description, "Write the parser");
+        assert_eq!(hit.field, MatchField::Notes);
+        assert_eq!(
+            hit.snippet,
+            "… two three four five six seven Nom-based eight nine ten eleven twelve thirteen"
+        );
+
+        // The first field with a match wins
+        let hit = task.hit("nom pars");
+        assert_eq!(hit.field, MatchField::Description);
+        assert_eq!(hit.snippet, "Write the parser");
+    }
+}
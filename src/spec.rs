@@ -0,0 +1,220 @@
+//! Declarative plan specifications
+//!
+//! This module defines a plan spec: a goal, notes, optional levels and a tree of tasks
+//! that can be checked into a repository, reviewed like any other file, and turned into
+//! a plan with `plan create --from-spec` or `POST /api/plans/spec`. A single
+//! [`TaskSpec`] is also the format subtrees are exported in and imported from.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::levels::Level;
+use crate::models::{normalize_tag, MAX_TASK_PRIORITY};
+use crate::redaction::Redactor;
+
+/// A plan described up front, usually written in YAML
+///
+/// ```
+/// # use scatterbrain::spec::PlanSpec;
+/// let spec = PlanSpec::from_yaml(r#"
+/// goal: Ship the importer
+/// tasks:
+///   - description: Design the format
+///     level: 0
+///     completed: true
+///     summary: Settled on YAML
+///   - description: Build it
+///     level: 0
+///     tasks:
+///       - description: Parser
+///         level: 1
+///         sections:
+///           checklist: "- [ ] nested tasks"
+/// "#).unwrap();
+/// assert_eq!(spec.tasks.len(), 2);
+/// assert_eq!(spec.tasks[1].tasks[0].description, "Parser");
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PlanSpec {
+    /// High-level goal of the plan
+    pub goal: String,
+    /// Longer-form notes for the plan
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+    /// Abstraction levels for the plan; the server's default levels are used when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub levels: Option<Vec<Level>>,
+    /// Replaces the usage summary in the plan's distilled context
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage_summary: Option<String>,
+    /// Root-level tasks, in order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tasks: Vec<TaskSpec>,
+}
+
+/// A task in a [`PlanSpec`], with its subtasks
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TaskSpec {
+    pub description: String,
+    /// Level index of the task
+    pub level: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+    /// Named notes sections, such as a `checklist`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub sections: BTreeMap<String, String>,
+    /// Whether the task is already done; its subtasks must be done too
+    #[serde(default)]
+    pub completed: bool,
+    /// Completion summary of a completed task
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub summary: Option<String>,
+    /// How much the task counts toward plan progress; 1 when omitted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weight: Option<u32>,
+    /// How urgent the task is, from 0 (most) to 9 (least)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<u8>,
+    /// Tags for slicing the plan by concern
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub tags: BTreeSet<String>,
+    /// Subtasks, in order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tasks: Vec<TaskSpec>,
+}
+
+impl PlanSpec {
+    /// Parses a spec from YAML. Since JSON is valid YAML, a JSON spec parses too.
+    pub fn from_yaml(contents: &str) -> Result<Self, serde_yaml::Error> {
+        serde_yaml::from_str(contents)
+    }
+
+    /// Checks the parts of a spec that level rules on the plan don't cover
+    pub fn check(&self) -> Result<(), String> {
+        if self.goal.trim().is_empty() {
+            return Err("goal must not be empty".to_string());
+        }
+        if self.levels.as_ref().is_some_and(Vec::is_empty) {
+            return Err("levels must not be empty when given".to_string());
+        }
+        self.tasks()
+            .try_for_each(|(path, task)| task.check_own(&path))
+    }
+
+    /// Every task in the spec, depth first, with its comma-separated index (e.g. `0,2`)
+    pub fn tasks(&self) -> impl Iterator<Item = (String, &TaskSpec)> {
+        walk(
+            self.tasks
+                .iter()
+                .enumerate()
+                .map(|(i, task)| (i.to_string(), task))
+                .collect(),
+        )
+    }
+}
+
+impl TaskSpec {
+    /// Checks this task and its subtasks the way [`PlanSpec::check`] checks a plan's tasks
+    pub fn check(&self) -> Result<(), String> {
+        self.tasks()
+            .try_for_each(|(path, task)| task.check_own(&path))
+    }
+
+    /// This task and every task below it, depth first, with its index relative to this
+    /// task (`""` for this task itself, then `0`, `0,1`, ...)
+    pub fn tasks(&self) -> impl Iterator<Item = (String, &TaskSpec)> {
+        walk(vec![(String::new(), self)])
+    }
+
+    /// Redacts secrets from the text of this task and its subtasks
+    pub(crate) fn redact(&mut self, redactor: &Redactor) {
+        redactor.redact_in_place(&mut self.description);
+        let texts = self
+            .notes
+            .iter_mut()
+            .chain(self.sections.values_mut())
+            .chain(self.summary.iter_mut());
+        for text in texts {
+            redactor.redact_in_place(text);
+        }
+        for subtask in &mut self.tasks {
+            subtask.redact(redactor);
+        }
+    }
+
+    fn check_own(&self, path: &str) -> Result<(), String> {
+        let name = match path {
+            "" => format!("'{}'", self.description),
+            path => format!("task {path}"),
+        };
+        if self.summary.is_some() && !self.completed {
+            return Err(format!("{name} has a summary but isn't completed"));
+        }
+        if self.completed && self.tasks.iter().any(|subtask| !subtask.completed) {
+            return Err(format!("{name} is completed but has incomplete subtasks"));
+        }
+        if let Some(priority) = self.priority.filter(|&p| p > MAX_TASK_PRIORITY) {
+            return Err(format!(
+                "{name} has priority {priority}; priorities go from 0 to {MAX_TASK_PRIORITY}"
+            ));
+        }
+        if let Some(tag) = self.tags.iter().find(|tag| normalize_tag(tag).is_none()) {
+            return Err(format!(
+                "{name} has tag '{tag}'; tags can't be empty or contain whitespace or commas"
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Walks the given tasks and their subtasks depth first, extending each index path
+fn walk(roots: Vec<(String, &TaskSpec)>) -> impl Iterator<Item = (String, &TaskSpec)> {
+    let mut pending = roots;
+    pending.reverse();
+    std::iter::from_fn(move || {
+        let (path, task) = pending.pop()?;
+        for (i, subtask) in task.tasks.iter().enumerate().rev() {
+            let subpath = match path.as_str() {
+                "" => i.to_string(),
+                path => format!("{path},{i}"),
+            };
+            pending.push((subpath, subtask));
+        }
+        Some((path, task))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_spec() {
+        let spec = PlanSpec::from_yaml(
+            r#"
+goal: Release
+tasks:
+  - description: Prepare
+    level: 0
+    completed: true
+    tasks:
+      - description: Changelog
+        level: 1
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            spec.check().unwrap_err(),
+            "task 0 is completed but has incomplete subtasks"
+        );
+        let paths: Vec<String> = spec.tasks().map(|(path, _)| path).collect();
+        assert_eq!(paths, ["0", "0,0"]);
+
+        // Typos are caught rather than silently ignored
+        assert!(PlanSpec::from_yaml("goal: Release\ntask: []").is_err());
+        assert!(PlanSpec::from_yaml("goal: ''").unwrap().check().is_err());
+    }
+}
@@ -0,0 +1,47 @@
+//! Suggestion engines
+//!
+//! A suggestion engine looks at a plan's distilled context and proposes next steps, which
+//! are added to the `suggested_followups` of the context served for the plan. Engines are
+//! registered on the [`Core`](crate::Core) by name, either a
+//! [`WasmPlugin`](crate::wasm::WasmPlugin) from the server config or one registered in code.
+
+use serde::Serialize;
+
+use crate::models::DistilledContext;
+
+/// The plan an engine is asked to make suggestions for
+#[derive(Debug, Clone, Serialize)]
+pub struct SuggestionRequest<'a> {
+    pub plan_id: u8,
+    pub context: &'a DistilledContext,
+}
+
+/// Proposes next steps for a plan
+///
+/// Engines run after the plan's context is built, outside the plan's lock, in the order
+/// of their names. An engine that fails is logged and skipped, so it can't keep the
+/// context from being served.
+///
+/// ```
+/// # use scatterbrain::suggestions::{SuggestionEngine, SuggestionRequest};
+/// # use scatterbrain::Core;
+/// use std::sync::Arc;
+///
+/// struct AskForGoal;
+///
+/// impl SuggestionEngine for AskForGoal {
+///     fn suggest(&self, request: &SuggestionRequest) -> Result<Vec<String>, String> {
+///         Ok(match request.context.goal {
+///             Some(_) => Vec::new(),
+///             None => vec!["Set a goal for the plan".to_string()],
+///         })
+///     }
+/// }
+///
+/// let core = Core::new();
+/// core.register_suggestion_engine("goal".to_string(), Arc::new(AskForGoal)).unwrap();
+/// ```
+pub trait SuggestionEngine: Send + Sync {
+    /// Returns the steps to suggest, if any
+    fn suggest(&self, request: &SuggestionRequest) -> Result<Vec<String>, String>;
+}
@@ -0,0 +1,222 @@
+//! Task templates for Scatterbrain
+//!
+//! This module defines reusable task templates: a description pattern, level, notes
+//! skeleton and checklist with `{param}` placeholders that are filled in when the
+//! template is instantiated.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::{Deserialize, Serialize};
+
+/// Parameter values used to instantiate a template, keyed by placeholder name
+pub type TemplateParams = BTreeMap<String, String>;
+
+/// A reusable task template
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TaskTemplate {
+    /// Unique name the template is referenced by
+    pub name: String,
+    /// Task description, may contain `{param}` placeholders
+    pub description: String,
+    /// Level index for tasks created from this template
+    pub level_index: usize,
+    /// Notes skeleton, may contain placeholders
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+    /// Checklist items, stored in the task's `checklist` notes section
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub checklist: Vec<String>,
+}
+
+/// A template with all placeholders filled in
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RenderedTemplate {
+    pub description: String,
+    pub level_index: usize,
+    pub notes: Option<String>,
+    /// The checklist rendered as a Markdown task list, if the template has one
+    pub checklist: Option<String>,
+}
+
+/// Name of the notes section a template's checklist is written to
+pub const CHECKLIST_SECTION: &str = "checklist";
+
+impl TaskTemplate {
+    /// Returns the names of all placeholders used anywhere in the template
+    pub fn parameters(&self) -> BTreeSet<String> {
+        let mut names = BTreeSet::new();
+        let texts = std::iter::once(self.description.as_str())
+            .chain(self.notes.as_deref())
+            .chain(self.checklist.iter().map(String::as_str));
+        for text in texts {
+            for segment in parse(text) {
+                if let Segment::Param(name) = segment {
+                    names.insert(name.to_string());
+                }
+            }
+        }
+        names
+    }
+
+    /// Fills in the placeholders with `params`.
+    ///
+    /// Every placeholder needs a value, and every value must be used by a placeholder,
+    /// so typos in parameter names are reported instead of silently ignored. Use `{{`
+    /// and `}}` for literal braces.
+    ///
+    /// # Examples
+    /// ```
+    /// # use scatterbrain::templates::{TaskTemplate, TemplateParams};
+    /// let template = TaskTemplate {
+    ///     name: "api-endpoint".to_string(),
+    ///     description: "Add /{name} endpoint".to_string(),
+    ///     level_index: 2,
+    ///     notes: None,
+    ///     checklist: vec!["Tests for /{name}".to_string()],
+    /// };
+    /// let params = TemplateParams::from([("name".to_string(), "users".to_string())]);
+    /// let rendered = template.render(&params).unwrap();
+    /// assert_eq!(rendered.description, "Add /users endpoint");
+    /// assert_eq!(rendered.checklist.as_deref(), Some("- [ ] Tests for /users"));
+    ///
+    /// assert!(template.render(&TemplateParams::new()).is_err());
+    /// ```
+    pub fn render(&self, params: &TemplateParams) -> Result<RenderedTemplate, String> {
+        let required = self.parameters();
+        let missing: Vec<&str> = required
+            .iter()
+            .filter(|name| !params.contains_key(*name))
+            .map(String::as_str)
+            .collect();
+        if !missing.is_empty() {
+            return Err(format!(
+                "Missing template parameter(s) for '{}': {}",
+                self.name,
+                missing.join(", ")
+            ));
+        }
+        let unknown: Vec<&str> = params
+            .keys()
+            .filter(|name| !required.contains(*name))
+            .map(String::as_str)
+            .collect();
+        if !unknown.is_empty() {
+            return Err(format!(
+                "Unknown template parameter(s) for '{}': {}",
+                self.name,
+                unknown.join(", ")
+            ));
+        }
+
+        let checklist = (!self.checklist.is_empty()).then(|| {
+            self.checklist
+                .iter()
+                .map(|item| format!("- [ ] {}", substitute(item, params)))
+                .collect::<Vec<_>>()
+                .join("\n")
+        });
+        Ok(RenderedTemplate {
+            description: substitute(&self.description, params),
+            level_index: self.level_index,
+            notes: self.notes.as_deref().map(|notes| substitute(notes, params)),
+            checklist,
+        })
+    }
+}
+
+/// Parses a `key=value` template parameter, e.g. from the command line
+pub fn parse_param(param: &str) -> Result<(String, String), String> {
+    match param.split_once('=') {
+        Some((key, value)) if !key.trim().is_empty() => {
+            Ok((key.trim().to_string(), value.to_string()))
+        }
+        _ => Err(format!(
+            "Invalid template parameter '{param}', expected key=value"
+        )),
+    }
+}
+
+enum Segment<'a> {
+    Text(&'a str),
+    Param(&'a str),
+}
+
+/// Splits a template string into literal text and `{param}` placeholders. `{{` and `}}`
+/// are literal braces; an unterminated `{` is kept as text.
+fn parse(text: &str) -> Vec<Segment<'_>> {
+    let mut segments = Vec::new();
+    let mut rest = text;
+    while let Some(pos) = rest.find(['{', '}']) {
+        segments.push(Segment::Text(&rest[..pos]));
+        let tail = &rest[pos..];
+        if tail.starts_with("{{") || tail.starts_with("}}") {
+            segments.push(Segment::Text(&tail[..1]));
+            rest = &tail[2..];
+        } else if let Some(inner) = tail.strip_prefix('{') {
+            match inner.find('}') {
+                Some(end) if end > 0 => {
+                    segments.push(Segment::Param(inner[..end].trim()));
+                    rest = &inner[end + 1..];
+                }
+                _ => {
+                    segments.push(Segment::Text(&tail[..1]));
+                    rest = inner;
+                }
+            }
+        } else {
+            segments.push(Segment::Text(&tail[..1]));
+            rest = &tail[1..];
+        }
+    }
+    segments.push(Segment::Text(rest));
+    segments
+}
+
+fn substitute(text: &str, params: &TemplateParams) -> String {
+    parse(text)
+        .into_iter()
+        .map(|segment| match segment {
+            Segment::Text(text) => text,
+            Segment::Param(name) => params.get(name).map(String::as_str).unwrap_or_default(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_placeholders() {
+        let template = TaskTemplate {
+            name: "literal".to_string(),
+            description: "{ method } {{path}} {".to_string(),
+            level_index: 0,
+            notes: Some("Owner: {owner}".to_string()),
+            checklist: Vec::new(),
+        };
+        assert_eq!(
+            template.parameters().into_iter().collect::<Vec<_>>(),
+            vec!["method", "owner"]
+        );
+
+        let params = TemplateParams::from([
+            ("method".to_string(), "GET".to_string()),
+            ("owner".to_string(), "api team".to_string()),
+        ]);
+        let rendered = template.render(&params).unwrap();
+        assert_eq!(rendered.description, "GET {path} {");
+        assert_eq!(rendered.notes.as_deref(), Some("Owner: api team"));
+        assert_eq!(rendered.checklist, None);
+
+        let mut typo = params.clone();
+        typo.insert("ownr".to_string(), "x".to_string());
+        assert!(template.render(&typo).unwrap_err().contains("ownr"));
+
+        assert_eq!(
+            parse_param("name=a=b").unwrap(),
+            ("name".to_string(), "a=b".to_string())
+        );
+        assert!(parse_param("=users").is_err());
+    }
+}
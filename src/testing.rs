@@ -0,0 +1,367 @@
+//! Test harness for integrations
+//!
+//! Enabled with the `testing` feature. [`TestServer`] runs the HTTP API on a [`Core`] in
+//! the test's own process, on a port the system picks, so tests can run in parallel.
+//! [`PlanFixture`] seeds a core with a plan of a given shape under a fixed ID, and
+//! [`outline`] and [`assert_outline`] compare whole task trees in one readable string.
+//!
+//! ```toml
+//! [dev-dependencies]
+//! scatterbrain = { version = "*", features = ["testing"] }
+//! ```
+
+use std::net::SocketAddr;
+
+use tokio::net::TcpListener;
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+
+use crate::api::server::serve_with_listener;
+use crate::api::{ClientConfig, HttpClientImpl, ServerConfig};
+use crate::models::{Index, Plan, PlanError, PlanId, Task};
+use crate::spec::{PlanSpec, TaskSpec};
+use crate::Core;
+
+/// The HTTP API served from the current process until stopped or dropped
+pub struct TestServer {
+    core: Core,
+    address: SocketAddr,
+    auth_token: Option<String>,
+    stop: Option<oneshot::Sender<()>>,
+    handle: JoinHandle<Result<(), String>>,
+}
+
+impl TestServer {
+    /// Serves `core` with the default configuration
+    pub async fn start(core: Core) -> std::io::Result<Self> {
+        Self::start_with_config(core, ServerConfig::default()).await
+    }
+
+    /// Serves `core` with `config` on a free port of 127.0.0.1; `config.address` is
+    /// ignored. The server accepts connections as soon as this returns.
+    pub async fn start_with_config(core: Core, config: ServerConfig) -> std::io::Result<Self> {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await?;
+        let address = listener.local_addr()?;
+        let auth_token = config.auth_token.clone();
+        let (stop, stopped) = oneshot::channel::<()>();
+        let handle = tokio::spawn({
+            let core = core.clone();
+            async move {
+                serve_with_listener(listener, core, config, None, async move {
+                    let _ = stopped.await;
+                })
+                .await
+                .map_err(|e| e.to_string())
+            }
+        });
+        Ok(Self {
+            core,
+            address,
+            auth_token,
+            stop: Some(stop),
+            handle,
+        })
+    }
+
+    /// The core the server serves, for setting up and inspecting plans directly
+    pub fn core(&self) -> &Core {
+        &self.core
+    }
+
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    /// Base URL of the server, e.g. `http://127.0.0.1:49152`
+    pub fn url(&self) -> String {
+        format!("http://{}", self.address)
+    }
+
+    /// A client for the server, sending its auth token if it has one
+    pub fn client(&self) -> HttpClientImpl {
+        HttpClientImpl::with_config(self.client_config())
+    }
+
+    /// Configuration of [`client`](Self::client), to adjust before creating a client
+    pub fn client_config(&self) -> ClientConfig {
+        ClientConfig {
+            base_url: self.url(),
+            auth_token: self.auth_token.clone(),
+            ..Default::default()
+        }
+    }
+
+    /// Stops the server, letting open requests finish, and reports whether it failed
+    pub async fn stop(mut self) -> Result<(), String> {
+        if let Some(stop) = self.stop.take() {
+            let _ = stop.send(());
+        }
+        (&mut self.handle)
+            .await
+            .map_err(|e| format!("test server task error: {e}"))?
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        if self.stop.is_some() {
+            self.handle.abort();
+        }
+    }
+}
+
+/// A plan of a given shape, created under a fixed ID
+///
+/// Tasks form a full tree `depth` levels deep with `fanout` subtasks per task, described
+/// by their index, e.g. `Task 0.1`. Levels below the most detailed one reuse it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PlanFixture {
+    pub id: u8,
+    pub goal: String,
+    pub notes: Option<String>,
+    pub depth: usize,
+    pub fanout: usize,
+    /// Tasks completed with their subtasks, with the summary `Done`
+    pub completed: Vec<Index>,
+    /// Give every task the notes `Notes for <index>`
+    pub task_notes: bool,
+}
+
+impl Default for PlanFixture {
+    fn default() -> Self {
+        Self {
+            id: 1,
+            goal: "Test plan".to_string(),
+            notes: None,
+            depth: 2,
+            fanout: 2,
+            completed: Vec::new(),
+            task_notes: false,
+        }
+    }
+}
+
+impl PlanFixture {
+    /// The plan as a spec, using `levels` levels
+    pub fn spec(&self, levels: usize) -> PlanSpec {
+        PlanSpec {
+            goal: self.goal.clone(),
+            notes: self.notes.clone(),
+            levels: None,
+            usage_summary: None,
+            tasks: self.tasks(&mut Vec::new(), levels.saturating_sub(1), false),
+        }
+    }
+
+    fn tasks(&self, parent: &mut Index, max_level: usize, completed: bool) -> Vec<TaskSpec> {
+        if parent.len() == self.depth {
+            return Vec::new();
+        }
+        (0..self.fanout)
+            .map(|n| {
+                parent.push(n);
+                let name = dotted(parent);
+                let completed = completed || self.completed.contains(parent);
+                let task = TaskSpec {
+                    description: format!("Task {name}"),
+                    level: (parent.len() - 1).min(max_level),
+                    notes: self.task_notes.then(|| format!("Notes for {name}")),
+                    sections: Default::default(),
+                    completed,
+                    summary: completed.then(|| "Done".to_string()),
+                    weight: None,
+                    priority: None,
+                    tags: Default::default(),
+                    tasks: self.tasks(parent, max_level, completed),
+                };
+                parent.pop();
+                task
+            })
+            .collect()
+    }
+
+    /// Creates the plan in `core` with its cursor at the root, replacing any plan with
+    /// the same ID
+    pub fn create(&self, core: &Core) -> Result<PlanId, PlanError> {
+        // Build it in a scratch core, which picks a random ID, then move it to ours
+        let scratch = Core::new();
+        scratch.set_default_levels(core.default_levels()?)?;
+        let levels = core.default_levels()?.len();
+        let scratch_id = scratch.create_plan_from_spec(self.spec(levels))?;
+        let id = PlanId::new(self.id);
+        core.import_plan(id, scratch.export_plan(&scratch_id)?)?;
+        Ok(id)
+    }
+}
+
+/// A core holding the plans of `fixtures`
+///
+/// ```
+/// # #[cfg(feature = "testing")] {
+/// # use scatterbrain::testing::{seeded_core, PlanFixture};
+/// let core = seeded_core(&[PlanFixture::default()]).unwrap();
+/// assert_eq!(core.list_plans().unwrap()[0].value(), 1);
+/// # }
+/// ```
+pub fn seeded_core(fixtures: &[PlanFixture]) -> Result<Core, PlanError> {
+    let core = Core::new();
+    for fixture in fixtures {
+        fixture.create(&core)?;
+    }
+    Ok(core)
+}
+
+fn dotted(index: &[usize]) -> String {
+    let parts: Vec<String> = index.iter().map(usize::to_string).collect();
+    parts.join(".")
+}
+
+/// The plan's task tree, one task per line: `[x]` or `[ ]`, then its description,
+/// indented two spaces per level
+pub fn outline(plan: &Plan) -> String {
+    fn write(tasks: &[Task], depth: usize, out: &mut String) {
+        for task in tasks.iter().filter(|task| !task.is_removed()) {
+            let mark = if task.is_completed() { "x" } else { " " };
+            out.push_str(&format!(
+                "{}[{mark}] {}\n",
+                "  ".repeat(depth),
+                task.description()
+            ));
+            write(task.subtasks(), depth + 1, out);
+        }
+    }
+    let mut out = String::new();
+    write(plan.root().subtasks(), 0, &mut out);
+    out
+}
+
+/// Asserts the plan's [`outline`] is `expected`, ignoring blank lines and the
+/// indentation all of `expected`'s lines share
+#[track_caller]
+pub fn assert_outline(plan: &Plan, expected: &str) {
+    let lines: Vec<&str> = expected.lines().filter(|l| !l.trim().is_empty()).collect();
+    let margin = lines
+        .iter()
+        .map(|line| line.len() - line.trim_start().len())
+        .min()
+        .unwrap_or(0);
+    let expected: String = lines
+        .iter()
+        .map(|line| format!("{}\n", line[margin..].trim_end()))
+        .collect();
+    let actual = outline(plan);
+    assert!(
+        actual == expected,
+        "plan outline differs\n--- expected\n{expected}--- actual\n{actual}"
+    );
+}
+
+/// The task at `index`, panicking with the index if there is none
+#[track_caller]
+pub fn task_at<'a>(plan: &'a Plan, index: &[usize]) -> &'a Task {
+    let mut task = plan.root();
+    for (depth, n) in index.iter().enumerate() {
+        task = task
+            .subtasks()
+            .get(*n)
+            .filter(|task| !task.is_removed())
+            .unwrap_or_else(|| panic!("no task at {:?}", &index[..=depth]));
+    }
+    task
+}
+
+/// Asserts whether the task at `index` is completed
+#[track_caller]
+pub fn assert_completed(plan: &Plan, index: &[usize], completed: bool) {
+    let task = task_at(plan, index);
+    assert_eq!(
+        task.is_completed(),
+        completed,
+        "task {index:?} ({}) is {}completed",
+        task.description(),
+        if task.is_completed() { "" } else { "not " }
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::Client;
+
+    #[test]
+    fn test_fixture_builds_the_same_tree_every_time() {
+        let fixture = PlanFixture {
+            id: 7,
+            depth: 2,
+            fanout: 2,
+            completed: vec![vec![1]],
+            task_notes: true,
+            ..Default::default()
+        };
+        let core = seeded_core(&[fixture.clone()]).unwrap();
+        let plan = core.get_plan(&PlanId::new(7)).unwrap().into_inner();
+        assert_outline(
+            &plan,
+            "
+            [ ] Task 0
+              [ ] Task 0.0
+              [ ] Task 0.1
+            [x] Task 1
+              [x] Task 1.0
+              [x] Task 1.1
+            ",
+        );
+        assert_completed(&plan, &[1, 0], true);
+        assert_eq!(task_at(&plan, &[0, 1]).notes(), Some("Notes for 0.1"));
+
+        // Creating it again replaces the plan with an identical one
+        fixture.create(&core).unwrap();
+        let again = core.get_plan(&PlanId::new(7)).unwrap().into_inner();
+        assert_eq!(outline(&again), outline(&plan));
+        assert_eq!(core.list_plans().unwrap().len(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "no task at [0, 5]")]
+    fn test_task_at_names_the_missing_index() {
+        let core = seeded_core(&[PlanFixture::default()]).unwrap();
+        let plan = core.get_plan(&PlanId::new(1)).unwrap().into_inner();
+        task_at(&plan, &[0, 5, 1]);
+    }
+
+    #[tokio::test]
+    async fn test_server_serves_the_seeded_core() {
+        let core = seeded_core(&[PlanFixture {
+            depth: 1,
+            fanout: 3,
+            ..Default::default()
+        }])
+        .unwrap();
+        let config = ServerConfig {
+            auth_token: Some("secret".to_string()),
+            ..Default::default()
+        };
+        let server = TestServer::start_with_config(core, config).await.unwrap();
+        let client = server.client();
+        client
+            .complete_task(1, vec![2], None, true, None, Some("Done".to_string()))
+            .await
+            .unwrap();
+
+        let plan = client.get_plan(1).await.unwrap().into_inner();
+        assert_outline(
+            &plan,
+            "
+            [ ] Task 0
+            [ ] Task 1
+            [x] Task 2
+            ",
+        );
+        let direct = server.core().get_plan(&PlanId::new(1)).unwrap();
+        assert_eq!(outline(direct.inner()), outline(&plan));
+
+        let address = server.address();
+        server.stop().await.unwrap();
+        assert!(std::net::TcpStream::connect(address).is_err());
+    }
+}
@@ -0,0 +1,267 @@
+//! Completion validators
+//!
+//! Validators gate task completion on checks made outside the planner, such as a test
+//! suite passing. A plan names the validators its completions must pass in its
+//! [`CompletionPolicy`](crate::models::CompletionPolicy); each name refers to a
+//! [`CompletionValidator`] registered on the [`Core`](crate::Core), either a
+//! [`HookValidator`] from the server config or one registered in code.
+
+use std::io::{Read, Write};
+use std::process::{Command, Stdio};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::models::Index;
+use crate::wasm::WasmPlugin;
+
+/// Longest validator output kept as the reason a completion was rejected
+const MAX_REASON_CHARS: usize = 2000;
+
+/// A task completion that is about to be applied
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PendingCompletion {
+    pub plan_id: u8,
+    /// Index of the task being completed
+    pub index: Index,
+    pub description: String,
+    pub summary: Option<String>,
+    /// Index of the task whose completion completes this one too, when it's an
+    /// incomplete subtask completed along with its parent; the summary is the parent's
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cascaded_from: Option<Index>,
+}
+
+/// Decides whether a task may be completed
+///
+/// Validators run before the plan is locked for the completion, so they may take as
+/// long as their check needs without blocking other requests on the plan. They are called
+/// synchronously; the server and MCP run completions on Tokio's blocking thread pool, so a
+/// slow check doesn't stall their other requests either.
+pub trait CompletionValidator: Send + Sync {
+    /// Returns why the completion must not go ahead, if it mustn't
+    fn validate(&self, completion: &PendingCompletion) -> Result<(), String>;
+}
+
+/// A validator defined in the server config
+///
+/// ```
+/// # use scatterbrain::validators::HookValidator;
+/// let hook: HookValidator = toml::from_str(r#"command = "cargo test --quiet""#).unwrap();
+/// assert_eq!(
+///     hook,
+///     HookValidator::Command { command: "cargo test --quiet".to_string(), timeout_secs: 300 }
+/// );
+/// let hook: HookValidator = toml::from_str(r#"url = "http://ci.local/gate""#).unwrap();
+/// assert!(matches!(hook, HookValidator::Webhook { timeout_secs: 30, .. }));
+/// let hook: HookValidator = toml::from_str(r#"wasm = "plugins/gate.wasm""#).unwrap();
+/// assert!(matches!(hook, HookValidator::Wasm(_)));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged, deny_unknown_fields)]
+pub enum HookValidator {
+    /// Runs `command` with `sh -c`, passing the completion as JSON on stdin. A zero exit
+    /// status lets the completion through; otherwise the command's output is the reason
+    /// it was rejected.
+    Command {
+        command: String,
+        #[serde(default = "default_command_timeout")]
+        timeout_secs: u64,
+    },
+    /// POSTs the completion as JSON to `url`. A 2xx response lets the completion
+    /// through; otherwise the response body is the reason it was rejected.
+    Webhook {
+        url: String,
+        #[serde(default = "default_webhook_timeout")]
+        timeout_secs: u64,
+    },
+    /// Runs the `validate` export of a WebAssembly module in a sandbox, for servers that
+    /// mustn't run commands. See [`WasmPlugin`] for what the module gets and returns.
+    Wasm(WasmPlugin),
+}
+
+fn default_command_timeout() -> u64 {
+    300
+}
+
+fn default_webhook_timeout() -> u64 {
+    30
+}
+
+impl CompletionValidator for HookValidator {
+    fn validate(&self, completion: &PendingCompletion) -> Result<(), String> {
+        match self {
+            HookValidator::Command {
+                command,
+                timeout_secs,
+            } => run_command(command, Duration::from_secs(*timeout_secs), completion),
+            HookValidator::Webhook { url, timeout_secs } => {
+                post_webhook(url, Duration::from_secs(*timeout_secs), completion)
+            }
+            HookValidator::Wasm(plugin) => plugin.validate(completion),
+        }
+    }
+}
+
+fn run_command(
+    command: &str,
+    timeout: Duration,
+    completion: &PendingCompletion,
+) -> Result<(), String> {
+    let input = serde_json::to_vec(completion).map_err(|e| e.to_string())?;
+    let index = completion
+        .index
+        .iter()
+        .map(|i| i.to_string())
+        .collect::<Vec<_>>()
+        .join(",");
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .env("SCATTERBRAIN_PLAN_ID", completion.plan_id.to_string())
+        .env("SCATTERBRAIN_TASK_INDEX", index)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("failed to run `{command}`: {e}"))?;
+
+    // Feed and drain the pipes on their own threads so a command that ignores its input
+    // or writes a lot of output can't stall the wait below
+    if let Some(mut stdin) = child.stdin.take() {
+        std::thread::spawn(move || {
+            let _ = stdin.write_all(&input);
+        });
+    }
+    let stdout = child.stdout.take().map(read_to_end);
+    let stderr = child.stderr.take().map(read_to_end);
+
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) if Instant::now() < deadline => std::thread::sleep(Duration::from_millis(20)),
+            Ok(None) => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(format!(
+                    "`{command}` timed out after {}s",
+                    timeout.as_secs()
+                ));
+            }
+            Err(e) => return Err(format!("failed to wait for `{command}`: {e}")),
+        }
+    };
+    if status.success() {
+        return Ok(());
+    }
+
+    let output = [stderr, stdout]
+        .into_iter()
+        .flatten()
+        .filter_map(|reader| reader.join().ok())
+        .find(|output| !output.trim().is_empty())
+        .unwrap_or_default();
+    Err(format!("`{command}` {status}: {}", tail(output.trim())))
+}
+
+fn read_to_end(mut pipe: impl Read + Send + 'static) -> JoinHandle<String> {
+    std::thread::spawn(move || {
+        let mut output = Vec::new();
+        let _ = pipe.read_to_end(&mut output);
+        String::from_utf8_lossy(&output).into_owned()
+    })
+}
+
+fn post_webhook(
+    url: &str,
+    timeout: Duration,
+    completion: &PendingCompletion,
+) -> Result<(), String> {
+    // Validators are called from synchronous code that may itself be running on a Tokio
+    // runtime, so the request gets a runtime of its own on a separate thread
+    let target = url.to_string();
+    let completion = completion.clone();
+    let request = std::thread::spawn(move || {
+        let url = target;
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(|e| e.to_string())?;
+        runtime.block_on(async {
+            let client = reqwest::Client::builder()
+                .timeout(timeout)
+                .build()
+                .map_err(|e| e.to_string())?;
+            let response = client
+                .post(&url)
+                .json(&completion)
+                .send()
+                .await
+                .map_err(|e| format!("failed to call {url}: {e}"))?;
+            let status = response.status();
+            if status.is_success() {
+                return Ok(());
+            }
+            let body = response.text().await.unwrap_or_default();
+            Err(format!(
+                "{url} responded with {status}: {}",
+                tail(body.trim())
+            ))
+        })
+    });
+    request
+        .join()
+        .unwrap_or_else(|_| Err(format!("request to {url} panicked")))
+}
+
+/// Keeps the end of long output, where test runners print their verdict
+fn tail(output: &str) -> &str {
+    let chars = output.chars().count();
+    match output
+        .char_indices()
+        .nth(chars.saturating_sub(MAX_REASON_CHARS))
+    {
+        Some((start, _)) => &output[start..],
+        None => output,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command_validator() {
+        let completion = PendingCompletion {
+            plan_id: 7,
+            index: vec![0, 2],
+            description: "Fix the parser".to_string(),
+            summary: Some("Tested: yes".to_string()),
+            cascaded_from: None,
+        };
+        let passes = HookValidator::Command {
+            command: r#"test "$SCATTERBRAIN_TASK_INDEX" = 0,2 && grep -q 'Tested: yes'"#
+                .to_string(),
+            timeout_secs: 10,
+        };
+        assert_eq!(passes.validate(&completion), Ok(()));
+
+        let fails = HookValidator::Command {
+            command: "echo ' 2 tests failed' >&2; exit 1".to_string(),
+            timeout_secs: 10,
+        };
+        let reason = fails.validate(&completion).unwrap_err();
+        assert!(reason.ends_with("2 tests failed"), "{reason}");
+
+        let hangs = HookValidator::Command {
+            command: "sleep 5".to_string(),
+            timeout_secs: 0,
+        };
+        assert!(hangs
+            .validate(&completion)
+            .unwrap_err()
+            .contains("timed out"));
+    }
+}
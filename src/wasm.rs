@@ -0,0 +1,404 @@
+//! WASM plugins
+//!
+//! Deployments that can't let the server run shell commands can still extend it with
+//! WebAssembly modules, run by wasmtime in a sandbox when scatterbrain is built with the
+//! `wasm-plugins` feature. A [`WasmPlugin`] can be a completion validator, a suggestion
+//! engine or both, depending on what its module exports.
+//!
+//! Modules get no imports, so they can't reach files, the network or the clock; each call
+//! runs in a fresh instance with at most `fuel` units of work and `memory_mib` MiB of
+//! memory. A module exports:
+//!
+//! - `memory`, and `alloc(len: i32) -> i32` returning where the host may write `len`
+//!   bytes of input
+//! - `validate(ptr: i32, len: i32) -> i64`, given the
+//!   [`PendingCompletion`] as JSON, to be used as a validator
+//! - `suggest(ptr: i32, len: i32) -> i64`, given the [`SuggestionRequest`] as JSON, to be
+//!   used as a suggestion engine
+//!
+//! Both return 0 to let the completion through or to suggest nothing. Otherwise they
+//! return `ptr << 32 | len` of their output in `memory`: the reason the completion was
+//! rejected, or a JSON array of suggestions.
+
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::suggestions::{SuggestionEngine, SuggestionRequest};
+use crate::validators::{CompletionValidator, PendingCompletion};
+
+/// A WebAssembly module run as a validator or suggestion engine
+///
+/// ```
+/// # use scatterbrain::wasm::WasmPlugin;
+/// let plugin: WasmPlugin = toml::from_str(r#"wasm = "plugins/gate.wasm""#).unwrap();
+/// assert_eq!(plugin.fuel, 100_000_000);
+/// assert_eq!(plugin.memory_mib, 64);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WasmPlugin {
+    /// Path to the module, compiled (`.wasm`) or as text (`.wat`), relative to the
+    /// server's working directory. It is compiled again after it changes.
+    pub wasm: PathBuf,
+    /// Units of work a call may take before it is stopped, roughly one per instruction
+    #[serde(default = "default_fuel")]
+    pub fuel: u64,
+    /// Most memory a call may use, in MiB
+    #[serde(default = "default_memory_mib")]
+    pub memory_mib: u32,
+}
+
+fn default_fuel() -> u64 {
+    100_000_000
+}
+
+fn default_memory_mib() -> u32 {
+    64
+}
+
+impl CompletionValidator for WasmPlugin {
+    fn validate(&self, completion: &PendingCompletion) -> Result<(), String> {
+        let input = serde_json::to_vec(completion).map_err(|e| e.to_string())?;
+        match runtime::call(self, "validate", &input)? {
+            None => Ok(()),
+            Some(reason) => Err(String::from_utf8_lossy(&reason).into_owned()),
+        }
+    }
+}
+
+impl SuggestionEngine for WasmPlugin {
+    fn suggest(&self, request: &SuggestionRequest) -> Result<Vec<String>, String> {
+        let input = serde_json::to_vec(request).map_err(|e| e.to_string())?;
+        match runtime::call(self, "suggest", &input)? {
+            None => Ok(Vec::new()),
+            Some(output) => serde_json::from_slice(&output).map_err(|e| {
+                format!(
+                    "{} returned suggestions that aren't a JSON array of strings: {e}",
+                    self.wasm.display()
+                )
+            }),
+        }
+    }
+}
+
+#[cfg(feature = "wasm-plugins")]
+mod runtime {
+    use std::collections::HashMap;
+    use std::path::{Path, PathBuf};
+    use std::sync::{Mutex, OnceLock};
+    use std::time::SystemTime;
+
+    use wasmtime::{Config, Engine, Instance, Module, Store, StoreLimitsBuilder};
+
+    use super::WasmPlugin;
+
+    /// The engine shared by every plugin, metering the fuel calls use
+    fn engine() -> Result<&'static Engine, String> {
+        static ENGINE: OnceLock<Result<Engine, String>> = OnceLock::new();
+        ENGINE
+            .get_or_init(|| {
+                let mut config = Config::new();
+                config.consume_fuel(true);
+                Engine::new(&config).map_err(|e| format!("failed to start wasmtime: {e:#}"))
+            })
+            .as_ref()
+            .map_err(Clone::clone)
+    }
+
+    /// The compiled module at `path`, compiled again when the file has changed since
+    fn module(path: &Path) -> Result<Module, String> {
+        static MODULES: OnceLock<Mutex<HashMap<PathBuf, (SystemTime, Module)>>> = OnceLock::new();
+        let modules = MODULES.get_or_init(Default::default);
+        let modified = std::fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .map_err(|e| format!("failed to read {}: {e}", path.display()))?;
+        {
+            let modules = modules.lock().map_err(|_| "plugin cache poisoned")?;
+            if let Some((compiled_at, module)) = modules.get(path) {
+                if *compiled_at == modified {
+                    return Ok(module.clone());
+                }
+            }
+        }
+
+        // Compiled outside the lock, so a slow compile doesn't hold up other plugins
+        let module = Module::from_file(engine()?, path)
+            .map_err(|e| format!("failed to compile {}: {e:#}", path.display()))?;
+        modules
+            .lock()
+            .map_err(|_| "plugin cache poisoned")?
+            .insert(path.to_path_buf(), (modified, module.clone()));
+        Ok(module)
+    }
+
+    /// Calls `export` with `input`, returning its output, or `None` if it returned 0
+    pub(super) fn call(
+        plugin: &WasmPlugin,
+        export: &str,
+        input: &[u8],
+    ) -> Result<Option<Vec<u8>>, String> {
+        let name = plugin.wasm.display();
+        let module = module(&plugin.wasm)?;
+        let limits = StoreLimitsBuilder::new()
+            .memory_size(plugin.memory_mib as usize * 1024 * 1024)
+            .instances(1)
+            .build();
+        let mut store = Store::new(engine()?, limits);
+        store.limiter(|limits| limits);
+        store.set_fuel(plugin.fuel).map_err(|e| e.to_string())?;
+
+        let instance = Instance::new(&mut store, &module, &[])
+            .map_err(|e| format!("failed to instantiate {name}: {e:#}"))?;
+        let memory = instance
+            .get_memory(&mut store, "memory")
+            .ok_or_else(|| format!("{name} exports no memory"))?;
+        let alloc = instance
+            .get_typed_func::<i32, i32>(&mut store, "alloc")
+            .map_err(|e| format!("{name} has no usable `alloc`: {e:#}"))?;
+        let entry = instance
+            .get_typed_func::<(i32, i32), i64>(&mut store, export)
+            .map_err(|e| format!("{name} has no usable `{export}`: {e:#}"))?;
+
+        let len = i32::try_from(input.len()).map_err(|_| "input too large".to_string())?;
+        let trapped = |store: &Store<_>, e: wasmtime::Error| {
+            if store.get_fuel().is_ok_and(|fuel| fuel == 0) {
+                format!("{name} ran out of fuel after {} units", plugin.fuel)
+            } else {
+                format!("{name} trapped: {e:#}")
+            }
+        };
+        let ptr = alloc
+            .call(&mut store, len)
+            .map_err(|e| trapped(&store, e))?;
+        memory
+            .write(&mut store, ptr as u32 as usize, input)
+            .map_err(|e| format!("{name} allocated memory out of bounds: {e}"))?;
+        let packed = entry
+            .call(&mut store, (ptr, len))
+            .map_err(|e| trapped(&store, e))? as u64;
+        if packed == 0 {
+            return Ok(None);
+        }
+
+        let (out_ptr, out_len) = ((packed >> 32) as usize, (packed & 0xffff_ffff) as usize);
+        let mut output = vec![0; out_len];
+        memory
+            .read(&store, out_ptr, &mut output)
+            .map_err(|e| format!("{name} returned output out of bounds: {e}"))?;
+        Ok(Some(output))
+    }
+}
+
+#[cfg(not(feature = "wasm-plugins"))]
+mod runtime {
+    use super::WasmPlugin;
+
+    pub(super) fn call(
+        plugin: &WasmPlugin,
+        _export: &str,
+        _input: &[u8],
+    ) -> Result<Option<Vec<u8>>, String> {
+        Err(format!(
+            "can't run {}: scatterbrain was built without the `wasm-plugins` feature",
+            plugin.wasm.display()
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn completion(summary: &str) -> PendingCompletion {
+        PendingCompletion {
+            plan_id: 7,
+            index: vec![0, 2],
+            description: "Fix the parser".to_string(),
+            summary: Some(summary.to_string()),
+            cascaded_from: None,
+        }
+    }
+
+    #[cfg(not(feature = "wasm-plugins"))]
+    #[test]
+    fn test_wasm_plugin_without_feature() {
+        let plugin = WasmPlugin {
+            wasm: PathBuf::from("gate.wasm"),
+            fuel: default_fuel(),
+            memory_mib: default_memory_mib(),
+        };
+        let reason = plugin.validate(&completion("Done")).unwrap_err();
+        assert!(reason.contains("`wasm-plugins` feature"), "{reason}");
+    }
+
+    /// Writes `wat` to a module file of its own, so tests don't share compiled modules
+    #[cfg(feature = "wasm-plugins")]
+    fn plugin(wat: &str, fuel: u64) -> WasmPlugin {
+        let path =
+            std::env::temp_dir().join(format!("scatterbrain-plugin-{}.wat", rand::random::<u64>()));
+        std::fs::write(&path, wat).unwrap();
+        WasmPlugin {
+            wasm: path,
+            fuel,
+            memory_mib: 1,
+        }
+    }
+
+    #[cfg(feature = "wasm-plugins")]
+    #[test]
+    fn test_wasm_validator() {
+        // Rejects completions whose summary JSON is shorter than 80 bytes
+        let gate = plugin(
+            r#"(module
+                (memory (export "memory") 1)
+                (data (i32.const 0) "summary too short")
+                (func (export "alloc") (param i32) (result i32) (i32.const 1024))
+                (func (export "validate") (param i32 i32) (result i64)
+                    (if (result i64) (i32.lt_u (local.get 1) (i32.const 80))
+                        (then (i64.const 17))
+                        (else (i64.const 0)))))"#,
+            default_fuel(),
+        );
+        assert_eq!(
+            gate.validate(&completion("Tested")),
+            Err("summary too short".to_string())
+        );
+        let long = "Tested with the full suite and the fuzzer".repeat(2);
+        assert_eq!(gate.validate(&completion(&long)), Ok(()));
+
+        // Modules get no imports to reach out of the sandbox with
+        let escapes = plugin(
+            r#"(module
+                (import "wasi_snapshot_preview1" "fd_write"
+                    (func (param i32 i32 i32 i32) (result i32)))
+                (memory (export "memory") 1)
+                (func (export "alloc") (param i32) (result i32) (i32.const 0))
+                (func (export "validate") (param i32 i32) (result i64) (i64.const 0)))"#,
+            default_fuel(),
+        );
+        let reason = escapes.validate(&completion("Done")).unwrap_err();
+        assert!(reason.contains("failed to instantiate"), "{reason}");
+    }
+
+    #[cfg(feature = "wasm-plugins")]
+    #[test]
+    fn test_wasm_plugin_limits() {
+        // A call that loops is stopped once its fuel runs out, and fuel isn't carried
+        // over between calls
+        let spins = plugin(
+            r#"(module
+                (memory (export "memory") 1)
+                (func (export "alloc") (param i32) (result i32) (i32.const 0))
+                (func (export "validate") (param i32 i32) (result i64)
+                    (loop (br 0))
+                    (i64.const 0)))"#,
+            10_000,
+        );
+        for _ in 0..2 {
+            let reason = spins.validate(&completion("Done")).unwrap_err();
+            assert!(
+                reason.contains("ran out of fuel after 10000 units"),
+                "{reason}"
+            );
+        }
+
+        // Memory can't grow past `memory_mib`, so the module sees memory.grow fail...
+        let grows = plugin(
+            r#"(module
+                (memory (export "memory") 1)
+                (data (i32.const 0) "grow refused")
+                (func (export "alloc") (param i32) (result i32) (i32.const 1024))
+                (func (export "validate") (param i32 i32) (result i64)
+                    (if (result i64) (i32.eq (memory.grow (i32.const 32)) (i32.const -1))
+                        (then (i64.const 12))
+                        (else (i64.const 0)))))"#,
+            default_fuel(),
+        );
+        assert_eq!(
+            grows.validate(&completion("Done")),
+            Err("grow refused".to_string())
+        );
+        // ... and a module asking for more up front can't be instantiated
+        let large = plugin(
+            r#"(module
+                (memory (export "memory") 32)
+                (func (export "alloc") (param i32) (result i32) (i32.const 0))
+                (func (export "validate") (param i32 i32) (result i64) (i64.const 0)))"#,
+            default_fuel(),
+        );
+        let reason = large.validate(&completion("Done")).unwrap_err();
+        assert!(reason.contains("failed to instantiate"), "{reason}");
+    }
+
+    #[cfg(feature = "wasm-plugins")]
+    #[test]
+    fn test_wasm_plugin_traps() {
+        let traps = plugin(
+            r#"(module
+                (memory (export "memory") 1)
+                (func (export "alloc") (param i32) (result i32) (i32.const 0))
+                (func (export "validate") (param i32 i32) (result i64) (unreachable)))"#,
+            default_fuel(),
+        );
+        let reason = traps.validate(&completion("Done")).unwrap_err();
+        assert!(reason.contains("trapped"), "{reason}");
+
+        // Input and output outside the module's memory are refused rather than read
+        let bad_alloc = plugin(
+            r#"(module
+                (memory (export "memory") 1)
+                (func (export "alloc") (param i32) (result i32) (i32.const 65530))
+                (func (export "validate") (param i32 i32) (result i64) (i64.const 0)))"#,
+            default_fuel(),
+        );
+        let reason = bad_alloc.validate(&completion("Done")).unwrap_err();
+        assert!(
+            reason.contains("allocated memory out of bounds"),
+            "{reason}"
+        );
+        let bad_output = plugin(
+            r#"(module
+                (memory (export "memory") 1)
+                (func (export "alloc") (param i32) (result i32) (i32.const 0))
+                (func (export "validate") (param i32 i32) (result i64)
+                    (i64.const 0x0000ffff00000010)))"#,
+            default_fuel(),
+        );
+        let reason = bad_output.validate(&completion("Done")).unwrap_err();
+        assert!(reason.contains("returned output out of bounds"), "{reason}");
+    }
+
+    #[cfg(feature = "wasm-plugins")]
+    #[test]
+    fn test_wasm_suggestion_engine() {
+        let engine = plugin(
+            r#"(module
+                (memory (export "memory") 1)
+                (data (i32.const 0) "[\"Write the tests first\"]")
+                (func (export "alloc") (param i32) (result i32) (i32.const 1024))
+                (func (export "suggest") (param i32 i32) (result i64)
+                    (i64.const 25)))"#,
+            default_fuel(),
+        );
+        let context = crate::models::DistilledContext::builder().build();
+        let request = SuggestionRequest {
+            plan_id: 1,
+            context: &context,
+        };
+        assert_eq!(
+            engine.suggest(&request),
+            Ok(vec!["Write the tests first".to_string()])
+        );
+        // A module can be a validator without being a suggestion engine
+        let reason = plugin(
+            r#"(module
+                (memory (export "memory") 1)
+                (func (export "alloc") (param i32) (result i32) (i32.const 0)))"#,
+            default_fuel(),
+        )
+        .suggest(&request)
+        .unwrap_err();
+        assert!(reason.contains("no usable `suggest`"), "{reason}");
+    }
+}